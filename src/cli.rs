@@ -1,26 +1,68 @@
 // One module per subcommand
 mod collections;
+mod export;
 mod generate;
 mod import;
+mod raw;
 mod request;
 mod show;
 
-use crate::{
-    cli::{
-        collections::CollectionsCommand, generate::GenerateCommand,
-        import::ImportCommand, request::RequestCommand, show::ShowCommand,
-    },
-    GlobalArgs,
+use crate::cli::{
+    collections::CollectionsCommand, export::ExportCommand,
+    generate::GenerateCommand, import::ImportCommand, raw::RawCommand,
+    request::RequestCommand, show::ShowCommand,
 };
+use anyhow::{anyhow, Context};
 use async_trait::async_trait;
-use std::process::ExitCode;
+use clap::Parser;
+use std::{net::IpAddr, path::PathBuf, process::ExitCode};
+
+/// Arguments that are available to all subcommands and the TUI
+#[derive(Debug, Parser)]
+pub struct GlobalArgs {
+    /// Collection file, which defines profiles, recipes, etc. If a directory
+    /// is given, all YAML files in it are merged into one collection. Use `-`
+    /// to read the collection from stdin, or an `http://`/`https://` URL to
+    /// fetch it. If omitted, check the current directory for the following
+    /// files (in this order): slumber.yml, slumber.yaml, .slumber.yml,
+    /// .slumber.yaml
+    #[clap(long, short)]
+    pub file: Option<PathBuf>,
+    /// Disable colors and other styling in CLI output. Colors are also
+    /// disabled if the `NO_COLOR` environment variable is set
+    #[clap(long)]
+    pub no_color: bool,
+    /// Replay responses from history instead of sending requests. Same as the
+    /// `offline` config field
+    #[clap(long)]
+    pub offline: bool,
+    /// Connect to a fixed IP address for a hostname, instead of looking it up
+    /// in DNS, e.g. `--resolve api.example.com=10.0.0.5`. The hostname is
+    /// still used for the `Host` header and TLS SNI. Can be given multiple
+    /// times. Added to the `resolve` config field
+    #[clap(long, value_name = "HOSTNAME=ADDRESS", value_parser = parse_resolve)]
+    pub resolve: Vec<(String, IpAddr)>,
+}
+
+/// Parse a `--resolve` override
+fn parse_resolve(value: &str) -> anyhow::Result<(String, IpAddr)> {
+    let (hostname, address) = value
+        .split_once('=')
+        .ok_or_else(|| anyhow!("Expected `HOSTNAME=ADDRESS`"))?;
+    let address = address
+        .parse()
+        .with_context(|| format!("Invalid IP address `{address}`"))?;
+    Ok((hostname.to_owned(), address))
+}
 
 /// A CLI subcommand
 #[derive(Clone, Debug, clap::Subcommand)]
 pub enum CliCommand {
     Request(RequestCommand),
+    Raw(RawCommand),
     Generate(GenerateCommand),
     Import(ImportCommand),
+    Export(ExportCommand),
     Collections(CollectionsCommand),
     Show(ShowCommand),
 }
@@ -40,7 +82,9 @@ impl CliCommand {
         match self {
             Self::Generate(command) => command.execute(global).await,
             Self::Request(command) => command.execute(global).await,
+            Self::Raw(command) => command.execute(global).await,
             Self::Import(command) => command.execute(global).await,
+            Self::Export(command) => command.execute(global).await,
             Self::Collections(command) => command.execute(global).await,
             Self::Show(command) => command.execute(global).await,
         }
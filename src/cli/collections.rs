@@ -1,7 +1,12 @@
-use crate::{cli::Subcommand, db::Database, GlobalArgs};
+use crate::{
+    cli::{GlobalArgs, Subcommand},
+    collection::{format_collection, lossy_features, CollectionFile},
+    db::Database,
+};
+use anyhow::{bail, Context};
 use async_trait::async_trait;
 use clap::Parser;
-use std::{path::PathBuf, process::ExitCode};
+use std::{fs, path::PathBuf, process::ExitCode};
 
 /// View and modify request collection state and history
 #[derive(Clone, Debug, Parser)]
@@ -15,6 +20,27 @@ enum CollectionsSubcommand {
     /// List all known request collections
     #[command(visible_alias = "ls")]
     List,
+    /// Summarize the current collection: its recipes, profiles, chains, and
+    /// which chains and profile fields each one uses
+    Info {
+        /// Print the summary as JSON
+        #[clap(long)]
+        json: bool,
+    },
+    /// Reformat the current collection's file(s) in place
+    ///
+    /// Output is deterministic, so diffs only show real changes. Comments
+    /// are removed and YAML anchors are expanded, so files that have either
+    /// are left alone unless `--force` is given.
+    Fmt {
+        /// Don't write anything. Print the files that aren't formatted, and
+        /// exit with an error if there are any
+        #[clap(long)]
+        check: bool,
+        /// Format even if it removes comments or expands anchors
+        #[clap(long, conflicts_with = "check")]
+        force: bool,
+    },
     /// Move all data from one collection to another.
     ///
     /// The data from the source collection will be merged into the target
@@ -29,16 +55,73 @@ enum CollectionsSubcommand {
 
 #[async_trait]
 impl Subcommand for CollectionsCommand {
-    async fn execute(self, _global: GlobalArgs) -> anyhow::Result<ExitCode> {
-        let database = Database::load()?;
+    async fn execute(self, global: GlobalArgs) -> anyhow::Result<ExitCode> {
         match self.subcommand {
             CollectionsSubcommand::List => {
-                for path in database.collections()? {
+                for path in Database::load()?.collections()? {
                     println!("{}", path.display());
                 }
             }
+            CollectionsSubcommand::Info { json } => {
+                let collection_path = CollectionFile::try_path(global.file)?;
+                let collection_file =
+                    CollectionFile::load(collection_path).await?;
+                let summary = collection_file.collection.summary();
+                if json {
+                    // Panic is intentional, indicates a wonky bug
+                    let json = serde_json::to_string_pretty(&summary)
+                        .expect("Error serializing");
+                    println!("{json}");
+                } else {
+                    print!("{summary}");
+                }
+            }
+            CollectionsSubcommand::Fmt { check, force } => {
+                let collection_path = CollectionFile::try_path(global.file)?;
+                let collection_file =
+                    CollectionFile::load(collection_path).await?;
+                // Format everything before writing anything, so one bad file
+                // doesn't leave the collection half-formatted
+                let mut changed = Vec::new();
+                for path in collection_file.files()? {
+                    let text = fs::read(&path)
+                        .with_context(|| format!("Error reading {path:?}"))?;
+                    let formatted =
+                        format_collection(&text).with_context(|| {
+                            format!("Error formatting {path:?}")
+                        })?;
+                    if formatted.as_bytes() == text {
+                        continue;
+                    }
+                    let lost = lossy_features(&String::from_utf8_lossy(&text));
+                    if !check && !force && !lost.is_empty() {
+                        bail!(
+                            "{path:?} has {}, which formatting would lose. \
+                            Pass `--force` to format anyway",
+                            lost.join(" and ")
+                        );
+                    }
+                    changed.push((path, formatted));
+                }
+
+                if check {
+                    for (path, _) in &changed {
+                        println!("{}", path.display());
+                    }
+                    if !changed.is_empty() {
+                        return Ok(ExitCode::FAILURE);
+                    }
+                } else {
+                    for (path, formatted) in changed {
+                        fs::write(&path, formatted).with_context(|| {
+                            format!("Error writing {path:?}")
+                        })?;
+                        println!("Formatted {}", path.display());
+                    }
+                }
+            }
             CollectionsSubcommand::Migrate { from, to } => {
-                database.merge_collections(&from, &to)?;
+                Database::load()?.merge_collections(&from, &to)?;
                 println!("Migrated {} into {}", from.display(), to.display());
             }
         }
@@ -0,0 +1,76 @@
+use crate::{
+    cli::{GlobalArgs, Subcommand},
+    collection::{CollectionFile, ProfileId, RecipeId},
+    config::Config,
+};
+use anyhow::Context;
+use async_trait::async_trait;
+use clap::Parser;
+use std::{
+    fs::File,
+    io::{self, Write},
+    path::PathBuf,
+    process::ExitCode,
+};
+
+/// Export part of a collection into a standalone collection file
+#[derive(Clone, Debug, Parser)]
+pub struct ExportCommand {
+    #[command(subcommand)]
+    subcommand: ExportSubcommand,
+}
+
+#[derive(Clone, Debug, clap::Subcommand)]
+enum ExportSubcommand {
+    /// Export a single recipe, along with the chains, recipes, and profile
+    /// fields it depends on
+    Recipe {
+        /// ID of the recipe to export
+        recipe_id: RecipeId,
+        /// Only include this profile. By default, all profiles are included,
+        /// trimmed down to the fields the recipe uses
+        #[clap(long = "profile", short)]
+        profile: Option<ProfileId>,
+        /// Destination for the new collection file [default: stdout]
+        #[clap(long, short)]
+        out: Option<PathBuf>,
+    },
+}
+
+#[async_trait]
+impl Subcommand for ExportCommand {
+    async fn execute(self, global: GlobalArgs) -> anyhow::Result<ExitCode> {
+        let collection_path = CollectionFile::try_path(global.file)?;
+        let mut collection =
+            CollectionFile::load(collection_path).await?.collection;
+        // Include global chains, so the export is self-contained
+        collection.merge_global_chains(&Config::load()?.chains);
+
+        match self.subcommand {
+            ExportSubcommand::Recipe {
+                recipe_id,
+                profile,
+                out,
+            } => {
+                let exported =
+                    collection.export_recipe(&recipe_id, profile.as_ref())?;
+
+                let mut writer: Box<dyn Write> = match out {
+                    Some(out) => Box::new(
+                        File::options()
+                            .create(true)
+                            .truncate(true)
+                            .write(true)
+                            .open(&out)
+                            .context(format!(
+                                "Error opening collection output file {out:?}"
+                            ))?,
+                    ),
+                    None => Box::new(io::stdout()),
+                };
+                serde_yaml::to_writer(&mut writer, &exported)?;
+            }
+        }
+        Ok(ExitCode::SUCCESS)
+    }
+}
@@ -1,7 +1,10 @@
 use crate::{
-    cli::{request::BuildRequestCommand, Subcommand},
+    cli::{
+        request::{BuildRequestCommand, BuiltRequest},
+        GlobalArgs, Subcommand,
+    },
+    http::RequestFormat,
     template::TemplateError,
-    GlobalArgs,
 };
 use async_trait::async_trait;
 use clap::{Parser, ValueEnum};
@@ -24,12 +27,25 @@ pub struct GenerateCommand {
 #[derive(Clone, Debug, ValueEnum)]
 pub enum GenerateFormat {
     Curl,
+    /// JavaScript `fetch()` call
+    Fetch,
+    Httpie,
+}
+
+impl From<GenerateFormat> for RequestFormat {
+    fn from(format: GenerateFormat) -> Self {
+        match format {
+            GenerateFormat::Curl => Self::Curl,
+            GenerateFormat::Fetch => Self::Fetch,
+            GenerateFormat::Httpie => Self::Httpie,
+        }
+    }
 }
 
 #[async_trait]
 impl Subcommand for GenerateCommand {
     async fn execute(self, global: GlobalArgs) -> anyhow::Result<ExitCode> {
-        let (_, request) = self
+        let BuiltRequest { request, .. } = self
             .build_request
             // User has to explicitly opt into executing triggered requests
             .build_request(global, self.execute_triggers)
@@ -46,7 +62,8 @@ impl Subcommand for GenerateCommand {
                     error
                 }
             })?;
-        println!("{}", request.to_curl()?);
+        let format: RequestFormat = self.format.into();
+        println!("{}", format.generate(&request)?);
         Ok(ExitCode::SUCCESS)
     }
 }
@@ -1,5 +1,8 @@
-use crate::{cli::Subcommand, collection::Collection, GlobalArgs};
-use anyhow::Context;
+use crate::{
+    cli::{GlobalArgs, Subcommand},
+    collection::Collection,
+};
+use anyhow::{bail, Context};
 use async_trait::async_trait;
 use clap::{Parser, ValueEnum};
 use std::{
@@ -18,11 +21,17 @@ pub struct ImportCommand {
     input_file: PathBuf,
     /// Destination for the new slumber collection file [default: stdout]
     output_file: Option<PathBuf>,
+    /// Put all imported recipes in a folder with this name. Only supported
+    /// for curl
+    #[clap(long)]
+    folder: Option<String>,
 }
 
 #[derive(Copy, Clone, Debug, ValueEnum)]
 enum Format {
     Insomnia,
+    /// A file of curl commands, e.g. a shell script
+    Curl,
 }
 
 #[async_trait]
@@ -30,7 +39,21 @@ impl Subcommand for ImportCommand {
     async fn execute(self, _global: GlobalArgs) -> anyhow::Result<ExitCode> {
         // Load the input
         let collection = match self.format {
-            Format::Insomnia => Collection::from_insomnia(&self.input_file)?,
+            Format::Insomnia => {
+                if self.folder.is_some() {
+                    bail!("`--folder` is only supported for curl imports");
+                }
+                Collection::from_insomnia(&self.input_file)?
+            }
+            Format::Curl => {
+                let (collection, errors) =
+                    Collection::from_curl(&self.input_file, self.folder)?;
+                // Skipped commands shouldn't stop the rest of the import
+                for error in errors {
+                    eprintln!("{error:#}");
+                }
+                collection
+            }
         };
 
         // Write the output
@@ -0,0 +1,65 @@
+use crate::{
+    cli::{GlobalArgs, Subcommand},
+    config::Config,
+    http::RawRequest,
+};
+use anyhow::{bail, Context};
+use async_trait::async_trait;
+use clap::Parser;
+use std::{
+    fs,
+    io::{self, Read, Write},
+    path::PathBuf,
+    process::ExitCode,
+};
+
+/// Send a hand-written HTTP request, as close to verbatim as possible
+///
+/// The request is given as HTTP message text: a request line, headers, a
+/// blank line, then the body. No collection is loaded, and the request isn't
+/// stored in history.
+#[derive(Clone, Debug, Parser)]
+pub struct RawCommand {
+    /// File containing the request text. Use `-` or omit to read from stdin
+    file: Option<PathBuf>,
+    /// If the request target is a path, connect with HTTPS instead of HTTP.
+    /// The host is taken from the `Host` header
+    #[clap(long)]
+    https: bool,
+}
+
+#[async_trait]
+impl Subcommand for RawCommand {
+    async fn execute(self, global: GlobalArgs) -> anyhow::Result<ExitCode> {
+        let mut config = Config::load()?;
+        if config.offline || global.offline {
+            bail!(
+                "Raw requests aren't stored in history, so they can't be \
+                replayed offline"
+            );
+        }
+        config.resolve.extend(global.resolve);
+
+        let text = match self.file.filter(|path| path.as_os_str() != "-") {
+            Some(path) => fs::read(&path).with_context(|| {
+                format!("Error reading request from {path:?}")
+            })?,
+            None => {
+                let mut text = Vec::new();
+                io::stdin()
+                    .read_to_end(&mut text)
+                    .context("Error reading request from stdin")?;
+                text
+            }
+        };
+        let request = RawRequest::parse(&text, self.https)?;
+        let response = request.send(&config).await?;
+
+        // Response is printed as an HTTP message, mirroring the input
+        let mut stdout = io::stdout();
+        write!(stdout, "{response}")
+            .and_then(|()| stdout.write_all(&response.body))
+            .context("Error writing to stdout")?;
+        Ok(ExitCode::SUCCESS)
+    }
+}
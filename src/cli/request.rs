@@ -1,24 +1,46 @@
 use crate::{
-    cli::Subcommand,
-    collection::{CollectionFile, ProfileId, RecipeId},
+    cli::{GlobalArgs, Subcommand},
+    collection::{
+        Assertions, ChainSource, Collection, CollectionFile, ProfileId, Recipe,
+        RecipeId, RecipeNode, ResponseSchema, STDIN_PATH,
+    },
     config::Config,
     db::Database,
-    http::{HttpEngine, RecipeOptions, Request, RequestBuilder},
-    template::{Prompt, Prompter, TemplateContext, TemplateError},
-    util::{MaybeStr, ResultExt},
-    GlobalArgs,
+    http::{
+        AssertionFailure, BodyOverride, HttpEngine, RecipeOptions, Request,
+        RequestBuilder, RequestRecord,
+    },
+    template::{
+        Prompt, Prompter, RandomSource, Template, TemplateContext,
+        TemplateError, ROW_PREFIX,
+    },
+    util::{
+        csv::{parse_csv, CsvRow},
+        MaybeStr, ResultExt,
+    },
 };
-use anyhow::{anyhow, Context};
+use anyhow::{anyhow, bail, Context};
 use async_trait::async_trait;
 use clap::Parser;
-use dialoguer::{console::Style, Input, Password};
-use indexmap::IndexMap;
+use dialoguer::{console::Style, Confirm, Input, Password, Select};
+use futures::{
+    future::{self, BoxFuture, Shared},
+    stream, FutureExt, StreamExt,
+};
+use indexmap::{IndexMap, IndexSet};
 use itertools::Itertools;
-use reqwest::header::HeaderMap;
+use reqwest::{
+    header::{self, HeaderMap, HeaderName, HeaderValue},
+    Method, StatusCode,
+};
 use std::{
     error::Error,
     fmt::{self, Display, Formatter},
-    io::{self, Write},
+    fs,
+    io::{self, Read, Write},
+    iter,
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
     process::ExitCode,
     str::FromStr,
 };
@@ -27,6 +49,13 @@ use tracing::warn;
 /// Exit code to return when `exit_status` flag is set and the HTTP response has
 /// an error status code
 const HTTP_ERROR_EXIT_CODE: u8 = 2;
+/// Exit code to return when the response body doesn't match the recipe's
+/// schema
+const SCHEMA_ERROR_EXIT_CODE: u8 = 3;
+/// Exit code to return when the response fails one of the recipe's assertions
+const ASSERTION_ERROR_EXIT_CODE: u8 = 4;
+/// Shown in place of sensitive header values in `--verbose` output
+const SENSITIVE_HEADER_PLACEHOLDER: &str = "<sensitive>";
 
 /// Execute a single request, and print its response
 #[derive(Clone, Debug, Parser)]
@@ -47,6 +76,21 @@ pub struct RequestCommand {
     #[clap(long)]
     no_body: bool,
 
+    /// Print the full request (method, URL, headers, and body) to stderr
+    /// before sending it, then the response status and headers. Values of
+    /// sensitive headers, e.g. `Authorization`, are masked
+    #[clap(long, short)]
+    verbose: bool,
+
+    /// With `--verbose`, show sensitive header values instead of masking them
+    #[clap(long, requires = "verbose")]
+    reveal: bool,
+
+    /// Only load the HTTP response status and headers, and print them to
+    /// stdout. The response body is discarded without being downloaded.
+    #[clap(long, visible_alias = "head")]
+    headers_only: bool,
+
     /// Set process exit code based on HTTP response status. If the status is
     /// <400, exit code is 0. If it's >=400, exit code is 2.
     #[clap(long)]
@@ -56,6 +100,41 @@ pub struct RequestCommand {
     /// sub-requests will also not be executed.
     #[clap(long)]
     dry_run: bool,
+
+    /// Print a single line per request with its status and duration, instead
+    /// of the response. Also accepts a folder ID, to send every recipe in the
+    /// folder in order. Exit code is 2 if any request fails.
+    #[clap(
+        long,
+        visible_alias = "quiet",
+        conflicts_with_all = [
+            "status", "headers", "no_body", "headers_only", "dry_run",
+            "verbose",
+        ],
+    )]
+    summary: bool,
+
+    /// With `--summary`, send up to this many requests at once. Recipes that
+    /// depend on an earlier recipe in the run (via a chain) still wait for
+    /// it. Output is always in collection order.
+    #[clap(long, default_value = "1", requires = "summary")]
+    concurrency: NonZeroUsize,
+
+    /// With `--summary`, send the recipe(s) once per row of this CSV file.
+    /// The first line names the columns, and each column is available to
+    /// templates as `{{row.<column>}}`
+    #[clap(long, requires = "summary")]
+    data: Option<PathBuf>,
+
+    /// With `--summary`, stop at the first failed request instead of
+    /// continuing. Requests that haven't finished yet are skipped.
+    #[clap(long, requires = "summary")]
+    fail_fast: bool,
+
+    /// With `--summary`, don't ask for confirmation before a batch that
+    /// includes requests that can modify data (e.g. `POST` or `DELETE`)
+    #[clap(long, short = 'y', requires = "summary")]
+    yes: bool,
 }
 
 /// A helper for any subcommand that needs to build requests. This handles
@@ -65,9 +144,10 @@ pub struct BuildRequestCommand {
     /// ID of the recipe to render into a request
     recipe_id: RecipeId,
 
-    /// ID of the profile to pull template values from
+    /// ID or name of the profile to pull template values from. This is a
+    /// template, e.g. `{{env.ENVIRONMENT}}`, but it can't use profile fields
     #[clap(long = "profile", short)]
-    profile: Option<ProfileId>,
+    profile: Option<String>,
 
     /// List of key=value template field overrides
     #[clap(
@@ -76,12 +156,38 @@ pub struct BuildRequestCommand {
         value_parser = parse_key_val::<String, String>,
     )]
     overrides: Vec<(String, String)>,
+
+    /// Read the request body from stdin, replacing the recipe's body. The
+    /// body is sent as-is, without templating. Headers (e.g. Content-Type)
+    /// still come from the recipe.
+    #[clap(long)]
+    body_stdin: bool,
+
+    /// Render the body read from stdin as a template, instead of sending it
+    /// as-is. Requires the body to be UTF-8.
+    #[clap(long, requires = "body_stdin")]
+    template_body_stdin: bool,
+
+    /// Seed for random template functions (e.g. `random_int`), so the same
+    /// seed gives the same values on every run
+    #[clap(long)]
+    seed: Option<u64>,
 }
 
 #[async_trait]
 impl Subcommand for RequestCommand {
     async fn execute(self, global: GlobalArgs) -> anyhow::Result<ExitCode> {
-        let (http_engine, request) = self
+        if self.summary {
+            return self.execute_summary(global).await;
+        }
+
+        let BuiltRequest {
+            http_engine,
+            request,
+            schema,
+            assertions,
+            template_context,
+        } = self
             .build_request
             // Don't execute sub-requests in a dry run
             .build_request(global, !self.dry_run)
@@ -102,19 +208,45 @@ impl Subcommand for RequestCommand {
         if let Some(http_engine) = http_engine {
             // Everything other than the body prints to stderr, to make it easy
             // to pipe the body to a file
-            if self.headers {
+            if self.verbose {
+                eprint!("{}", VerboseRequest(&request, self.reveal));
+            } else if self.headers {
                 eprintln!("{}", HeaderDisplay(&request.headers));
             }
 
             // Run the request
-            let record = http_engine.send(request.into()).await?;
+            let record = if self.headers_only {
+                http_engine.send_headers_only(request.into()).await?
+            } else {
+                http_engine.send(request.into()).await?
+            };
             let status = record.response.status;
+            if self.verbose {
+                eprint!("{}", VerboseResponse(&record, self.reveal));
+            }
+            if record.response.replayed {
+                eprintln!("Response replayed from history (offline mode)");
+            }
+            if record.response.cached {
+                eprintln!("Not modified; body loaded from history");
+            }
+
+            if self.headers_only {
+                // Status and headers are the actual output here, so they go
+                // to stdout
+                println!("{}", status.as_u16());
+                print!("{}", HeaderDisplay(&record.response.headers));
+                if let Some(code) = check_assertions(&assertions, &record) {
+                    return Ok(code);
+                }
+                return Ok(exit_code(self.exit_status, status));
+            }
 
             // Print stuff!
             if self.status {
                 eprintln!("{}", status.as_u16());
             }
-            if self.headers {
+            if self.headers && !self.verbose {
                 eprintln!("{}", HeaderDisplay(&record.response.headers));
             }
             if !self.no_body {
@@ -130,11 +262,31 @@ impl Subcommand for RequestCommand {
                 }
             }
 
-            if self.exit_status && status.as_u16() >= 400 {
-                Ok(ExitCode::from(HTTP_ERROR_EXIT_CODE))
-            } else {
-                Ok(ExitCode::SUCCESS)
+            // Validate the body against the recipe's schema, if it has one.
+            // Assertions are checked either way, so every failure is reported
+            let mut schema_exit_code = None;
+            if let Some(schema) = schema {
+                let violations = schema
+                    .validate(&template_context, &record.response)
+                    .await
+                    .context("Error validating response")?;
+                if !violations.is_empty() {
+                    eprintln!("Response does not match schema:");
+                    for violation in violations {
+                        eprintln!("- {violation}");
+                    }
+                    schema_exit_code =
+                        Some(ExitCode::from(SCHEMA_ERROR_EXIT_CODE));
+                }
             }
+            let assertion_exit_code = check_assertions(&assertions, &record);
+
+            Ok(schema_exit_code
+                .or(assertion_exit_code)
+                .unwrap_or_else(|| exit_code(self.exit_status, status)))
+        } else if self.verbose {
+            eprint!("{}", VerboseRequest(&request, self.reveal));
+            Ok(ExitCode::SUCCESS)
         } else {
             println!("{:#?}", request);
             Ok(ExitCode::SUCCESS)
@@ -142,6 +294,113 @@ impl Subcommand for RequestCommand {
     }
 }
 
+impl RequestCommand {
+    /// Send one request, or every request in a folder, printing one line for
+    /// each. Failures don't stop the run, but are reflected in the exit code.
+    async fn execute_summary(
+        self,
+        global: GlobalArgs,
+    ) -> anyhow::Result<ExitCode> {
+        let recipe_id = self.build_request.recipe_id.clone();
+        // Read the data file first, so a bad file fails before anything else
+        let rows = self
+            .data
+            .as_deref()
+            .map(|path| {
+                let text = fs::read_to_string(path)
+                    .with_context(|| format!("Error reading {path:?}"))?;
+                parse_csv(&text)
+                    .with_context(|| format!("Error parsing {path:?}"))
+            })
+            .transpose()?;
+        let context = self.build_request.load(global, true).await?;
+        let recipes =
+            summary_recipes(&context.template_context.collection, &recipe_id)?;
+        let http_engine = context
+            .http_engine
+            .clone()
+            .expect("HTTP engine is created when triggers are enabled");
+
+        let dependencies = batch_dependencies(
+            &context.template_context.collection,
+            context.template_context.selected_profile.as_ref(),
+            &recipes,
+        );
+
+        // Each data row gets its own context, with the row's values given as
+        // overrides. Rows are numbered from 1, not counting the header
+        let contexts: Vec<(Option<usize>, RequestContext)> = match rows {
+            Some(rows) => rows
+                .into_iter()
+                .enumerate()
+                .map(|(i, row)| (Some(i + 1), context.with_row(row)))
+                .collect(),
+            None => vec![(None, context)],
+        };
+
+        // A batch can fire a lot of writes at once, so give the user one
+        // chance to back out before anything is sent
+        if !self.yes && contexts.len() * recipes.len() > 1 {
+            let plan = MutatingRequests::new(&contexts, &recipes).await;
+            if plan.count > 0 {
+                plan.confirm()?;
+            }
+        }
+
+        // Each request waits for its dependencies, which are always earlier
+        // in the same row. The futures are shared so dependents can wait on
+        // the same request that's reported on, instead of sending it again
+        let http_engine = &http_engine;
+        let mut requests: Vec<Shared<BoxFuture<SummaryLine>>> = Vec::new();
+        for (row, context) in &contexts {
+            let row = *row;
+            let offset = requests.len();
+            for (recipe, dependencies) in recipes.iter().zip(&dependencies) {
+                let dependencies = dependencies
+                    .iter()
+                    .map(|i| requests[offset + i].clone())
+                    .collect_vec();
+                let request = async move {
+                    future::join_all(dependencies).await;
+                    SummaryLine::run(context, http_engine, recipe, row).await
+                };
+                requests.push(request.boxed().shared());
+            }
+        }
+        let total = requests.len();
+        // Buffering limits how many run at once, and yields results in order
+        // so output is the same regardless of timing
+        let mut lines = stream::iter(requests).buffered(self.concurrency.get());
+
+        let mut sent = 0;
+        let mut failed = 0;
+        while let Some(line) = lines.next().await {
+            sent += 1;
+            println!("{line}");
+            if line.is_failure() {
+                failed += 1;
+                if self.fail_fast {
+                    break;
+                }
+            }
+        }
+        print!(
+            "{sent} request{}, {failed} failed",
+            if sent == 1 { "" } else { "s" }
+        );
+        if sent < total {
+            print!(", {} skipped", total - sent);
+        }
+        println!();
+
+        Ok(if failed > 0 {
+            ExitCode::from(HTTP_ERROR_EXIT_CODE)
+        } else {
+            ExitCode::SUCCESS
+        })
+    }
+}
+
 impl BuildRequestCommand {
     /// Render the request specified by the user. This returns the HTTP engine
     /// too so it can be re-used if necessary (iff `trigger_dependencies` is
@@ -153,58 +412,515 @@ impl BuildRequestCommand {
         self,
         global: GlobalArgs,
         trigger_dependencies: bool,
-    ) -> anyhow::Result<(Option<HttpEngine>, Request)> {
+    ) -> anyhow::Result<BuiltRequest> {
+        let recipe_id = self.recipe_id.clone();
+        let context = self.load(global, trigger_dependencies).await?;
+
+        // Find recipe by ID
+        let recipe = context
+            .template_context
+            .collection
+            .recipes
+            .get_recipe(&recipe_id)
+            .ok_or_else(|| {
+                anyhow!(
+                    "No recipe with ID `{recipe_id}`; options are: {}",
+                    context
+                        .template_context
+                        .collection
+                        .recipes
+                        .recipe_ids()
+                        .join(", ")
+                )
+            })?
+            .clone();
+
+        let schema = recipe.schema.clone();
+        let assertions = recipe.assert.clone();
+        let request = context.build(recipe).await?;
+        Ok(BuiltRequest {
+            http_engine: context.http_engine,
+            request,
+            schema,
+            assertions,
+            template_context: context.template_context,
+        })
+    }
+
+    /// Load everything needed to build requests: the collection, config,
+    /// database, and HTTP engine (iff `trigger_dependencies` is enabled)
+    async fn load(
+        self,
+        global: GlobalArgs,
+        trigger_dependencies: bool,
+    ) -> anyhow::Result<RequestContext> {
+        if self.body_stdin
+            && global.file.as_deref() == Some(Path::new(STDIN_PATH))
+        {
+            bail!("Can't read both the collection and the body from stdin");
+        }
+
+        // Read stdin up front, so the body is fully loaded before anything
+        // else (e.g. prompts) touches the terminal
+        let options = RecipeOptions {
+            body: self.read_body_stdin()?,
+            ..Default::default()
+        };
         let collection_path = CollectionFile::try_path(global.file)?;
         let database = Database::load()?.into_collection(&collection_path)?;
+        let mut config = Config::load()?;
+        config.offline |= global.offline;
+        config.resolve.extend(global.resolve);
         let collection_file = CollectionFile::load(collection_path).await?;
-        let collection = collection_file.collection;
+        let path_root = config.path_root(&collection_file);
+        let mut collection = collection_file.collection;
+        collection.merge_global_chains(&config.chains);
         // Passing the HTTP engine is how we tell the template renderer that
         // it's ok to execute subrequests during render
         let http_engine = if trigger_dependencies {
-            let config = Config::load()?;
             Some(HttpEngine::new(&config, database.clone()))
         } else {
             None
         };
 
-        // Validate profile ID, so we can provide a good error if it's invalid
-        if let Some(profile_id) = &self.profile {
-            collection.profiles.get(profile_id).ok_or_else(|| {
-                anyhow!(
-                    "No profile with ID `{profile_id}`; options are: {}",
-                    collection.profiles.keys().join(", ")
-                )
-            })?;
-        }
-
-        // Find recipe by ID
-        let recipe = collection
-            .recipes
-            .get_recipe(&self.recipe_id)
-            .ok_or_else(|| {
-                anyhow!(
-                    "No recipe with ID `{}`; options are: {}",
-                    self.recipe_id,
-                    collection.recipes.recipe_ids().join(", ")
-                )
-            })?
-            .clone();
-
-        // Build the request
         let overrides: IndexMap<_, _> = self.overrides.into_iter().collect();
-        let template_context = TemplateContext {
-            selected_profile: self.profile,
+        let mut template_context = TemplateContext {
+            // Selected below, because the profile argument is rendered with
+            // this context
+            selected_profile: None,
             collection,
             http_engine: http_engine.clone(),
             database,
             overrides,
             prompter: Box::new(CliPrompter),
+            chain_choices: Default::default(),
+            random: self.seed.map(RandomSource::seeded).unwrap_or_default(),
+            variables: Default::default(),
+            path_root,
             recursion_count: Default::default(),
         };
-        let request = RequestBuilder::new(recipe, RecipeOptions::default())
-            .build(&template_context)
+
+        template_context.selected_profile =
+            select_profile(&template_context, self.profile).await?;
+
+        Ok(RequestContext {
+            http_engine,
+            template_context,
+            options,
+        })
+    }
+
+    /// If enabled, read the request body override from stdin
+    fn read_body_stdin(&self) -> anyhow::Result<Option<BodyOverride>> {
+        if !self.body_stdin {
+            return Ok(None);
+        }
+        let mut body = Vec::new();
+        io::stdin()
+            .read_to_end(&mut body)
+            .context("Error reading body from stdin")?;
+        let body = if self.template_body_stdin {
+            let template = String::from_utf8(body)
+                .context("Body from stdin is not valid UTF-8")?;
+            let template = Template::try_from(template)
+                .context("Error parsing body from stdin as template")?;
+            BodyOverride::Template(template)
+        } else {
+            BodyOverride::Raw(body.into())
+        };
+        Ok(Some(body))
+    }
+}
+
+/// Output of [BuildRequestCommand::build_request]. Along with the request
+/// itself, this includes everything needed to send it and check its response.
+pub struct BuiltRequest {
+    /// Defined iff `trigger_dependencies` was enabled
+    pub http_engine: Option<HttpEngine>,
+    pub request: Request,
+    /// Schema to validate the response body against, from the recipe
+    pub schema: Option<ResponseSchema>,
+    /// Checks for the response to pass, from the recipe
+    pub assertions: Assertions,
+    /// Context the request was rendered with. Re-used to render the schema
+    pub template_context: TemplateContext,
+}
+
+/// Everything needed to build requests from the collection, and send them.
+/// See [BuildRequestCommand::load].
+struct RequestContext {
+    /// Defined iff `trigger_dependencies` was enabled
+    http_engine: Option<HttpEngine>,
+    template_context: TemplateContext,
+    /// Applied to every recipe that's built
+    options: RecipeOptions,
+}
+
+impl RequestContext {
+    /// Get a copy of this context with a data row's values as overrides, for
+    /// `row.` keys. Overrides given by the user are kept
+    fn with_row(&self, row: CsvRow) -> Self {
+        let template_context = &self.template_context;
+        let mut overrides = template_context.overrides.clone();
+        overrides.extend(
+            row.into_iter().map(|(column, value)| {
+                (format!("{ROW_PREFIX}{column}"), value)
+            }),
+        );
+        Self {
+            http_engine: self.http_engine.clone(),
+            template_context: TemplateContext {
+                collection: template_context.collection.clone(),
+                selected_profile: template_context.selected_profile.clone(),
+                http_engine: template_context.http_engine.clone(),
+                database: template_context.database.clone(),
+                overrides,
+                prompter: Box::new(CliPrompter),
+                // Remember choices across rows
+                chain_choices: template_context.chain_choices.clone(),
+                random: template_context.random.clone(),
+                // Captures from one row are visible to the next
+                variables: template_context.variables.clone(),
+                path_root: template_context.path_root.clone(),
+                recursion_count: Default::default(),
+            },
+            options: self.options.clone(),
+        }
+    }
+
+    /// Get a copy of the template context that never sends requests or
+    /// prompts the user, for looking at a batch before it runs
+    fn preview(&self) -> TemplateContext {
+        let template_context = &self.template_context;
+        TemplateContext {
+            collection: template_context.collection.clone(),
+            selected_profile: template_context.selected_profile.clone(),
+            http_engine: None,
+            database: template_context.database.clone(),
+            overrides: template_context.overrides.clone(),
+            prompter: Box::new(SilentPrompter),
+            chain_choices: template_context.chain_choices.clone(),
+            // Don't use up any seeded values
+            random: Default::default(),
+            variables: template_context.variables.clone(),
+            path_root: template_context.path_root.clone(),
+            recursion_count: Default::default(),
+        }
+    }
+
+    /// Render a recipe into a request
+    async fn build(&self, recipe: Recipe) -> anyhow::Result<Request> {
+        let request = RequestBuilder::new(recipe, self.options.clone())
+            .build(&self.template_context)
             .await?;
-        Ok((http_engine, request))
+        Ok(request)
+    }
+}
+
+/// Get the recipes to send for `--summary`, in order. The ID can be a single
+/// recipe, or a folder to send everything under it. Disabled recipes and
+/// folders are skipped, unless they're the one given.
+fn summary_recipes(
+    collection: &Collection,
+    id: &RecipeId,
+) -> anyhow::Result<Vec<Recipe>> {
+    fn collect(node: &RecipeNode, recipes: &mut Vec<Recipe>) {
+        match node {
+            RecipeNode::Folder(folder) => {
+                for child in folder.children.values() {
+                    if !child.is_disabled() {
+                        collect(child, recipes);
+                    }
+                }
+            }
+            RecipeNode::Recipe(recipe) => recipes.push(recipe.clone()),
+        }
+    }
+
+    let node = collection.recipes.get(id).ok_or_else(|| {
+        anyhow!(
+            "No recipe or folder with ID `{id}`; recipes are: {}",
+            collection.recipes.recipe_ids().join(", ")
+        )
+    })?;
+    let mut recipes = Vec::new();
+    collect(node, &mut recipes);
+    if recipes.is_empty() {
+        bail!("Folder `{id}` has no enabled recipes");
+    }
+    Ok(recipes)
+}
+
+/// For each recipe in a `--summary` run, get the indexes of the earlier
+/// recipes it depends on. A recipe depends on another if it loads a chain from
+/// that recipe, directly or transitively. A `!last_response` chain depends on
+/// everything before it, since any of those could be the last response.
+///
+/// Recipes that can't be resolved have no dependencies. They'll fail to build
+/// anyway, and report the error then.
+fn batch_dependencies(
+    collection: &Collection,
+    profile_id: Option<&ProfileId>,
+    recipes: &[Recipe],
+) -> Vec<Vec<usize>> {
+    recipes
+        .iter()
+        .enumerate()
+        .map(|(index, recipe)| {
+            let Ok(exported) = collection.export_recipe(&recipe.id, profile_id)
+            else {
+                return Vec::new();
+            };
+            let last_response = exported
+                .chains
+                .values()
+                .any(|chain| matches!(chain.source, ChainSource::LastResponse));
+            recipes[..index]
+                .iter()
+                .enumerate()
+                .filter(|(_, other)| {
+                    // Any recipe could use a captured variable, so wait for
+                    // every recipe that captures one
+                    last_response
+                        || !other.capture.is_empty()
+                        || exported.recipes.get_recipe(&other.id).is_some()
+                })
+                .map(|(i, _)| i)
+                .collect()
+        })
+        .collect()
+}
+
+/// Requests in a batch run that can modify data, counted before anything is
+/// sent so the user can confirm the run. This is independent of the
+/// `confirm_methods` config field, which only applies to the TUI.
+struct MutatingRequests {
+    /// Total number of requests in the batch
+    total: usize,
+    /// Number of requests that can modify data
+    count: usize,
+    /// Number of mutating requests per method, in the order first seen
+    methods: IndexMap<String, usize>,
+    /// Hosts that mutating requests go to, in the order first seen
+    hosts: IndexSet<String>,
+}
+
+impl MutatingRequests {
+    /// Render the method and URL of every request in the batch. Nothing is
+    /// sent or prompted for while doing this, and a method or URL that uses a
+    /// chain with side effects (e.g. a command) isn't rendered at all. If
+    /// either one can't be rendered, assume the request can modify data,
+    /// because we can't tell otherwise.
+    async fn new(
+        contexts: &[(Option<usize>, RequestContext)],
+        recipes: &[Recipe],
+    ) -> Self {
+        let mut plan = Self {
+            total: contexts.len() * recipes.len(),
+            count: 0,
+            methods: IndexMap::new(),
+            hosts: IndexSet::new(),
+        };
+        for (_, context) in contexts {
+            let template_context = context.preview();
+            let collection = &template_context.collection;
+            let profile = template_context
+                .selected_profile
+                .as_ref()
+                .and_then(|profile_id| collection.profiles.get(profile_id));
+            for recipe in recipes {
+                let builder = || {
+                    RequestBuilder::new(recipe.clone(), context.options.clone())
+                };
+                let method = if collection
+                    .templates_have_side_effects([&recipe.method], profile)
+                {
+                    None
+                } else {
+                    builder().build_method(&template_context).await.ok()
+                };
+                if method.as_ref().is_some_and(Method::is_safe) {
+                    continue;
+                }
+                // The URL includes the base URL and query parameters
+                let url_templates = iter::once(&recipe.url)
+                    .chain(
+                        profile.and_then(|profile| profile.base_url.as_ref()),
+                    )
+                    .chain(recipe.query.values());
+                let host = if collection
+                    .templates_have_side_effects(url_templates, profile)
+                {
+                    None
+                } else {
+                    builder().build_url(&template_context).await.ok()
+                }
+                .and_then(|url| {
+                    let host = url.host_str()?.to_owned();
+                    Some(match url.port() {
+                        Some(port) => format!("{host}:{port}"),
+                        None => host,
+                    })
+                });
+                plan.count += 1;
+                *plan
+                    .methods
+                    .entry(method.map_or_else(
+                        || "unknown method".into(),
+                        |method| method.to_string(),
+                    ))
+                    .or_default() += 1;
+                plan.hosts
+                    .insert(host.unwrap_or_else(|| "unknown host".into()));
+            }
+        }
+        plan
+    }
+
+    /// Print the plan to stderr and ask the user to confirm. Returns an error
+    /// if they decline, or if there's no terminal to ask on
+    fn confirm(&self) -> anyhow::Result<()> {
+        eprintln!(
+            "About to send {} request{}, {} of which can modify data ({})",
+            self.total,
+            if self.total == 1 { "" } else { "s" },
+            self.count,
+            self.methods
+                .iter()
+                .map(|(method, count)| format!("{count} {method}"))
+                .format(", ")
+        );
+        eprintln!("Hosts: {}", self.hosts.iter().format(", "));
+        let confirmed = Confirm::new()
+            .with_prompt("Send?")
+            .default(false)
+            .interact()
+            .context(
+                "Error asking for confirmation. Pass `--yes` to skip it",
+            )?;
+        if confirmed {
+            Ok(())
+        } else {
+            bail!("Cancelled; no requests were sent")
+        }
+    }
+}
+
+/// One line of `--summary` output, describing a single request. These are
+/// meant to be grepped, so the format should stay stable. Failed lines start
+/// with `FAIL`.
+#[derive(Clone)]
+struct SummaryLine {
+    /// Data file row the request was rendered with, if any
+    row: Option<usize>,
+    /// The request's label or `METHOD /path` if the request was built,
+    /// otherwise the recipe ID
+    target: String,
+    /// Response status and time, or why we didn't get a response
+    outcome: Result<(StatusCode, chrono::Duration), String>,
+    /// Recipe assertions the response didn't pass
+    failures: Vec<String>,
+}
+
+impl SummaryLine {
+    /// Build and send a recipe, and describe how it went
+    async fn run(
+        context: &RequestContext,
+        http_engine: &HttpEngine,
+        recipe: &Recipe,
+        row: Option<usize>,
+    ) -> Self {
+        let request = match context.build(recipe.clone()).await {
+            Ok(request) => request,
+            Err(error) => {
+                return Self {
+                    row,
+                    target: recipe.id.to_string(),
+                    outcome: Err(format!("{error:#}")),
+                    failures: Vec::new(),
+                }
+            }
+        };
+        // The recipe's label is the most useful name, if it has one
+        let target = request.label.clone().unwrap_or_else(|| {
+            let mut target =
+                format!("{} {}", request.method, request.url.path());
+            if let Some(query) = request.url.query() {
+                target.push('?');
+                target.push_str(query);
+            }
+            target
+        });
+        let mut failures = Vec::new();
+        let outcome = match http_engine.clone().send(request.into()).await {
+            Ok(record) => {
+                if let Err(error) =
+                    context.template_context.variables.capture(recipe, &record)
+                {
+                    eprintln!("{error:#}");
+                }
+                failures = recipe
+                    .assert
+                    .check(&record)
+                    .iter()
+                    .map(AssertionFailure::to_string)
+                    .collect();
+                Ok((record.response.status, record.duration()))
+            }
+            Err(error) => Err(format!("{:#}", error.error)),
+        };
+        Self {
+            row,
+            target,
+            outcome,
+            failures,
+        }
+    }
+
+    /// Did the request fail, either with an error, error status, or failed
+    /// assertion?
+    fn is_failure(&self) -> bool {
+        match &self.outcome {
+            Ok((status, _)) => {
+                status.is_client_error()
+                    || status.is_server_error()
+                    || !self.failures.is_empty()
+            }
+            Err(_) => true,
+        }
+    }
+}
+
+impl Display for SummaryLine {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.is_failure() {
+            write!(f, "FAIL ")?;
+        }
+        if let Some(row) = self.row {
+            write!(f, "row {row}: ")?;
+        }
+        match &self.outcome {
+            Ok((status, duration)) => {
+                write!(
+                    f,
+                    "{} → {} ({}ms)",
+                    self.target,
+                    status.as_u16(),
+                    duration.num_milliseconds()
+                )?;
+                if !self.failures.is_empty() {
+                    write!(f, " [{}]", self.failures.join("; "))?;
+                }
+                Ok(())
+            }
+            // Keep it to one line, even if the error has several
+            Err(error) => write!(
+                f,
+                "{} → error: {}",
+                self.target,
+                error.replace('\n', " ")
+            ),
+        }
     }
 }
 
@@ -216,7 +932,27 @@ impl Prompter for CliPrompter {
     fn prompt(&self, prompt: Prompt) {
         // This will implicitly queue the prompts by blocking the main thread.
         // Since the CLI has nothing else to do while waiting on a response,
-        // that's fine.
+        // that's fine. Dialoguer re-prompts until the validator passes
+        if !prompt.options.is_empty() {
+            let result = Select::new()
+                .with_prompt(prompt.message)
+                .items(&prompt.options)
+                .default(0)
+                .interact()
+                .context("Error reading value from prompt")
+                .traced();
+            if let Ok(index) = result {
+                prompt.channel.respond(prompt.options[index].clone());
+            }
+            return;
+        }
+        let pattern = prompt.pattern;
+        let validator = move |value: &String| match &pattern {
+            Some(pattern) if !pattern.is_match(value) => {
+                Err(format!("Value must match `{pattern}`"))
+            }
+            _ => Ok(()),
+        };
         let result = if prompt.sensitive {
             // Dialoguer doesn't support default values here so there's nothing
             // we can do
@@ -229,10 +965,13 @@ impl Prompter for CliPrompter {
             Password::new()
                 .with_prompt(prompt.message)
                 .allow_empty_password(true)
+                .validate_with(validator)
                 .interact()
         } else {
-            let mut input =
-                Input::new().with_prompt(prompt.message).allow_empty(true);
+            let mut input = Input::new()
+                .with_prompt(prompt.message)
+                .allow_empty(true)
+                .validate_with(validator);
             if let Some(default) = prompt.default {
                 input = input.default(default);
             }
@@ -248,6 +987,62 @@ impl Prompter for CliPrompter {
     }
 }
 
+/// Never responds to prompts, so any template that needs one fails to render
+#[derive(Debug)]
+struct SilentPrompter;
+
+impl Prompter for SilentPrompter {
+    fn prompt(&self, _: Prompt) {}
+}
+
+/// Pick the profile to render with. An explicit profile always wins, and must
+/// exist. Otherwise, fall back to the collection's default profile. If that's
+/// invalid, just ignore it. It's not the user's fault if they didn't ask for
+/// it
+async fn select_profile(
+    context: &TemplateContext,
+    profile: Option<String>,
+) -> anyhow::Result<Option<ProfileId>> {
+    match profile {
+        Some(profile) => Ok(Some(context.resolve_profile(&profile).await?)),
+        None => Ok(context.collection.default_profile.clone().filter(
+            |profile_id| {
+                let exists =
+                    context.collection.profiles.contains_key(profile_id);
+                if !exists {
+                    warn!("Default profile `{profile_id}` does not exist");
+                }
+                exists
+            },
+        )),
+    }
+}
+
+/// Check a response against the recipe's assertions, and report any failures.
+/// Returns an exit code iff an assertion failed.
+fn check_assertions(
+    assertions: &Assertions,
+    record: &RequestRecord,
+) -> Option<ExitCode> {
+    let failures = assertions.check(record);
+    if failures.is_empty() {
+        return None;
+    }
+    eprintln!("Response failed assertions:");
+    for failure in failures {
+        eprintln!("- Response {failure}");
+    }
+    Some(ExitCode::from(ASSERTION_ERROR_EXIT_CODE))
+}
+
+fn exit_code(exit_status: bool, status: StatusCode) -> ExitCode {
+    if exit_status && status.as_u16() >= 400 {
+        ExitCode::from(HTTP_ERROR_EXIT_CODE)
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
 /// Parse a single key=value pair for an argument
 fn parse_key_val<T, U>(
     s: &str,
@@ -281,3 +1076,260 @@ impl<'a> Display for HeaderDisplay<'a> {
         Ok(())
     }
 }
+
+/// A request in `--verbose` output, in the style of `curl -v`: each line of
+/// the request line and headers is prefixed with `>`, followed by the body.
+/// The `bool` is whether to show sensitive header values.
+struct VerboseRequest<'a>(&'a Request, bool);
+
+impl<'a> Display for VerboseRequest<'a> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let Self(request, reveal) = self;
+        writeln!(f, "> {} {}", request.method, request.url)?;
+        write_verbose_headers(f, ">", &request.headers, *reveal)?;
+        if let Some(command) = &request.body_command {
+            writeln!(f, "<body streamed from `{}`>", command.join(" "))?;
+        } else if let Some(body) = &request.body {
+            writeln!(f, "{}", MaybeStr(body))?;
+        }
+        Ok(())
+    }
+}
+
+/// A response in `--verbose` output: the status, time, and headers, each
+/// prefixed with `<`. The body is printed separately.
+struct VerboseResponse<'a>(&'a RequestRecord, bool);
+
+impl<'a> Display for VerboseResponse<'a> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let Self(record, reveal) = self;
+        let status = record.response.status;
+        write!(f, "< {}", status.as_u16())?;
+        if let Some(reason) = status.canonical_reason() {
+            write!(f, " {reason}")?;
+        }
+        write!(f, " ({}ms", record.duration().num_milliseconds())?;
+        if let Some(tls_version) = record.response.tls_version {
+            write!(f, ", {tls_version}")?;
+        }
+        writeln!(f, ")")?;
+        write_verbose_headers(f, "<", &record.response.headers, *reveal)
+    }
+}
+
+/// Write headers for `--verbose` output, followed by an empty prefixed line
+/// to mark the end of the headers
+fn write_verbose_headers(
+    f: &mut Formatter<'_>,
+    prefix: &str,
+    headers: &HeaderMap,
+    reveal: bool,
+) -> fmt::Result {
+    let key_style = Style::new().bold();
+    for (key, value) in headers {
+        write!(f, "{prefix} {}: ", key_style.apply_to(key))?;
+        if reveal || !is_sensitive_header(key, value) {
+            writeln!(f, "{}", MaybeStr(value.as_bytes()))?;
+        } else {
+            writeln!(f, "{SENSITIVE_HEADER_PLACEHOLDER}")?;
+        }
+    }
+    writeln!(f, "{prefix}")
+}
+
+/// Should a header's value be masked in `--verbose` output? This covers
+/// headers generated from the recipe's `authentication`, and any header that
+/// commonly holds credentials.
+fn is_sensitive_header(name: &HeaderName, value: &HeaderValue) -> bool {
+    value.is_sensitive()
+        || [
+            header::AUTHORIZATION,
+            header::PROXY_AUTHORIZATION,
+            header::COOKIE,
+            header::SET_COOKIE,
+        ]
+        .contains(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_util::*, util::assert_err};
+    use chrono::Utc;
+    use dialoguer::console::strip_ansi_codes;
+    use factori::create;
+    use indexmap::indexmap;
+    use rstest::rstest;
+    use std::env;
+    use uuid::Uuid;
+
+    #[rstest]
+    #[case::default(None, Some("dev"), Some("dev"))]
+    #[case::explicit_beats_default(Some("prod"), Some("dev"), Some("prod"))]
+    #[case::unknown_default(None, Some("staging"), None)]
+    #[case::no_default(None, None, None)]
+    #[tokio::test]
+    async fn test_select_profile(
+        #[case] profile: Option<&str>,
+        #[case] default_profile: Option<&str>,
+        #[case] expected: Option<&str>,
+    ) {
+        let context = create!(
+            TemplateContext,
+            collection: create!(
+                Collection,
+                default_profile: default_profile.map(ProfileId::from),
+                profiles: indexmap! {
+                    "dev".into() => create!(Profile, id: "dev".into()),
+                    "prod".into() => create!(Profile, id: "prod".into()),
+                },
+            ),
+        );
+        let selected = select_profile(&context, profile.map(String::from))
+            .await
+            .unwrap();
+        assert_eq!(selected, expected.map(ProfileId::from));
+    }
+
+    /// An unknown explicit profile is an error, even with a valid default
+    #[tokio::test]
+    async fn test_select_profile_unknown() {
+        let context = create!(
+            TemplateContext,
+            collection: create!(
+                Collection,
+                default_profile: Some("dev".into()),
+                profiles: indexmap! {
+                    "dev".into() => create!(Profile, id: "dev".into()),
+                },
+            ),
+        );
+        assert_err!(
+            select_profile(&context, Some("prod".into())).await,
+            "No profile with ID or name `prod`; options are: dev"
+        );
+    }
+
+    /// Previewing a batch for confirmation doesn't run chains with side
+    /// effects, so nothing happens if the user declines
+    #[tokio::test]
+    async fn test_mutating_requests_command_chain() {
+        let path =
+            env::temp_dir().join(format!("slumber-{}.txt", Uuid::new_v4()));
+        let chain = create!(
+            Chain,
+            id: "host".into(),
+            source: ChainSource::Command {
+                command: vec![
+                    "sh".into(),
+                    "-c".into(),
+                    format!("touch {} && echo localhost", path.display())
+                        .as_str()
+                        .into(),
+                ],
+            },
+        );
+        let recipe = create!(
+            Recipe,
+            method: "POST".into(),
+            url: "http://{{chains.host}}/users".into(),
+        );
+        let context = RequestContext {
+            http_engine: None,
+            template_context: create!(
+                TemplateContext,
+                collection: create!(
+                    Collection,
+                    chains: indexmap! {chain.id.clone() => chain},
+                ),
+            ),
+            options: RecipeOptions::default(),
+        };
+
+        let plan = MutatingRequests::new(
+            &[(None, context)],
+            &[recipe.clone(), recipe],
+        )
+        .await;
+        assert_eq!(plan.count, 2);
+        assert_eq!(plan.methods, indexmap! {"POST".into() => 2});
+        assert_eq!(plan.hosts, IndexSet::from(["unknown host".into()]));
+        // There's no terminal to confirm on, which counts as declining
+        assert_err!(plan.confirm(), "Error asking for confirmation");
+        assert!(!path.exists(), "Command chain was executed");
+    }
+
+    /// Sensitive header values are masked in verbose output, unless revealed
+    #[rstest]
+    #[case::masked(
+        false,
+        "> GET http://localhost/url\n\
+        > authorization: <sensitive>\n\
+        > cookie: <sensitive>\n\
+        > x-api-key: <sensitive>\n\
+        > content-type: text/plain\n\
+        >\n",
+        "< 200 OK (0ms)\n\
+        < set-cookie: <sensitive>\n\
+        < content-type: text/plain\n\
+        <\n"
+    )]
+    #[case::revealed(
+        true,
+        "> GET http://localhost/url\n\
+        > authorization: Bearer hunter2\n\
+        > cookie: session=hunter2\n\
+        > x-api-key: hunter2\n\
+        > content-type: text/plain\n\
+        >\n",
+        "< 200 OK (0ms)\n\
+        < set-cookie: session=hunter2\n\
+        < content-type: text/plain\n\
+        <\n"
+    )]
+    fn test_verbose_headers(
+        #[case] reveal: bool,
+        #[case] expected_request: &str,
+        #[case] expected_response: &str,
+    ) {
+        // Generated from `authentication`, so it's flagged as sensitive
+        let mut api_key = HeaderValue::from_static("hunter2");
+        api_key.set_sensitive(true);
+        let request = create!(
+            Request,
+            headers: [
+                (header::AUTHORIZATION, "Bearer hunter2".try_into().unwrap()),
+                (header::COOKIE, "session=hunter2".try_into().unwrap()),
+                (HeaderName::from_static("x-api-key"), api_key),
+                (header::CONTENT_TYPE, "text/plain".try_into().unwrap()),
+            ]
+            .into_iter()
+            .collect(),
+        );
+        let now = Utc::now();
+        let record = create!(
+            RequestRecord,
+            response: create!(
+                Response,
+                headers: [
+                    (header::SET_COOKIE, "session=hunter2".try_into().unwrap()),
+                    (header::CONTENT_TYPE, "text/plain".try_into().unwrap()),
+                ]
+                .into_iter()
+                .collect(),
+            ),
+            start_time: now,
+            end_time: now,
+        );
+
+        // Styling depends on global console state, so ignore it
+        assert_eq!(
+            strip_ansi_codes(&VerboseRequest(&request, reveal).to_string()),
+            expected_request
+        );
+        assert_eq!(
+            strip_ansi_codes(&VerboseResponse(&record, reveal).to_string()),
+            expected_response
+        );
+    }
+}
@@ -1,6 +1,9 @@
 use crate::{
-    cli::Subcommand, collection::CollectionFile, config::Config, db::Database,
-    util::paths::DataDirectory, GlobalArgs,
+    cli::{GlobalArgs, Subcommand},
+    collection::CollectionFile,
+    config::Config,
+    db::Database,
+    util::paths::DataDirectory,
 };
 use async_trait::async_trait;
 use clap::Parser;
@@ -1,21 +1,49 @@
 //! A request collection defines recipes, profiles, etc. that make requests
 //! possible
 
+mod builder;
 mod cereal;
+mod curl;
+mod diff;
+mod error;
+mod export;
+mod format;
 mod insomnia;
+mod local;
+mod merge;
 mod models;
 mod recipe_tree;
+mod summary;
 
+pub use builder::{
+    ChainBuilder, CollectionBuilder, FolderBuilder, ProfileBuilder,
+    RecipeBuilder,
+};
+pub(crate) use cereal::deserialize_id_map;
+pub use diff::{CollectionDiff, ItemDiff};
+pub use error::{
+    CollectionBuildError, CollectionLoadError, LocalOverlayError, MergeError,
+};
+pub use format::{format_collection, lossy_features};
+pub use local::{LocalOverlay, LocalProfile};
 pub use models::*;
 pub use recipe_tree::*;
+pub use summary::{
+    ChainSummary, CollectionSummary, ProfileSummary, RecipeSummary, References,
+};
 
 use crate::util::{parse_yaml, ResultExt};
-use anyhow::{anyhow, Context};
+use anyhow::{anyhow, bail, Context};
+use itertools::Itertools;
+use reqwest::{Client, Response};
 use std::{
+    ffi::OsStr,
     fmt::Debug,
     fs,
     future::Future,
+    io::{self, Read},
     path::{Path, PathBuf},
+    time::Duration,
 };
 use tokio::task;
 use tracing::{info, warn};
@@ -30,11 +58,25 @@ pub const CONFIG_FILES: &[&str] = &[
     ".slumber.yaml",
 ];
 
+/// How long to wait for a collection fetched from a URL. Loading blocks
+/// startup, so a server that never responds shouldn't hang it forever.
+const FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Collection path that means "read the collection from stdin"
+pub const STDIN_PATH: &str = "-";
+
 /// A wrapper around a request collection, to handle functionality around the
-/// file system.
+/// file system. The path can also be a directory, in which case every YAML
+/// file directly inside it is loaded and merged into a single collection. See
+/// [Collection::merge].
+///
+/// Collections can also come from outside the file system: a path of `-`
+/// ([STDIN_PATH]) reads the collection from stdin, and an `http://` or
+/// `https://` URL fetches it. These have no local overlay or directory, so
+/// relative file paths within them can't be resolved.
 #[derive(Debug)]
 pub struct CollectionFile {
-    /// Path to the file that this collection was loaded from
+    /// Path to the file (or directory) that this collection was loaded from
     path: PathBuf,
     pub collection: Collection,
 }
@@ -53,7 +95,7 @@ impl CollectionFile {
     /// Load config from the given file. The caller is responsible for using
     /// [Self::try_path] to find the file themself. This pattern enables the
     /// TUI to start up and watch the collection file, even if it's invalid.
-    pub async fn load(path: PathBuf) -> anyhow::Result<Self> {
+    pub async fn load(path: PathBuf) -> Result<Self, CollectionLoadError> {
         let collection = load_collection(path.clone()).await?;
         Ok(Self { path, collection })
     }
@@ -61,15 +103,77 @@ impl CollectionFile {
     /// Reload a new collection from the same file used for this one.
     ///
     /// Returns `impl Future` to unlink the future from `&self`'s lifetime.
-    pub fn reload(&self) -> impl Future<Output = anyhow::Result<Collection>> {
-        load_collection(self.path.clone())
+    pub fn reload(
+        &self,
+    ) -> impl Future<Output = Result<Collection, CollectionLoadError>> {
+        let path = self.path.clone();
+        async move {
+            // Stdin has already been consumed, so there's nothing to reload
+            if let CollectionSource::Stdin = CollectionSource::new(&path) {
+                return Err(CollectionLoadError::StdinReload);
+            }
+            load_collection(path).await
+        }
     }
 
-    /// Get the path of the file that this collection was loaded from
+    /// Get the path of the file that this collection was loaded from. For
+    /// collections from stdin or a URL, this is `-` or the URL.
     pub fn path(&self) -> &Path {
         &self.path
     }
 
+    /// Was this collection loaded from the file system, as opposed to stdin
+    /// or a URL?
+    pub fn is_on_disk(&self) -> bool {
+        matches!(CollectionSource::new(&self.path), CollectionSource::Path(_))
+    }
+
+    /// Get the path of the local overlay for this collection, if it has one.
+    /// Only applies to single-file collections; in a directory, the overlays
+    /// are alongside the other files.
+    pub fn local_path(&self) -> Option<PathBuf> {
+        let local_path = local::local_path(&self.path);
+        (self.is_on_disk() && self.path.is_file() && local_path.is_file())
+            .then_some(local_path)
+    }
+
+    /// Get the directory that the collection lives in. For a collection loaded
+    /// from a directory, this is that directory. `None` if the collection
+    /// isn't on the file system.
+    pub fn directory(&self) -> Option<&Path> {
+        if !self.is_on_disk() {
+            None
+        } else if self.path.is_dir() {
+            Some(&self.path)
+        } else {
+            // A bare file name has an empty parent, which means the current
+            // directory. That's what we want anyway.
+            Some(self.path.parent().unwrap_or(Path::new("")))
+        }
+    }
+
+    /// Get the files that make up this collection: the file itself, or every
+    /// YAML file in its directory. Local overlays aren't included.
+    pub fn files(&self) -> anyhow::Result<Vec<PathBuf>> {
+        if !self.is_on_disk() {
+            bail!(
+                "Collection `{}` isn't on the file system",
+                self.path.display()
+            );
+        }
+        if self.path.is_dir() {
+            let paths = directory_files(&self.path).with_context(|| {
+                format!("Error reading directory {:?}", self.path)
+            })?;
+            Ok(paths
+                .into_iter()
+                .filter(|path| !local::is_local(path))
+                .collect())
+        } else {
+            Ok(vec![self.path.clone()])
+        }
+    }
+
     /// Get the path to the collection file, returning an error if none is
     /// available. This will use the override if given, otherwise it will fall
     /// back to searching the current directory for a collection.
@@ -80,6 +184,31 @@ impl CollectionFile {
     }
 }
 
+/// Where a collection is loaded from, based on the path the user gave
+pub(crate) enum CollectionSource<'a> {
+    /// A file or directory
+    Path(&'a Path),
+    /// `-`
+    Stdin,
+    /// An `http://` or `https://` URL
+    Url(&'a str),
+}
+
+impl<'a> CollectionSource<'a> {
+    pub fn new(path: &'a Path) -> Self {
+        match path.to_str() {
+            Some(STDIN_PATH) => Self::Stdin,
+            Some(url)
+                if url.starts_with("http://")
+                    || url.starts_with("https://") =>
+            {
+                Self::Url(url)
+            }
+            _ => Self::Path(path),
+        }
+    }
+}
+
 /// Search the current directory for a config file matching one of the known
 /// file names, and return it if found
 fn detect_path() -> Option<PathBuf> {
@@ -105,21 +234,38 @@ fn detect_path() -> Option<PathBuf> {
     }
 }
 
-/// Load a collection from the given file. Takes an owned path because it
-/// needs to be passed to a future
-async fn load_collection(path: PathBuf) -> anyhow::Result<Collection> {
+/// Load a collection from the given file, directory, stdin, or URL. Takes an
+/// owned path because it needs to be passed to a future
+async fn load_collection(
+    path: PathBuf,
+) -> Result<Collection, CollectionLoadError> {
     info!(?path, "Loading collection file");
-    // A bit pessimistic, huh... This gets around some lifetime struggles
-    let error_context = format!("Error loading data from {path:?}");
-
-    // This async block is really just a try block
-    let result =
-        task::spawn_blocking::<_, anyhow::Result<Collection>>(move || {
-            let bytes = fs::read(path)?;
-            let collection = parse_yaml(&bytes)?;
+    if let CollectionSource::Url(url) = CollectionSource::new(&path) {
+        return load_url(url).await.traced();
+    }
+
+    let result = task::spawn_blocking(move || {
+        if let CollectionSource::Stdin = CollectionSource::new(&path) {
+            let mut bytes = Vec::new();
+            io::stdin()
+                .read_to_end(&mut bytes)
+                .map_err(CollectionLoadError::Stdin)?;
+            parse_yaml(&bytes).map_err(|error| CollectionLoadError::Parse {
+                path: path.clone(),
+                error,
+            })
+        } else if path.is_dir() {
+            load_directory(&path)
+        } else {
+            let mut collection = load_file(&path)?;
+            let local_path = local::local_path(&path);
+            if local_path.is_file() {
+                local::apply_local_file(&mut collection, &local_path)?;
+            }
             Ok(collection)
-        })
-        .await;
+        }
+    })
+    .await;
 
     // Flatten the join error result into the inner task result. Result::flatten
     // is experimental :(
@@ -129,5 +275,220 @@ async fn load_collection(path: PathBuf) -> anyhow::Result<Collection> {
         Err(error) => Err(error.into()),
     };
 
-    result.context(error_context).traced()
+    result.traced()
+}
+
+/// Fetch a collection over HTTP. Any non-success status is an error
+async fn load_url(url: &str) -> Result<Collection, CollectionLoadError> {
+    let fetch_error = |error| CollectionLoadError::Fetch {
+        url: url.to_owned(),
+        error,
+    };
+    let client = Client::builder()
+        .timeout(FETCH_TIMEOUT)
+        .build()
+        .map_err(fetch_error)?;
+    let bytes = client
+        .get(url)
+        .send()
+        .await
+        .and_then(Response::error_for_status)
+        .map_err(fetch_error)?
+        .bytes()
+        .await
+        .map_err(fetch_error)?;
+    parse_yaml(&bytes).map_err(|error| CollectionLoadError::Parse {
+        path: url.into(),
+        error,
+    })
+}
+
+/// Load a single collection file
+fn load_file(path: &Path) -> Result<Collection, CollectionLoadError> {
+    let bytes = fs::read(path).map_err(|error| CollectionLoadError::Io {
+        path: path.to_owned(),
+        error,
+    })?;
+    parse_yaml(&bytes).map_err(|error| CollectionLoadError::Parse {
+        path: path.to_owned(),
+        error,
+    })
+}
+
+/// Get every `.yml`/`.yaml` file directly in a directory, including local
+/// overlays, in alphabetical order. Hidden files and subdirectories are
+/// ignored.
+fn directory_files(directory: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut paths = fs::read_dir(directory)?
+        .map_ok(|entry| entry.path())
+        .filter_ok(|path| {
+            let is_hidden = path
+                .file_name()
+                .and_then(OsStr::to_str)
+                .map_or(true, |name| name.starts_with('.'));
+            let is_yaml = path.extension().is_some_and(|extension| {
+                extension == "yml" || extension == "yaml"
+            });
+            path.is_file() && is_yaml && !is_hidden
+        })
+        .collect::<io::Result<Vec<_>>>()?;
+    paths.sort();
+    Ok(paths)
+}
+
+/// Load every `.yml`/`.yaml` file directly in a directory, and merge them into
+/// one collection. Files are loaded in alphabetical order, which determines the
+/// order of recipes, profiles, etc. in the merged collection. Hidden files and
+/// subdirectories are ignored. Local overlays (`*.local.yml`) are applied
+/// over the merged collection, also in alphabetical order.
+fn load_directory(directory: &Path) -> Result<Collection, CollectionLoadError> {
+    let paths = directory_files(directory).map_err(|error| {
+        CollectionLoadError::Io {
+            path: directory.to_owned(),
+            error,
+        }
+    })?;
+    let (local_paths, paths): (Vec<_>, Vec<_>) =
+        paths.into_iter().partition(|path| local::is_local(path));
+    if paths.is_empty() {
+        return Err(CollectionLoadError::EmptyDirectory {
+            path: directory.to_owned(),
+        });
+    }
+
+    let collections = paths
+        .into_iter()
+        .map(|path| {
+            info!(?path, "Loading collection file from directory");
+            let collection = load_file(&path)?;
+            Ok((path, collection))
+        })
+        .collect::<Result<Vec<_>, CollectionLoadError>>()?;
+    let mut collection = Collection::merge(collections).map_err(|error| {
+        CollectionLoadError::Merge {
+            path: directory.to_owned(),
+            error: error.into(),
+        }
+    })?;
+    for path in local_paths {
+        local::apply_local_file(&mut collection, &path)?;
+    }
+    Ok(collection)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use uuid::Uuid;
+
+    /// Loading a directory merges its YAML files in alphabetical order,
+    /// skipping everything else. Local overlays are applied on top
+    #[tokio::test]
+    async fn test_load_directory() {
+        let directory = env::temp_dir().join(Uuid::new_v4().to_string());
+        fs::create_dir(&directory).unwrap();
+        let recipe = |id: &str| {
+            format!("requests: {{{id}: !request {{method: GET, url: {id}}}}}")
+        };
+        fs::write(directory.join("b.yaml"), recipe("b")).unwrap();
+        fs::write(directory.join("a.yml"), recipe("a")).unwrap();
+        fs::write(directory.join(".hidden.yml"), recipe("hidden")).unwrap();
+        fs::write(directory.join("notes.txt"), "not yaml").unwrap();
+        fs::create_dir(directory.join("nested.yml")).unwrap();
+        fs::write(directory.join("me.local.yml"), "default_profile: mine")
+            .unwrap();
+
+        let collection = CollectionFile::load(directory).await.unwrap();
+        assert_eq!(
+            collection
+                .collection
+                .recipes
+                .recipe_ids()
+                .map(|id| id.as_str())
+                .collect_vec(),
+            vec!["a", "b"]
+        );
+        assert_eq!(collection.collection.default_profile, Some("mine".into()));
+    }
+
+    /// Collections can be fetched from a URL. They aren't on disk, so they
+    /// have no directory or local overlay
+    #[tokio::test]
+    async fn test_load_url() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/slumber.yml")
+            .with_body("requests: {a: !request {method: GET, url: a}}")
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/missing.yml")
+            .with_status(404)
+            .create_async()
+            .await;
+
+        let url = format!("{}/slumber.yml", server.url());
+        let collection = CollectionFile::load(url.into()).await.unwrap();
+        assert_eq!(
+            collection
+                .collection
+                .recipes
+                .recipe_ids()
+                .map(|id| id.as_str())
+                .collect_vec(),
+            vec!["a"]
+        );
+        assert!(!collection.is_on_disk());
+        assert_eq!(collection.directory(), None);
+        assert_eq!(collection.local_path(), None);
+
+        let url = format!("{}/missing.yml", server.url());
+        let error = CollectionFile::load(url.into()).await.unwrap_err();
+        assert!(
+            matches!(error, CollectionLoadError::Fetch { .. }),
+            "{error:?}"
+        );
+    }
+
+    /// Each kind of failure has its own variant, so callers can match on it
+    #[tokio::test]
+    async fn test_load_error() {
+        let directory = env::temp_dir().join(Uuid::new_v4().to_string());
+        fs::create_dir(&directory).unwrap();
+
+        let error = CollectionFile::load(directory.join("missing.yml"))
+            .await
+            .unwrap_err();
+        assert!(matches!(error, CollectionLoadError::Io { .. }), "{error:?}");
+
+        let error = CollectionFile::load(directory.clone()).await.unwrap_err();
+        assert!(
+            matches!(error, CollectionLoadError::EmptyDirectory { .. }),
+            "{error:?}"
+        );
+
+        let path = directory.join("slumber.yml");
+        fs::write(&path, "requests: [").unwrap();
+        let error = CollectionFile::load(path.clone()).await.unwrap_err();
+        assert!(
+            matches!(&error, CollectionLoadError::Parse { path: p, .. } if *p == path),
+            "{error:?}"
+        );
+
+        fs::write(&path, "profiles: {dev: {data: {}}}").unwrap();
+        fs::write(directory.join("slumber.local.yml"), "profiles: {prod: {}}")
+            .unwrap();
+        let error = CollectionFile::load(path).await.unwrap_err();
+        assert!(
+            matches!(
+                error,
+                CollectionLoadError::Local {
+                    error: LocalOverlayError::ProfileUnknown { .. },
+                    ..
+                }
+            ),
+            "{error:?}"
+        );
+    }
 }
@@ -0,0 +1,903 @@
+//! Build a collection in code, rather than loading it from YAML
+
+use crate::{
+    collection::{
+        export::{authentication_templates, chain_templates, recipe_templates},
+        Authentication, BodyFormat, Chain, ChainSource, Collection,
+        CollectionBuildError, Folder, Method, Profile, ProfileColor, ProfileId,
+        Recipe, RecipeId, RecipeNode, RecipeTree, SelectorFallback,
+        SelectorMode,
+    },
+    http::{ContentType, Query},
+    template::{Template, TemplateReference},
+};
+use indexmap::IndexMap;
+use std::collections::HashSet;
+
+/// Build a [Collection] in code, rather than loading it from YAML. Templates
+/// are given as plain strings, and everything is parsed and validated at once
+/// in [CollectionBuilder::build], so a builder chain only has one error to
+/// handle.
+///
+/// ```
+/// use slumber::collection::{
+///     ChainBuilder, Collection, FolderBuilder, Method, ProfileBuilder,
+///     RecipeBuilder,
+/// };
+///
+/// let collection = Collection::builder()
+///     .profile(
+///         ProfileBuilder::new("dev")
+///             .base_url("http://localhost:3000")
+///             .field("user_id", "abc123"),
+///     )
+///     .default_profile("dev")
+///     .chain(ChainBuilder::request("token", "login").selector("$.token"))
+///     .recipe(RecipeBuilder::new("login", Method::Post, "/login"))
+///     .folder(
+///         FolderBuilder::new("users").recipe(
+///             RecipeBuilder::new(
+///                 "get_user",
+///                 Method::Get,
+///                 "/users/{{user_id}}",
+///             )
+///             .header("Authorization", "Bearer {{chains.token}}"),
+///         ),
+///     )
+///     .build()
+///     .unwrap();
+/// assert_eq!(collection.recipes.recipe_ids().count(), 2);
+/// ```
+#[derive(Debug, Default)]
+pub struct CollectionBuilder {
+    default_profile: Option<String>,
+    profiles: Vec<ProfileBuilder>,
+    constants: Vec<(String, String)>,
+    chains: Vec<ChainBuilder>,
+    recipes: Vec<NodeBuilder>,
+}
+
+/// Build a [Profile]. Only usable as part of a [CollectionBuilder]
+#[derive(Debug)]
+pub struct ProfileBuilder {
+    id: String,
+    name: Option<String>,
+    base_url: Option<String>,
+    authentication: Option<AuthenticationBuilder>,
+    color: Option<ProfileColor>,
+    label: Option<String>,
+    headers: Vec<(String, String)>,
+    data: Vec<(String, String)>,
+}
+
+/// Build a [Chain]. Only usable as part of a [CollectionBuilder]. Chain
+/// sources are given already built, since each has its own shape.
+#[derive(Debug)]
+pub struct ChainBuilder {
+    id: String,
+    source: ChainSource,
+    sensitive: bool,
+    selector: Option<String>,
+    content_type: Option<ContentType>,
+    selector_mode: SelectorMode,
+    selector_fallback: Option<SelectorFallback>,
+}
+
+/// Build a [Recipe]. Only usable as part of a [CollectionBuilder] or
+/// [FolderBuilder]
+#[derive(Debug)]
+pub struct RecipeBuilder {
+    id: String,
+    name: Option<String>,
+    label: Option<String>,
+    method: Method,
+    url: String,
+    body: Option<String>,
+    body_format: BodyFormat,
+    authentication: Option<AuthenticationBuilder>,
+    query: Vec<(String, String)>,
+    headers: Vec<(String, String)>,
+    cookies: Vec<(String, String)>,
+    expects: Option<ContentType>,
+    persist: bool,
+    confirm: Option<bool>,
+    disabled: bool,
+}
+
+/// Build a [Folder]. Only usable as part of a [CollectionBuilder], or another
+/// folder
+#[derive(Debug)]
+pub struct FolderBuilder {
+    id: String,
+    name: Option<String>,
+    children: Vec<NodeBuilder>,
+    disabled: bool,
+}
+
+#[derive(Debug)]
+enum NodeBuilder {
+    Recipe(Box<RecipeBuilder>),
+    Folder(FolderBuilder),
+}
+
+#[derive(Debug)]
+enum AuthenticationBuilder {
+    Basic {
+        username: String,
+        password: Option<String>,
+    },
+    Bearer(String),
+}
+
+impl Collection {
+    /// Start building a collection in code
+    pub fn builder() -> CollectionBuilder {
+        CollectionBuilder::default()
+    }
+}
+
+impl CollectionBuilder {
+    /// Profile to use when one isn't specified. Must be one of the profiles
+    pub fn default_profile(mut self, profile_id: impl Into<String>) -> Self {
+        self.default_profile = Some(profile_id.into());
+        self
+    }
+
+    pub fn profile(mut self, profile: ProfileBuilder) -> Self {
+        self.profiles.push(profile);
+        self
+    }
+
+    /// Add a constant, which is available to templates as `{{const.<name>}}`
+    pub fn constant(
+        mut self,
+        name: impl Into<String>,
+        value: impl Into<String>,
+    ) -> Self {
+        self.constants.push((name.into(), value.into()));
+        self
+    }
+
+    pub fn chain(mut self, chain: ChainBuilder) -> Self {
+        self.chains.push(chain);
+        self
+    }
+
+    /// Add a recipe at the root of the recipe tree
+    pub fn recipe(mut self, recipe: RecipeBuilder) -> Self {
+        self.recipes.push(NodeBuilder::Recipe(Box::new(recipe)));
+        self
+    }
+
+    /// Add a folder at the root of the recipe tree
+    pub fn folder(mut self, folder: FolderBuilder) -> Self {
+        self.recipes.push(NodeBuilder::Folder(folder));
+        self
+    }
+
+    /// Parse all templates and build the collection. The result is validated
+    /// more strictly than a collection file: IDs must be unique, and every
+    /// reference to a profile, constant, chain, or recipe must resolve.
+    /// References to profile fields aren't checked, since they can be supplied
+    /// at render time.
+    pub fn build(self) -> Result<Collection, CollectionBuildError> {
+        let mut profiles = IndexMap::new();
+        for profile in self.profiles {
+            let profile = profile.build()?;
+            let id = profile.id.clone();
+            if profiles.insert(id.clone(), profile).is_some() {
+                return Err(duplicate("profile", id.to_string()));
+            }
+        }
+
+        let mut constants = IndexMap::new();
+        for (name, value) in self.constants {
+            let template = parse(value, "constant", &format!("`{name}`"))?;
+            if constants.insert(name.clone(), template).is_some() {
+                return Err(duplicate("constant", name));
+            }
+        }
+
+        let mut chains = IndexMap::new();
+        for chain in self.chains {
+            let chain = chain.build()?;
+            let id = chain.id.clone();
+            if chains.insert(id.clone(), chain).is_some() {
+                return Err(duplicate("chain", id.to_string()));
+            }
+        }
+
+        let recipes = build_nodes(self.recipes)?;
+        let recipes = RecipeTree::new(recipes)
+            .map_err(|id| duplicate("recipe/folder", id.to_string()))?;
+
+        let default_profile = self.default_profile.map(ProfileId::from);
+        let collection = Collection {
+            default_profile,
+            profiles,
+            constants,
+            chains,
+            recipes,
+            _ignore: Default::default(),
+        };
+        validate(&collection)?;
+        Ok(collection)
+    }
+}
+
+impl ProfileBuilder {
+    pub fn new(id: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            name: None,
+            base_url: None,
+            authentication: None,
+            color: None,
+            label: None,
+            headers: Vec::new(),
+            data: Vec::new(),
+        }
+    }
+
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    pub fn base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = Some(base_url.into());
+        self
+    }
+
+    /// Replace the authentication of recipes with `Basic` authentication
+    pub fn basic_auth(
+        mut self,
+        username: impl Into<String>,
+        password: Option<impl Into<String>>,
+    ) -> Self {
+        self.authentication = Some(AuthenticationBuilder::Basic {
+            username: username.into(),
+            password: password.map(Into::into),
+        });
+        self
+    }
+
+    /// Replace the authentication of recipes with `Bearer` authentication
+    pub fn bearer_auth(mut self, token: impl Into<String>) -> Self {
+        self.authentication = Some(AuthenticationBuilder::Bearer(token.into()));
+        self
+    }
+
+    pub fn color(mut self, color: ProfileColor) -> Self {
+        self.color = Some(color);
+        self
+    }
+
+    pub fn label(mut self, label: impl Into<String>) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+
+    /// Add a default header for every request sent with this profile.
+    /// Setting the same header again replaces it
+    pub fn header(
+        mut self,
+        header: impl Into<String>,
+        value: impl Into<String>,
+    ) -> Self {
+        self.headers.push((header.into(), value.into()));
+        self
+    }
+
+    /// Add a data field. Setting the same field again replaces it
+    pub fn field(
+        mut self,
+        field: impl Into<String>,
+        value: impl Into<String>,
+    ) -> Self {
+        self.data.push((field.into(), value.into()));
+        self
+    }
+
+    fn build(self) -> Result<Profile, CollectionBuildError> {
+        let location = format!("profile `{}`", self.id);
+        Ok(Profile {
+            base_url: self
+                .base_url
+                .map(|base_url| parse(base_url, &location, "base_url"))
+                .transpose()?,
+            authentication: self
+                .authentication
+                .map(|authentication| authentication.build(&location))
+                .transpose()?,
+            headers: self
+                .headers
+                .into_iter()
+                .map(|(header, value)| {
+                    let template =
+                        parse(value, &location, &format!("header `{header}`"))?;
+                    Ok((header, template))
+                })
+                .collect::<Result<_, _>>()?,
+            data: self
+                .data
+                .into_iter()
+                .map(|(field, value)| {
+                    let template = parse(value, &location, &field)?;
+                    Ok((field, template))
+                })
+                .collect::<Result<_, _>>()?,
+            id: self.id.into(),
+            name: self.name,
+            color: self.color,
+            label: self.label,
+        })
+    }
+}
+
+impl ChainBuilder {
+    pub fn new(id: impl Into<String>, source: ChainSource) -> Self {
+        Self {
+            id: id.into(),
+            source,
+            sensitive: false,
+            selector: None,
+            content_type: None,
+            selector_mode: SelectorMode::default(),
+            selector_fallback: None,
+        }
+    }
+
+    /// Shortcut for a chain that takes the body of a recipe's most recent
+    /// response
+    pub fn request(
+        id: impl Into<String>,
+        recipe_id: impl Into<String>,
+    ) -> Self {
+        Self::new(
+            id,
+            ChainSource::Request {
+                recipe: recipe_id.into().into(),
+                trigger: Default::default(),
+                section: Default::default(),
+                timeout: None,
+                max_body_size: None,
+            },
+        )
+    }
+
+    /// Mask the chained value in the UI
+    pub fn sensitive(mut self, sensitive: bool) -> Self {
+        self.sensitive = sensitive;
+        self
+    }
+
+    /// Query to extract a value from the source
+    pub fn selector(mut self, selector: impl Into<String>) -> Self {
+        self.selector = Some(selector.into());
+        self
+    }
+
+    pub fn content_type(mut self, content_type: ContentType) -> Self {
+        self.content_type = Some(content_type);
+        self
+    }
+
+    /// What to do if the selector matches more than one value
+    pub fn selector_mode(mut self, selector_mode: SelectorMode) -> Self {
+        self.selector_mode = selector_mode;
+        self
+    }
+
+    /// Which match to use if the user doesn't pick one
+    pub fn selector_fallback(mut self, fallback: SelectorFallback) -> Self {
+        self.selector_fallback = Some(fallback);
+        self
+    }
+
+    fn build(self) -> Result<Chain, CollectionBuildError> {
+        let selector = self
+            .selector
+            .map(|selector| {
+                selector.parse::<Query>().map_err(|error| {
+                    CollectionBuildError::Selector {
+                        chain_id: self.id.clone().into(),
+                        error,
+                    }
+                })
+            })
+            .transpose()?;
+        Ok(Chain {
+            id: self.id.into(),
+            source: self.source,
+            sensitive: self.sensitive,
+            selector,
+            content_type: self.content_type,
+            selector_mode: self.selector_mode,
+            selector_fallback: self.selector_fallback,
+        })
+    }
+}
+
+impl RecipeBuilder {
+    pub fn new(
+        id: impl Into<String>,
+        method: Method,
+        url: impl Into<String>,
+    ) -> Self {
+        Self {
+            id: id.into(),
+            name: None,
+            label: None,
+            method,
+            url: url.into(),
+            body: None,
+            body_format: BodyFormat::default(),
+            authentication: None,
+            query: Vec::new(),
+            headers: Vec::new(),
+            cookies: Vec::new(),
+            expects: None,
+            persist: true,
+            confirm: None,
+            disabled: false,
+        }
+    }
+
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Template to name each request sent from the recipe
+    pub fn label(mut self, label: impl Into<String>) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+
+    pub fn body(mut self, body: impl Into<String>) -> Self {
+        self.body = Some(body.into());
+        self
+    }
+
+    pub fn body_format(mut self, body_format: BodyFormat) -> Self {
+        self.body_format = body_format;
+        self
+    }
+
+    /// Authenticate with `Basic` authentication
+    pub fn basic_auth(
+        mut self,
+        username: impl Into<String>,
+        password: Option<impl Into<String>>,
+    ) -> Self {
+        self.authentication = Some(AuthenticationBuilder::Basic {
+            username: username.into(),
+            password: password.map(Into::into),
+        });
+        self
+    }
+
+    /// Authenticate with `Bearer` authentication
+    pub fn bearer_auth(mut self, token: impl Into<String>) -> Self {
+        self.authentication = Some(AuthenticationBuilder::Bearer(token.into()));
+        self
+    }
+
+    /// Add a query parameter. Setting the same parameter again replaces it
+    pub fn query(
+        mut self,
+        param: impl Into<String>,
+        value: impl Into<String>,
+    ) -> Self {
+        self.query.push((param.into(), value.into()));
+        self
+    }
+
+    /// Add a header. Setting the same header again replaces it
+    pub fn header(
+        mut self,
+        header: impl Into<String>,
+        value: impl Into<String>,
+    ) -> Self {
+        self.headers.push((header.into(), value.into()));
+        self
+    }
+
+    /// Add a cookie. Setting the same cookie again replaces it
+    pub fn cookie(
+        mut self,
+        name: impl Into<String>,
+        value: impl Into<String>,
+    ) -> Self {
+        self.cookies.push((name.into(), value.into()));
+        self
+    }
+
+    /// Content type the response should have
+    pub fn expects(mut self, content_type: ContentType) -> Self {
+        self.expects = Some(content_type);
+        self
+    }
+
+    /// Should requests for this recipe be saved in history? Defaults to true
+    pub fn persist(mut self, persist: bool) -> Self {
+        self.persist = persist;
+        self
+    }
+
+    /// Ask for confirmation before sending from the TUI
+    pub fn confirm(mut self, confirm: bool) -> Self {
+        self.confirm = Some(confirm);
+        self
+    }
+
+    /// Skip this recipe when sending a whole folder
+    pub fn disabled(mut self, disabled: bool) -> Self {
+        self.disabled = disabled;
+        self
+    }
+
+    fn build(self) -> Result<Recipe, CollectionBuildError> {
+        let location = format!("recipe `{}`", self.id);
+        let map = |pairs: Vec<(String, String)>, kind: &str| {
+            pairs
+                .into_iter()
+                .map(|(key, value)| {
+                    let template =
+                        parse(value, &location, &format!("{kind} `{key}`"))?;
+                    Ok((key, template))
+                })
+                .collect::<Result<IndexMap<_, _>, _>>()
+        };
+        Ok(Recipe {
+            label: self
+                .label
+                .map(|label| parse(label, &location, "label"))
+                .transpose()?,
+            method: parse(self.method.to_string(), &location, "method")?,
+            url: parse(self.url, &location, "url")?,
+            body: self
+                .body
+                .map(|body| parse(body, &location, "body"))
+                .transpose()?,
+            body_format: self.body_format,
+            body_command: None,
+            multipart: None,
+            authentication: self
+                .authentication
+                .map(|authentication| authentication.build(&location))
+                .transpose()?,
+            query: map(self.query, "query parameter")?,
+            headers: map(self.headers, "header")?,
+            cookies: map(self.cookies, "cookie")?,
+            capture: IndexMap::new(),
+            poll: None,
+            pagination: None,
+            schema: None,
+            expects: self.expects,
+            replay: Default::default(),
+            expect_continue: Default::default(),
+            tls: Default::default(),
+            assert: Default::default(),
+            persist: self.persist,
+            confirm: self.confirm,
+            disabled: self.disabled,
+            id: self.id.into(),
+            name: self.name,
+        })
+    }
+}
+
+impl FolderBuilder {
+    pub fn new(id: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            name: None,
+            children: Vec::new(),
+            disabled: false,
+        }
+    }
+
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    pub fn recipe(mut self, recipe: RecipeBuilder) -> Self {
+        self.children.push(NodeBuilder::Recipe(Box::new(recipe)));
+        self
+    }
+
+    pub fn folder(mut self, folder: FolderBuilder) -> Self {
+        self.children.push(NodeBuilder::Folder(folder));
+        self
+    }
+
+    /// Skip everything in this folder when sending a parent folder
+    pub fn disabled(mut self, disabled: bool) -> Self {
+        self.disabled = disabled;
+        self
+    }
+
+    fn build(self) -> Result<Folder, CollectionBuildError> {
+        Ok(Folder {
+            id: self.id.into(),
+            name: self.name,
+            children: build_nodes(self.children)?,
+            disabled: self.disabled,
+        })
+    }
+}
+
+impl AuthenticationBuilder {
+    fn build(
+        self,
+        location: &str,
+    ) -> Result<Authentication, CollectionBuildError> {
+        match self {
+            Self::Basic { username, password } => Ok(Authentication::Basic {
+                username: parse(username, location, "username")?,
+                password: password
+                    .map(|password| parse(password, location, "password"))
+                    .transpose()?,
+            }),
+            Self::Bearer(token) => {
+                Ok(Authentication::Bearer(parse(token, location, "token")?))
+            }
+        }
+    }
+}
+
+/// Build a level of the recipe tree. Duplicate IDs are caught afterward, by
+/// [RecipeTree::new], which checks the entire tree
+fn build_nodes(
+    nodes: Vec<NodeBuilder>,
+) -> Result<IndexMap<RecipeId, RecipeNode>, CollectionBuildError> {
+    let mut map = IndexMap::new();
+    for node in nodes {
+        let node = match node {
+            NodeBuilder::Recipe(recipe) => RecipeNode::Recipe(recipe.build()?),
+            NodeBuilder::Folder(folder) => RecipeNode::Folder(folder.build()?),
+        };
+        let id = node.id().clone();
+        if map.insert(id.clone(), node).is_some() {
+            return Err(duplicate("recipe/folder", id.to_string()));
+        }
+    }
+    Ok(map)
+}
+
+/// Check that every reference in the collection resolves
+fn validate(collection: &Collection) -> Result<(), CollectionBuildError> {
+    if let Some(profile_id) = &collection.default_profile {
+        if !collection.profiles.contains_key(profile_id) {
+            return Err(CollectionBuildError::UnknownProfile {
+                profile_id: profile_id.clone(),
+            });
+        }
+    }
+
+    let recipe_ids: HashSet<&RecipeId> =
+        collection.recipes.recipe_ids().collect();
+    for chain in collection.chains.values() {
+        if let ChainSource::Request { recipe, .. } = &chain.source {
+            if !recipe_ids.contains(recipe) {
+                return Err(CollectionBuildError::UnknownRecipe {
+                    chain_id: chain.id.clone(),
+                    recipe_id: recipe.clone(),
+                });
+            }
+        }
+    }
+
+    // Every chain and constant used in a template must exist
+    let check_references = |location: String, templates: Vec<&Template>| {
+        templates
+            .into_iter()
+            .flat_map(Template::references)
+            .find_map(|reference| match reference {
+                TemplateReference::Chain(chain_id)
+                    if !collection.chains.contains_key(&chain_id) =>
+                {
+                    Some(CollectionBuildError::UnknownChain {
+                        location: location.clone(),
+                        chain_id,
+                    })
+                }
+                TemplateReference::Constant(name)
+                    if !collection.constants.contains_key(&name) =>
+                {
+                    Some(CollectionBuildError::UnknownConstant {
+                        location: location.clone(),
+                        name,
+                    })
+                }
+                _ => None,
+            })
+            .map_or(Ok(()), Err)
+    };
+    for (name, template) in &collection.constants {
+        check_references(format!("constant `{name}`"), vec![template])?;
+    }
+    for profile in collection.profiles.values() {
+        let mut templates = Vec::from_iter(&profile.base_url);
+        templates.extend(profile.headers.values());
+        templates.extend(profile.data.values());
+        if let Some(authentication) = &profile.authentication {
+            templates.extend(authentication_templates(authentication));
+        }
+        check_references(format!("profile `{}`", profile.id), templates)?;
+    }
+    for chain in collection.chains.values() {
+        check_references(
+            format!("chain `{}`", chain.id),
+            chain_templates(chain),
+        )?;
+    }
+    for (_, node) in collection.recipes.iter() {
+        if let RecipeNode::Recipe(recipe) = node {
+            check_references(
+                format!("recipe `{}`", recipe.id),
+                recipe_templates(recipe),
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Parse a template, attaching its location to any error
+fn parse(
+    template: String,
+    location: &str,
+    field: &str,
+) -> Result<Template, CollectionBuildError> {
+    Template::parse(template).map_err(|error| CollectionBuildError::Template {
+        location: format!("{location} {field}"),
+        error,
+    })
+}
+
+fn duplicate(kind: &'static str, id: String) -> CollectionBuildError {
+    CollectionBuildError::DuplicateId { kind, id }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::assert_err;
+    use rstest::rstest;
+
+    /// The builder produces the same collection as the equivalent YAML
+    #[test]
+    fn test_build() {
+        let built = Collection::builder()
+            .profile(
+                ProfileBuilder::new("dev")
+                    .name("Development")
+                    .base_url("http://localhost")
+                    .field("user", "{{chains.user}}"),
+            )
+            .default_profile("dev")
+            .constant("version", "v2")
+            .chain(ChainBuilder::request("user", "login").selector("$.user"))
+            .recipe(
+                RecipeBuilder::new(
+                    "login",
+                    Method::Post,
+                    "/{{const.version}}/login",
+                )
+                .body(r#"{"user": "fish"}"#)
+                .header("Content-Type", "application/json"),
+            )
+            .folder(
+                FolderBuilder::new("users").recipe(
+                    RecipeBuilder::new("get_user", Method::Get, "/users")
+                        .query("user", "{{user}}")
+                        .bearer_auth("token"),
+                ),
+            )
+            .build()
+            .unwrap();
+
+        let expected: Collection = serde_yaml::from_str(
+            r#"
+default_profile: dev
+profiles:
+  dev:
+    name: Development
+    base_url: http://localhost
+    data:
+      user: "{{chains.user}}"
+constants:
+  version: v2
+chains:
+  user:
+    source: !request
+      recipe: login
+    selector: $.user
+requests:
+  login: !request
+    method: POST
+    url: /{{const.version}}/login
+    body: '{"user": "fish"}'
+    headers:
+      Content-Type: application/json
+  users: !folder
+    requests:
+      get_user: !request
+        method: GET
+        url: /users
+        authentication: !bearer token
+        query:
+          user: "{{user}}"
+"#,
+        )
+        .unwrap();
+        assert_eq!(built, expected);
+    }
+
+    #[rstest]
+    #[case::duplicate_profile(
+        Collection::builder()
+            .profile(ProfileBuilder::new("dev"))
+            .profile(ProfileBuilder::new("dev")),
+        "Duplicate profile ID `dev`"
+    )]
+    #[case::duplicate_constant(
+        Collection::builder().constant("v", "1").constant("v", "2"),
+        "Duplicate constant ID `v`"
+    )]
+    #[case::duplicate_chain(
+        Collection::builder()
+            .recipe(RecipeBuilder::new("r", Method::Get, "/"))
+            .chain(ChainBuilder::request("c", "r"))
+            .chain(ChainBuilder::request("c", "r")),
+        "Duplicate chain ID `c`"
+    )]
+    #[case::duplicate_recipe(
+        Collection::builder()
+            .recipe(RecipeBuilder::new("r", Method::Get, "/"))
+            .folder(
+                FolderBuilder::new("f")
+                    .recipe(RecipeBuilder::new("r", Method::Get, "/"))
+            ),
+        "Duplicate recipe/folder ID `r`"
+    )]
+    #[case::template(
+        Collection::builder()
+            .recipe(RecipeBuilder::new("r", Method::Get, "/").header("h", "{{")),
+        "Invalid template in recipe `r` header `h`"
+    )]
+    #[case::selector(
+        Collection::builder()
+            .recipe(RecipeBuilder::new("r", Method::Get, "/"))
+            .chain(ChainBuilder::request("c", "r").selector("$.")),
+        "Invalid selector for chain `c`"
+    )]
+    #[case::unknown_profile(
+        Collection::builder().default_profile("dev"),
+        "Default profile `dev` is not in the collection"
+    )]
+    #[case::unknown_recipe(
+        Collection::builder().chain(ChainBuilder::request("c", "r")),
+        "Chain `c` loads from recipe `r`, which is not in the collection"
+    )]
+    #[case::unknown_chain(
+        Collection::builder()
+            .recipe(RecipeBuilder::new("r", Method::Get, "/{{chains.c}}")),
+        "Template in recipe `r` references chain `c`, which is not in the \
+        collection"
+    )]
+    #[case::unknown_constant(
+        Collection::builder().constant("path", "/{{const.version}}"),
+        "Template in constant `path` references constant `version`, which is \
+        not in the collection"
+    )]
+    fn test_build_error(
+        #[case] builder: CollectionBuilder,
+        #[case] expected: &str,
+    ) {
+        assert_err!(builder.build(), expected);
+    }
+}
@@ -2,7 +2,8 @@
 
 use crate::{
     collection::{
-        recipe_tree::RecipeNode, Chain, ChainId, Profile, ProfileId, RecipeId,
+        recipe_tree::RecipeNode, Chain, ChainId, Method, Profile, ProfileId,
+        RecipeId,
     },
     template::Template,
 };
@@ -68,6 +69,21 @@ where
     Ok(map)
 }
 
+/// Deserialize a recipe's method template. If the template is static (i.e. has
+/// no keys), check that it's a valid method now, rather than waiting until the
+/// request is built.
+pub fn deserialize_method<'de, D>(deserializer: D) -> Result<Template, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let template = Template::deserialize(deserializer)?;
+    if template.is_static() {
+        Method::try_from(template.as_str().to_owned())
+            .map_err(D::Error::custom)?;
+    }
+    Ok(template)
+}
+
 // Custom deserializer for `Template`. This is useful for deserializing values
 // that are not strings, but should be treated as strings such as numbers,
 // booleans, and nulls.
@@ -179,6 +195,37 @@ pub mod serde_duration {
         }
     }
 
+    /// Same format, for optional durations. Use with `#[serde(default)]` so
+    /// the field can be omitted
+    pub mod option {
+        use serde::{Deserialize, Deserializer, Serialize, Serializer};
+        use std::time::Duration;
+
+        #[derive(Serialize, Deserialize)]
+        #[serde(transparent)]
+        struct Wrap(#[serde(with = "super")] Duration);
+
+        pub fn serialize<S>(
+            duration: &Option<Duration>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            duration.map(Wrap).serialize(serializer)
+        }
+
+        pub fn deserialize<'de, D>(
+            deserializer: D,
+        ) -> Result<Option<Duration>, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            let duration = Option::<Wrap>::deserialize(deserializer)?;
+            Ok(duration.map(|Wrap(duration)| duration))
+        }
+    }
+
     #[cfg(test)]
     mod tests {
         use super::*;
@@ -244,12 +291,25 @@ pub mod serde_duration {
         ) {
             assert_de_tokens_error::<Wrap>(&[Token::Str(s)], error)
         }
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        #[serde(transparent)]
+        struct WrapOption(#[serde(with = "super::option")] Option<Duration>);
+
+        #[test]
+        fn test_option() {
+            let some = WrapOption(Some(Duration::from_secs(120)));
+            assert_de_tokens(&some, &[Token::Some, Token::Str("2m")]);
+            assert_ser_tokens(&some, &[Token::Some, Token::String("120s")]);
+            assert_de_tokens(&WrapOption(None), &[Token::None]);
+            assert_ser_tokens(&WrapOption(None), &[Token::None]);
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::template::Template;
+    use crate::{collection::Recipe, template::Template, util::assert_err};
     use rstest::rstest;
     use serde_test::{assert_de_tokens, Token};
 
@@ -270,4 +330,25 @@ mod tests {
     fn test_deserialize_template(#[case] token: Token, #[case] expected: &str) {
         assert_de_tokens(&Template::from(expected), &[token]);
     }
+
+    /// Static methods are validated immediately, templated ones are deferred
+    #[rstest]
+    #[case::valid("GET", None)]
+    #[case::lowercase("post", None)]
+    #[case::templated("\"{{method}}\"", None)]
+    #[case::invalid("GOT", Some("Invalid HTTP method `GOT`"))]
+    fn test_deserialize_method(
+        #[case] method: &str,
+        #[case] expected_error: Option<&str>,
+    ) {
+        let yaml = format!("method: {method}\nurl: http://localhost");
+        let result = serde_yaml::from_str::<Recipe>(&yaml);
+        match expected_error {
+            None => assert_eq!(
+                result.unwrap().method,
+                Template::from(method.trim_matches('"'))
+            ),
+            Some(expected_error) => assert_err!(result, expected_error),
+        }
+    }
 }
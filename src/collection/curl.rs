@@ -0,0 +1,619 @@
+//! Import recipes from curl commands, e.g. a shell script full of them. Only
+//! the options that affect the request itself are supported; options that
+//! only change curl's own behavior (e.g. `--silent`) are ignored.
+
+use crate::{
+    collection::{
+        Authentication, Collection, Folder, Method, Recipe, RecipeId,
+        RecipeNode, RecipeTree,
+    },
+    template::Template,
+};
+use anyhow::{anyhow, bail, Context};
+use indexmap::IndexMap;
+use reqwest::header;
+use std::{fs, path::Path};
+use tracing::{info, warn};
+use url::{form_urlencoded, Url};
+
+/// Short options that take no value
+const SHORT_FLAGS: &str = "fgGiIkLNsSv";
+/// Long options that take no value, and don't affect the request
+const IGNORED_FLAGS: &[&str] = &[
+    "compressed",
+    "fail",
+    "globoff",
+    "include",
+    "insecure",
+    "location",
+    "no-buffer",
+    "show-error",
+    "silent",
+    "verbose",
+];
+/// Long options that take a value, but don't affect the request
+const IGNORED_OPTIONS: &[&str] = &[
+    "connect-timeout",
+    "max-time",
+    "output",
+    "retry",
+    "write-out",
+];
+
+impl Collection {
+    /// Convert a file of curl commands into a collection, with one recipe per
+    /// command. Blank lines and comments are skipped, and commands can span
+    /// lines with `\` or quotes. Commands that can't be converted don't fail
+    /// the import; they're skipped and returned as errors, so the caller can
+    /// report them. If a folder name is given, all recipes are put in one
+    /// folder.
+    ///
+    /// This is not async because it's only called by the CLI, where we don't
+    /// care about blocking. It keeps the code simpler.
+    pub fn from_curl(
+        curl_file: impl AsRef<Path>,
+        folder: Option<String>,
+    ) -> anyhow::Result<(Self, Vec<anyhow::Error>)> {
+        let curl_file = curl_file.as_ref();
+        info!(file = ?curl_file, "Loading curl commands");
+        warn!(
+            "The curl importer is approximate. Only common options are \
+            supported, and anything that reads from a file is not."
+        );
+        let script = fs::read_to_string(curl_file).with_context(|| {
+            format!("Error reading curl file {curl_file:?}")
+        })?;
+        Ok(from_script(&script, folder))
+    }
+}
+
+/// Convert every command in a script into a collection. See
+/// [Collection::from_curl]
+fn from_script(
+    script: &str,
+    folder: Option<String>,
+) -> (Collection, Vec<anyhow::Error>) {
+    let mut recipes: IndexMap<RecipeId, RecipeNode> = IndexMap::new();
+    let mut errors = Vec::new();
+    for (line, command) in split_commands(script) {
+        let result = command.and_then(|words| {
+            // Blank lines and comments have no words
+            if words.is_empty() {
+                return Ok(None);
+            }
+            parse_command(&words).map(Some)
+        });
+        match result {
+            Ok(Some(mut recipe)) => {
+                recipe.id = unique_id(&recipes, &recipe.id);
+                recipes.insert(recipe.id.clone(), recipe.into());
+            }
+            Ok(None) => {}
+            Err(error) => errors
+                .push(error.context(format!(
+                    "Error importing command on line {line}"
+                ))),
+        }
+    }
+
+    let tree = match folder {
+        Some(name) => {
+            let id: RecipeId = slug(&name).into();
+            let folder = Folder {
+                id: unique_id(&recipes, &id),
+                name: Some(name),
+                children: recipes,
+                disabled: false,
+            };
+            IndexMap::from([(folder.id.clone(), folder.into())])
+        }
+        None => recipes,
+    };
+    let collection = Collection {
+        // IDs were deduplicated above, so this can't fail
+        recipes: RecipeTree::new(tree).expect("Recipe IDs are unique"),
+        ..Default::default()
+    };
+    (collection, errors)
+}
+
+/// Split a script into the words of each command, along with the line that
+/// each command starts on. Commands end at the first line break that isn't
+/// escaped or in quotes. Anything after a pipe or command separator is
+/// dropped, so `curl ... | jq` imports just the curl part.
+fn split_commands(script: &str) -> Vec<(usize, anyhow::Result<Vec<String>>)> {
+    let mut commands = Vec::new();
+    let mut buffer = String::new();
+    let mut start_line = 1;
+    for (i, line) in script.lines().enumerate() {
+        if buffer.is_empty() {
+            start_line = i + 1;
+        }
+        buffer.push_str(line);
+        buffer.push('\n');
+        // A trailing backslash continues onto the next line
+        if line.ends_with('\\') {
+            continue;
+        }
+        // If the line ends inside quotes, the command isn't done yet
+        if let Ok(words) = shell_words::split(&buffer) {
+            let words = words
+                .into_iter()
+                .take_while(|word| !["|", "||", "&&", ";"].contains(&&**word))
+                .collect();
+            commands.push((start_line, Ok(words)));
+            buffer.clear();
+        }
+    }
+    if !buffer.trim().is_empty() {
+        commands.push((start_line, Err(anyhow!("Unterminated quote"))));
+    }
+    commands
+}
+
+/// Convert the words of a single curl command into a recipe
+fn parse_command(words: &[String]) -> anyhow::Result<Recipe> {
+    let mut words = words.iter().map(String::as_str);
+    // Allow commands copied along with a shell prompt
+    let mut program = words.next().unwrap_or_default();
+    if program == "$" {
+        program = words.next().unwrap_or_default();
+    }
+    if program != "curl" {
+        bail!("Not a curl command: `{program}`");
+    }
+
+    let mut url = None;
+    let mut method = None;
+    let mut headers: IndexMap<String, String> = IndexMap::new();
+    let mut data: Vec<String> = Vec::new();
+    // Path from `--data @file`. This becomes an `@file` body
+    let mut data_file: Option<String> = None;
+    let mut json = false;
+    let mut get = false;
+    let mut authentication = None;
+
+    while let Some(word) = words.next() {
+        let (option, mut value) = match word.strip_prefix("--") {
+            Some(long) => match long.split_once('=') {
+                Some((option, value)) => (option, Some(value)),
+                None => (long, None),
+            },
+            None => match word.strip_prefix('-') {
+                Some(short) if !short.is_empty() => {
+                    let (option, rest) = short.split_at(1);
+                    if SHORT_FLAGS.contains(option) {
+                        // A cluster of flags, e.g. `-sSL`
+                        if let Some(c) =
+                            rest.chars().find(|c| !SHORT_FLAGS.contains(*c))
+                        {
+                            bail!("Unsupported option `-{c}`");
+                        }
+                        if short.contains('G') {
+                            get = true;
+                        }
+                        if short.contains('I') {
+                            method = Some("HEAD".to_owned());
+                        }
+                        continue;
+                    }
+                    // The value can be attached, e.g. `-XPOST`
+                    (long_name(option)?, Some(rest).filter(|s| !s.is_empty()))
+                }
+                _ => {
+                    if url.replace(word.to_owned()).is_some() {
+                        bail!("Multiple URLs aren't supported");
+                    }
+                    continue;
+                }
+            },
+        };
+
+        if IGNORED_FLAGS.contains(&option) {
+            continue;
+        }
+        match option {
+            "get" => get = true,
+            "head" => method = Some("HEAD".to_owned()),
+            _ => {}
+        }
+        if ["get", "head"].contains(&option) {
+            continue;
+        }
+
+        let mut next_value = || {
+            value
+                .take()
+                .or_else(|| words.next())
+                .ok_or_else(|| anyhow!("Missing value for `--{option}`"))
+        };
+        match option {
+            _ if IGNORED_OPTIONS.contains(&option) => {
+                next_value()?;
+            }
+            "url" => {
+                if url.replace(next_value()?.to_owned()).is_some() {
+                    bail!("Multiple URLs aren't supported");
+                }
+            }
+            "request" => method = Some(next_value()?.to_uppercase()),
+            "header" => {
+                let header = next_value()?;
+                let (name, value) = header
+                    .split_once(':')
+                    .ok_or_else(|| anyhow!("Invalid header `{header}`"))?;
+                headers.insert(name.trim().to_owned(), value.trim().to_owned());
+            }
+            "user-agent" => {
+                headers.insert("User-Agent".into(), next_value()?.to_owned());
+            }
+            "referer" => {
+                headers.insert("Referer".into(), next_value()?.to_owned());
+            }
+            "cookie" => {
+                let cookie = next_value()?;
+                if !cookie.contains('=') {
+                    bail!("Reading cookies from a file isn't supported");
+                }
+                headers.insert("Cookie".into(), cookie.to_owned());
+            }
+            "user" => {
+                let user = next_value()?;
+                let (username, password) = match user.split_once(':') {
+                    Some((username, password)) => {
+                        (username, Some(Template::dangerous(password.into())))
+                    }
+                    None => (user, None),
+                };
+                authentication = Some(Authentication::Basic {
+                    username: Template::dangerous(username.into()),
+                    password,
+                });
+            }
+            "data" | "data-ascii" | "data-binary" | "data-raw" | "json" => {
+                let value = next_value()?;
+                json |= option == "json";
+                if let Some(path) =
+                    value.strip_prefix('@').filter(|_| option != "data-raw")
+                {
+                    if data_file.replace(path.to_owned()).is_some() {
+                        bail!(
+                            "Reading data from multiple files isn't supported"
+                        );
+                    }
+                    continue;
+                }
+                data.push(match option {
+                    // curl strips line breaks from plain data
+                    "data" | "data-ascii" => value.replace(['\r', '\n'], ""),
+                    _ => value.to_owned(),
+                });
+            }
+            "data-urlencode" => {
+                let value = next_value()?;
+                let encode = |s: &str| -> String {
+                    form_urlencoded::byte_serialize(s.as_bytes()).collect()
+                };
+                data.push(match value.split_once('=') {
+                    Some((name, content)) => {
+                        format!("{name}={}", encode(content))
+                    }
+                    None => encode(value),
+                });
+            }
+            "form" => bail!("Multipart forms (`--form`) aren't supported"),
+            _ => bail!("Unsupported option `--{option}`"),
+        }
+    }
+
+    let url = url.ok_or_else(|| anyhow!("Command has no URL"))?;
+    if let Some(path) = &data_file {
+        if get || !data.is_empty() {
+            bail!(
+                "Combining data from a file (`@{path}`) with other data isn't \
+                supported"
+            );
+        }
+    }
+    let mut query = IndexMap::new();
+    let body = if let Some(path) = data_file {
+        if json {
+            set_json_headers(&mut headers);
+        }
+        Some(format!("@{path}"))
+    } else if get {
+        // With `--get`, data goes in the query instead of the body
+        for (param, value) in form_urlencoded::parse(data.join("&").as_bytes())
+        {
+            query.insert(param.into_owned(), Template::dangerous(value.into()));
+        }
+        None
+    } else if json {
+        set_json_headers(&mut headers);
+        Some(Recipe::escape_body(data.concat()))
+    } else if data.is_empty() {
+        None
+    } else {
+        Some(Recipe::escape_body(data.join("&")))
+    };
+    // Same default as curl
+    let method = method.unwrap_or_else(|| {
+        if body.is_some() { "POST" } else { "GET" }.to_owned()
+    });
+    Method::try_from(method.clone())?;
+
+    Ok(Recipe {
+        id: recipe_id(&method, &url).into(),
+        name: Some(format!("{method} {url}")),
+        label: None,
+        method: Template::dangerous(method),
+        url: Template::dangerous(url),
+        body: body.map(Template::dangerous),
+        body_format: Default::default(),
+        body_command: None,
+        multipart: None,
+        authentication,
+        query,
+        headers: headers
+            .into_iter()
+            .map(|(name, value)| (name, Template::dangerous(value)))
+            .collect(),
+        cookies: IndexMap::new(),
+        capture: IndexMap::new(),
+        poll: None,
+        pagination: None,
+        schema: None,
+        expects: None,
+        replay: Default::default(),
+        expect_continue: Default::default(),
+        tls: Default::default(),
+        assert: Default::default(),
+        persist: true,
+        confirm: None,
+        disabled: false,
+    })
+}
+
+/// Get the long name of a short option that takes a value
+fn long_name(short: &str) -> anyhow::Result<&'static str> {
+    Ok(match short {
+        "A" => "user-agent",
+        "b" => "cookie",
+        "d" => "data",
+        "e" => "referer",
+        "F" => "form",
+        "H" => "header",
+        "m" => "max-time",
+        "o" => "output",
+        "u" => "user",
+        "w" => "write-out",
+        "X" => "request",
+        _ => bail!("Unsupported option `-{short}`"),
+    })
+}
+
+/// Generate a recipe ID from the method and the last segment of the URL's
+/// path, e.g. `get_users`. Falls back to the host if there's no path.
+fn recipe_id(method: &str, url: &str) -> String {
+    // curl assumes HTTP if there's no scheme
+    let parsed =
+        Url::parse(url).or_else(|_| Url::parse(&format!("http://{url}")));
+    let name = parsed.ok().and_then(|url| {
+        url.path_segments()
+            .and_then(|segments| {
+                segments.filter(|segment| !segment.is_empty()).last()
+            })
+            .map(String::from)
+            .or_else(|| url.host_str().map(String::from))
+    });
+    match name {
+        Some(name) => format!("{}_{}", method.to_lowercase(), slug(&name)),
+        None => method.to_lowercase(),
+    }
+}
+
+/// Convert a string into something usable as an ID
+fn slug(s: &str) -> String {
+    s.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Add a numeric suffix to an ID, if it's already taken
+fn unique_id(
+    recipes: &IndexMap<RecipeId, RecipeNode>,
+    id: &RecipeId,
+) -> RecipeId {
+    if !recipes.contains_key(id) {
+        return id.clone();
+    }
+    (2..)
+        .map(|i| RecipeId::from(format!("{id}_{i}")))
+        .find(|id| !recipes.contains_key(id))
+        .unwrap()
+}
+
+/// `--json` sets these headers, unless the command already has them
+fn set_json_headers(headers: &mut IndexMap<String, String>) {
+    for header in [header::CONTENT_TYPE, header::ACCEPT] {
+        if !headers.keys().any(|name| header == name.as_str()) {
+            headers.insert(header.to_string(), "application/json".into());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::assert_err;
+    use indexmap::indexmap;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    /// Options are mapped onto the equivalent recipe fields
+    #[test]
+    fn test_parse_command() {
+        let words = shell_words::split(
+            r#"curl -sSL -XPUT 'https://fish.fish/fishes/3?a=1'
+            -H 'Content-Type: application/json' -A slumber
+            --user admin:hunter2 -d '{"name": "Barry"}'"#,
+        )
+        .unwrap();
+        let recipe = parse_command(&words).unwrap();
+        assert_eq!(recipe.id, RecipeId::from("put_3".to_owned()));
+        assert_eq!(recipe.method.as_str(), "PUT");
+        assert_eq!(recipe.url.as_str(), "https://fish.fish/fishes/3?a=1");
+        assert_eq!(
+            recipe.headers,
+            indexmap! {
+                "Content-Type".into() => Template::dangerous("application/json".into()),
+                "User-Agent".into() => Template::dangerous("slumber".into()),
+            }
+        );
+        assert_eq!(
+            recipe.authentication,
+            Some(Authentication::Basic {
+                username: Template::dangerous("admin".into()),
+                password: Some(Template::dangerous("hunter2".into())),
+            })
+        );
+        assert_eq!(
+            recipe.body,
+            Some(Template::dangerous(r#"{"name": "Barry"}"#.into()))
+        );
+    }
+
+    /// Method and body follow curl's rules
+    #[rstest]
+    #[case::get("curl fish.fish", "GET", None, &[])]
+    #[case::data_is_post("curl fish.fish -d a=1 --data b=2", "POST", Some("a=1&b=2"), &[])]
+    #[case::head("curl -I fish.fish", "HEAD", None, &[])]
+    #[case::get_with_data(
+        "curl -G fish.fish -d a=1 --data-urlencode 'b=c d'",
+        "GET",
+        None,
+        &[("a", "1"), ("b", "c d")],
+    )]
+    #[case::json(
+        "curl --url fish.fish --json '{}'",
+        "POST",
+        Some("{}"),
+        &[],
+    )]
+    #[case::data_raw_at("curl fish.fish --data-raw @me", "POST", Some("@@me"), &[])]
+    #[case::data_file("curl fish.fish -d @body.json", "POST", Some("@body.json"), &[])]
+    #[case::json_file(
+        "curl fish.fish --json @body.json",
+        "POST",
+        Some("@body.json"),
+        &[],
+    )]
+    fn test_parse_command_request(
+        #[case] command: &str,
+        #[case] expected_method: &str,
+        #[case] expected_body: Option<&str>,
+        #[case] expected_query: &[(&str, &str)],
+    ) {
+        let recipe =
+            parse_command(&shell_words::split(command).unwrap()).unwrap();
+        assert_eq!(recipe.method.as_str(), expected_method);
+        assert_eq!(recipe.body.as_ref().map(Template::as_str), expected_body);
+        assert_eq!(
+            recipe
+                .query
+                .iter()
+                .map(|(k, v)| (k.as_str(), v.as_str()))
+                .collect::<Vec<_>>(),
+            expected_query
+        );
+    }
+
+    #[rstest]
+    #[case::not_curl("wget fish.fish", "Not a curl command: `wget`")]
+    #[case::no_url("curl -s", "Command has no URL")]
+    #[case::unknown_option(
+        "curl fish.fish --proxy x",
+        "Unsupported option `--proxy`"
+    )]
+    #[case::unknown_short("curl -sZ fish.fish", "Unsupported option `-Z`")]
+    #[case::missing_value("curl fish.fish -H", "Missing value for `--header`")]
+    #[case::data_file_and_data(
+        "curl fish.fish -d @body.json -d a=1",
+        "Combining data from a file (`@body.json`) with other data"
+    )]
+    #[case::data_file_get(
+        "curl -G fish.fish -d @body.json",
+        "Combining data from a file"
+    )]
+    #[case::data_files(
+        "curl fish.fish -d @a.json --data-binary @b.json",
+        "multiple files"
+    )]
+    #[case::form("curl fish.fish -F a=b", "Multipart forms")]
+    #[case::bad_method("curl -X 'NOT A METHOD' fish.fish", "NOT A METHOD")]
+    fn test_parse_command_error(#[case] command: &str, #[case] expected: &str) {
+        assert_err!(
+            parse_command(&shell_words::split(command).unwrap()),
+            expected
+        );
+    }
+
+    /// Comments, blank lines, and multi-line commands are handled. Bad
+    /// commands are reported without stopping the import.
+    #[test]
+    fn test_from_script() {
+        let script = r#"#!/bin/sh
+# List fishes
+curl https://fish.fish/fishes | jq .
+
+curl -X POST https://fish.fish/fishes \
+  -H 'Content-Type: application/json' \
+  -d '{
+    "name": "Barry"
+  }'
+wget https://fish.fish/fishes
+curl https://fish.fish/fishes
+curl 'https://fish.fish
+"#;
+        let (collection, errors) = from_script(script, Some("My Fish".into()));
+
+        let ids = collection
+            .recipes
+            .recipe_ids()
+            .map(RecipeId::to_string)
+            .collect::<Vec<_>>();
+        assert_eq!(ids, ["get_fishes", "post_fishes", "get_fishes_2"]);
+        assert!(matches!(
+            collection.recipes.get(&"my_fish".into()),
+            Some(RecipeNode::Folder(_))
+        ));
+        let RecipeNode::Recipe(post) =
+            collection.recipes.get(&"post_fishes".into()).unwrap()
+        else {
+            panic!("Expected recipe")
+        };
+        assert_eq!(
+            post.body.as_ref().map(Template::as_str),
+            // curl strips line breaks from `-d`
+            Some("{    \"name\": \"Barry\"  }")
+        );
+
+        let errors = errors
+            .iter()
+            .map(|error| format!("{error:#}"))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            errors,
+            [
+                "Error importing command on line 10: Not a curl command: `wget`",
+                "Error importing command on line 12: Unterminated quote",
+            ]
+        );
+    }
+}
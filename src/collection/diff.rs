@@ -0,0 +1,210 @@
+//! Compare two versions of a collection, e.g. before and after a reload
+
+use crate::collection::{Collection, RecipeNode};
+use indexmap::IndexMap;
+use itertools::Itertools;
+use serde::Serialize;
+use std::{
+    fmt::{self, Display},
+    hash::Hash,
+};
+
+/// What changed between two versions of a collection, by ID. See
+/// [Collection::diff]
+#[derive(Debug, Default, PartialEq)]
+pub struct CollectionDiff {
+    pub profiles: ItemDiff,
+    pub chains: ItemDiff,
+    pub recipes: ItemDiff,
+}
+
+/// IDs of added, removed, and changed items of one type. Each list is in the
+/// order the items are defined (new collection for added/changed, old
+/// collection for removed).
+#[derive(Debug, Default, PartialEq)]
+pub struct ItemDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<String>,
+}
+
+impl Collection {
+    /// Compare this collection to a newer version of it. Items are matched by
+    /// ID, so a renamed ID shows up as one removed and one added. Folders
+    /// aren't compared themselves, but moving a recipe between folders
+    /// doesn't count as a change.
+    pub fn diff(&self, new: &Self) -> CollectionDiff {
+        fn recipes(
+            collection: &Collection,
+        ) -> impl Iterator<Item = (String, &RecipeNode)> {
+            collection
+                .recipes
+                .iter()
+                .filter(|(_, node)| node.recipe().is_some())
+                .map(|(_, node)| (node.id().to_string(), node))
+        }
+
+        CollectionDiff {
+            profiles: ItemDiff::new(
+                self.profiles.iter().map(|(id, p)| (id.to_string(), p)),
+                new.profiles.iter().map(|(id, p)| (id.to_string(), p)),
+            ),
+            chains: ItemDiff::new(
+                self.chains.iter().map(|(id, c)| (id.to_string(), c)),
+                new.chains.iter().map(|(id, c)| (id.to_string(), c)),
+            ),
+            recipes: ItemDiff::new(recipes(self), recipes(new)),
+        }
+    }
+}
+
+impl CollectionDiff {
+    pub fn is_empty(&self) -> bool {
+        [&self.profiles, &self.chains, &self.recipes]
+            .iter()
+            .all(|diff| diff.is_empty())
+    }
+}
+
+impl ItemDiff {
+    fn new<'a, K, V>(
+        old: impl IntoIterator<Item = (K, &'a V)>,
+        new: impl IntoIterator<Item = (K, &'a V)>,
+    ) -> Self
+    where
+        K: Display + Eq + Hash,
+        V: 'a + Serialize,
+    {
+        let old: IndexMap<K, &V> = old.into_iter().collect();
+        let new: IndexMap<K, &V> = new.into_iter().collect();
+        let mut diff = Self::default();
+        for (id, value) in &new {
+            match old.get(id) {
+                None => diff.added.push(id.to_string()),
+                Some(old_value) if !same(*old_value, *value) => {
+                    diff.changed.push(id.to_string())
+                }
+                Some(_) => {}
+            }
+        }
+        diff.removed = old
+            .keys()
+            .filter(|id| !new.contains_key(*id))
+            .map(K::to_string)
+            .collect();
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.changed.is_empty()
+    }
+}
+
+/// Compare two items by their serialized form. Collection types only derive
+/// `PartialEq` for tests, and serialization covers every field the user can
+/// write, which is exactly what a reload could change. If serialization
+/// fails, assume the item changed.
+fn same<T: Serialize>(a: &T, b: &T) -> bool {
+    match (serde_json::to_value(a), serde_json::to_value(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// Summarize the changes in a single line, e.g.
+/// `1 recipe added (login), 2 recipes changed (a, b)`
+impl Display for CollectionDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, "no changes");
+        }
+        let parts = [
+            ("profile", &self.profiles),
+            ("chain", &self.chains),
+            ("recipe", &self.recipes),
+        ]
+        .into_iter()
+        .flat_map(|(kind, diff)| {
+            [
+                ("added", &diff.added),
+                ("removed", &diff.removed),
+                ("changed", &diff.changed),
+            ]
+            .into_iter()
+            .filter(|(_, ids)| !ids.is_empty())
+            .map(move |(verb, ids)| {
+                format!(
+                    "{} {kind}{} {verb} ({})",
+                    ids.len(),
+                    if ids.len() == 1 { "" } else { "s" },
+                    ids.join(", ")
+                )
+            })
+        });
+        write!(f, "{}", parts.format(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+    use factori::create;
+    use indexmap::indexmap;
+
+    #[test]
+    fn test_diff() {
+        let profile = create!(Profile);
+        let old = create!(
+            Collection,
+            profiles: indexmap! {profile.id.clone() => profile.clone()},
+            chains: indexmap! {"old".into() => create!(Chain, id: "old".into())},
+            recipes: indexmap! {
+                "kept".into() => create!(Recipe, id: "kept".into()),
+                "edited".into() => create!(Recipe, id: "edited".into()),
+                "deleted".into() => create!(Recipe, id: "deleted".into()),
+            }
+            .into(),
+        );
+        let new = create!(
+            Collection,
+            profiles: indexmap! {profile.id.clone() => profile},
+            recipes: indexmap! {
+                "kept".into() => create!(Recipe, id: "kept".into()),
+                "edited".into() => create!(
+                    Recipe,
+                    id: "edited".into(),
+                    url: "http://localhost/edited".into(),
+                ),
+                "new1".into() => create!(Recipe, id: "new1".into()),
+                "new2".into() => create!(Recipe, id: "new2".into()),
+            }
+            .into(),
+        );
+
+        let diff = old.diff(&new);
+        assert_eq!(
+            diff,
+            CollectionDiff {
+                profiles: ItemDiff::default(),
+                chains: ItemDiff {
+                    removed: vec!["old".into()],
+                    ..Default::default()
+                },
+                recipes: ItemDiff {
+                    added: vec!["new1".into(), "new2".into()],
+                    removed: vec!["deleted".into()],
+                    changed: vec!["edited".into()],
+                },
+            }
+        );
+        assert_eq!(
+            diff.to_string(),
+            "1 chain removed (old), 2 recipes added (new1, new2), \
+            1 recipe removed (deleted), 1 recipe changed (edited)"
+        );
+        assert_eq!(new.diff(&new).to_string(), "no changes");
+    }
+}
@@ -0,0 +1,169 @@
+use crate::{
+    collection::{ChainId, ProfileId, RecipeId},
+    http::QueryParseError,
+    template::TemplateParseError,
+    util::YamlError,
+};
+use std::{io, path::PathBuf};
+use thiserror::Error;
+use tokio::task::JoinError;
+
+/// Any error that can occur while loading a collection from disk. Paths are
+/// included so the user knows which file to fix, which is especially helpful
+/// for collections split across a directory.
+#[derive(Debug, Error)]
+pub enum CollectionLoadError {
+    /// Couldn't read a collection file or directory
+    #[error("Error reading {path:?}")]
+    Io {
+        path: PathBuf,
+        #[source]
+        error: io::Error,
+    },
+
+    /// File isn't valid YAML, or doesn't match the collection format
+    #[error("Error loading data from {path:?}")]
+    Parse {
+        path: PathBuf,
+        #[source]
+        error: YamlError,
+    },
+
+    /// Couldn't read a collection from stdin
+    #[error("Error reading collection from stdin")]
+    Stdin(#[source] io::Error),
+
+    /// A collection from stdin can only be read once
+    #[error("Collection was read from stdin, and can't be reloaded")]
+    StdinReload,
+
+    /// Couldn't fetch a collection from a URL
+    #[error("Error fetching collection from {url}")]
+    Fetch {
+        url: String,
+        #[source]
+        error: reqwest::Error,
+    },
+
+    /// Directory has no YAML files, other than local overlays
+    #[error("Directory {path:?} contains no collection files")]
+    EmptyDirectory { path: PathBuf },
+
+    /// Files in a directory collection conflict with each other
+    #[error("Error merging collection files in {path:?}")]
+    Merge {
+        path: PathBuf,
+        #[source]
+        error: Box<MergeError>,
+    },
+
+    /// A local overlay doesn't fit the collection it's applied to
+    #[error("Error applying local overlay {path:?}")]
+    Local {
+        path: PathBuf,
+        #[source]
+        error: LocalOverlayError,
+    },
+
+    /// The background task doing the loading panicked or was cancelled
+    #[error("Error loading collection")]
+    Task(#[from] JoinError),
+}
+
+/// A conflict between two files merged into one collection. See
+/// [Collection::merge](crate::collection::Collection::merge)
+#[derive(Debug, Error)]
+pub enum MergeError {
+    /// Files have different values for `default_profile`
+    #[error(
+        "Conflicting `default_profile` in {first_path:?} (`{first}`) and \
+        {second_path:?} (`{second}`)"
+    )]
+    DefaultProfile {
+        first: ProfileId,
+        first_path: PathBuf,
+        second: ProfileId,
+        second_path: PathBuf,
+    },
+
+    /// Profile, chain, or recipe ID defined in more than one file
+    #[error(
+        "Duplicate {kind} ID `{id}` in {first_path:?} and {second_path:?}"
+    )]
+    DuplicateId {
+        /// Type of the duplicated item, e.g. `profile`
+        kind: &'static str,
+        id: String,
+        first_path: PathBuf,
+        second_path: PathBuf,
+    },
+}
+
+/// An error applying a local overlay to a collection. See
+/// [Collection::apply_local](crate::collection::Collection::apply_local)
+#[derive(Debug, Error)]
+pub enum LocalOverlayError {
+    /// Overlay references a profile that the collection doesn't define
+    #[error("Profile `{profile_id}` is not in the collection")]
+    ProfileUnknown { profile_id: ProfileId },
+}
+
+/// An invalid collection built in code. See
+/// [CollectionBuilder](crate::collection::CollectionBuilder)
+#[derive(Debug, Error)]
+pub enum CollectionBuildError {
+    /// Two profiles, constants, chains, or recipes/folders have the same ID
+    #[error("Duplicate {kind} ID `{id}`")]
+    DuplicateId {
+        /// Type of the duplicated item, e.g. `profile`
+        kind: &'static str,
+        id: String,
+    },
+
+    #[error("Invalid template in {location}")]
+    Template {
+        /// Where the template is, e.g. ``recipe `login` url``
+        location: String,
+        #[source]
+        error: TemplateParseError,
+    },
+
+    #[error("Invalid selector for chain `{chain_id}`")]
+    Selector {
+        chain_id: ChainId,
+        #[source]
+        error: QueryParseError,
+    },
+
+    #[error("Default profile `{profile_id}` is not in the collection")]
+    UnknownProfile { profile_id: ProfileId },
+
+    #[error(
+        "Chain `{chain_id}` loads from recipe `{recipe_id}`, which is not in \
+        the collection"
+    )]
+    UnknownRecipe {
+        chain_id: ChainId,
+        recipe_id: RecipeId,
+    },
+
+    #[error(
+        "Template in {location} references chain `{chain_id}`, which is not \
+        in the collection"
+    )]
+    UnknownChain {
+        /// What has the template, e.g. ``recipe `login` ``
+        location: String,
+        chain_id: ChainId,
+    },
+
+    #[error(
+        "Template in {location} references constant `{name}`, which is not \
+        in the collection"
+    )]
+    UnknownConstant {
+        /// What has the template, e.g. ``recipe `login` ``
+        location: String,
+        name: String,
+    },
+}
@@ -0,0 +1,456 @@
+//! Export parts of a collection into a standalone collection
+
+use crate::{
+    collection::{
+        Authentication, Chain, ChainId, ChainRequestSection, ChainSource,
+        Collection, Profile, ProfileId, Recipe, RecipeId, RecipeNode,
+        RecipeTree, ResponseSchema,
+    },
+    template::{Template, TemplateReference},
+};
+use anyhow::anyhow;
+use indexmap::{IndexMap, IndexSet};
+use itertools::Itertools;
+use std::collections::VecDeque;
+
+impl Collection {
+    /// Export a single recipe into a minimal standalone collection. The new
+    /// collection includes everything the recipe needs to be built: chains it
+    /// references, recipes those chains load from, and the profile fields and
+    /// constants used along the way. Dependencies are followed transitively.
+    /// Everything else is left out, so unrelated recipes and profile data
+    /// don't leak.
+    ///
+    /// If a profile is given, only that profile is included. Otherwise all
+    /// profiles are included, each trimmed to the referenced fields.
+    pub fn export_recipe(
+        &self,
+        recipe_id: &RecipeId,
+        profile_id: Option<&ProfileId>,
+    ) -> anyhow::Result<Self> {
+        let profiles: Vec<&Profile> = match profile_id {
+            Some(profile_id) => {
+                vec![self.profiles.get(profile_id).ok_or_else(|| {
+                    anyhow!(
+                        "No profile with ID `{profile_id}`; options are: {}",
+                        self.profiles.keys().join(", ")
+                    )
+                })?]
+            }
+            None => self.profiles.values().collect(),
+        };
+
+        // Walk the dependency graph breadth-first, tracking what we've seen
+        let mut recipe_ids: IndexSet<&RecipeId> = IndexSet::new();
+        let mut chain_ids: IndexSet<ChainId> = IndexSet::new();
+        let mut fields: IndexSet<String> = IndexSet::new();
+        let mut constants: IndexSet<String> = IndexSet::new();
+        let mut queue: VecDeque<Dependency> =
+            [Dependency::Recipe(recipe_id)].into();
+        // Base URLs, headers, and authentication are kept on every exported
+        // profile, so include whatever they reference
+        queue.extend(references(profiles.iter().flat_map(|profile| {
+            let mut templates = Vec::from_iter(&profile.base_url);
+            templates.extend(profile.headers.values());
+            if let Some(authentication) = &profile.authentication {
+                templates.extend(authentication_templates(authentication));
+            }
+            templates
+        })));
+        while let Some(dependency) = queue.pop_front() {
+            match dependency {
+                Dependency::Recipe(recipe_id) => {
+                    if !recipe_ids.insert(recipe_id) {
+                        continue;
+                    }
+                    let recipe = self
+                        .recipes
+                        .get_recipe(recipe_id)
+                        .ok_or_else(|| {
+                            anyhow!(
+                                "No recipe with ID `{recipe_id}`; options \
+                                are: {}",
+                                self.recipes.recipe_ids().join(", ")
+                            )
+                        })?;
+                    queue.extend(references(recipe_templates(recipe)));
+                }
+                Dependency::Template(TemplateReference::Chain(chain_id)) => {
+                    let chain = self
+                        .chains
+                        .get(&chain_id)
+                        .ok_or_else(|| anyhow!("Unknown chain `{chain_id}`"))?;
+                    if !chain_ids.insert(chain_id) {
+                        continue;
+                    }
+                    queue.extend(references(chain_templates(chain)));
+                    if let ChainSource::Request { recipe, .. } = &chain.source {
+                        queue.push_back(Dependency::Recipe(recipe));
+                    }
+                }
+                Dependency::Template(TemplateReference::Field(field)) => {
+                    // Fields may be nested templates, which have their own
+                    // dependencies
+                    let templates = profiles
+                        .iter()
+                        .filter_map(|profile| profile.data.get(&field))
+                        .collect_vec();
+                    if fields.insert(field) {
+                        queue.extend(references(templates));
+                    }
+                }
+                Dependency::Template(TemplateReference::Constant(name)) => {
+                    // Unknown constants are left for rendering to report
+                    let template = self.constants.get(&name);
+                    if constants.insert(name) {
+                        queue.extend(references(template));
+                    }
+                }
+            }
+        }
+
+        // Build the new collection. Retain the original ordering of
+        // everything, so it looks familiar. Folders are flattened out.
+        let profiles: IndexMap<ProfileId, Profile> = profiles
+            .into_iter()
+            .map(|profile| {
+                let data = profile
+                    .data
+                    .iter()
+                    .filter(|(field, _)| fields.contains(*field))
+                    .map(|(field, template)| (field.clone(), template.clone()))
+                    .collect();
+                let profile = Profile {
+                    data,
+                    ..profile.clone()
+                };
+                (profile.id.clone(), profile)
+            })
+            .collect();
+        let constants = self
+            .constants
+            .iter()
+            .filter(|(name, _)| constants.contains(*name))
+            .map(|(name, template)| (name.clone(), template.clone()))
+            .collect();
+        let chains = self
+            .chains
+            .iter()
+            .filter(|(chain_id, _)| chain_ids.contains(*chain_id))
+            .map(|(chain_id, chain)| (chain_id.clone(), chain.clone()))
+            .collect();
+        let recipes = self
+            .recipes
+            .iter()
+            .filter_map(|(_, node)| node.recipe())
+            .filter(|recipe| recipe_ids.contains(&recipe.id))
+            .map(|recipe| {
+                (recipe.id.clone(), RecipeNode::Recipe(recipe.clone()))
+            })
+            .collect();
+        let default_profile = self
+            .default_profile
+            .clone()
+            .filter(|profile_id| profiles.contains_key(profile_id));
+
+        Ok(Self {
+            default_profile,
+            profiles,
+            constants,
+            chains,
+            // IDs were unique in the original tree, so they're still unique
+            recipes: RecipeTree::new(recipes)
+                .expect("Recipe IDs are unique in source tree"),
+            _ignore: Default::default(),
+        })
+    }
+}
+
+/// Something an exported recipe depends on
+enum Dependency<'a> {
+    Recipe(&'a RecipeId),
+    Template(TemplateReference),
+}
+
+/// Get all collection references from a set of templates
+fn references<'a, 'b>(
+    templates: impl IntoIterator<Item = &'a Template>,
+) -> impl Iterator<Item = Dependency<'b>> {
+    templates
+        .into_iter()
+        .flat_map(Template::references)
+        .map(Dependency::Template)
+        // Collect so the iterator doesn't borrow from the templates
+        .collect_vec()
+        .into_iter()
+}
+
+/// Get every template in an authentication block
+pub(super) fn authentication_templates(
+    authentication: &Authentication,
+) -> Vec<&Template> {
+    match authentication {
+        Authentication::Basic { username, password } => {
+            let mut templates = vec![username];
+            templates.extend(password);
+            templates
+        }
+        Authentication::Bearer(token) => vec![token],
+    }
+}
+
+/// Get every template in a recipe
+pub(super) fn recipe_templates(recipe: &Recipe) -> Vec<&Template> {
+    let mut templates = vec![&recipe.method, &recipe.url];
+    templates.extend(&recipe.label);
+    templates.extend(&recipe.body);
+    templates.extend(recipe.body_command.iter().flatten());
+    if let Some(multipart) = &recipe.multipart {
+        templates.extend(&multipart.boundary);
+        templates.extend(multipart.parameters.values());
+        for part in &multipart.parts {
+            templates.extend(part.headers.values());
+            templates.extend(&part.body);
+            templates.extend(&part.file);
+        }
+    }
+    templates.extend(recipe.query.values());
+    templates.extend(recipe.headers.values());
+    templates.extend(recipe.cookies.values());
+    if let Some(authentication) = &recipe.authentication {
+        templates.extend(authentication_templates(authentication));
+    }
+    if let Some(poll) = &recipe.poll {
+        templates.push(&poll.value);
+    }
+    if let Some(ResponseSchema::File { path }) = &recipe.schema {
+        templates.push(path);
+    }
+    templates
+}
+
+/// Get every template in a chain
+pub(super) fn chain_templates(chain: &Chain) -> Vec<&Template> {
+    match &chain.source {
+        ChainSource::Request { section, .. } => match section {
+            ChainRequestSection::Body | ChainRequestSection::Status => vec![],
+            ChainRequestSection::Header(header) => vec![header],
+            ChainRequestSection::Cookie(cookie) => vec![cookie],
+        },
+        ChainSource::LastResponse => vec![],
+        ChainSource::Command { command } => command.iter().collect(),
+        ChainSource::File { path } => vec![path],
+        ChainSource::Prompt {
+            message, default, ..
+        } => message.iter().chain(default).collect(),
+        ChainSource::Patch { base, changes, .. } => vec![base, changes],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{util::assert_err, util::parse_yaml};
+    use rstest::{fixture, rstest};
+
+    #[fixture]
+    fn collection() -> Collection {
+        parse_yaml(
+            br#"
+            default_profile: dev
+            profiles:
+              dev:
+                data:
+                  host: https://dev
+                  user: "{{username}}"
+                  username: alice
+                  unused: unused
+              prod:
+                data:
+                  host: https://prod
+                  secret: hunter2
+            constants:
+              api: "/api/{{const.version}}"
+              version: v2
+              unused: unused
+            chains:
+              token:
+                source: !request
+                  recipe: login
+                selector: $.token
+              password:
+                source: !file
+                  path: "{{secret}}.txt"
+              unused:
+                source: !prompt
+                  message: Unused
+            requests:
+              login: !request
+                method: POST
+                url: "{{host}}/login"
+                body: '{"user": "{{user}}", "password": "{{chains.password}}"}'
+              folder: !folder
+                requests:
+                  get_user: !request
+                    method: GET
+                    url: "{{host}}{{const.api}}/users/{{user}}"
+                    authentication: !bearer "{{chains.token}}"
+              unused: !request
+                method: GET
+                url: "{{unused}}"
+            "#,
+        )
+        .unwrap()
+    }
+
+    /// Dependencies are followed transitively through chains, other recipes,
+    /// and nested profile fields and constants
+    #[rstest]
+    fn test_export_recipe(collection: Collection) {
+        let exported =
+            collection.export_recipe(&"get_user".into(), None).unwrap();
+
+        assert_eq!(exported.default_profile, Some("dev".into()));
+        let profile_fields = |profile_id: &str| {
+            exported.profiles[&ProfileId::from(profile_id)]
+                .data
+                .keys()
+                .map(String::as_str)
+                .collect_vec()
+        };
+        assert_eq!(profile_fields("dev"), vec!["host", "user", "username"]);
+        assert_eq!(profile_fields("prod"), vec!["host", "secret"]);
+        assert_eq!(
+            exported.constants.keys().collect_vec(),
+            vec!["api", "version"]
+        );
+        assert_eq!(
+            exported.chains.keys().map(|id| id.as_str()).collect_vec(),
+            vec!["token", "password"]
+        );
+        assert_eq!(
+            exported
+                .recipes
+                .recipe_ids()
+                .map(|id| id.as_str())
+                .collect_vec(),
+            vec!["login", "get_user"]
+        );
+    }
+
+    /// Selecting a profile drops the others
+    #[rstest]
+    fn test_export_recipe_profile(collection: Collection) {
+        let exported = collection
+            .export_recipe(&"login".into(), Some(&"prod".into()))
+            .unwrap();
+
+        // Default profile was excluded
+        assert_eq!(exported.default_profile, None);
+        assert_eq!(
+            exported.profiles.keys().map(|id| id.as_str()).collect_vec(),
+            vec!["prod"]
+        );
+        assert_eq!(
+            exported
+                .recipes
+                .recipe_ids()
+                .map(|id| id.as_str())
+                .collect_vec(),
+            vec!["login"]
+        );
+    }
+
+    /// The exported file only includes what the user would write, with
+    /// nulls and defaults omitted, and loads back to the same collection
+    #[rstest]
+    fn test_export_recipe_yaml(collection: Collection) {
+        let exported = collection
+            .export_recipe(&"login".into(), Some(&"prod".into()))
+            .unwrap();
+        let yaml = serde_yaml::to_string(&exported).unwrap();
+        assert_eq!(
+            yaml,
+            r#"profiles:
+  prod:
+    data:
+      host: https://prod
+      secret: hunter2
+chains:
+  password:
+    source: !file
+      path: '{{secret}}.txt'
+requests:
+  login: !request
+    method: POST
+    url: '{{host}}/login'
+    body: '{"user": "{{user}}", "password": "{{chains.password}}"}'
+"#
+        );
+        assert_eq!(
+            parse_yaml::<Collection>(yaml.as_bytes()).unwrap(),
+            exported
+        );
+    }
+
+    /// Numbers in inline schemas are plain YAML numbers, both when the whole
+    /// collection is printed (`slumber show collection`) and when a recipe is
+    /// exported, so the output loads back to the same collection
+    #[test]
+    fn test_schema_numbers_round_trip() {
+        let collection: Collection = parse_yaml(
+            br#"
+            requests:
+              get_user: !request
+                method: GET
+                url: "https://localhost/users"
+                schema: !inline
+                  properties:
+                    id: {maximum: 9007199254740993, minimum: 1}
+                    score: {multipleOf: 0.5}
+            "#,
+        )
+        .unwrap();
+        let exported =
+            collection.export_recipe(&"get_user".into(), None).unwrap();
+
+        for collection in [collection, exported] {
+            let yaml = serde_yaml::to_string(&collection).unwrap();
+            assert!(
+                yaml.contains(
+                    "      properties:
+        id:
+          maximum: 9007199254740993
+          minimum: 1
+        score:
+          multipleOf: 0.5"
+                ),
+                "{yaml}"
+            );
+            assert_eq!(
+                parse_yaml::<Collection>(yaml.as_bytes()).unwrap(),
+                collection
+            );
+        }
+    }
+
+    #[rstest]
+    #[case::unknown_recipe("bogus", None, "No recipe with ID `bogus`")]
+    #[case::unknown_profile(
+        "login",
+        Some("bogus"),
+        "No profile with ID `bogus`"
+    )]
+    fn test_export_recipe_error(
+        collection: Collection,
+        #[case] recipe_id: &str,
+        #[case] profile_id: Option<&str>,
+        #[case] expected_error: &str,
+    ) {
+        let profile_id = profile_id.map(ProfileId::from);
+        assert_err!(
+            collection.export_recipe(&recipe_id.into(), profile_id.as_ref()),
+            expected_error
+        );
+    }
+}
@@ -0,0 +1,126 @@
+//! Reformat collection files, so diffs stay clean in version control
+
+use crate::{collection::Collection, util::parse_yaml};
+use anyhow::{bail, Context};
+use regex::Regex;
+use serde_yaml::Value;
+use std::sync::OnceLock;
+
+/// Reformat the text of a collection file. The output is deterministic:
+/// two-space indentation, one key per line, and keys kept in the order they're
+/// written. The text must be a valid collection on its own.
+///
+/// YAML libraries don't keep the source formatting around, so comments are
+/// removed and aliases are replaced by the values they refer to. Use
+/// [lossy_features] to check for those first.
+pub fn format_collection(text: &[u8]) -> anyhow::Result<String> {
+    // Make sure it's a collection, so we don't rewrite something else
+    parse_yaml::<Collection>(text)?;
+    let value: Value = serde_yaml::from_slice(text)?;
+    if value.is_null() {
+        // An empty file is already as formatted as it gets
+        return Ok(String::from_utf8_lossy(text).into_owned());
+    }
+    let formatted = serde_yaml::to_string(&value)?;
+
+    // Merge keys (`<<`) are kept as-is, so the data should survive untouched
+    let reparsed: Value = serde_yaml::from_str(&formatted)
+        .context("Error parsing formatted collection")?;
+    if reparsed != value {
+        bail!("Formatting would change the collection's contents");
+    }
+    Ok(formatted)
+}
+
+/// Find anything in collection text that [format_collection] can't keep: `#`
+/// comments, and `&anchor`/`*alias` references. This is a best-effort check
+/// on the raw text, so it errs on the side of finding too much, e.g. a `#` in
+/// a quoted string counts as a comment.
+pub fn lossy_features(text: &str) -> Vec<&'static str> {
+    static COMMENT: OnceLock<Regex> = OnceLock::new();
+    static ALIAS: OnceLock<Regex> = OnceLock::new();
+    let comment = COMMENT.get_or_init(|| Regex::new(r"(?m)(^|\s)#").unwrap());
+    let alias = ALIAS.get_or_init(|| {
+        Regex::new(r"(?m)(^|[:\[{,-])\s*[&*][^\s,\]}]").unwrap()
+    });
+
+    let mut features = Vec::new();
+    if comment.is_match(text) {
+        features.push("comments");
+    }
+    if alias.is_match(text) {
+        features.push("anchors");
+    }
+    features
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::assert_err;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    #[test]
+    fn test_format_collection() {
+        let text = "
+profiles:
+    dev:
+        data: {host: 'https://dev'}
+requests:
+    login: !request
+        method: POST
+        url: \"{{host}}/login\"
+        headers: {Accept: application/json}
+    users: !folder
+        requests:
+            get_user: !request {method: GET, url: '{{host}}/users'}
+";
+        let formatted = format_collection(text.as_bytes()).unwrap();
+        assert_eq!(
+            formatted,
+            "profiles:
+  dev:
+    data:
+      host: https://dev
+requests:
+  login: !request
+    method: POST
+    url: '{{host}}/login'
+    headers:
+      Accept: application/json
+  users: !folder
+    requests:
+      get_user: !request
+        method: GET
+        url: '{{host}}/users'
+"
+        );
+        // Formatting is stable
+        assert_eq!(format_collection(formatted.as_bytes()).unwrap(), formatted);
+    }
+
+    #[test]
+    fn test_format_collection_invalid() {
+        assert_err!(
+            format_collection(b"requests: {r: !request {url: 'x'}}"),
+            "missing field `method`"
+        );
+    }
+
+    #[rstest]
+    #[case::none("requests: {}\nurl: 'https://host/#/fragment'", &[])]
+    #[case::comment_line("# Recipes\nrequests: {}", &["comments"])]
+    #[case::comment_inline("requests: {} # Recipes", &["comments"])]
+    #[case::anchor(
+        ".ignore:\n  base: &base\n    method: GET\nrequests:\n  \
+        r: !request\n    <<: *base\n    url: x",
+        &["anchors"]
+    )]
+    #[case::alias_in_list("list: [*a]", &["anchors"])]
+    #[case::glob("url: https://host/*/path", &[])]
+    #[case::both("a: &a 1 # one", &["comments", "anchors"])]
+    fn test_lossy_features(#[case] text: &str, #[case] expected: &[&str]) {
+        assert_eq!(lossy_features(text), expected);
+    }
+}
@@ -3,8 +3,8 @@
 
 use crate::{
     collection::{
-        self, Collection, Folder, Method, Profile, ProfileId, Recipe, RecipeId,
-        RecipeNode, RecipeTree,
+        self, BodyFormat, Collection, Folder, Method, Profile, ProfileId,
+        Recipe, RecipeId, RecipeNode, RecipeTree,
     },
     template::Template,
 };
@@ -62,7 +62,9 @@ impl Collection {
             build_recipe_tree(&workspace_id, request_groups, requests)?;
 
         Ok(Collection {
+            default_profile: None,
             profiles,
+            constants: IndexMap::new(),
             recipes,
             // Parse templates into chains:
             // https://github.com/LucasPickering/slumber/issues/164
@@ -244,6 +246,11 @@ impl From<Environment> for Profile {
         Profile {
             id: environment.id.into(),
             name: Some(environment.name),
+            base_url: None,
+            authentication: None,
+            color: None,
+            label: None,
+            headers: Default::default(),
             data: environment
                 .data
                 .into_iter()
@@ -260,6 +267,7 @@ impl From<RequestGroup> for RecipeNode {
             name: Some(folder.name),
             // This will be populated later
             children: IndexMap::new(),
+            disabled: false,
         })
     }
 }
@@ -298,16 +306,38 @@ impl From<Request> for RecipeNode {
         RecipeNode::Recipe(Recipe {
             id: request.id.into(),
             name: Some(request.name),
-            method: request.method,
+            label: None,
+            method: Template::dangerous(request.method.to_string()),
             url: request.url,
-            body: request.body.map(|body| body.text),
+            body: request.body.map(|body| {
+                let escaped =
+                    Recipe::escape_body(body.text.as_str().to_owned());
+                // Prepending static text can't make the template invalid
+                Template::try_from(escaped).unwrap_or(body.text)
+            }),
+            body_format: BodyFormat::Raw,
+            multipart: None,
             query: request
                 .parameters
                 .into_iter()
                 .map(|parameter| (parameter.name, parameter.value))
                 .collect(),
             headers,
+            cookies: IndexMap::new(),
+            capture: IndexMap::new(),
             authentication,
+            poll: None,
+            pagination: None,
+            schema: None,
+            body_command: None,
+            persist: true,
+            confirm: None,
+            expects: None,
+            replay: Default::default(),
+            expect_continue: Default::default(),
+            tls: Default::default(),
+            assert: Default::default(),
+            disabled: false,
         })
     }
 }
@@ -373,6 +403,11 @@ fn build_profiles(
                 Profile {
                     id,
                     name: Some(environment.name),
+                    base_url: None,
+                    authentication: None,
+                    color: None,
+                    label: None,
+                    headers: Default::default(),
                     data,
                 },
             )
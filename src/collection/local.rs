@@ -0,0 +1,205 @@
+//! Local overlays: machine-specific values (e.g. a personal token) that are
+//! layered over a collection, but kept in a separate file so they don't have
+//! to be shared
+
+use crate::{
+    collection::{
+        Collection, CollectionLoadError, LocalOverlayError, ProfileId,
+    },
+    template::Template,
+    util::parse_yaml,
+};
+use indexmap::IndexMap;
+use serde::Deserialize;
+use std::{
+    ffi::OsStr,
+    fs,
+    path::{Path, PathBuf},
+};
+use tracing::info;
+
+/// Suffix (before the extension) that marks a file as a local overlay, e.g.
+/// `slumber.local.yml`
+const LOCAL_SUFFIX: &str = ".local";
+
+/// Contents of a local overlay file. Only these fields can be overridden;
+/// anything else is an error, so it's explicit what a local file can change.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LocalOverlay {
+    /// Replaces the collection's `default_profile`
+    pub default_profile: Option<ProfileId>,
+    /// Values layered over existing profiles
+    #[serde(default)]
+    pub profiles: IndexMap<ProfileId, LocalProfile>,
+}
+
+/// Overrides for a single profile
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LocalProfile {
+    /// Replaces the profile's `base_url`
+    pub base_url: Option<Template>,
+    /// Merged over the profile's `headers`
+    #[serde(default)]
+    pub headers: IndexMap<String, Template>,
+    /// Merged over the profile's `data`. Fields not given here are kept from
+    /// the collection.
+    #[serde(default)]
+    pub data: IndexMap<String, Template>,
+}
+
+impl Collection {
+    /// Layer a local overlay over this collection. Local values take
+    /// precedence over the collection. Every profile in the overlay must
+    /// already be defined in the collection, so a typo doesn't go unnoticed.
+    pub fn apply_local(
+        &mut self,
+        local: LocalOverlay,
+    ) -> Result<(), LocalOverlayError> {
+        if let Some(default_profile) = local.default_profile {
+            self.default_profile = Some(default_profile);
+        }
+        for (profile_id, overlay) in local.profiles {
+            let Some(profile) = self.profiles.get_mut(&profile_id) else {
+                return Err(LocalOverlayError::ProfileUnknown { profile_id });
+            };
+            if let Some(base_url) = overlay.base_url {
+                profile.base_url = Some(base_url);
+            }
+            profile.headers.extend(overlay.headers);
+            profile.data.extend(overlay.data);
+        }
+        Ok(())
+    }
+}
+
+/// Get the path of the local overlay for a collection file, e.g.
+/// `slumber.local.yml` for `slumber.yml`. The file may not exist.
+pub fn local_path(path: &Path) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let mut file_name = format!("{stem}{LOCAL_SUFFIX}");
+    if let Some(extension) = path.extension().and_then(OsStr::to_str) {
+        file_name.push('.');
+        file_name.push_str(extension);
+    }
+    path.with_file_name(file_name)
+}
+
+/// Is this file a local overlay, rather than part of a collection?
+pub fn is_local(path: &Path) -> bool {
+    path.file_stem()
+        .and_then(OsStr::to_str)
+        .is_some_and(|stem| stem.ends_with(LOCAL_SUFFIX))
+}
+
+/// Load a local overlay from a file, and apply it to a collection
+pub fn apply_local_file(
+    collection: &mut Collection,
+    path: &Path,
+) -> Result<(), CollectionLoadError> {
+    info!(?path, "Loading local overlay");
+    let bytes = fs::read(path).map_err(|error| CollectionLoadError::Io {
+        path: path.to_owned(),
+        error,
+    })?;
+    let local =
+        parse_yaml(&bytes).map_err(|error| CollectionLoadError::Parse {
+            path: path.to_owned(),
+            error,
+        })?;
+    collection
+        .apply_local(local)
+        .map_err(|error| CollectionLoadError::Local {
+            path: path.to_owned(),
+            error,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{collection::Profile, test_util::*, util::assert_err};
+    use factori::create;
+    use indexmap::indexmap;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::yml("slumber.yml", "slumber.local.yml")]
+    #[case::hidden(".slumber.yaml", ".slumber.local.yaml")]
+    #[case::nested("api/fish.yml", "api/fish.local.yml")]
+    fn test_local_path(#[case] path: &str, #[case] expected: &str) {
+        let local = local_path(Path::new(path));
+        assert_eq!(local, PathBuf::from(expected));
+        assert!(is_local(&local));
+        assert!(!is_local(Path::new(path)));
+    }
+
+    /// Local values replace or extend the collection's, and everything else
+    /// is left alone
+    #[test]
+    fn test_apply_local() {
+        let profile = Profile {
+            base_url: Some("https://fish.fish".into()),
+            data: indexmap! {
+                "host".into() => "shared".into(),
+                "token".into() => "".into(),
+            },
+            ..create!(Profile)
+        };
+        let mut collection = create!(
+            Collection,
+            default_profile: Some("shared".into()),
+            profiles: indexmap! {profile.id.clone() => profile.clone()},
+        );
+        let local: LocalOverlay = serde_yaml::from_str(
+            "
+default_profile: mine
+profiles:
+  profile1:
+    headers:
+      Accept-Language: fr-CA
+    data:
+      token: secret
+      port: '3000'
+",
+        )
+        .unwrap();
+        collection.apply_local(local).unwrap();
+
+        assert_eq!(collection.default_profile, Some("mine".into()));
+        let profile = &collection.profiles[&profile.id];
+        assert_eq!(profile.base_url, Some("https://fish.fish".into()));
+        assert_eq!(
+            profile.headers,
+            indexmap! {"Accept-Language".into() => "fr-CA".into()}
+        );
+        assert_eq!(
+            profile.data,
+            indexmap! {
+                "host".into() => "shared".into(),
+                "token".into() => "secret".into(),
+                "port".into() => "3000".into(),
+            }
+        );
+    }
+
+    #[rstest]
+    #[case::unknown_profile(
+        "profiles: {bogus: {data: {a: b}}}",
+        "Profile `bogus` is not in the collection"
+    )]
+    fn test_apply_local_error(#[case] yaml: &str, #[case] expected: &str) {
+        let mut collection = create!(Collection);
+        let local: LocalOverlay = serde_yaml::from_str(yaml).unwrap();
+        assert_err!(collection.apply_local(local), expected);
+    }
+
+    /// Only the explicitly supported fields can be overridden
+    #[test]
+    fn test_local_unknown_field() {
+        let result =
+            serde_yaml::from_str::<LocalOverlay>("requests: {a: null}");
+        assert!(result.unwrap_err().to_string().contains("unknown field"));
+    }
+}
@@ -0,0 +1,230 @@
+//! Merge multiple collection files into a single workspace collection
+
+use crate::collection::{Collection, MergeError, RecipeTree};
+use indexmap::IndexMap;
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    fmt::Display,
+    hash::Hash,
+    path::{Path, PathBuf},
+};
+
+impl Collection {
+    /// Merge collections loaded from multiple files into one. Profiles,
+    /// constants, chains, and recipes are combined in the order the files are
+    /// given. There's no precedence between files, so any ID that's defined in
+    /// more than one file is an error. Recipe and folder IDs share one
+    /// namespace, including nested nodes, just like within a single file. If
+    /// more than one file sets `default_profile`, they must agree.
+    pub fn merge(
+        collections: impl IntoIterator<Item = (PathBuf, Collection)>,
+    ) -> Result<Self, MergeError> {
+        let mut default_profile: Option<(_, PathBuf)> = None;
+        let mut profiles = IndexMap::new();
+        let mut constants = IndexMap::new();
+        let mut chains = IndexMap::new();
+        let mut recipes = IndexMap::new();
+        // Track which file each ID came from, for error messages
+        let mut profile_sources = HashMap::new();
+        let mut constant_sources = HashMap::new();
+        let mut chain_sources = HashMap::new();
+        let mut recipe_sources = HashMap::new();
+
+        for (path, collection) in collections {
+            if let Some(profile_id) = collection.default_profile {
+                match &default_profile {
+                    None => default_profile = Some((profile_id, path.clone())),
+                    Some((existing, _)) if *existing == profile_id => {}
+                    Some((existing, existing_path)) => {
+                        return Err(MergeError::DefaultProfile {
+                            first: existing.clone(),
+                            first_path: existing_path.clone(),
+                            second: profile_id,
+                            second_path: path,
+                        });
+                    }
+                }
+            }
+
+            for (profile_id, profile) in collection.profiles {
+                add_source(
+                    &mut profile_sources,
+                    &profile_id,
+                    &path,
+                    "profile",
+                )?;
+                profiles.insert(profile_id, profile);
+            }
+            for (name, template) in collection.constants {
+                add_source(&mut constant_sources, &name, &path, "constant")?;
+                constants.insert(name, template);
+            }
+            for (chain_id, chain) in collection.chains {
+                add_source(&mut chain_sources, &chain_id, &path, "chain")?;
+                chains.insert(chain_id, chain);
+            }
+            // Check nested nodes too, because IDs are unique across the tree
+            for (_, node) in collection.recipes.iter() {
+                add_source(&mut recipe_sources, node.id(), &path, "recipe")?;
+            }
+            recipes.extend(collection.recipes.into_tree());
+        }
+
+        Ok(Self {
+            default_profile: default_profile.map(|(profile_id, _)| profile_id),
+            profiles,
+            constants,
+            chains,
+            recipes: RecipeTree::new(recipes)
+                .expect("Recipe IDs were checked for uniqueness across files"),
+            _ignore: Default::default(),
+        })
+    }
+}
+
+/// Record the file that an ID was defined in. If it was already defined in
+/// another file, return an error naming both
+fn add_source<K: Clone + Display + Eq + Hash>(
+    sources: &mut HashMap<K, PathBuf>,
+    id: &K,
+    path: &Path,
+    kind: &'static str,
+) -> Result<(), MergeError> {
+    match sources.entry(id.clone()) {
+        Entry::Vacant(entry) => {
+            entry.insert(path.to_owned());
+            Ok(())
+        }
+        Entry::Occupied(entry) => Err(MergeError::DuplicateId {
+            kind,
+            id: id.to_string(),
+            first_path: entry.get().clone(),
+            second_path: path.to_owned(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::{assert_err, parse_yaml};
+    use itertools::Itertools;
+    use rstest::rstest;
+
+    /// Parse a collection for each file
+    fn collections<const N: usize>(
+        files: [(&str, &str); N],
+    ) -> Vec<(PathBuf, Collection)> {
+        files
+            .into_iter()
+            .map(|(path, yaml)| {
+                (path.into(), parse_yaml(yaml.as_bytes()).unwrap())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_merge() {
+        let merged = Collection::merge(collections([
+            (
+                "users.yml",
+                r#"
+                default_profile: dev
+                profiles:
+                  dev:
+                    data:
+                      host: https://dev
+                chains:
+                  token:
+                    source: !file
+                      path: token.txt
+                requests:
+                  users: !folder
+                    requests:
+                      get_user: !request
+                        method: GET
+                        url: "{{host}}/users"
+                "#,
+            ),
+            (
+                "orders.yml",
+                r#"
+                default_profile: dev
+                profiles:
+                  prod:
+                    data:
+                      host: https://prod
+                constants:
+                  version: v2
+                requests:
+                  get_order: !request
+                    method: GET
+                    url: "{{host}}/orders"
+                "#,
+            ),
+        ]))
+        .unwrap();
+
+        assert_eq!(merged.default_profile, Some("dev".into()));
+        assert_eq!(
+            merged.profiles.keys().map(|id| id.as_str()).collect_vec(),
+            vec!["dev", "prod"]
+        );
+        assert_eq!(merged.constants.keys().collect_vec(), vec!["version"]);
+        assert_eq!(
+            merged.chains.keys().map(|id| id.as_str()).collect_vec(),
+            vec!["token"]
+        );
+        assert_eq!(
+            merged
+                .recipes
+                .recipe_ids()
+                .map(|id| id.as_str())
+                .collect_vec(),
+            vec!["get_user", "get_order"]
+        );
+    }
+
+    #[rstest]
+    #[case::profile(
+        "profiles: {dev: {data: {}}}",
+        "profiles: {dev: {data: {}}}",
+        "Duplicate profile ID `dev` in \"a.yml\" and \"b.yml\""
+    )]
+    #[case::constant(
+        "constants: {version: v1}",
+        "constants: {version: v2}",
+        "Duplicate constant ID `version`"
+    )]
+    #[case::chain(
+        "chains: {c: {source: !file {path: a}}}",
+        "chains: {c: {source: !file {path: b}}}",
+        "Duplicate chain ID `c`"
+    )]
+    #[case::recipe(
+        "requests: {r: !request {method: GET, url: a}}",
+        "requests: {r: !request {method: GET, url: b}}",
+        "Duplicate recipe ID `r`"
+    )]
+    #[case::nested_recipe(
+        "requests: {f: !folder {requests: {r: !request {method: GET, url: a}}}}",
+        "requests: {r: !folder {}}",
+        "Duplicate recipe ID `r`"
+    )]
+    #[case::default_profile(
+        "default_profile: dev",
+        "default_profile: prod",
+        "Conflicting `default_profile` in \"a.yml\" (`dev`) and \"b.yml\" \
+        (`prod`)"
+    )]
+    fn test_merge_conflict(
+        #[case] a: &str,
+        #[case] b: &str,
+        #[case] expected_error: &str,
+    ) {
+        assert_err!(
+            Collection::merge(collections([("a.yml", a), ("b.yml", b)])),
+            expected_error
+        );
+    }
+}
@@ -3,19 +3,22 @@
 use crate::{
     collection::{
         cereal,
+        export::chain_templates,
         recipe_tree::{RecipeNode, RecipeTree},
     },
-    http::{ContentType, Query},
-    template::Template,
+    http::{ContentType, Query, Response, TlsVersions},
+    template::{Template, TemplateReference},
 };
 use anyhow::anyhow;
 use derive_more::{Deref, Display, From, FromStr};
 use equivalent::Equivalent;
 use indexmap::IndexMap;
 use itertools::Itertools;
+use regex::Regex;
 use serde::{Deserialize, Serialize};
-use std::time::Duration;
+use std::{collections::HashSet, time::Duration};
 use strum::{EnumIter, IntoEnumIterator};
+use tracing::info;
 
 /// A collection of profiles, requests, etc. This is the primary Slumber unit
 /// of configuration.
@@ -23,9 +26,24 @@ use strum::{EnumIter, IntoEnumIterator};
 #[cfg_attr(test, derive(PartialEq))]
 #[serde(deny_unknown_fields)]
 pub struct Collection {
-    #[serde(default, deserialize_with = "cereal::deserialize_id_map")]
+    /// Profile to use when one isn't specified, in the CLI or on TUI launch
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_profile: Option<ProfileId>,
+    #[serde(
+        default,
+        deserialize_with = "cereal::deserialize_id_map",
+        skip_serializing_if = "IndexMap::is_empty"
+    )]
     pub profiles: IndexMap<ProfileId, Profile>,
-    #[serde(default, deserialize_with = "cereal::deserialize_id_map")]
+    /// Values that are the same in every profile, available to templates as
+    /// `{{const.<name>}}`. These are templates, like profile fields.
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub constants: IndexMap<String, Template>,
+    #[serde(
+        default,
+        deserialize_with = "cereal::deserialize_id_map",
+        skip_serializing_if = "IndexMap::is_empty"
+    )]
     pub chains: IndexMap<ChainId, Chain>,
     /// Internally we call these recipes, but to a user `requests` is more
     /// intuitive
@@ -46,10 +64,46 @@ pub struct Collection {
 pub struct Profile {
     #[serde(skip)] // This will be auto-populated from the map key
     pub id: ProfileId,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub name: Option<String>,
+    /// Prefix for relative recipe URLs, e.g. `https://example.com/api`.
+    /// Recipes with absolute URLs ignore this.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub base_url: Option<Template>,
+    /// Replaces the authentication of every recipe that has authentication.
+    /// Recipes without authentication aren't affected. See
+    /// [Recipe::authentication].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub authentication: Option<Authentication>,
+    /// Color to flag this profile with in the TUI, e.g. red for production
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<ProfileColor>,
+    /// Text to show prominently in the TUI when this profile is selected,
+    /// e.g. `PRODUCTION`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    /// Default headers for every request sent with this profile, e.g.
+    /// `Accept-Language`. A recipe header with the same name (in any case)
+    /// takes precedence.
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub headers: IndexMap<String, Template>,
     pub data: IndexMap<String, Template>,
 }
 
+/// Colors a profile can be flagged with. These are the terminal's named
+/// colors, so they fit whatever palette the terminal uses.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProfileColor {
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    Gray,
+}
+
 #[derive(
     Clone,
     Debug,
@@ -72,6 +126,7 @@ pub struct ProfileId(String);
 pub struct Folder {
     #[serde(skip)] // This will be auto-populated from the map key
     pub id: RecipeId,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub name: Option<String>,
     /// RECURSION. Use `requests` in serde to match the root field.
     #[serde(
@@ -79,7 +134,12 @@ pub struct Folder {
         deserialize_with = "cereal::deserialize_id_map",
         rename = "requests"
     )]
+    #[serde(skip_serializing_if = "IndexMap::is_empty")]
     pub children: IndexMap<RecipeId, RecipeNode>,
+    /// Disable everything in this folder, without deleting it. See
+    /// [Recipe::disabled]
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub disabled: bool,
 }
 
 /// A definition of how to make a request. This is *not* called `Request` in
@@ -92,18 +152,95 @@ pub struct Folder {
 pub struct Recipe {
     #[serde(skip)] // This will be auto-populated from the map key
     pub id: RecipeId,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub name: Option<String>,
-    /// *Not* a template string because the usefulness doesn't justify the
-    /// complexity. This gives the user an immediate error if the method is
-    /// wrong which is helpful.
-    pub method: Method,
+    /// Name for each request sent from this recipe, rendered along with the
+    /// rest of the request. Use this to tell apart requests for the same
+    /// recipe with different inputs, e.g. `User {{user_id}}`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<Template>,
+    /// HTTP method. This is a template so it can vary by profile, and must
+    /// render to a valid [Method]. If the template has no keys, it's
+    /// validated during deserialization to give the user an immediate error.
+    #[serde(deserialize_with = "cereal::deserialize_method")]
+    pub method: Template,
     pub url: Template,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub body: Option<Template>,
+    /// How the rendered body should be processed before it's sent
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub body_format: BodyFormat,
+    /// Generate the body by running a command. The command's stdout is
+    /// streamed to the server as it's produced, rather than loaded into
+    /// memory first. Mutually exclusive with `body`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body_command: Option<Vec<Template>>,
+    /// Build the body from a list of parts, in any `multipart/*` format.
+    /// Mutually exclusive with `body` and `body_command`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub multipart: Option<Multipart>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub authentication: Option<Authentication>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
     pub query: IndexMap<String, Template>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
     pub headers: IndexMap<String, Template>,
+    /// Cookies to send, by name. These are encoded and combined into a single
+    /// `Cookie` header, along with any cookies given in `headers`
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub cookies: IndexMap<String, Template>,
+    /// Values to pull out of successful response bodies, by variable name.
+    /// Each one is stored for the rest of the session, and available to
+    /// templates as `{{vars.<name>}}`
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub capture: IndexMap<String, Query>,
+    /// Re-send the request until its response meets a condition
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub poll: Option<Poll>,
+    /// Where to find the next page of a paginated response, for APIs that
+    /// don't use `Link` headers
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pagination: Option<Pagination>,
+    /// Validate response bodies against this JSON Schema
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub schema: Option<ResponseSchema>,
+    /// Checks for responses to meet, e.g. a maximum response time. The CLI
+    /// fails if any of them don't pass
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub assert: Assertions,
+    /// Content type the response should have. This sets a default `Accept`
+    /// header, and responses are displayed as this type even if their
+    /// `Content-Type` header disagrees
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expects: Option<ContentType>,
+    /// In offline mode, how to pick the recorded response to replay
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub replay: ReplayMatch,
+    /// When to send `Expect: 100-continue`, so the server can reject the
+    /// request before the body is uploaded
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub expect_continue: ExpectContinue,
+    /// Allowed TLS versions for this recipe. Each bound overrides the one
+    /// from the `tls` config field.
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub tls: TlsVersions,
+    /// Should requests for this recipe be saved in history? Disable this for
+    /// endpoints with sensitive data. Responses are still visible in the TUI
+    /// for the rest of the session.
+    #[serde(
+        default = "persist_default",
+        skip_serializing_if = "is_persist_default"
+    )]
+    pub persist: bool,
+    /// Ask for confirmation before sending this recipe from the TUI. If
+    /// omitted, the `confirm_methods` config field decides based on the method
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub confirm: Option<bool>,
+    /// Keep the recipe in the collection, but skip it when sending a whole
+    /// folder. It's grayed out (or hidden, per config) in the TUI, but can
+    /// still be sent explicitly.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub disabled: bool,
 }
 
 #[derive(
@@ -152,6 +289,150 @@ pub enum Method {
     Trace,
 }
 
+/// Configuration for re-sending a request until its response meets some
+/// condition, e.g. to wait for an async job to finish. Every attempt is a
+/// separate request, and is stored in history as such.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq))]
+#[serde(deny_unknown_fields)]
+pub struct Poll {
+    /// Selector to extract a value from the response body. The body is parsed
+    /// according to its `Content-Type` header.
+    pub selector: Query,
+    /// Polling stops once the selected value equals this (as a string)
+    pub value: Template,
+    /// Time to wait after a response before sending the next attempt
+    #[serde(
+        default = "Poll::default_interval",
+        with = "cereal::serde_duration"
+    )]
+    pub interval: Duration,
+    /// Maximum number of requests to send, including the first
+    #[serde(default = "Poll::default_max_attempts")]
+    pub max_attempts: u32,
+}
+
+/// How to find the next page of a response in its body, for APIs that don't
+/// send a `Link` header. Each page is a separate request, and is stored in
+/// history as such.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq))]
+#[serde(deny_unknown_fields)]
+pub struct Pagination {
+    /// Selector to extract the next page's URL from the response body, or
+    /// its cursor if `param` is given. If it doesn't match or the value is
+    /// empty, this is the last page.
+    pub next: Query,
+    /// Query parameter to send the cursor in. The rest of the URL is kept
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub param: Option<String>,
+}
+
+/// Checks that a response has to pass, beyond its content. Every check is
+/// optional, and omitted checks always pass.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Assertions {
+    /// Maximum time for the response to arrive, in milliseconds. This is
+    /// measured from when the request is sent until the response body has
+    /// loaded
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_duration: Option<u64>,
+}
+
+/// Format of a recipe's body, which determines how it's processed after
+/// rendering
+#[derive(Copy, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BodyFormat {
+    /// Send the rendered body as-is
+    #[default]
+    Raw,
+    /// Parse the rendered body as [JSON5](https://json5.org/), and send it as
+    /// standard JSON. This allows comments, trailing commas, etc. in the body.
+    Json5,
+}
+
+/// A multipart body. The body is encoded exactly as defined, with parts in
+/// order, so any subtype (e.g. `related` or `mixed`) can be used, not just
+/// `form-data`. The `Content-Type` header is generated from the subtype,
+/// boundary, and parameters, unless the recipe sets it explicitly.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq))]
+#[serde(deny_unknown_fields)]
+pub struct Multipart {
+    /// Subtype of the body, as in `multipart/<subtype>`
+    #[serde(default = "Multipart::default_subtype")]
+    pub subtype: String,
+    /// Delimiter between parts. If omitted, a random boundary is generated
+    /// for each request
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub boundary: Option<Template>,
+    /// Additional `Content-Type` parameters, e.g. `type` and `start` for
+    /// `multipart/related`
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub parameters: IndexMap<String, Template>,
+    pub parts: Vec<MultipartPart>,
+}
+
+/// A single part of a multipart body
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq))]
+#[serde(deny_unknown_fields)]
+pub struct MultipartPart {
+    /// Headers for this part, e.g. `Content-Disposition` or `Content-ID`.
+    /// Names are sent exactly as written, including case.
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub headers: IndexMap<String, Template>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body: Option<Template>,
+    /// Path of a file to load the body from, as raw bytes. Use this instead
+    /// of `body` for binary content. Mutually exclusive with `body`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<Template>,
+}
+
+/// In offline mode, how a request is matched to a recorded response from
+/// history. Only responses for the same recipe and profile are considered.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReplayMatch {
+    /// The most recent response
+    #[default]
+    Latest,
+    /// The most recent response whose request had the same method, URL, and
+    /// body. Headers are ignored, since they often contain timestamps or
+    /// signatures.
+    Request,
+}
+
+/// When a request should ask the server to approve it (with `Expect:
+/// 100-continue`) before its body is sent
+#[derive(Copy, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExpectContinue {
+    /// Only for large bodies
+    Auto,
+    /// For any request with a body
+    Always,
+    /// Never add the header. An `Expect` header written in the recipe is
+    /// still honored.
+    #[default]
+    Never,
+}
+
+/// A [JSON Schema](https://json-schema.org/) that response bodies for a recipe
+/// should match. Bodies of other content types are converted to JSON first.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq))]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum ResponseSchema {
+    /// Schema defined directly in the collection
+    Inline(serde_json::Value),
+    /// Load the schema from a JSON or YAML file
+    File { path: Template },
+}
+
 /// Shortcut for defining authentication method. If this is defined in addition
 /// to the `Authorization` header, that header will end up being included in the
 /// request twice.
@@ -179,18 +460,28 @@ pub struct Chain {
     pub id: ChainId,
     pub source: ChainSource,
     /// Mask chained value in the UI
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
     pub sensitive: bool,
     /// Selector to extract a value from the response. This uses JSONPath
     /// regardless of the content type. Non-JSON values will be converted to
     /// JSON, then converted back.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub selector: Option<Query>,
     /// Hard-code the content type of the response. Only needed if a selector
     /// is given and the content type can't be dynamically determined
     /// correctly. This is needed if the chain source is not an HTTP
     /// response (e.g. a file) **or** if the response's `Content-Type` header
     /// is incorrect.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub content_type: Option<ContentType>,
+    /// What to do if the selector matches more than one value
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub selector_mode: SelectorMode,
+    /// With [SelectorMode::Prompt], which match to use if the user doesn't
+    /// pick one, e.g. because there's no terminal to ask on. If not given,
+    /// rendering fails instead.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub selector_fallback: Option<SelectorFallback>,
 }
 
 /// Unique ID for a chain. Takes a generic param so we can create these during
@@ -238,9 +529,26 @@ pub enum ChainSource {
     Request {
         recipe: RecipeId,
         /// When should this request be automatically re-executed?
-        #[serde(default)]
+        #[serde(default, skip_serializing_if = "is_default")]
         trigger: ChainRequestTrigger,
+        /// Which part of the response to take the value from
+        #[serde(default, skip_serializing_if = "ChainRequestSection::is_body")]
+        section: ChainRequestSection,
+        /// If the request is triggered, fail if it takes longer than this
+        #[serde(
+            default,
+            with = "cereal::serde_duration::option",
+            skip_serializing_if = "Option::is_none"
+        )]
+        timeout: Option<Duration>,
+        /// If the request is triggered, fail if the response body is larger
+        /// than this many bytes
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        max_body_size: Option<u64>,
     },
+    /// Load data from the most recent response received for the selected
+    /// profile, from any recipe. This never sends a request.
+    LastResponse,
     /// Run an external command to get a result
     Command { command: Vec<Template> },
     /// Load data from a file
@@ -248,16 +556,128 @@ pub enum ChainSource {
     /// Prompt the user for a value
     Prompt {
         /// Descriptor to show to the user
+        #[serde(skip_serializing_if = "Option::is_none")]
         message: Option<Template>,
         /// Default value for the shown textbox
+        #[serde(skip_serializing_if = "Option::is_none")]
         default: Option<Template>,
+        /// If given, the entered value must match this pattern. The user is
+        /// asked again until it does.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pattern: Option<PromptPattern>,
     },
+    /// Compute a patch document that applies a set of changes to a base JSON
+    /// document. Useful for building `PATCH` bodies without hand-writing them
+    Patch {
+        /// The document being modified, e.g. from a chained GET request
+        base: Template,
+        /// JSON object of the fields to change. This is applied to the base
+        /// with merge patch semantics, so `null` removes a field.
+        changes: Template,
+        /// Format of the generated patch
+        #[serde(default, skip_serializing_if = "is_default")]
+        format: PatchFormat,
+    },
+}
+
+/// A regex that a [ChainSource::Prompt] value must match. The pattern has to
+/// match the *entire* value, so `\d+` rejects `abc123`.
+#[derive(Clone, Debug, Display, Serialize, Deserialize)]
+#[display("{source}")]
+#[serde(into = "String", try_from = "String")]
+pub struct PromptPattern {
+    /// The pattern as written, for display and serialization
+    source: String,
+    /// Anchored version of the source, for matching
+    regex: Regex,
+}
+
+impl PromptPattern {
+    /// Does the value match the pattern?
+    pub fn is_match(&self, value: &str) -> bool {
+        self.regex.is_match(value)
+    }
+}
+
+impl PartialEq for PromptPattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.source == other.source
+    }
+}
+
+/// For deserialization
+impl TryFrom<String> for PromptPattern {
+    type Error = regex::Error;
+
+    fn try_from(source: String) -> Result<Self, Self::Error> {
+        let regex = Regex::new(&format!("^(?:{source})$"))?;
+        Ok(Self { source, regex })
+    }
+}
+
+/// For serialization
+impl From<PromptPattern> for String {
+    fn from(pattern: PromptPattern) -> Self {
+        pattern.source
+    }
+}
+
+/// Format of a patch document generated by [ChainSource::Patch]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PatchFormat {
+    /// JSON Merge Patch (RFC 7386)
+    #[default]
+    MergePatch,
+    /// JSON Patch (RFC 6902)
+    JsonPatch,
+}
+
+/// How a chain handles a selector that matches more than one value. This only
+/// applies to selectors without transforms, since transforms always produce a
+/// single value.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SelectorMode {
+    /// The selector must match exactly one value
+    #[default]
+    Single,
+    /// Ask the user to pick one of the matches. The choice is remembered for
+    /// the rest of the session, as long as it's still one of the matches.
+    Prompt,
+}
+
+/// Which match to use when the user doesn't pick one. See
+/// [SelectorMode::Prompt]
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq))]
+#[serde(rename_all = "snake_case")]
+pub enum SelectorFallback {
+    First,
+    Last,
+}
+
+/// The part of a response that a [ChainSource::Request] takes its value from
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq))]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum ChainRequestSection {
+    /// The response body
+    #[default]
+    Body,
+    /// The value of a response header. The name is case-insensitive. If the
+    /// header appears more than once, the values are joined with `, `.
+    Header(Template),
+    /// The numeric status code, e.g. `201`
+    Status,
+    /// The value of a cookie set by the response, via `Set-Cookie`. Unlike
+    /// headers, cookie names are case-sensitive.
+    Cookie(Template),
 }
 
 /// Define when a recipe with a chained request should auto-execute the
 /// dependency request.
-#[derive(Copy, Clone, Debug, Default, Serialize, Deserialize)]
-#[cfg_attr(test, derive(PartialEq))]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case", deny_unknown_fields)]
 pub enum ChainRequestTrigger {
     /// Never trigger the request. This is the default because upstream
@@ -274,11 +694,118 @@ pub enum ChainRequestTrigger {
     Always,
 }
 
+impl Collection {
+    /// Add chains defined in the global config. If the collection defines a
+    /// chain with the same ID, the collection's chain wins. Global chains are
+    /// ordered after the collection's own chains.
+    pub fn merge_global_chains(&mut self, chains: &IndexMap<ChainId, Chain>) {
+        for (chain_id, chain) in chains {
+            if self.chains.contains_key(chain_id) {
+                info!(
+                    %chain_id,
+                    "Collection chain overrides global chain with same ID"
+                );
+            } else {
+                self.chains.insert(chain_id.clone(), chain.clone());
+            }
+        }
+    }
+
+    /// Get the chains that a profile's fields use, in the order they're first
+    /// used. Each is paired with whether rendering it has side effects, i.e.
+    /// it runs a command or triggers a request, either itself or through
+    /// anything it references. Unknown chains are left out.
+    pub fn profile_chains(&self, profile: &Profile) -> Vec<(ChainId, bool)> {
+        profile
+            .data
+            .values()
+            .flat_map(Template::references)
+            .filter_map(|reference| match reference {
+                TemplateReference::Chain(chain_id) => Some(chain_id),
+                _ => None,
+            })
+            .unique()
+            .filter(|chain_id| self.chains.contains_key(chain_id))
+            .map(|chain_id| {
+                let side_effects = self.has_side_effects(
+                    TemplateReference::Chain(chain_id.clone()),
+                    Some(profile),
+                    &mut HashSet::new(),
+                );
+                (chain_id, side_effects)
+            })
+            .collect()
+    }
+
+    /// Does rendering any of these templates have side effects, i.e. run a
+    /// command or trigger a request? Profile fields are looked up in the
+    /// given profile, if any.
+    pub fn templates_have_side_effects<'a>(
+        &self,
+        templates: impl IntoIterator<Item = &'a Template>,
+        profile: Option<&Profile>,
+    ) -> bool {
+        let mut seen = HashSet::new();
+        templates
+            .into_iter()
+            .flat_map(Template::references)
+            .any(|reference| {
+                self.has_side_effects(reference, profile, &mut seen)
+            })
+    }
+
+    /// Does rendering a template reference have side effects? Fields and
+    /// constants are nested templates, so they're followed too. `seen`
+    /// protects against cycles, which fail during rendering anyway.
+    fn has_side_effects(
+        &self,
+        reference: TemplateReference,
+        profile: Option<&Profile>,
+        seen: &mut HashSet<TemplateReference>,
+    ) -> bool {
+        if !seen.insert(reference.clone()) {
+            return false;
+        }
+        let templates = match &reference {
+            TemplateReference::Field(field) => Vec::from_iter(
+                profile.and_then(|profile| profile.data.get(field)),
+            ),
+            TemplateReference::Constant(name) => {
+                Vec::from_iter(self.constants.get(name))
+            }
+            TemplateReference::Chain(chain_id) => {
+                let Some(chain) = self.chains.get(chain_id) else {
+                    return false;
+                };
+                match &chain.source {
+                    ChainSource::Command { .. } => return true,
+                    ChainSource::Request { trigger, .. }
+                        if !matches!(trigger, ChainRequestTrigger::Never) =>
+                    {
+                        return true
+                    }
+                    _ => chain_templates(chain),
+                }
+            }
+        };
+        templates
+            .into_iter()
+            .flat_map(Template::references)
+            .any(|reference| self.has_side_effects(reference, profile, seen))
+    }
+}
+
 impl Profile {
     /// Get a presentable name for this profile
     pub fn name(&self) -> &str {
         self.name.as_deref().unwrap_or(&self.id)
     }
+
+    /// Should this profile be called out in the TUI? True if it has a color
+    /// or label
+    pub fn is_flagged(&self) -> bool {
+        self.color.is_some() || self.label.is_some()
+    }
 }
 
 impl Folder {
@@ -288,11 +815,89 @@ impl Folder {
     }
 }
 
+impl Multipart {
+    fn default_subtype() -> String {
+        "form-data".into()
+    }
+}
+
 impl Recipe {
     /// Get a presentable name for this recipe
     pub fn name(&self) -> &str {
         self.name.as_deref().unwrap_or(&self.id)
     }
+
+    /// Escape literal text for use as a body. A leading `@` in a body means
+    /// "load from a file", so text that starts with `@` is escaped as `@@`.
+    /// Used when importing bodies from other formats.
+    pub(super) fn escape_body(body: String) -> String {
+        if body.starts_with('@') {
+            format!("@{body}")
+        } else {
+            body
+        }
+    }
+
+    /// Get the authentication to use for this recipe under a profile. If the
+    /// recipe has authentication and the profile defines its own, the
+    /// profile's block replaces the recipe's entirely. Fields aren't merged,
+    /// because different schemes don't share fields. Recipes without
+    /// authentication (e.g. a login endpoint) never get any from the profile.
+    pub fn authentication<'a>(
+        &'a self,
+        profile: Option<&'a Profile>,
+    ) -> Option<&'a Authentication> {
+        let authentication = self.authentication.as_ref()?;
+        Some(
+            profile
+                .and_then(|profile| profile.authentication.as_ref())
+                .unwrap_or(authentication),
+        )
+    }
+}
+
+/// Default for [Recipe::persist] and [crate::http::Request::persist]
+pub(crate) fn persist_default() -> bool {
+    true
+}
+
+/// Omit [Recipe::persist] from serialization when it has the default value
+fn is_persist_default(persist: &bool) -> bool {
+    *persist == persist_default()
+}
+
+/// Omit a field from serialization when it has the default value, so
+/// serialized collections only include what the user would have written
+fn is_default<T: Default + PartialEq>(value: &T) -> bool {
+    *value == T::default()
+}
+
+impl ChainRequestSection {
+    /// Is this the default section? Used to omit it from serialization
+    fn is_body(&self) -> bool {
+        matches!(self, Self::Body)
+    }
+}
+
+impl Poll {
+    fn default_interval() -> Duration {
+        Duration::from_secs(1)
+    }
+
+    fn default_max_attempts() -> u32 {
+        10
+    }
+
+    /// Does a response meet the poll condition, given the rendered target
+    /// value? A body that can't be parsed or doesn't contain the selected
+    /// value just means the condition isn't met (yet).
+    pub fn is_satisfied(&self, response: &Response, expected: &str) -> bool {
+        response
+            .parse_body()
+            .ok()
+            .and_then(|body| self.selector.query_to_string(&*body).ok())
+            .is_some_and(|value| value == expected)
+    }
 }
 
 /// For deserialization
@@ -316,3 +921,168 @@ impl From<Method> for String {
         method.to_string()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_util::*, util::parse_yaml};
+    use factori::create;
+    use indexmap::indexmap;
+    use rstest::rstest;
+
+    /// An unknown default profile still loads, so each frontend can warn and
+    /// fall back from it
+    #[rstest]
+    #[case::known("default_profile: dev", Some("dev"))]
+    #[case::unknown("default_profile: prod", Some("prod"))]
+    #[case::none("", None)]
+    fn test_deserialize_default_profile(
+        #[case] yaml: &str,
+        #[case] expected: Option<&str>,
+    ) {
+        let yaml = format!("{yaml}\nprofiles: {{dev: {{data: {{}}}}}}");
+        let collection: Collection = parse_yaml(yaml.as_bytes()).unwrap();
+        assert_eq!(collection.default_profile, expected.map(ProfileId::from));
+        assert!(collection.profiles.contains_key(&ProfileId::from("dev")));
+    }
+
+    /// Global chains are added, unless the collection already has that ID
+    #[test]
+    fn test_merge_global_chains() {
+        let local = create!(Chain, id: "token".into());
+        let mut collection = create!(
+            Collection,
+            chains: indexmap! {"token".into() => local.clone()},
+        );
+        let global_token = create!(
+            Chain,
+            id: "token".into(),
+            source: ChainSource::Prompt {
+                message: None,
+                default: None,
+                pattern: None,
+            },
+        );
+        let global_user = create!(Chain, id: "user".into());
+        collection.merge_global_chains(&indexmap! {
+            "token".into() => global_token,
+            "user".into() => global_user.clone(),
+        });
+
+        assert_eq!(
+            collection.chains,
+            indexmap! {
+                "token".into() => local,
+                "user".into() => global_user,
+            }
+        );
+    }
+
+    /// Only chains used by the profile are included, and anything that can
+    /// run a command or send a request is flagged, even indirectly
+    #[test]
+    fn test_profile_chains() {
+        let file = |path: &str| ChainSource::File { path: path.into() };
+        let request = |trigger| ChainSource::Request {
+            recipe: "recipe1".into(),
+            trigger,
+            section: Default::default(),
+            timeout: None,
+            max_body_size: None,
+        };
+        let command = ChainSource::Command {
+            command: vec!["whoami".into()],
+        };
+        let chains = [
+            ("safe", file("token.txt")),
+            ("command", command.clone()),
+            ("nested", file("{{chains.command}}")),
+            ("via_field", file("{{command_field}}")),
+            ("never", request(ChainRequestTrigger::Never)),
+            ("always", request(ChainRequestTrigger::Always)),
+            ("unused", command),
+        ];
+        let collection = create!(
+            Collection,
+            chains: chains
+                .into_iter()
+                .map(|(id, source)| {
+                    (id.into(), create!(Chain, id: id.into(), source: source))
+                })
+                .collect(),
+        );
+        let profile = create!(
+            Profile,
+            data: indexmap! {
+                "a".into() => "{{chains.safe}}{{chains.nested}}".into(),
+                "command_field".into() => "{{chains.command}}".into(),
+                "b".into() => "{{chains.via_field}}{{chains.safe}}".into(),
+                "c".into() => "{{chains.never}}{{chains.always}}".into(),
+                "d".into() => "{{chains.unknown}}".into(),
+            },
+        );
+
+        assert_eq!(
+            collection.profile_chains(&profile),
+            [
+                ("safe".into(), false),
+                ("nested".into(), true),
+                ("command".into(), true),
+                ("via_field".into(), true),
+                ("never".into(), false),
+                ("always".into(), true),
+            ]
+        );
+    }
+
+    #[rstest]
+    #[case::met(r#"{"status": "done"}"#, "done", true)]
+    #[case::not_met(r#"{"status": "pending"}"#, "done", false)]
+    #[case::number(r#"{"status": 3}"#, "3", true)]
+    #[case::missing(r#"{"state": "done"}"#, "done", false)]
+    #[case::invalid_body("not json", "done", false)]
+    fn test_poll_is_satisfied(
+        #[case] body: &str,
+        #[case] expected: &str,
+        #[case] satisfied: bool,
+    ) {
+        let poll = Poll {
+            selector: "$.status".parse().unwrap(),
+            value: expected.into(),
+            interval: Poll::default_interval(),
+            max_attempts: Poll::default_max_attempts(),
+        };
+        let response = create!(
+            Response,
+            headers: header_map([("content-type", "application/json")]),
+            body: body.into(),
+        );
+        assert_eq!(poll.is_satisfied(&response, expected), satisfied);
+    }
+
+    #[rstest]
+    #[case::none("{data: {}}", None, None, false)]
+    #[case::color(
+        "{color: red, data: {}}",
+        Some(ProfileColor::Red),
+        None,
+        true
+    )]
+    #[case::label(
+        "{label: PRODUCTION, data: {}}",
+        None,
+        Some("PRODUCTION"),
+        true
+    )]
+    fn test_profile_flag(
+        #[case] yaml: &str,
+        #[case] expected_color: Option<ProfileColor>,
+        #[case] expected_label: Option<&str>,
+        #[case] expected_flagged: bool,
+    ) {
+        let profile: Profile = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(profile.color, expected_color);
+        assert_eq!(profile.label.as_deref(), expected_label);
+        assert_eq!(profile.is_flagged(), expected_flagged);
+    }
+}
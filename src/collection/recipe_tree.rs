@@ -99,6 +99,17 @@ impl RecipeTree {
         self.get(id).and_then(RecipeNode::recipe)
     }
 
+    /// Is this folder/recipe disabled, either directly or because one of its
+    /// ancestor folders is? Unknown IDs are *not* disabled.
+    pub fn is_disabled(&self, id: &RecipeId) -> bool {
+        self.get_lookup_key(id).is_some_and(|lookup_key| {
+            lookup_key
+                .as_slice()
+                .iter()
+                .any(|id| self.get(id).is_some_and(RecipeNode::is_disabled))
+        })
+    }
+
     /// Get all **recipe** IDs in the tree. Useful for printing a list to the
     /// user
     pub fn recipe_ids(&self) -> impl Iterator<Item = &RecipeId> {
@@ -107,6 +118,11 @@ impl RecipeTree {
             .filter(|id| self.get_recipe(id).is_some())
     }
 
+    /// Consume the tree, returning its top-level nodes
+    pub fn into_tree(self) -> IndexMap<RecipeId, RecipeNode> {
+        self.tree
+    }
+
     /// Get a flat iterator over all nodes in the tree, using depth first
     /// search. Each yielded item will include the lookup key to retrieve
     /// that item.
@@ -198,6 +214,15 @@ impl RecipeNode {
         }
     }
 
+    /// Is this folder/recipe disabled itself? This doesn't account for
+    /// ancestors; use [RecipeTree::is_disabled] for that.
+    pub fn is_disabled(&self) -> bool {
+        match self {
+            RecipeNode::Folder(folder) => folder.disabled,
+            RecipeNode::Recipe(recipe) => recipe.disabled,
+        }
+    }
+
     /// If this node is a recipe, return it. Otherwise return `None`
     pub fn recipe(&self) -> Option<&Recipe> {
         match self {
@@ -316,6 +341,45 @@ mod tests {
         );
     }
 
+    /// Disabling a folder disables everything under it
+    #[rstest]
+    #[case::recipe("r3", &["r3"])]
+    #[case::folder("f2", &["f2", "r2"])]
+    #[case::root_folder("f1", &["f1", "f2", "r2", "r3"])]
+    fn test_is_disabled(
+        mut tree: IndexMap<RecipeId, RecipeNode>,
+        #[case] disable: &str,
+        #[case] expected: &[&str],
+    ) {
+        fn disable_node(
+            nodes: &mut IndexMap<RecipeId, RecipeNode>,
+            target: &RecipeId,
+        ) {
+            for (id, node) in nodes {
+                match node {
+                    RecipeNode::Folder(folder) => {
+                        folder.disabled = id == target;
+                        disable_node(&mut folder.children, target);
+                    }
+                    RecipeNode::Recipe(recipe) => {
+                        recipe.disabled = id == target
+                    }
+                }
+            }
+        }
+
+        disable_node(&mut tree, &id(disable));
+        let tree = RecipeTree::new(tree).unwrap();
+        let disabled = tree
+            .iter()
+            .map(|(_, node)| node.id())
+            .filter(|id| tree.is_disabled(id))
+            .map(|id| id.as_str())
+            .collect_vec();
+        assert_eq!(disabled, expected);
+        assert!(!tree.is_disabled(&id("unknown")));
+    }
+
     /// Deserializing with a duplicate ID anywhere in the tree should fail
     #[rstest]
     #[case::anywhere(
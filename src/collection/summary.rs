@@ -0,0 +1,338 @@
+//! Summarize what's in a collection, for getting to know an unfamiliar one
+
+use crate::{
+    collection::{
+        export::{authentication_templates, chain_templates, recipe_templates},
+        ChainId, ChainSource, Collection, ProfileId, RecipeId,
+    },
+    template::{Template, TemplateReference},
+};
+use indexmap::IndexSet;
+use itertools::Itertools;
+use serde::Serialize;
+use std::fmt::{self, Display};
+
+/// Overview of a collection's contents, and the template references between
+/// them. See [Collection::summary]
+#[derive(Debug, Serialize)]
+pub struct CollectionSummary {
+    pub recipe_count: usize,
+    pub folder_count: usize,
+    pub profiles: Vec<ProfileSummary>,
+    pub chains: Vec<ChainSummary>,
+    pub recipes: Vec<RecipeSummary>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProfileSummary {
+    pub id: ProfileId,
+    pub name: Option<String>,
+    /// Is this the collection's `default_profile`?
+    pub default: bool,
+    /// References from the profile's fields, base URL, headers, and
+    /// authentication
+    #[serde(flatten)]
+    pub references: References,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChainSummary {
+    pub id: ChainId,
+    /// Source type, as written in YAML, e.g. `request`
+    pub source: &'static str,
+    /// For `!request` chains, the recipe the value comes from
+    pub recipe: Option<RecipeId>,
+    #[serde(flatten)]
+    pub references: References,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RecipeSummary {
+    pub id: RecipeId,
+    #[serde(flatten)]
+    pub references: References,
+}
+
+/// Chains, constants, and profile fields used in templates, each listed once
+/// in the order they first appear. References aren't followed, so this only
+/// includes direct dependencies.
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct References {
+    pub chains: Vec<ChainId>,
+    pub constants: Vec<String>,
+    pub fields: Vec<String>,
+}
+
+impl Collection {
+    /// Summarize the collection's contents, in the order they're defined
+    pub fn summary(&self) -> CollectionSummary {
+        let (folders, recipes): (Vec<_>, Vec<_>) = self
+            .recipes
+            .iter()
+            .map(|(_, node)| node)
+            .partition(|node| node.folder().is_some());
+        let recipes = recipes
+            .into_iter()
+            .filter_map(|node| node.recipe())
+            .collect_vec();
+
+        CollectionSummary {
+            recipe_count: recipes.len(),
+            folder_count: folders.len(),
+            profiles: self
+                .profiles
+                .values()
+                .map(|profile| {
+                    let mut templates = Vec::from_iter(&profile.base_url);
+                    templates.extend(profile.headers.values());
+                    templates.extend(profile.data.values());
+                    if let Some(authentication) = &profile.authentication {
+                        templates
+                            .extend(authentication_templates(authentication));
+                    }
+                    ProfileSummary {
+                        id: profile.id.clone(),
+                        name: profile.name.clone(),
+                        default: self.default_profile.as_ref()
+                            == Some(&profile.id),
+                        references: References::new(templates),
+                    }
+                })
+                .collect(),
+            chains: self
+                .chains
+                .values()
+                .map(|chain| ChainSummary {
+                    id: chain.id.clone(),
+                    source: source_type(&chain.source),
+                    recipe: match &chain.source {
+                        ChainSource::Request { recipe, .. } => {
+                            Some(recipe.clone())
+                        }
+                        _ => None,
+                    },
+                    references: References::new(chain_templates(chain)),
+                })
+                .collect(),
+            recipes: recipes
+                .into_iter()
+                .map(|recipe| RecipeSummary {
+                    id: recipe.id.clone(),
+                    references: References::new(recipe_templates(recipe)),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl References {
+    fn new<'a>(templates: impl IntoIterator<Item = &'a Template>) -> Self {
+        let mut chains = IndexSet::new();
+        let mut constants = IndexSet::new();
+        let mut fields = IndexSet::new();
+        for reference in templates.into_iter().flat_map(Template::references) {
+            match reference {
+                TemplateReference::Chain(chain_id) => {
+                    chains.insert(chain_id);
+                }
+                TemplateReference::Constant(name) => {
+                    constants.insert(name);
+                }
+                TemplateReference::Field(field) => {
+                    fields.insert(field);
+                }
+            }
+        }
+        Self {
+            chains: chains.into_iter().collect(),
+            constants: constants.into_iter().collect(),
+            fields: fields.into_iter().collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chains.is_empty()
+            && self.constants.is_empty()
+            && self.fields.is_empty()
+    }
+}
+
+/// Name of the YAML tag for a chain source
+fn source_type(source: &ChainSource) -> &'static str {
+    match source {
+        ChainSource::Request { .. } => "request",
+        ChainSource::LastResponse => "last_response",
+        ChainSource::Command { .. } => "command",
+        ChainSource::File { .. } => "file",
+        ChainSource::Prompt { .. } => "prompt",
+        ChainSource::Patch { .. } => "patch",
+    }
+}
+
+/// Multi-line, human-readable summary. Dependencies are only listed for items
+/// that have any.
+impl Display for CollectionSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} recipe{} in {} folder{}",
+            self.recipe_count,
+            plural(self.recipe_count),
+            self.folder_count,
+            plural(self.folder_count),
+        )?;
+
+        writeln!(f, "Profiles: {}", self.profiles.len())?;
+        for profile in &self.profiles {
+            write!(f, "  {}", profile.id)?;
+            if let Some(name) = &profile.name {
+                write!(f, " ({name})")?;
+            }
+            if profile.default {
+                write!(f, " [default]")?;
+            }
+            writeln!(f)?;
+            write_references(f, &profile.references)?;
+        }
+
+        writeln!(f, "Chains: {}", self.chains.len())?;
+        for chain in &self.chains {
+            write!(f, "  {}: !{}", chain.id, chain.source)?;
+            if let Some(recipe) = &chain.recipe {
+                write!(f, " {recipe}")?;
+            }
+            writeln!(f)?;
+            write_references(f, &chain.references)?;
+        }
+
+        let recipes = self
+            .recipes
+            .iter()
+            .filter(|recipe| !recipe.references.is_empty())
+            .collect_vec();
+        if !recipes.is_empty() {
+            writeln!(f, "Recipe dependencies:")?;
+            for recipe in recipes {
+                writeln!(f, "  {}", recipe.id)?;
+                write_references(f, &recipe.references)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn plural(count: usize) -> &'static str {
+    if count == 1 {
+        ""
+    } else {
+        "s"
+    }
+}
+
+/// Write references as template keys, e.g. `uses: chains.token,
+/// const.version, host`
+fn write_references(
+    f: &mut fmt::Formatter<'_>,
+    references: &References,
+) -> fmt::Result {
+    if references.is_empty() {
+        return Ok(());
+    }
+    let keys = references
+        .chains
+        .iter()
+        .map(|chain_id| format!("chains.{chain_id}"))
+        .chain(
+            references
+                .constants
+                .iter()
+                .map(|name| format!("const.{name}")),
+        )
+        .chain(references.fields.iter().cloned());
+    writeln!(f, "    uses: {}", keys.format(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::parse_yaml;
+
+    #[test]
+    fn test_summary() {
+        let collection: Collection = parse_yaml(
+            br#"
+            default_profile: dev
+            profiles:
+              dev:
+                name: Development
+                data:
+                  host: https://dev
+                  user: "{{username}}"
+              prod:
+                data:
+                  host: https://prod
+            constants:
+              version: v2
+            chains:
+              token:
+                source: !request
+                  recipe: login
+                selector: $.token
+              password:
+                source: !file
+                  path: "{{user}}.txt"
+            requests:
+              login: !request
+                method: POST
+                url: "{{host}}/login"
+                body: '{"password": "{{chains.password}}", "host": "{{host}}"}'
+              users: !folder
+                requests:
+                  get_user: !request
+                    method: GET
+                    url: "{{host}}/{{const.version}}/users/me"
+                    headers:
+                      Authorization: "Bearer {{chains.token}}"
+              health: !request
+                method: GET
+                url: https://example.com/health
+            "#,
+        )
+        .unwrap();
+
+        let summary = collection.summary();
+        assert_eq!(summary.recipe_count, 3);
+        assert_eq!(summary.folder_count, 1);
+        assert_eq!(
+            summary.recipes[0].references,
+            References {
+                chains: vec!["password".into()],
+                constants: vec![],
+                fields: vec!["host".into()],
+            }
+        );
+        assert_eq!(
+            summary.to_string(),
+            "3 recipes in 1 folder
+Profiles: 2
+  dev (Development) [default]
+    uses: username
+  prod
+Chains: 2
+  token: !request login
+  password: !file
+    uses: user
+Recipe dependencies:
+  login
+    uses: chains.password, host
+  get_user
+    uses: chains.token, const.version, host
+"
+        );
+
+        let json = serde_json::to_value(&summary).unwrap();
+        assert_eq!(json["chains"][0]["source"], "request");
+        assert_eq!(json["chains"][0]["recipe"], "login");
+        assert_eq!(json["recipes"][1]["chains"][0], "token");
+    }
+}
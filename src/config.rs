@@ -1,4 +1,7 @@
 use crate::{
+    collection::{self, Chain, ChainId, CollectionFile},
+    http::{RedirectHeaderPolicy, TlsVersions},
+    template::PathRoot,
     tui::input::{Action, InputBinding},
     util::{
         parse_yaml,
@@ -8,8 +11,9 @@ use crate::{
 };
 use anyhow::Context;
 use indexmap::IndexMap;
+use reqwest::Method;
 use serde::{Deserialize, Serialize};
-use std::fs;
+use std::{fs, net::IpAddr, path::PathBuf};
 use tracing::info;
 
 /// App-level configuration, which is global across all sessions and
@@ -19,12 +23,80 @@ use tracing::info;
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(default, deny_unknown_fields)]
 pub struct Config {
+    /// Where the database, logs, and other generated files are stored. If
+    /// relative, this is relative to the directory containing the config
+    /// file. Applied at startup by [DataDirectory::init].
+    pub data_directory: Option<PathBuf>,
     /// TLS cert errors on these hostnames are ignored. Be careful!
-    #[serde(default)]
     pub ignore_certificate_hosts: Vec<String>,
+    /// Which headers to forward when a redirect goes to a different origin
+    pub redirect_header_policy: RedirectHeaderPolicy,
+    /// Maximum number of requests per second to send to each host. Requests
+    /// over the limit are queued until a slot is available.
+    pub rate_limits: IndexMap<String, f64>,
+    /// Connect to a fixed address for these hostnames, instead of looking
+    /// them up in DNS. The URL's hostname is still used for the `Host` header
+    /// and TLS SNI.
+    pub resolve: IndexMap<String, IpAddr>,
+    /// Tuning for how HTTP connections are reused between requests
+    pub connection_pool: ConnectionPool,
+    /// Send header names in Title-Case (e.g. `Content-Type`) over HTTP/1,
+    /// instead of lowercase. The casing written in the collection can't be
+    /// kept as-is, so this is the closest we can get for servers that care.
+    pub title_case_headers: bool,
+    /// Allowed TLS versions for every request. Recipes can override either
+    /// bound with their own `tls` field.
+    pub tls: TlsVersions,
     /// Should templates be rendered inline in the UI, or should we show the
     /// raw text?
     pub preview_templates: bool,
+    /// Should UI state (selected recipe/profile, pane, tabs, etc.) be saved
+    /// and restored between sessions? If disabled, every session starts
+    /// fresh.
+    pub persist_ui_state: bool,
+    /// Should completed requests be saved to history? If disabled, nothing is
+    /// written to disk. Responses are still visible in the TUI for the rest
+    /// of the session, but are lost when it exits.
+    pub persist_history: bool,
+    /// Instead of sending requests, replay the most recent matching response
+    /// from history. Useful for demos, or when the network is unreliable.
+    pub offline: bool,
+    /// Remember `ETag`/`Last-Modified` from responses, and send them back
+    /// with the next identical request. If the server responds `304 Not
+    /// Modified`, the previous body is reused.
+    pub conditional_requests: bool,
+    /// What relative file paths in the collection (e.g. `!file` chains) are
+    /// resolved against
+    pub relative_paths: RelativePaths,
+    /// Response times above these thresholds are highlighted in the TUI
+    pub latency_thresholds: LatencyThresholds,
+    /// Ask for confirmation before sending requests with these methods from
+    /// the TUI (case-insensitive). Recipes can override this with `confirm`.
+    pub confirm_methods: Vec<String>,
+    /// Open every request in the user's editor before it's sent from the TUI,
+    /// so it can be tweaked
+    pub review_requests: bool,
+    /// Should the response pane flag GraphQL responses that contain errors?
+    /// GraphQL servers often return errors with a 200 status.
+    pub highlight_graphql_errors: bool,
+    /// Display numbers in JSON responses exactly as the server sent them.
+    /// Disable this to normalize them instead, e.g. `1E5` as `100000.0`.
+    /// Numbers that don't fit in a 64-bit integer or float are then rounded.
+    pub exact_json_numbers: bool,
+    /// Commands to format response bodies that Slumber can't parse itself,
+    /// keyed by content type. The raw body is piped to the command, and its
+    /// stdout is displayed in the TUI instead.
+    pub formatters: IndexMap<String, Vec<String>>,
+    /// Leave disabled recipes and folders out of the TUI's recipe list,
+    /// instead of graying them out
+    pub hide_disabled_recipes: bool,
+    /// How the TUI arranges the request and response panes
+    pub layout: LayoutConfig,
+
+    /// Chains available to every collection. A chain in the collection with
+    /// the same ID takes precedence.
+    #[serde(deserialize_with = "collection::deserialize_id_map")]
+    pub chains: IndexMap<ChainId, Chain>,
 
     /// Overrides for default key bindings
     pub input_bindings: IndexMap<Action, InputBinding>,
@@ -33,6 +105,26 @@ pub struct Config {
 impl Config {
     const FILE: &'static str = "config.yml";
 
+    /// Should requests with this method be confirmed before sending? This
+    /// only applies to recipes that don't set `confirm`
+    pub fn should_confirm(&self, method: &Method) -> bool {
+        self.confirm_methods
+            .iter()
+            .any(|confirm| confirm.eq_ignore_ascii_case(method.as_str()))
+    }
+
+    /// Get the formatter command for a `Content-Type` header value, if one is
+    /// configured. Parameters (e.g. `charset`) are ignored, and content types
+    /// are case-insensitive.
+    pub fn formatter(&self, content_type: &[u8]) -> Option<&[String]> {
+        let content_type = std::str::from_utf8(content_type).ok()?;
+        let essence = content_type.split(';').next()?.trim();
+        self.formatters
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(essence))
+            .map(|(_, command)| command.as_slice())
+    }
+
     /// Load configuration from the file, if present. If not, just return a
     /// default value. This only returns an error if the file could be read, but
     /// deserialization failed. This is *not* async because it's only run during
@@ -58,18 +150,172 @@ impl Config {
         }
     }
 
-    /// Path to the configuration file
+    /// Path to the configuration file. This isn't affected by the
+    /// `data_directory` field, for obvious reasons.
     pub fn path() -> FileGuard {
-        DataDirectory::root().file(Self::FILE)
+        DataDirectory::base().file(Self::FILE)
+    }
+
+    /// Get just the `data_directory` field from the configuration file. This
+    /// is needed at startup, before logging is set up and the rest of the
+    /// config is loaded. Any error is left for [Self::load] to report, so
+    /// this falls back to the default.
+    pub fn load_data_directory() -> Option<PathBuf> {
+        #[derive(Deserialize)]
+        struct Partial {
+            data_directory: Option<PathBuf>,
+        }
+
+        let bytes = fs::read(Self::path().create_parent().ok()?).ok()?;
+        parse_yaml::<Partial>(&bytes).ok()?.data_directory
+    }
+
+    /// Get what relative file paths in a collection should be resolved
+    /// against. If they're relative to the collection but it isn't on the file
+    /// system, they can't be resolved.
+    pub fn path_root(&self, collection_file: &CollectionFile) -> PathRoot {
+        match self.relative_paths {
+            RelativePaths::Collection => match collection_file.directory() {
+                Some(directory) => PathRoot::Directory(directory.to_owned()),
+                None => PathRoot::Disallowed,
+            },
+            RelativePaths::CurrentDirectory => PathRoot::CurrentDirectory,
+        }
     }
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
+            data_directory: None,
             ignore_certificate_hosts: Vec::new(),
+            redirect_header_policy: RedirectHeaderPolicy::default(),
+            rate_limits: IndexMap::default(),
+            resolve: IndexMap::default(),
+            connection_pool: ConnectionPool::default(),
+            title_case_headers: false,
+            tls: TlsVersions::default(),
             preview_templates: true,
+            persist_ui_state: true,
+            persist_history: true,
+            offline: false,
+            conditional_requests: false,
+            relative_paths: RelativePaths::default(),
+            latency_thresholds: LatencyThresholds::default(),
+            confirm_methods: vec!["DELETE".into()],
+            review_requests: false,
+            highlight_graphql_errors: false,
+            exact_json_numbers: true,
+            formatters: IndexMap::default(),
+            hide_disabled_recipes: false,
+            layout: LayoutConfig::default(),
+            chains: IndexMap::default(),
             input_bindings: IndexMap::default(),
         }
     }
 }
+
+/// What relative file paths in a collection are resolved against
+#[derive(Copy, Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RelativePaths {
+    /// The directory containing the collection file, so the collection works
+    /// the same no matter where Slumber is launched from
+    #[default]
+    Collection,
+    /// The directory Slumber was launched from. This is how paths were
+    /// resolved in older versions.
+    CurrentDirectory,
+}
+
+/// Layout of the request and response panes in the TUI. The layout can be
+/// changed during a session, but that isn't saved back here.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LayoutConfig {
+    /// Layout to use when the terminal is at least [Self::narrow_width] wide
+    pub mode: ExchangeLayout,
+    /// Layout to switch to when the terminal is narrower than
+    /// [Self::narrow_width]. If `None`, [Self::mode] is always used.
+    pub narrow_mode: Option<ExchangeLayout>,
+    /// Width of the terminal (in columns) below which [Self::narrow_mode]
+    /// applies
+    pub narrow_width: u16,
+}
+
+impl LayoutConfig {
+    /// Get the layout for a terminal of the given width
+    pub fn mode(&self, width: u16) -> ExchangeLayout {
+        match self.narrow_mode {
+            Some(narrow_mode) if width < self.narrow_width => narrow_mode,
+            _ => self.mode,
+        }
+    }
+}
+
+impl Default for LayoutConfig {
+    fn default() -> Self {
+        Self {
+            mode: ExchangeLayout::default(),
+            narrow_mode: None,
+            narrow_width: 120,
+        }
+    }
+}
+
+/// How the request and response panes are arranged relative to each other
+#[derive(Copy, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExchangeLayout {
+    /// Side by side, below the recipe pane
+    Split,
+    /// One on top of the other, below the recipe pane
+    #[default]
+    Stacked,
+    /// Only one is shown at a time, depending on which pane is selected
+    Tabbed,
+}
+
+impl ExchangeLayout {
+    /// The layout after this one, for cycling through them
+    pub fn next(self) -> Self {
+        match self {
+            Self::Split => Self::Stacked,
+            Self::Stacked => Self::Tabbed,
+            Self::Tabbed => Self::Split,
+        }
+    }
+}
+
+/// Settings for the HTTP connection pool. Anything omitted uses the HTTP
+/// client's default. Durations are in seconds.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConnectionPool {
+    /// Maximum number of idle connections to keep open for each host. `0`
+    /// disables reuse, so every request opens a fresh connection.
+    pub max_idle_per_host: Option<usize>,
+    /// Close connections that have been idle for this long
+    pub idle_timeout: Option<u64>,
+    /// Interval for TCP keep-alive probes on open connections
+    pub tcp_keepalive: Option<u64>,
+}
+
+/// Thresholds for highlighting slow responses, in milliseconds
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LatencyThresholds {
+    /// Responses slower than this are shown as a warning
+    pub slow: u64,
+    /// Responses slower than this are shown as an error
+    pub very_slow: u64,
+}
+
+impl Default for LatencyThresholds {
+    fn default() -> Self {
+        Self {
+            slow: 1000,
+            very_slow: 5000,
+        }
+    }
+}
@@ -2,7 +2,7 @@
 //! responses.
 
 use crate::{
-    collection::{ProfileId, RecipeId},
+    collection::{CollectionSource, ProfileId, RecipeId},
     http::{RequestId, RequestRecord},
     util::{
         paths::{DataDirectory, FileGuard},
@@ -10,6 +10,7 @@ use crate::{
     },
 };
 use anyhow::{anyhow, Context};
+use chrono::Utc;
 use derive_more::Display;
 use rusqlite::{
     named_params,
@@ -129,6 +130,10 @@ impl Database {
             // serialization of all binary blobs, so there's no easy way to
             // migrate it all. It's easiest just to wipe it all out.
             M::up("DELETE FROM requests; DELETE FROM ui_state;").down(""),
+            // Null for collections that haven't been opened since this was
+            // added
+            M::up("ALTER TABLE collections ADD COLUMN last_opened TEXT")
+                .down("ALTER TABLE collections DROP COLUMN last_opened"),
         ]);
         migrations.to_latest(connection)?;
         Ok(())
@@ -149,6 +154,25 @@ impl Database {
             .context("Error extracting collection data")
     }
 
+    /// Get the most recently opened collections, newest first. Paths are
+    /// returned as stored, so some may no longer exist
+    pub fn recent_collections(
+        &self,
+        limit: usize,
+    ) -> anyhow::Result<Vec<PathBuf>> {
+        self.connection()
+            .prepare(
+                "SELECT path FROM collections WHERE last_opened IS NOT NULL
+                ORDER BY last_opened DESC LIMIT :limit",
+            )?
+            .query_map(named_params! {":limit": limit}, |row| {
+                Ok(row.get::<_, Bytes<_>>("path")?.0)
+            })
+            .context("Error fetching recent collections")?
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .context("Error extracting collection data")
+    }
+
     /// Migrate all data for one collection into another, deleting the source
     /// collection
     pub fn merge_collections(
@@ -221,7 +245,9 @@ impl Database {
 
     /// Convert this database connection into a handle for a single collection
     /// file. This will store the collection in the DB if it isn't already,
-    /// then grab its generated ID to create a [CollectionDatabase].
+    /// then grab its generated ID to create a [CollectionDatabase]. The
+    /// collection is also marked as opened now, for
+    /// [Self::recent_collections].
     pub fn into_collection(
         self,
         path: &Path,
@@ -233,11 +259,13 @@ impl Database {
         // return anything if the insert didn't modify
         self.connection()
             .execute(
-                "INSERT INTO collections (id, path) VALUES (:id, :path)
-                ON CONFLICT(path) DO NOTHING",
+                "INSERT INTO collections (id, path, last_opened)
+                VALUES (:id, :path, :last_opened)
+                ON CONFLICT(path) DO UPDATE SET last_opened = :last_opened",
                 named_params! {
                     ":id": CollectionId(Uuid::new_v4()),
                     ":path": &path,
+                    ":last_opened": Utc::now(),
                 },
             )
             .context("Error setting collection ID")
@@ -323,6 +351,82 @@ impl CollectionDatabase {
             .traced()
     }
 
+    /// Get the most recent request+response for a profile+recipe that
+    /// satisfies a predicate, or `None` if there isn't one. Profiles are
+    /// matched the same as in [Self::get_last_request].
+    pub fn find_last_request(
+        &self,
+        profile_id: Option<&ProfileId>,
+        recipe_id: &RecipeId,
+        predicate: impl Fn(&RequestRecord) -> bool,
+    ) -> anyhow::Result<Option<RequestRecord>> {
+        let connection = self.database.connection();
+        // Shitty try block
+        let result = (|| -> rusqlite::Result<_> {
+            let mut statement = connection.prepare(
+                "SELECT * FROM requests
+                WHERE collection_id = :collection_id
+                    AND profile_id IS :profile_id
+                    AND recipe_id = :recipe_id
+                ORDER BY start_time DESC",
+            )?;
+            let mut rows = statement.query(named_params! {
+                ":collection_id": self.collection_id,
+                ":profile_id": profile_id,
+                ":recipe_id": recipe_id,
+            })?;
+            // Deserialize lazily, so we can stop at the first match
+            while let Some(row) = rows.next()? {
+                let record = RequestRecord::try_from(row)?;
+                if predicate(&record) {
+                    return Ok(Some(record));
+                }
+            }
+            Ok(None)
+        })();
+        result
+            .with_context(|| {
+                format!(
+                    "Error fetching request [profile={}; recipe={}] \
+                    from database",
+                    profile_id.map(ProfileId::to_string).unwrap_or_default(),
+                    recipe_id
+                )
+            })
+            .traced()
+    }
+
+    /// Get the most recent request+response for a profile, from any recipe.
+    /// Return `None` if no response has been received for the profile. As with
+    /// [Self::get_last_request], a `None` profile matches requests that have no
+    /// associated profile.
+    pub fn get_last_request_any_recipe(
+        &self,
+        profile_id: Option<&ProfileId>,
+    ) -> anyhow::Result<Option<RequestRecord>> {
+        self.database
+            .connection()
+            .query_row(
+                "SELECT * FROM requests
+                WHERE collection_id = :collection_id
+                    AND profile_id IS :profile_id
+                ORDER BY start_time DESC LIMIT 1",
+                named_params! {
+                    ":collection_id": self.collection_id,
+                    ":profile_id": profile_id,
+                },
+                |row| row.try_into(),
+            )
+            .optional()
+            .with_context(|| {
+                format!(
+                    "Error fetching last request [profile={}] from database",
+                    profile_id.map(ProfileId::to_string).unwrap_or_default(),
+                )
+            })
+            .traced()
+    }
+
     /// Add a new request to history. The HTTP engine is responsible for
     /// inserting its own requests. Only requests that received a valid HTTP
     /// response should be stored. In-flight requests, invalid requests, and
@@ -368,6 +472,64 @@ impl CollectionDatabase {
         Ok(())
     }
 
+    /// Delete a single request from history. Returns `false` if it wasn't
+    /// there, e.g. because it was never saved. Requests from other
+    /// collections are never touched.
+    pub fn delete_request(&self, id: RequestId) -> anyhow::Result<bool> {
+        debug!(%id, "Deleting request record from database");
+        let deleted = self
+            .database
+            .connection()
+            .execute(
+                "DELETE FROM requests
+                WHERE id = :id AND collection_id = :collection_id",
+                named_params! {
+                    ":id": id,
+                    ":collection_id": self.collection_id,
+                },
+            )
+            .context(format!("Error deleting request {id} from database"))
+            .traced()?;
+        Ok(deleted > 0)
+    }
+
+    /// Delete every request for a profile+recipe from history. Profiles are
+    /// matched the same as in [Self::get_last_request]. Returns the number of
+    /// requests deleted.
+    pub fn delete_requests(
+        &self,
+        profile_id: Option<&ProfileId>,
+        recipe_id: &RecipeId,
+    ) -> anyhow::Result<usize> {
+        debug!(
+            ?profile_id,
+            %recipe_id,
+            "Deleting request records from database"
+        );
+        self.database
+            .connection()
+            .execute(
+                "DELETE FROM requests
+                WHERE collection_id = :collection_id
+                    AND profile_id IS :profile_id
+                    AND recipe_id = :recipe_id",
+                named_params! {
+                    ":collection_id": self.collection_id,
+                    ":profile_id": profile_id,
+                    ":recipe_id": recipe_id,
+                },
+            )
+            .with_context(|| {
+                format!(
+                    "Error deleting requests [profile={}; recipe={}] \
+                    from database",
+                    profile_id.map(ProfileId::to_string).unwrap_or_default(),
+                    recipe_id
+                )
+            })
+            .traced()
+    }
+
     /// Get the value of a UI state field
     pub fn get_ui<K, V>(&self, key: K) -> anyhow::Result<Option<V>>
     where
@@ -495,7 +657,8 @@ impl FromSql for RecipeId {
 }
 
 /// Neat little wrapper for a collection path, to make sure it gets
-/// canonicalized and serialized/deserialized consistently
+/// canonicalized and serialized/deserialized consistently. Stdin and URL
+/// collections aren't on the file system, so they're stored as given.
 #[derive(Debug, Display)]
 #[display("{}", _0.0.display())]
 struct CollectionPath(Bytes<PathBuf>);
@@ -510,6 +673,9 @@ impl TryFrom<&Path> for CollectionPath {
     type Error = anyhow::Error;
 
     fn try_from(path: &Path) -> Result<Self, Self::Error> {
+        if !matches!(CollectionSource::new(path), CollectionSource::Path(_)) {
+            return Ok(Self(Bytes(path.to_owned())));
+        }
         path.canonicalize()
             .context(format!("Error canonicalizing path {path:?}"))
             .traced()
@@ -571,8 +737,9 @@ impl<'a, 'b> TryFrom<&'a Row<'b>> for RequestRecord {
 mod tests {
     use super::*;
     use crate::test_util::*;
+    use chrono::Utc;
     use factori::create;
-    use std::collections::HashMap;
+    use std::{collections::HashMap, time::Duration};
 
     #[test]
     fn test_merge() {
@@ -642,6 +809,29 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_recent_collections() {
+        let database = Database::testing();
+        let path1 = Path::new("slumber.yml");
+        let path2 = Path::new("README.md"); // Has to be a real file
+        database.clone().into_collection(path1).unwrap();
+        // Make sure timestamps are distinct
+        std::thread::sleep(Duration::from_millis(2));
+        database.clone().into_collection(path2).unwrap();
+        assert_eq!(
+            database.recent_collections(10).unwrap(),
+            vec![path2.canonicalize().unwrap(), path1.canonicalize().unwrap()]
+        );
+
+        // Re-opening moves it to the front
+        std::thread::sleep(Duration::from_millis(2));
+        database.clone().into_collection(path1).unwrap();
+        assert_eq!(
+            database.recent_collections(1).unwrap(),
+            vec![path1.canonicalize().unwrap()]
+        );
+    }
+
     /// Test request storage and retrieval
     #[test]
     fn test_request() {
@@ -723,6 +913,110 @@ mod tests {
         }
     }
 
+    /// Test fetching the most recent request across all recipes
+    #[test]
+    fn test_last_request_any_recipe() {
+        let collection = CollectionDatabase::testing();
+        let profile_id: ProfileId = "profile1".into();
+        let now = Utc::now();
+
+        // Insert an older request, then a newer one for a different recipe,
+        // then a newer one still but for a different profile
+        let insert =
+            |profile_id: Option<&ProfileId>, recipe_id: &str, age_secs: u64| {
+                let request = create!(
+                    Request,
+                    profile_id: profile_id.cloned(),
+                    recipe_id: recipe_id.into(),
+                );
+                let record = create!(
+                    RequestRecord,
+                    request: request.into(),
+                    start_time: now - Duration::from_secs(age_secs),
+                );
+                collection.insert_request(&record).unwrap();
+                record.id
+            };
+        insert(Some(&profile_id), "recipe1", 3);
+        let expected_id = insert(Some(&profile_id), "recipe2", 2);
+        let other_id = insert(None, "recipe1", 1);
+
+        let last_id = |profile_id| {
+            collection
+                .get_last_request_any_recipe(profile_id)
+                .unwrap()
+                .map(|record| record.id)
+        };
+        assert_eq!(last_id(Some(&profile_id)), Some(expected_id));
+        assert_eq!(last_id(None), Some(other_id));
+        assert_eq!(last_id(Some(&"extra_profile".into())), None);
+    }
+
+    /// Test deleting requests, one at a time and in bulk
+    #[test]
+    fn test_delete_request() {
+        let database = Database::testing();
+        let collection = database
+            .clone()
+            .into_collection(Path::new("slumber.yml"))
+            .unwrap();
+        let other_collection =
+            database.into_collection(Path::new("README.md")).unwrap();
+        let profile_id: ProfileId = "profile1".into();
+        let recipe_id: RecipeId = "recipe1".into();
+        let now = Utc::now();
+
+        let insert = |collection: &CollectionDatabase,
+                      profile_id: Option<&ProfileId>,
+                      age_secs: u64| {
+            let request = create!(
+                Request,
+                profile_id: profile_id.cloned(),
+                recipe_id: recipe_id.clone(),
+            );
+            let record = create!(
+                RequestRecord,
+                request: request.into(),
+                start_time: now - Duration::from_secs(age_secs),
+            );
+            collection.insert_request(&record).unwrap();
+            record.id
+        };
+        let older_id = insert(&collection, Some(&profile_id), 3);
+        let newer_id = insert(&collection, Some(&profile_id), 2);
+        let no_profile_id = insert(&collection, None, 1);
+        let other_id = insert(&other_collection, Some(&profile_id), 1);
+        let last_id = |collection: &CollectionDatabase, profile_id| {
+            collection
+                .get_last_request(profile_id, &recipe_id)
+                .unwrap()
+                .map(|record| record.id)
+        };
+
+        // Deleting the latest request exposes the one before it
+        assert!(collection.delete_request(newer_id).unwrap());
+        assert_eq!(last_id(&collection, Some(&profile_id)), Some(older_id));
+        assert!(!collection.delete_request(newer_id).unwrap());
+        // Other collections are off limits
+        assert!(!collection.delete_request(other_id).unwrap());
+
+        let newest_id = insert(&collection, Some(&profile_id), 0);
+        assert_eq!(last_id(&collection, Some(&profile_id)), Some(newest_id));
+        assert_eq!(
+            collection
+                .delete_requests(Some(&profile_id), &recipe_id)
+                .unwrap(),
+            2
+        );
+        assert_eq!(last_id(&collection, Some(&profile_id)), None);
+        // Other profiles and collections are untouched
+        assert_eq!(last_id(&collection, None), Some(no_profile_id));
+        assert_eq!(
+            last_id(&other_collection, Some(&profile_id)),
+            Some(other_id)
+        );
+    }
+
     /// Test UI state storage and retrieval
     #[test]
     fn test_ui_state() {
@@ -33,38 +33,84 @@
 //! | RequestRecord |
 //! +---------------+
 
+mod assertion;
+mod cache;
+mod client;
+mod command;
+mod edit;
+mod expect;
+mod generate;
+mod multipart;
 mod parse;
+mod progress;
 mod query;
+mod rate_limit;
+mod raw;
 mod record;
+mod redirect;
+mod schema;
+mod tls;
+mod trailers;
 
+pub use assertion::AssertionFailure;
+pub use generate::RequestFormat;
 pub use parse::*;
+pub use progress::*;
 pub use query::*;
+pub use raw::{RawRequest, RawResponse};
 pub use record::*;
+pub use redirect::RedirectHeaderPolicy;
+pub use schema::*;
+pub use tls::{TlsVersion, TlsVersions};
 
+use crate::http::{
+    client::Clients,
+    command::command_stream,
+    expect::{
+        expects_continue, should_expect_continue, ContinueGate,
+        EXPECT_CONTINUE_TIMEOUT,
+    },
+    rate_limit::RateLimiter,
+    redirect::{next_request, MAX_REDIRECTS},
+};
 use crate::{
-    collection::{self, Authentication, Method, Recipe},
+    collection::{
+        self, Authentication, BodyFormat, Method, Profile, Recipe, ReplayMatch,
+    },
     config::Config,
     db::CollectionDatabase,
-    template::{Template, TemplateContext},
+    template::{
+        with_chain_cache, with_request_body, without_request_body, Template,
+        TemplateContext, TemplateExplanation,
+    },
     util::ResultExt,
 };
-use anyhow::Context;
+use anyhow::{anyhow, bail, Context};
 use base64::{prelude::BASE64_STANDARD, write::EncoderWriter};
-use bytes::Bytes;
+use bytes::{Bytes, BytesMut};
 use chrono::Utc;
-use futures::future;
+use futures::future::{self, BoxFuture};
 use indexmap::IndexMap;
 use reqwest::{
     header::{self, HeaderMap, HeaderName, HeaderValue},
+    tls::TlsInfo,
     Client,
 };
-use std::{collections::HashSet, future::Future, io::Write, sync::Arc};
+use std::{
+    collections::HashSet,
+    future::{Future, IntoFuture},
+    io::Write,
+    sync::Arc,
+    time::Duration,
+};
 use tokio::try_join;
 use tracing::{debug, info, info_span};
 use url::Url;
 
-const USER_AGENT: &str =
-    concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+/// Most space to reserve for a response body up front, based on its
+/// `Content-Length`. The header comes from the server, so it can't be trusted
+/// to size an allocation. Bodies larger than this grow the buffer as they load.
+const MAX_BODY_PREALLOCATION: u64 = 10 * 1024 * 1024;
 
 /// Utility for handling all HTTP operations. The main purpose of this is to
 /// de-asyncify HTTP so it can be called in the main TUI thread. All heavy
@@ -74,38 +120,70 @@ const USER_AGENT: &str =
 /// internally. [reqwest::Client]
 #[derive(Clone, Debug)]
 pub struct HttpEngine {
-    client: Client,
-    /// This client ignores TLS cert errors. Only use it if the user
-    /// specifically wants to ignore errors for the request!
-    danger_client: Client,
+    clients: Arc<Clients>,
     database: CollectionDatabase,
+    /// TLS cert errors are ignored for these hosts
     danger_hostnames: HashSet<String>,
+    /// Allowed TLS versions, unless the recipe overrides them
+    tls: TlsVersions,
+    /// Should completed requests be saved in history? If disabled, nothing
+    /// is written to the database, regardless of recipe settings
+    persist_history: bool,
+    /// Which headers to forward on cross-origin redirects. We follow
+    /// redirects ourselves rather than letting reqwest do it, so we can
+    /// apply this.
+    redirect_header_policy: RedirectHeaderPolicy,
+    /// Shared between all clones, so limits apply across the whole program
+    rate_limiter: Arc<RateLimiter>,
+    /// Replay responses from history instead of sending requests
+    offline: bool,
+    /// Revalidate cached responses with `If-None-Match`/`If-Modified-Since`
+    conditional_requests: bool,
+    /// Fail any request that takes longer than this, including loading the
+    /// body. Not set from config; see [Self::with_limits]
+    timeout: Option<Duration>,
+    /// Fail any request whose response body is larger than this, in bytes
+    max_body_size: Option<u64>,
 }
 
 impl HttpEngine {
     /// Build a new HTTP engine, which can be used for the entire program life
     pub fn new(config: &Config, database: CollectionDatabase) -> Self {
         Self {
-            client: Client::builder()
-                .user_agent(USER_AGENT)
-                .build()
-                .expect("Error building reqwest client"),
-            danger_client: Client::builder()
-                .user_agent(USER_AGENT)
-                .danger_accept_invalid_certs(true)
-                .build()
-                .expect("Error building reqwest client"),
+            clients: Clients::new(config).into(),
             database,
             danger_hostnames: config
                 .ignore_certificate_hosts
                 .iter()
                 .cloned()
                 .collect(),
+            tls: config.tls,
+            persist_history: config.persist_history,
+            redirect_header_policy: config.redirect_header_policy.clone(),
+            rate_limiter: RateLimiter::new(&config.rate_limits).into(),
+            offline: config.offline,
+            conditional_requests: config.conditional_requests,
+            timeout: None,
+            max_body_size: None,
         }
     }
 
+    /// Limit how long requests sent by this engine can take, and how large
+    /// their response bodies can be. `None` means no limit. Time spent
+    /// waiting on a rate limit doesn't count toward the timeout.
+    pub fn with_limits(
+        mut self,
+        timeout: Option<Duration>,
+        max_body_size: Option<u64>,
+    ) -> Self {
+        self.timeout = timeout;
+        self.max_body_size = max_body_size;
+        self
+    }
+
     /// Launch an HTTP request. Upon completion, it will automatically be
-    /// registered in the database for posterity.
+    /// registered in the database for posterity, unless history is disabled
+    /// globally or for the request's recipe.
     ///
     /// This consumes the HTTP engine so that the future can outlive the scope
     /// that created the future. This allows the future to be created outside
@@ -117,9 +195,69 @@ impl HttpEngine {
     /// launched until the consumer starts awaiting the future. For in-flight
     /// time tracking, track your own start time immediately before/after
     /// sending the request.
+    ///
+    /// If the request's host has a rate limit configured, this will wait for
+    /// a slot before sending. Use [Self::queue] to find out how long the wait
+    /// will be.
     pub async fn send(
         self,
         request: Arc<Request>,
+    ) -> Result<RequestRecord, RequestError> {
+        self.queue(request, false).await
+    }
+
+    /// Launch an HTTP request, but only load the response status and headers.
+    /// The body is discarded without being downloaded, which is useful when
+    /// the body is large and you don't need it. Otherwise identical to
+    /// [Self::send].
+    pub async fn send_headers_only(
+        self,
+        request: Arc<Request>,
+    ) -> Result<RequestRecord, RequestError> {
+        self.queue(request, true).await
+    }
+
+    /// Reserve a slot for a request under its host's rate limit, and return a
+    /// future that will send it once the slot comes up. The slot is reserved
+    /// immediately, so requests are sent in the order they're queued. Set
+    /// `headers_only` to skip loading the response body, as in
+    /// [Self::send_headers_only].
+    pub fn queue(
+        self,
+        request: Arc<Request>,
+        headers_only: bool,
+    ) -> QueuedRequest {
+        // Replayed requests don't touch the network, so they aren't limited
+        let wait = request
+            .url
+            .host_str()
+            .filter(|_| !self.offline)
+            .and_then(|host| self.rate_limiter.reserve(host));
+        if let Some(wait) = wait {
+            info!(request_id = %request.id, ?wait, "Request throttled");
+        }
+        let progress = Arc::new(RequestProgress::default());
+        let future = Box::pin({
+            let progress = Arc::clone(&progress);
+            async move {
+                if let Some(wait) = wait {
+                    tokio::time::sleep(wait).await;
+                }
+                self.send_inner(request, headers_only, &progress).await
+            }
+        });
+        QueuedRequest {
+            wait,
+            progress,
+            future,
+        }
+    }
+
+    async fn send_inner(
+        self,
+        request: Arc<Request>,
+        headers_only: bool,
+        progress: &Arc<RequestProgress>,
     ) -> Result<RequestRecord, RequestError> {
         let id = request.id;
 
@@ -130,8 +268,36 @@ impl HttpEngine {
 
             // Technically the elapsed time will include the conversion time,
             // but that should be extremely minimal compared to network IO
+            // Body of a cached response, in case the server tells us it's
+            // still good. Look it up before the clock starts.
+            let (request, cached_body) = if self.conditional_requests
+                && !self.offline
+                && !headers_only
+            {
+                self.add_validators(request)
+            } else {
+                (request, None)
+            };
+
             let start_time = Utc::now();
-            let result = self.send_request_helper(&request).await;
+            let result = if self.offline {
+                self.replay(&request, headers_only)
+            } else {
+                let future =
+                    self.send_request_helper(&request, headers_only, progress);
+                match self.timeout {
+                    Some(timeout) => tokio::time::timeout(timeout, future)
+                        .await
+                        .unwrap_or_else(|_| {
+                            Err(anyhow!("Request timed out after {timeout:?}"))
+                        }),
+                    None => future.await,
+                }
+                .map(|mut response| {
+                    response.apply_cached_body(cached_body);
+                    response
+                })
+            };
             let end_time = Utc::now();
 
             // Attach metadata to the error and yeet it
@@ -148,8 +314,15 @@ impl HttpEngine {
                         end_time,
                     };
 
-                    // Error here should *not* kill the request
-                    let _ = self.database.insert_request(&record);
+                    if record.response.replayed {
+                        // It's already in history
+                        debug!("Response was replayed, not saving request");
+                    } else if self.persist_history && record.request.persist {
+                        // Error here should *not* kill the request
+                        let _ = self.database.insert_request(&record);
+                    } else {
+                        debug!("History disabled, not saving request");
+                    }
                     Ok(record)
                 }
                 Err(error) => Err(RequestError {
@@ -164,31 +337,136 @@ impl HttpEngine {
         .await
     }
 
+    /// Find a recorded response in history for a request, instead of sending
+    /// it. The recipe's [ReplayMatch] decides which response is used.
+    fn replay(
+        &self,
+        request: &Request,
+        headers_only: bool,
+    ) -> anyhow::Result<Response> {
+        let profile_id = request.profile_id.as_ref();
+        let recipe_id = &request.recipe_id;
+        let record = match request.replay {
+            ReplayMatch::Latest => {
+                self.database.get_last_request(profile_id, recipe_id)?
+            }
+            ReplayMatch::Request => self.database.find_last_request(
+                profile_id,
+                recipe_id,
+                |record| {
+                    record.request.method == request.method
+                        && record.request.url == request.url
+                        && record.request.body == request.body
+                },
+            )?,
+        };
+        let Some(record) = record else {
+            bail!(
+                "Offline mode is enabled, and history has no response to \
+                replay for recipe `{recipe_id}`{}",
+                match request.replay {
+                    ReplayMatch::Latest => "",
+                    ReplayMatch::Request => " matching this request",
+                }
+            );
+        };
+        info!(replayed_request_id = %record.id, "Replaying response");
+
+        let mut response = record.response;
+        if headers_only {
+            response.body = Body::default();
+            response.headers_only = true;
+        }
+        response.replayed = true;
+        Ok(response)
+    }
+
     /// An exact encapsulation of the "request". The execution of this function
     /// is synonymous with a request's elapsed time.
     async fn send_request_helper(
         &self,
         request: &Request,
-    ) -> reqwest::Result<Response> {
-        // Convert to reqwest format as part of the execution. This means
-        // certain builder errors will show up as "request" errors which is
-        // janky, but reqwest already doesn't report some builder erorrs until
-        // you execute the request, and this is much easier than frontloading
-        // the conversion during the build process.
-        let reqwest_request = self.convert_request(request)?;
-
-        // If the user wants to ignore cert errors on this host, use the client
-        // that's set up for that
-        let host = reqwest_request.url().host_str().unwrap_or_default();
-        let client = if self.danger_hostnames.contains(host) {
-            &self.danger_client
-        } else {
-            &self.client
-        };
+        headers_only: bool,
+        progress: &Arc<RequestProgress>,
+    ) -> anyhow::Result<Response> {
+        // Follow redirects manually, so we can control which headers are
+        // forwarded. The original request is what gets stored in history; the
+        // intermediate requests are only used here.
+        let mut redirected: Option<Request> = None;
+        let mut redirects = 0;
+        let tls = request.tls.or(self.tls);
+        loop {
+            let current = redirected.as_ref().unwrap_or(request);
+
+            // If the user wants to ignore cert errors on this host, use a
+            // client that's set up for that
+            let host = current.url.host_str().unwrap_or_default();
+            let (client, negotiated_tls) = self
+                .clients
+                .get(self.danger_hostnames.contains(host), tls)?;
+
+            // Convert to reqwest format as part of the execution. This means
+            // certain builder errors will show up as "request" errors which is
+            // janky, but reqwest already doesn't report some builder erorrs
+            // until you execute the request, and this is much easier than
+            // frontloading the conversion during the build process.
+            let (reqwest_request, continue_gate) =
+                self.convert_request(&client, current, progress)?;
+
+            let reqwest_response = client.execute(reqwest_request).await?;
+            // If the body is still being held back, it never needs to go out.
+            // The gate has to live until the response is loaded though,
+            // otherwise the connection is closed under it.
+            let body_withheld =
+                continue_gate.as_ref().is_some_and(ContinueGate::answer);
+
+            if redirects < MAX_REDIRECTS {
+                if let Some(next) = next_request(
+                    current,
+                    reqwest_response.status(),
+                    reqwest_response.headers(),
+                    &self.redirect_header_policy,
+                ) {
+                    debug!(url = %next.url, "Following redirect");
+                    redirects += 1;
+                    redirected = Some(next);
+                    continue;
+                }
+            }
 
-        let reqwest_response = client.execute(reqwest_request).await?;
-        // Load the full response and convert it to our format
-        self.convert_response(reqwest_response).await
+            // Streamed formats can be shown while they load
+            let content_type = current.expects.or_else(|| {
+                let header = reqwest_response
+                    .headers()
+                    .get(header::CONTENT_TYPE)?
+                    .to_str()
+                    .ok()?;
+                ContentType::from_header(header).ok()
+            });
+            if content_type == Some(ContentType::Ndjson) {
+                progress.keep_partial_body();
+            }
+
+            // Match the connection back to the handshake that set it up. Only
+            // TLS connections have this info
+            let tls_version = reqwest_response
+                .extensions()
+                .get::<TlsInfo>()
+                .and_then(TlsInfo::peer_certificate)
+                .and_then(|certificate| negotiated_tls.get(certificate));
+
+            // Load the full response and convert it to our format
+            let mut response = self
+                .convert_response(
+                    reqwest_response,
+                    headers_only,
+                    body_withheld,
+                    progress,
+                )
+                .await?;
+            response.tls_version = tls_version;
+            return Ok(response);
+        }
     }
 
     /// Convert from our request type to reqwest's. The input request should
@@ -199,47 +477,182 @@ impl HttpEngine {
     /// This will pretty much clone all the data out of the request, which sucks
     /// but there's no alternative. Reqwest wants to own it all, but we also
     /// need to retain ownership for the UI.
+    ///
+    /// If the request has `Expect: 100-continue`, the body is held back
+    /// behind the returned gate. See [ContinueGate].
     fn convert_request(
         &self,
+        client: &Client,
         request: &Request,
-    ) -> reqwest::Result<reqwest::Request> {
+        progress: &Arc<RequestProgress>,
+    ) -> reqwest::Result<(reqwest::Request, Option<ContinueGate>)> {
         // Convert to reqwest's request format
-        let mut request_builder = self
-            .client
+        let mut request_builder = client
             .request(request.method.clone(), request.url.clone())
             .headers(request.headers.clone());
 
-        // Add body
-        if let Some(body) = &request.body {
-            request_builder = request_builder.body(body.clone());
+        let expect_continue = expects_continue(&request.headers);
+        let mut continue_gate = None;
+        // Add body. Large bodies are streamed so we can track upload
+        // progress. Bodies waiting on a `100 Continue` are streamed too, so
+        // they can be held back.
+        match &request.body {
+            Some(body) if body.len() > UPLOAD_CHUNK_SIZE || expect_continue => {
+                // Streamed bodies are sent with chunked encoding by default.
+                // We know the length, so send that instead, unless the user
+                // has asked for something specific
+                if !request.headers.contains_key(header::CONTENT_LENGTH)
+                    && !request.headers.contains_key(header::TRANSFER_ENCODING)
+                {
+                    request_builder = request_builder
+                        .header(header::CONTENT_LENGTH, body.len());
+                }
+                let stream = upload_stream(body.clone(), Arc::clone(progress));
+                request_builder = if expect_continue {
+                    let (gate, stream) =
+                        ContinueGate::gate(stream, EXPECT_CONTINUE_TIMEOUT);
+                    continue_gate = Some(gate);
+                    request_builder.body(reqwest::Body::wrap_stream(stream))
+                } else {
+                    request_builder.body(reqwest::Body::wrap_stream(stream))
+                };
+            }
+            Some(body) => {
+                request_builder = request_builder.body(body.clone());
+            }
+            None => {}
+        }
+        // Command output is streamed as it's generated, with chunked encoding
+        if let Some(command) = &request.body_command {
+            let stream = command_stream(command.clone(), Arc::clone(progress));
+            request_builder = if expect_continue {
+                let (gate, stream) =
+                    ContinueGate::gate(stream, EXPECT_CONTINUE_TIMEOUT);
+                continue_gate = Some(gate);
+                request_builder.body(reqwest::Body::wrap_stream(stream))
+            } else {
+                request_builder.body(reqwest::Body::wrap_stream(stream))
+            };
         }
 
-        request_builder.build()
+        Ok((request_builder.build()?, continue_gate))
     }
 
     /// Convert reqwest's response type into ours. This is async because the
     /// response content is not necessarily loaded when we first get the
-    /// response. Only fallible if the response content fails to load.
+    /// response. Fails if the response content fails to load, or is larger
+    /// than the body size limit.
+    ///
+    /// If the request body was withheld (see [ContinueGate]), the end of the
+    /// response body isn't reported until the connection closes. In that case,
+    /// the body is done once it reaches its `Content-Length`, or once it goes
+    /// quiet for [EXPECT_CONTINUE_TIMEOUT].
     async fn convert_response(
         &self,
-        response: reqwest::Response,
-    ) -> reqwest::Result<Response> {
+        mut response: reqwest::Response,
+        headers_only: bool,
+        body_withheld: bool,
+        progress: &RequestProgress,
+    ) -> anyhow::Result<Response> {
         // Copy response metadata out first, because we need to move the
         // response to resolve content (not sure why...)
         let status = response.status();
         let headers = response.headers().clone();
 
-        // Pre-resolve the content, so we get all the async work done
-        let body = response.bytes().await?.into();
+        // Pre-resolve the content, so we get all the async work done. If we
+        // don't want the body, dropping the response closes the connection
+        // without reading it
+        let body = if headers_only {
+            Body::default()
+        } else {
+            // Load chunk-by-chunk so we can track progress
+            let total = response.content_length();
+            let too_large = |size: u64| {
+                self.max_body_size
+                    .filter(|limit| size > *limit)
+                    .map(|limit| {
+                        anyhow!(
+                        "Response body is larger than the limit of {limit} \
+                        bytes"
+                    )
+                    })
+            };
+            // Check the advertised length up front, so we don't bother
+            // loading any of it
+            if let Some(error) = total.and_then(too_large) {
+                return Err(error);
+            }
+            progress.start_download(total);
+            let mut body = BytesMut::with_capacity(
+                total.unwrap_or(0).min(MAX_BODY_PREALLOCATION) as usize,
+            );
+            loop {
+                let chunk = if body_withheld {
+                    if total == Some(body.len() as u64) {
+                        break;
+                    }
+                    match tokio::time::timeout(
+                        EXPECT_CONTINUE_TIMEOUT,
+                        response.chunk(),
+                    )
+                    .await
+                    {
+                        Ok(chunk) => chunk?,
+                        Err(_) => break,
+                    }
+                } else {
+                    tokio::select! {
+                        chunk = response.chunk() => chunk?,
+                        _ = progress.stopped() => {
+                            debug!("Stopped loading response body");
+                            break;
+                        }
+                    }
+                };
+                let Some(chunk) = chunk else { break };
+                progress.add_downloaded(&chunk);
+                body.extend_from_slice(&chunk);
+                if let Some(error) = too_large(body.len() as u64) {
+                    return Err(error);
+                }
+            }
+            Body::new(body.freeze())
+        };
+        let trailers = trailers::parse_trailers(&headers, body.bytes());
 
         Ok(Response {
             status,
             headers,
+            trailers,
             body,
+            headers_only,
+            replayed: false,
+            cached: false,
+            tls_version: None,
         })
     }
 }
 
+/// A request that's waiting for its turn under a rate limit. Await this to
+/// send the request. See [HttpEngine::queue].
+pub struct QueuedRequest {
+    /// How long the request has to wait before it's sent. `None` if it will
+    /// be sent immediately
+    pub wait: Option<Duration>,
+    /// Upload/download progress, updated as the request is sent
+    pub progress: Arc<RequestProgress>,
+    future: BoxFuture<'static, Result<RequestRecord, RequestError>>,
+}
+
+impl IntoFuture for QueuedRequest {
+    type Output = Result<RequestRecord, RequestError>;
+    type IntoFuture = BoxFuture<'static, Self::Output>;
+
+    fn into_future(self) -> Self::IntoFuture {
+        self.future
+    }
+}
+
 /// The foundation of a request. This builder captures *how* the request will
 /// be built, but it hasn't actually been built yet.
 pub struct RequestBuilder {
@@ -264,6 +677,29 @@ pub struct RecipeOptions {
     /// Which query parameters should be excluded?  A blacklist allows the
     /// default to be "include all".
     pub disabled_query_parameters: HashSet<String>,
+    /// Replacement for the recipe's body, e.g. after the user edited it
+    /// in the TUI. `None` means use the body defined in the recipe.
+    pub body: Option<BodyOverride>,
+}
+
+/// How one templated field of a request was rendered. See
+/// [RequestBuilder::explain]
+#[derive(Debug)]
+pub struct FieldExplanation {
+    /// Which part of the request this is, e.g. ``header `Accept` ``
+    pub field: String,
+    /// The field's template, before rendering
+    pub template: Template,
+    pub explanation: TemplateExplanation,
+}
+
+/// A replacement for a recipe's body
+#[derive(Clone, Debug)]
+pub enum BodyOverride {
+    /// Template to render in place of the recipe's body
+    Template(Template),
+    /// Raw bytes to send as-is, without templating, e.g. from stdin
+    Raw(Bytes),
 }
 
 impl RequestBuilder {
@@ -295,8 +731,19 @@ impl RequestBuilder {
         self,
         template_context: &TemplateContext,
     ) -> Result<Request, RequestBuildError> {
-        self.apply_error(self.render_request(template_context))
-            .await
+        // Chains are shared between every template in the request
+        self.apply_error(with_chain_cache(
+            self.render_request(template_context),
+        ))
+        .await
+    }
+
+    /// Build just a request's method
+    pub async fn build_method(
+        self,
+        template_context: &TemplateContext,
+    ) -> Result<reqwest::Method, RequestBuildError> {
+        self.apply_error(self.render_method(template_context)).await
     }
 
     /// Build just a request's URL
@@ -315,6 +762,137 @@ impl RequestBuilder {
         self.apply_error(self.render_body(template_context)).await
     }
 
+    /// Render each templated field of the request, recording where every
+    /// value came from. Disabled headers and query parameters are skipped,
+    /// just like in a real build. This doesn't fail, because the point is to
+    /// show what went wrong. Authentication credentials are masked.
+    pub async fn explain(
+        &self,
+        template_context: &TemplateContext,
+    ) -> Vec<FieldExplanation> {
+        // (field, template, sensitive)
+        type Field<'a> = (String, &'a Template, bool);
+
+        let profile =
+            template_context
+                .selected_profile
+                .as_ref()
+                .and_then(|profile_id| {
+                    template_context.collection.profiles.get(profile_id)
+                });
+        let mut fields: Vec<Field> = vec![
+            ("method".into(), &self.recipe.method, false),
+            ("URL".into(), &self.recipe.url, false),
+        ];
+        if let Some(base_url) =
+            profile.and_then(|profile| profile.base_url.as_ref())
+        {
+            fields.push(("base URL".into(), base_url, false));
+        }
+        fields.extend(
+            self.recipe
+                .query
+                .iter()
+                .filter(|(param, _)| {
+                    !self.options.disabled_query_parameters.contains(*param)
+                })
+                .map(|(param, value)| {
+                    (format!("query parameter `{param}`"), value, false)
+                }),
+        );
+        let body = match &self.options.body {
+            Some(BodyOverride::Template(template)) => Some(template),
+            // Raw bodies aren't templates, so there's nothing to explain
+            Some(BodyOverride::Raw(_)) => None,
+            None => self.recipe.body.as_ref(),
+        };
+        if let Some(body) = body {
+            fields.push(("body".into(), body, false));
+        }
+        for (i, argument) in
+            self.recipe.body_command.iter().flatten().enumerate()
+        {
+            fields.push((
+                format!("body command argument {i}"),
+                argument,
+                false,
+            ));
+        }
+
+        // Headers (including authentication) are rendered last, so they can
+        // reference the body, e.g. to sign it
+        let mut header_fields: Vec<Field> = self
+            .profile_headers(profile)
+            .map(|(header, value)| {
+                (format!("profile header `{header}`"), value, false)
+            })
+            .collect();
+        header_fields.extend(
+            self.recipe
+                .headers
+                .iter()
+                .filter(|(header, _)| {
+                    !self.options.disabled_headers.contains(*header)
+                })
+                .map(|(header, value)| {
+                    (format!("header `{header}`"), value, false)
+                }),
+        );
+        header_fields.extend(
+            self.recipe.cookies.iter().map(|(name, value)| {
+                (format!("cookie `{name}`"), value, false)
+            }),
+        );
+        match self.recipe.authentication(profile) {
+            Some(Authentication::Basic { username, password }) => {
+                header_fields.push(("username".into(), username, false));
+                if let Some(password) = password {
+                    header_fields.push(("password".into(), password, true));
+                }
+            }
+            Some(Authentication::Bearer(token)) => {
+                header_fields.push(("bearer token".into(), token, true));
+            }
+            None => {}
+        }
+
+        async fn explain(
+            (field, template, sensitive): Field<'_>,
+            template_context: &TemplateContext,
+        ) -> FieldExplanation {
+            let mut explanation = template.explain(template_context).await;
+            if sensitive {
+                explanation.mask();
+            }
+            FieldExplanation {
+                field,
+                template: template.clone(),
+                explanation,
+            }
+        }
+
+        let mut explanations = without_request_body(future::join_all(
+            fields
+                .into_iter()
+                .map(|field| explain(field, template_context)),
+        ))
+        .await;
+        let request_body =
+            self.render_body(template_context).await.ok().flatten();
+        explanations.extend(
+            with_request_body(
+                request_body,
+                future::join_all(
+                    header_fields
+                        .into_iter()
+                        .map(|field| explain(field, template_context)),
+                ),
+            )
+            .await,
+        );
+        explanations
+    }
+
     /// Wrapper to apply a helpful error around some request build step
     async fn apply_error<T>(
         &self,
@@ -331,12 +909,67 @@ impl RequestBuilder {
         &self,
         template_context: &TemplateContext,
     ) -> anyhow::Result<Request> {
-        // Render everything in parallel
-        let (url, headers, body) = try_join!(
-            self.render_url(template_context),
-            self.render_headers(template_context),
-            self.render_body(template_context),
-        )?;
+        // Render everything but headers in parallel. Hide the body of any
+        // enclosing request, in case this is a triggered request
+        let (method, url, body, body_command, multipart) =
+            without_request_body(async {
+                try_join!(
+                    self.render_method(template_context),
+                    self.render_url(template_context),
+                    self.render_body(template_context),
+                    self.render_body_command(template_context),
+                    self.render_multipart(template_context),
+                )
+            })
+            .await?;
+        let (body, multipart_content_type) = match multipart {
+            Some(multipart) => {
+                (Some(multipart.body), Some(multipart.content_type))
+            }
+            None => (body, None),
+        };
+        // Headers go last, so they can reference the final body, e.g. to sign
+        // it. A body from a command is streamed, so it's not available.
+        let mut headers = if body_command.is_some() {
+            without_request_body(self.render_headers(template_context)).await
+        } else {
+            with_request_body(
+                body.clone(),
+                self.render_headers(template_context),
+            )
+            .await
+        }?;
+        // The recipe can override the generated header, e.g. to use a
+        // subtype or parameter that needs special formatting
+        if let Some(content_type) = multipart_content_type {
+            headers.entry(header::CONTENT_TYPE).or_insert(content_type);
+        } else if !self.has_header(template_context, header::CONTENT_TYPE) {
+            // Some servers reject bodies without a content type. A header
+            // defined in the recipe or profile always wins, even if disabled.
+            if let Some(content_type) =
+                body.as_deref().and_then(infer_content_type)
+            {
+                headers.insert(header::CONTENT_TYPE, content_type);
+            }
+        }
+
+        if should_expect_continue(
+            self.recipe.expect_continue,
+            body.as_ref().map(Bytes::len),
+            body_command.is_some(),
+        ) && !self.has_header(template_context, header::EXPECT)
+        {
+            headers.insert(
+                header::EXPECT,
+                HeaderValue::from_static("100-continue"),
+            );
+        }
+
+        // The label doesn't affect the request, so it comes after everything
+        // else. Rendering it concurrently bloats the future, which matters
+        // for deeply nested triggered requests.
+        let label =
+            without_request_body(self.render_label(template_context)).await?;
 
         info!(
             recipe_id = %self.recipe.id,
@@ -347,13 +980,45 @@ impl RequestBuilder {
             id: self.id,
             profile_id: template_context.selected_profile.clone(),
             recipe_id: self.recipe.id.clone(),
-            method: self.recipe.method.into(),
+            label,
+            method,
             url,
             headers,
             body,
+            body_command,
+            persist: self.recipe.persist,
+            expects: self.recipe.expects,
+            replay: self.recipe.replay,
+            tls: self.recipe.tls,
+            pagination: self.recipe.pagination.clone(),
         })
     }
 
+    /// Render the label template, if the recipe has one
+    async fn render_label(
+        &self,
+        template_context: &TemplateContext,
+    ) -> anyhow::Result<Option<String>> {
+        Template::render_opt(self.recipe.label.as_ref(), template_context)
+            .await
+            .context("Error rendering label")
+    }
+
+    /// Render the method template, and make sure it's a valid HTTP method
+    async fn render_method(
+        &self,
+        template_context: &TemplateContext,
+    ) -> anyhow::Result<reqwest::Method> {
+        let method = self
+            .recipe
+            .method
+            .render(template_context)
+            .await
+            .context("Error rendering method")?;
+        let method: Method = method.try_into()?;
+        Ok(method.into())
+    }
+
     /// Render URL, including query params
     async fn render_url(
         &self,
@@ -368,6 +1033,13 @@ impl RequestBuilder {
                     .render(template_context)
                     .await
                     .context("Error rendering URL")?;
+                let url = match url.parse::<Url>() {
+                    // Relative URLs get the profile's base URL, if any
+                    Err(url::ParseError::RelativeUrlWithoutBase) => {
+                        Self::prefix_base_url(template_context, &url).await?
+                    }
+                    _ => url,
+                };
                 url.parse::<Url>()
                     .with_context(|| format!("Invalid URL: `{url}`"))
             },
@@ -382,6 +1054,35 @@ impl RequestBuilder {
         Ok(url)
     }
 
+    /// Prepend the selected profile's base URL to a relative URL. If there's
+    /// no base URL, the URL is returned unchanged.
+    async fn prefix_base_url(
+        template_context: &TemplateContext,
+        url: &str,
+    ) -> anyhow::Result<String> {
+        let Some(base_url) = template_context
+            .selected_profile
+            .as_ref()
+            .and_then(|profile_id| {
+                template_context.collection.profiles.get(profile_id)
+            })
+            .and_then(|profile| profile.base_url.as_ref())
+        else {
+            return Ok(url.to_owned());
+        };
+        let base_url = base_url
+            .render(template_context)
+            .await
+            .context("Error rendering base URL")?;
+        // Join as strings rather than with Url::join, so a path on the base
+        // URL (e.g. `/api`) is kept
+        Ok(format!(
+            "{}/{}",
+            base_url.trim_end_matches('/'),
+            url.trim_start_matches('/')
+        ))
+    }
+
     /// Render query key=value params
     async fn render_query(
         &self,
@@ -409,31 +1110,70 @@ impl RequestBuilder {
             .collect::<IndexMap<String, String>>())
     }
 
+    /// Does the recipe or the selected profile's default headers define this
+    /// header? This ignores disabled headers, and header names are
+    /// case-insensitive
+    fn has_header(
+        &self,
+        template_context: &TemplateContext,
+        name: HeaderName,
+    ) -> bool {
+        let profile_headers = template_context
+            .selected_profile
+            .as_ref()
+            .and_then(|profile_id| {
+                template_context.collection.profiles.get(profile_id)
+            })
+            .into_iter()
+            .flat_map(|profile| profile.headers.keys());
+        self.recipe
+            .headers
+            .keys()
+            .chain(profile_headers)
+            .any(|header| header.eq_ignore_ascii_case(name.as_str()))
+    }
+
     /// Render all headers. This will also render authentication and merge it
     /// into the headers
     async fn render_headers(
         &self,
         template_context: &TemplateContext,
     ) -> anyhow::Result<HeaderMap> {
-        // Render base headers
+        let profile =
+            template_context
+                .selected_profile
+                .as_ref()
+                .and_then(|profile_id| {
+                    template_context.collection.profiles.get(profile_id)
+                });
+
+        // Profile headers go first, so they're beneath the recipe's. Inserting
+        // one at a time means a repeated name (in any case) replaces the
+        // earlier value, instead of adding another
         let iter = self
-            .recipe
-            .headers
-            .iter()
-            // Filter out disabled headers
-            .filter(|(header, _)| {
+            .profile_headers(profile)
+            .chain(self.recipe.headers.iter().filter(|(header, _)| {
+                // Filter out disabled headers
                 !self.options.disabled_headers.contains(*header)
-            })
+            }))
             .map(move |(header, value_template)| {
                 self.render_header(template_context, header, value_template)
             });
-        let mut headers = future::try_join_all(iter)
-            .await?
-            .into_iter()
-            .collect::<HeaderMap>();
+        let mut headers = HeaderMap::new();
+        for (header, value) in future::try_join_all(iter).await? {
+            headers.insert(header, value);
+        }
+
+        if !self.recipe.cookies.is_empty() {
+            let cookies = self.render_cookies(template_context).await?;
+            headers.insert(
+                header::COOKIE,
+                merge_cookies(headers.get(header::COOKIE), &cookies)?,
+            );
+        }
 
         // Render auth method and modify headers accordingly
-        if let Some(authentication) = &self.recipe.authentication {
+        if let Some(authentication) = self.recipe.authentication(profile) {
             headers.insert(
                 header::AUTHORIZATION,
                 self.render_authentication(template_context, authentication)
@@ -441,9 +1181,41 @@ impl RequestBuilder {
             );
         }
 
+        // Ask for the expected content type, unless the recipe or profile
+        // already asks for something. Check the definitions rather than the
+        // rendered headers, so disabling the recipe's header doesn't bring
+        // back the default.
+        if let Some(content_type) = self.recipe.expects {
+            if !self.has_header(template_context, header::ACCEPT) {
+                headers.insert(
+                    header::ACCEPT,
+                    HeaderValue::from_static(content_type.mime_type()),
+                );
+            }
+        }
+
         Ok(headers)
     }
 
+    /// Get the selected profile's default headers, minus any that the recipe
+    /// sets itself. Check the recipe rather than the rendered headers, so
+    /// disabling the recipe's header doesn't bring back the default.
+    fn profile_headers<'a>(
+        &'a self,
+        profile: Option<&'a Profile>,
+    ) -> impl Iterator<Item = (&'a String, &'a Template)> {
+        profile
+            .into_iter()
+            .flat_map(|profile| &profile.headers)
+            .filter(|(header, _)| {
+                !self
+                    .recipe
+                    .headers
+                    .keys()
+                    .any(|other| other.eq_ignore_ascii_case(header))
+            })
+    }
+
     /// Render authentication and return a value for the Authorization header
     async fn render_authentication(
         &self,
@@ -526,16 +1298,198 @@ impl RequestBuilder {
         ))
     }
 
+    /// Render the recipe's cookies as `(name, encoded value)` pairs
+    async fn render_cookies(
+        &self,
+        template_context: &TemplateContext,
+    ) -> anyhow::Result<Vec<(String, String)>> {
+        let iter =
+            self.recipe
+                .cookies
+                .iter()
+                .map(|(name, template)| async move {
+                    if !is_cookie_name(name) {
+                        bail!("Invalid cookie name `{name}`");
+                    }
+                    let value = template
+                        .render(template_context)
+                        .await
+                        .context(format!("Error rendering cookie `{name}`"))?;
+                    Ok((name.clone(), encode_cookie_value(&value)))
+                });
+        future::try_join_all(iter).await
+    }
+
     async fn render_body(
         &self,
         template_context: &TemplateContext,
     ) -> anyhow::Result<Option<Bytes>> {
-        let body =
-            Template::render_opt(self.recipe.body.as_ref(), template_context)
+        let body = match &self.options.body {
+            Some(BodyOverride::Raw(bytes)) => return Ok(Some(bytes.clone())),
+            Some(BodyOverride::Template(template)) => Some(template),
+            None => self.recipe.body.as_ref(),
+        };
+        let Some(template) = body else {
+            return Ok(None);
+        };
+        let rendered = template
+            .render(template_context)
+            .await
+            .context("Error rendering body")?;
+
+        // Like curl's `--data @file`, a leading `@` loads the body from a
+        // file, and `@@` escapes a literal `@`. Check the template rather than
+        // the rendered value, so a rendered value that happens to start with
+        // `@` isn't treated as a path. The `@` is static text, so it's still
+        // at the start of the rendered value.
+        let body: Vec<u8> = if template.as_str().starts_with("@@") {
+            rendered[1..].into()
+        } else if template.as_str().starts_with('@') {
+            let path = template_context.resolve_path(rendered[1..].trim())?;
+            tokio::fs::read(&path)
                 .await
-                .context("Error rendering body")?;
-        Ok(body.map(Bytes::from))
+                .with_context(|| format!("Error reading body file {path:?}"))?
+        } else {
+            rendered.into_bytes()
+        };
+
+        let body = match self.recipe.body_format {
+            BodyFormat::Raw => body,
+            BodyFormat::Json5 => {
+                let text = std::str::from_utf8(&body)
+                    .context("Error parsing body as JSON5")?;
+                let value: serde_json::Value = json5::from_str(text)
+                    .context("Error parsing body as JSON5")?;
+                value.to_string().into_bytes()
+            }
+        };
+        Ok(Some(body.into()))
+    }
+
+    /// Render the command that will generate the body, if the recipe has one.
+    /// A body override from the user takes precedence over the command.
+    async fn render_body_command(
+        &self,
+        template_context: &TemplateContext,
+    ) -> anyhow::Result<Option<Vec<String>>> {
+        let Some(command) = &self.recipe.body_command else {
+            return Ok(None);
+        };
+        if self.options.body.is_some() {
+            return Ok(None);
+        }
+        if self.recipe.body.is_some() {
+            anyhow::bail!("Recipe cannot have both `body` and `body_command`");
+        }
+        if command.is_empty() {
+            anyhow::bail!("Body command cannot be empty");
+        }
+        let command = future::try_join_all(command.iter().enumerate().map(
+            |(i, template)| async move {
+                template.render(template_context).await.with_context(|| {
+                    format!("Error rendering body_command[{i}]")
+                })
+            },
+        ))
+        .await?;
+        Ok(Some(command))
+    }
+}
+
+/// Guess the `Content-Type` of a body that was given without one. This only
+/// covers the unambiguous cases: JSON (which JSON5 bodies are converted to),
+/// and `key=value&...` forms. Anything else gets no header, same as before.
+fn infer_content_type(body: &[u8]) -> Option<HeaderValue> {
+    let first = body.iter().find(|c| !c.is_ascii_whitespace());
+    let is_json = matches!(first, Some(b'{' | b'['))
+        && serde_json::from_slice::<serde::de::IgnoredAny>(body).is_ok();
+    if is_json {
+        return Some(HeaderValue::from_static(ContentType::Json.mime_type()));
+    }
+
+    // Only characters that can appear unescaped in a urlencoded form
+    let is_form_char =
+        |c: &u8| c.is_ascii_alphanumeric() || b"%+-._~*".contains(c);
+    let is_form = !body.is_empty()
+        && body.split(|c| *c == b'&').all(|pair| {
+            match pair.iter().position(|c| *c == b'=') {
+                Some(i) => {
+                    i > 0
+                        && pair[..i].iter().all(is_form_char)
+                        && pair[i + 1..].iter().all(is_form_char)
+                }
+                None => false,
+            }
+        });
+    if is_form {
+        return Some(HeaderValue::from_static(
+            "application/x-www-form-urlencoded",
+        ));
+    }
+    None
+}
+
+/// Can this be used as a cookie name? Names must be HTTP tokens (RFC 6265)
+fn is_cookie_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.bytes().all(|c| {
+            c.is_ascii_graphic() && !b"()<>@,;:\\\"/[]?={}".contains(&c)
+        })
+}
+
+/// Percent-encode anything that isn't allowed in a cookie value (RFC 6265),
+/// such as spaces, semicolons, and non-ASCII characters. `%` is encoded too,
+/// so the server can decode the value unambiguously.
+fn encode_cookie_value(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for c in value.bytes() {
+        match c {
+            0x21
+            | 0x23..=0x24
+            | 0x26..=0x2B
+            | 0x2D..=0x3A
+            | 0x3C..=0x5B
+            | 0x5D..=0x7E => encoded.push(c as char),
+            _ => encoded.push_str(&format!("%{c:02X}")),
+        }
     }
+    encoded
+}
+
+/// Combine cookies from an existing `Cookie` header with the recipe's
+/// cookies. Cookies from the header come first, except those that the recipe
+/// also sets, which are replaced by the recipe's value.
+fn merge_cookies(
+    header: Option<&HeaderValue>,
+    cookies: &[(String, String)],
+) -> anyhow::Result<HeaderValue> {
+    let existing = header
+        .map(|value| {
+            value
+                .to_str()
+                .context("Error reading `Cookie` header: not valid UTF-8")
+        })
+        .transpose()?
+        .unwrap_or_default();
+    let pairs = existing
+        .split(';')
+        .map(str::trim)
+        .filter(|pair| {
+            let name = pair.split_once('=').map_or(*pair, |(name, _)| name);
+            !pair.is_empty()
+                && !cookies.iter().any(|(other, _)| other == name.trim())
+        })
+        .map(str::to_owned)
+        .chain(
+            cookies
+                .iter()
+                .map(|(name, value)| format!("{name}={value}")),
+        )
+        .collect::<Vec<_>>();
+    pairs
+        .join("; ")
+        .try_into()
+        .context("Error encoding `Cookie` header")
 }
 
 impl From<Method> for reqwest::Method {
@@ -557,13 +1511,20 @@ impl From<Method> for reqwest::Method {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{collection::Authentication, test_util::*};
+    use crate::{
+        collection::{
+            Authentication, ExpectContinue, Multipart, MultipartPart,
+        },
+        http::expect::EXPECT_CONTINUE_MIN_SIZE,
+        test_util::*,
+        util::assert_err,
+    };
     use factori::create;
     use indexmap::indexmap;
     use pretty_assertions::assert_eq;
-    use reqwest::Method;
+    use reqwest::{Method, StatusCode};
     use rstest::rstest;
-    use std::collections::HashMap;
+    use std::{collections::HashMap, env};
 
     #[tokio::test]
     async fn test_build_request() {
@@ -586,7 +1547,8 @@ mod tests {
         );
         let recipe = create!(
             Recipe,
-            method: "POST".parse().unwrap(),
+            label: Some("User {{user_id}}".into()),
+            method: "POST".into(),
             url: "{{host}}/users/{{user_id}}".into(),
             query: indexmap! {
                 "mode".into() => "{{mode}}".into(),
@@ -614,11 +1576,18 @@ mod tests {
                 id: request.id,
                 profile_id: Some(profile_id),
                 recipe_id,
+                label: Some("User 1".into()),
                 method: Method::POST,
                 url: "http://localhost/users/1?mode=sudo&fast=true"
                     .parse()
                     .unwrap(),
                 body: Some(Vec::from(b"{\"group_id\":\"3\"}").into()),
+                body_command: None,
+                persist: true,
+                expects: None,
+                replay: Default::default(),
+                tls: Default::default(),
+                pagination: None,
                 headers: header_map(expected_headers),
             }
         );
@@ -678,35 +1647,170 @@ mod tests {
                 id: request.id,
                 profile_id: Some(profile_id),
                 recipe_id,
+                label: None,
                 method: Method::GET,
                 url: "http://localhost".parse().unwrap(),
                 headers: (&expected_headers).try_into().unwrap(),
                 body: None,
+                body_command: None,
+                persist: true,
+                expects: None,
+                replay: Default::default(),
+                tls: Default::default(),
+                pagination: None,
             }
         );
     }
 
+    /// A profile's authentication replaces the recipe's, but isn't added to
+    /// recipes that don't have any
+    #[rstest]
+    #[case::replaced(
+        Some(Authentication::Basic {
+            username: "user".into(),
+            password: None,
+        }),
+        Some("Bearer token!")
+    )]
+    #[case::no_recipe_authentication(None, None)]
     #[tokio::test]
-    async fn test_disable_headers_and_query_params() {
-        let context = create!(TemplateContext);
-        let recipe = create!(
-            Recipe,
-            query: indexmap! {
-                "mode".into() => "sudo".into(),
-                "fast".into() => "true".into(),
-            },
-            headers: indexmap! {
-                "Accept".into() => "application/json".into(),
-                "Content-Type".into() => "application/json".into(),
-            },
+    async fn test_profile_authentication(
+        #[case] recipe_authentication: Option<Authentication>,
+        #[case] expected_header: Option<&str>,
+    ) {
+        let profile = create!(
+            Profile,
+            authentication: Some(Authentication::Bearer("{{token}}".into())),
+            data: indexmap! {"token".into() => "token!".into()},
         );
-        let recipe_id = recipe.id.clone();
+        let profile_id = profile.id.clone();
+        let context = create!(
+            TemplateContext,
+            collection: create!(
+                Collection,
+                profiles: indexmap!{profile_id.clone() => profile},
+            ),
+            selected_profile: Some(profile_id.clone()),
+        );
+        let recipe = create!(Recipe, authentication: recipe_authentication);
+
+        let request = RequestBuilder::new(recipe, RecipeOptions::default())
+            .build(&context)
+            .await
+            .unwrap();
+        assert_eq!(
+            request
+                .headers
+                .get(header::AUTHORIZATION)
+                .map(|value| value.to_str().unwrap()),
+            expected_header
+        );
+    }
+
+    /// Method can be templated, and is validated after rendering
+    #[rstest]
+    #[case::static_method("DELETE", Ok(Method::DELETE))]
+    #[case::templated("{{method}}", Ok(Method::PATCH))]
+    #[case::invalid("{{bad_method}}", Err("Invalid HTTP method `GOT`"))]
+    #[tokio::test]
+    async fn test_build_method(
+        #[case] method: &str,
+        #[case] expected: Result<Method, &str>,
+    ) {
+        let profile_data = indexmap! {
+            "method".into() => "patch".into(),
+            "bad_method".into() => "GOT".into(),
+        };
+        let profile = create!(Profile, data: profile_data);
+        let profile_id = profile.id.clone();
+        let context = create!(
+            TemplateContext,
+            collection: create!(
+                Collection,
+                profiles: indexmap!{profile_id.clone() => profile},
+            ),
+            selected_profile: Some(profile_id),
+        );
+        let recipe = create!(Recipe, method: method.into());
+
+        let builder = RequestBuilder::new(recipe, RecipeOptions::default());
+        let result = builder.build(&context).await;
+        match expected {
+            Ok(expected) => assert_eq!(result.unwrap().method, expected),
+            Err(expected) => assert_err!(result, expected),
+        }
+    }
+
+    /// Relative URLs are prefixed with the profile's base URL
+    #[rstest]
+    #[case::relative(
+        "/users/{{id}}",
+        Some("{{host}}/api/"),
+        "https://example.com/api/users/1"
+    )]
+    #[case::no_slash(
+        "users",
+        Some("https://example.com"),
+        "https://example.com/users"
+    )]
+    #[case::absolute(
+        "http://localhost/users",
+        Some("https://example.com"),
+        "http://localhost/users"
+    )]
+    #[case::no_base_url("{{host}}/users", None, "https://example.com/users")]
+    #[tokio::test]
+    async fn test_base_url(
+        #[case] url: &str,
+        #[case] base_url: Option<&str>,
+        #[case] expected: &str,
+    ) {
+        let profile = create!(
+            Profile,
+            base_url: base_url.map(Template::from),
+            data: indexmap! {
+                "host".into() => "https://example.com".into(),
+                "id".into() => "1".into(),
+            },
+        );
+        let profile_id = profile.id.clone();
+        let context = create!(
+            TemplateContext,
+            collection: create!(
+                Collection,
+                profiles: indexmap!{profile_id.clone() => profile},
+            ),
+            selected_profile: Some(profile_id),
+        );
+        let recipe = create!(Recipe, url: url.into());
+
+        let builder = RequestBuilder::new(recipe, RecipeOptions::default());
+        let request = builder.build(&context).await.unwrap();
+        assert_eq!(request.url.as_str(), expected);
+    }
+
+    #[tokio::test]
+    async fn test_disable_headers_and_query_params() {
+        let context = create!(TemplateContext);
+        let recipe = create!(
+            Recipe,
+            query: indexmap! {
+                "mode".into() => "sudo".into(),
+                "fast".into() => "true".into(),
+            },
+            headers: indexmap! {
+                "Accept".into() => "application/json".into(),
+                "Content-Type".into() => "application/json".into(),
+            },
+        );
+        let recipe_id = recipe.id.clone();
 
         let builder = RequestBuilder::new(
             recipe,
             RecipeOptions {
                 disabled_headers: ["Content-Type".to_owned()].into(),
                 disabled_query_parameters: ["fast".to_owned()].into(),
+                ..Default::default()
             },
         );
         let request = builder.build(&context).await.unwrap();
@@ -723,11 +1827,1357 @@ mod tests {
                 id: request.id,
                 profile_id: None,
                 recipe_id,
+                label: None,
                 method: Method::GET,
                 url: "http://localhost?mode=sudo".parse().unwrap(),
                 headers: (&expected_headers).try_into().unwrap(),
                 body: None,
+                body_command: None,
+                persist: true,
+                expects: None,
+                replay: Default::default(),
+                tls: Default::default(),
+                pagination: None,
+            }
+        );
+    }
+
+    /// Profile headers are defaults beneath the recipe's headers. A recipe
+    /// header with the same name (in any case) replaces the profile's, even
+    /// if it's disabled
+    #[tokio::test]
+    async fn test_profile_headers() {
+        let profile = create!(
+            Profile,
+            headers: indexmap! {
+                "Accept-Language".into() => "{{locale}}".into(),
+                "accept".into() => "text/plain".into(),
+                "x-disabled".into() => "profile".into(),
+            },
+            data: indexmap! {"locale".into() => "fr-CA".into()},
+        );
+        let context = create!(
+            TemplateContext,
+            collection: create!(
+                Collection,
+                profiles: indexmap! {profile.id.clone() => profile},
+            ),
+            selected_profile: Some("profile1".into()),
+        );
+        let recipe = create!(
+            Recipe,
+            headers: indexmap! {
+                "Accept".into() => "application/json".into(),
+                "X-Disabled".into() => "recipe".into(),
+            },
+        );
+
+        let builder = RequestBuilder::new(
+            recipe,
+            RecipeOptions {
+                disabled_headers: ["X-Disabled".to_owned()].into(),
+                ..Default::default()
+            },
+        );
+        let request = builder.build(&context).await.unwrap();
+        assert_eq!(
+            request.headers,
+            header_map([
+                ("accept-language", "fr-CA"),
+                ("accept", "application/json"),
+            ])
+        );
+    }
+
+    /// Profile headers also count as defined for the `Content-Type` and
+    /// `Accept` defaults, so they aren't overwritten
+    #[tokio::test]
+    async fn test_profile_headers_block_defaults() {
+        let profile = create!(
+            Profile,
+            headers: indexmap! {
+                "content-type".into() => "text/plain".into(),
+                "Accept".into() => "application/xml".into(),
+            },
+        );
+        let context = create!(
+            TemplateContext,
+            collection: create!(
+                Collection,
+                profiles: indexmap! {profile.id.clone() => profile},
+            ),
+            selected_profile: Some("profile1".into()),
+        );
+        let recipe = create!(
+            Recipe,
+            body: Some(r#"{"a": 1}"#.into()),
+            expects: Some(ContentType::Json),
+        );
+
+        let request = RequestBuilder::new(recipe, RecipeOptions::default())
+            .build(&context)
+            .await
+            .unwrap();
+        assert_eq!(
+            request.headers,
+            header_map([
+                ("content-type", "text/plain"),
+                ("accept", "application/xml"),
+            ])
+        );
+    }
+
+    /// Cookies are encoded and merged into the `Cookie` header. A cookie set
+    /// in both places takes the value from `cookies`
+    #[rstest]
+    #[case::cookies_only(None, "{{token}}", "session=abc123")]
+    #[case::encoded(
+        None,
+        "a b;c,\"d\"%é",
+        "session=a%20b%3Bc%2C%22d%22%25%C3%A9"
+    )]
+    #[case::merged(
+        Some("theme=dark; session=old; lang=en"),
+        "{{token}}",
+        "theme=dark; lang=en; session=abc123"
+    )]
+    #[tokio::test]
+    async fn test_cookies(
+        #[case] header: Option<&str>,
+        #[case] session: &str,
+        #[case] expected: &str,
+    ) {
+        let profile = create!(
+            Profile,
+            data: indexmap! {"token".into() => "abc123".into()},
+        );
+        let context = create!(
+            TemplateContext,
+            collection: create!(
+                Collection,
+                profiles: indexmap! {profile.id.clone() => profile},
+            ),
+            selected_profile: Some("profile1".into()),
+        );
+        let recipe = create!(
+            Recipe,
+            headers: header
+                .into_iter()
+                .map(|value| ("Cookie".to_owned(), value.into()))
+                .collect(),
+            cookies: indexmap! {"session".into() => session.into()},
+        );
+
+        let request = RequestBuilder::new(recipe, RecipeOptions::default())
+            .build(&context)
+            .await
+            .unwrap();
+        assert_eq!(request.headers, header_map([("cookie", expected)]));
+    }
+
+    #[tokio::test]
+    async fn test_cookie_invalid_name() {
+        let context = create!(TemplateContext);
+        let recipe = create!(
+            Recipe,
+            cookies: indexmap! {"bad name".into() => "value".into()},
+        );
+
+        assert_err!(
+            RequestBuilder::new(recipe, RecipeOptions::default())
+                .build(&context)
+                .await
+                .map_err(|error| error.error),
+            "Invalid cookie name `bad name`"
+        );
+    }
+
+    /// Explain every enabled field, and mask credentials
+    #[tokio::test]
+    async fn test_explain() {
+        let profile = create!(
+            Profile,
+            data: indexmap! {"token".into() => "hunter2".into()},
+        );
+        let context = create!(
+            TemplateContext,
+            collection: create!(
+                Collection,
+                profiles: indexmap! {profile.id.clone() => profile},
+            ),
+            selected_profile: Some("profile1".into()),
+        );
+        let recipe = create!(
+            Recipe,
+            url: "http://localhost/{{missing}}".into(),
+            headers: indexmap! {
+                "Accept".into() => "application/json".into(),
+                "Content-Type".into() => "application/json".into(),
+            },
+            authentication: Some(Authentication::Bearer("{{token}}".into())),
+            body: Some("{\"token\": \"{{token}}\"}".into()),
+        );
+        let builder = RequestBuilder::new(
+            recipe,
+            RecipeOptions {
+                disabled_headers: ["Content-Type".to_owned()].into(),
+                ..Default::default()
+            },
+        );
+
+        let explanations = builder.explain(&context).await;
+        let summary = explanations
+            .iter()
+            .map(|field| {
+                (field.field.as_str(), field.explanation.value.as_deref())
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![
+                ("method", Some("GET")),
+                ("URL", None),
+                ("body", Some("{\"token\": \"hunter2\"}")),
+                ("header `Accept`", Some("application/json")),
+                ("bearer token", Some("<sensitive>")),
+            ]
+        );
+        let token = &explanations[4].explanation.keys[0];
+        assert_eq!(token.key, "token");
+        assert_eq!(token.value.as_deref().ok(), Some("<sensitive>"));
+    }
+
+    /// Template overrides are rendered, raw overrides are sent verbatim
+    #[rstest]
+    #[case::template(
+        BodyOverride::Template("{{user_id}}".into()),
+        b"1".as_slice()
+    )]
+    #[case::raw(
+        BodyOverride::Raw(Bytes::from_static(b"{{user_id}}")),
+        b"{{user_id}}".as_slice()
+    )]
+    #[tokio::test]
+    async fn test_body_override(
+        #[case] body: BodyOverride,
+        #[case] expected: &[u8],
+    ) {
+        let profile_data = indexmap! {"user_id".into() => "1".into()};
+        let profile = create!(Profile, data: profile_data);
+        let profile_id = profile.id.clone();
+        let context = create!(
+            TemplateContext,
+            collection: create!(
+                Collection,
+                profiles: indexmap! {profile_id.clone() => profile},
+            ),
+            selected_profile: Some(profile_id),
+        );
+        let recipe = create!(Recipe, body: Some("original".into()));
+
+        let builder = RequestBuilder::new(
+            recipe,
+            RecipeOptions {
+                body: Some(body),
+                ..Default::default()
+            },
+        );
+        let request = builder.build(&context).await.unwrap();
+        assert_eq!(request.body.as_deref(), Some(expected));
+    }
+
+    /// Body command templates are rendered, and can't be combined with a
+    /// static body
+    #[rstest]
+    #[case::command(None, None, Ok(Some(vec!["echo", "1"])))]
+    #[case::override_wins(None, Some("override"), Ok(None))]
+    #[case::both(
+        Some("body"),
+        None,
+        Err("Recipe cannot have both `body` and `body_command`")
+    )]
+    #[tokio::test]
+    async fn test_body_command(
+        #[case] body: Option<&str>,
+        #[case] body_override: Option<&str>,
+        #[case] expected: Result<Option<Vec<&str>>, &str>,
+    ) {
+        let profile = create!(
+            Profile,
+            data: indexmap! {"user_id".into() => "1".into()},
+        );
+        let profile_id = profile.id.clone();
+        let context = create!(
+            TemplateContext,
+            collection: create!(
+                Collection,
+                profiles: indexmap!{profile_id.clone() => profile},
+            ),
+            selected_profile: Some(profile_id),
+        );
+        let recipe = create!(
+            Recipe,
+            body: body.map(Template::from),
+            body_command: Some(vec!["echo".into(), "{{user_id}}".into()]),
+        );
+        let options = RecipeOptions {
+            body: body_override.map(|body| BodyOverride::Template(body.into())),
+            ..Default::default()
+        };
+
+        let result = RequestBuilder::new(recipe, options).build(&context).await;
+        match expected {
+            Ok(expected) => {
+                let request = result.unwrap();
+                assert_eq!(
+                    request.body_command,
+                    expected.map(|command| command
+                        .into_iter()
+                        .map(String::from)
+                        .collect())
+                );
+            }
+            Err(expected) => assert_err!(result, expected),
+        }
+    }
+
+    /// JSON5 bodies are converted to standard JSON after rendering
+    #[tokio::test]
+    async fn test_body_json5() {
+        let context = create!(TemplateContext);
+        let recipe = create!(
+            Recipe,
+            body: Some(
+                "{\n  // Comment\n  name: 'Bob', tags: [1, 2,],\n}".into()
+            ),
+            body_format: BodyFormat::Json5,
+        );
+
+        let request = RequestBuilder::new(recipe, RecipeOptions::default())
+            .build(&context)
+            .await
+            .unwrap();
+        assert_eq!(
+            request.body.as_deref(),
+            Some(br#"{"name":"Bob","tags":[1,2]}"#.as_slice())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_body_json5_error() {
+        let context = create!(TemplateContext);
+        let recipe = create!(
+            Recipe,
+            body: Some("{name: }".into()),
+            body_format: BodyFormat::Json5,
+        );
+
+        assert_err!(
+            RequestBuilder::new(recipe, RecipeOptions::default())
+                .build(&context)
+                .await
+                .map_err(|error| error.error),
+            "Error parsing body as JSON5"
+        );
+    }
+
+    /// A leading `@` loads the body from a file, and `@@` escapes a literal
+    /// `@`. Only a static `@` counts, not one from a rendered value.
+    #[rstest]
+    #[case::file("@{{dir}}/body.bin", b"\x00{{id}}\xff".as_slice())]
+    #[case::file_whitespace(
+        "@ {{dir}}/body.bin\n",
+        b"\x00{{id}}\xff".as_slice()
+    )]
+    #[case::escaped("@@{{id}}", b"@1".as_slice())]
+    #[case::rendered_at("{{at}}", b"@1".as_slice())]
+    #[case::not_leading("user@{{id}}", b"user@1".as_slice())]
+    #[tokio::test]
+    async fn test_body_file(#[case] body: &str, #[case] expected: &[u8]) {
+        let dir = TempDir::new();
+        tokio::fs::write(dir.join("body.bin"), b"\x00{{id}}\xff")
+            .await
+            .unwrap();
+        let context = create!(
+            TemplateContext,
+            overrides: indexmap! {
+                "dir".into() => dir.to_str().unwrap().into(),
+                "id".into() => "1".into(),
+                "at".into() => "@1".into(),
+            },
+        );
+        let recipe = create!(Recipe, body: Some(body.into()));
+
+        let request = RequestBuilder::new(recipe, RecipeOptions::default())
+            .build(&context)
+            .await
+            .unwrap();
+        assert_eq!(request.body.as_deref(), Some(expected));
+    }
+
+    #[tokio::test]
+    async fn test_body_file_missing() {
+        let context = create!(TemplateContext);
+        let recipe =
+            create!(Recipe, body: Some("@/slumber/not/a/file.json".into()));
+
+        assert_err!(
+            RequestBuilder::new(recipe, RecipeOptions::default())
+                .build(&context)
+                .await
+                .map_err(|error| error.error),
+            "Error reading body file"
+        );
+    }
+
+    /// An expected content type sets a default `Accept` header
+    #[rstest]
+    #[case::default(indexmap! {}, "application/json")]
+    #[case::recipe_header(
+        indexmap! {"accept".into() => "application/ld+json".into()},
+        "application/ld+json"
+    )]
+    #[tokio::test]
+    async fn test_expects_accept(
+        #[case] headers: IndexMap<String, Template>,
+        #[case] expected: &str,
+    ) {
+        let context = create!(TemplateContext);
+        let recipe = create!(
+            Recipe,
+            headers: headers,
+            expects: Some(ContentType::Json),
+        );
+
+        let request = RequestBuilder::new(recipe, RecipeOptions::default())
+            .build(&context)
+            .await
+            .unwrap();
+        assert_eq!(request.headers.get(header::ACCEPT).unwrap(), expected);
+        assert_eq!(request.expects, Some(ContentType::Json));
+    }
+
+    /// Bodies without an explicit `Content-Type` get one inferred, unless the
+    /// recipe defines the header (even if it's disabled)
+    #[rstest]
+    #[case::json(r#" {"a": 1}"#, &[], &[], Some("application/json"))]
+    #[case::json_array("[1, 2]", &[], &[], Some("application/json"))]
+    #[case::invalid_json("{a: 1}", &[], &[], None)]
+    #[case::form("a=1&b=x%20y", &[], &[], Some("application/x-www-form-urlencoded"))]
+    #[case::form_empty_value("a=", &[], &[], Some("application/x-www-form-urlencoded"))]
+    #[case::not_form("a=1&b", &[], &[], None)]
+    #[case::text("hello world", &[], &[], None)]
+    #[case::recipe_header(
+        "{}",
+        &[("Content-Type", "text/plain")],
+        &[],
+        Some("text/plain")
+    )]
+    #[case::disabled_header(
+        "{}",
+        &[("content-type", "text/plain")],
+        &["content-type"],
+        None
+    )]
+    #[tokio::test]
+    async fn test_infer_content_type(
+        #[case] body: &str,
+        #[case] headers: &[(&str, &str)],
+        #[case] disabled_headers: &[&str],
+        #[case] expected: Option<&str>,
+    ) {
+        let context = create!(TemplateContext);
+        let recipe = create!(
+            Recipe,
+            headers: headers
+                .iter()
+                .map(|(header, value)| ((*header).into(), (*value).into()))
+                .collect(),
+            body: Some(body.into()),
+        );
+        let options = RecipeOptions {
+            disabled_headers: disabled_headers
+                .iter()
+                .map(|header| (*header).to_owned())
+                .collect(),
+            ..Default::default()
+        };
+
+        let request = RequestBuilder::new(recipe, options)
+            .build(&context)
+            .await
+            .unwrap();
+        assert_eq!(
+            request
+                .headers
+                .get(header::CONTENT_TYPE)
+                .map(|value| value.to_str().unwrap()),
+            expected
+        );
+    }
+
+    /// Headers can sign the body, exactly as it's sent
+    #[tokio::test]
+    async fn test_header_body_signature() {
+        let context = create!(TemplateContext);
+        let recipe = create!(
+            Recipe,
+            body: Some("{name: 'Bob'}".into()),
+            body_format: BodyFormat::Json5,
+            headers: indexmap! {
+                "Digest".into() => "{{sha256(body)}}".into(),
+            },
+        );
+
+        let request = RequestBuilder::new(recipe, RecipeOptions::default())
+            .build(&context)
+            .await
+            .unwrap();
+        // Hash of the formatted body, `{"name":"Bob"}`
+        assert_eq!(
+            request.headers.get("Digest").unwrap(),
+            "840c3985f212fbe59d713f02acf464269bdb7abe7fcd66fb40d52320ef0da799"
+        );
+    }
+
+    /// Multipart bodies are encoded exactly as defined, including header case
+    #[tokio::test]
+    async fn test_multipart() {
+        let path = env::temp_dir().join("slumber-attachment.bin");
+        tokio::fs::write(&path, b"\x00\xff").await.unwrap();
+        let context = create!(TemplateContext);
+        let recipe = create!(
+            Recipe,
+            multipart: Some(Multipart {
+                subtype: "related".into(),
+                boundary: Some("MIME_boundary".into()),
+                parameters: indexmap! {
+                    "type".into() => "text/xml".into(),
+                    "start".into() => "<root@example>".into(),
+                },
+                parts: vec![
+                    MultipartPart {
+                        headers: indexmap! {
+                            "Content-Type".into() => "text/xml".into(),
+                            "Content-ID".into() => "<root@example>".into(),
+                        },
+                        body: Some("<Envelope/>".into()),
+                        file: None,
+                    },
+                    MultipartPart {
+                        headers: indexmap! {
+                            "Content-ID".into() => "<file@example>".into(),
+                        },
+                        body: None,
+                        file: Some(path.to_str().unwrap().into()),
+                    },
+                ],
+            }),
+        );
+
+        let request = RequestBuilder::new(recipe, RecipeOptions::default())
+            .build(&context)
+            .await
+            .unwrap();
+        assert_eq!(
+            request.headers.get(header::CONTENT_TYPE).unwrap(),
+            "multipart/related; boundary=MIME_boundary; type=\"text/xml\"; \
+            start=\"<root@example>\""
+        );
+        assert_eq!(
+            request.body.as_deref(),
+            Some(
+                b"--MIME_boundary\r\n\
+                Content-Type: text/xml\r\n\
+                Content-ID: <root@example>\r\n\
+                \r\n\
+                <Envelope/>\r\n\
+                --MIME_boundary\r\n\
+                Content-ID: <file@example>\r\n\
+                \r\n\
+                \x00\xff\r\n\
+                --MIME_boundary--\r\n"
+                    .as_slice()
+            )
+        );
+    }
+
+    /// Without a boundary, one is generated. An explicit `Content-Type`
+    /// header replaces the generated one.
+    #[rstest]
+    #[case::generated(None, "multipart/form-data; boundary=slumber-")]
+    #[case::explicit(Some("multipart/mixed"), "multipart/mixed")]
+    #[tokio::test]
+    async fn test_multipart_content_type(
+        #[case] header: Option<&str>,
+        #[case] expected_prefix: &str,
+    ) {
+        let context = create!(TemplateContext);
+        let recipe = create!(
+            Recipe,
+            headers: header
+                .map(|header| ("content-type".into(), header.into()))
+                .into_iter()
+                .collect(),
+            multipart: Some(Multipart {
+                subtype: "form-data".into(),
+                boundary: None,
+                parameters: Default::default(),
+                parts: vec![],
+            }),
+        );
+
+        let request = RequestBuilder::new(recipe, RecipeOptions::default())
+            .build(&context)
+            .await
+            .unwrap();
+        let content_type = request.headers.get(header::CONTENT_TYPE).unwrap();
+        assert!(
+            content_type.to_str().unwrap().starts_with(expected_prefix),
+            "{content_type:?}"
+        );
+    }
+
+    #[rstest]
+    #[case::body(
+        Some("body"),
+        "x",
+        MultipartPart {
+            headers: Default::default(),
+            body: None,
+            file: None,
+        },
+        "Recipe cannot have both `body` and `multipart`"
+    )]
+    #[case::boundary_in_part(
+        None,
+        "x",
+        MultipartPart {
+            headers: Default::default(),
+            body: Some("a\r\n--x".into()),
+            file: None,
+        },
+        "Multipart boundary `x` appears in part 0"
+    )]
+    #[case::boundary_length(
+        None,
+        "",
+        MultipartPart {
+            headers: Default::default(),
+            body: None,
+            file: None,
+        },
+        "Multipart boundary must be 1-70 characters, got 0"
+    )]
+    #[case::header_name(
+        None,
+        "x",
+        MultipartPart {
+            headers: indexmap! {"bad header".into() => "".into()},
+            body: None,
+            file: None,
+        },
+        "Error encoding header name `bad header`"
+    )]
+    #[case::body_and_file(
+        None,
+        "x",
+        MultipartPart {
+            headers: Default::default(),
+            body: Some("".into()),
+            file: Some("".into()),
+        },
+        "Part cannot have both `body` and `file`"
+    )]
+    #[tokio::test]
+    async fn test_multipart_error(
+        #[case] body: Option<&str>,
+        #[case] boundary: &str,
+        #[case] part: MultipartPart,
+        #[case] expected_error: &str,
+    ) {
+        let context = create!(TemplateContext);
+        let recipe = create!(
+            Recipe,
+            body: body.map(Template::from),
+            multipart: Some(Multipart {
+                subtype: "form-data".into(),
+                boundary: Some(boundary.into()),
+                parameters: Default::default(),
+                parts: vec![part],
+            }),
+        );
+
+        assert_err!(
+            RequestBuilder::new(recipe, RecipeOptions::default())
+                .build(&context)
+                .await
+                .map_err(|error| error.error),
+            expected_error
+        );
+    }
+
+    /// Large bodies are streamed, with progress tracked in both directions
+    #[tokio::test]
+    async fn test_send_progress() {
+        let request_body = "a".repeat(UPLOAD_CHUNK_SIZE * 3);
+        let response_body = "b".repeat(UPLOAD_CHUNK_SIZE * 2);
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/upload")
+            // Streaming shouldn't change how the body is sent
+            .match_header(
+                "content-length",
+                request_body.len().to_string().as_str(),
+            )
+            .match_header("transfer-encoding", mockito::Matcher::Missing)
+            .match_body(request_body.as_str())
+            .with_body(&response_body)
+            .create_async()
+            .await;
+
+        let request = create!(
+            Request,
+            method: Method::POST,
+            url: format!("{}/upload", server.url()).parse().unwrap(),
+            body: Some(request_body.clone().into()),
+        );
+        let http_engine =
+            HttpEngine::new(&Config::default(), CollectionDatabase::testing());
+        let queued = http_engine.queue(request.into(), false);
+        let progress = Arc::clone(&queued.progress);
+        let record = queued.await.unwrap();
+
+        mock.assert_async().await;
+        assert_eq!(record.response.body.bytes(), response_body.as_bytes());
+        let total = |body: &str| Some(body.len() as u64);
+        assert_eq!(
+            progress.upload(),
+            Some(Transfer {
+                done: request_body.len() as u64,
+                total: total(&request_body),
+            })
+        );
+        assert_eq!(
+            progress.download(),
+            Some(Transfer {
+                done: response_body.len() as u64,
+                total: total(&response_body),
+            })
+        );
+    }
+
+    /// Body command output is streamed to the server, and a failing command
+    /// aborts the request
+    #[rstest]
+    #[case::success(&["printf", "hello"], true)]
+    #[case::failure(&["sh", "-c", "printf hello; exit 1"], false)]
+    #[case::missing(&["slumber-bogus-command"], false)]
+    #[tokio::test]
+    async fn test_send_body_command(
+        #[case] command: &[&str],
+        #[case] success: bool,
+    ) {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/upload")
+            .match_header("transfer-encoding", "chunked")
+            .match_body("hello")
+            .create_async()
+            .await;
+
+        let request = create!(
+            Request,
+            method: Method::POST,
+            url: format!("{}/upload", server.url()).parse().unwrap(),
+            body_command: Some(
+                command.iter().map(|arg| arg.to_string()).collect()
+            ),
+        );
+        let http_engine =
+            HttpEngine::new(&Config::default(), CollectionDatabase::testing());
+        let queued = http_engine.queue(request.into(), false);
+        let progress = Arc::clone(&queued.progress);
+        let result = queued.await;
+
+        if success {
+            result.unwrap();
+            mock.assert_async().await;
+            assert_eq!(
+                progress.upload(),
+                Some(Transfer {
+                    done: 5,
+                    total: None
+                })
+            );
+        } else {
+            assert!(result.is_err());
+        }
+    }
+
+    /// Requests are only saved to history if enabled both globally and for
+    /// the recipe
+    #[rstest]
+    #[case::enabled(true, true, true)]
+    #[case::disabled_config(false, true, false)]
+    #[case::disabled_recipe(true, false, false)]
+    #[tokio::test]
+    async fn test_send_persist(
+        #[case] persist_history: bool,
+        #[case] persist: bool,
+        #[case] expected_stored: bool,
+    ) {
+        let mut server = mockito::Server::new_async().await;
+        server.mock("GET", "/get").create_async().await;
+
+        let request = create!(
+            Request,
+            url: format!("{}/get", server.url()).parse().unwrap(),
+            persist: persist,
+        );
+        let profile_id = request.profile_id.clone();
+        let recipe_id = request.recipe_id.clone();
+        let config = Config {
+            persist_history,
+            ..Config::default()
+        };
+        let database = CollectionDatabase::testing();
+        let http_engine = HttpEngine::new(&config, database.clone());
+        let record = http_engine.send(request.into()).await.unwrap();
+
+        let stored = database
+            .get_last_request(profile_id.as_ref(), &recipe_id)
+            .unwrap()
+            .map(|stored| stored.id);
+        assert_eq!(stored, expected_stored.then_some(record.id));
+    }
+
+    /// A resolve override changes where the request is sent, but not the
+    /// `Host` header
+    #[tokio::test]
+    async fn test_send_resolve() {
+        let mut server = mockito::Server::new_async().await;
+        let port = server.socket_address().port();
+        let mock = server
+            .mock("GET", "/get")
+            .match_header("host", format!("fish.test:{port}").as_str())
+            .create_async()
+            .await;
+
+        let request = create!(
+            Request,
+            url: format!("http://fish.test:{port}/get").parse().unwrap(),
+        );
+        let config = Config {
+            resolve: indexmap! {
+                "fish.test".into() => server.socket_address().ip(),
+            },
+            ..Config::default()
+        };
+        let http_engine =
+            HttpEngine::new(&config, CollectionDatabase::testing());
+        let record = http_engine.send(request.into()).await.unwrap();
+
+        mock.assert_async().await;
+        assert_eq!(record.response.status, StatusCode::OK);
+    }
+
+    /// With conditional requests enabled, validators from the last matching
+    /// response are sent back, and a 304 gets the cached body
+    #[tokio::test]
+    async fn test_send_conditional() {
+        let mut server = mockito::Server::new_async().await;
+        let fresh = server
+            .mock("GET", "/get")
+            .match_header("if-none-match", mockito::Matcher::Missing)
+            .with_header("etag", "\"v1\"")
+            .with_header("vary", "accept")
+            .with_body("hello")
+            .expect(2)
+            .create_async()
+            .await;
+        let not_modified = server
+            .mock("GET", "/get")
+            .match_header("if-none-match", "\"v1\"")
+            .with_status(304)
+            .with_header("etag", "\"v1\"")
+            .create_async()
+            .await;
+
+        let config = Config {
+            conditional_requests: true,
+            ..Config::default()
+        };
+        let database = CollectionDatabase::testing();
+        let http_engine = HttpEngine::new(&config, database.clone());
+        let send = |accept: &str| {
+            let request = create!(
+                Request,
+                url: format!("{}/get", server.url()).parse().unwrap(),
+                headers: header_map([("accept", accept)]),
+            );
+            http_engine.clone().send(request.into())
+        };
+
+        let record = send("text/plain").await.unwrap();
+        assert_eq!(record.response.status, StatusCode::OK);
+        assert!(!record.response.cached);
+
+        let record = send("text/plain").await.unwrap();
+        assert_eq!(record.response.status, StatusCode::NOT_MODIFIED);
+        assert_eq!(record.response.body.bytes(), b"hello".as_slice());
+        assert!(record.response.cached);
+        assert_eq!(
+            record.request.headers.get("if-none-match").unwrap(),
+            "\"v1\""
+        );
+        // The 304 is what goes in history
+        let stored = database
+            .get_last_request(None, &"recipe1".into())
+            .unwrap()
+            .unwrap();
+        assert_eq!(stored.id, record.id);
+        assert_eq!(stored.response.status, StatusCode::NOT_MODIFIED);
+
+        // A different value for a `Vary` header doesn't use the cache
+        let record = send("application/json").await.unwrap();
+        assert_eq!(record.response.status, StatusCode::OK);
+        assert!(!record.response.cached);
+
+        fresh.assert_async().await;
+        not_modified.assert_async().await;
+    }
+
+    /// In offline mode, responses are replayed from history according to the
+    /// recipe's match strategy, and replays aren't stored again
+    #[rstest]
+    #[case::latest(ReplayMatch::Latest, "/a", Ok(StatusCode::CREATED))]
+    #[case::request(ReplayMatch::Request, "/a", Ok(StatusCode::OK))]
+    #[case::no_match(
+        ReplayMatch::Request,
+        "/c",
+        Err(
+            "no response to replay for recipe `recipe1` matching this request"
+        )
+    )]
+    #[tokio::test]
+    async fn test_send_offline(
+        #[case] replay: ReplayMatch,
+        #[case] path: &str,
+        #[case] expected: Result<StatusCode, &str>,
+    ) {
+        let database = CollectionDatabase::testing();
+        let url =
+            |path: &str| format!("http://localhost{path}").parse().unwrap();
+        let old = create!(
+            RequestRecord,
+            request: create!(Request, url: url("/a")).into(),
+            start_time: Utc::now() - chrono::Duration::seconds(10),
+        );
+        let new = create!(
+            RequestRecord,
+            request: create!(Request, url: url("/b")).into(),
+            response: create!(Response, status: StatusCode::CREATED),
+        );
+        database.insert_request(&old).unwrap();
+        database.insert_request(&new).unwrap();
+
+        let config = Config {
+            offline: true,
+            ..Config::default()
+        };
+        let http_engine = HttpEngine::new(&config, database.clone());
+        let request = create!(Request, url: url(path), replay: replay);
+        let result = http_engine.send(request.into()).await;
+        match expected {
+            Ok(status) => {
+                let record = result.unwrap();
+                assert_eq!(record.response.status, status);
+                assert!(record.response.replayed);
+            }
+            Err(expected) => {
+                assert_err!(result.map_err(|error| error.error), expected)
             }
+        }
+
+        // Nothing new was stored
+        let stored = database
+            .get_last_request(None, &"recipe1".into())
+            .unwrap()
+            .map(|stored| stored.id);
+        assert_eq!(stored, Some(new.id));
+    }
+
+    /// Redirects are followed, and credentials are only forwarded to a
+    /// different host if the policy allows it
+    #[rstest]
+    #[case::strip_sensitive(RedirectHeaderPolicy::StripSensitive, false)]
+    #[case::keep_all(RedirectHeaderPolicy::KeepAll, true)]
+    #[tokio::test]
+    async fn test_send_redirect(
+        #[case] redirect_header_policy: RedirectHeaderPolicy,
+        #[case] expect_authorization: bool,
+    ) {
+        // Separate servers have separate ports, so they're different origins
+        let mut start_server = mockito::Server::new_async().await;
+        let mut end_server = mockito::Server::new_async().await;
+        let authorization = if expect_authorization {
+            mockito::Matcher::Exact("Bearer token".into())
+        } else {
+            mockito::Matcher::Missing
+        };
+        let start_mock = start_server
+            .mock("GET", "/start")
+            .match_header("authorization", "Bearer token")
+            .with_status(302)
+            .with_header("location", &format!("{}/end", end_server.url()))
+            .create_async()
+            .await;
+        let end_mock = end_server
+            .mock("GET", "/end")
+            .match_header("authorization", authorization)
+            .match_header("x-custom", "value")
+            .with_body("done")
+            .create_async()
+            .await;
+
+        let request = create!(
+            Request,
+            url: format!("{}/start", start_server.url()).parse().unwrap(),
+            headers: header_map([
+                ("authorization", "Bearer token"),
+                ("x-custom", "value"),
+            ]),
+        );
+        let config = Config {
+            redirect_header_policy,
+            ..Config::default()
+        };
+        let http_engine =
+            HttpEngine::new(&config, CollectionDatabase::testing());
+        let record = http_engine.send(request.into()).await.unwrap();
+
+        start_mock.assert_async().await;
+        end_mock.assert_async().await;
+        assert_eq!(record.response.status, StatusCode::OK);
+        assert_eq!(record.response.body.bytes(), b"done".as_slice());
+    }
+
+    /// Header names are sent lowercase by default, or Title-Case if enabled.
+    /// mockito matches headers case-insensitively, so read the raw request
+    /// off the socket instead
+    #[rstest]
+    #[case::lowercase(false, "x-api-key: 1\r\n")]
+    #[case::title_case(true, "X-Api-Key: 1\r\n")]
+    #[tokio::test]
+    async fn test_title_case_headers(
+        #[case] title_case_headers: bool,
+        #[case] expected: &str,
+    ) {
+        use std::{
+            io::{BufRead, BufReader, Write},
+            net::TcpListener,
+        };
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        // Capture the request head, then send an empty response
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut head = String::new();
+            while !head.ends_with("\r\n\r\n") {
+                reader.read_line(&mut head).unwrap();
+            }
+            reader
+                .get_mut()
+                .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n")
+                .unwrap();
+            head
+        });
+
+        let request = create!(
+            Request,
+            url: url.parse().unwrap(),
+            headers: header_map([("X-API-KEY", "1")]),
+        );
+        let config = Config {
+            title_case_headers,
+            ..Config::default()
+        };
+        let http_engine =
+            HttpEngine::new(&config, CollectionDatabase::testing());
+        http_engine.send(request.into()).await.unwrap();
+
+        let head = server.join().unwrap();
+        assert!(head.contains(expected), "{head}");
+    }
+
+    /// `expect_continue` adds the header based on body size, unless the recipe
+    /// sets `Expect` itself
+    #[rstest]
+    #[case::never(ExpectContinue::Never, "a".repeat(EXPECT_CONTINUE_MIN_SIZE), &[], None)]
+    #[case::always(ExpectContinue::Always, "a".into(), &[], Some("100-continue"))]
+    #[case::always_empty(ExpectContinue::Always, "".into(), &[], None)]
+    #[case::auto_small(ExpectContinue::Auto, "a".into(), &[], None)]
+    #[case::auto_large(
+        ExpectContinue::Auto,
+        "a".repeat(EXPECT_CONTINUE_MIN_SIZE),
+        &[],
+        Some("100-continue")
+    )]
+    #[case::recipe_header(
+        ExpectContinue::Always,
+        "a".into(),
+        &[("Expect", "something-else")],
+        Some("something-else")
+    )]
+    #[tokio::test]
+    async fn test_expect_continue(
+        #[case] expect_continue: ExpectContinue,
+        #[case] body: String,
+        #[case] headers: &[(&str, &str)],
+        #[case] expected: Option<&str>,
+    ) {
+        let context = create!(TemplateContext);
+        let recipe = create!(
+            Recipe,
+            method: "POST".into(),
+            body: Some(body.as_str().into()),
+            headers: headers
+                .iter()
+                .map(|(header, value)| ((*header).into(), (*value).into()))
+                .collect(),
+            expect_continue: expect_continue,
+        );
+
+        let request = RequestBuilder::new(recipe, RecipeOptions::default())
+            .build(&context)
+            .await
+            .unwrap();
+        assert_eq!(
+            request
+                .headers
+                .get(header::EXPECT)
+                .map(|value| value.to_str().unwrap()),
+            expected
+        );
+    }
+
+    /// With `Expect: 100-continue`, the body is still sent if the server
+    /// doesn't reject the request
+    #[tokio::test]
+    async fn test_send_expect_continue() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("PUT", "/upload")
+            .match_header("expect", "100-continue")
+            .match_body("hello")
+            .with_status(201)
+            .create_async()
+            .await;
+
+        let request = create!(
+            Request,
+            method: Method::PUT,
+            url: format!("{}/upload", server.url()).parse().unwrap(),
+            headers: header_map([("expect", "100-continue")]),
+            body: Some("hello".into()),
+        );
+        let http_engine =
+            HttpEngine::new(&Config::default(), CollectionDatabase::testing());
+        let record = http_engine.send(request.into()).await.unwrap();
+
+        mock.assert_async().await;
+        assert_eq!(record.response.status, StatusCode::CREATED);
+    }
+
+    /// If the server rejects a request with `Expect: 100-continue` right away,
+    /// the body is never sent. mockito always reads the body, so use a raw
+    /// socket instead
+    #[tokio::test]
+    async fn test_send_expect_continue_rejected() {
+        use std::{
+            io::{BufRead, BufReader, Read, Write},
+            net::TcpListener,
+            time::Duration,
+        };
+
+        // Big enough to arrive in several pieces, so it all has to be loaded
+        // before the connection is closed
+        let response_body = "b".repeat(UPLOAD_CHUNK_SIZE * 3);
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        // Reject as soon as the head arrives, then collect anything else the
+        // client sends until it hangs up
+        let server = std::thread::spawn({
+            let response_body = response_body.clone();
+            move || {
+                let (stream, _) = listener.accept().unwrap();
+                stream
+                    .set_read_timeout(Some(Duration::from_secs(10)))
+                    .unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut head = String::new();
+                while !head.ends_with("\r\n\r\n") {
+                    reader.read_line(&mut head).unwrap();
+                }
+                let response = format!(
+                    "HTTP/1.1 413 Payload Too Large\r\n\
+                content-length: {}\r\n\r\n{response_body}",
+                    response_body.len()
+                );
+                reader.get_mut().write_all(response.as_bytes()).unwrap();
+                let mut rest = Vec::new();
+                reader.read_to_end(&mut rest).unwrap();
+                rest
+            }
+        });
+
+        let request = create!(
+            Request,
+            method: Method::PUT,
+            url: url.parse().unwrap(),
+            headers: header_map([("expect", "100-continue")]),
+            body: Some("a".repeat(UPLOAD_CHUNK_SIZE * 2).into()),
+        );
+        let http_engine =
+            HttpEngine::new(&Config::default(), CollectionDatabase::testing());
+        let record = http_engine.send(request.into()).await.unwrap();
+
+        assert_eq!(record.response.status, StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(record.response.body.bytes(), response_body.as_bytes());
+        // The connection is closed in the background, so don't block the
+        // runtime while waiting for it
+        let rest = tokio::task::spawn_blocking(|| server.join().unwrap())
+            .await
+            .unwrap();
+        assert!(rest.is_empty(), "Body was sent: {} bytes", rest.len());
+    }
+
+    /// The TLS version actually negotiated with the server is recorded,
+    /// whether it was narrowed down by the client or the server. mockito
+    /// doesn't do TLS, so serve a self-signed cert from a raw socket instead
+    #[rstest]
+    #[case::newest(
+        &[&rustls::version::TLS13, &rustls::version::TLS12],
+        None,
+        TlsVersion::Tls13
+    )]
+    #[case::client_max(
+        &[&rustls::version::TLS13, &rustls::version::TLS12],
+        Some(TlsVersion::Tls12),
+        TlsVersion::Tls12
+    )]
+    #[case::server_max(&[&rustls::version::TLS12], None, TlsVersion::Tls12)]
+    #[tokio::test]
+    async fn test_send_tls_version_negotiated(
+        #[case] server_versions: &[&'static rustls::SupportedProtocolVersion],
+        #[case] max: Option<TlsVersion>,
+        #[case] expected: TlsVersion,
+    ) {
+        use rustls::{
+            Certificate, PrivateKey, ServerConfig, ServerConnection,
+            StreamOwned,
+        };
+        use std::{
+            io::{BufRead, BufReader, Write},
+            net::{Ipv4Addr, TcpListener},
+        };
+
+        let server_config = ServerConfig::builder()
+            .with_safe_default_cipher_suites()
+            .with_safe_default_kx_groups()
+            .with_protocol_versions(server_versions)
+            .unwrap()
+            .with_no_client_auth()
+            .with_single_cert(
+                vec![Certificate(
+                    include_bytes!("../test_data/tls/cert.der").to_vec(),
+                )],
+                PrivateKey(include_bytes!("../test_data/tls/key.der").to_vec()),
+            )
+            .unwrap();
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let url = format!(
+            "https://localhost:{}/",
+            listener.local_addr().unwrap().port()
+        );
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let connection =
+                ServerConnection::new(Arc::new(server_config)).unwrap();
+            let mut stream = StreamOwned::new(connection, stream);
+            let mut reader = BufReader::new(&mut stream);
+            let mut head = String::new();
+            while !head.ends_with("\r\n\r\n") {
+                reader.read_line(&mut head).unwrap();
+            }
+            stream
+                .write_all(
+                    b"HTTP/1.1 200 OK\r\n\
+                    content-length: 0\r\n\
+                    connection: close\r\n\r\n",
+                )
+                .unwrap();
+            stream.conn.send_close_notify();
+            stream.flush().unwrap();
+        });
+
+        // The cert is self-signed, so it can't be verified
+        let config = Config {
+            ignore_certificate_hosts: vec!["localhost".into()],
+            resolve: indexmap! {"localhost".into() => Ipv4Addr::LOCALHOST.into()},
+            ..Config::default()
+        };
+        let request = create!(
+            Request,
+            url: url.parse().unwrap(),
+            tls: TlsVersions { min: None, max },
+        );
+        let http_engine =
+            HttpEngine::new(&config, CollectionDatabase::testing());
+        let record = http_engine.send(request.into()).await.unwrap();
+        assert_eq!(record.response.status, StatusCode::OK);
+        assert_eq!(record.response.tls_version, Some(expected));
+        tokio::task::spawn_blocking(|| server.join().unwrap())
+            .await
+            .unwrap();
+    }
+
+    /// A recipe's TLS versions are combined with the config's, and plain HTTP
+    /// requests aren't affected
+    #[tokio::test]
+    async fn test_send_tls_versions() {
+        let mut server = mockito::Server::new_async().await;
+        server.mock("GET", "/get").create_async().await;
+
+        let config = Config {
+            tls: TlsVersions {
+                min: Some(TlsVersion::Tls13),
+                max: None,
+            },
+            ..Config::default()
+        };
+        let url: Url = format!("{}/get", server.url()).parse().unwrap();
+        let http_engine =
+            HttpEngine::new(&config, CollectionDatabase::testing());
+
+        let request = create!(Request, url: url.clone());
+        let record = http_engine.clone().send(request.into()).await.unwrap();
+        assert_eq!(record.response.tls_version, None);
+
+        let request = create!(
+            Request,
+            url: url,
+            tls: TlsVersions {
+                min: None,
+                max: Some(TlsVersion::Tls12),
+            },
+        );
+        assert_err!(
+            http_engine.send(request.into()).await.map_err(|err| err.error),
+            "Minimum TLS version (TLS 1.3) is higher than the maximum (TLS 1.2)"
         );
     }
 }
@@ -0,0 +1,59 @@
+//! Checks on responses beyond their content, from a recipe's `assert` field
+
+use crate::{collection::Assertions, http::RequestRecord};
+use derive_more::Display;
+
+/// A single assertion that a response didn't pass
+#[derive(Debug, Display, PartialEq)]
+pub enum AssertionFailure {
+    #[display("took {actual}ms, expected at most {max}ms")]
+    Duration { actual: i64, max: u64 },
+}
+
+impl Assertions {
+    /// Check a completed request against every assertion. Returns each one
+    /// that failed, so an empty list means the response passed.
+    pub fn check(&self, record: &RequestRecord) -> Vec<AssertionFailure> {
+        let mut failures = Vec::new();
+        if let Some(max) = self.max_duration {
+            let actual = record.duration().num_milliseconds();
+            if actual > max as i64 {
+                failures.push(AssertionFailure::Duration { actual, max });
+            }
+        }
+        failures
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+    use chrono::{Duration, Utc};
+    use factori::create;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::none(None, 100, &[])]
+    #[case::under(Some(500), 499, &[])]
+    #[case::exact(Some(500), 500, &[])]
+    #[case::over(
+        Some(500),
+        501,
+        &[AssertionFailure::Duration { actual: 501, max: 500 }]
+    )]
+    fn test_check_duration(
+        #[case] max_duration: Option<u64>,
+        #[case] duration_ms: i64,
+        #[case] expected: &[AssertionFailure],
+    ) {
+        let start_time = Utc::now();
+        let record = create!(
+            RequestRecord,
+            start_time: start_time,
+            end_time: start_time + Duration::milliseconds(duration_ms),
+        );
+        let assertions = Assertions { max_duration };
+        assert_eq!(assertions.check(&record), expected);
+    }
+}
@@ -0,0 +1,160 @@
+//! Conditional requests. With `conditional_requests` enabled, we remember the
+//! validators (`ETag`/`Last-Modified`) from past responses and send them back
+//! on the next identical request. If the server says the resource hasn't
+//! changed, the body is served from history.
+
+use crate::{
+    http::{Body, HttpEngine, Request, RequestRecord, Response},
+    util::ResultExt,
+};
+use reqwest::{
+    header::{self, HeaderMap},
+    Method, StatusCode,
+};
+use std::sync::Arc;
+use tracing::info;
+
+impl HttpEngine {
+    /// If there's a cached response for this request, add its validators to
+    /// the request so the server can tell us if it's unchanged. Returns the
+    /// request to send, and the body to use if the server responds with
+    /// `304 Not Modified`. If there's nothing cached, the request is returned
+    /// untouched.
+    pub(super) fn add_validators(
+        &self,
+        request: Arc<Request>,
+    ) -> (Arc<Request>, Option<Body>) {
+        if !is_cacheable(&request) {
+            return (request, None);
+        }
+        let Ok(Some(cached)) = self
+            .database
+            .find_last_request(
+                request.profile_id.as_ref(),
+                &request.recipe_id,
+                |record| matches_cached(&request, record),
+            )
+            .traced()
+        else {
+            return (request, None);
+        };
+        info!(cached_request_id = %cached.id, "Revalidating cached response");
+
+        let mut headers = request.headers.clone();
+        let cached_headers = &cached.response.headers;
+        if let Some(etag) = cached_headers.get(header::ETAG) {
+            headers.insert(header::IF_NONE_MATCH, etag.clone());
+        }
+        if let Some(last_modified) = cached_headers.get(header::LAST_MODIFIED) {
+            headers.insert(header::IF_MODIFIED_SINCE, last_modified.clone());
+        }
+        // Build a new request so history shows exactly what was sent
+        let request = Request {
+            id: request.id,
+            headers,
+            ..request.duplicate()
+        };
+        (request.into(), Some(cached.response.body))
+    }
+}
+
+impl Response {
+    /// If this is a `304 Not Modified` for a request that we revalidated, fill
+    /// in the cached body
+    pub(super) fn apply_cached_body(&mut self, cached_body: Option<Body>) {
+        if let Some(body) = cached_body {
+            if self.status == StatusCode::NOT_MODIFIED && !self.headers_only {
+                self.body = body;
+                self.cached = true;
+            }
+        }
+    }
+}
+
+/// Can this request reuse a cached response? Only safe methods are cached,
+/// and we stay out of the way if the request already has its own validators
+fn is_cacheable(request: &Request) -> bool {
+    (request.method == Method::GET || request.method == Method::HEAD)
+        && request.body_command.is_none()
+        && !request.headers.contains_key(header::IF_NONE_MATCH)
+        && !request.headers.contains_key(header::IF_MODIFIED_SINCE)
+}
+
+/// Is this past exchange a valid cache entry for the request? The request has
+/// to be identical (by the same standard as
+/// [crate::collection::ReplayMatch::Request]), the response has to have a full
+/// body and a validator, and any headers named in the response's `Vary` have
+/// to match as well.
+fn matches_cached(request: &Request, record: &RequestRecord) -> bool {
+    let response = &record.response;
+    record.request.method == request.method
+        && record.request.url == request.url
+        && record.request.body == request.body
+        // 304s are skipped because they may omit headers, e.g. `Vary`. We go
+        // back to the full response they were validated against instead.
+        && response.status == StatusCode::OK
+        && !response.headers_only
+        && (response.headers.contains_key(header::ETAG)
+            || response.headers.contains_key(header::LAST_MODIFIED))
+        && vary_matches(
+            &response.headers,
+            &record.request.headers,
+            &request.headers,
+        )
+}
+
+/// Do two requests match on every header named in a response's `Vary`?
+/// `Vary: *` means the response can never be reused.
+fn vary_matches(
+    response_headers: &HeaderMap,
+    cached_headers: &HeaderMap,
+    headers: &HeaderMap,
+) -> bool {
+    response_headers
+        .get_all(header::VARY)
+        .iter()
+        .flat_map(|value| value.to_str().unwrap_or("*").split(','))
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .all(|name| {
+            name != "*"
+                && cached_headers
+                    .get_all(name)
+                    .iter()
+                    .eq(headers.get_all(name))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::header_map;
+    use indexmap::indexmap;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::no_vary(None, "a", "b", true)]
+    #[case::same(Some("accept"), "a", "a", true)]
+    #[case::different(Some("Accept, Accept-Language"), "a", "b", false)]
+    #[case::unrelated(Some("accept-language"), "a", "b", true)]
+    #[case::star(Some("*"), "a", "a", false)]
+    fn test_vary_matches(
+        #[case] vary: Option<&str>,
+        #[case] cached_accept: &str,
+        #[case] accept: &str,
+        #[case] expected: bool,
+    ) {
+        let response_headers = match vary {
+            Some(vary) => header_map(indexmap! {"vary" => vary}),
+            None => HeaderMap::new(),
+        };
+        assert_eq!(
+            vary_matches(
+                &response_headers,
+                &header_map(indexmap! {"accept" => cached_accept}),
+                &header_map(indexmap! {"accept" => accept}),
+            ),
+            expected
+        );
+    }
+}
@@ -0,0 +1,100 @@
+//! The reqwest clients used to send requests
+
+use crate::{
+    config::Config,
+    http::{tls::NegotiatedVersions, TlsVersion, TlsVersions},
+};
+use anyhow::Context;
+use indexmap::IndexMap;
+use reqwest::{Client, ClientBuilder};
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+const USER_AGENT: &str =
+    concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+
+/// A client for each combination of settings that can vary between requests.
+/// Each client has its own connection pool, so they're only built once they're
+/// needed, then reused.
+#[derive(Debug)]
+pub(super) struct Clients {
+    max_idle_per_host: Option<usize>,
+    idle_timeout: Option<u64>,
+    tcp_keepalive: Option<u64>,
+    title_case_headers: bool,
+    resolve: IndexMap<String, IpAddr>,
+    clients: Mutex<HashMap<ClientKey, (Client, Arc<NegotiatedVersions>)>>,
+}
+
+/// Whether TLS cert errors are ignored, and the allowed TLS versions
+type ClientKey = (bool, TlsVersion, TlsVersion);
+
+impl Clients {
+    pub fn new(config: &Config) -> Self {
+        let pool = &config.connection_pool;
+        Self {
+            max_idle_per_host: pool.max_idle_per_host,
+            idle_timeout: pool.idle_timeout,
+            tcp_keepalive: pool.tcp_keepalive,
+            title_case_headers: config.title_case_headers,
+            resolve: config.resolve.clone(),
+            clients: Default::default(),
+        }
+    }
+
+    /// Get a client restricted to the given TLS versions, along with the
+    /// TLS versions its connections have negotiated. If `danger` is enabled,
+    /// the client ignores TLS cert errors. Only use that if the user
+    /// specifically wants to ignore errors for the request!
+    pub fn get(
+        &self,
+        danger: bool,
+        tls: TlsVersions,
+    ) -> anyhow::Result<(Client, Arc<NegotiatedVersions>)> {
+        let (min, max) = tls.bounds()?;
+        let mut clients = self.clients.lock().expect("Lock poisoned");
+        if let Some(entry) = clients.get(&(danger, min, max)) {
+            return Ok(entry.clone());
+        }
+        let negotiated = Arc::<NegotiatedVersions>::default();
+        let client = self
+            .builder()
+            .use_preconfigured_tls(
+                tls.client_config(danger, Arc::clone(&negotiated))?,
+            )
+            // Needed to match responses to their negotiated TLS version
+            .tls_info(true)
+            .build()
+            .context("Error building HTTP client")?;
+        let entry = (client, negotiated);
+        clients.insert((danger, min, max), entry.clone());
+        Ok(entry)
+    }
+
+    fn builder(&self) -> ClientBuilder {
+        let mut builder = Client::builder()
+            .user_agent(USER_AGENT)
+            .redirect(reqwest::redirect::Policy::none());
+        if let Some(max_idle) = self.max_idle_per_host {
+            builder = builder.pool_max_idle_per_host(max_idle);
+        }
+        if let Some(timeout) = self.idle_timeout {
+            builder = builder.pool_idle_timeout(Duration::from_secs(timeout));
+        }
+        if let Some(interval) = self.tcp_keepalive {
+            builder = builder.tcp_keepalive(Duration::from_secs(interval));
+        }
+        if self.title_case_headers {
+            builder = builder.http1_title_case_headers();
+        }
+        for (hostname, address) in &self.resolve {
+            // reqwest ignores the port, and uses the one from the URL
+            builder = builder.resolve(hostname, SocketAddr::new(*address, 0));
+        }
+        builder
+    }
+}
@@ -0,0 +1,93 @@
+//! Streaming request bodies from a command's output
+
+use crate::http::{RequestProgress, UPLOAD_CHUNK_SIZE};
+use bytes::{Bytes, BytesMut};
+use futures::{stream, Stream, TryStreamExt};
+use std::{io, process::Stdio, sync::Arc};
+use tokio::{
+    io::AsyncReadExt,
+    process::{Child, ChildStdout, Command},
+};
+use tracing::{debug, info};
+
+/// Spawn a command and stream its stdout, to be used as a request body. The
+/// command isn't spawned until the stream is first polled, and output is only
+/// read as the HTTP client pulls it. If the client is slow, the command will
+/// block on a full pipe, so memory use stays bounded.
+///
+/// If the command can't be spawned, or exits with a non-zero status, the
+/// stream yields an error, which aborts the request. If the stream is dropped
+/// early (e.g. the request is cancelled), the command is killed.
+pub(super) fn command_stream(
+    command: Vec<String>,
+    progress: Arc<RequestProgress>,
+) -> impl Stream<Item = io::Result<Bytes>> {
+    // Total size is unknown until the command exits
+    progress.start_upload(None);
+    stream::once(async move { spawn(&command) })
+        .map_ok(move |(program, child, stdout)| {
+            read_stdout(program, child, stdout, Arc::clone(&progress))
+        })
+        .try_flatten()
+}
+
+fn spawn(command: &[String]) -> io::Result<(String, Child, ChildStdout)> {
+    let [program, args @ ..] = command else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Body command is empty",
+        ));
+    };
+    info!(?command, "Spawning body command");
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        // Nothing reads stderr, so don't let it clog up a pipe
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|error| {
+            io::Error::new(
+                error.kind(),
+                format!("Error executing body command `{program}`: {error}"),
+            )
+        })?;
+    // Stdout is always present because we piped it
+    let stdout = child.stdout.take().expect("Child stdout not piped");
+    Ok((program.clone(), child, stdout))
+}
+
+/// Read chunks of stdout until it closes, then check the command's exit
+/// status
+fn read_stdout(
+    program: String,
+    child: Child,
+    stdout: ChildStdout,
+    progress: Arc<RequestProgress>,
+) -> impl Stream<Item = io::Result<Bytes>> {
+    stream::try_unfold((child, stdout), move |(mut child, mut stdout)| {
+        let program = program.clone();
+        let progress = Arc::clone(&progress);
+        async move {
+            let mut buffer = BytesMut::with_capacity(UPLOAD_CHUNK_SIZE);
+            let read = stdout.read_buf(&mut buffer).await?;
+            if read > 0 {
+                progress.add_uploaded(read);
+                return Ok(Some((buffer.freeze(), (child, stdout))));
+            }
+
+            // Stdout is closed, so the command should be done
+            let status = child.wait().await?;
+            debug!(%status, "Body command exited");
+            if status.success() {
+                Ok(None)
+            } else {
+                Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    format!("Body command `{program}` exited with {status}"),
+                ))
+            }
+        }
+    })
+}
@@ -0,0 +1,204 @@
+//! Edit a built request as plain text, so it can be tweaked right before it's
+//! sent
+
+use crate::{collection::Method, http::Request};
+use anyhow::{anyhow, bail, Context};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use std::fmt::Write;
+use url::Url;
+
+impl Request {
+    /// Format the request as an HTTP message: a `METHOD URL` line, then one
+    /// line per header, then a blank line and the body. This is the format
+    /// accepted by [Self::edit].
+    pub fn to_editable(&self) -> anyhow::Result<String> {
+        if self.body_command.is_some() {
+            bail!("Requests with `body_command` can't be edited");
+        }
+
+        // Writing to a string can't fail
+        let mut buf = String::new();
+        let _ = writeln!(&mut buf, "{} {}", self.method, self.url);
+        for (header, value) in &self.headers {
+            let value =
+                value.to_str().context("Error decoding header value")?;
+            let _ = writeln!(&mut buf, "{header}: {value}");
+        }
+        buf.push('\n');
+        if let Some(body) = self.body_str()? {
+            buf.push_str(body);
+        }
+        Ok(buf)
+    }
+
+    /// Build a replacement for this request with the edits from some text,
+    /// in the format generated by [Self::to_editable]. Everything other than
+    /// method, URL, headers, and body (including the ID) is kept from this
+    /// request. An empty body is treated as no body. If the text is blank, the
+    /// user wants to call off the request, so return `None`.
+    pub fn edit(&self, text: &str) -> anyhow::Result<Option<Self>> {
+        if text.trim().is_empty() {
+            return Ok(None);
+        }
+        let (head, body) = split_message(text);
+        let mut lines = head.lines();
+
+        let request_line = lines.next().unwrap_or_default();
+        let (method, url) = request_line
+            .trim()
+            .split_once(' ')
+            .ok_or_else(|| anyhow!("Expected `METHOD URL` on first line"))?;
+        let method: Method = method.to_owned().try_into()?;
+        let url: Url = url
+            .trim()
+            .parse()
+            .with_context(|| format!("Invalid URL: `{url}`"))?;
+
+        let mut headers = HeaderMap::new();
+        // Line numbers are 1-indexed, and the request line was first
+        for (i, line) in lines.enumerate() {
+            let line_number = i + 2;
+            let (header, value) = line.split_once(':').ok_or_else(|| {
+                anyhow!("Expected `Header: value` on line {line_number}")
+            })?;
+            let header: HeaderName =
+                header.trim().parse().with_context(|| {
+                    format!("Invalid header name on line {line_number}")
+                })?;
+            let value: HeaderValue =
+                value.trim().parse().with_context(|| {
+                    format!("Invalid header value on line {line_number}")
+                })?;
+            headers.append(header, value);
+        }
+
+        Ok(Some(Self {
+            method: method.into(),
+            url,
+            headers,
+            body: (!body.is_empty()).then(|| body.to_owned().into()),
+            id: self.id,
+            ..self.duplicate()
+        }))
+    }
+}
+
+/// Split text into head and body at the first blank line. Accept either line
+/// ending, since some editors will change them. The body can contain blank
+/// lines of its own (e.g. multipart), so only the earliest one counts. If
+/// there's no blank line, the whole thing is the head.
+fn split_message(text: &str) -> (&str, &str) {
+    for (i, _) in text.match_indices('\n') {
+        let rest = &text[i + 1..];
+        if let Some(body) = rest
+            .strip_prefix('\n')
+            .or_else(|| rest.strip_prefix("\r\n"))
+        {
+            let head = &text[..i];
+            return (head.strip_suffix('\r').unwrap_or(head), body);
+        }
+    }
+    (text, "")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{test_util::*, util::assert_err};
+    use factori::create;
+    use indexmap::indexmap;
+    use rstest::rstest;
+
+    /// Formatting a request and parsing it back should be lossless
+    #[test]
+    fn test_edit_round_trip() {
+        let request = create!(
+            Request,
+            method: reqwest::Method::POST,
+            url: "http://localhost/url?q=1".parse().unwrap(),
+            headers: header_map(indexmap! {
+                "content-type" => "application/json",
+                "x-empty" => "",
+            }),
+            body: Some("{\n  \"a\": 1\n}\n\nmore".into()),
+        );
+        let text = request.to_editable().unwrap();
+        assert_eq!(
+            text,
+            "POST http://localhost/url?q=1\ncontent-type: application/json\n\
+            x-empty: \n\n{\n  \"a\": 1\n}\n\nmore"
+        );
+
+        let edited = request.edit(&text).unwrap().unwrap();
+        assert_eq!(edited, request);
+    }
+
+    #[test]
+    fn test_edit() {
+        let request = create!(Request, body: Some("body".into()));
+        let edited = request
+            .edit("put http://localhost/new\r\nX-New:  value\r\n\r\n")
+            .unwrap()
+            .unwrap();
+        assert_eq!(edited.method, reqwest::Method::PUT);
+        assert_eq!(edited.url.as_str(), "http://localhost/new");
+        assert_eq!(edited.headers, header_map(indexmap! {"x-new" => "value"}));
+        assert_eq!(edited.body, None);
+        assert_eq!(edited.recipe_id, request.recipe_id);
+
+        // Blank text cancels the request
+        assert_eq!(request.edit(" \n").unwrap(), None);
+    }
+
+    /// A body with CRLF blank lines (e.g. multipart) should be split from the
+    /// head at the first blank line, not inside the body
+    #[rstest]
+    #[case::lf_head("\n")]
+    #[case::crlf_head("\r\n")]
+    fn test_edit_crlf_body(#[case] newline: &str) {
+        let body = "--boundary\r\nContent-Disposition: form-data; \
+            name=\"a\"\r\n\r\nvalue\r\n--boundary--\r\n";
+        let request = create!(Request);
+        let edited = request
+            .edit(&format!(
+                "POST http://localhost/{newline}\
+                content-type: multipart/form-data; boundary=boundary\
+                {newline}{newline}{body}"
+            ))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            edited.headers,
+            header_map(indexmap! {
+                "content-type" => "multipart/form-data; boundary=boundary"
+            })
+        );
+        assert_eq!(edited.body.as_deref(), Some(body.as_bytes()));
+
+        // The editable format uses LF in the head, so this round trips
+        let request = create!(Request, body: Some(body.into()));
+        let edited = request.edit(&request.to_editable().unwrap()).unwrap();
+        assert_eq!(edited, Some(request));
+    }
+
+    #[rstest]
+    #[case::no_url("GET", "Expected `METHOD URL` on first line")]
+    #[case::method("GIT http://localhost", "Invalid HTTP method `GIT`")]
+    #[case::url("GET localhost", "Invalid URL: `localhost`")]
+    #[case::header(
+        "GET http://localhost\nbad",
+        "Expected `Header: value` on line 2"
+    )]
+    #[case::header_name(
+        "GET http://localhost\nx-good: 1\nbad header: 2",
+        "Invalid header name on line 3"
+    )]
+    fn test_edit_error(#[case] text: &str, #[case] expected_error: &str) {
+        assert_err!(create!(Request).edit(text), expected_error);
+    }
+
+    #[test]
+    fn test_to_editable_body_command() {
+        let request = create!(Request, body_command: Some(vec!["cat".into()]));
+        assert_err!(request.to_editable(), "can't be edited");
+    }
+}
@@ -0,0 +1,211 @@
+//! `Expect: 100-continue` handling, so large uploads aren't wasted on
+//! requests the server is going to reject anyway
+
+use crate::collection::ExpectContinue;
+use bytes::Bytes;
+use futures::{stream, Stream, TryStreamExt};
+use reqwest::header::{self, HeaderMap};
+use std::{
+    error::Error,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::sync::watch;
+use tracing::debug;
+
+/// With [ExpectContinue::Auto], bodies at least this large ask for approval
+/// before they're sent
+pub const EXPECT_CONTINUE_MIN_SIZE: usize = 1024 * 1024;
+
+/// How long to hold back a body before sending it anyway. The HTTP client
+/// doesn't tell us when a `100 Continue` arrives, so we have to wait this long
+/// for a rejection either way. Many servers never send a `100` at all,
+/// which is why the wait can't be longer.
+pub const EXPECT_CONTINUE_TIMEOUT: Duration = Duration::from_secs(1);
+
+type BoxError = Box<dyn Error + Send + Sync>;
+
+/// Should an `Expect: 100-continue` header be added to a request? `body_size`
+/// is `None` if there's no body. Streamed bodies (from a command) have no
+/// known size, so they only get the header with [ExpectContinue::Always].
+pub(super) fn should_expect_continue(
+    mode: ExpectContinue,
+    body_size: Option<usize>,
+    streamed: bool,
+) -> bool {
+    let has_body = streamed || body_size.is_some_and(|size| size > 0);
+    match mode {
+        ExpectContinue::Never => false,
+        ExpectContinue::Always => has_body,
+        ExpectContinue::Auto => {
+            body_size.is_some_and(|size| size >= EXPECT_CONTINUE_MIN_SIZE)
+        }
+    }
+}
+
+/// Does this request wait for the server's approval before sending a body?
+/// This applies whether the header was added for the recipe's
+/// `expect_continue` or written into the recipe by hand.
+pub(super) fn expects_continue(headers: &HeaderMap) -> bool {
+    headers.get(header::EXPECT).is_some_and(|value| {
+        value.as_bytes().eq_ignore_ascii_case(b"100-continue")
+    })
+}
+
+/// Holds back a request body until the server has had a chance to reject the
+/// request. See [Self::gate].
+#[derive(Debug)]
+pub(super) struct ContinueGate {
+    /// Set once a final response arrives. Dropping this unblocks the body
+    /// stream, which then aborts instead of sending anything
+    answered: watch::Sender<bool>,
+    /// Has the body started sending? Checked under the same lock as
+    /// `answered`, so the stream and [Self::answer] agree on what happened
+    started: Arc<Mutex<bool>>,
+}
+
+impl ContinueGate {
+    /// Wrap a body stream so nothing is sent until `timeout` has passed
+    /// (normally [EXPECT_CONTINUE_TIMEOUT]). If the server responds within
+    /// that time, the body is never sent. Once the caller is done with the
+    /// response and drops the gate, the body stream fails, which closes the
+    /// connection rather than leaving it half-written.
+    pub fn gate<S, E>(
+        body: S,
+        timeout: Duration,
+    ) -> (Self, impl Stream<Item = Result<Bytes, BoxError>>)
+    where
+        S: Stream<Item = Result<Bytes, E>>,
+        E: Into<BoxError>,
+    {
+        let (answered, mut receiver) = watch::channel(false);
+        let started = Arc::new(Mutex::new(false));
+        let stream = stream::once({
+            let started = Arc::clone(&started);
+            async move {
+                let wait = receiver.wait_for(|answered| *answered);
+                let _ = tokio::time::timeout(timeout, wait).await;
+                {
+                    let mut started = started.lock().expect("Lock poisoned");
+                    if !*receiver.borrow() {
+                        // No answer yet, so go ahead
+                        *started = true;
+                        return Ok(body.map_err(Into::into));
+                    }
+                }
+                debug!("Server responded early, request body won't be sent");
+                // This only finishes after the gate is dropped
+                let _ = receiver.wait_for(|_| false).await;
+                Err(BoxError::from(
+                    "Server responded before the request body was sent",
+                ))
+            }
+        })
+        .try_flatten();
+        (Self { answered, started }, stream)
+    }
+
+    /// The server sent a final response, so the body shouldn't be sent if it
+    /// hasn't started yet. Returns `true` if the body was held back.
+    ///
+    /// If so, the connection can't be reused, and the HTTP client won't report
+    /// the end of the response body until the connection closes. The caller
+    /// has to decide for itself when it has the whole response, then drop the
+    /// gate.
+    pub fn answer(&self) -> bool {
+        let started = self.started.lock().expect("Lock poisoned");
+        self.answered.send_replace(true);
+        !*started
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+    use reqwest::header::HeaderValue;
+    use rstest::rstest;
+    use std::convert::Infallible;
+
+    #[rstest]
+    #[case::never(
+        ExpectContinue::Never,
+        Some(EXPECT_CONTINUE_MIN_SIZE),
+        false,
+        false
+    )]
+    #[case::always(ExpectContinue::Always, Some(1), false, true)]
+    #[case::always_empty(ExpectContinue::Always, Some(0), false, false)]
+    #[case::always_no_body(ExpectContinue::Always, None, false, false)]
+    #[case::always_streamed(ExpectContinue::Always, None, true, true)]
+    #[case::auto_small(
+        ExpectContinue::Auto,
+        Some(EXPECT_CONTINUE_MIN_SIZE - 1),
+        false,
+        false
+    )]
+    #[case::auto_large(
+        ExpectContinue::Auto,
+        Some(EXPECT_CONTINUE_MIN_SIZE),
+        false,
+        true
+    )]
+    #[case::auto_streamed(ExpectContinue::Auto, None, true, false)]
+    fn test_should_expect_continue(
+        #[case] mode: ExpectContinue,
+        #[case] body_size: Option<usize>,
+        #[case] streamed: bool,
+        #[case] expected: bool,
+    ) {
+        assert_eq!(should_expect_continue(mode, body_size, streamed), expected);
+    }
+
+    #[rstest]
+    #[case::missing(None, false)]
+    #[case::lowercase(Some("100-continue"), true)]
+    #[case::uppercase(Some("100-Continue"), true)]
+    #[case::other(Some("something-else"), false)]
+    fn test_expects_continue(
+        #[case] value: Option<&'static str>,
+        #[case] expected: bool,
+    ) {
+        let mut headers = HeaderMap::new();
+        if let Some(value) = value {
+            headers.insert(header::EXPECT, HeaderValue::from_static(value));
+        }
+        assert_eq!(expects_continue(&headers), expected);
+    }
+
+    fn body() -> impl Stream<Item = Result<Bytes, Infallible>> {
+        stream::iter([Ok(Bytes::from("hello"))])
+    }
+
+    const TIMEOUT: Duration = Duration::from_millis(50);
+
+    /// With no answer from the server, the body is sent after the timeout
+    #[tokio::test]
+    async fn test_gate_timeout() {
+        let (_gate, stream) = ContinueGate::gate(body(), TIMEOUT);
+        let chunks: Vec<_> = stream.map(|chunk| chunk.unwrap()).collect().await;
+        assert_eq!(chunks, [Bytes::from("hello")]);
+    }
+
+    /// If the server answers first, the body is never sent
+    #[tokio::test]
+    async fn test_gate_answered() {
+        let (gate, stream) = ContinueGate::gate(body(), TIMEOUT);
+        let mut stream = Box::pin(stream);
+        assert!(gate.answer());
+        // Stream stays blocked until the gate is dropped
+        tokio::select! {
+            _ = stream.next() => panic!("Body stream should be blocked"),
+            _ = tokio::time::sleep(TIMEOUT * 2) => {}
+        }
+        drop(gate);
+        let error = stream.next().await.unwrap().unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Server responded before the request body was sent"
+        );
+    }
+}
@@ -0,0 +1,285 @@
+//! Generate code for third-party clients from a built request
+
+use crate::http::Request;
+use anyhow::{bail, Context};
+use derive_more::Display;
+use indexmap::IndexMap;
+use std::fmt::Write;
+
+/// A format that a request can be exported to, e.g. to share a repro with
+/// someone who doesn't use Slumber. Each format generates an equivalent
+/// request, including method, URL (with query), headers, and body.
+#[derive(Copy, Clone, Debug, Display, PartialEq)]
+pub enum RequestFormat {
+    /// A shell command for cURL
+    #[display("cURL")]
+    Curl,
+    /// A JavaScript `fetch()` call
+    #[display("fetch()")]
+    Fetch,
+    /// A shell command for HTTPie
+    #[display("HTTPie")]
+    Httpie,
+}
+
+impl RequestFormat {
+    /// Generate the request in this format.
+    ///
+    /// This fails if one of the headers or body is binary and can't be
+    /// converted to UTF-8, or if the request can't be expressed in this format.
+    pub fn generate(self, request: &Request) -> anyhow::Result<String> {
+        match self {
+            Self::Curl => curl(request),
+            Self::Fetch => fetch(request),
+            Self::Httpie => httpie(request),
+        }
+    }
+}
+
+// These writes are all infallible because we're writing to a string, but use
+// ? because it's shorter than unwrap().
+
+fn curl(request: &Request) -> anyhow::Result<String> {
+    let mut buf = String::new();
+
+    // Pipe the command's output into curl
+    if let Some(command) = &request.body_command {
+        write!(&mut buf, "{} | ", shell_command(command))?;
+    }
+
+    let method = &request.method;
+    let url = shell_quote(request.url.as_str());
+    write!(&mut buf, "curl -X{method} --url {url}")?;
+
+    for (header, value) in headers(request)? {
+        let header = shell_quote(&format!("{header}: {value}"));
+        write!(&mut buf, " --header {header}")?;
+    }
+
+    if let Some(body) = request.body_str()? {
+        // --data would read a file if the body starts with @
+        write!(&mut buf, " --data-raw {}", shell_quote(body))?;
+    } else if request.body_command.is_some() {
+        write!(&mut buf, " --data-binary @-")?;
+    }
+
+    Ok(buf)
+}
+
+fn fetch(request: &Request) -> anyhow::Result<String> {
+    if request.body_command.is_some() {
+        bail!("Requests with `body_command` can't be exported to fetch()");
+    }
+
+    // JSON string literals are valid JS string literals
+    let js_string =
+        |s: &str| serde_json::to_string(s).expect("Strings always serialize");
+
+    let mut buf = String::new();
+    writeln!(&mut buf, "fetch({}, {{", js_string(request.url.as_str()))?;
+    writeln!(
+        &mut buf,
+        "  method: {},",
+        js_string(request.method.as_str())
+    )?;
+
+    // An object can't repeat keys, so join repeated headers into one value.
+    // This is equivalent for all request headers.
+    let mut headers_by_name: IndexMap<&str, Vec<&str>> = IndexMap::new();
+    for (header, value) in headers(request)? {
+        headers_by_name.entry(header).or_default().push(value);
+    }
+    if !headers_by_name.is_empty() {
+        writeln!(&mut buf, "  headers: {{")?;
+        for (header, values) in headers_by_name {
+            writeln!(
+                &mut buf,
+                "    {}: {},",
+                js_string(header),
+                js_string(&values.join(", "))
+            )?;
+        }
+        writeln!(&mut buf, "  }},")?;
+    }
+
+    if let Some(body) = request.body_str()? {
+        writeln!(&mut buf, "  body: {},", js_string(body))?;
+    }
+    write!(&mut buf, "}});")?;
+
+    Ok(buf)
+}
+
+fn httpie(request: &Request) -> anyhow::Result<String> {
+    let mut buf = String::new();
+
+    // HTTPie reads the body from stdin by default
+    if let Some(command) = &request.body_command {
+        write!(&mut buf, "{} | ", shell_command(command))?;
+    }
+
+    let method = &request.method;
+    let url = shell_quote(request.url.as_str());
+    write!(&mut buf, "http")?;
+    // Without a body, stdin is ignored so the command doesn't hang when run
+    // from a script
+    if request.body.is_none() && request.body_command.is_none() {
+        write!(&mut buf, " --ignore-stdin")?;
+    }
+    if let Some(body) = request.body_str()? {
+        write!(&mut buf, " --raw {}", shell_quote(body))?;
+    }
+    write!(&mut buf, " {method} {url}")?;
+
+    for (header, value) in headers(request)? {
+        let item = if value.is_empty() {
+            // `Header:` would *remove* the header, `Header;` sends it empty
+            format!("{header};")
+        } else if value.starts_with('=') {
+            // Otherwise this would be parsed as a `:=` JSON field
+            format!("{header}:\\{value}")
+        } else {
+            format!("{header}:{value}")
+        };
+        write!(&mut buf, " {}", shell_quote(&item))?;
+    }
+
+    Ok(buf)
+}
+
+/// Get all request headers, decoded as UTF-8
+fn headers(request: &Request) -> anyhow::Result<Vec<(&str, &str)>> {
+    request
+        .headers
+        .iter()
+        .map(|(header, value)| {
+            let value =
+                value.to_str().context("Error decoding header value")?;
+            Ok((header.as_str(), value))
+        })
+        .collect()
+}
+
+/// Join a command and its arguments into a shell command
+fn shell_command(command: &[String]) -> String {
+    command
+        .iter()
+        .map(|argument| shell_quote(argument))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Quote a string so a POSIX shell treats it as a single literal argument.
+/// Single quotes disable all special characters, except single quotes
+/// themselves, which have to be closed, escaped, and reopened.
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_util::*, util::assert_err};
+    use factori::create;
+    use indexmap::indexmap;
+    use reqwest::Method;
+    use rstest::{fixture, rstest};
+
+    /// A request with a bit of everything, including characters that need
+    /// escaping in each format
+    #[fixture]
+    fn request() -> Request {
+        create!(
+            Request,
+            method: Method::POST,
+            url: "http://localhost/url?q=1".parse().unwrap(),
+            headers: header_map(indexmap! {
+                "content-type" => "application/json",
+                "x-name" => "O'Brien",
+                "x-empty" => "",
+            }),
+            body: Some(r#"{"name": "O'Brien \"Bob\""}"#.into()),
+        )
+    }
+
+    #[rstest]
+    #[case::curl(
+        RequestFormat::Curl,
+        "curl -XPOST --url 'http://localhost/url?q=1' \
+        --header 'content-type: application/json' \
+        --header 'x-name: O'\\''Brien' \
+        --header 'x-empty: ' \
+        --data-raw '{\"name\": \"O'\\''Brien \\\"Bob\\\"\"}'"
+    )]
+    #[case::fetch(
+        RequestFormat::Fetch,
+        r#"fetch("http://localhost/url?q=1", {
+  method: "POST",
+  headers: {
+    "content-type": "application/json",
+    "x-name": "O'Brien",
+    "x-empty": "",
+  },
+  body: "{\"name\": \"O'Brien \\\"Bob\\\"\"}",
+});"#
+    )]
+    #[case::httpie(
+        RequestFormat::Httpie,
+        "http --raw '{\"name\": \"O'\\''Brien \\\"Bob\\\"\"}' \
+        POST 'http://localhost/url?q=1' 'content-type:application/json' \
+        'x-name:O'\\''Brien' 'x-empty;'"
+    )]
+    fn test_generate(
+        request: Request,
+        #[case] format: RequestFormat,
+        #[case] expected: &str,
+    ) {
+        assert_eq!(format.generate(&request).unwrap(), expected);
+    }
+
+    /// A body starting with `@` is sent as-is, not read from a file
+    #[test]
+    fn test_generate_curl_at_body() {
+        let request = create!(Request, body: Some("@/etc/passwd".into()));
+        assert_eq!(
+            RequestFormat::Curl.generate(&request).unwrap(),
+            "curl -XGET --url 'http://localhost/url' \
+            --data-raw '@/etc/passwd'"
+        );
+    }
+
+    /// Body commands are piped in for shell formats
+    #[rstest]
+    #[case::curl(
+        RequestFormat::Curl,
+        "'cat' 'my file.json' | curl -XPUT --url 'http://localhost/url' \
+        --data-binary @-"
+    )]
+    #[case::httpie(
+        RequestFormat::Httpie,
+        "'cat' 'my file.json' | http PUT 'http://localhost/url'"
+    )]
+    fn test_generate_body_command(
+        #[case] format: RequestFormat,
+        #[case] expected: &str,
+    ) {
+        let request = create!(
+            Request,
+            method: Method::PUT,
+            body_command: Some(vec!["cat".into(), "my file.json".into()]),
+        );
+        assert_eq!(format.generate(&request).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_fetch_body_command() {
+        let request = create!(
+            Request,
+            body_command: Some(vec!["cat".into()]),
+        );
+        assert_err!(
+            RequestFormat::Fetch.generate(&request),
+            "can't be exported to fetch()"
+        );
+    }
+}
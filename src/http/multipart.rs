@@ -0,0 +1,229 @@
+//! Encode multipart request bodies. We build these ourselves rather than
+//! using reqwest's form helpers, so any multipart subtype can be sent, with
+//! full control over part order and headers.
+
+use crate::{
+    collection::{Multipart, MultipartPart},
+    http::RequestBuilder,
+    template::TemplateContext,
+};
+use anyhow::{anyhow, bail, Context};
+use bytes::Bytes;
+use futures::future;
+use reqwest::header::{HeaderName, HeaderValue};
+use tokio::fs;
+use uuid::Uuid;
+
+/// Maximum boundary length, from RFC 2046
+const MAX_BOUNDARY_LENGTH: usize = 70;
+
+/// A rendered multipart body, along with its `Content-Type` header
+#[derive(Debug)]
+pub struct MultipartBody {
+    pub body: Bytes,
+    pub content_type: HeaderValue,
+}
+
+/// A single rendered part
+struct RenderedPart {
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl RequestBuilder {
+    /// Render the recipe's multipart body, if it has one. A body override from
+    /// the user takes precedence over the recipe.
+    pub(super) async fn render_multipart(
+        &self,
+        template_context: &TemplateContext,
+    ) -> anyhow::Result<Option<MultipartBody>> {
+        let Some(multipart) = &self.recipe.multipart else {
+            return Ok(None);
+        };
+        if self.options.body.is_some() {
+            return Ok(None);
+        }
+        if self.recipe.body.is_some() {
+            bail!("Recipe cannot have both `body` and `multipart`");
+        }
+        if self.recipe.body_command.is_some() {
+            bail!("Recipe cannot have both `body_command` and `multipart`");
+        }
+        render_multipart(multipart, template_context)
+            .await
+            .map(Some)
+    }
+}
+
+async fn render_multipart(
+    multipart: &Multipart,
+    template_context: &TemplateContext,
+) -> anyhow::Result<MultipartBody> {
+    let (boundary, parameters, parts) = tokio::try_join!(
+        async {
+            match &multipart.boundary {
+                Some(boundary) => boundary
+                    .render(template_context)
+                    .await
+                    .context("Error rendering multipart boundary"),
+                None => Ok(format!("slumber-{}", Uuid::new_v4().simple())),
+            }
+        },
+        future::try_join_all(multipart.parameters.iter().map(
+            |(parameter, template)| async move {
+                let value = template
+                    .render(template_context)
+                    .await
+                    .with_context(|| {
+                        format!(
+                            "Error rendering multipart parameter `{parameter}`"
+                        )
+                    })?;
+                Ok::<_, anyhow::Error>((parameter, value))
+            }
+        )),
+        future::try_join_all(multipart.parts.iter().enumerate().map(
+            |(i, part)| async move {
+                render_part(part, template_context).await.with_context(|| {
+                    format!("Error rendering multipart part {i}")
+                })
+            }
+        )),
+    )?;
+
+    if boundary.is_empty() || boundary.len() > MAX_BOUNDARY_LENGTH {
+        bail!(
+            "Multipart boundary must be 1-{MAX_BOUNDARY_LENGTH} characters, \
+            got {}",
+            boundary.len()
+        );
+    }
+
+    let mut content_type = format!(
+        "multipart/{}; boundary={}",
+        multipart.subtype,
+        parameter_value(&boundary)
+    );
+    for (parameter, value) in parameters {
+        content_type
+            .push_str(&format!("; {parameter}={}", parameter_value(&value)));
+    }
+    let content_type = HeaderValue::try_from(content_type)
+        .context("Error encoding multipart `Content-Type` header")?;
+
+    let delimiter = format!("--{boundary}");
+    let mut body = Vec::new();
+    for (i, part) in parts.into_iter().enumerate() {
+        // The server would read the boundary as the end of the part
+        if contains(&part.body, delimiter.as_bytes()) {
+            bail!("Multipart boundary `{boundary}` appears in part {i}");
+        }
+        body.extend_from_slice(delimiter.as_bytes());
+        body.extend_from_slice(b"\r\n");
+        for (header, value) in part.headers {
+            body.extend_from_slice(format!("{header}: {value}\r\n").as_bytes());
+        }
+        body.extend_from_slice(b"\r\n");
+        body.extend_from_slice(&part.body);
+        body.extend_from_slice(b"\r\n");
+    }
+    body.extend_from_slice(format!("{delimiter}--\r\n").as_bytes());
+
+    Ok(MultipartBody {
+        body: body.into(),
+        content_type,
+    })
+}
+
+async fn render_part(
+    part: &MultipartPart,
+    template_context: &TemplateContext,
+) -> anyhow::Result<RenderedPart> {
+    let (headers, body) = tokio::try_join!(
+        future::try_join_all(part.headers.iter().map(
+            |(header, template)| async move {
+                let value =
+                    template.render(template_context).await.with_context(
+                        || format!("Error rendering header `{header}`"),
+                    )?;
+                // Same leniency as request headers
+                let value = value.trim_matches(|c| c == '\n' || c == '\r');
+                // Validate, but send the name as written. Some servers that
+                // expect this kind of body also care about header case.
+                HeaderName::try_from(header.as_str()).with_context(|| {
+                    format!("Error encoding header name `{header}`")
+                })?;
+                HeaderValue::try_from(value).with_context(|| {
+                    format!("Error encoding value for header `{header}`")
+                })?;
+                Ok::<_, anyhow::Error>((header.clone(), value.to_owned()))
+            }
+        )),
+        render_part_body(part, template_context),
+    )?;
+    Ok(RenderedPart { headers, body })
+}
+
+async fn render_part_body(
+    part: &MultipartPart,
+    template_context: &TemplateContext,
+) -> anyhow::Result<Vec<u8>> {
+    match (&part.body, &part.file) {
+        (Some(_), Some(_)) => {
+            Err(anyhow!("Part cannot have both `body` and `file`"))
+        }
+        (Some(body), None) => Ok(body
+            .render(template_context)
+            .await
+            .context("Error rendering body")?
+            .into_bytes()),
+        (None, Some(path)) => {
+            let path = template_context.resolve_path(
+                path.render(template_context)
+                    .await
+                    .context("Error rendering file path")?,
+            )?;
+            fs::read(&path)
+                .await
+                .with_context(|| format!("Error reading file {path:?}"))
+        }
+        (None, None) => Ok(Vec::new()),
+    }
+}
+
+/// Format a `Content-Type` parameter value, quoting it if necessary
+fn parameter_value(value: &str) -> String {
+    // Token characters per RFC 2045
+    let is_token = !value.is_empty()
+        && value.bytes().all(|c| {
+            c.is_ascii_graphic() && !b"()<>@,;:\\\"/[]?=".contains(&c)
+        });
+    if is_token {
+        value.to_owned()
+    } else {
+        format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+    }
+}
+
+/// Does the haystack contain the needle?
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack
+        .windows(needle.len())
+        .any(|window| window == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::slash("text/xml", "\"text/xml\"")]
+    #[case::plain("abc-123", "abc-123")]
+    #[case::angle_brackets("<root@x>", "\"<root@x>\"")]
+    #[case::escape(r#"a"b\c"#, r#""a\"b\\c""#)]
+    #[case::empty("", "\"\"")]
+    fn test_parameter_value(#[case] value: &str, #[case] expected: &str) {
+        assert_eq!(parameter_value(value), expected);
+    }
+}
@@ -10,7 +10,15 @@ use anyhow::{anyhow, Context};
 use derive_more::{Deref, Display, From};
 use regex::Regex;
 use serde::{de::IntoDeserializer, Deserialize, Serialize};
-use std::{borrow::Cow, ffi::OsStr, fmt::Debug, path::Path, sync::OnceLock};
+use std::{
+    borrow::Cow,
+    ffi::OsStr,
+    fmt::{self, Debug},
+    iter::Peekable,
+    path::Path,
+    str::CharIndices,
+    sync::OnceLock,
+};
 
 /// All supported content types. Each variant should have a corresponding
 /// implementation of [ResponseContent].
@@ -18,15 +26,20 @@ use std::{borrow::Cow, ffi::OsStr, fmt::Debug, path::Path, sync::OnceLock};
 /// Serialization/deserialization of this only uses the short name. To parse
 /// a MIME type (from an HTTP header), use [Self::from_response]. This is to
 /// prevent accidentally supporting invalid MIME types.
-#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
-#[cfg_attr(test, derive(PartialEq))]
+#[derive(Copy, Clone, Debug, Display, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum ContentType {
     // Primary serialization string here should match the string we expect
     // users to enter in their collection file for manual overrides, i.e. the
     // most obvious/user-friendly value. MIME types are implemented
     // separately.
+    #[display("JSON")]
     Json,
+    /// Newline-delimited JSON: one JSON value per line, e.g. for log or event
+    /// streams
+    #[display("NDJSON")]
+    #[serde(alias = "jsonl")]
+    Ndjson,
 }
 
 /// A response content type that we know how to parse. This is defined as a
@@ -54,8 +67,32 @@ pub trait ResponseContent: Debug + Display {
     fn as_any(&self) -> &dyn std::any::Any;
 }
 
-#[derive(Debug, Display, Deref, From, PartialEq)]
-pub struct Json(serde_json::Value);
+#[derive(Debug, Display, Deref)]
+#[display("{value}")]
+pub struct Json {
+    #[deref]
+    value: serde_json::Value,
+    /// Text the value was parsed from, if any. Prettifying from this keeps
+    /// number literals exactly as written, while [serde_json::Value] would
+    /// round anything that doesn't fit in a `u64`, `i64`, or `f64`.
+    source: Option<String>,
+}
+
+impl From<serde_json::Value> for Json {
+    fn from(value: serde_json::Value) -> Self {
+        Self {
+            value,
+            source: None,
+        }
+    }
+}
+
+/// Compare by value only, since the same value can be written many ways
+impl PartialEq for Json {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
 
 impl ResponseContent for Json {
     fn content_type(&self) -> ContentType {
@@ -63,16 +100,22 @@ impl ResponseContent for Json {
     }
 
     fn parse(body: &[u8]) -> anyhow::Result<Self> {
-        Ok(Self(serde_json::from_slice(body)?))
+        let value = serde_json::from_slice(body)?;
+        // Valid JSON is always UTF-8, so this shouldn't fail
+        let source = std::str::from_utf8(body).ok().map(str::to_owned);
+        Ok(Self { value, source })
     }
 
     fn prettify(&self) -> String {
-        // serde_json can't fail serializing its own Value type
-        serde_json::to_string_pretty(&self.0).unwrap()
+        match &self.source {
+            Some(source) => prettify_exact(source),
+            // serde_json can't fail serializing its own Value type
+            None => serde_json::to_string_pretty(&self.value).unwrap(),
+        }
     }
 
     fn to_json(&self) -> Cow<'_, serde_json::Value> {
-        Cow::Borrowed(&self.0)
+        Cow::Borrowed(&self.value)
     }
 
     #[cfg(test)]
@@ -81,7 +124,158 @@ impl ResponseContent for Json {
     }
 }
 
+/// Newline-delimited JSON ([NDJSON](https://github.com/ndjson/ndjson-spec)).
+/// Each non-blank line is a separate JSON value. For querying, this is
+/// treated as an array of the values.
+#[derive(Debug, Deref, From, PartialEq)]
+pub struct Ndjson(Vec<serde_json::Value>);
+
+impl ResponseContent for Ndjson {
+    fn content_type(&self) -> ContentType {
+        ContentType::Ndjson
+    }
+
+    fn parse(body: &[u8]) -> anyhow::Result<Self> {
+        body.split(|&byte| byte == b'\n')
+            .enumerate()
+            .filter(|(_, line)| !line.iter().all(u8::is_ascii_whitespace))
+            .map(|(i, line)| {
+                serde_json::from_slice(line)
+                    .with_context(|| format!("Error parsing line {}", i + 1))
+            })
+            .collect::<anyhow::Result<_>>()
+            .map(Self)
+    }
+
+    /// Prettify each value on its own, so each line of the original body is
+    /// still a separate entry
+    fn prettify(&self) -> String {
+        self.0
+            .iter()
+            // serde_json can't fail serializing its own Value type
+            .map(|value| serde_json::to_string_pretty(value).unwrap())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn to_json(&self) -> Cow<'_, serde_json::Value> {
+        Cow::Owned(serde_json::Value::Array(self.0.clone()))
+    }
+
+    #[cfg(test)]
+    fn as_any(&self) -> &dyn std::any::Any {
+        self as &dyn std::any::Any
+    }
+}
+
+/// One compact value per line, as it would be sent
+impl Display for Ndjson {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, value) in self.0.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{value}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Pretty-print JSON text in the same format as
+/// [serde_json::to_string_pretty], but with number literals copied exactly as
+/// written. The text must already be valid JSON.
+fn prettify_exact(source: &str) -> String {
+    fn newline(output: &mut String, depth: usize) {
+        output.push('\n');
+        output.push_str(&"  ".repeat(depth));
+    }
+
+    /// Skip over whitespace to the next token
+    fn next_token(chars: &mut Peekable<CharIndices>) -> Option<(usize, char)> {
+        chars.find(|(_, c)| !matches!(c, ' ' | '\t' | '\n' | '\r'))
+    }
+
+    let mut output = String::with_capacity(source.len());
+    let mut depth = 0;
+    let mut chars = source.char_indices().peekable();
+    while let Some((start, c)) = next_token(&mut chars) {
+        match c {
+            '{' | '[' => {
+                output.push(c);
+                let close = if c == '{' { '}' } else { ']' };
+                let is_empty = source[start + 1..]
+                    .trim_start_matches([' ', '\t', '\n', '\r'])
+                    .starts_with(close);
+                if is_empty {
+                    // Empty containers stay on one line
+                    next_token(&mut chars);
+                    output.push(close);
+                } else {
+                    depth += 1;
+                    newline(&mut output, depth);
+                }
+            }
+            '}' | ']' => {
+                depth -= 1;
+                newline(&mut output, depth);
+                output.push(c);
+            }
+            ',' => {
+                output.push(',');
+                newline(&mut output, depth);
+            }
+            ':' => output.push_str(": "),
+            '"' => {
+                // Find the closing quote, skipping escaped characters
+                let mut end = start + 1;
+                while let Some((i, c)) = chars.next() {
+                    match c {
+                        '\\' => {
+                            chars.next();
+                        }
+                        '"' => {
+                            end = i + 1;
+                            break;
+                        }
+                        _ => {}
+                    }
+                }
+                // Re-encode the string so escapes match serde_json's output
+                let token = &source[start..end];
+                let encoded = serde_json::from_str::<String>(token)
+                    .and_then(|string| serde_json::to_string(&string));
+                match encoded {
+                    Ok(encoded) => output.push_str(&encoded),
+                    Err(_) => output.push_str(token),
+                }
+            }
+            // Numbers and keywords are copied as-is
+            _ => {
+                let mut end = start + c.len_utf8();
+                while let Some((i, _)) = chars.next_if(|(_, c)| {
+                    !matches!(
+                        c,
+                        ',' | ':' | ']' | '}' | ' ' | '\t' | '\n' | '\r'
+                    )
+                }) {
+                    end = i + 1;
+                }
+                output.push_str(&source[start..end]);
+            }
+        }
+    }
+    output
+}
+
 impl ContentType {
+    /// Canonical MIME type for this content type, e.g. for an `Accept` header
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            Self::Json => "application/json",
+            Self::Ndjson => "application/x-ndjson",
+        }
+    }
+
     /// Parse some content of this type. Return a dynamically dispatched content
     /// object.
     pub fn parse_content(
@@ -90,6 +284,7 @@ impl ContentType {
     ) -> anyhow::Result<Box<dyn ResponseContent>> {
         match self {
             Self::Json => Ok(Box::new(Json::parse(content)?)),
+            Self::Ndjson => Ok(Box::new(Ndjson::parse(content)?)),
         }
     }
 
@@ -102,17 +297,42 @@ impl ContentType {
         content: Cow<'_, serde_json::Value>,
     ) -> Box<dyn ResponseContent> {
         match self {
-            Self::Json => Box::new(Json(content.into_owned())),
+            Self::Json => Box::new(Json::from(content.into_owned())),
+            // A single value becomes a single line
+            Self::Ndjson => match content.into_owned() {
+                serde_json::Value::Array(values) => Box::new(Ndjson(values)),
+                value => Box::new(Ndjson(vec![value])),
+            },
         }
     }
 
     /// Helper for parsing the body of a response. Use [Response::parse_body]
     /// for external usage.
+    ///
+    /// Some servers send NDJSON labelled as JSON, so if a JSON body fails to
+    /// parse but [Self::sniff_ndjson] recognizes it, it's parsed as NDJSON
+    /// instead.
     pub(super) fn parse_response(
         response: &Response,
     ) -> anyhow::Result<Box<dyn ResponseContent>> {
         let content_type = Self::from_response(response)?;
-        content_type.parse_content(response.body.bytes())
+        let body = response.decoded_body();
+        match content_type.parse_content(&body) {
+            Err(error) if content_type == Self::Json => {
+                match Self::sniff_ndjson(&body) {
+                    Some(ndjson) => Ok(Box::new(ndjson)),
+                    None => Err(error),
+                }
+            }
+            result => result,
+        }
+    }
+
+    /// Parse a body as NDJSON, if it looks like NDJSON: more than one line,
+    /// where every non-blank line is a JSON value
+    fn sniff_ndjson(body: &[u8]) -> Option<Ndjson> {
+        let ndjson = Ndjson::parse(body).ok()?;
+        (ndjson.len() > 1).then_some(ndjson)
     }
 
     /// Parse the content type from a file's extension
@@ -139,9 +359,10 @@ impl ContentType {
 
     /// Parse the value of the content-type header and map it to a known content
     /// type
-    fn from_header(header_value: &str) -> anyhow::Result<Self> {
+    pub(super) fn from_header(header_value: &str) -> anyhow::Result<Self> {
         // unstable: use LazyLock https://github.com/rust-lang/rust/pull/121377
         static JSON_REGEX: OnceLock<Regex> = OnceLock::new();
+        static NDJSON_REGEX: OnceLock<Regex> = OnceLock::new();
 
         // Remove extra metadata from the header. It feels like there should be
         // a helper for this in hyper or reqwest but I couldn't find it.
@@ -155,8 +376,14 @@ impl ContentType {
             Regex::new("^application/(\\w+\\+)?json$").unwrap()
         });
 
+        let ndjson_regex = NDJSON_REGEX.get_or_init(|| {
+            Regex::new("^application/(x-)?(ndjson|jsonl|jsonlines)$").unwrap()
+        });
+
         if regex.is_match(content_type) {
             Ok(Self::Json)
+        } else if ndjson_regex.is_match(content_type) {
+            Ok(Self::Ndjson)
         } else {
             Err(anyhow!("Unknown content type {header_value:?}"))
         }
@@ -185,6 +412,9 @@ mod tests {
     )]
     // Test extended MIME type
     #[case::json_extended("application/geo+json", ContentType::Json)]
+    #[case::ndjson("application/x-ndjson", ContentType::Ndjson)]
+    #[case::ndjson_no_prefix("application/ndjson", ContentType::Ndjson)]
+    #[case::jsonl("application/jsonl", ContentType::Ndjson)]
     fn test_try_from_mime(
         #[case] mime_type: &str,
         #[case] expected: ContentType,
@@ -211,6 +441,10 @@ mod tests {
             ContentType::from_extension(Path::new("turbo.json")).unwrap(),
             ContentType::Json
         );
+        assert_eq!(
+            ContentType::from_extension(Path::new("events.jsonl")).unwrap(),
+            ContentType::Ndjson
+        );
 
         // Errors
         assert_err!(
@@ -228,7 +462,18 @@ mod tests {
     #[case::json(
         "application/json",
         "{\"hello\": \"goodbye\"}",
-        Json(json!({"hello": "goodbye"}))
+        Json::from(json!({"hello": "goodbye"}))
+    )]
+    #[case::ndjson(
+        "application/x-ndjson",
+        "{\"level\": \"info\"}\n\n[1, 2]\n3\n",
+        Ndjson(vec![json!({"level": "info"}), json!([1, 2]), json!(3)])
+    )]
+    // Mislabelled NDJSON is detected
+    #[case::ndjson_as_json(
+        "application/json",
+        "{\"a\": 1}\n{\"a\": 2}",
+        Ndjson(vec![json!({"a": 1}), json!({"a": 2})])
     )]
     fn test_parse_body<T: ResponseContent + PartialEq + 'static>(
         #[case] content_type: &str,
@@ -250,6 +495,44 @@ mod tests {
         );
     }
 
+    /// Numbers are displayed exactly as the server sent them, without a lossy
+    /// round trip through a float
+    #[rstest]
+    #[case::above_f64_precision("9007199254740993")]
+    #[case::above_u64("18446744073709551617")]
+    #[case::below_i64("-9223372036854775809")]
+    #[case::long_decimal("0.10000000000000000000001")]
+    #[case::exponent("1E5")]
+    fn test_json_number_precision(#[case] number: &str) {
+        let body = format!("{{\"id\": {number}}}");
+        let json = Json::parse(body.as_bytes()).unwrap();
+        assert_eq!(json.prettify(), format!("{{\n  \"id\": {number}\n}}"));
+    }
+
+    /// Prettifying from the source text should look the same as prettifying
+    /// the parsed value, other than numbers
+    #[rstest]
+    #[case::scalar(" \"a\" ")]
+    #[case::empty_containers(r#"{"a": {}, "b": [ ], "c": [{}]}"#)]
+    #[case::nested(r#"{"a":[1,{"b":null,"c":[true,false]}],"d":-0.5}"#)]
+    #[case::whitespace("{\n\t\"a\" :\r\n[ 1 , 2 ] }")]
+    #[case::string_escapes(r#"{"a\"b": "\u00e9\n\\/ {[,:]}"}"#)]
+    fn test_prettify_exact(#[case] body: &str) {
+        let json = Json::parse(body.as_bytes()).unwrap();
+        let expected = serde_json::to_string_pretty(&json.value).unwrap();
+        assert_eq!(json.prettify(), expected);
+    }
+
+    #[test]
+    fn test_ndjson_display() {
+        let ndjson = Ndjson(vec![json!({"a": [1, 2]}), json!("b")]);
+        assert_eq!(ndjson.to_string(), "{\"a\":[1,2]}\n\"b\"");
+        assert_eq!(
+            ndjson.prettify(),
+            "{\n  \"a\": [\n    1,\n    2\n  ]\n}\n\"b\""
+        );
+    }
+
     /// Test various failure cases
     #[rstest]
     #[case::no_content_type(None::<&str>, "", "no content-type header")]
@@ -264,6 +547,11 @@ mod tests {
         "not json!",
         "expected ident"
     )]
+    #[case::invalid_ndjson_line(
+        Some("application/x-ndjson"),
+        "{}\nnot json!",
+        "Error parsing line 2"
+    )]
     fn test_parse_body_error<
         T: TryInto<HeaderValue, Error = InvalidHeaderValue>,
     >(
@@ -0,0 +1,207 @@
+//! Tracking for upload/download progress of in-flight requests
+
+use bytes::Bytes;
+use futures::{stream, Stream};
+use std::{
+    cmp,
+    convert::Infallible,
+    sync::{Arc, Mutex},
+};
+use tokio::sync::Notify;
+
+/// Request bodies larger than this are streamed in chunks of this size, so we
+/// can track upload progress. Smaller bodies are sent in one shot.
+pub const UPLOAD_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Progress of a request's upload and download. This is shared between the
+/// HTTP engine, which updates it as bytes are sent/received, and whoever
+/// wants to show progress to the user.
+#[derive(Debug, Default)]
+pub struct RequestProgress {
+    state: Mutex<ProgressState>,
+    /// Response body received so far. Only kept for streamed formats (e.g.
+    /// NDJSON), so they can be shown while they load
+    partial_body: Mutex<Option<Vec<u8>>>,
+    /// Signalled when the user wants to stop loading the response body
+    stop: Notify,
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+struct ProgressState {
+    upload: Option<Transfer>,
+    download: Option<Transfer>,
+}
+
+/// Progress of a single transfer (upload or download)
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Transfer {
+    /// Bytes transferred so far
+    pub done: u64,
+    /// Total bytes to transfer, if known. For downloads, this comes from the
+    /// `Content-Length` header, so it may be missing.
+    pub total: Option<u64>,
+}
+
+impl RequestProgress {
+    /// Get upload progress. `None` if the body isn't being streamed, e.g.
+    /// because it's small or missing. For bodies generated by a command, the
+    /// total is unknown
+    pub fn upload(&self) -> Option<Transfer> {
+        self.lock().upload
+    }
+
+    /// Get download progress. `None` until the response body starts loading
+    pub fn download(&self) -> Option<Transfer> {
+        self.lock().download
+    }
+
+    /// Get the response body received so far, if it's being kept. See
+    /// [Self::keep_partial_body]
+    pub fn partial_body(&self) -> Option<Vec<u8>> {
+        self.partial_body.lock().unwrap().clone()
+    }
+
+    /// Stop loading the response body. The response is completed with
+    /// whatever has been received so far. Has no effect once the body is
+    /// loaded.
+    pub fn stop(&self) {
+        self.stop.notify_one();
+    }
+
+    /// Resolves once [Self::stop] is called, even if it was called earlier
+    pub(super) async fn stopped(&self) {
+        self.stop.notified().await
+    }
+
+    /// Keep each chunk of the response body as it arrives
+    pub(super) fn keep_partial_body(&self) {
+        *self.partial_body.lock().unwrap() = Some(Vec::new());
+    }
+
+    /// Total is `None` if the body is generated on the fly
+    pub(super) fn start_upload(&self, total: Option<u64>) {
+        self.lock().upload = Some(Transfer { done: 0, total });
+    }
+
+    pub(super) fn start_download(&self, total: Option<u64>) {
+        self.lock().download = Some(Transfer { done: 0, total });
+    }
+
+    pub(super) fn add_uploaded(&self, bytes: usize) {
+        if let Some(upload) = &mut self.lock().upload {
+            upload.done += bytes as u64;
+        }
+    }
+
+    pub(super) fn add_downloaded(&self, chunk: &[u8]) {
+        if let Some(download) = &mut self.lock().download {
+            download.done += chunk.len() as u64;
+        }
+        if let Some(body) = &mut *self.partial_body.lock().unwrap() {
+            body.extend_from_slice(chunk);
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, ProgressState> {
+        // Nothing panics while holding the lock, so it can't be poisoned
+        self.state.lock().unwrap()
+    }
+}
+
+impl Transfer {
+    /// Fraction of the transfer that's complete, in `[0, 1]`. `None` if the
+    /// total is unknown
+    pub fn ratio(&self) -> Option<f64> {
+        let total = self.total?;
+        if total == 0 {
+            Some(1.0)
+        } else {
+            Some((self.done as f64 / total as f64).min(1.0))
+        }
+    }
+}
+
+/// Split a request body into a stream of chunks. Progress is recorded as each
+/// chunk is pulled by the HTTP client.
+pub(super) fn upload_stream(
+    body: Bytes,
+    progress: Arc<RequestProgress>,
+) -> impl Stream<Item = Result<Bytes, Infallible>> {
+    progress.start_upload(Some(body.len() as u64));
+    // Iterators are lazy, so progress is only recorded as chunks are pulled
+    let chunks = (0..body.len())
+        .step_by(UPLOAD_CHUNK_SIZE)
+        .map(move |start| {
+            let end = cmp::min(start + UPLOAD_CHUNK_SIZE, body.len());
+            progress.add_uploaded(end - start);
+            Ok(body.slice(start..end))
+        });
+    stream::iter(chunks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+    use rstest::rstest;
+
+    #[tokio::test]
+    async fn test_upload_stream() {
+        let body = Bytes::from(vec![0u8; UPLOAD_CHUNK_SIZE * 2 + 10]);
+        let progress = Arc::new(RequestProgress::default());
+        let mut stream =
+            Box::pin(upload_stream(body.clone(), Arc::clone(&progress)));
+
+        // Nothing is uploaded until the client pulls it
+        assert_eq!(
+            progress.upload(),
+            Some(Transfer {
+                done: 0,
+                total: Some(body.len() as u64)
+            })
+        );
+
+        let mut uploaded = Vec::new();
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk.unwrap();
+            uploaded.extend_from_slice(&chunk);
+            assert_eq!(progress.upload().unwrap().done, uploaded.len() as u64);
+        }
+        assert_eq!(uploaded, body);
+        assert_eq!(progress.upload().unwrap().ratio(), Some(1.0));
+    }
+
+    #[tokio::test]
+    async fn test_partial_body() {
+        let progress = RequestProgress::default();
+        progress.start_download(None);
+        progress.add_downloaded(b"ignored\n");
+        assert_eq!(progress.partial_body(), None);
+
+        progress.keep_partial_body();
+        progress.add_downloaded(b"{\"a\": 1}\n");
+        progress.add_downloaded(b"{\"a\": 2}");
+        assert_eq!(
+            progress.partial_body().as_deref(),
+            Some(b"{\"a\": 1}\n{\"a\": 2}".as_slice())
+        );
+        assert_eq!(progress.download().unwrap().done, 25);
+
+        // A stop before anyone is waiting isn't lost
+        progress.stop();
+        progress.stopped().await;
+    }
+
+    #[rstest]
+    #[case::unknown(5, None, None)]
+    #[case::empty(0, Some(0), Some(1.0))]
+    #[case::partial(1, Some(4), Some(0.25))]
+    #[case::overflow(5, Some(4), Some(1.0))]
+    fn test_ratio(
+        #[case] done: u64,
+        #[case] total: Option<u64>,
+        #[case] expected: Option<f64>,
+    ) {
+        assert_eq!(Transfer { done, total }.ratio(), expected);
+    }
+}
@@ -1,17 +1,57 @@
 //! Utilities for querying HTTP response data
 
-use crate::http::ResponseContent;
-use derive_more::{Display, FromStr};
+use crate::http::{ContentType, ResponseContent};
+use derive_more::Display;
 use serde::{Deserialize, Serialize};
-use serde_json_path::{ExactlyOneError, JsonPath};
-use std::borrow::Cow;
+use serde_json::Value;
+use serde_json_path::{ExactlyOneError, JsonPath, ParseError};
+use std::{borrow::Cow, cmp::Ordering, fmt, str::FromStr};
 use thiserror::Error;
 
 /// A wrapper around a JSONPath. This combines some common behavior, and will
 /// make it easy to swap out the query language in the future if necessary.
-#[derive(Clone, Debug, Display, FromStr, PartialEq, Serialize, Deserialize)]
-#[serde(transparent)]
-pub struct Query(JsonPath);
+///
+/// The path can be followed by any number of jq-style transforms, separated by
+/// `|`, e.g. `$.items[*].id | sort | first`. The first transform is applied
+/// to the list of every value matched by the path, and each subsequent
+/// transform is applied to the output of the previous one.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub struct Query {
+    path: JsonPath,
+    transforms: Vec<Transform>,
+}
+
+/// A jq-style operation applied to the output of a query
+#[derive(Copy, Clone, Debug, Display, PartialEq)]
+pub enum Transform {
+    /// Number of elements in an array/object, or characters in a string
+    #[display("length")]
+    Length,
+    /// Keys of an object (sorted), or indexes of an array
+    #[display("keys")]
+    Keys,
+    /// Values of an object, ordered by key. Arrays are returned as-is
+    #[display("values")]
+    Values,
+    /// First element of an array, or `null` if it's empty
+    #[display("first")]
+    First,
+    /// Last element of an array, or `null` if it's empty
+    #[display("last")]
+    Last,
+    #[display("reverse")]
+    Reverse,
+    /// Sort an array, using the same ordering as jq
+    #[display("sort")]
+    Sort,
+    /// Sort an array and remove duplicates
+    #[display("unique")]
+    Unique,
+    /// Flatten nested arrays by one level
+    #[display("flatten")]
+    Flatten,
+}
 
 #[derive(Debug, Error)]
 pub enum QueryError {
@@ -22,23 +62,76 @@ pub enum QueryError {
         #[source]
         error: ExactlyOneError,
     },
+
+    /// Transform doesn't support the type of value it was given
+    #[error("Cannot apply `{transform}` to {kind}")]
+    Transform {
+        transform: Transform,
+        /// Type of the value, e.g. `string`
+        kind: &'static str,
+    },
+}
+
+/// Error parsing a query string
+#[derive(Debug, Error)]
+pub enum QueryParseError {
+    #[error(transparent)]
+    Path(#[from] ParseError),
+
+    #[error(
+        "Unknown transform `{0}`; expected one of: length, keys, values, \
+        first, last, reverse, sort, unique, flatten"
+    )]
+    Transform(String),
 }
 
 impl Query {
     /// Apply a query to some content, returning the result in the original
     /// format. This will convert to a common format, apply the query, then
-    /// convert back.
+    /// convert back. Without any transforms, the result is an array of every
+    /// match.
+    ///
+    /// NDJSON is queried line by line, so the query applies to each value on
+    /// its own. Lines that don't match are left out, and lines with a single
+    /// match are unwrapped from the array.
     pub fn query(
         &self,
         value: &dyn ResponseContent,
-    ) -> Box<dyn ResponseContent> {
+    ) -> Result<Box<dyn ResponseContent>, QueryError> {
         let content_type = value.content_type();
         let json_value = value.to_json();
+        if content_type == ContentType::Ndjson {
+            let lines = json_value.as_array().map(Vec::as_slice).unwrap_or(&[]);
+            let queried = lines
+                .iter()
+                .filter_map(|line| {
+                    let mut matches = self.path.query(line).all();
+                    if self.transforms.is_empty() {
+                        match matches.len() {
+                            0 => None,
+                            1 => Some(Ok(matches.remove(0).clone())),
+                            _ => Some(Ok(Value::Array(
+                                matches.into_iter().cloned().collect(),
+                            ))),
+                        }
+                    } else {
+                        let matches = Value::Array(
+                            matches.into_iter().cloned().collect(),
+                        );
+                        Some(self.transform(matches))
+                    }
+                })
+                .collect::<Result<_, _>>()?;
+            return Ok(
+                content_type.parse_json(Cow::Owned(Value::Array(queried)))
+            );
+        }
         // We have to clone all the elements to put them into a JSON array
-        let queried = serde_json::Value::Array(
-            self.0.query(&json_value).into_iter().cloned().collect(),
+        let queried = Value::Array(
+            self.path.query(&json_value).into_iter().cloned().collect(),
         );
-        content_type.parse_json(Cow::Owned(queried))
+        let transformed = self.transform(queried)?;
+        Ok(content_type.parse_json(Cow::Owned(transformed)))
     }
 
     /// Apply a query to some content, returning a string. The query should
@@ -54,28 +147,280 @@ impl Query {
         // All content types get converted to JSON for querying, then converted
         // back. This is fucky but we need *some* common format
         let json_value = value.to_json();
-        let queried = self.0.query(&json_value).exactly_one()?;
-
-        // If we got a scalar value, use that. Otherwise convert back to the
-        // input content type to re-stringify
-        let stringified = match queried {
-            serde_json::Value::Null => "".into(),
-            serde_json::Value::Number(n) => n.to_string(),
-            serde_json::Value::Bool(b) => b.to_string(),
-            serde_json::Value::String(s) => s.clone(),
-            serde_json::Value::Array(_) | serde_json::Value::Object(_) => {
-                content_type.parse_json(Cow::Borrowed(queried)).to_string()
+        // Transforms always produce a single value, so only a bare path has
+        // to be checked for exactly one match
+        let queried = if self.transforms.is_empty() {
+            Cow::Borrowed(self.path.query(&json_value).exactly_one()?)
+        } else {
+            let matches = Value::Array(
+                self.path.query(&json_value).into_iter().cloned().collect(),
+            );
+            Cow::Owned(self.transform(matches)?)
+        };
+
+        Ok(stringify(content_type, queried))
+    }
+
+    /// Like [Self::query_to_string], but if the path matches more than one
+    /// value, return each match stringified instead of failing. With
+    /// transforms, there's always exactly one value.
+    pub fn query_to_strings(
+        &self,
+        value: &dyn ResponseContent,
+    ) -> Result<Vec<String>, QueryError> {
+        let content_type = value.content_type();
+        let json_value = value.to_json();
+        let matches = self.path.query(&json_value).all();
+        if !self.transforms.is_empty() || matches.len() < 2 {
+            // Let the single-value path handle errors for 0 matches
+            return Ok(vec![self.query_to_string(value)?]);
+        }
+        Ok(matches
+            .into_iter()
+            .map(|value| stringify(content_type, Cow::Borrowed(value)))
+            .collect())
+    }
+
+    /// Does this query include any transforms after the path?
+    pub fn has_transforms(&self) -> bool {
+        !self.transforms.is_empty()
+    }
+
+    /// Apply each transform, in order, to the query matches
+    fn transform(&self, value: Value) -> Result<Value, QueryError> {
+        self.transforms
+            .iter()
+            .try_fold(value, |value, transform| transform.apply(value))
+    }
+}
+
+/// Convert a query result to a string. Scalars are used directly. Arrays and
+/// objects are converted back to the input content type, then stringified
+fn stringify(content_type: ContentType, value: Cow<'_, Value>) -> String {
+    match value.as_ref() {
+        Value::Null => "".into(),
+        Value::Number(n) => n.to_string(),
+        Value::Bool(b) => b.to_string(),
+        Value::String(s) => s.clone(),
+        Value::Array(_) | Value::Object(_) => {
+            content_type.parse_json(value).to_string()
+        }
+    }
+}
+
+impl FromStr for Query {
+    type Err = QueryParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = split_pipes(s).into_iter();
+        // split_pipes always returns at least one part
+        let path = parts.next().unwrap_or_default().trim().parse()?;
+        let transforms = parts
+            .map(|part| part.trim().parse())
+            .collect::<Result<_, _>>()?;
+        Ok(Self { path, transforms })
+    }
+}
+
+impl TryFrom<String> for Query {
+    type Error = QueryParseError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl fmt::Display for Query {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.path)?;
+        for transform in &self.transforms {
+            write!(f, " | {transform}")?;
+        }
+        Ok(())
+    }
+}
+
+impl From<Query> for String {
+    fn from(query: Query) -> Self {
+        query.to_string()
+    }
+}
+
+impl Transform {
+    fn apply(self, value: Value) -> Result<Value, QueryError> {
+        let error = |value: &Value| QueryError::Transform {
+            transform: self,
+            kind: kind(value),
+        };
+        let transformed = match (self, value) {
+            (Self::Length, Value::Array(array)) => array.len().into(),
+            (Self::Length, Value::Object(object)) => object.len().into(),
+            (Self::Length, Value::String(s)) => s.chars().count().into(),
+            (Self::Length, Value::Null) => 0.into(),
+
+            (Self::Keys, Value::Object(object)) => {
+                object.keys().cloned().map(Value::from).collect()
+            }
+            (Self::Keys, Value::Array(array)) => {
+                (0..array.len()).map(Value::from).collect()
+            }
+
+            (Self::Values, Value::Object(object)) => {
+                object.into_iter().map(|(_, value)| value).collect()
+            }
+            (Self::Values, value @ Value::Array(_)) => value,
+
+            (Self::First, Value::Array(array)) => {
+                array.into_iter().next().unwrap_or_default()
+            }
+            (Self::Last, Value::Array(array)) => {
+                array.into_iter().last().unwrap_or_default()
+            }
+
+            (Self::Reverse, Value::Array(mut array)) => {
+                array.reverse();
+                Value::Array(array)
+            }
+            (Self::Sort, Value::Array(mut array)) => {
+                array.sort_by(compare);
+                Value::Array(array)
             }
+            (Self::Unique, Value::Array(mut array)) => {
+                array.sort_by(compare);
+                array.dedup();
+                Value::Array(array)
+            }
+            (Self::Flatten, Value::Array(array)) => array
+                .into_iter()
+                .flat_map(|value| match value {
+                    Value::Array(inner) => inner,
+                    value => vec![value],
+                })
+                .collect(),
+
+            (_, value) => return Err(error(&value)),
         };
+        Ok(transformed)
+    }
+}
+
+impl FromStr for Transform {
+    type Err = QueryParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "length" => Ok(Self::Length),
+            "keys" => Ok(Self::Keys),
+            "values" => Ok(Self::Values),
+            "first" => Ok(Self::First),
+            "last" => Ok(Self::Last),
+            "reverse" => Ok(Self::Reverse),
+            "sort" => Ok(Self::Sort),
+            "unique" => Ok(Self::Unique),
+            "flatten" => Ok(Self::Flatten),
+            _ => Err(QueryParseError::Transform(s.to_owned())),
+        }
+    }
+}
+
+/// Split a query on each `|` that separates transforms. Pipes within quotes,
+/// and the `||` operator in JSONPath filters, aren't separators.
+fn split_pipes(s: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut quote: Option<char> = None;
+    let mut start = 0;
+    let mut chars = s.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match (quote, c) {
+            // Skip escaped characters, so an escaped quote doesn't end the
+            // string
+            (Some(_), '\\') => {
+                chars.next();
+            }
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"') => quote = Some(c),
+            (None, '|') if chars.peek().is_some_and(|(_, c)| *c == '|') => {
+                chars.next();
+            }
+            (None, '|') => {
+                parts.push(&s[start..i]);
+                start = i + 1;
+            }
+            (None, _) => {}
+        }
+    }
+    parts.push(&s[start..]);
+    parts
+}
 
-        Ok(stringified)
+/// Name of a JSON value's type, for error messages
+fn kind(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/// Order JSON values the same way as jq:
+/// `null < false < true < numbers < strings < arrays < objects`
+fn compare(a: &Value, b: &Value) -> Ordering {
+    fn rank(value: &Value) -> u8 {
+        match value {
+            Value::Null => 0,
+            Value::Bool(false) => 1,
+            Value::Bool(true) => 2,
+            Value::Number(_) => 3,
+            Value::String(_) => 4,
+            Value::Array(_) => 5,
+            Value::Object(_) => 6,
+        }
+    }
+
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => {
+            let (a, b) = (a.as_f64(), b.as_f64());
+            a.partial_cmp(&b).unwrap_or(Ordering::Equal)
+        }
+        (Value::String(a), Value::String(b)) => a.cmp(b),
+        (Value::Array(a), Value::Array(b)) => a
+            .iter()
+            .zip(b)
+            .map(|(a, b)| compare(a, b))
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or_else(|| a.len().cmp(&b.len())),
+        (Value::Object(a), Value::Object(b)) => {
+            // jq compares objects by their sorted keys, then values
+            let mut a: Vec<_> = a.iter().collect();
+            let mut b: Vec<_> = b.iter().collect();
+            a.sort_by(|(a, _), (b, _)| a.cmp(b));
+            b.sort_by(|(a, _), (b, _)| a.cmp(b));
+            a.iter()
+                .map(|(k, _)| k)
+                .cmp(b.iter().map(|(k, _)| k))
+                .then_with(|| {
+                    a.iter()
+                        .zip(&b)
+                        .map(|((_, a), (_, b))| compare(a, b))
+                        .find(|ordering| ordering.is_ne())
+                        .unwrap_or(Ordering::Equal)
+                })
+        }
+        _ => rank(a).cmp(&rank(b)),
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{http::Json, util::assert_err};
+    use crate::{
+        http::{Json, Ndjson},
+        util::assert_err,
+    };
     use rstest::rstest;
     use serde_json::json;
 
@@ -94,6 +439,82 @@ mod tests {
         assert_eq!(out, expected);
     }
 
+    #[rstest]
+    #[case::length("$.a[*] | length", json!({"a": [1, 2, 3]}), "3")]
+    #[case::length_string("$.s | first | length", json!({"s": "fish"}), "4")]
+    #[case::keys("$ | first | keys", json!({"b": 1, "a": 2}), r#"["a","b"]"#)]
+    #[case::values("$ | first | values", json!({"b": 1, "a": 2}), "[2,1]")]
+    #[case::first("$.a[*] | first", json!({"a": [1, 2]}), "1")]
+    #[case::first_empty("$.a[*] | first", json!({"a": []}), "")]
+    #[case::last("$.a[*] | last", json!({"a": [1, 2]}), "2")]
+    #[case::reverse("$.a[*] | reverse", json!({"a": [1, 2]}), "[2,1]")]
+    #[case::sort(
+        "$.a[*] | sort",
+        json!({"a": ["b", 3, null, "a", true, 1.5, false]}),
+        r#"[null,false,true,1.5,3,"a","b"]"#
+    )]
+    #[case::unique("$.a[*] | unique", json!({"a": [2, 1, 2, 1]}), "[1,2]")]
+    #[case::flatten(
+        "$.a[*] | flatten",
+        json!({"a": [[1, 2], 3, [[4]]]}),
+        "[1,2,3,[4]]"
+    )]
+    #[case::chained(
+        "$.a[*].id | sort | last",
+        json!({"a": [{"id": 3}, {"id": 5}, {"id": 4}]}),
+        "5"
+    )]
+    #[case::filter_or(
+        "$.a[?@.x == 1 || @.x == 2] | length",
+        json!({"a": [{"x": 1}, {"x": 2}, {"x": 3}]}),
+        "2"
+    )]
+    #[case::quoted_pipe("$['a|b'] | length", json!({"a|b": 1}), "1")]
+    fn test_transform(
+        #[case] query: &str,
+        #[case] content: serde_json::Value,
+        #[case] expected: &str,
+    ) {
+        let query = Query::from_str(query).unwrap();
+        let out = query.query_to_string(&*json(content)).unwrap();
+        assert_eq!(out, expected);
+    }
+
+    #[rstest]
+    #[case::wrong_type(
+        "$.a[*] | first | sort",
+        json!({"a": [1]}),
+        "Cannot apply `sort` to number"
+    )]
+    #[case::object("$ | first | first", json!({}), "Cannot apply `first` to object")]
+    fn test_transform_error(
+        #[case] query: &str,
+        #[case] content: serde_json::Value,
+        #[case] expected_err: &str,
+    ) {
+        let query = Query::from_str(query).unwrap();
+        assert_err!(query.query_to_string(&*json(content)), expected_err);
+    }
+
+    /// Queries round trip through their string form, which is also how
+    /// they're serialized
+    #[rstest]
+    #[case::path("$.a", "$.a")]
+    #[case::transforms("$.a[*]|sort  |first", "$.a[*] | sort | first")]
+    fn test_parse_query(#[case] input: &str, #[case] expected: &str) {
+        let query = Query::from_str(input).unwrap();
+        assert_eq!(query.to_string(), expected);
+        assert_eq!(Query::from_str(expected).unwrap(), query);
+    }
+
+    #[rstest]
+    #[case::bad_path("$.", "")]
+    #[case::bad_transform("$.a | bogus", "Unknown transform `bogus`")]
+    #[case::empty_transform("$.a |", "Unknown transform ``")]
+    fn test_parse_query_error(#[case] input: &str, #[case] expected_err: &str) {
+        assert_err!(Query::from_str(input), expected_err);
+    }
+
     #[rstest]
     #[case::too_many_results("$[*]", json(json!([1, 2])), "Expected exactly one result")]
     #[case::no_results("$[*]", json(json!([])), "Expected exactly one result")]
@@ -106,6 +527,42 @@ mod tests {
         assert_err!(query.query_to_string(&*content), expected_err);
     }
 
+    #[rstest]
+    #[case::one("$.a[0]", json!({"a": [1, 2]}), &["1"])]
+    #[case::multiple(
+        "$.a[*]",
+        json!({"a": ["x", {"b": 1}, null]}),
+        &["x", r#"{"b":1}"#, ""]
+    )]
+    #[case::transform("$.a[*] | reverse", json!({"a": [1, 2]}), &["[2,1]"])]
+    fn test_query_to_strings(
+        #[case] query: &str,
+        #[case] content: serde_json::Value,
+        #[case] expected: &[&str],
+    ) {
+        let query = Query::from_str(query).unwrap();
+        let out = query.query_to_strings(&*json(content)).unwrap();
+        assert_eq!(out, expected);
+    }
+
+    /// NDJSON is queried a line at a time, and lines without a match are
+    /// dropped
+    #[rstest]
+    #[case::field("$.level", &[r#""info""#, r#""error""#])]
+    #[case::multiple("$.tags[*]", &["[1,2]", "3"])]
+    #[case::transform("$.tags[*] | length", &["2", "1", "0"])]
+    fn test_query_ndjson(#[case] query: &str, #[case] expected: &[&str]) {
+        let content = Ndjson::from(vec![
+            json!({"level": "info", "tags": [1, 2]}),
+            json!({"level": "error", "tags": [3]}),
+            json!({"tags": []}),
+        ]);
+        let query = Query::from_str(query).unwrap();
+        let out = query.query(&content).unwrap();
+        assert_eq!(out.content_type(), ContentType::Ndjson);
+        assert_eq!(out.to_string().lines().collect::<Vec<_>>(), expected);
+    }
+
     /// Helper to create JSON content
     fn json(value: serde_json::Value) -> Box<dyn ResponseContent> {
         Box::new(Json::from(value))
@@ -0,0 +1,145 @@
+//! Per-host throttling of outgoing requests
+
+use indexmap::IndexMap;
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use tracing::warn;
+
+/// Throttle outgoing requests to hosts that have a configured rate limit. Each
+/// host gets its own token bucket. Requests over the limit are queued rather
+/// than rejected: each request reserves the next available slot, then waits
+/// until that slot comes up. This means requests go out in the order they were
+/// reserved.
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+    buckets: HashMap<String, Mutex<TokenBucket>>,
+}
+
+impl RateLimiter {
+    /// Build a rate limiter from a mapping of hostname to maximum requests per
+    /// second. Invalid limits are ignored.
+    pub fn new(limits: &IndexMap<String, f64>) -> Self {
+        let buckets = limits
+            .iter()
+            .filter_map(|(host, &rate)| {
+                if rate.is_finite() && rate > 0.0 {
+                    Some((host.clone(), Mutex::new(TokenBucket::new(rate))))
+                } else {
+                    warn!(host, rate, "Ignoring invalid rate limit");
+                    None
+                }
+            })
+            .collect();
+        Self { buckets }
+    }
+
+    /// Reserve a slot for a request to the given host. Returns how long the
+    /// request has to wait for its slot, or `None` if it can be sent
+    /// immediately (including if the host isn't limited).
+    pub fn reserve(&self, host: &str) -> Option<Duration> {
+        let bucket = self.buckets.get(host)?;
+        bucket
+            .lock()
+            .expect("Lock poisoned")
+            .reserve(Instant::now())
+    }
+}
+
+/// Token bucket for a single host. The bucket holds up to one second's worth
+/// of tokens, refilling continuously. Tokens can go negative, which represents
+/// requests that are queued for a future slot.
+#[derive(Debug)]
+struct TokenBucket {
+    /// Tokens added per second
+    rate: f64,
+    /// Maximum number of tokens, i.e. the largest allowed burst
+    capacity: f64,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate: f64) -> Self {
+        // Always allow at least one request, even if the rate is less than one
+        // per second
+        let capacity = rate.max(1.0);
+        Self {
+            rate,
+            capacity,
+            tokens: capacity,
+            last_refill: Instant::now(),
+        }
+    }
+
+    /// Take a token for a request, and return how long the request has to
+    /// wait until that token is actually available
+    fn reserve(&mut self, now: Instant) -> Option<Duration> {
+        let elapsed = now.saturating_duration_since(self.last_refill);
+        self.tokens = (self.tokens + elapsed.as_secs_f64() * self.rate)
+            .min(self.capacity);
+        self.last_refill = now;
+
+        self.tokens -= 1.0;
+        if self.tokens >= 0.0 {
+            None
+        } else {
+            Some(Duration::from_secs_f64(-self.tokens / self.rate))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indexmap::indexmap;
+
+    /// Requests up to the limit go immediately, then each subsequent request
+    /// gets queued behind the last
+    #[test]
+    fn test_token_bucket() {
+        let mut bucket = TokenBucket::new(2.0);
+        let now = bucket.last_refill;
+        assert_eq!(bucket.reserve(now), None);
+        assert_eq!(bucket.reserve(now), None);
+        assert_eq!(bucket.reserve(now), Some(Duration::from_millis(500)));
+        assert_eq!(bucket.reserve(now), Some(Duration::from_secs(1)));
+
+        // Refill catches up with the queue
+        let later = now + Duration::from_secs(2);
+        assert_eq!(bucket.reserve(later), None);
+        // Bucket doesn't overfill after a long idle
+        let much_later = later + Duration::from_secs(60);
+        assert_eq!(bucket.reserve(much_later), None);
+        assert_eq!(bucket.reserve(much_later), None);
+        assert_eq!(
+            bucket.reserve(much_later),
+            Some(Duration::from_millis(500))
+        );
+    }
+
+    /// Rates under one per second still allow an initial request
+    #[test]
+    fn test_token_bucket_slow() {
+        let mut bucket = TokenBucket::new(0.5);
+        let now = bucket.last_refill;
+        assert_eq!(bucket.reserve(now), None);
+        assert_eq!(bucket.reserve(now), Some(Duration::from_secs(2)));
+    }
+
+    #[test]
+    fn test_rate_limiter() {
+        let limiter = RateLimiter::new(&indexmap! {
+            "limited".into() => 1.0,
+            "invalid".into() => 0.0,
+        });
+        assert_eq!(limiter.reserve("limited"), None);
+        assert!(limiter.reserve("limited").is_some());
+        // Unlimited hosts never wait
+        assert_eq!(limiter.reserve("invalid"), None);
+        assert_eq!(limiter.reserve("invalid"), None);
+        assert_eq!(limiter.reserve("other"), None);
+    }
+}
@@ -0,0 +1,301 @@
+//! Send a request written out by hand as an HTTP message, with as little
+//! processing as the HTTP client allows. This skips recipes, templates,
+//! redirects, and history entirely, and is meant for poking at how a server
+//! handles unusual requests.
+
+use crate::config::Config;
+use anyhow::{anyhow, bail, Context};
+use reqwest::{
+    header::{self, HeaderMap, HeaderName, HeaderValue},
+    Client, Method, StatusCode, Version,
+};
+use std::{
+    fmt::{self, Display},
+    net::SocketAddr,
+};
+use url::Url;
+
+/// A request parsed from raw HTTP message text. See [Self::parse]
+#[derive(Debug)]
+pub struct RawRequest {
+    pub method: Method,
+    pub url: Url,
+    /// HTTP version from the request line. If omitted, the client picks
+    pub version: Option<Version>,
+    /// Headers in the order they were given. Repeated headers are kept, but
+    /// grouped together with the first occurrence of the same name
+    pub headers: HeaderMap,
+    pub body: Vec<u8>,
+}
+
+/// A response to a [RawRequest], exactly as reported by the client
+#[derive(Debug)]
+pub struct RawResponse {
+    pub version: Version,
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Vec<u8>,
+}
+
+impl RawRequest {
+    /// Parse an HTTP message: a `METHOD TARGET [VERSION]` request line, one
+    /// `Header: value` line per header, then a blank line and the body. Lines
+    /// can end in either `\n` or `\r\n`. The body is everything after the
+    /// blank line, byte for byte, including any trailing newline.
+    ///
+    /// The target can be an absolute URL, or a path. For a path, the URL is
+    /// built from the `Host` header, using `https` if `https` is set and
+    /// `http` otherwise. Any method name is accepted, as long as it's a valid
+    /// token.
+    pub fn parse(text: &[u8], https: bool) -> anyhow::Result<Self> {
+        let (head, body) = split_message(text);
+        let head = std::str::from_utf8(head)
+            .context("Request line and headers must be valid UTF-8")?;
+        let mut lines = head.lines();
+
+        let request_line = lines.next().unwrap_or_default();
+        let mut parts = request_line.split_whitespace();
+        let (Some(method), Some(target), version, None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            bail!("Expected `METHOD TARGET [VERSION]` on first line");
+        };
+        let method = Method::from_bytes(method.as_bytes())
+            .map_err(|_| anyhow!("Invalid HTTP method `{method}`"))?;
+        let version = version.map(parse_version).transpose()?;
+
+        let mut headers = HeaderMap::new();
+        // Line numbers are 1-indexed, and the request line was first
+        for (i, line) in lines.enumerate() {
+            let line_number = i + 2;
+            let (header, value) = line.split_once(':').ok_or_else(|| {
+                anyhow!("Expected `Header: value` on line {line_number}")
+            })?;
+            // Whitespace before the colon isn't allowed, so don't trim that
+            let header = HeaderName::from_bytes(header.as_bytes())
+                .with_context(|| {
+                    format!("Invalid header name on line {line_number}")
+                })?;
+            let value = HeaderValue::from_bytes(value.trim().as_bytes())
+                .with_context(|| {
+                    format!("Invalid header value on line {line_number}")
+                })?;
+            headers.append(header, value);
+        }
+
+        let url = if target.starts_with('/') {
+            let host = headers
+                .get(header::HOST)
+                .and_then(|host| host.to_str().ok())
+                .ok_or_else(|| {
+                    anyhow!(
+                        "Target `{target}` is a path, so a `Host` header is \
+                        required"
+                    )
+                })?;
+            let scheme = if https { "https" } else { "http" };
+            format!("{scheme}://{host}{target}").parse()
+        } else {
+            target.parse()
+        }
+        .with_context(|| format!("Invalid URL: `{target}`"))?;
+
+        Ok(Self {
+            method,
+            url,
+            version,
+            headers,
+            body: body.to_owned(),
+        })
+    }
+
+    /// Send the request and load the full response. This uses its own client
+    /// rather than [HttpEngine](super::HttpEngine), so nothing is added to
+    /// the request other than what the client insists on. Redirects aren't
+    /// followed. The `resolve`, `ignore_certificate_hosts`,
+    /// `title_case_headers`, and `tls` config fields still apply.
+    pub async fn send(self, config: &Config) -> anyhow::Result<RawResponse> {
+        let host = self.url.host_str().unwrap_or_default();
+        let mut builder = Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .danger_accept_invalid_certs(
+                config.ignore_certificate_hosts.iter().any(|h| h == host),
+            );
+        for (hostname, address) in &config.resolve {
+            // reqwest ignores the port, and uses the one from the URL
+            builder = builder.resolve(hostname, SocketAddr::new(*address, 0));
+        }
+        if config.title_case_headers {
+            builder = builder.http1_title_case_headers();
+        }
+        let client = config.tls.apply(builder)?.build()?;
+
+        let mut request =
+            client.request(self.method, self.url).headers(self.headers);
+        if let Some(version) = self.version {
+            request = request.version(version);
+        }
+        // An empty body would get a `Content-Length: 0` on some methods
+        if !self.body.is_empty() {
+            request = request.body(self.body);
+        }
+        let response = request.send().await?;
+
+        Ok(RawResponse {
+            version: response.version(),
+            status: response.status(),
+            headers: response.headers().clone(),
+            body: response.bytes().await?.into(),
+        })
+    }
+}
+
+/// Split a message into head and body at the first blank line. If there's no
+/// blank line, the whole thing is the head.
+fn split_message(text: &[u8]) -> (&[u8], &[u8]) {
+    for (i, _) in text.iter().enumerate().filter(|(_, c)| **c == b'\n') {
+        let rest = &text[i + 1..];
+        if let Some(body) = rest
+            .strip_prefix(b"\n")
+            .or_else(|| rest.strip_prefix(b"\r\n"))
+        {
+            return (&text[..i], body);
+        }
+    }
+    (text, &[])
+}
+
+fn parse_version(version: &str) -> anyhow::Result<Version> {
+    match version {
+        "HTTP/1.0" => Ok(Version::HTTP_10),
+        "HTTP/1.1" => Ok(Version::HTTP_11),
+        "HTTP/2" | "HTTP/2.0" => Ok(Version::HTTP_2),
+        _ => Err(anyhow!(
+            "Unsupported HTTP version `{version}`; expected HTTP/1.0, \
+            HTTP/1.1, or HTTP/2"
+        )),
+    }
+}
+
+/// Status line and headers, formatted like an HTTP message head, e.g.
+/// `HTTP/1.1 200 OK`. Header values that aren't valid UTF-8 are shown lossily.
+/// Ends with the blank line that separates the head from the body.
+impl Display for RawResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{:?} {}", self.version, self.status)?;
+        for (header, value) in &self.headers {
+            writeln!(
+                f,
+                "{header}: {}",
+                String::from_utf8_lossy(value.as_bytes())
+            )?;
+        }
+        writeln!(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::assert_err;
+    use rstest::rstest;
+
+    #[test]
+    fn test_parse() {
+        let request = RawRequest::parse(
+            b"PURGE /cache?a=1 HTTP/1.0\r\nHost: localhost:3000\r\n\
+            X-Dup: 1\r\nx-other:  2 \r\nX-Dup: 3\r\n\r\nbody\r\n\r\nmore\n",
+            false,
+        )
+        .unwrap();
+        assert_eq!(request.method.as_str(), "PURGE");
+        assert_eq!(request.url.as_str(), "http://localhost:3000/cache?a=1");
+        assert_eq!(request.version, Some(Version::HTTP_10));
+        let headers: Vec<_> = request
+            .headers
+            .iter()
+            .map(|(header, value)| (header.as_str(), value.to_str().unwrap()))
+            .collect();
+        assert_eq!(
+            headers,
+            vec![
+                ("host", "localhost:3000"),
+                ("x-dup", "1"),
+                ("x-dup", "3"),
+                ("x-other", "2"),
+            ]
+        );
+        // Body is kept verbatim
+        assert_eq!(request.body, b"body\r\n\r\nmore\n");
+
+        // Absolute URL, no version, no body
+        let request =
+            RawRequest::parse(b"GET https://example.com/\n", false).unwrap();
+        assert_eq!(request.url.as_str(), "https://example.com/");
+        assert_eq!(request.version, None);
+        assert!(request.headers.is_empty());
+        assert!(request.body.is_empty());
+
+        // Paths use the requested scheme
+        let request =
+            RawRequest::parse(b"GET / HTTP/2\nHost: example.com\n\n", true)
+                .unwrap();
+        assert_eq!(request.url.as_str(), "https://example.com/");
+        assert_eq!(request.version, Some(Version::HTTP_2));
+    }
+
+    #[rstest]
+    #[case::empty("", "Expected `METHOD TARGET [VERSION]`")]
+    #[case::no_target("GET", "Expected `METHOD TARGET [VERSION]`")]
+    #[case::too_many_parts(
+        "GET / HTTP/1.1 extra",
+        "Expected `METHOD TARGET [VERSION]`"
+    )]
+    #[case::method("G(T http://localhost", "Invalid HTTP method `G(T`")]
+    #[case::version(
+        "GET http://localhost HTTP/3",
+        "Unsupported HTTP version `HTTP/3`"
+    )]
+    #[case::header("GET http://localhost\nbad", "Expected `Header: value`")]
+    #[case::header_name(
+        "GET http://localhost\nX-Space : 1",
+        "Invalid header name on line 2"
+    )]
+    #[case::no_host("GET /path", "a `Host` header is required")]
+    #[case::url("GET localhost", "Invalid URL: `localhost`")]
+    fn test_parse_error(#[case] text: &str, #[case] expected_error: &str) {
+        assert_err!(RawRequest::parse(text.as_bytes(), false), expected_error);
+    }
+
+    /// Headers go out as given, and nothing extra is sent other than what
+    /// the client adds itself
+    #[tokio::test]
+    async fn test_send() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("PATCH", "/raw")
+            .match_header("x-custom", "value")
+            .match_header("user-agent", mockito::Matcher::Missing)
+            .match_body("hello")
+            .with_status(418)
+            .with_header("x-response", "yes")
+            .with_body("short and stout")
+            .create_async()
+            .await;
+
+        let text = format!(
+            "PATCH /raw HTTP/1.1\nHost: {}\nX-Custom: value\n\nhello",
+            server.host_with_port()
+        );
+        let request = RawRequest::parse(text.as_bytes(), false).unwrap();
+        let response = request.send(&Config::default()).await.unwrap();
+
+        mock.assert_async().await;
+        assert_eq!(response.status, StatusCode::IM_A_TEAPOT);
+        assert_eq!(response.body, b"short and stout");
+        let head = response.to_string();
+        assert!(head.starts_with("HTTP/1.1 418 I'm a teapot\n"), "{head}");
+        assert!(head.contains("\nx-response: yes\n"), "{head}");
+        assert!(head.ends_with("\n\n"), "{head}");
+    }
+}
@@ -1,8 +1,10 @@
 //! HTTP-related data types
 
 use crate::{
-    collection::{ProfileId, RecipeId},
-    http::{ContentType, ResponseContent},
+    collection::{
+        persist_default, Pagination, ProfileId, RecipeId, ReplayMatch,
+    },
+    http::{ContentType, ResponseContent, TlsVersion, TlsVersions},
     util::ResultExt,
 };
 use anyhow::Context;
@@ -10,16 +12,14 @@ use bytes::Bytes;
 use bytesize::ByteSize;
 use chrono::{DateTime, Duration, Utc};
 use derive_more::{Display, From};
+use encoding_rs::{Encoding, UTF_8};
 use indexmap::IndexMap;
 use reqwest::{
     header::{self, HeaderMap, HeaderValue},
     Method, StatusCode,
 };
 use serde::{Deserialize, Serialize};
-use std::{
-    fmt::{Debug, Write},
-    sync::Arc,
-};
+use std::{borrow::Cow, fmt::Debug, sync::Arc};
 use thiserror::Error;
 use url::Url;
 use uuid::Uuid;
@@ -41,8 +41,10 @@ pub struct RequestBuildError {
 #[derive(Debug, Error)]
 #[error("Error executing request {}", .request.id)]
 pub struct RequestError {
+    /// Usually an HTTP error, but in offline mode this can be a failure to
+    /// find a recorded response
     #[source]
-    pub error: reqwest::Error,
+    pub error: anyhow::Error,
     /// The request that caused all this ruckus
     pub request: Arc<Request>,
     /// When was the request launched?
@@ -92,6 +94,82 @@ impl RequestRecord {
     pub fn duration(&self) -> Duration {
         self.end_time - self.start_time
     }
+
+    /// If the response links to a next page, build a request for that page.
+    /// If the recipe has `pagination`, the next page comes from the body.
+    /// Otherwise, it comes from an
+    /// [RFC 8288](https://datatracker.ietf.org/doc/html/rfc8288) `Link`
+    /// header. The new request is identical to this one, other than the URL.
+    /// Relative links are resolved against the URL of this request.
+    pub fn next_page(&self) -> Option<Request> {
+        let url = match &self.request.pagination {
+            Some(pagination) => self.next_page_from_body(pagination)?,
+            None => {
+                let link = self.response.next_page_link()?;
+                self.request.url.join(link).ok()?
+            }
+        };
+        Some(Request {
+            url,
+            ..self.request.duplicate()
+        })
+    }
+
+    /// Get the URL of the next page from the response body. Errors just mean
+    /// there's no next page, e.g. the cursor is missing on the last page.
+    fn next_page_from_body(&self, pagination: &Pagination) -> Option<Url> {
+        let body = self.parse_body().ok()?;
+        let next = pagination.next.query_to_string(&*body).ok()?;
+        if next.is_empty() {
+            return None;
+        }
+        match &pagination.param {
+            Some(param) => {
+                let mut url = self.request.url.clone();
+                let query = url
+                    .query_pairs()
+                    .filter(|(key, _)| key != param)
+                    .map(|(key, value)| (key.into_owned(), value.into_owned()))
+                    .collect::<Vec<_>>();
+                url.query_pairs_mut()
+                    .clear()
+                    .extend_pairs(query)
+                    .append_pair(param, &next);
+                Some(url)
+            }
+            None => self.request.url.join(&next).ok(),
+        }
+    }
+
+    /// Parse the response body. If the recipe declared an expected content
+    /// type, parse as that regardless of the `Content-Type` header. Otherwise
+    /// fall back to the header.
+    pub fn parse_body(&self) -> anyhow::Result<Box<dyn ResponseContent>> {
+        match self.request.expects {
+            Some(content_type) => content_type
+                .parse_content(&self.response.decoded_body())
+                .context("Error parsing response body")
+                .traced(),
+            None => self.response.parse_body(),
+        }
+    }
+
+    /// If the recipe declared an expected content type and the response's
+    /// `Content-Type` header doesn't match it, get a message describing the
+    /// mismatch
+    pub fn content_type_mismatch(&self) -> Option<String> {
+        let expected = self.request.expects?;
+        if ContentType::from_response(&self.response).ok() == Some(expected) {
+            return None;
+        }
+        let actual = match self.response.content_type() {
+            Some(content_type) => {
+                String::from_utf8_lossy(content_type).into_owned()
+            }
+            None => "no content type".into(),
+        };
+        Some(format!("Expected {expected}, got {actual}"))
+    }
 }
 
 /// A single instance of an HTTP request. There are a few reasons we need this
@@ -110,6 +188,10 @@ pub struct Request {
     pub profile_id: Option<ProfileId>,
     /// The recipe used to generate this request (for historical context)
     pub recipe_id: RecipeId,
+    /// Rendered from the recipe's `label` field, to tell this request apart
+    /// from others for the same recipe
+    #[serde(default)]
+    pub label: Option<String>,
 
     #[serde(with = "serde_method")]
     pub method: Method,
@@ -119,33 +201,55 @@ pub struct Request {
     pub headers: HeaderMap,
     /// Body content as bytes. This should be decoded as needed
     pub body: Option<Bytes>,
+    /// Command whose stdout is streamed as the body, instead of a static
+    /// body. The streamed content isn't captured, so only the command itself
+    /// is available after the request is sent.
+    #[serde(default)]
+    pub body_command: Option<Vec<String>>,
+    /// Should this request be saved in history once it completes? Copied from
+    /// the recipe. This isn't stored, because anything loaded from history
+    /// was obviously persisted.
+    #[serde(skip, default = "persist_default")]
+    pub persist: bool,
+    /// Content type the response is expected to have, copied from the recipe.
+    /// This overrides the response's `Content-Type` header when parsing.
+    #[serde(default)]
+    pub expects: Option<ContentType>,
+    /// In offline mode, how to find a recorded response for this request.
+    /// Copied from the recipe. Not stored, because it only matters for
+    /// requests that haven't been sent yet.
+    #[serde(skip)]
+    pub replay: ReplayMatch,
+    /// Allowed TLS versions, copied from the recipe. These are combined with
+    /// the `tls` config field when the request is sent. Not stored, for the
+    /// same reason as `replay`.
+    #[serde(skip)]
+    pub tls: TlsVersions,
+    /// Where to find the next page in a response body, copied from the
+    /// recipe. If omitted, the `Link` header is used.
+    #[serde(default)]
+    pub pagination: Option<Pagination>,
 }
 
 impl Request {
-    /// Generate a cURL command equivalent to this request
-    ///
-    /// This only fails if one of the headers or body is binary and can't be
-    /// converted to UTF-8.
-    pub fn to_curl(&self) -> anyhow::Result<String> {
-        let mut buf = String::new();
-
-        // These writes are all infallible because we're writing to a string,
-        // but use ? because it's shorter than unwrap().
-        let method = &self.method;
-        let url = &self.url;
-        write!(&mut buf, "curl -X{method} --url '{url}'")?;
-
-        for (header, value) in &self.headers {
-            let value =
-                value.to_str().context("Error decoding header value")?;
-            write!(&mut buf, " --header '{header}: {value}'")?;
-        }
-
-        if let Some(body) = &self.body_str()? {
-            write!(&mut buf, " --data '{body}'")?;
+    /// Create an identical request with a new ID, so it can be sent again
+    pub fn duplicate(&self) -> Self {
+        Self {
+            id: RequestId::new(),
+            profile_id: self.profile_id.clone(),
+            recipe_id: self.recipe_id.clone(),
+            label: self.label.clone(),
+            method: self.method.clone(),
+            url: self.url.clone(),
+            headers: self.headers.clone(),
+            body: self.body.clone(),
+            body_command: self.body_command.clone(),
+            persist: self.persist,
+            expects: self.expects,
+            replay: self.replay,
+            tls: self.tls,
+            pagination: self.pagination.clone(),
         }
-
-        Ok(buf)
     }
 
     /// Get the body of the request, decoded as UTF-8. Returns an error if the
@@ -171,7 +275,30 @@ pub struct Response {
     pub status: StatusCode,
     #[serde(with = "serde_header_map")]
     pub headers: HeaderMap,
+    /// Trailers sent after the body. reqwest doesn't expose HTTP trailers, so
+    /// this is only populated for gRPC-web responses, which encode their
+    /// trailers in the body.
+    #[serde(default, with = "serde_header_map")]
+    pub trailers: HeaderMap,
     pub body: Body,
+    /// Was the body discarded without being loaded? If so, the body is empty
+    /// regardless of what the server sent.
+    #[serde(default)]
+    pub headers_only: bool,
+    /// Was this response replayed from history in offline mode, rather than
+    /// received from the server? Replayed responses aren't stored again, so
+    /// this is never persisted.
+    #[serde(skip)]
+    pub replayed: bool,
+    /// Was the body served from history, because the server responded `304
+    /// Not Modified` to a conditional request? The status is still the 304.
+    #[serde(default)]
+    pub cached: bool,
+    /// TLS version negotiated for the connection the response came over.
+    /// `None` for plain HTTP. Older history only has a version if the
+    /// request's settings pinned it down to one.
+    #[serde(default, alias = "pinned_tls_version")]
+    pub tls_version: Option<TlsVersion>,
 }
 
 impl Response {
@@ -189,6 +316,77 @@ impl Response {
             .map(HeaderValue::as_bytes)
     }
 
+    /// Get the character encoding declared by the `charset` parameter of the
+    /// `content-type` header. `None` if there's no charset, or it isn't one we
+    /// recognize.
+    pub fn charset(&self) -> Option<&'static Encoding> {
+        let content_type = std::str::from_utf8(self.content_type()?).ok()?;
+        content_type.split(';').skip(1).find_map(|param| {
+            let (key, value) = param.split_once('=')?;
+            if key.trim().eq_ignore_ascii_case("charset") {
+                Encoding::for_label(value.trim().trim_matches('"').as_bytes())
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Get the body as text, decoded according to the declared charset. See
+    /// [decode_text]
+    pub fn body_text(&self) -> Option<Cow<'_, str>> {
+        decode_text(self.body.bytes(), self.charset())
+    }
+
+    /// Get the body as UTF-8 bytes for parsing, converting from the declared
+    /// charset if necessary. If the body can't be decoded, get the raw bytes.
+    pub(super) fn decoded_body(&self) -> Cow<'_, [u8]> {
+        match self.body_text() {
+            Some(Cow::Owned(text)) => Cow::Owned(text.into_bytes()),
+            _ => Cow::Borrowed(self.body.bytes()),
+        }
+    }
+
+    /// Find the target of the first `Link` header entry with `rel="next"`, if
+    /// any. Headers that aren't valid UTF-8 are skipped, as are entries that
+    /// can't be parsed.
+    pub fn next_page_link(&self) -> Option<&str> {
+        self.headers
+            .get_all(header::LINK)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| split_unquoted(value, ','))
+            .find_map(|link| {
+                // Entries look like `<url>; rel="next"; title="Next page"`
+                let mut parts = split_unquoted(link, ';').into_iter();
+                let target = parts
+                    .next()?
+                    .trim()
+                    .strip_prefix('<')?
+                    .strip_suffix('>')?;
+                // Relation can contain multiple space-separated values
+                let is_next = parts.any(|param| {
+                    param.split_once('=').is_some_and(|(key, value)| {
+                        key.trim().eq_ignore_ascii_case("rel")
+                            && unquote(value)
+                                .split_whitespace()
+                                .any(|rel| rel.eq_ignore_ascii_case("next"))
+                    })
+                });
+                is_next.then_some(target)
+            })
+    }
+
+    /// Count the entries in the `errors` array of a GraphQL response. GraphQL
+    /// servers typically return 200 even when a query fails, so the status
+    /// code can't be trusted. Returns 0 if the body isn't JSON, or doesn't
+    /// look like a GraphQL response.
+    pub fn graphql_error_count(&self) -> usize {
+        serde_json::from_slice::<serde_json::Value>(self.body.bytes())
+            .ok()
+            .and_then(|body| Some(body.get("errors")?.as_array()?.len()))
+            .unwrap_or(0)
+    }
+
     /// Make the response body pretty, if possible. This fails if the response
     /// has an unknown content-type, or if the body doesn't parse according to
     /// the content-type.
@@ -197,9 +395,25 @@ impl Response {
     }
 }
 
+/// Decode text with the given encoding, falling back to UTF-8 if it's not
+/// valid in that encoding (or no encoding is given). `None` if the bytes
+/// aren't valid text either way. This never alters the original bytes, so
+/// callers can fall back to displaying them as-is.
+pub fn decode_text<'a>(
+    bytes: &'a [u8],
+    encoding: Option<&'static Encoding>,
+) -> Option<Cow<'a, str>> {
+    encoding
+        .filter(|encoding| *encoding != UTF_8)
+        .and_then(|encoding| {
+            encoding.decode_without_bom_handling_and_without_replacement(bytes)
+        })
+        .or_else(|| std::str::from_utf8(bytes).ok().map(Cow::Borrowed))
+}
+
 /// HTTP response body. Content is stored as bytes to support non-text content.
 /// Should be converted to text only as needed
-#[derive(Default, From, Serialize, Deserialize)]
+#[derive(Clone, Default, From, Serialize, Deserialize)]
 pub struct Body(Bytes);
 
 impl Body {
@@ -282,6 +496,17 @@ mod serde_header_map {
     use reqwest::header::{HeaderName, HeaderValue};
     use serde::{de, Deserializer, Serializer};
 
+    /// Values for a single header. Most headers appear once, and are stored
+    /// as a single value so the format matches what older versions wrote.
+    /// Repeated headers (e.g. `Set-Cookie`) are stored as a list, so none of
+    /// the values are lost.
+    #[derive(Serialize, Deserialize)]
+    #[serde(untagged)]
+    enum Values<T> {
+        One(T),
+        Many(Vec<T>),
+    }
+
     pub fn serialize<S>(
         headers: &HeaderMap,
         serializer: S,
@@ -290,10 +515,22 @@ mod serde_header_map {
         S: Serializer,
     {
         // HeaderValue -> str is fallible, so we'll serialize as bytes instead
-        <IndexMap<&str, &[u8]>>::serialize(
+        <IndexMap<&str, Values<&[u8]>>>::serialize(
             &headers
-                .into_iter()
-                .map(|(k, v)| (k.as_str(), v.as_bytes()))
+                .keys()
+                .map(|k| {
+                    let mut values: Vec<&[u8]> = headers
+                        .get_all(k)
+                        .iter()
+                        .map(HeaderValue::as_bytes)
+                        .collect();
+                    let values = if values.len() == 1 {
+                        Values::One(values.remove(0))
+                    } else {
+                        Values::Many(values)
+                    };
+                    (k.as_str(), values)
+                })
                 .collect(),
             serializer,
         )
@@ -303,16 +540,24 @@ mod serde_header_map {
     where
         D: Deserializer<'de>,
     {
-        <IndexMap<String, Vec<u8>>>::deserialize(deserializer)?
-            .into_iter()
-            .map::<Result<(HeaderName, HeaderValue), _>, _>(|(k, v)| {
-                // Fallibly map each key and value to header types
-                Ok((
-                    k.try_into().map_err(de::Error::custom)?,
-                    v.try_into().map_err(de::Error::custom)?,
-                ))
-            })
-            .collect()
+        let mut headers = HeaderMap::new();
+        for (k, v) in
+            <IndexMap<String, Values<Vec<u8>>>>::deserialize(deserializer)?
+        {
+            // Fallibly map each key and value to header types
+            let name: HeaderName = k.try_into().map_err(de::Error::custom)?;
+            let values = match v {
+                Values::One(value) => vec![value],
+                Values::Many(values) => values,
+            };
+            for value in values {
+                headers.append(
+                    &name,
+                    HeaderValue::try_from(value).map_err(de::Error::custom)?,
+                );
+            }
+        }
+        Ok(headers)
     }
 }
 
@@ -340,34 +585,302 @@ mod serde_status_code {
     }
 }
 
+/// Split a header value on a separator, ignoring any separators in quoted
+/// strings (which can contain `\`-escaped quotes) or `<...>` URLs
+fn split_unquoted(value: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut in_quotes = false;
+    let mut in_url = false;
+    let mut escaped = false;
+    for (i, c) in value.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_quotes => escaped = true,
+            '"' if !in_url => in_quotes = !in_quotes,
+            '<' if !in_quotes => in_url = true,
+            '>' if !in_quotes => in_url = false,
+            _ if c == separator && !in_quotes && !in_url => {
+                parts.push(&value[start..i]);
+                start = i + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(&value[start..]);
+    parts
+}
+
+/// Get the value of a header parameter, which may be a quoted string
+fn unquote(value: &str) -> Cow<'_, str> {
+    let value = value.trim();
+    match value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+    {
+        Some(quoted) if quoted.contains('\\') => {
+            let mut unescaped = String::with_capacity(quoted.len());
+            let mut chars = quoted.chars();
+            while let Some(c) = chars.next() {
+                if c == '\\' {
+                    unescaped.extend(chars.next());
+                } else {
+                    unescaped.push(c);
+                }
+            }
+            unescaped.into()
+        }
+        Some(quoted) => quoted.into(),
+        None => value.into(),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::test_util::*;
     use factori::create;
     use indexmap::indexmap;
+    use rstest::rstest;
     use serde_json::json;
 
+    #[rstest]
+    #[case::none(&[], None)]
+    #[case::next(&["<http://localhost/2>; rel=\"next\""], Some("http://localhost/2"))]
+    #[case::unquoted(&["</2>; rel=next"], Some("/2"))]
+    #[case::multiple_entries(
+        &["</1>; rel=\"prev\", </3>; rel=\"next\", </9>; rel=\"last\""],
+        Some("/3"),
+    )]
+    #[case::multiple_headers(&["</1>; rel=\"prev\"", "</3>; rel=\"next\""], Some("/3"))]
+    #[case::multiple_rels(&["</3>; title=\"Next\"; rel=\"last next\""], Some("/3"))]
+    #[case::no_next(&["</1>; rel=\"prev\""], None)]
+    #[case::quoted_comma(
+        &["</1>; title=\"a, b\"; rel=\"prev\", </3>; title=\"c, d\"; rel=\"next\""],
+        Some("/3"),
+    )]
+    #[case::quoted_semicolon(&["</3>; title=\"a; rel=next\""], None)]
+    #[case::escaped_quote(&["</3>; title=\"a \\\", b\"; rel=\"next\""], Some("/3"))]
+    #[case::url_comma(&["</search?tags=a,b>; rel=\"next\""], Some("/search?tags=a,b"))]
+    #[case::multiple_rels_next_first(&["</3>; rel=\"next last\""], Some("/3"))]
+    #[case::invalid_entry(&["garbage, </3>; rel=next"], Some("/3"))]
+    fn test_next_page_link(
+        #[case] links: &[&str],
+        #[case] expected: Option<&str>,
+    ) {
+        let mut headers = HeaderMap::new();
+        for link in links {
+            headers.append(header::LINK, HeaderValue::from_str(link).unwrap());
+        }
+        let response = create!(Response, headers: headers);
+        assert_eq!(response.next_page_link(), expected);
+    }
+
+    /// Next page request should copy the original, with a resolved URL
     #[test]
-    fn test_to_curl() {
-        let headers = indexmap! {
-            "accept" => "application/json",
-            "content-type" => "application/json",
-        };
-        let body = json!({"data": "value"});
+    fn test_next_page() {
         let request = create!(
             Request,
-            method: Method::DELETE,
-            headers: header_map(headers),
-            body: Some(serde_json::to_vec(&body).unwrap().into()),
+            url: "http://localhost/users?page=1".parse().unwrap(),
+            headers: header_map(indexmap! {"accept" => "application/json"}),
         );
+        let headers =
+            header_map(indexmap! {"link" => "</users?page=2>; rel=\"next\""});
+        let record = create!(
+            RequestRecord,
+            request: request.into(),
+            response: create!(Response, headers: headers),
+        );
+
+        let next = record.next_page().unwrap();
+        assert_ne!(next.id, record.request.id);
+        assert_eq!(next.url.as_str(), "http://localhost/users?page=2");
+        assert_eq!(next.headers, record.request.headers);
+        assert_eq!(next.recipe_id, record.request.recipe_id);
 
+        // No link, no next page
+        let record = create!(RequestRecord);
+        assert!(record.next_page().is_none());
+    }
+
+    /// With `pagination`, the next page comes from the body instead of the
+    /// `Link` header
+    #[rstest]
+    #[case::url(
+        "$.next",
+        None,
+        r#"{"next": "/users?page=2"}"#,
+        Some("http://localhost/users?page=2")
+    )]
+    #[case::cursor(
+        "$.cursor",
+        Some("cursor"),
+        r#"{"cursor": "def"}"#,
+        Some("http://localhost/users?limit=10&cursor=def")
+    )]
+    #[case::null("$.next", None, r#"{"next": null}"#, None)]
+    #[case::missing("$.next", None, "{}", None)]
+    #[case::not_json("$.next", None, "not json", None)]
+    fn test_next_page_from_body(
+        #[case] next: &str,
+        #[case] param: Option<&str>,
+        #[case] body: &str,
+        #[case] expected: Option<&str>,
+    ) {
+        let request = create!(
+            Request,
+            url: "http://localhost/users?cursor=abc&limit=10".parse().unwrap(),
+            pagination: Some(Pagination {
+                next: next.parse().unwrap(),
+                param: param.map(String::from),
+            }),
+        );
+        // The header is ignored
+        let headers = header_map(indexmap! {
+            "content-type" => "application/json",
+            "link" => "</users?page=9>; rel=\"next\"",
+        });
+        let record = create!(
+            RequestRecord,
+            request: request.into(),
+            response: create!(Response, headers: headers, body: body.into()),
+        );
+        assert_eq!(
+            record
+                .next_page()
+                .as_ref()
+                .map(|request| request.url.as_str()),
+            expected
+        );
+    }
+
+    #[rstest]
+    #[case::not_json("not json", 0)]
+    #[case::no_errors(r#"{"data": {"user": null}}"#, 0)]
+    #[case::empty_errors(r#"{"data": null, "errors": []}"#, 0)]
+    #[case::errors(
+        r#"{"data": null, "errors": [{"message": "a"}, {"message": "b"}]}"#,
+        2
+    )]
+    #[case::errors_not_array(r#"{"errors": "bad"}"#, 0)]
+    fn test_graphql_error_count(#[case] body: &str, #[case] expected: usize) {
+        let response = create!(Response, body: body.into());
+        assert_eq!(response.graphql_error_count(), expected);
+    }
+
+    /// Bodies are decoded with their declared charset, falling back to UTF-8
+    #[rstest]
+    #[case::no_charset("text/plain", b"caf\xc3\xa9", Some("café"))]
+    #[case::utf8("text/plain; charset=utf-8", b"caf\xc3\xa9", Some("café"))]
+    #[case::latin1("text/html; charset=ISO-8859-1", b"caf\xe9", Some("café"))]
+    #[case::quoted(r#"text/plain; charset="latin1""#, b"caf\xe9", Some("café"))]
+    #[case::shift_jis("text/plain;charset=Shift_JIS", b"\x82\xa0", Some("あ"))]
+    #[case::unknown_charset("text/plain; charset=bogus", b"fish", Some("fish"))]
+    #[case::invalid_utf8("text/plain", b"caf\xe9", None)]
+    // Odd length isn't valid UTF-16, but it's valid UTF-8
+    #[case::fallback("text/plain; charset=utf-16le", b"f\xc3\xa9", Some("fé"))]
+    fn test_body_text(
+        #[case] content_type: &str,
+        #[case] body: &[u8],
+        #[case] expected: Option<&str>,
+    ) {
+        let response = create!(
+            Response,
+            headers: header_map([("content-type", content_type)]),
+            body: Body::new(Bytes::copy_from_slice(body)),
+        );
+        assert_eq!(response.body_text().as_deref(), expected);
+        // Original bytes are untouched
+        assert_eq!(response.body.bytes(), body);
+    }
+
+    /// Non-UTF-8 bodies are decoded before parsing
+    #[test]
+    fn test_parse_body_charset() {
+        let response = create!(
+            Response,
+            headers: header_map([(
+                "content-type",
+                "application/json; charset=iso-8859-1"
+            )]),
+            body: Body::new(Bytes::from_static(b"{\"name\": \"caf\xe9\"}")),
+        );
+        let parsed = response.parse_body().unwrap();
+        assert_eq!(parsed.to_json().into_owned(), json!({"name": "café"}));
+    }
+
+    /// An expected content type overrides the response's header. If the header
+    /// disagrees, report the mismatch
+    #[rstest]
+    #[case::no_expectation(None, Some("application/json"), true, None)]
+    #[case::matches(
+        Some(ContentType::Json),
+        Some("application/json"),
+        true,
+        None
+    )]
+    #[case::mismatch(
+        Some(ContentType::Json),
+        Some("text/html"),
+        true,
+        Some("Expected JSON, got text/html")
+    )]
+    #[case::no_header(
+        Some(ContentType::Json),
+        None,
+        true,
+        Some("Expected JSON, got no content type")
+    )]
+    #[case::no_header_no_expectation(None, None, false, None)]
+    fn test_expects(
+        #[case] expects: Option<ContentType>,
+        #[case] content_type: Option<&str>,
+        #[case] parses: bool,
+        #[case] expected_mismatch: Option<&str>,
+    ) {
+        let headers = match content_type {
+            Some(content_type) => header_map(indexmap! {
+                "content-type" => content_type,
+            }),
+            None => HeaderMap::new(),
+        };
+        let record = create!(
+            RequestRecord,
+            request: create!(Request, expects: expects).into(),
+            response: create!(
+                Response,
+                headers: headers,
+                body: r#"{"a": 1}"#.into(),
+            ),
+        );
+        assert_eq!(record.parse_body().is_ok(), parses);
+        assert_eq!(
+            record.content_type_mismatch().as_deref(),
+            expected_mismatch
+        );
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Headers(#[serde(with = "serde_header_map")] HeaderMap);
+
+    /// Repeated headers survive a round trip, and maps written before
+    /// repeated headers were supported still load
+    #[test]
+    fn test_serde_header_map() {
+        let mut headers = header_map([("content-type", "text/plain")]);
+        headers.append(header::SET_COOKIE, "a=1".parse().unwrap());
+        headers.append(header::SET_COOKIE, "b=2".parse().unwrap());
+        let headers = Headers(headers);
+        let bytes = rmp_serde::to_vec_named(&headers).unwrap();
+        assert_eq!(rmp_serde::from_slice::<Headers>(&bytes).unwrap(), headers);
+
+        let old = rmp_serde::to_vec_named(&indexmap! {
+            "content-type" => b"text/plain".as_slice(),
+        })
+        .unwrap();
         assert_eq!(
-            request.to_curl().unwrap(),
-            "curl -XDELETE --url 'http://localhost/url' \
-            --header 'accept: application/json' \
-            --header 'content-type: application/json' \
-            --data '{\"data\":\"value\"}'"
+            rmp_serde::from_slice::<Headers>(&old).unwrap(),
+            Headers(header_map([("content-type", "text/plain")]))
         );
     }
 }
@@ -0,0 +1,264 @@
+//! Redirect following, with control over which headers are forwarded
+
+use crate::http::Request;
+use reqwest::{
+    header::{self, HeaderMap, HeaderName},
+    Method, StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+use url::Url;
+
+/// Maximum number of redirects to follow for a single request. Once this is
+/// hit, the last redirect response is returned as-is.
+pub const MAX_REDIRECTS: usize = 10;
+
+/// Headers that carry credentials, which are stripped by
+/// [RedirectHeaderPolicy::StripSensitive]
+const SENSITIVE_HEADERS: &[HeaderName] = &[
+    header::AUTHORIZATION,
+    header::COOKIE,
+    header::PROXY_AUTHORIZATION,
+];
+
+/// Headers describing the request body, which are dropped when a redirect
+/// turns the request into a `GET`
+const BODY_HEADERS: &[HeaderName] = &[
+    header::CONTENT_ENCODING,
+    header::CONTENT_LENGTH,
+    header::CONTENT_TYPE,
+    header::TRANSFER_ENCODING,
+];
+
+/// Which request headers are forwarded when following a redirect to a
+/// different origin (scheme, host, or port). Same-origin redirects always
+/// keep all headers.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq))]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum RedirectHeaderPolicy {
+    /// Drop headers that carry credentials (`Authorization`, `Cookie`, etc.)
+    #[default]
+    StripSensitive,
+    /// Forward all headers. This can leak credentials to other hosts!
+    KeepAll,
+    /// Forward only these headers (case-insensitive), and drop all others
+    Allow(Vec<String>),
+}
+
+impl RedirectHeaderPolicy {
+    /// Should this header be forwarded across origins?
+    fn allows(&self, name: &HeaderName) -> bool {
+        match self {
+            Self::StripSensitive => !SENSITIVE_HEADERS.contains(name),
+            Self::KeepAll => true,
+            Self::Allow(allowed) => allowed
+                .iter()
+                .any(|allowed| allowed.eq_ignore_ascii_case(name.as_str())),
+        }
+    }
+}
+
+/// If the response is a redirect, build the request to send next. Return
+/// `None` if the response isn't a followable redirect.
+///
+/// This follows the same rules as browsers: `301`, `302`, and `303` change the
+/// method to `GET` and drop the body (except for `HEAD` requests), while
+/// `307` and `308` resend the request unchanged.
+pub(super) fn next_request(
+    request: &Request,
+    status: StatusCode,
+    response_headers: &HeaderMap,
+    policy: &RedirectHeaderPolicy,
+) -> Option<Request> {
+    let keep_method = match status {
+        StatusCode::MOVED_PERMANENTLY
+        | StatusCode::FOUND
+        | StatusCode::SEE_OTHER => false,
+        StatusCode::TEMPORARY_REDIRECT | StatusCode::PERMANENT_REDIRECT => true,
+        _ => return None,
+    };
+    let location = response_headers.get(header::LOCATION)?.to_str().ok()?;
+    let url = request.url.join(location).ok()?;
+    if !matches!(url.scheme(), "http" | "https") {
+        debug!(%url, "Not following redirect to non-HTTP URL");
+        return None;
+    }
+
+    let mut headers = request.headers.clone();
+    // A body command is re-run if the body is resent
+    let (method, body, body_command) =
+        if keep_method || request.method == Method::HEAD {
+            (
+                request.method.clone(),
+                request.body.clone(),
+                request.body_command.clone(),
+            )
+        } else {
+            for name in BODY_HEADERS {
+                headers.remove(name);
+            }
+            (Method::GET, None, None)
+        };
+
+    if !is_same_origin(&request.url, &url) {
+        let mut filtered = HeaderMap::with_capacity(headers.len());
+        for (name, value) in &headers {
+            if policy.allows(name) {
+                filtered.append(name, value.clone());
+            } else {
+                debug!(header = %name, %url, "Dropping header on redirect");
+            }
+        }
+        headers = filtered;
+    }
+
+    Some(Request {
+        method,
+        url,
+        headers,
+        body,
+        body_command,
+        ..request.duplicate()
+    })
+}
+
+fn is_same_origin(a: &Url, b: &Url) -> bool {
+    a.scheme() == b.scheme()
+        && a.host_str() == b.host_str()
+        && a.port_or_known_default() == b.port_or_known_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+    use factori::create;
+    use rstest::rstest;
+
+    fn request(url: &str) -> Request {
+        create!(
+            Request,
+            method: Method::POST,
+            url: url.parse().unwrap(),
+            headers: header_map([
+                ("authorization", "Bearer token"),
+                ("cookie", "session=1"),
+                ("content-type", "application/json"),
+                ("x-api-key", "key"),
+            ]),
+            body: Some("{}".into()),
+        )
+    }
+
+    /// Get the (sorted) names of a request's headers
+    fn header_names(request: &Request) -> Vec<&str> {
+        let mut names: Vec<_> =
+            request.headers.keys().map(HeaderName::as_str).collect();
+        names.sort();
+        names
+    }
+
+    /// Test which headers are forwarded by each policy
+    #[rstest]
+    #[case::same_origin(
+        "http://localhost/other",
+        RedirectHeaderPolicy::StripSensitive,
+        &["authorization", "content-type", "cookie", "x-api-key"],
+    )]
+    #[case::different_host(
+        "http://example.com/other",
+        RedirectHeaderPolicy::StripSensitive,
+        &["content-type", "x-api-key"],
+    )]
+    #[case::different_port(
+        "http://localhost:3000/other",
+        RedirectHeaderPolicy::StripSensitive,
+        &["content-type", "x-api-key"],
+    )]
+    #[case::different_scheme(
+        "https://localhost/other",
+        RedirectHeaderPolicy::StripSensitive,
+        &["content-type", "x-api-key"],
+    )]
+    #[case::keep_all(
+        "http://example.com/other",
+        RedirectHeaderPolicy::KeepAll,
+        &["authorization", "content-type", "cookie", "x-api-key"],
+    )]
+    #[case::allow(
+        "http://example.com/other",
+        RedirectHeaderPolicy::Allow(vec!["Authorization".into()]),
+        &["authorization"],
+    )]
+    fn test_redirect_headers(
+        #[case] location: &str,
+        #[case] policy: RedirectHeaderPolicy,
+        #[case] expected: &[&str],
+    ) {
+        let request = request("http://localhost/start");
+        let next = next_request(
+            &request,
+            StatusCode::TEMPORARY_REDIRECT,
+            &header_map([("location", location)]),
+            &policy,
+        )
+        .expect("Expected redirect to be followed");
+        assert_eq!(next.url.as_str(), location);
+        assert_eq!(header_names(&next), expected);
+    }
+
+    /// Test how the method and body change for each redirect status
+    #[rstest]
+    #[case::moved_permanently(StatusCode::MOVED_PERMANENTLY, Method::GET)]
+    #[case::found(StatusCode::FOUND, Method::GET)]
+    #[case::see_other(StatusCode::SEE_OTHER, Method::GET)]
+    #[case::temporary(StatusCode::TEMPORARY_REDIRECT, Method::POST)]
+    #[case::permanent(StatusCode::PERMANENT_REDIRECT, Method::POST)]
+    fn test_redirect_method(
+        #[case] status: StatusCode,
+        #[case] expected_method: Method,
+    ) {
+        let request = request("http://localhost/start");
+        let next = next_request(
+            &request,
+            status,
+            // Relative locations are resolved against the request URL
+            &header_map([("location", "/other")]),
+            &RedirectHeaderPolicy::default(),
+        )
+        .expect("Expected redirect to be followed");
+        assert_eq!(next.url.as_str(), "http://localhost/other");
+        assert_ne!(next.id, request.id);
+        if expected_method == Method::POST {
+            assert_eq!(next.body, request.body);
+            assert!(next.headers.contains_key(header::CONTENT_TYPE));
+        } else {
+            assert_eq!(next.body, None);
+            assert!(!next.headers.contains_key(header::CONTENT_TYPE));
+        }
+        assert_eq!(next.method, expected_method);
+    }
+
+    /// Responses that aren't followable redirects
+    #[rstest]
+    #[case::not_redirect(StatusCode::OK, Some("/other"))]
+    #[case::not_modified(StatusCode::NOT_MODIFIED, Some("/other"))]
+    #[case::no_location(StatusCode::FOUND, None)]
+    #[case::bad_scheme(StatusCode::FOUND, Some("ftp://localhost/file"))]
+    fn test_redirect_not_followed(
+        #[case] status: StatusCode,
+        #[case] location: Option<&str>,
+    ) {
+        let headers = header_map(location.map(|value| ("location", value)));
+        assert_eq!(
+            next_request(
+                &request("http://localhost/start"),
+                status,
+                &headers,
+                &RedirectHeaderPolicy::default(),
+            ),
+            None
+        );
+    }
+}
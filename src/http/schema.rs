@@ -0,0 +1,139 @@
+//! Validation of response bodies against a JSON Schema
+
+use crate::{
+    collection::ResponseSchema, http::Response, template::TemplateContext,
+};
+use anyhow::{anyhow, Context};
+use derive_more::Display;
+use jsonschema::JSONSchema;
+use tokio::fs;
+
+/// A single way in which a response body doesn't match its schema
+#[derive(Debug, Display, PartialEq)]
+#[display("{path}: {message}")]
+pub struct SchemaViolation {
+    /// JSON pointer to the offending value. The root is shown as `/`, for
+    /// readability
+    pub path: String,
+    pub message: String,
+}
+
+impl ResponseSchema {
+    /// Load the schema as JSON. For file schemas, the path is rendered as a
+    /// template, and the file can be either JSON or YAML.
+    pub async fn load(
+        &self,
+        context: &TemplateContext,
+    ) -> anyhow::Result<serde_json::Value> {
+        match self {
+            Self::Inline(schema) => Ok(schema.clone()),
+            Self::File { path } => {
+                let path = context.resolve_path(
+                    path.render(context)
+                        .await
+                        .context("Error rendering schema path")?,
+                )?;
+                let content = fs::read(&path).await.with_context(|| {
+                    format!("Error reading schema from {path:?}")
+                })?;
+                // YAML is a superset of JSON, so this handles both
+                serde_yaml::from_slice(&content).with_context(|| {
+                    format!("Error parsing schema from {path:?}")
+                })
+            }
+        }
+    }
+
+    /// Load the schema, then validate a response body against it. Returns
+    /// every violation found, so an empty list means the body is valid. An
+    /// error means the validation couldn't be performed at all, e.g. because
+    /// the schema is invalid or the body couldn't be parsed.
+    pub async fn validate(
+        &self,
+        context: &TemplateContext,
+        response: &Response,
+    ) -> anyhow::Result<Vec<SchemaViolation>> {
+        let schema = self.load(context).await?;
+        validate(&schema, response)
+    }
+}
+
+/// Validate a response body against a JSON schema. The body is parsed
+/// according to its content type, then converted to JSON.
+fn validate(
+    schema: &serde_json::Value,
+    response: &Response,
+) -> anyhow::Result<Vec<SchemaViolation>> {
+    let schema = JSONSchema::compile(schema)
+        .map_err(|error| anyhow!("Invalid schema: {error}"))?;
+    let body = response.parse_body()?;
+    let body = body.to_json();
+    let violations = match schema.validate(&body) {
+        Ok(()) => Vec::new(),
+        Err(errors) => errors
+            .map(|error| {
+                let path = error.instance_path.to_string();
+                SchemaViolation {
+                    path: if path.is_empty() { "/".into() } else { path },
+                    message: error.to_string(),
+                }
+            })
+            .collect(),
+    };
+    Ok(violations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+    use factori::create;
+    use rstest::rstest;
+    use serde_json::json;
+
+    #[rstest]
+    #[case::valid(r#"{"id": 3, "name": "Jimmy"}"#, &[])]
+    #[case::wrong_type(
+        r#"{"id": "3", "name": "Jimmy"}"#,
+        &["/id: \"3\" is not of type \"integer\""],
+    )]
+    #[case::multiple(
+        r#"{"id": "3"}"#,
+        &[
+            "/id: \"3\" is not of type \"integer\"",
+            "/: \"name\" is a required property",
+        ],
+    )]
+    fn test_validate(#[case] body: &str, #[case] expected: &[&str]) {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "id": {"type": "integer"},
+                "name": {"type": "string"},
+            },
+            "required": ["id", "name"],
+        });
+        let response = create!(
+            Response,
+            headers: header_map([("content-type", "application/json")]),
+            body: body.into(),
+        );
+        let violations = validate(&schema, &response)
+            .unwrap()
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        assert_eq!(violations, expected);
+    }
+
+    #[test]
+    fn test_validate_invalid_body() {
+        let schema = json!({"type": "object"});
+        let response = create!(
+            Response,
+            headers: header_map([("content-type", "application/json")]),
+            body: "not json".into(),
+        );
+        assert!(validate(&schema, &response).is_err());
+    }
+}
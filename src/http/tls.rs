@@ -0,0 +1,381 @@
+//! Restricting which TLS versions a request can negotiate, from the `tls`
+//! config and recipe fields, and finding out which one it got
+
+use anyhow::bail;
+use derive_more::Display;
+use reqwest::{tls, ClientBuilder};
+use rustls::{
+    client::{
+        HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier,
+        WebPkiVerifier,
+    },
+    version, Certificate, ClientConfig, DigitallySignedStruct,
+    OwnedTrustAnchor, RootCertStore, ServerName, SupportedProtocolVersion,
+};
+use serde::{
+    de::{self, Visitor},
+    Deserialize, Deserializer, Serialize,
+};
+use std::{
+    collections::HashMap,
+    fmt,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+/// A TLS protocol version. Only the versions supported by the TLS library are
+/// accepted; anything older than 1.2 is insecure anyway.
+#[derive(
+    Copy, Clone, Debug, Display, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize,
+)]
+pub enum TlsVersion {
+    #[display("TLS 1.2")]
+    #[serde(rename = "1.2")]
+    Tls12,
+    #[display("TLS 1.3")]
+    #[serde(rename = "1.3")]
+    Tls13,
+}
+
+/// The range of TLS versions a request may use. Either bound can be omitted,
+/// in which case it's left open.
+#[derive(
+    Copy, Clone, Debug, Default, Eq, Hash, PartialEq, Serialize, Deserialize,
+)]
+#[serde(default, deny_unknown_fields)]
+pub struct TlsVersions {
+    pub min: Option<TlsVersion>,
+    pub max: Option<TlsVersion>,
+}
+
+/// The TLS version negotiated with each server, keyed by the certificate the
+/// server presented. reqwest only tells us the peer certificate of the
+/// connection a response came over, so that's how a response is matched to
+/// its version.
+#[derive(Debug, Default)]
+pub(super) struct NegotiatedVersions(Mutex<HashMap<Vec<u8>, TlsVersion>>);
+
+/// Verifies server certs (unless cert errors are ignored), recording the TLS
+/// version of each handshake along the way. rustls checks the handshake
+/// signature differently for each version, which is how we can tell them
+/// apart.
+struct RecordingVerifier {
+    /// `None` if cert errors are ignored
+    verifier: Option<WebPkiVerifier>,
+    negotiated: Arc<NegotiatedVersions>,
+}
+
+impl TlsVersion {
+    const ALL: [Self; 2] = [Self::Tls12, Self::Tls13];
+    const OLDEST: Self = Self::Tls12;
+    const NEWEST: Self = Self::Tls13;
+
+    fn to_reqwest(self) -> tls::Version {
+        match self {
+            Self::Tls12 => tls::Version::TLS_1_2,
+            Self::Tls13 => tls::Version::TLS_1_3,
+        }
+    }
+
+    fn to_rustls(self) -> &'static SupportedProtocolVersion {
+        match self {
+            Self::Tls12 => &version::TLS12,
+            Self::Tls13 => &version::TLS13,
+        }
+    }
+}
+
+impl FromStr for TlsVersion {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "1.2" => Ok(Self::Tls12),
+            "1.3" => Ok(Self::Tls13),
+            "1.0" | "1" | "1.1" => {
+                bail!("TLS {s} is not supported; expected 1.2 or 1.3")
+            }
+            _ => bail!("Invalid TLS version `{s}`; expected 1.2 or 1.3"),
+        }
+    }
+}
+
+// YAML parses `1.3` as a number, so accept that as well as a string
+impl<'de> Deserialize<'de> for TlsVersion {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct TlsVersionVisitor;
+
+        impl<'de> Visitor<'de> for TlsVersionVisitor {
+            type Value = TlsVersion;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("TLS version, e.g. 1.3")
+            }
+
+            fn visit_f64<E: de::Error>(self, v: f64) -> Result<Self::Value, E> {
+                self.visit_str(&v.to_string())
+            }
+
+            fn visit_u64<E: de::Error>(self, v: u64) -> Result<Self::Value, E> {
+                self.visit_str(&v.to_string())
+            }
+
+            fn visit_str<E: de::Error>(
+                self,
+                v: &str,
+            ) -> Result<Self::Value, E> {
+                v.parse().map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_any(TlsVersionVisitor)
+    }
+}
+
+impl TlsVersions {
+    /// Apply these versions on top of another set, e.g. a recipe's over the
+    /// global config. Each bound is overridden separately.
+    pub fn or(self, base: Self) -> Self {
+        Self {
+            min: self.min.or(base.min),
+            max: self.max.or(base.max),
+        }
+    }
+
+    /// Get the lowest and highest allowed versions, with open bounds filled
+    /// in. Fails if no version is allowed.
+    pub fn bounds(self) -> anyhow::Result<(TlsVersion, TlsVersion)> {
+        let min = self.min.unwrap_or(TlsVersion::OLDEST);
+        let max = self.max.unwrap_or(TlsVersion::NEWEST);
+        if min > max {
+            bail!(
+                "Minimum TLS version ({min}) is higher than the maximum \
+                ({max})"
+            );
+        }
+        Ok((min, max))
+    }
+
+    /// Restrict a client to these versions
+    pub(super) fn apply(
+        self,
+        builder: ClientBuilder,
+    ) -> anyhow::Result<ClientBuilder> {
+        let (min, max) = self.bounds()?;
+        Ok(builder
+            .min_tls_version(min.to_reqwest())
+            .max_tls_version(max.to_reqwest()))
+    }
+
+    /// Build TLS config for a client restricted to these versions. This is
+    /// the same as what reqwest would build on its own, except the version
+    /// of each connection is recorded in `negotiated`. If `danger` is
+    /// enabled, the client ignores TLS cert errors.
+    pub(super) fn client_config(
+        self,
+        danger: bool,
+        negotiated: Arc<NegotiatedVersions>,
+    ) -> anyhow::Result<ClientConfig> {
+        let (min, max) = self.bounds()?;
+        let versions: Vec<_> = TlsVersion::ALL
+            .into_iter()
+            .filter(|version| (min..=max).contains(version))
+            .map(TlsVersion::to_rustls)
+            .collect();
+
+        let mut roots = RootCertStore::empty();
+        roots.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(
+            |anchor| {
+                OwnedTrustAnchor::from_subject_spki_name_constraints(
+                    anchor.subject,
+                    anchor.spki,
+                    anchor.name_constraints,
+                )
+            },
+        ));
+        let verifier = RecordingVerifier {
+            verifier: (!danger).then(|| WebPkiVerifier::new(roots, None)),
+            negotiated,
+        };
+
+        let mut config = ClientConfig::builder()
+            .with_safe_default_cipher_suites()
+            .with_safe_default_kx_groups()
+            .with_protocol_versions(&versions)?
+            .with_custom_certificate_verifier(Arc::new(verifier))
+            .with_no_client_auth();
+        config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+        Ok(config)
+    }
+}
+
+impl NegotiatedVersions {
+    /// Get the version negotiated with the server that presented a
+    /// certificate
+    pub fn get(&self, certificate: &[u8]) -> Option<TlsVersion> {
+        self.0
+            .lock()
+            .expect("Lock poisoned")
+            .get(certificate)
+            .copied()
+    }
+
+    fn insert(&self, certificate: &Certificate, version: TlsVersion) {
+        self.0
+            .lock()
+            .expect("Lock poisoned")
+            .insert(certificate.0.clone(), version);
+    }
+}
+
+impl RecordingVerifier {
+    fn verify_signature(
+        &self,
+        version: TlsVersion,
+        certificate: &Certificate,
+        verify: impl FnOnce(
+            &WebPkiVerifier,
+        )
+            -> Result<HandshakeSignatureValid, rustls::Error>,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        let valid = match &self.verifier {
+            Some(verifier) => verify(verifier)?,
+            None => HandshakeSignatureValid::assertion(),
+        };
+        self.negotiated.insert(certificate, version);
+        Ok(valid)
+    }
+}
+
+impl ServerCertVerifier for RecordingVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+        server_name: &ServerName,
+        scts: &mut dyn Iterator<Item = &[u8]>,
+        ocsp_response: &[u8],
+        now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        match &self.verifier {
+            Some(verifier) => verifier.verify_server_cert(
+                end_entity,
+                intermediates,
+                server_name,
+                scts,
+                ocsp_response,
+                now,
+            ),
+            None => Ok(ServerCertVerified::assertion()),
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        certificate: &Certificate,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.verify_signature(TlsVersion::Tls12, certificate, |verifier| {
+            verifier.verify_tls12_signature(message, certificate, dss)
+        })
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        certificate: &Certificate,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.verify_signature(TlsVersion::Tls13, certificate, |verifier| {
+            verifier.verify_tls13_signature(message, certificate, dss)
+        })
+    }
+
+    fn request_scts(&self) -> bool {
+        self.verifier
+            .as_ref()
+            .is_some_and(ServerCertVerifier::request_scts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::{assert_err, parse_yaml};
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::number("min: 1.3", Some(TlsVersion::Tls13), None)]
+    #[case::string("max: '1.2'", None, Some(TlsVersion::Tls12))]
+    #[case::both(
+        "{min: 1.2, max: 1.3}",
+        Some(TlsVersion::Tls12),
+        Some(TlsVersion::Tls13)
+    )]
+    fn test_deserialize(
+        #[case] yaml: &str,
+        #[case] min: Option<TlsVersion>,
+        #[case] max: Option<TlsVersion>,
+    ) {
+        let versions: TlsVersions = parse_yaml(yaml.as_bytes()).unwrap();
+        assert_eq!(versions, TlsVersions { min, max });
+    }
+
+    #[rstest]
+    #[case::old("min: 1.1", "TLS 1.1 is not supported")]
+    #[case::invalid("min: 2", "Invalid TLS version `2`")]
+    fn test_deserialize_error(
+        #[case] yaml: &str,
+        #[case] expected_error: &str,
+    ) {
+        assert_err!(parse_yaml::<TlsVersions>(yaml.as_bytes()), expected_error);
+    }
+
+    #[rstest]
+    #[case::open(None, None, Ok((TlsVersion::Tls12, TlsVersion::Tls13)))]
+    #[case::pinned(
+        Some(TlsVersion::Tls13),
+        None,
+        Ok((TlsVersion::Tls13, TlsVersion::Tls13))
+    )]
+    #[case::inverted(
+        Some(TlsVersion::Tls13),
+        Some(TlsVersion::Tls12),
+        Err("Minimum TLS version (TLS 1.3) is higher than the maximum (TLS 1.2)")
+    )]
+    fn test_bounds(
+        #[case] min: Option<TlsVersion>,
+        #[case] max: Option<TlsVersion>,
+        #[case] expected: Result<(TlsVersion, TlsVersion), &str>,
+    ) {
+        let versions = TlsVersions { min, max };
+        match expected {
+            Ok(expected) => assert_eq!(versions.bounds().unwrap(), expected),
+            Err(expected) => assert_err!(versions.bounds(), expected),
+        }
+    }
+
+    #[test]
+    fn test_or() {
+        let global = TlsVersions {
+            min: Some(TlsVersion::Tls12),
+            max: Some(TlsVersion::Tls12),
+        };
+        let recipe = TlsVersions {
+            min: None,
+            max: Some(TlsVersion::Tls13),
+        };
+        assert_eq!(
+            recipe.or(global),
+            TlsVersions {
+                min: Some(TlsVersion::Tls12),
+                max: Some(TlsVersion::Tls13),
+            }
+        );
+    }
+}
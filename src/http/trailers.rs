@@ -0,0 +1,124 @@
+//! Response trailers. reqwest doesn't expose HTTP trailers, so the only ones
+//! we can capture are gRPC-web's, which are encoded in the body as a final
+//! frame rather than sent as real trailers.
+
+use reqwest::header::{self, HeaderMap, HeaderName, HeaderValue};
+
+/// Content type prefix for binary gRPC-web responses. The `-text` variant is
+/// base64-encoded, and isn't supported.
+const GRPC_WEB_CONTENT_TYPE: &str = "application/grpc-web";
+/// Flag bit marking a gRPC-web frame as trailers rather than a message
+const TRAILER_FLAG: u8 = 0x80;
+/// Flag byte plus the 4-byte big-endian payload length
+const FRAME_HEADER_LENGTH: usize = 5;
+
+/// Get the trailers out of a response body, if the response is gRPC-web.
+/// Malformed frames end parsing, keeping whatever was parsed before them, so
+/// a bad trailer never fails the response. The body is left as-is.
+pub fn parse_trailers(headers: &HeaderMap, body: &[u8]) -> HeaderMap {
+    let mut trailers = HeaderMap::new();
+    let is_grpc_web = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .is_some_and(|value| {
+            let value = value.trim();
+            value == GRPC_WEB_CONTENT_TYPE
+                || value.starts_with(&format!("{GRPC_WEB_CONTENT_TYPE}+"))
+        });
+    if !is_grpc_web {
+        return trailers;
+    }
+
+    let mut rest = body;
+    while rest.len() >= FRAME_HEADER_LENGTH {
+        let flag = rest[0];
+        let length =
+            u32::from_be_bytes([rest[1], rest[2], rest[3], rest[4]]) as usize;
+        let Some(payload) =
+            rest.get(FRAME_HEADER_LENGTH..FRAME_HEADER_LENGTH + length)
+        else {
+            break;
+        };
+        rest = &rest[FRAME_HEADER_LENGTH + length..];
+        if flag & TRAILER_FLAG == 0 {
+            continue;
+        }
+
+        // The payload is an HTTP/1-style header block
+        for line in String::from_utf8_lossy(payload).lines() {
+            let Some((name, value)) = line.split_once(':') else {
+                continue;
+            };
+            let (Ok(name), Ok(value)) = (
+                HeaderName::try_from(name.trim()),
+                HeaderValue::try_from(value.trim()),
+            ) else {
+                continue;
+            };
+            trailers.append(name, value);
+        }
+    }
+    trailers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+    use rstest::rstest;
+
+    /// Encode a gRPC-web frame
+    fn frame(flag: u8, payload: &[u8]) -> Vec<u8> {
+        let mut frame = vec![flag];
+        frame.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+        frame.extend_from_slice(payload);
+        frame
+    }
+
+    #[rstest]
+    #[case::grpc_web(
+        "application/grpc-web",
+        [
+            frame(0, b"message"),
+            frame(0x80, b"grpc-status: 0\r\ngrpc-message: OK\r\n"),
+        ].concat(),
+        &[("grpc-status", "0"), ("grpc-message", "OK")],
+    )]
+    #[case::grpc_web_proto(
+        "application/grpc-web+proto",
+        frame(0x80, b"grpc-status:5"),
+        &[("grpc-status", "5")],
+    )]
+    #[case::not_grpc_web(
+        "application/octet-stream",
+        frame(0x80, b"grpc-status: 0"),
+        &[],
+    )]
+    #[case::text_unsupported(
+        "application/grpc-web-text",
+        frame(0x80, b"grpc-status: 0"),
+        &[],
+    )]
+    #[case::truncated(
+        "application/grpc-web",
+        frame(0x80, b"grpc-status: 0")[..8].to_vec(),
+        &[],
+    )]
+    #[case::bad_line(
+        "application/grpc-web",
+        frame(0x80, b"nonsense\r\ngrpc-status: 0"),
+        &[("grpc-status", "0")],
+    )]
+    fn test_parse_trailers(
+        #[case] content_type: &str,
+        #[case] body: Vec<u8>,
+        #[case] expected: &[(&str, &str)],
+    ) {
+        let headers = header_map([("content-type", content_type)]);
+        assert_eq!(
+            parse_trailers(&headers, &body),
+            header_map(expected.iter().copied())
+        );
+    }
+}
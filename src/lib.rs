@@ -0,0 +1,52 @@
+//! Slumber's core engine, as used by the `slumber` TUI and CLI. This can be
+//! used to build other frontends on top of the same collections, templates,
+//! and request history.
+//!
+//! The supported API is made up of these modules:
+//! - [collection]: Load a request collection ([CollectionFile]) and access
+//!   its profiles, chains, and recipes ([Collection]), or build one in code
+//!   ([CollectionBuilder])
+//! - [template]: Render templates against a profile with a
+//!   [TemplateContext]
+//! - [http]: Build requests from recipes with a [RequestBuilder], and send
+//!   them with an [HttpEngine]
+//! - [db]: Persist and query request history ([Database],
+//!   [CollectionDatabase])
+//! - [config]: Load the user's global [Config]
+//!
+//! Each of these reports failures with a concrete error type (e.g.
+//! [CollectionLoadError], [TemplateError], [RequestBuildError]), so callers
+//! can match on specific errors. Anything else that's public (the CLI, TUI,
+//! and assorted utilities) exists to support the `slumber` binary, and is
+//! hidden from the docs because it may change in any release.
+//!
+//! [Collection]: collection::Collection
+//! [CollectionBuilder]: collection::CollectionBuilder
+//! [CollectionFile]: collection::CollectionFile
+//! [CollectionLoadError]: collection::CollectionLoadError
+//! [TemplateContext]: template::TemplateContext
+//! [TemplateError]: template::TemplateError
+//! [RequestBuilder]: http::RequestBuilder
+//! [HttpEngine]: http::HttpEngine
+//! [RequestBuildError]: http::RequestBuildError
+//! [Database]: db::Database
+//! [CollectionDatabase]: db::CollectionDatabase
+//! [Config]: config::Config
+
+#![forbid(unsafe_code)]
+#![deny(clippy::all)]
+
+pub mod collection;
+pub mod config;
+pub mod db;
+pub mod http;
+pub mod template;
+
+#[doc(hidden)]
+pub mod cli;
+#[cfg(test)]
+mod test_util;
+#[doc(hidden)]
+pub mod tui;
+#[doc(hidden)]
+pub mod util;
@@ -1,20 +1,15 @@
 #![forbid(unsafe_code)]
 #![deny(clippy::all)]
 
-mod cli;
-mod collection;
-mod config;
-mod db;
-mod http;
-mod template;
-#[cfg(test)]
-mod test_util;
-mod tui;
-mod util;
-
-use crate::{cli::CliCommand, tui::Tui, util::paths::DataDirectory};
 use clap::Parser;
-use std::{fs::File, io, path::PathBuf, process::ExitCode};
+use dialoguer::console;
+use slumber::{
+    cli::{CliCommand, GlobalArgs},
+    config::Config,
+    tui::{Tui, TuiArgs},
+    util::paths::DataDirectory,
+};
+use std::{env, fs::File, io, process::ExitCode};
 use tracing::level_filters::LevelFilter;
 use tracing_subscriber::{filter::EnvFilter, fmt::format::FmtSpan, prelude::*};
 
@@ -28,33 +23,31 @@ use tracing_subscriber::{filter::EnvFilter, fmt::format::FmtSpan, prelude::*};
 struct Args {
     #[command(flatten)]
     global: GlobalArgs,
+    #[command(flatten)]
+    tui: TuiArgs,
     /// Subcommand to execute. If omitted, run the TUI
     #[command(subcommand)]
     subcommand: Option<CliCommand>,
 }
 
-/// Arguments that are available to all subcommands and the TUI
-#[derive(Debug, Parser)]
-struct GlobalArgs {
-    /// Collection file, which defines profiles, recipes, etc. If omitted,
-    /// check the current directory for the following files (in this order):
-    /// slumber.yml, slumber.yaml, .slumber.yml, .slumber.yaml
-    #[clap(long, short)]
-    file: Option<PathBuf>,
-}
-
 #[tokio::main]
 async fn main() -> anyhow::Result<ExitCode> {
     // Global initialization
     let args = Args::parse();
-    initialize_tracing(args.subcommand.is_some()).unwrap();
+    let color = color_enabled(args.global.no_color);
+    // This has to happen before logging starts, so logs go to the right spot
+    if let Err(error) = DataDirectory::init(Config::load_data_directory()) {
+        print_error(&error);
+        return Ok(ExitCode::FAILURE);
+    }
+    initialize_tracing(args.subcommand.is_some(), color).unwrap();
 
     // Select mode based on whether request ID(s) were given
     match args.subcommand {
         // Run the TUI
         None => {
             // This should return the error so we get a full stack trac
-            Tui::start(args.global.file).await?;
+            Tui::start(args.global, args.tui).await?;
             Ok(ExitCode::SUCCESS)
         }
 
@@ -65,19 +58,38 @@ async fn main() -> anyhow::Result<ExitCode> {
             // Do *not* return the error, because that prints a stack trace
             // which is way too verbose. Just print the error messages instead
             .unwrap_or_else(|error| {
-                eprintln!("{error}");
-                error
-                    .chain()
-                    .skip(1)
-                    .for_each(|cause| eprintln!("  {cause}"));
+                print_error(&error);
                 ExitCode::FAILURE
             })),
     }
 }
 
+/// Print an error and its causes, without the full stack trace
+fn print_error(error: &anyhow::Error) {
+    eprintln!("{error}");
+    error
+        .chain()
+        .skip(1)
+        .for_each(|cause| eprintln!("  {cause}"));
+}
+
+/// Should CLI output be styled? This follows the
+/// [NO_COLOR](https://no-color.org) convention: colors are disabled if the
+/// variable is set to any non-empty value. This also configures styling for
+/// prompts and other console output.
+fn color_enabled(no_color_flag: bool) -> bool {
+    let no_color_env = env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+    let enabled = !(no_color_flag || no_color_env);
+    if !enabled {
+        console::set_colors_enabled(false);
+        console::set_colors_enabled_stderr(false);
+    }
+    enabled
+}
+
 /// Set up tracing to log to a file. Optionally also log to stderr (for CLI
 /// usage)
-fn initialize_tracing(console_output: bool) -> anyhow::Result<()> {
+fn initialize_tracing(console_output: bool, color: bool) -> anyhow::Result<()> {
     let path = DataDirectory::log().create_parent()?;
     let log_file = File::create(path)?;
     let file_subscriber = tracing_subscriber::fmt::layer()
@@ -94,6 +106,7 @@ fn initialize_tracing(console_output: bool) -> anyhow::Result<()> {
         Some(
             tracing_subscriber::fmt::layer()
                 .with_writer(io::stderr)
+                .with_ansi(color)
                 .with_target(false)
                 .with_span_events(FmtSpan::NEW)
                 .without_time()
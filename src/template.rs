@@ -1,25 +1,45 @@
+mod chain_cache;
 mod error;
+mod explain;
+mod format;
+mod function;
 mod parse;
 mod prompt;
 mod render;
+mod variables;
 
-pub use error::{ChainError, TemplateError, TriggeredRequestError};
-pub use parse::Span;
-pub use prompt::{Prompt, PromptChannel, Prompter};
+pub use chain_cache::with_chain_cache;
+pub use error::{
+    ChainError, FileError, RelativePathError, TemplateError,
+    TemplateParseError, TriggeredRequestError,
+};
+pub use explain::{KeyExplanation, KeySource, TemplateExplanation};
+pub use format::format_json;
+pub use function::{with_request_body, without_request_body, RandomSource};
+pub use parse::{Span, ROW_PREFIX};
+pub use prompt::{ChainChoices, Prompt, PromptChannel, Prompter};
+pub use variables::SessionVariables;
 
 use crate::{
-    collection::{Collection, ProfileId},
+    collection::{ChainId, Collection, ProfileId},
     db::CollectionDatabase,
     http::HttpEngine,
     template::{
-        error::TemplateParseError,
-        parse::{TemplateInputChunk, CHAIN_PREFIX, ENV_PREFIX},
+        function::{RandomFunction, TemplateFunction},
+        parse::{
+            parse_function_arguments, FunctionArgument, TemplateInputChunk,
+            CHAIN_PREFIX, CONST_PREFIX, ENV_PREFIX, FILE_BASE64_PREFIX,
+            FILE_PREFIX, VARS_PREFIX,
+        },
     },
+    util::paths::expand_home,
 };
+use anyhow::{anyhow, bail, Context};
 use derive_more::Display;
 use indexmap::IndexMap;
+use itertools::Itertools;
 use serde::Serialize;
-use std::{fmt::Debug, sync::atomic::AtomicU8};
+use std::{fmt::Debug, path::PathBuf, sync::atomic::AtomicU8};
 
 /// Maximum number of layers of nested templates
 const RECURSION_LIMIT: u8 = 10;
@@ -47,6 +67,18 @@ pub struct TemplateContext {
     pub overrides: IndexMap<String, String>,
     /// A conduit to ask the user questions
     pub prompter: Box<dyn Prompter>,
+    /// Matches the user picked for chains with multiple selector matches.
+    /// Share this between contexts to remember choices across renders
+    pub chain_choices: ChainChoices,
+    /// Source of values for random functions, e.g. `random_int(1, 10)`.
+    /// Seed it to get the same values on every run
+    pub random: RandomSource,
+    /// Values captured from responses, for `vars.` keys. Share this between
+    /// contexts so captures are visible to later renders
+    pub variables: SessionVariables,
+    /// What to resolve relative file paths against, typically the
+    /// collection's directory
+    pub path_root: PathRoot,
     /// A count of how many templates have *already* been rendered with this
     /// context. This is used to prevent infinite recursion in templates. For
     /// all external calls, you can start this at 0.
@@ -58,6 +90,83 @@ pub struct TemplateContext {
     pub recursion_count: AtomicU8,
 }
 
+impl TemplateContext {
+    /// Resolve a file path from the collection. A leading `~` is replaced
+    /// with the user's home directory, then relative paths are resolved
+    /// against [Self::path_root]. Fails if the path is relative and relative
+    /// paths aren't allowed.
+    pub fn resolve_path(
+        &self,
+        path: impl Into<PathBuf>,
+    ) -> Result<PathBuf, RelativePathError> {
+        let path = expand_home(path.into());
+        if path.is_absolute() {
+            return Ok(path);
+        }
+        match &self.path_root {
+            PathRoot::CurrentDirectory => Ok(path),
+            PathRoot::Directory(root) => Ok(root.join(path)),
+            PathRoot::Disallowed => Err(RelativePathError { path }),
+        }
+    }
+    /// Render a profile selector, e.g. the `--profile` CLI argument, and find
+    /// the profile it refers to. The rendered value can be either a profile ID
+    /// or a profile's `name`. IDs take precedence, and a name has to be unique
+    /// to be used.
+    pub async fn resolve_profile(
+        &self,
+        profile: &str,
+    ) -> anyhow::Result<ProfileId> {
+        let template = Template::try_from(profile.to_owned())
+            .with_context(|| format!("Error parsing profile `{profile}`"))?;
+        let rendered = template
+            .render(self)
+            .await
+            .with_context(|| format!("Error rendering profile `{profile}`"))?;
+        if rendered.is_empty() {
+            bail!("Profile `{profile}` rendered to an empty string");
+        }
+
+        let profiles = &self.collection.profiles;
+        let profile_id = ProfileId::from(rendered);
+        if profiles.contains_key(&profile_id) {
+            return Ok(profile_id);
+        }
+        let named = profiles
+            .values()
+            .filter(|profile| profile.name.as_deref() == Some(&profile_id))
+            .map(|profile| &profile.id)
+            .collect_vec();
+        match named.as_slice() {
+            [id] => Ok((*id).clone()),
+            [] => Err(anyhow!(
+                "No profile with ID or name `{profile_id}`; options are: {}",
+                profiles.keys().join(", ")
+            )),
+            _ => Err(anyhow!(
+                "Multiple profiles are named `{profile_id}`; use one of their \
+                IDs instead: {}",
+                named.iter().join(", ")
+            )),
+        }
+    }
+}
+
+/// What relative file paths in a collection are resolved against
+#[derive(Clone, Debug, Default)]
+pub enum PathRoot {
+    /// Leave relative paths as-is, so they're relative to the current
+    /// directory
+    #[default]
+    CurrentDirectory,
+    /// Resolve relative paths against a directory, typically the collection's
+    Directory(PathBuf),
+    /// The collection didn't come from the file system (e.g. it was read from
+    /// stdin), so there's nothing to resolve relative paths against. Using
+    /// one is an error.
+    Disallowed,
+}
+
 /// An immutable string that can contain templated content. The string is parsed
 /// during creation to identify template keys, hence the immutability.
 #[derive(Clone, Debug, Display, Serialize)]
@@ -78,11 +187,76 @@ impl Template {
         &self.template
     }
 
+    /// Does this template contain no keys? A static template always renders
+    /// to its raw text.
+    pub fn is_static(&self) -> bool {
+        self.chunks
+            .iter()
+            .all(|chunk| matches!(chunk, TemplateInputChunk::Raw(_)))
+    }
+
     /// Get a substring of this template. Panics if the span is out of range
     pub fn substring(&self, span: Span) -> &str {
         &self.template[span.start()..span.end()]
     }
 
+    /// Get every profile field, constant, and chain that this template
+    /// references, including in nested templates (e.g. file paths). This does
+    /// *not* follow references into the profile, constant, or chain, because
+    /// that requires the collection.
+    pub fn references(&self) -> Vec<TemplateReference> {
+        let mut references = Vec::new();
+        for chunk in &self.chunks {
+            let TemplateInputChunk::Key(key) = chunk else {
+                continue;
+            };
+            match key.map(|span| self.substring(span)) {
+                TemplateKey::Field(field) => {
+                    references.push(TemplateReference::Field(field.into()))
+                }
+                TemplateKey::Chain(chain_id) => {
+                    references.push(TemplateReference::Chain(chain_id.into()))
+                }
+                TemplateKey::Constant(name) => {
+                    references.push(TemplateReference::Constant(name.into()))
+                }
+                TemplateKey::Environment(_)
+                | TemplateKey::Row(_)
+                | TemplateKey::Variable(_)
+                | TemplateKey::Random { .. } => {}
+                TemplateKey::File(path) | TemplateKey::FileBase64(path) => {
+                    // Path was already parsed as part of this template, so it
+                    // has to be valid
+                    let path = Template::parse(path.to_owned())
+                        .expect("Nested template was already parsed");
+                    references.extend(path.references());
+                }
+                TemplateKey::Function { arguments, .. } => {
+                    for argument in parse_function_arguments(arguments) {
+                        match argument {
+                            FunctionArgument::Key(TemplateKey::Field(
+                                field,
+                            )) => references
+                                .push(TemplateReference::Field(field.into())),
+                            FunctionArgument::Key(TemplateKey::Chain(
+                                chain_id,
+                            )) => references.push(TemplateReference::Chain(
+                                chain_id.into(),
+                            )),
+                            FunctionArgument::Key(TemplateKey::Constant(
+                                name,
+                            )) => references
+                                .push(TemplateReference::Constant(name.into())),
+                            FunctionArgument::Key(_)
+                            | FunctionArgument::Body => {}
+                        }
+                    }
+                }
+            }
+        }
+        references
+    }
+
     /// Create a new template **without parsing**. The created template should
     /// *never* be rendered. This is only useful when creating templates purely
     /// for the purpose of being serialized, e.g. when importing an external
@@ -136,6 +310,17 @@ pub enum TemplateChunk {
     Error(TemplateError),
 }
 
+/// Something in the collection that a template depends on. See
+/// [Template::references]
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum TemplateReference {
+    /// A profile field
+    Field(String),
+    Chain(ChainId),
+    /// A collection constant
+    Constant(String),
+}
+
 /// A parsed template key. The variant of this determines how the key will be
 /// resolved into a value.
 ///
@@ -158,9 +343,41 @@ enum TemplateKey<T> {
     /// A value from a predefined chain of another recipe
     #[display("{CHAIN_PREFIX}{_0}")]
     Chain(T),
+    /// A collection constant, which is the same in every profile
+    #[display("{CONST_PREFIX}{_0}")]
+    Constant(T),
     /// A value pulled from the process environment
     #[display("{ENV_PREFIX}{_0}")]
     Environment(T),
+    /// A column from the current row of a data file, e.g. with `--data` in
+    /// the CLI. The value is given as an override
+    #[display("{ROW_PREFIX}{_0}")]
+    Row(T),
+    /// A session variable, captured from an earlier response
+    #[display("{VARS_PREFIX}{_0}")]
+    Variable(T),
+    /// Text contents of a file. The path is a nested template
+    #[display("{FILE_PREFIX}{_0}")]
+    File(T),
+    /// Contents of a file, base64-encoded so binary files are safe to
+    /// include. The path is a nested template
+    #[display("{FILE_BASE64_PREFIX}{_0}")]
+    FileBase64(T),
+    /// A function computed from other keys, e.g. a hash. The arguments are a
+    /// comma-separated list, which has already been validated
+    #[display("{function}({arguments})")]
+    Function {
+        function: TemplateFunction,
+        arguments: T,
+    },
+    /// A random value, e.g. `random_int(1, 10)`. The function holds the
+    /// parsed arguments; the raw list is kept so the key displays exactly as
+    /// written
+    #[display("{function}({arguments})")]
+    Random {
+        function: RandomFunction,
+        arguments: T,
+    },
 }
 
 impl<T> TemplateKey<T> {
@@ -171,6 +388,25 @@ impl<T> TemplateKey<T> {
             Self::Field(value) => TemplateKey::Field(f(value)),
             Self::Chain(value) => TemplateKey::Chain(f(value)),
             Self::Environment(value) => TemplateKey::Environment(f(value)),
+            Self::Row(value) => TemplateKey::Row(f(value)),
+            Self::Constant(value) => TemplateKey::Constant(f(value)),
+            Self::Variable(value) => TemplateKey::Variable(f(value)),
+            Self::File(value) => TemplateKey::File(f(value)),
+            Self::FileBase64(value) => TemplateKey::FileBase64(f(value)),
+            Self::Function {
+                function,
+                arguments,
+            } => TemplateKey::Function {
+                function,
+                arguments: f(arguments),
+            },
+            Self::Random {
+                function,
+                arguments,
+            } => TemplateKey::Random {
+                function,
+                arguments: f(arguments),
+            },
         }
     }
 }
@@ -179,7 +415,10 @@ impl<T> TemplateKey<T> {
 mod tests {
     use super::*;
     use crate::{
-        collection::{Chain, ChainRequestTrigger, ChainSource, RecipeId},
+        collection::{
+            Chain, ChainRequestSection, ChainRequestTrigger, ChainSource,
+            PatchFormat, Profile, RecipeId, SelectorFallback, SelectorMode,
+        },
         config::Config,
         http::{ContentType, RequestRecord},
         test_util::*,
@@ -192,6 +431,7 @@ mod tests {
     use serde_json::json;
     use std::{env, time::Duration};
     use tokio::fs;
+    use uuid::Uuid;
 
     /// Test overriding all key types, as well as missing keys
     #[tokio::test]
@@ -344,6 +584,9 @@ mod tests {
             source: ChainSource::Request {
                 recipe: recipe_id.clone(),
                 trigger: Default::default(),
+                section: Default::default(),
+                timeout: None,
+                max_body_size: None,
             },
             selector: selector,
             content_type: Some(ContentType::Json),
@@ -364,6 +607,153 @@ mod tests {
         );
     }
 
+    /// Load a value from each section of the response
+    #[rstest]
+    #[case::body(ChainRequestSection::Body, Ok("{\"id\": 3}"))]
+    #[case::header(
+        ChainRequestSection::Header("location".into()),
+        Ok("/fishes/3")
+    )]
+    #[case::header_case_insensitive(
+        ChainRequestSection::Header("LoCaTiOn".into()),
+        Ok("/fishes/3")
+    )]
+    #[case::header_templated(
+        ChainRequestSection::Header("{{name}}".into()),
+        Ok("/fishes/3")
+    )]
+    #[case::header_repeated(
+        ChainRequestSection::Header("link".into()),
+        Ok("</1>; rel=\"prev\", </3>; rel=\"next\"")
+    )]
+    #[case::header_missing(
+        ChainRequestSection::Header("etag".into()),
+        Err("Header `etag` not in response")
+    )]
+    #[case::status(ChainRequestSection::Status, Ok("201"))]
+    #[case::cookie(ChainRequestSection::Cookie("token".into()), Ok("abc"))]
+    #[case::cookie_last_wins(
+        ChainRequestSection::Cookie("session".into()),
+        Ok("new")
+    )]
+    #[case::cookie_case_sensitive(
+        ChainRequestSection::Cookie("Token".into()),
+        Err("Cookie `Token` not set by response")
+    )]
+    #[tokio::test]
+    async fn test_chain_request_section(
+        #[case] section: ChainRequestSection,
+        #[case] expected: Result<&str, &str>,
+    ) {
+        let profile = create!(
+            Profile,
+            data: indexmap! {"name".into() => "Location".into()},
+        );
+        let recipe = create!(Recipe);
+        let database = CollectionDatabase::testing();
+        let mut headers = header_map([("location", "/fishes/3")]);
+        for (header, value) in [
+            ("link", "</1>; rel=\"prev\""),
+            ("link", "</3>; rel=\"next\""),
+            ("set-cookie", "token=abc; Path=/; HttpOnly"),
+            ("set-cookie", "session=old"),
+            ("set-cookie", "session=new"),
+        ] {
+            headers.append(header, value.parse().unwrap());
+        }
+        let response = create!(
+            Response,
+            status: reqwest::StatusCode::CREATED,
+            headers: headers,
+            body: "{\"id\": 3}".into(),
+        );
+        database
+            .insert_request(&create!(
+                RequestRecord,
+                request: create!(
+                    Request,
+                    profile_id: Some(profile.id.clone()),
+                    recipe_id: recipe.id.clone(),
+                ).into(),
+                response: response,
+            ))
+            .unwrap();
+        let chain = create!(
+            Chain,
+            source: ChainSource::Request {
+                recipe: recipe.id.clone(),
+                trigger: Default::default(),
+                section,
+                timeout: None,
+                max_body_size: None,
+            },
+        );
+        let context = create!(
+            TemplateContext,
+            collection: create!(
+                Collection,
+                profiles: indexmap! {profile.id.clone() => profile.clone()},
+                recipes: indexmap! {recipe.id.clone() => recipe}.into(),
+                chains: indexmap! {chain.id.clone() => chain},
+            ),
+            selected_profile: Some(profile.id),
+            database: database,
+        );
+
+        let result = render!("{{chains.chain1}}", context);
+        match expected {
+            Ok(expected) => assert_eq!(result.unwrap(), expected),
+            Err(expected) => assert_err!(result, expected),
+        }
+    }
+
+    /// Test loading the most recent response from any recipe
+    #[tokio::test]
+    async fn test_chain_last_response() {
+        let database = CollectionDatabase::testing();
+        let response = create!(
+            Response,
+            headers: header_map([("content-type", "application/json")]),
+            body: json!({"id": 3}).to_string().into(),
+        );
+        database
+            .insert_request(&create!(RequestRecord, response: response))
+            .unwrap();
+        let chain = create!(
+            Chain,
+            source: ChainSource::LastResponse,
+            selector: Some("$.id".parse().unwrap()),
+        );
+        let context = create!(
+            TemplateContext,
+            collection: create!(
+                Collection,
+                chains: indexmap! {chain.id.clone() => chain},
+            ),
+            database: database,
+        );
+
+        assert_eq!(render!("{{chains.chain1}}", context).unwrap(), "3");
+    }
+
+    /// With nothing in history, there's no response to load
+    #[tokio::test]
+    async fn test_chain_last_response_error() {
+        let chain = create!(Chain, source: ChainSource::LastResponse);
+        let context = create!(
+            TemplateContext,
+            collection: create!(
+                Collection,
+                chains: indexmap! {chain.id.clone() => chain},
+            ),
+        );
+
+        assert_err!(
+            render!("{{chains.chain1}}", context),
+            "No response available"
+        );
+    }
+
     /// Test all possible error cases for chained requests. This covers all
     /// chain-specific error variants
     #[rstest]
@@ -377,6 +767,9 @@ mod tests {
             source: ChainSource::Request {
                 recipe: "unknown".into(),
                 trigger: Default::default(),
+                section: Default::default(),
+                timeout: None,
+                max_body_size: None,
             }
         ),
         None,
@@ -391,6 +784,9 @@ mod tests {
             source: ChainSource::Request {
                 recipe: "recipe1".into(),
                 trigger: Default::default(),
+                section: Default::default(),
+                timeout: None,
+                max_body_size: None,
             }
         ),
         Some("recipe1"),
@@ -405,6 +801,9 @@ mod tests {
             source: ChainSource::Request {
                 recipe: "recipe1".into(),
                 trigger: ChainRequestTrigger::Always,
+                section: Default::default(),
+                timeout: None,
+                max_body_size: None,
             }
         ),
         Some("recipe1"),
@@ -419,6 +818,9 @@ mod tests {
             source: ChainSource::Request {
                 recipe: "recipe1".into(),
                 trigger: Default::default(),
+                section: Default::default(),
+                timeout: None,
+                max_body_size: None,
             },
             selector: Some("$.message".parse().unwrap()),
         ),
@@ -437,6 +839,9 @@ mod tests {
             source: ChainSource::Request {
                 recipe: "recipe1".into(),
                 trigger: Default::default(),
+                section: Default::default(),
+                timeout: None,
+                max_body_size: None,
             },
             selector: Some("$.message".parse().unwrap()),
             content_type: Some(ContentType::Json),
@@ -456,6 +861,9 @@ mod tests {
             source: ChainSource::Request {
                 recipe: "recipe1".into(),
                 trigger: Default::default(),
+                section: Default::default(),
+                timeout: None,
+                max_body_size: None,
             },
             selector: Some("$.*".parse().unwrap()),
             content_type: Some(ContentType::Json),
@@ -547,6 +955,9 @@ mod tests {
             source: ChainSource::Request {
                 recipe: recipe.id.clone(),
                 trigger,
+                section: Default::default(),
+                timeout: None,
+                max_body_size: None,
             },
         );
         let http_engine = HttpEngine::new(&Config::default(), database.clone());
@@ -566,6 +977,69 @@ mod tests {
         mock.assert();
     }
 
+    /// Triggered requests fail if they exceed the chain's limits, and the
+    /// error says which chain it was
+    #[rstest]
+    #[case::max_body_size(
+        None,
+        Some(5),
+        "Response body is larger than the limit of 5 bytes"
+    )]
+    #[case::timeout(
+        Some(Duration::from_secs(1)),
+        None,
+        "Request timed out after 1s"
+    )]
+    #[tokio::test]
+    async fn test_triggered_request_limits(
+        #[case] timeout: Option<Duration>,
+        #[case] max_body_size: Option<u64>,
+        #[case] expected_error: &str,
+    ) {
+        let database = CollectionDatabase::testing();
+
+        let mut server = mockito::Server::new_async().await;
+        let _mock = server
+            .mock("GET", "/get")
+            .with_body("too much data")
+            .create_async()
+            .await;
+        // A server that accepts connections but never responds
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = if timeout.is_some() {
+            format!("http://{}/get", listener.local_addr().unwrap())
+        } else {
+            format!("{}/get", server.url())
+        };
+
+        let recipe = create!(Recipe, url: url.as_str().into());
+        let chain = create!(
+            Chain,
+            source: ChainSource::Request {
+                recipe: recipe.id.clone(),
+                trigger: ChainRequestTrigger::Always,
+                section: Default::default(),
+                timeout,
+                max_body_size,
+            },
+        );
+        let http_engine = HttpEngine::new(&Config::default(), database.clone());
+        let context = create!(
+            TemplateContext,
+            collection: create!(
+                Collection,
+                recipes: indexmap! {recipe.id.clone() => recipe}.into(),
+                chains: indexmap! {chain.id.clone() => chain},
+            ),
+            http_engine: Some(http_engine),
+            database: database,
+        );
+
+        let error = render!("{{chains.chain1}}", context).unwrap_err();
+        assert!(error.to_string().contains("`chain1`"), "{error}");
+        assert_err!(Err::<(), _>(error), expected_error);
+    }
+
     /// Test success with chained command
     #[tokio::test]
     async fn test_chain_command() {
@@ -628,6 +1102,147 @@ mod tests {
         assert_eq!(render!("{{chains.chain1}}", context).unwrap(), "hello!");
     }
 
+    /// Relative chain paths are resolved against the root directory
+    #[tokio::test]
+    async fn test_chain_file_relative() {
+        let directory = env::temp_dir().join(Uuid::new_v4().to_string());
+        fs::create_dir(&directory).await.unwrap();
+        fs::write(directory.join("stuff.txt"), "hello!")
+            .await
+            .unwrap();
+
+        let chain = create!(
+            Chain, source: ChainSource::File { path: "stuff.txt".into() },
+        );
+        let context = create!(
+            TemplateContext,
+            collection: create!(
+                Collection,
+                chains: indexmap! {chain.id.clone() => chain},
+            ),
+            path_root: PathRoot::Directory(directory),
+        );
+
+        assert_eq!(render!("{{chains.chain1}}", context).unwrap(), "hello!");
+    }
+
+    #[rstest]
+    #[case::relative(Some("/root"), "a/b.txt", "/root/a/b.txt")]
+    #[case::absolute(Some("/root"), "/a/b.txt", "/a/b.txt")]
+    #[case::current_directory(None, "a/b.txt", "a/b.txt")]
+    #[case::home(Some("/root"), "~/b.txt", "{home}/b.txt")]
+    #[case::tilde_in_name(Some("/root"), "~b.txt", "/root/~b.txt")]
+    fn test_resolve_path(
+        #[case] root_directory: Option<&str>,
+        #[case] path: &str,
+        #[case] expected: &str,
+    ) {
+        let context = create!(
+            TemplateContext,
+            path_root: root_directory
+                .map(|directory| PathRoot::Directory(directory.into()))
+                .unwrap_or_default(),
+        );
+        let home = dirs::home_dir().unwrap();
+        let expected = expected.replace("{home}", home.to_str().unwrap());
+        assert_eq!(
+            context.resolve_path(path).unwrap(),
+            PathBuf::from(expected)
+        );
+    }
+
+    /// Relative paths are an error when there's nothing to resolve them
+    /// against, but absolute paths are fine
+    #[test]
+    fn test_resolve_path_disallowed() {
+        let context = create!(TemplateContext, path_root: PathRoot::Disallowed);
+        assert_err!(
+            context.resolve_path("a/b.txt"),
+            "Relative path `a/b.txt` can't be resolved"
+        );
+        assert_eq!(
+            context.resolve_path("/a/b.txt").unwrap(),
+            PathBuf::from("/a/b.txt")
+        );
+    }
+
+    /// Test resolving a profile selector by ID, name, or a template that
+    /// renders to either
+    #[rstest]
+    #[case::id("dev", "dev")]
+    #[case::name("Production", "prod")]
+    #[case::id_beats_name("staging", "staging")]
+    #[case::template_id("{{env.TEST_PROFILE_ENVIRONMENT}}", "prod")]
+    #[case::template_name("Dev{{env.TEST_PROFILE_SUFFIX}}", "dev")]
+    #[tokio::test]
+    async fn test_resolve_profile(
+        #[case] profile: &str,
+        #[case] expected: &str,
+    ) {
+        env::set_var("TEST_PROFILE_ENVIRONMENT", "prod");
+        env::set_var("TEST_PROFILE_SUFFIX", "elopment");
+        let context = create!(
+            TemplateContext,
+            collection: create!(Collection, profiles: profiles()),
+        );
+        assert_eq!(
+            context.resolve_profile(profile).await.unwrap(),
+            ProfileId::from(expected)
+        );
+    }
+
+    /// Test errors when resolving a profile selector
+    #[rstest]
+    #[case::parse("{{env.", "Error parsing profile `{{env.`")]
+    #[case::render(
+        "{{chains.unknown}}",
+        "Error rendering profile `{{chains.unknown}}`"
+    )]
+    #[case::empty(
+        "{{env.TEST_PROFILE_EMPTY}}",
+        "Profile `{{env.TEST_PROFILE_EMPTY}}` rendered to an empty string"
+    )]
+    #[case::unknown(
+        "ci",
+        "No profile with ID or name `ci`; options are: dev, prod, staging, \
+        other"
+    )]
+    #[case::duplicate_name(
+        "Shared",
+        "Multiple profiles are named `Shared`; use one of their IDs instead: \
+        staging, other"
+    )]
+    #[tokio::test]
+    async fn test_resolve_profile_error(
+        #[case] profile: &str,
+        #[case] expected: &str,
+    ) {
+        env::set_var("TEST_PROFILE_EMPTY", "");
+        let context = create!(
+            TemplateContext,
+            collection: create!(Collection, profiles: profiles()),
+        );
+        assert_err!(context.resolve_profile(profile).await, expected);
+    }
+
+    /// Profiles for [test_resolve_profile] and [test_resolve_profile_error]
+    fn profiles() -> IndexMap<ProfileId, Profile> {
+        let profile = |id: &str, name: &str| {
+            let id = ProfileId::from(id);
+            let profile =
+                create!(Profile, id: id.clone(), name: Some(name.into()));
+            (id, profile)
+        };
+        [
+            profile("dev", "Development"),
+            profile("prod", "Production"),
+            profile("staging", "Shared"),
+            profile("other", "Shared"),
+        ]
+        .into_iter()
+        .collect()
+    }
+
     /// Test failure with chained file
     #[tokio::test]
     async fn test_chain_file_error() {
@@ -648,6 +1263,64 @@ mod tests {
         );
     }
 
+    /// Test success with chained patch, in both formats
+    #[rstest]
+    #[case::merge_patch(
+        PatchFormat::MergePatch,
+        r#"{"address":{"zip":null},"name":"Bob"}"#
+    )]
+    #[case::json_patch(
+        PatchFormat::JsonPatch,
+        r#"[{"op":"remove","path":"/address/zip"},{"op":"replace","path":"/name","value":"Bob"}]"#
+    )]
+    #[tokio::test]
+    async fn test_chain_patch(
+        #[case] format: PatchFormat,
+        #[case] expected: &str,
+    ) {
+        let source = ChainSource::Patch {
+            base: r#"{"id": 3, "name": "Alice", "address": {"zip": "02114"} }"#
+                .into(),
+            // Unchanged fields shouldn't end up in the patch
+            changes: r#"{"id": 3, "name": "Bob", "address": {"zip": null} }"#
+                .into(),
+            format,
+        };
+        let chain = create!(Chain, source: source);
+        let context = create!(
+            TemplateContext,
+            collection: create!(
+                Collection,
+                chains: indexmap! {chain.id.clone() => chain},
+            ),
+        );
+
+        assert_eq!(render!("{{chains.chain1}}", context).unwrap(), expected);
+    }
+
+    /// Test failure with chained patch
+    #[tokio::test]
+    async fn test_chain_patch_error() {
+        let source = ChainSource::Patch {
+            base: "not json".into(),
+            changes: "{}".into(),
+            format: PatchFormat::MergePatch,
+        };
+        let chain = create!(Chain, source: source);
+        let context = create!(
+            TemplateContext,
+            collection: create!(
+                Collection,
+                chains: indexmap! {chain.id.clone() => chain},
+            ),
+        );
+
+        assert_err!(
+            render!("{{chains.chain1}}", context),
+            "Parsing field `base` as JSON"
+        );
+    }
+
     #[tokio::test]
     async fn test_chain_prompt() {
         let chain = create!(
@@ -655,6 +1328,7 @@ mod tests {
             source: ChainSource::Prompt {
                 message: Some("password".into()),
                 default: Some("default".into()),
+                pattern: None,
             },
         );
 
@@ -674,6 +1348,44 @@ mod tests {
         assert_eq!(render!("{{chains.chain1}}", context).unwrap(), "default");
     }
 
+    /// Values that don't match the pattern are rejected, and the user is asked
+    /// again. The default has to match too.
+    #[rstest]
+    #[case::valid(&["123"], None, Ok("123"))]
+    #[case::retry(&["abc", "12a", "45"], None, Ok("45"))]
+    #[case::full_match(&["abc123"], None, Err("No response from prompt"))]
+    #[case::default(&[], Some("7"), Ok("7"))]
+    #[case::invalid_default(&[], Some("x"), Err("No response from prompt"))]
+    #[tokio::test]
+    async fn test_chain_prompt_pattern(
+        #[case] attempts: &[&str],
+        #[case] default: Option<&str>,
+        #[case] expected: Result<&str, &str>,
+    ) {
+        let chain = create!(
+            Chain,
+            source: ChainSource::Prompt {
+                message: None,
+                default: default.map(Template::from),
+                pattern: Some(r"\d+".to_owned().try_into().unwrap()),
+            },
+        );
+        let context = create!(
+            TemplateContext,
+            collection: create!(
+                Collection,
+                chains: indexmap! {chain.id.clone() => chain},
+            ),
+            prompter: Box::new(TestPrompter::with_attempts(attempts.to_vec())),
+        );
+
+        let result = render!("{{chains.chain1}}", context);
+        match expected {
+            Ok(expected) => assert_eq!(result.unwrap(), expected),
+            Err(expected) => assert_err!(result, expected),
+        }
+    }
+
     /// Prompting gone wrong
     #[tokio::test]
     async fn test_chain_prompt_error() {
@@ -682,6 +1394,7 @@ mod tests {
             source: ChainSource::Prompt {
                 message: Some("password".into()),
                 default: None,
+                pattern: None,
             },
         );
         let context = create!(
@@ -700,6 +1413,86 @@ mod tests {
         );
     }
 
+    /// With prompt mode, the user picks one of multiple selector matches, and
+    /// their pick is remembered for later renders
+    #[rstest]
+    #[case::pick(&["b"], None, Ok("b"))]
+    #[case::not_a_match(&["d"], None, Err("Selector matched 3 values"))]
+    #[case::no_pick(&[], None, Err("Selector matched 3 values"))]
+    #[case::fallback_first(&[], Some(SelectorFallback::First), Ok("a"))]
+    #[case::fallback_last(&[], Some(SelectorFallback::Last), Ok("c"))]
+    #[tokio::test]
+    async fn test_chain_selector_prompt(
+        #[case] attempts: &[&str],
+        #[case] fallback: Option<SelectorFallback>,
+        #[case] expected: Result<&str, &str>,
+    ) {
+        let command =
+            vec!["echo".into(), r#"{"items": ["a", "b", "c"]}"#.into()];
+        let chain = create!(
+            Chain,
+            source: ChainSource::Command { command },
+            selector: Some("$.items[*]".parse().unwrap()),
+            content_type: Some(ContentType::Json),
+            selector_mode: SelectorMode::Prompt,
+            selector_fallback: fallback,
+        );
+        let mut context = create!(
+            TemplateContext,
+            collection: create!(
+                Collection,
+                chains: indexmap! {chain.id.clone() => chain},
+            ),
+            prompter: Box::new(TestPrompter::with_attempts(attempts.to_vec())),
+        );
+
+        let result = render!("{{chains.chain1}}", context);
+        match expected {
+            Ok(expected) => assert_eq!(result.unwrap(), expected),
+            Err(expected) => assert_err!(result, expected),
+        }
+
+        // A pick is reused without asking again; a fallback isn't remembered
+        context.prompter = Box::new(TestPrompter::new::<String>(None));
+        let result = render!("{{chains.chain1}}", context);
+        match (attempts, expected) {
+            (["b"], _) => assert_eq!(result.unwrap(), "b"),
+            (_, Ok(expected)) => assert_eq!(result.unwrap(), expected),
+            (_, Err(expected)) => assert_err!(result, expected),
+        }
+    }
+
+    /// Prompt mode doesn't prompt if there's only one match, or if the
+    /// selector has transforms
+    #[rstest]
+    #[case::one_match("$.items[0]", "a")]
+    #[case::transform("$.items[*] | last", "c")]
+    #[tokio::test]
+    async fn test_chain_selector_prompt_single(
+        #[case] selector: &str,
+        #[case] expected: &str,
+    ) {
+        let command =
+            vec!["echo".into(), r#"{"items": ["a", "b", "c"]}"#.into()];
+        let chain = create!(
+            Chain,
+            source: ChainSource::Command { command },
+            selector: Some(selector.parse().unwrap()),
+            content_type: Some(ContentType::Json),
+            selector_mode: SelectorMode::Prompt,
+        );
+        let context = create!(
+            TemplateContext,
+            collection: create!(
+                Collection,
+                chains: indexmap! {chain.id.clone() => chain},
+            ),
+            // Prompter gives no response
+            prompter: Box::new(TestPrompter::new::<String>(None)),
+        );
+        assert_eq!(render!("{{chains.chain1}}", context).unwrap(), expected);
+    }
+
     /// Values marked sensitive should have that flag set in the rendered output
     #[tokio::test]
     async fn test_chain_sensitive() {
@@ -708,6 +1501,7 @@ mod tests {
             source: ChainSource::Prompt {
                 message: Some("password".into()),
                 default: None,
+                pattern: None,
             },
             sensitive: true,
         );
@@ -772,6 +1566,75 @@ mod tests {
         );
     }
 
+    /// Within a request build, a chain that's used multiple times is only
+    /// resolved once
+    #[tokio::test]
+    async fn test_chain_shared() {
+        let path =
+            env::temp_dir().join(format!("slumber-{}.txt", Uuid::new_v4()));
+        let path = path.to_str().unwrap();
+        let script = format!("echo -n x >> '{path}'; cat '{path}'");
+        let chain = create!(
+            Chain,
+            id: "count".into(),
+            source: ChainSource::Command {
+                command: vec!["sh".into(), "-c".into(), script.as_str().into()],
+            },
+        );
+        let context = create!(
+            TemplateContext,
+            collection: create!(
+                Collection,
+                chains: indexmap! {chain.id.clone() => chain},
+            ),
+        );
+
+        let template = Template::from("{{chains.count}} {{chains.count}}");
+        let rendered = with_chain_cache(template.render_stitched(&context))
+            .await
+            .unwrap();
+        assert_eq!(rendered, "x x");
+        // Each build gets its own values
+        let template = Template::from("{{chains.count}}");
+        let rendered = with_chain_cache(template.render_stitched(&context))
+            .await
+            .unwrap();
+        assert_eq!(rendered, "xx");
+    }
+
+    /// Chains that depend on each other fail, rather than waiting forever for
+    /// each other
+    #[tokio::test]
+    async fn test_chain_cycle() {
+        let chain = |id: &str, other: &str| {
+            let command = vec![
+                "echo".into(),
+                "-n".into(),
+                format!("{{{{chains.{other}}}}}").as_str().into(),
+            ];
+            create!(
+                Chain,
+                id: id.into(),
+                source: ChainSource::Command { command },
+            )
+        };
+        let context = create!(
+            TemplateContext,
+            collection: create!(
+                Collection,
+                chains: indexmap! {
+                    "a".into() => chain("a", "b"),
+                    "b".into() => chain("b", "a"),
+                },
+            ),
+        );
+        let template = Template::from("{{chains.a}} {{chains.b}}");
+        assert_err!(
+            with_chain_cache(template.render_stitched(&context)).await,
+            "Chains depend on each other"
+        );
+    }
+
     /// Test when an error occurs in a nested chain
     #[tokio::test]
     async fn test_chain_nested_error() {
@@ -816,6 +1679,21 @@ mod tests {
         assert_eq!(render!("{{env.TEST}}", context).unwrap(), "test!");
     }
 
+    /// Row values come from overrides, so without one there's nothing to
+    /// render
+    #[tokio::test]
+    async fn test_row() {
+        let context = create!(
+            TemplateContext,
+            overrides: indexmap! {"row.id".into() => "3".into()},
+        );
+        assert_eq!(render!("{{row.id}}", context).unwrap(), "3");
+        assert_err!(
+            render!("{{row.name}}", context),
+            "No column `name` in the data row"
+        );
+    }
+
     #[tokio::test]
     async fn test_environment_error() {
         let context = create!(TemplateContext);
@@ -825,6 +1703,283 @@ mod tests {
         );
     }
 
+    /// Test including files inline, with a templated path
+    #[rstest]
+    #[case::text("{{file.{{dir}}/inline.txt}}", "hello!")]
+    #[case::base64("{{file_base64.{{dir}}/inline.bin}}", "/wBo")]
+    #[tokio::test]
+    async fn test_file(#[case] template: &str, #[case] expected: &str) {
+        let temp_dir = env::temp_dir();
+        fs::write(temp_dir.join("inline.txt"), "hello!")
+            .await
+            .unwrap();
+        fs::write(temp_dir.join("inline.bin"), [0xff, 0x00, 0x68])
+            .await
+            .unwrap();
+        let profile_data = indexmap! {
+            "dir".into() => temp_dir.to_str().unwrap().into(),
+        };
+        let profile = create!(Profile, data: profile_data);
+        let profile_id = profile.id.clone();
+        let context = create!(
+            TemplateContext,
+            collection: create!(
+                Collection,
+                profiles: indexmap! {profile_id.clone() => profile},
+            ),
+            selected_profile: Some(profile_id),
+        );
+
+        assert_eq!(render!(template, context).unwrap(), expected);
+    }
+
+    /// Test errors from including files inline
+    #[rstest]
+    #[case::missing(
+        "{{file.not-real}}",
+        "Loading file `not-real`: Reading file `not-real`"
+    )]
+    #[case::path("{{file.{{unknown}}}}", "Rendering path: Unknown field")]
+    #[case::invalid_utf8(
+        "{{file.{{dir}}/inline_error.bin}}",
+        "File is not valid UTF-8; use `{{file_base64.<path>}}`"
+    )]
+    #[tokio::test]
+    async fn test_file_error(
+        #[case] template: &str,
+        #[case] expected_error: &str,
+    ) {
+        let temp_dir = env::temp_dir();
+        fs::write(temp_dir.join("inline_error.bin"), [0xff])
+            .await
+            .unwrap();
+        let profile_data = indexmap! {
+            "dir".into() => temp_dir.to_str().unwrap().into(),
+        };
+        let profile = create!(Profile, data: profile_data);
+        let profile_id = profile.id.clone();
+        let context = create!(
+            TemplateContext,
+            collection: create!(
+                Collection,
+                profiles: indexmap! {profile_id.clone() => profile},
+            ),
+            selected_profile: Some(profile_id),
+        );
+
+        assert_err!(render!(template, context), expected_error);
+    }
+
+    /// Test calling functions, with each kind of argument
+    #[rstest]
+    #[case::field("{{md5(message)}}", "5d41402abc4b2a76b9719d911017c592")]
+    #[case::override_(
+        "{{md5(overridden)}}",
+        "098f6bcd4621d373cade4e832627b4f6"
+    )]
+    #[case::hmac(
+        "{{hmac_sha256_base64(secret, message)}}",
+        "kwezuRXvtRcf8U2MtV+8x5jGwO8UVtZt7RpqpyOli3s="
+    )]
+    #[case::body(
+        "{{sha256(body)}}",
+        "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+    )]
+    #[tokio::test]
+    async fn test_function(#[case] template: &str, #[case] expected: &str) {
+        let profile_data = indexmap! {
+            "message".into() => "hello".into(),
+            "secret".into() => "{{chains.secret}}".into(),
+        };
+        let source = ChainSource::Command {
+            command: vec!["echo".into(), "-n".into(), "key".into()],
+        };
+        let chain = create!(Chain, id: "secret".into(), source: source);
+        let profile = create!(Profile, data: profile_data);
+        let profile_id = profile.id.clone();
+        let context = create!(
+            TemplateContext,
+            collection: create!(
+                Collection,
+                profiles: indexmap!{profile_id.clone() => profile},
+                chains: indexmap! {chain.id.clone() => chain},
+            ),
+            selected_profile: Some(profile_id),
+            overrides: indexmap! {"overridden".into() => "test".into()},
+        );
+
+        // An empty body is hashed as empty bytes
+        let rendered =
+            with_request_body(None, async { render!(template, context) }).await;
+        assert_eq!(rendered.unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case::argument(
+        "{{md5(env.UNKNOWN)}}",
+        "Calling `md5`: Rendering argument `env.UNKNOWN`: Accessing \
+        environment variable `UNKNOWN`"
+    )]
+    #[case::body_unavailable(
+        "{{sha256_base64(body)}}",
+        "Calling `sha256_base64`: Request body is not available"
+    )]
+    #[tokio::test]
+    async fn test_function_error(
+        #[case] template: &str,
+        #[case] expected_error: &str,
+    ) {
+        let context = create!(TemplateContext);
+        assert_err!(render!(template, context), expected_error);
+    }
+
+    /// Session variables are shared between contexts, and can be overridden
+    #[rstest]
+    #[case::set("{{vars.token}}", Ok("abc"))]
+    #[case::function_argument(
+        "{{md5(vars.token)}}",
+        Ok("900150983cd24fb0d6963f7d28e17f72")
+    )]
+    #[case::overridden("{{vars.user}}", Ok("override"))]
+    #[case::unknown("{{vars.unknown}}", Err("Variable `unknown` is not set"))]
+    #[tokio::test]
+    async fn test_variable(
+        #[case] template: &str,
+        #[case] expected: Result<&str, &str>,
+    ) {
+        let variables = SessionVariables::default();
+        let context = create!(
+            TemplateContext,
+            variables: variables.clone(),
+            overrides: indexmap! {"vars.user".into() => "override".into()},
+        );
+        variables.set("token".into(), "abc".into());
+        variables.set("user".into(), "captured".into());
+
+        let result = render!(template, context);
+        match expected {
+            Ok(expected) => assert_eq!(result.unwrap(), expected),
+            Err(expected) => assert_err!(result, expected),
+        }
+    }
+
+    /// Constants don't depend on the profile, and are separate from profile
+    /// fields with the same name
+    #[rstest]
+    #[case::constant("{{const.version}}", Ok("v2"))]
+    #[case::field("{{version}}", Ok("v1"))]
+    #[case::nested("{{const.path}}", Ok("/api/v2"))]
+    #[case::function_argument(
+        "{{md5(const.version)}}",
+        Ok("1b267619c4812cc46ee281747884ca50")
+    )]
+    #[case::overridden("{{const.overridden}}", Ok("override"))]
+    #[case::unknown("{{const.unknown}}", Err("Unknown constant `unknown`"))]
+    #[case::nested_error(
+        "{{const.broken}}",
+        Err(
+            "Rendering nested template for constant `broken`: \
+        Unknown constant `unknown`"
+        )
+    )]
+    #[tokio::test]
+    async fn test_constant(
+        #[case] template: &str,
+        #[case] expected: Result<&str, &str>,
+    ) {
+        let profile = create!(
+            Profile,
+            data: indexmap! {"version".into() => "v1".into()},
+        );
+        let profile_id = profile.id.clone();
+        let collection = create!(
+            Collection,
+            profiles: indexmap! {profile_id.clone() => profile},
+            constants: indexmap! {
+                "version".into() => "v2".into(),
+                "path".into() => "/api/{{const.version}}".into(),
+                "overridden".into() => "constant".into(),
+                "broken".into() => "{{const.unknown}}".into(),
+            },
+        );
+        let overrides =
+            indexmap! {"const.overridden".into() => "override".into()};
+        let context = create!(
+            TemplateContext,
+            collection: collection.clone(),
+            selected_profile: Some(profile_id),
+            overrides: overrides.clone(),
+        );
+        let result = render!(template, context);
+        match expected {
+            Ok(expected) => assert_eq!(result.unwrap(), expected),
+            Err(expected) => assert_err!(result, expected),
+        }
+
+        // Constants are available without a profile
+        if template.contains("const.") {
+            let context = create!(TemplateContext, collection: collection, overrides: overrides);
+            let result = render!(template, context);
+            match expected {
+                Ok(expected) => assert_eq!(result.unwrap(), expected),
+                Err(expected) => assert_err!(result, expected),
+            }
+        }
+    }
+
+    /// Each random key gets its own value, even in the same template. With
+    /// a seed, the values are the same on every render
+    #[tokio::test]
+    async fn test_random() {
+        let template: Template =
+            "{{random_string(32)}} {{random_string(32)}}".into();
+        let context = create!(TemplateContext);
+        let rendered = template.render_stitched(&context).await.unwrap();
+        let (first, second) = rendered.split_once(' ').unwrap();
+        assert_eq!(first.len(), 32);
+        assert_ne!(first, second);
+
+        let seeded =
+            || create!(TemplateContext, random: RandomSource::seeded(1));
+        let rendered = template.render_stitched(&seeded()).await.unwrap();
+        assert_eq!(
+            template.render_stitched(&seeded()).await.unwrap(),
+            rendered
+        );
+
+        // Random keys can be overridden like any other
+        let context = create!(
+            TemplateContext,
+            overrides: indexmap! {
+                "random_int(1, 10)".into() => "11".into(),
+            },
+        );
+        assert_eq!(render!("{{random_int(1, 10)}}", context).unwrap(), "11");
+    }
+
+    /// Test finding references in a template, including nested file paths
+    /// and function arguments
+    #[test]
+    fn test_references() {
+        let template: Template = "{{field1}} {{chains.chain1}} {{env.ENV}} \
+            {{file.{{field2}}/{{chains.chain2}}}} \
+            {{hmac_sha256(chains.chain3, body)}} {{const.const1}} \
+            {{md5(const.const2)}}"
+            .into();
+        assert_eq!(
+            template.references(),
+            vec![
+                TemplateReference::Field("field1".into()),
+                TemplateReference::Chain("chain1".into()),
+                TemplateReference::Field("field2".into()),
+                TemplateReference::Chain("chain2".into()),
+                TemplateReference::Chain("chain3".into()),
+                TemplateReference::Constant("const1".into()),
+                TemplateReference::Constant("const2".into()),
+            ]
+        );
+    }
+
     /// Test rendering into individual chunks with complex unicode
     #[tokio::test]
     async fn test_render_chunks() {
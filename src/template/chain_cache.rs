@@ -0,0 +1,164 @@
+//! Sharing resolved chain values between every template in a request, so
+//! each chain is only resolved once per build
+
+use crate::{
+    collection::ChainId,
+    template::{function::request_body, ChainError, TemplateError},
+};
+use bytes::Bytes;
+use indexmap::IndexSet;
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, Mutex},
+};
+use tokio::sync::OnceCell;
+
+tokio::task_local! {
+    /// Chains resolved during the current request build. This is a
+    /// task-local rather than part of the template context because the CLI
+    /// reuses one context for multiple builds, and values shouldn't leak
+    /// between them. Triggered requests are built within their parent's
+    /// scope, so they share its cache.
+    static CACHE: Arc<ChainCache>;
+    /// The chain whose templates are being rendered, if any. Any chain
+    /// rendered in this scope is a dependency of it.
+    static PARENT: ChainId;
+}
+
+/// The value of a resolved chain, and whether it's sensitive
+pub(super) type ChainValue = (String, bool);
+
+/// Chain values for a single request build. Every template in a request is
+/// rendered concurrently, so two templates that use the same chain would
+/// otherwise resolve it twice, e.g. sending a triggered request or showing a
+/// prompt twice. Instead, the first one resolves the chain and any others
+/// wait for it.
+#[derive(Debug, Default)]
+struct ChainCache {
+    values: Mutex<HashMap<CacheKey, Arc<OnceCell<ChainValue>>>>,
+    /// Chains that each chain needed while it was resolved. With concurrent
+    /// resolution, a cycle would be a deadlock rather than hitting the
+    /// recursion limit, so cycles are caught before waiting on anything.
+    dependencies: Mutex<HashMap<ChainId, IndexSet<ChainId>>>,
+}
+
+/// A chain can use the body of the request being built (e.g. to sign it), so
+/// its value is only shared between templates that see the same body
+type CacheKey = (ChainId, Option<Option<Bytes>>);
+
+/// Run a future (typically a request build) with a fresh chain cache. If
+/// there's already a cache in scope (e.g. for a triggered request), that one
+/// is used instead.
+pub async fn with_chain_cache<F: Future>(future: F) -> F::Output {
+    if CACHE.try_with(|_| ()).is_ok() {
+        future.await
+    } else {
+        CACHE.scope(Arc::default(), future).await
+    }
+}
+
+/// Resolve a chain, or wait for a value that's already being resolved. Errors
+/// aren't cached, so each template that uses a failing chain tries it again.
+/// Outside of [with_chain_cache], this just resolves the chain.
+pub(super) async fn resolve_chain<F>(
+    chain_id: &ChainId,
+    resolve: F,
+) -> Result<ChainValue, TemplateError>
+where
+    F: Future<Output = Result<ChainValue, TemplateError>>,
+{
+    let Ok(cache) = CACHE.try_with(Arc::clone) else {
+        return resolve.await;
+    };
+    if let Ok(parent) = PARENT.try_with(Clone::clone) {
+        if let Some(cycle) = cache.add_dependency(parent, chain_id.clone()) {
+            return Err(TemplateError::Chain {
+                chain_id: chain_id.clone(),
+                error: ChainError::Cycle(cycle),
+            });
+        }
+    }
+
+    let cell = Arc::clone(
+        cache
+            .values
+            .lock()
+            .expect("Lock poisoned")
+            .entry((chain_id.clone(), request_body()))
+            .or_default(),
+    );
+    cell.get_or_try_init(|| PARENT.scope(chain_id.clone(), resolve))
+        .await
+        .cloned()
+}
+
+impl ChainCache {
+    /// Record that `parent` needs `child`. If that closes a loop, return the
+    /// loop instead, starting and ending with `parent`.
+    fn add_dependency(
+        &self,
+        parent: ChainId,
+        child: ChainId,
+    ) -> Option<Vec<ChainId>> {
+        let mut dependencies = self.dependencies.lock().expect("Lock poisoned");
+        if let Some(mut path) = find_path(&dependencies, &child, &parent) {
+            path.insert(0, parent);
+            return Some(path);
+        }
+        dependencies.entry(parent).or_default().insert(child);
+        None
+    }
+}
+
+/// Find a path of dependencies from one chain to another, including both
+/// ends. A chain always has a path to itself.
+fn find_path(
+    dependencies: &HashMap<ChainId, IndexSet<ChainId>>,
+    from: &ChainId,
+    to: &ChainId,
+) -> Option<Vec<ChainId>> {
+    if from == to {
+        return Some(vec![from.clone()]);
+    }
+    dependencies.get(from)?.iter().find_map(|next| {
+        let mut path = find_path(dependencies, next, to)?;
+        path.insert(0, from.clone());
+        Some(path)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::independent(&[("a", "b"), ("a", "c"), ("b", "c")], None)]
+    #[case::self_reference(&[("a", "a")], Some(&["a", "a"][..]))]
+    #[case::direct(&[("a", "b"), ("b", "a")], Some(&["b", "a", "b"][..]))]
+    #[case::indirect(
+        &[("a", "b"), ("b", "c"), ("c", "a")],
+        Some(&["c", "a", "b", "c"][..])
+    )]
+    fn test_add_dependency(
+        #[case] edges: &[(&str, &str)],
+        #[case] expected: Option<&[&str]>,
+    ) {
+        let cache = ChainCache::default();
+        let (last, rest) = edges.split_last().unwrap();
+        for (parent, child) in rest {
+            assert_eq!(
+                cache.add_dependency((*parent).into(), (*child).into()),
+                None
+            );
+        }
+        let expected = expected.map(|ids| {
+            ids.iter().copied().map(ChainId::from).collect::<Vec<_>>()
+        });
+        assert_eq!(
+            cache.add_dependency(last.0.into(), last.1.into()),
+            expected
+        );
+    }
+}
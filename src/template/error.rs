@@ -54,6 +54,18 @@ pub enum TemplateError {
         error: Box<Self>,
     },
 
+    /// A `const.` key for a constant that isn't in the collection
+    #[error("Unknown constant `{name}`")]
+    ConstantUnknown { name: String },
+
+    /// An bubbled-up error from rendering a collection constant
+    #[error("Rendering nested template for constant `{name}`")]
+    ConstantNested {
+        name: String,
+        #[source]
+        error: Box<Self>,
+    },
+
     /// Too many templates!
     #[error(
         "Template recursion limit reached; cannot render more than \
@@ -75,6 +87,100 @@ pub enum TemplateError {
         #[source]
         error: VarError,
     },
+
+    /// A `row.` key without a matching column, or rendered without a data
+    /// row at all
+    #[error(
+        "No column `{column}` in the data row. `row.` keys are only available \
+        when running with a data file"
+    )]
+    RowColumnUnknown { column: String },
+
+    /// A `vars.` key for a variable that hasn't been captured yet
+    #[error(
+        "Variable `{variable}` is not set. Variables are set by a recipe's \
+        `capture` field when it gets a response"
+    )]
+    VariableUnknown { variable: String },
+
+    /// Error loading a file included inline. The path is the unrendered
+    /// template from the key
+    #[error("Loading file `{path}`")]
+    File {
+        path: String,
+        #[source]
+        error: FileError,
+    },
+
+    /// Error computing a function, e.g. `{{sha256(body)}}`
+    #[error("Calling `{function}`")]
+    Function {
+        function: String,
+        #[source]
+        error: FunctionError,
+    },
+}
+
+/// An error sub-type for template functions
+#[derive(Debug, Error)]
+#[cfg_attr(test, derive(PartialEq))]
+pub enum FunctionError {
+    /// A bubbled-up error from rendering one of the arguments
+    #[error("Rendering argument `{argument}`")]
+    Argument {
+        argument: String,
+        #[source]
+        error: Box<TemplateError>,
+    },
+
+    /// `body` was passed outside of request headers, or with a streamed body
+    #[error(
+        "Request body is not available; `body` can only be used in request \
+        headers, and not with `body_command`"
+    )]
+    BodyUnavailable,
+}
+
+/// An error sub-type for files included inline with `{{file.<path>}}`
+#[derive(Debug, Error)]
+pub enum FileError {
+    /// A bubbled-up error from rendering the path template
+    #[error("Rendering path")]
+    Path(#[source] Box<TemplateError>),
+
+    /// Error opening/reading the file
+    #[error("Reading file `{path}`")]
+    Read {
+        path: PathBuf,
+        #[source]
+        error: io::Error,
+    },
+
+    #[error(transparent)]
+    RelativePath(#[from] RelativePathError),
+
+    /// File contents weren't text
+    #[error(
+        "File is not valid UTF-8; use `{{{{file_base64.<path>}}}}` for \
+        binary files"
+    )]
+    InvalidUtf8 {
+        #[source]
+        error: FromUtf8Error,
+    },
+}
+
+/// A relative file path in a collection that wasn't loaded from the file
+/// system (e.g. read from stdin), so there's no directory to resolve it
+/// against
+#[derive(Debug, Error)]
+#[error(
+    "Relative path `{}` can't be resolved, because the collection wasn't \
+    loaded from a file. Use an absolute path instead",
+    path.display()
+)]
+pub struct RelativePathError {
+    pub path: PathBuf,
 }
 
 /// An error sub-type, for any error that occurs while resolving a chained
@@ -102,8 +208,8 @@ pub enum ChainError {
         error: FromUtf8Error,
     },
 
-    /// The chain ID is valid, but the corresponding recipe has no successful
-    /// response
+    /// The chain ID is valid, but there's no successful response to load from,
+    /// either for the corresponding recipe or for the profile as a whole
     #[error("No response available")]
     NoResponse,
 
@@ -155,11 +261,46 @@ pub enum ChainError {
         error: io::Error,
     },
 
+    #[error(transparent)]
+    RelativePath(#[from] RelativePathError),
+
+    /// Patch source input wasn't valid JSON
+    #[error("Parsing field `{field}` as JSON")]
+    PatchParse {
+        field: String,
+        #[source]
+        error: serde_json::Error,
+    },
+
+    /// Chained response doesn't have the requested header
+    #[error("Header `{header}` not in response")]
+    MissingHeader { header: String },
+
+    /// Chained response doesn't set the requested cookie
+    #[error("Cookie `{cookie}` not set by response")]
+    MissingCookie { cookie: String },
+
     /// Never got a response from the prompt channel. Do *not* store the
     /// `RecvError` here, because it provides useless extra output to the user.
     #[error("No response from prompt")]
     PromptNoResponse,
 
+    /// Selector matched multiple values and the user didn't pick one, with
+    /// no fallback configured
+    #[error(
+        "Selector matched {count} values, and none was picked. Set \
+        `selector_fallback` to pick one automatically"
+    )]
+    SelectorNoChoice { count: usize },
+
+    /// Chains that depend on each other, so none of them can be resolved.
+    /// The first and last chains are the same.
+    #[error(
+        "Chains depend on each other: {}",
+        _0.iter().map(|id| format!("`{id}`")).collect::<Vec<_>>().join(" -> ")
+    )]
+    Cycle(Vec<ChainId>),
+
     /// A bubbled-error from rendering a nested template in the chain arguments
     #[error("Rendering nested template for field `{field}`")]
     Nested {
@@ -217,3 +358,11 @@ impl PartialEq for ChainError {
         unimplemented!("PartialEq for ChainError is hard to implement")
     }
 }
+
+/// Placeholder, same as [ChainError]. `io::Error` doesn't impl `PartialEq`
+#[cfg(test)]
+impl PartialEq for FileError {
+    fn eq(&self, _: &Self) -> bool {
+        unimplemented!("PartialEq for FileError is hard to implement")
+    }
+}
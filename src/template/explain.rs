@@ -0,0 +1,380 @@
+//! Explain where each value in a rendered template came from. This is a
+//! debugging aid: it renders the template just like normal, but keeps track
+//! of which source (profile, chain, override, etc.) produced each key.
+
+use crate::{
+    collection::{ChainId, ChainSource, ProfileId},
+    template::{
+        function::{RandomFunction, TemplateFunction},
+        parse::TemplateInputChunk,
+        Template, TemplateChunk, TemplateContext, TemplateError, TemplateKey,
+        RECURSION_LIMIT,
+    },
+};
+use futures::{future::BoxFuture, FutureExt};
+use std::fmt::{self, Display};
+
+/// Placeholder shown in place of sensitive values
+const SENSITIVE_PLACEHOLDER: &str = "<sensitive>";
+
+/// How a template was rendered, key by key. See [Template::explain]
+#[derive(Debug)]
+pub struct TemplateExplanation {
+    /// The whole rendered template, with sensitive values masked. `None` if
+    /// any key failed to render
+    pub value: Option<String>,
+    /// Every key in the template, in order
+    pub keys: Vec<KeyExplanation>,
+}
+
+/// Where a single template key got its value
+#[derive(Debug)]
+pub struct KeyExplanation {
+    /// The key as written in the template, e.g. `chains.token`
+    pub key: String,
+    pub source: KeySource,
+    /// Rendered value, or the error that prevented rendering it. Sensitive
+    /// values are masked.
+    pub value: Result<String, TemplateError>,
+    /// For profile fields, how the field's own template was rendered
+    pub nested: Option<TemplateExplanation>,
+}
+
+/// What kind of thing a template key was resolved from
+#[derive(Debug)]
+pub enum KeySource {
+    /// An override given by the user, e.g. with `--override` in the CLI
+    Override,
+    /// A field in the selected profile
+    Profile {
+        profile_id: ProfileId,
+        /// The field's template, before rendering
+        template: Template,
+    },
+    /// A field that isn't in the profile, or there's no profile selected
+    UnknownField,
+    /// A collection constant. `None` if it isn't in the collection
+    Constant {
+        template: Option<Template>,
+    },
+    Chain {
+        chain_id: ChainId,
+        /// `None` if the chain isn't in the collection
+        source: Option<ChainSource>,
+    },
+    Environment,
+    /// A data file row. Only reached if the column is missing, since row
+    /// values are given as overrides
+    Row,
+    /// A session variable, captured from an earlier response
+    Variable,
+    File,
+    Function(TemplateFunction),
+    Random(RandomFunction),
+}
+
+impl Template {
+    /// Render the template, recording where each key's value came from.
+    /// Profile fields are explained recursively, since they're templates
+    /// themselves. Sensitive values (e.g. from a sensitive chain) are masked.
+    pub async fn explain(
+        &self,
+        context: &TemplateContext,
+    ) -> TemplateExplanation {
+        self.explain_inner(context, 0).await
+    }
+
+    fn explain_inner<'a>(
+        &'a self,
+        context: &'a TemplateContext,
+        depth: u8,
+    ) -> BoxFuture<'a, TemplateExplanation> {
+        async move {
+            let rendered = self.render_chunks(context).await;
+            let mut value = Some(String::new());
+            let mut keys = Vec::new();
+            for (input, chunk) in self.chunks.iter().zip(rendered) {
+                let (rendered, sensitive) = match chunk {
+                    TemplateChunk::Raw(span) => {
+                        if let Some(value) = &mut value {
+                            value.push_str(self.substring(span));
+                        }
+                        continue;
+                    }
+                    TemplateChunk::Rendered { value, sensitive } => {
+                        (Ok(value), sensitive)
+                    }
+                    TemplateChunk::Error(error) => (Err(error), false),
+                };
+                let TemplateInputChunk::Key(key) = input else {
+                    continue;
+                };
+                let key = key.map(|span| self.substring(span));
+
+                let rendered = rendered.map(|rendered| {
+                    if sensitive {
+                        SENSITIVE_PLACEHOLDER.to_owned()
+                    } else {
+                        rendered
+                    }
+                });
+                match (&mut value, &rendered) {
+                    (Some(value), Ok(rendered)) => value.push_str(rendered),
+                    (_, Err(_)) => value = None,
+                    (None, Ok(_)) => {}
+                }
+
+                let (source, nested) =
+                    Self::key_source(context, key, depth).await;
+                keys.push(KeyExplanation {
+                    key: key.to_string(),
+                    source,
+                    value: rendered,
+                    nested,
+                });
+            }
+            TemplateExplanation { value, keys }
+        }
+        .boxed()
+    }
+
+    /// Figure out where a key gets its value from. For profile fields, also
+    /// explain the field's template, unless we've gone too deep (probably a
+    /// cycle)
+    async fn key_source(
+        context: &TemplateContext,
+        key: TemplateKey<&str>,
+        depth: u8,
+    ) -> (KeySource, Option<TemplateExplanation>) {
+        // Overrides take precedence over everything, same as in rendering
+        if context.overrides.contains_key(&key.to_string()) {
+            return (KeySource::Override, None);
+        }
+        match key {
+            TemplateKey::Field(field) => {
+                let Some((profile_id, template)) =
+                    context.selected_profile.as_ref().and_then(|profile_id| {
+                        let profile =
+                            context.collection.profiles.get(profile_id)?;
+                        Some((profile_id, profile.data.get(field)?))
+                    })
+                else {
+                    return (KeySource::UnknownField, None);
+                };
+                let nested = if depth < RECURSION_LIMIT {
+                    Some(template.explain_inner(context, depth + 1).await)
+                } else {
+                    None
+                };
+                let source = KeySource::Profile {
+                    profile_id: profile_id.clone(),
+                    template: template.clone(),
+                };
+                (source, nested)
+            }
+            TemplateKey::Chain(chain_id) => {
+                let chain_id: ChainId = chain_id.into();
+                let source = context
+                    .collection
+                    .chains
+                    .get(&chain_id)
+                    .map(|chain| chain.source.clone());
+                (KeySource::Chain { chain_id, source }, None)
+            }
+            TemplateKey::Constant(name) => {
+                let Some(template) = context.collection.constants.get(name)
+                else {
+                    return (KeySource::Constant { template: None }, None);
+                };
+                let nested = if depth < RECURSION_LIMIT {
+                    Some(template.explain_inner(context, depth + 1).await)
+                } else {
+                    None
+                };
+                let source = KeySource::Constant {
+                    template: Some(template.clone()),
+                };
+                (source, nested)
+            }
+            TemplateKey::Environment(_) => (KeySource::Environment, None),
+            TemplateKey::Row(_) => (KeySource::Row, None),
+            TemplateKey::Variable(_) => (KeySource::Variable, None),
+            TemplateKey::File(_) | TemplateKey::FileBase64(_) => {
+                (KeySource::File, None)
+            }
+            TemplateKey::Function { function, .. } => {
+                (KeySource::Function(function), None)
+            }
+            TemplateKey::Random { function, .. } => {
+                (KeySource::Random(function), None)
+            }
+        }
+    }
+}
+
+impl TemplateExplanation {
+    /// Hide every value in this explanation, including nested ones. Useful
+    /// when the whole template is sensitive, e.g. a password
+    pub fn mask(&mut self) {
+        if let Some(value) = &mut self.value {
+            *value = SENSITIVE_PLACEHOLDER.to_owned();
+        }
+        for key in &mut self.keys {
+            if let Ok(value) = &mut key.value {
+                *value = SENSITIVE_PLACEHOLDER.to_owned();
+            }
+            if let Some(nested) = &mut key.nested {
+                nested.mask();
+            }
+        }
+    }
+}
+
+impl Display for KeySource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Override => write!(f, "override"),
+            Self::Profile {
+                profile_id,
+                template,
+            } => write!(f, "profile `{profile_id}`: `{template}`"),
+            Self::UnknownField => write!(f, "unknown field"),
+            Self::Constant { template: None } => write!(f, "unknown constant"),
+            Self::Constant {
+                template: Some(template),
+            } => write!(f, "constant: `{template}`"),
+            Self::Chain {
+                chain_id,
+                source: None,
+            } => write!(f, "unknown chain `{chain_id}`"),
+            Self::Chain {
+                chain_id,
+                source: Some(source),
+            } => {
+                write!(f, "chain `{chain_id}` from ")?;
+                match source {
+                    ChainSource::Request { recipe, .. } => {
+                        write!(f, "request `{recipe}`")
+                    }
+                    ChainSource::LastResponse => write!(f, "last response"),
+                    ChainSource::Command { command } => {
+                        write!(f, "command `")?;
+                        for (i, argument) in command.iter().enumerate() {
+                            if i > 0 {
+                                write!(f, " ")?;
+                            }
+                            write!(f, "{argument}")?;
+                        }
+                        write!(f, "`")
+                    }
+                    ChainSource::File { path } => write!(f, "file `{path}`"),
+                    ChainSource::Prompt { .. } => write!(f, "prompt"),
+                    ChainSource::Patch { .. } => write!(f, "patch"),
+                }
+            }
+            Self::Environment => write!(f, "environment variable"),
+            Self::Row => write!(f, "data row"),
+            Self::Variable => write!(f, "session variable"),
+            Self::File => write!(f, "file"),
+            Self::Function(function) => write!(f, "function `{function}`"),
+            Self::Random(function) => write!(f, "random `{function}`"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+    use factori::create;
+    use indexmap::indexmap;
+    use itertools::Itertools;
+
+    /// Summarize an explanation as (key, source, value) for easy comparison
+    fn summarize(
+        explanation: &TemplateExplanation,
+    ) -> Vec<(&str, String, &str)> {
+        explanation
+            .keys
+            .iter()
+            .map(|key| {
+                (
+                    key.key.as_str(),
+                    key.source.to_string(),
+                    key.value.as_deref().unwrap_or("<error>"),
+                )
+            })
+            .collect_vec()
+    }
+
+    /// Each key is attributed to its source, and profile fields are explained
+    /// recursively
+    #[tokio::test]
+    async fn test_explain() {
+        let profile = create!(
+            Profile,
+            data: indexmap! {
+                "host".into() => "{{scheme}}://localhost".into(),
+                "scheme".into() => "https".into(),
+            },
+        );
+        let token = create!(
+            Chain,
+            id: "token".into(),
+            source: ChainSource::Command {
+                command: vec!["echo".into(), "secret".into()],
+            },
+            sensitive: true,
+        );
+        let context = create!(
+            TemplateContext,
+            collection: create!(
+                Collection,
+                profiles: indexmap! {profile.id.clone() => profile},
+                constants: indexmap! {"version".into() => "v2".into()},
+                chains: indexmap! {token.id.clone() => token},
+            ),
+            selected_profile: Some("profile1".into()),
+            overrides: indexmap! {"user".into() => "me".into()},
+        );
+        let template: Template =
+            "{{host}}/{{user}}?t={{chains.token}}&v={{const.version}}&x={{missing}}"
+                .into();
+
+        let explanation = template.explain(&context).await;
+        assert_eq!(explanation.value, None);
+        assert_eq!(
+            summarize(&explanation),
+            vec![
+                (
+                    "host",
+                    "profile `profile1`: `{{scheme}}://localhost`".into(),
+                    "https://localhost"
+                ),
+                ("user", "override".into(), "me"),
+                (
+                    "chains.token",
+                    "chain `token` from command `echo secret`".into(),
+                    "<sensitive>"
+                ),
+                ("const.version", "constant: `v2`".into(), "v2"),
+                ("missing", "unknown field".into(), "<error>"),
+            ]
+        );
+        let nested = explanation.keys[0].nested.as_ref().unwrap();
+        assert_eq!(nested.value.as_deref(), Some("https://localhost"));
+        assert_eq!(
+            summarize(nested),
+            vec![("scheme", "profile `profile1`: `https`".into(), "https")]
+        );
+
+        // Masking hides everything, all the way down
+        let template: Template = "{{host}}/{{user}}".into();
+        let mut explanation = template.explain(&context).await;
+        assert_eq!(explanation.value.as_deref(), Some("https://localhost/me"));
+        explanation.mask();
+        assert_eq!(explanation.value.as_deref(), Some("<sensitive>"));
+        let nested = explanation.keys[0].nested.as_ref().unwrap();
+        assert_eq!(nested.keys[0].value.as_deref().ok(), Some("<sensitive>"));
+    }
+}
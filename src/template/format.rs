@@ -0,0 +1,408 @@
+//! Pretty-printing for template source text. This operates on the raw
+//! template, *before* rendering, so template keys are kept intact.
+
+use std::iter::Peekable;
+use thiserror::Error;
+
+const INDENT: &str = "  ";
+
+/// An error formatting template text, with the position of the problem
+#[derive(Debug, Error)]
+#[cfg_attr(test, derive(PartialEq))]
+#[error("{kind} at line {line}, column {column}")]
+pub struct FormatError {
+    pub kind: FormatErrorKind,
+    /// 1-indexed
+    pub line: usize,
+    /// 1-indexed, in characters
+    pub column: usize,
+}
+
+#[derive(Debug, Error)]
+#[cfg_attr(test, derive(PartialEq))]
+pub enum FormatErrorKind {
+    #[error("Unexpected `{0}`")]
+    Unexpected(String),
+    #[error("Unexpected end of input")]
+    UnexpectedEnd,
+    #[error("Unterminated string")]
+    UnterminatedString,
+    #[error("Unterminated template key")]
+    UnterminatedKey,
+    #[error("Invalid value `{0}`")]
+    InvalidValue(String),
+    #[error("Object keys must be strings, found `{0}`")]
+    InvalidObjectKey(String),
+}
+
+/// Pretty-print a template containing JSON. Template keys (`{{...}}`) are
+/// allowed anywhere a value or object key is, and are copied as-is. Strings
+/// are also copied as-is, so keys and escape sequences within them are
+/// untouched. Only whitespace outside of strings is changed.
+pub fn format_json(template: &str) -> Result<String, FormatError> {
+    let mut formatter = JsonFormatter {
+        source: template,
+        tokens: Tokenizer {
+            source: template,
+            position: 0,
+        }
+        .peekable(),
+        output: String::with_capacity(template.len()),
+        depth: 0,
+    };
+    formatter.value()?;
+    // Anything after the root value is an error
+    match formatter.tokens.next() {
+        None => Ok(formatter.output),
+        Some(result) => {
+            let token = result
+                .map_err(|(kind, position)| formatter.error(kind, position))?;
+            Err(formatter.error(
+                FormatErrorKind::Unexpected(token.text.to_owned()),
+                token.position,
+            ))
+        }
+    }
+}
+
+/// A single tokenized element of JSON, borrowed from the source
+#[derive(Copy, Clone, Debug)]
+struct Token<'a> {
+    kind: TokenKind,
+    text: &'a str,
+    /// Byte offset into the source
+    position: usize,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum TokenKind {
+    ObjectStart,
+    ObjectEnd,
+    ArrayStart,
+    ArrayEnd,
+    Colon,
+    Comma,
+    String,
+    /// Number, literal (`true`/`false`/`null`), or anything else unquoted.
+    /// May contain template keys.
+    Bare {
+        has_key: bool,
+    },
+}
+
+/// Tokenizer errors carry only a byte offset, to be converted to a line and
+/// column by the formatter
+type TokenResult<'a> = Result<Token<'a>, (FormatErrorKind, usize)>;
+
+struct Tokenizer<'a> {
+    source: &'a str,
+    /// Byte offset of the next character to read
+    position: usize,
+}
+
+impl<'a> Tokenizer<'a> {
+    fn rest(&self) -> &'a str {
+        &self.source[self.position..]
+    }
+
+    /// Consume a quoted string, *including* both quotes
+    fn string(&mut self) -> Result<(), (FormatErrorKind, usize)> {
+        let start = self.position;
+        let mut chars = self.rest().char_indices().skip(1);
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '\\' => {
+                    chars.next();
+                }
+                '"' => {
+                    self.position += i + 1;
+                    return Ok(());
+                }
+                _ => {}
+            }
+        }
+        Err((FormatErrorKind::UnterminatedString, start))
+    }
+
+    /// Consume an unquoted value, which may include template keys. Return
+    /// whether it contained a key
+    fn bare(&mut self) -> Result<bool, (FormatErrorKind, usize)> {
+        let mut has_key = false;
+        loop {
+            let rest = self.rest();
+            if rest.starts_with("{{") {
+                let end = rest
+                    .find("}}")
+                    .ok_or((FormatErrorKind::UnterminatedKey, self.position))?;
+                self.position += end + 2;
+                has_key = true;
+                continue;
+            }
+            match rest.chars().next() {
+                Some(c) if !is_delimiter(c) => self.position += c.len_utf8(),
+                _ => return Ok(has_key),
+            }
+        }
+    }
+}
+
+impl<'a> Iterator for Tokenizer<'a> {
+    type Item = TokenResult<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let trimmed = self.rest().trim_start();
+        self.position = self.source.len() - trimmed.len();
+        let start = self.position;
+        let c = trimmed.chars().next()?;
+
+        let result = match c {
+            // A template key is a value, not the start of an object. `{{{` is
+            // an object whose first key is a template key
+            '{' if trimmed.starts_with("{{") && !trimmed.starts_with("{{{") => {
+                self.bare().map(|has_key| TokenKind::Bare { has_key })
+            }
+            '{' => Ok(TokenKind::ObjectStart),
+            '}' => Ok(TokenKind::ObjectEnd),
+            '[' => Ok(TokenKind::ArrayStart),
+            ']' => Ok(TokenKind::ArrayEnd),
+            ':' => Ok(TokenKind::Colon),
+            ',' => Ok(TokenKind::Comma),
+            '"' => self.string().map(|()| TokenKind::String),
+            _ => self.bare().map(|has_key| TokenKind::Bare { has_key }),
+        };
+        // Single-char tokens haven't advanced yet
+        if self.position == start {
+            self.position += c.len_utf8();
+        }
+
+        Some(result.map(|kind| Token {
+            kind,
+            text: &self.source[start..self.position],
+            position: start,
+        }))
+    }
+}
+
+/// Recursive descent formatter. Each method consumes one grammar element and
+/// writes its formatted form to the output.
+struct JsonFormatter<'a> {
+    source: &'a str,
+    tokens: Peekable<Tokenizer<'a>>,
+    output: String,
+    depth: usize,
+}
+
+impl<'a> JsonFormatter<'a> {
+    fn value(&mut self) -> Result<(), FormatError> {
+        let token = self.next()?;
+        match token.kind {
+            TokenKind::ObjectStart => self.container(
+                TokenKind::ObjectEnd,
+                Self::object_entry,
+                token.text,
+            ),
+            TokenKind::ArrayStart => {
+                self.container(TokenKind::ArrayEnd, Self::value, token.text)
+            }
+            TokenKind::String | TokenKind::Bare { has_key: true } => {
+                self.output.push_str(token.text);
+                Ok(())
+            }
+            TokenKind::Bare { has_key: false }
+                if is_json_scalar(token.text) =>
+            {
+                self.output.push_str(token.text);
+                Ok(())
+            }
+            TokenKind::Bare { has_key: false } => Err(self.error(
+                FormatErrorKind::InvalidValue(token.text.to_owned()),
+                token.position,
+            )),
+            _ => Err(self.unexpected(token)),
+        }
+    }
+
+    /// Format an object or array, whose opening token has already been
+    /// consumed. Each element is formatted by the given function
+    fn container(
+        &mut self,
+        end: TokenKind,
+        element: fn(&mut Self) -> Result<(), FormatError>,
+        open: &str,
+    ) -> Result<(), FormatError> {
+        self.output.push_str(open);
+        // Empty containers stay on one line
+        if self.peek_kind() == Some(end) {
+            let token = self.next()?;
+            self.output.push_str(token.text);
+            return Ok(());
+        }
+
+        self.depth += 1;
+        loop {
+            self.newline();
+            element(self)?;
+            let token = self.next()?;
+            match token.kind {
+                TokenKind::Comma => self.output.push(','),
+                kind if kind == end => {
+                    self.depth -= 1;
+                    self.newline();
+                    self.output.push_str(token.text);
+                    return Ok(());
+                }
+                _ => return Err(self.unexpected(token)),
+            }
+        }
+    }
+
+    fn object_entry(&mut self) -> Result<(), FormatError> {
+        let token = self.next()?;
+        match token.kind {
+            TokenKind::String | TokenKind::Bare { has_key: true } => {
+                self.output.push_str(token.text)
+            }
+            TokenKind::Bare { has_key: false } => {
+                return Err(self.error(
+                    FormatErrorKind::InvalidObjectKey(token.text.to_owned()),
+                    token.position,
+                ))
+            }
+            _ => return Err(self.unexpected(token)),
+        }
+        let token = self.next()?;
+        if token.kind != TokenKind::Colon {
+            return Err(self.unexpected(token));
+        }
+        self.output.push_str(": ");
+        self.value()
+    }
+
+    fn next(&mut self) -> Result<Token<'a>, FormatError> {
+        match self.tokens.next() {
+            Some(Ok(token)) => Ok(token),
+            Some(Err((kind, position))) => Err(self.error(kind, position)),
+            None => {
+                Err(self
+                    .error(FormatErrorKind::UnexpectedEnd, self.source.len()))
+            }
+        }
+    }
+
+    fn peek_kind(&mut self) -> Option<TokenKind> {
+        match self.tokens.peek() {
+            Some(Ok(token)) => Some(token.kind),
+            _ => None,
+        }
+    }
+
+    fn newline(&mut self) {
+        self.output.push('\n');
+        for _ in 0..self.depth {
+            self.output.push_str(INDENT);
+        }
+    }
+
+    fn unexpected(&self, token: Token) -> FormatError {
+        self.error(
+            FormatErrorKind::Unexpected(token.text.to_owned()),
+            token.position,
+        )
+    }
+
+    /// Build an error, converting the byte offset into a line and column
+    fn error(&self, kind: FormatErrorKind, position: usize) -> FormatError {
+        let before = &self.source[..position];
+        let line = before.matches('\n').count() + 1;
+        let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+        let column = before[line_start..].chars().count() + 1;
+        FormatError { kind, line, column }
+    }
+}
+
+/// Does this character end an unquoted value?
+fn is_delimiter(c: char) -> bool {
+    c.is_whitespace() || matches!(c, '{' | '}' | '[' | ']' | ',' | ':' | '"')
+}
+
+/// Is this a valid unquoted JSON value (number or literal)?
+fn is_json_scalar(text: &str) -> bool {
+    matches!(text, "true" | "false" | "null")
+        || serde_json::from_str::<serde_json::Number>(text).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::scalar(" 3 ", "3")]
+    #[case::empty("{ }", "{}")]
+    #[case::object(
+        r#"{"a":1,"b":[true, null,"c"],"d":{}}"#,
+        "{\n  \"a\": 1,\n  \"b\": [\n    true,\n    null,\n    \"c\"\n  ],\n  \"d\": {}\n}"
+    )]
+    #[case::strings_untouched(
+        r#"{"a b": "{ \"x\" : [1] }"}"#,
+        "{\n  \"a b\": \"{ \\\"x\\\" : [1] }\"\n}"
+    )]
+    #[case::template_keys(
+        r#"{{{key}}: {{ value }}, "id": {{id}}0, "s": "{{s}}"}"#,
+        "{\n  {{key}}: {{ value }},\n  \"id\": {{id}}0,\n  \"s\": \"{{s}}\"\n}"
+    )]
+    fn test_format_json(#[case] template: &str, #[case] expected: &str) {
+        assert_eq!(format_json(template).unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case::trailing_comma(
+        "{\n  \"a\": 1,\n}",
+        FormatErrorKind::Unexpected("}".into()),
+        3,
+        1
+    )]
+    #[case::missing_comma(
+        "[1 2]",
+        FormatErrorKind::Unexpected("2".into()),
+        1,
+        4
+    )]
+    #[case::unclosed("[1, [2]", FormatErrorKind::UnexpectedEnd, 1, 8)]
+    #[case::unterminated_string(
+        "{\"a\": \"oops}",
+        FormatErrorKind::UnterminatedString,
+        1,
+        7
+    )]
+    #[case::unterminated_key(
+        "[{{oops]",
+        FormatErrorKind::UnterminatedKey,
+        1,
+        2
+    )]
+    #[case::invalid_value(
+        "{\"a\": tru}",
+        FormatErrorKind::InvalidValue("tru".into()),
+        1,
+        7
+    )]
+    #[case::invalid_key(
+        "{a: 1}",
+        FormatErrorKind::InvalidObjectKey("a".into()),
+        1,
+        2
+    )]
+    #[case::trailing_value("{} {}", FormatErrorKind::Unexpected("{".into()), 1, 4)]
+    fn test_format_json_error(
+        #[case] template: &str,
+        #[case] kind: FormatErrorKind,
+        #[case] line: usize,
+        #[case] column: usize,
+    ) {
+        assert_eq!(
+            format_json(template).unwrap_err(),
+            FormatError { kind, line, column }
+        );
+    }
+}
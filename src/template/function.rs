@@ -0,0 +1,292 @@
+//! Functions that can be called within template keys, e.g.
+//! `{{sha256(body)}}`. Functions compute a value from their arguments, which
+//! are other template keys. Random functions, e.g. `{{random_int(1, 10)}}`,
+//! take literal arguments instead.
+
+use base64::{prelude::BASE64_STANDARD, Engine};
+use bytes::Bytes;
+use derive_more::Display;
+use hmac::{Hmac, Mac};
+use md5::Md5;
+use rand::{
+    distr::{Alphanumeric, SampleString},
+    rngs::StdRng,
+    Rng, SeedableRng,
+};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    fmt::Write,
+    future::Future,
+    sync::{Arc, Mutex},
+};
+
+tokio::task_local! {
+    /// The fully rendered body of the request currently being built. This
+    /// enables `body` as a function argument, e.g. to sign the body in a
+    /// header. It's a task-local rather than part of the template context
+    /// because triggered requests reuse the context of their parent, but each
+    /// request has its own body.
+    ///
+    /// The outer option is `None` when the body isn't available. The inner
+    /// option is `None` when the request has no body.
+    static REQUEST_BODY: Option<Option<Bytes>>;
+}
+
+/// Run a future (typically a render) with access to a request's rendered
+/// body
+pub async fn with_request_body<F: Future>(
+    body: Option<Bytes>,
+    future: F,
+) -> F::Output {
+    REQUEST_BODY.scope(Some(body), future).await
+}
+
+/// Run a future without access to any request body. This hides the body of an
+/// enclosing request, e.g. while a triggered request renders its own body.
+pub async fn without_request_body<F: Future>(future: F) -> F::Output {
+    REQUEST_BODY.scope(None, future).await
+}
+
+/// Get the body of the request being built. Returns `None` if the body isn't
+/// available, i.e. we're not rendering request headers. The inner option is
+/// `None` if the request has no body.
+pub(super) fn request_body() -> Option<Option<Bytes>> {
+    REQUEST_BODY.try_with(Clone::clone).ok().flatten()
+}
+
+/// A function callable in a template, e.g. `sha256` or `hmac_sha256_base64`
+#[derive(Copy, Clone, Debug, Display)]
+#[cfg_attr(test, derive(PartialEq))]
+#[display("{algorithm}{encoding}")]
+pub struct TemplateFunction {
+    pub algorithm: HashAlgorithm,
+    pub encoding: Encoding,
+}
+
+/// A hash or signature algorithm. The `Display` impl gives the function name
+#[derive(Copy, Clone, Debug, Display)]
+#[cfg_attr(test, derive(PartialEq))]
+pub enum HashAlgorithm {
+    #[display("md5")]
+    Md5,
+    #[display("sha256")]
+    Sha256,
+    /// Takes a secret key, then the message to sign
+    #[display("hmac_sha256")]
+    HmacSha256,
+}
+
+/// How the output bytes of a function are converted to text. The `Display`
+/// impl gives the function name suffix
+#[derive(Copy, Clone, Debug, Default, Display)]
+#[cfg_attr(test, derive(PartialEq))]
+pub enum Encoding {
+    /// Lowercase hexadecimal
+    #[default]
+    #[display("")]
+    Hex,
+    /// Standard base64, with padding
+    #[display("_base64")]
+    Base64,
+}
+
+impl HashAlgorithm {
+    /// Number of arguments the function takes
+    pub fn arity(self) -> usize {
+        match self {
+            Self::Md5 | Self::Sha256 => 1,
+            Self::HmacSha256 => 2,
+        }
+    }
+}
+
+impl TemplateFunction {
+    /// Call the function. The number of arguments must match
+    /// [HashAlgorithm::arity]; that's enforced during parsing
+    pub fn call(self, arguments: &[&[u8]]) -> String {
+        let output: Vec<u8> = match (self.algorithm, arguments) {
+            (HashAlgorithm::Md5, [message]) => Md5::digest(message).to_vec(),
+            (HashAlgorithm::Sha256, [message]) => {
+                Sha256::digest(message).to_vec()
+            }
+            (HashAlgorithm::HmacSha256, [key, message]) => {
+                // HMAC accepts keys of any length
+                let mut mac = Hmac::<Sha256>::new_from_slice(key)
+                    .expect("HMAC accepts keys of any length");
+                mac.update(message);
+                mac.finalize().into_bytes().to_vec()
+            }
+            (algorithm, _) => panic!(
+                "Expected {} arguments for `{algorithm}`, got {}",
+                algorithm.arity(),
+                arguments.len()
+            ),
+        };
+        match self.encoding {
+            Encoding::Hex => {
+                output.iter().fold(String::new(), |mut hex, byte| {
+                    // Writing to a string can't fail
+                    let _ = write!(hex, "{byte:02x}");
+                    hex
+                })
+            }
+            Encoding::Base64 => BASE64_STANDARD.encode(output),
+        }
+    }
+}
+
+/// A function that generates a random value, e.g. `random_int(1, 10)`.
+/// Arguments are literals, and are validated during parsing. The `Display`
+/// impl gives just the function name.
+#[derive(Copy, Clone, Debug, Display)]
+#[cfg_attr(test, derive(PartialEq))]
+pub enum RandomFunction {
+    /// An integer between `min` and `max`, inclusive
+    #[display("random_int")]
+    Int { min: i64, max: i64 },
+    /// ASCII letters and digits
+    #[display("random_string")]
+    String { length: usize },
+    /// A lowercase address at `example.com`, which is reserved so it can
+    /// never belong to a real person
+    #[display("random_email")]
+    Email,
+}
+
+/// Where random functions get their values. By default every call gives a
+/// fresh value. With a seed, each value is derived from the seed, the key
+/// text, and how many times that key has been rendered before, so rendering
+/// the same templates with the same seed gives the same values. Clones share
+/// the same counts.
+#[derive(Clone, Debug, Default)]
+pub struct RandomSource(Option<Arc<SeededSource>>);
+
+#[derive(Debug)]
+struct SeededSource {
+    seed: u64,
+    /// Number of values generated so far for each key
+    counts: Mutex<HashMap<String, u64>>,
+}
+
+impl RandomSource {
+    pub fn seeded(seed: u64) -> Self {
+        Self(Some(Arc::new(SeededSource {
+            seed,
+            counts: Default::default(),
+        })))
+    }
+
+    /// Generate a value for a random function. `key` is the full key text,
+    /// e.g. `random_int(1, 10)`
+    pub fn generate(&self, function: RandomFunction, key: &str) -> String {
+        let mut rng = match &self.0 {
+            None => StdRng::from_rng(&mut rand::rng()),
+            Some(seeded) => {
+                let mut counts = seeded.counts.lock().unwrap();
+                let count = counts.entry(key.to_owned()).or_default();
+                let digest = Sha256::new()
+                    .chain_update(seeded.seed.to_le_bytes())
+                    .chain_update(key)
+                    .chain_update(count.to_le_bytes())
+                    .finalize();
+                *count += 1;
+                StdRng::from_seed(digest.into())
+            }
+        };
+        match function {
+            RandomFunction::Int { min, max } => {
+                rng.random_range(min..=max).to_string()
+            }
+            RandomFunction::String { length } => {
+                Alphanumeric.sample_string(&mut rng, length)
+            }
+            RandomFunction::Email => {
+                let user = Alphanumeric.sample_string(&mut rng, 12);
+                format!("{}@example.com", user.to_lowercase())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    /// Test each function against known outputs
+    #[rstest]
+    #[case::md5(HashAlgorithm::Md5, Encoding::Hex, &["hello"], "5d41402abc4b2a76b9719d911017c592")]
+    #[case::sha256(
+        HashAlgorithm::Sha256,
+        Encoding::Hex,
+        &["hello"],
+        "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+    )]
+    #[case::sha256_base64(
+        HashAlgorithm::Sha256,
+        Encoding::Base64,
+        &["hello"],
+        "LPJNul+wow4m6DsqxbninhsWHlwfp0JecwQzYpOLmCQ="
+    )]
+    #[case::hmac_sha256(
+        HashAlgorithm::HmacSha256,
+        Encoding::Hex,
+        &["key", "The quick brown fox jumps over the lazy dog"],
+        "f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8"
+    )]
+    fn test_call(
+        #[case] algorithm: HashAlgorithm,
+        #[case] encoding: Encoding,
+        #[case] arguments: &[&str],
+        #[case] expected: &str,
+    ) {
+        let function = TemplateFunction {
+            algorithm,
+            encoding,
+        };
+        let arguments: Vec<&[u8]> = arguments
+            .iter()
+            .map(|argument| argument.as_bytes())
+            .collect();
+        assert_eq!(function.call(&arguments), expected);
+    }
+
+    #[test]
+    fn test_random() {
+        let source = RandomSource::default();
+        for _ in 0..100 {
+            let value: i64 = source
+                .generate(RandomFunction::Int { min: -2, max: 2 }, "")
+                .parse()
+                .unwrap();
+            assert!((-2..=2).contains(&value), "{value}");
+        }
+        let value = source.generate(RandomFunction::String { length: 16 }, "");
+        assert_eq!(value.len(), 16);
+        assert!(value.chars().all(|c| c.is_ascii_alphanumeric()), "{value}");
+        let value = source.generate(RandomFunction::Email, "");
+        assert!(value.ends_with("@example.com"), "{value}");
+    }
+
+    /// The same seed and key give the same sequence of values. Different keys
+    /// get their own sequence, so the order keys are rendered in doesn't
+    /// matter
+    #[test]
+    fn test_random_seeded() {
+        let function = RandomFunction::String { length: 20 };
+        let generate = |seed: u64, keys: &[&str]| {
+            let source = RandomSource::seeded(seed);
+            keys.iter()
+                .map(|key| source.generate(function, key))
+                .collect::<Vec<_>>()
+        };
+        let values = generate(42, &["a", "a", "b"]);
+        assert_ne!(values[0], values[1]);
+        assert_eq!(
+            generate(42, &["b", "a", "a"]),
+            [values[2].clone(), values[0].clone(), values[1].clone()]
+        );
+        assert_ne!(generate(43, &["a", "a", "b"]), values);
+    }
+}
@@ -1,13 +1,24 @@
 //! Template string parser
 
-use crate::template::{error::TemplateParseError, Template, TemplateKey};
+use crate::{
+    collection::ChainId,
+    template::{
+        error::TemplateParseError,
+        function::{Encoding, HashAlgorithm, RandomFunction, TemplateFunction},
+        Template, TemplateKey,
+    },
+};
 use nom::{
     branch::alt,
     bytes::complete::{tag, take_while1},
-    combinator::{all_consuming, cut},
+    character::complete::{char, digit1, space0},
+    combinator::{
+        all_consuming, consumed, cut, map_res, opt, recognize, success, value,
+        verify,
+    },
     error::{context, ErrorKind, ParseError, VerboseError},
-    multi::many0,
-    sequence::{preceded, terminated},
+    multi::{many0, many1, separated_list1},
+    sequence::{delimited, pair, preceded, separated_pair, terminated, tuple},
     FindSubstring, Finish, IResult, InputLength, InputTake, Offset, Parser,
 };
 
@@ -15,7 +26,16 @@ const KEY_OPEN: &str = "{{";
 const KEY_CLOSE: &str = "}}";
 // Export these so they can be used in TemplateKey's Display impl
 pub const CHAIN_PREFIX: &str = "chains.";
+pub const CONST_PREFIX: &str = "const.";
 pub const ENV_PREFIX: &str = "env.";
+pub const FILE_PREFIX: &str = "file.";
+pub const FILE_BASE64_PREFIX: &str = "file_base64.";
+pub const ROW_PREFIX: &str = "row.";
+pub const VARS_PREFIX: &str = "vars.";
+/// Function argument referring to the rendered request body
+const BODY_ARGUMENT: &str = "body";
+/// Longest string `random_string` can generate
+const MAX_RANDOM_LENGTH: usize = 10_000;
 
 type ParseResult<'a, T> = IResult<&'a str, T, VerboseError<&'a str>>;
 
@@ -35,6 +55,18 @@ impl Template {
 
         Ok(Self { template, chunks })
     }
+
+    /// Create a template that renders a single chain, i.e.
+    /// `{{chains.<id>}}`. This skips parsing, so the chain ID doesn't have to
+    /// be a valid identifier.
+    pub fn from_chain(id: &ChainId) -> Self {
+        let template = format!("{KEY_OPEN}{CHAIN_PREFIX}{id}{KEY_CLOSE}");
+        let span = Span::new(KEY_OPEN.len() + CHAIN_PREFIX.len(), id.len());
+        Self {
+            template,
+            chunks: vec![TemplateInputChunk::Key(TemplateKey::Chain(span))],
+        }
+    }
 }
 
 /// A parsed piece of a template. After parsing, each chunk is either raw text
@@ -57,6 +89,26 @@ impl<T> TemplateInputChunk<T> {
     }
 }
 
+/// An argument to a template function
+#[derive(Copy, Clone, Debug)]
+#[cfg_attr(test, derive(PartialEq))]
+pub enum FunctionArgument<T> {
+    /// The rendered body of the request being built
+    Body,
+    Key(TemplateKey<T>),
+}
+
+/// Parse the argument list of a function. The list was already validated when
+/// the parent template was parsed, so this panics if it's invalid.
+pub(super) fn parse_function_arguments(
+    arguments: &str,
+) -> Vec<FunctionArgument<&str>> {
+    let (_, arguments) = all_consuming(function_arguments)(arguments)
+        .finish()
+        .expect("Function arguments were parsed as part of parent template");
+    arguments
+}
+
 /// Indexes defining a substring of text within some string. This is a useful
 /// alternative to string slices when avoiding self-referential structs.
 #[derive(Copy, Clone, Debug)]
@@ -113,17 +165,168 @@ fn key(input: &str) -> ParseResult<TemplateKey<&str>> {
 fn key_contents(input: &str) -> ParseResult<TemplateKey<&str>> {
     alt((
         context(
-            "chain",
-            preceded(tag(CHAIN_PREFIX), identifier).map(TemplateKey::Chain),
+            "function",
+            function.map(|(function, arguments)| TemplateKey::Function {
+                function,
+                arguments,
+            }),
+        ),
+        context(
+            "random",
+            random.map(|(function, arguments)| TemplateKey::Random {
+                function,
+                arguments,
+            }),
+        ),
+        chain,
+        constant,
+        environment,
+        row,
+        variable,
+        context(
+            "file",
+            preceded(tag(FILE_PREFIX), file_path).map(TemplateKey::File),
         ),
         context(
-            "environment",
-            preceded(tag(ENV_PREFIX), identifier).map(TemplateKey::Environment),
+            "file_base64",
+            preceded(tag(FILE_BASE64_PREFIX), file_path)
+                .map(TemplateKey::FileBase64),
         ),
         context("field", identifier.map(TemplateKey::Field)),
     ))(input)
 }
 
+fn chain(input: &str) -> ParseResult<TemplateKey<&str>> {
+    context(
+        "chain",
+        preceded(tag(CHAIN_PREFIX), identifier).map(TemplateKey::Chain),
+    )(input)
+}
+
+fn constant(input: &str) -> ParseResult<TemplateKey<&str>> {
+    context(
+        "constant",
+        preceded(tag(CONST_PREFIX), identifier).map(TemplateKey::Constant),
+    )(input)
+}
+
+fn environment(input: &str) -> ParseResult<TemplateKey<&str>> {
+    context(
+        "environment",
+        preceded(tag(ENV_PREFIX), identifier).map(TemplateKey::Environment),
+    )(input)
+}
+
+fn variable(input: &str) -> ParseResult<TemplateKey<&str>> {
+    context(
+        "variable",
+        preceded(tag(VARS_PREFIX), identifier).map(TemplateKey::Variable),
+    )(input)
+}
+
+fn row(input: &str) -> ParseResult<TemplateKey<&str>> {
+    context(
+        "row",
+        preceded(tag(ROW_PREFIX), identifier).map(TemplateKey::Row),
+    )(input)
+}
+
+/// Parse a function call, e.g. `hmac_sha256(chains.secret, body)`. Returns
+/// the function and its unparsed argument list. The list is parsed again at
+/// render time, which is easier than storing a variable number of spans.
+fn function(input: &str) -> ParseResult<(TemplateFunction, &str)> {
+    let (input, algorithm) = alt((
+        value(HashAlgorithm::HmacSha256, tag("hmac_sha256")),
+        value(HashAlgorithm::Sha256, tag("sha256")),
+        value(HashAlgorithm::Md5, tag("md5")),
+    ))(input)?;
+    let (input, encoding) = alt((
+        value(Encoding::Base64, tag("_base64")),
+        success(Encoding::Hex),
+    ))(input)?;
+    let function = TemplateFunction {
+        algorithm,
+        encoding,
+    };
+    // Without the paren, this could be a field with the same name. After it,
+    // this has to be a function
+    let (input, _) = char('(')(input)?;
+    let (input, (arguments, _)) = cut(terminated(
+        context(
+            "arguments",
+            verify(consumed(function_arguments), |(_, arguments)| {
+                arguments.len() == algorithm.arity()
+            }),
+        ),
+        char(')'),
+    ))(input)?;
+    Ok((input, (function, arguments)))
+}
+
+/// Parse a random function call, e.g. `random_int(1, 10)`. Returns the
+/// function with its parsed arguments, and the raw argument list. Arguments
+/// are literals rather than keys, so they can be checked up front.
+fn random(input: &str) -> ParseResult<(RandomFunction, &str)> {
+    fn integer<T: std::str::FromStr>(input: &str) -> ParseResult<T> {
+        map_res(recognize(pair(opt(char('-')), digit1)), str::parse)(input)
+    }
+
+    let comma = || tuple((space0, char(','), space0));
+    let (input, name) =
+        alt((tag("random_int"), tag("random_string"), tag("random_email")))(
+            input,
+        )?;
+    // Without the paren, this could be a field with the same name
+    let (input, _) = char('(')(input)?;
+    let arguments = |input| match name {
+        "random_int" => {
+            verify(separated_pair(integer, comma(), integer), |(min, max)| {
+                min <= max
+            })
+            .map(|(min, max)| RandomFunction::Int { min, max })
+            .parse(input)
+        }
+        "random_string" => {
+            verify(integer, |length| *length <= MAX_RANDOM_LENGTH)
+                .map(|length| RandomFunction::String { length })
+                .parse(input)
+        }
+        _ => success(RandomFunction::Email)(input),
+    };
+    let (input, (arguments, function)) = cut(terminated(
+        context("arguments", consumed(delimited(space0, arguments, space0))),
+        char(')'),
+    ))(input)?;
+    Ok((input, (function, arguments)))
+}
+
+/// Parse a comma-separated list of function arguments. Arguments can be keys
+/// (but not files, whose paths could contain commas), or `body`
+fn function_arguments(input: &str) -> ParseResult<Vec<FunctionArgument<&str>>> {
+    let argument = alt((
+        chain.map(FunctionArgument::Key),
+        constant.map(FunctionArgument::Key),
+        environment.map(FunctionArgument::Key),
+        row.map(FunctionArgument::Key),
+        variable.map(FunctionArgument::Key),
+        context(
+            "field",
+            identifier.map(|field| {
+                if field == BODY_ARGUMENT {
+                    FunctionArgument::Body
+                } else {
+                    FunctionArgument::Key(TemplateKey::Field(field))
+                }
+            }),
+        ),
+    ));
+    delimited(
+        space0,
+        separated_list1(tuple((space0, char(','), space0)), argument),
+        space0,
+    )(input)
+}
+
 /// Parse a field name/chain ID/env variable etc, inside a key
 fn identifier(input: &str) -> ParseResult<&str> {
     context(
@@ -132,6 +335,19 @@ fn identifier(input: &str) -> ParseResult<&str> {
     )(input)
 }
 
+/// Parse a file path inside a key. The path is itself a template, so it can
+/// contain nested keys. Braces are otherwise not allowed, so we know where the
+/// path ends.
+fn file_path(input: &str) -> ParseResult<&str> {
+    context(
+        "path",
+        recognize(many1(alt((
+            recognize(key),
+            take_while1(|c: char| !"{}".contains(c)),
+        )))),
+    )(input)
+}
+
 /// A copy pasta of nom's `take_until` that will take up to the end of a string
 /// if the terminator never appears, instead of erroring out. I couldn't
 /// figure out how to do this with other combinators, so here we go
@@ -169,10 +385,86 @@ mod tests {
         "{{chains.chain1}}",
         vec![TemplateInputChunk::Key(TemplateKey::Chain("chain1"))]
     )]
+    #[case::constant(
+        "{{const.api_version}}",
+        vec![TemplateInputChunk::Key(TemplateKey::Constant("api_version"))]
+    )]
     #[case::env(
         "{{env.ENV}}",
         vec![TemplateInputChunk::Key(TemplateKey::Environment("ENV"))]
     )]
+    #[case::variable(
+        "{{vars.token}}",
+        vec![TemplateInputChunk::Key(TemplateKey::Variable("token"))]
+    )]
+    #[case::row(
+        "{{row.user_id}}",
+        vec![TemplateInputChunk::Key(TemplateKey::Row("user_id"))]
+    )]
+    #[case::file(
+        "{{file.../dir/key.txt}}",
+        vec![TemplateInputChunk::Key(TemplateKey::File("../dir/key.txt"))]
+    )]
+    #[case::file_nested(
+        "{{file.{{dir}}/{{env.NAME}}.txt}} after",
+        vec![
+            TemplateInputChunk::Key(TemplateKey::File("{{dir}}/{{env.NAME}}.txt")),
+            TemplateInputChunk::Raw(" after"),
+        ]
+    )]
+    #[case::file_base64(
+        "{{file_base64.image.png}}",
+        vec![TemplateInputChunk::Key(TemplateKey::FileBase64("image.png"))]
+    )]
+    #[case::function(
+        "{{sha256(body)}}",
+        vec![TemplateInputChunk::Key(TemplateKey::Function {
+            function: TemplateFunction {
+                algorithm: HashAlgorithm::Sha256,
+                encoding: Encoding::Hex,
+            },
+            arguments: "body",
+        })]
+    )]
+    #[case::function_arguments(
+        "{{hmac_sha256_base64( chains.secret ,body )}}",
+        vec![TemplateInputChunk::Key(TemplateKey::Function {
+            function: TemplateFunction {
+                algorithm: HashAlgorithm::HmacSha256,
+                encoding: Encoding::Base64,
+            },
+            arguments: " chains.secret ,body ",
+        })]
+    )]
+    #[case::function_name_field(
+        "{{sha256_base64x}}",
+        vec![TemplateInputChunk::Key(TemplateKey::Field("sha256_base64x"))]
+    )]
+    #[case::random_int(
+        "{{random_int( -5,10 )}}",
+        vec![TemplateInputChunk::Key(TemplateKey::Random {
+            function: RandomFunction::Int { min: -5, max: 10 },
+            arguments: " -5,10 ",
+        })]
+    )]
+    #[case::random_string(
+        "{{random_string(8)}}",
+        vec![TemplateInputChunk::Key(TemplateKey::Random {
+            function: RandomFunction::String { length: 8 },
+            arguments: "8",
+        })]
+    )]
+    #[case::random_email(
+        "{{random_email()}}",
+        vec![TemplateInputChunk::Key(TemplateKey::Random {
+            function: RandomFunction::Email,
+            arguments: "",
+        })]
+    )]
+    #[case::random_name_field(
+        "{{random_int}}",
+        vec![TemplateInputChunk::Key(TemplateKey::Field("random_int"))]
+    )]
     #[case::utf8(
         "intro\n{{user_id}} 💚💙💜 {{chains.chain}}\noutro\r\nmore outro",
         vec![
@@ -209,7 +501,46 @@ mod tests {
     #[case::invalid_chain("{{chains.one.two}}")]
     #[case::invalid_env("{{env.one.two}}")]
     #[case::whitespace("{{ field }}")]
+    #[case::empty_file("{{file.}}")]
+    #[case::invalid_file_brace("{{file.a{b}}")]
+    #[case::invalid_file_nested("{{file.{{.}}}}")]
+    #[case::function_unclosed("{{md5(body}}")]
+    #[case::function_no_arguments("{{md5()}}")]
+    #[case::function_too_many_arguments("{{md5(body, field)}}")]
+    #[case::function_too_few_arguments("{{hmac_sha256(body)}}")]
+    #[case::function_file_argument("{{md5(file.a.txt)}}")]
+    #[case::function_unknown("{{sha1(body)}}")]
+    #[case::random_int_order("{{random_int(10, 1)}}")]
+    #[case::random_int_field("{{random_int(1, max)}}")]
+    #[case::random_int_overflow("{{random_int(1, 99999999999999999999)}}")]
+    #[case::random_string_negative("{{random_string(-1)}}")]
+    #[case::random_string_too_long("{{random_string(10001)}}")]
+    #[case::random_email_argument("{{random_email(1)}}")]
     fn test_parse_error(#[case] template: &str) {
         assert_err!(Template::parse(template.into()), "at line 1");
     }
+
+    #[test]
+    fn test_parse_function_arguments() {
+        assert_eq!(
+            parse_function_arguments("chains.secret, env.KEY, field, body"),
+            vec![
+                FunctionArgument::Key(TemplateKey::Chain("secret")),
+                FunctionArgument::Key(TemplateKey::Environment("KEY")),
+                FunctionArgument::Key(TemplateKey::Field("field")),
+                FunctionArgument::Body,
+            ]
+        );
+    }
+
+    /// Chain templates built without parsing should be equivalent to their
+    /// parsed counterpart
+    #[test]
+    fn test_from_chain() {
+        let template = Template::from_chain(&"chain1".into());
+        assert_eq!(
+            template,
+            Template::parse("{{chains.chain1}}".into()).unwrap()
+        );
+    }
 }
@@ -1,7 +1,14 @@
-use crate::util::ResultExt;
+use crate::{
+    collection::{ChainId, PromptPattern},
+    util::ResultExt,
+};
 use anyhow::anyhow;
 use derive_more::From;
-use std::fmt::Debug;
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    sync::{Arc, Mutex},
+};
 use tokio::sync::oneshot;
 
 /// A prompter is a bridge between the user and the template engine. It enables
@@ -30,6 +37,13 @@ pub struct Prompt {
     pub default: Option<String>,
     /// Should the value the user is typing be masked? E.g. password input
     pub sensitive: bool,
+    /// If given, the value must match this pattern. The prompter should keep
+    /// asking until the user enters a valid value (or gives up).
+    pub pattern: Option<PromptPattern>,
+    /// If not empty, the user should pick one of these instead of typing a
+    /// value, and the response is the picked option. `default`, `sensitive`,
+    /// and `pattern` don't apply.
+    pub options: Vec<String>,
     /// How the prompter will pass the answer back
     pub channel: PromptChannel,
 }
@@ -52,3 +66,22 @@ impl PromptChannel {
             .traced();
     }
 }
+
+/// Values the user picked when a chain's selector matched more than one value,
+/// keyed by chain. Clones share the same storage, so a choice made during one
+/// render is remembered for the rest of the session.
+#[derive(Clone, Debug, Default)]
+pub struct ChainChoices(Arc<Mutex<HashMap<ChainId, String>>>);
+
+impl ChainChoices {
+    pub fn get(&self, chain_id: &ChainId) -> Option<String> {
+        self.0.lock().expect("Lock poisoned").get(chain_id).cloned()
+    }
+
+    pub fn set(&self, chain_id: ChainId, value: String) {
+        self.0
+            .lock()
+            .expect("Lock poisoned")
+            .insert(chain_id, value);
+    }
+}
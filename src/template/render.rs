@@ -1,22 +1,32 @@
 //! Template rendering implementation
 
 use crate::{
-    collection::{ChainId, ChainRequestTrigger, ChainSource, RecipeId},
+    collection::{
+        Chain, ChainId, ChainRequestSection, ChainRequestTrigger, ChainSource,
+        PatchFormat, PromptPattern, RecipeId, SelectorFallback, SelectorMode,
+    },
     http::{ContentType, RequestBuilder, RequestRecord, Response},
     template::{
-        error::TriggeredRequestError, parse::TemplateInputChunk, ChainError,
-        Prompt, Template, TemplateChunk, TemplateContext, TemplateError,
-        TemplateKey, RECURSION_LIMIT,
+        chain_cache::{resolve_chain, ChainValue},
+        error::{FileError, FunctionError, TriggeredRequestError},
+        function::{self, RandomFunction, TemplateFunction},
+        parse::{
+            parse_function_arguments, FunctionArgument, TemplateInputChunk,
+        },
+        ChainError, Prompt, Template, TemplateChunk, TemplateContext,
+        TemplateError, TemplateKey, RECURSION_LIMIT,
     },
     util::ResultExt,
 };
 use async_trait::async_trait;
+use base64::{prelude::BASE64_STANDARD, Engine};
 use chrono::Utc;
 use futures::future;
+use reqwest::header::{HeaderValue, SET_COOKIE};
 use std::{
     env,
-    path::PathBuf,
     sync::{atomic::Ordering, Arc},
+    time::Duration,
 };
 use tokio::{fs, process::Command, sync::oneshot};
 use tracing::{debug, debug_span, instrument, trace};
@@ -120,9 +130,10 @@ impl Template {
         future::join_all(futures).await
     }
 
-    /// Helper for stitching chunks together into a single string. If any chunk
-    /// failed to render, return an error.
-    pub(super) async fn render_stitched(
+    /// Render the template string using values from the given context,
+    /// stitching the chunks together into a single string. If any chunk
+    /// failed to render, return its error.
+    pub async fn render_stitched(
         &self,
         context: &TemplateContext,
     ) -> Result<String, TemplateError> {
@@ -172,9 +183,35 @@ impl<'a> TemplateKey<&'a str> {
             Self::Chain(chain_id) => Box::new(ChainTemplateSource {
                 chain_id: chain_id.into(),
             }),
+            Self::Constant(name) => Box::new(ConstantTemplateSource { name }),
             Self::Environment(variable) => {
                 Box::new(EnvironmentTemplateSource { variable })
             }
+            Self::Row(column) => Box::new(RowTemplateSource { column }),
+            Self::Variable(variable) => {
+                Box::new(VariableTemplateSource { variable })
+            }
+            Self::File(path) => Box::new(FileTemplateSource {
+                path,
+                base64: false,
+            }),
+            Self::FileBase64(path) => {
+                Box::new(FileTemplateSource { path, base64: true })
+            }
+            Self::Function {
+                function,
+                arguments,
+            } => Box::new(FunctionTemplateSource {
+                function,
+                arguments,
+            }),
+            Self::Random {
+                function,
+                arguments,
+            } => Box::new(RandomTemplateSource {
+                function,
+                arguments,
+            }),
         }
     }
 }
@@ -240,6 +277,39 @@ impl<'a> TemplateSource<'a> for FieldTemplateSource<'a> {
     }
 }
 
+/// A collection constant. Like a profile field, this is a template itself,
+/// but it's the same regardless of which profile is selected
+struct ConstantTemplateSource<'a> {
+    pub name: &'a str,
+}
+
+#[async_trait]
+impl<'a> TemplateSource<'a> for ConstantTemplateSource<'a> {
+    async fn render(&self, context: &'a TemplateContext) -> TemplateResult {
+        let name = self.name;
+        let template =
+            context.collection.constants.get(name).ok_or_else(|| {
+                TemplateError::ConstantUnknown {
+                    name: name.to_owned(),
+                }
+            })?;
+
+        trace!(%name, %template, "Rendering recursive template");
+        context.recursion_count.fetch_add(1, Ordering::Relaxed);
+        let rendered =
+            template.render_stitched(context).await.map_err(|error| {
+                TemplateError::ConstantNested {
+                    name: name.to_owned(),
+                    error: Box::new(error),
+                }
+            })?;
+        Ok(RenderedChunk {
+            value: rendered,
+            sensitive: false,
+        })
+    }
+}
+
 /// A chained value from a complex source. Could be an HTTP response, file, etc.
 struct ChainTemplateSource<'a> {
     pub chain_id: ChainId<&'a str>,
@@ -248,6 +318,20 @@ struct ChainTemplateSource<'a> {
 #[async_trait]
 impl<'a> TemplateSource<'a> for ChainTemplateSource<'a> {
     async fn render(&self, context: &'a TemplateContext) -> TemplateResult {
+        let chain_id = (&self.chain_id).into();
+        let (value, sensitive) =
+            resolve_chain(&chain_id, self.resolve(context)).await?;
+        Ok(RenderedChunk { value, sensitive })
+    }
+}
+
+impl<'a> ChainTemplateSource<'a> {
+    /// Compute the value of the chain. Use [resolve_chain] to share the value
+    /// with other templates in the same request.
+    async fn resolve(
+        &self,
+        context: &'a TemplateContext,
+    ) -> Result<ChainValue, TemplateError> {
         // Any error in here is the chain error subtype
         let result: Result<_, ChainError> = async {
             // Resolve chained value
@@ -264,10 +348,57 @@ impl<'a> TemplateSource<'a> for ChainTemplateSource<'a> {
             // We intentionally throw the content detection error away here,
             // because it isn't that intuitive for users and is hard to plumb
             let (value, content_type) = match &chain.source {
-                ChainSource::Request { recipe, trigger } => {
-                    let response =
-                        self.get_response(context, recipe, *trigger).await?;
-                    // Guess content type based on HTTP header
+                ChainSource::Request {
+                    recipe,
+                    trigger,
+                    section,
+                    timeout,
+                    max_body_size,
+                } => {
+                    let response = self
+                        .get_response(
+                            context,
+                            recipe,
+                            *trigger,
+                            *timeout,
+                            *max_body_size,
+                        )
+                        .await?;
+                    match section {
+                        ChainRequestSection::Body => {
+                            // Guess content type based on HTTP header
+                            let content_type =
+                                ContentType::from_response(&response).ok();
+                            (response.body.into_bytes(), content_type)
+                        }
+                        ChainRequestSection::Header(header) => {
+                            let value = self
+                                .render_header(context, header, &response)
+                                .await?;
+                            // No way to guess content type on this
+                            (value, None)
+                        }
+                        ChainRequestSection::Status => (
+                            response.status.as_u16().to_string().into_bytes(),
+                            None,
+                        ),
+                        ChainRequestSection::Cookie(cookie) => {
+                            let value = self
+                                .render_cookie(context, cookie, &response)
+                                .await?;
+                            (value.into_bytes(), None)
+                        }
+                    }
+                }
+                ChainSource::LastResponse => {
+                    let response = context
+                        .database
+                        .get_last_request_any_recipe(
+                            context.selected_profile.as_ref(),
+                        )
+                        .map_err(ChainError::Database)?
+                        .ok_or(ChainError::NoResponse)?
+                        .response;
                     let content_type =
                         ContentType::from_response(&response).ok();
                     (response.body.into_bytes(), content_type)
@@ -279,11 +410,16 @@ impl<'a> TemplateSource<'a> for ChainTemplateSource<'a> {
                     // No way to guess content type on this
                     (self.render_command(context, command).await?, None)
                 }
-                ChainSource::Prompt { message, default } => (
+                ChainSource::Prompt {
+                    message,
+                    default,
+                    pattern,
+                } => (
                     self.render_prompt(
                         context,
                         message.as_ref(),
                         default.as_ref(),
+                        pattern.as_ref(),
                         chain.sensitive,
                     )
                     .await?
@@ -291,6 +427,14 @@ impl<'a> TemplateSource<'a> for ChainTemplateSource<'a> {
                     // No way to guess content type on this
                     None,
                 ),
+                ChainSource::Patch {
+                    base,
+                    changes,
+                    format,
+                } => (
+                    self.render_patch(context, base, changes, *format).await?,
+                    Some(ContentType::Json),
+                ),
             };
             // If the user provided a content type, prefer that over the
             // detected one
@@ -304,17 +448,25 @@ impl<'a> TemplateSource<'a> for ChainTemplateSource<'a> {
                 let value = content_type
                     .parse_content(&value)
                     .map_err(|err| ChainError::ParseResponse { error: err })?;
-                selector.query_to_string(&*value)?
+                match chain.selector_mode {
+                    SelectorMode::Single => {
+                        selector.query_to_string(&*value)?
+                    }
+                    SelectorMode::Prompt => {
+                        let matches = selector.query_to_strings(&*value)?;
+                        // Parsed content isn't Send, so it can't be held
+                        // across the prompt
+                        drop(value);
+                        self.choose_match(context, chain, matches).await?
+                    }
+                }
             } else {
                 // We just want raw text - decode as UTF-8
                 String::from_utf8(value)
                     .map_err(|error| ChainError::InvalidUtf8 { error })?
             };
 
-            Ok(RenderedChunk {
-                value,
-                sensitive: chain.sensitive,
-            })
+            Ok((value, chain.sensitive))
         }
         .await;
 
@@ -324,9 +476,7 @@ impl<'a> TemplateSource<'a> for ChainTemplateSource<'a> {
             error,
         })
     }
-}
 
-impl<'a> ChainTemplateSource<'a> {
     /// Get an HTTP response for a recipe. This will either get the most recent
     /// response from history or re-execute the request, depending on trigger
     /// behavior.
@@ -335,6 +485,8 @@ impl<'a> ChainTemplateSource<'a> {
         context: &'a TemplateContext,
         recipe_id: &RecipeId,
         trigger: ChainRequestTrigger,
+        timeout: Option<Duration>,
+        max_body_size: Option<u64>,
     ) -> Result<Response, ChainError> {
         // Get the referenced recipe. We actually only need the whole recipe if
         // we're executing the request, but we want this to error out if the
@@ -385,11 +537,18 @@ impl<'a> ChainTemplateSource<'a> {
                     .http_engine
                     .clone()
                     .ok_or(TriggeredRequestError::NotAllowed)?
+                    .with_limits(timeout, max_body_size)
                     .send(Arc::new(request))
                     .await
                     .map_err(TriggeredRequestError::Send)
             };
-            result.await.map_err(|error| ChainError::Trigger {
+            let result = result.await;
+            if let Ok(record) = &result {
+                // A failed capture shouldn't fail the chain, which only
+                // needs the response
+                let _ = context.variables.capture(recipe, record).traced();
+            }
+            result.map_err(|error| ChainError::Trigger {
                 recipe_id: recipe.id.clone(),
                 error,
             })
@@ -427,14 +586,14 @@ impl<'a> ChainTemplateSource<'a> {
         context: &TemplateContext,
         path: &Template,
     ) -> Result<(Vec<u8>, Option<ContentType>), ChainError> {
-        let path: PathBuf = path
-            .render_stitched(context)
-            .await
-            .map_err(|error| ChainError::Nested {
-                field: "path".into(),
-                error: error.into(),
-            })?
-            .into();
+        let path = context.resolve_path(
+            path.render_stitched(context).await.map_err(|error| {
+                ChainError::Nested {
+                    field: "path".into(),
+                    error: error.into(),
+                }
+            })?,
+        )?;
         // Guess content type based on file extension
         let content_type = ContentType::from_extension(&path).ok();
         let content = fs::read(&path)
@@ -487,12 +646,99 @@ impl<'a> ChainTemplateSource<'a> {
             .await
     }
 
+    /// Render a patch document, computed from a base document and a set of
+    /// changes to apply to it
+    async fn render_patch(
+        &self,
+        context: &TemplateContext,
+        base: &Template,
+        changes: &Template,
+        format: PatchFormat,
+    ) -> Result<Vec<u8>, ChainError> {
+        // Render and parse both inputs
+        let (base, changes) = future::try_join(
+            render_json(context, "base", base),
+            render_json(context, "changes", changes),
+        )
+        .await?;
+
+        // Apply the changes to get the modified document, then diff it against
+        // the original. This way the patch only includes what actually changed
+        let mut modified = base.clone();
+        json_patch::merge(&mut modified, &changes);
+        let patch = match format {
+            PatchFormat::MergePatch => merge_patch_diff(&base, &modified),
+            PatchFormat::JsonPatch => {
+                serde_json::to_value(json_patch::diff(&base, &modified))
+                    .expect("Patch serialization cannot fail")
+            }
+        };
+        Ok(patch.to_string().into_bytes())
+    }
+
+    /// Get the value of a response header. Header names are case-insensitive,
+    /// and repeated headers are joined into one value, per RFC 9110
+    async fn render_header(
+        &self,
+        context: &'a TemplateContext,
+        header: &Template,
+        response: &Response,
+    ) -> Result<Vec<u8>, ChainError> {
+        let header =
+            header.render_stitched(context).await.map_err(|error| {
+                ChainError::Nested {
+                    field: "section".into(),
+                    error: error.into(),
+                }
+            })?;
+        let values = response
+            .headers
+            .get_all(header.as_str())
+            .iter()
+            .map(HeaderValue::as_bytes)
+            .collect::<Vec<_>>();
+        if values.is_empty() {
+            return Err(ChainError::MissingHeader { header });
+        }
+        Ok(values.join(b", ".as_slice()))
+    }
+
+    /// Get the value of a cookie from the response's `Set-Cookie` headers. If
+    /// the cookie is set more than once, the last one wins, same as a browser.
+    async fn render_cookie(
+        &self,
+        context: &'a TemplateContext,
+        cookie: &Template,
+        response: &Response,
+    ) -> Result<String, ChainError> {
+        let cookie =
+            cookie.render_stitched(context).await.map_err(|error| {
+                ChainError::Nested {
+                    field: "section".into(),
+                    error: error.into(),
+                }
+            })?;
+        response
+            .headers
+            .get_all(SET_COOKIE)
+            .iter()
+            .filter_map(|value| {
+                // Attributes (Path, Expires, etc.) come after the first `;`
+                let pair = value.to_str().ok()?.split(';').next()?;
+                let (name, value) = pair.split_once('=')?;
+                (name.trim() == cookie).then(|| value.trim().to_owned())
+            })
+            .last()
+            .ok_or(ChainError::MissingCookie { cookie })
+    }
+
     /// Render a value by asking the user to provide it
     async fn render_prompt(
         &self,
         context: &'a TemplateContext,
         message: Option<&Template>,
         default: Option<&Template>,
+        pattern: Option<&PromptPattern>,
         sensitive: bool,
     ) -> Result<String, ChainError> {
         // Use the prompter to ask the user a question, and wait for a response
@@ -523,10 +769,63 @@ impl<'a> ChainTemplateSource<'a> {
             message,
             default,
             sensitive,
+            pattern: pattern.cloned(),
+            options: Vec::new(),
             channel: tx.into(),
         });
         rx.await.map_err(|_| ChainError::PromptNoResponse)
     }
+
+    /// Pick one of a selector's matches. If there's more than one, reuse the
+    /// user's previous choice if it's still available, otherwise ask them.
+    /// If they don't answer, use the chain's fallback.
+    async fn choose_match(
+        &self,
+        context: &'a TemplateContext,
+        chain: &Chain,
+        mut matches: Vec<String>,
+    ) -> Result<String, ChainError> {
+        if matches.len() == 1 {
+            return Ok(matches.remove(0));
+        }
+        let chain_id: ChainId = (&self.chain_id).into();
+        if let Some(choice) = context
+            .chain_choices
+            .get(&chain_id)
+            .filter(|choice| matches.contains(choice))
+        {
+            return Ok(choice);
+        }
+
+        let (tx, rx) = oneshot::channel();
+        context.prompter.prompt(Prompt {
+            message: format!("Select a value for `{chain_id}`"),
+            default: None,
+            sensitive: false,
+            pattern: None,
+            options: matches.clone(),
+            channel: tx.into(),
+        });
+        match rx.await {
+            Ok(choice) => {
+                // Previews respond with a placeholder, which isn't worth
+                // remembering
+                if matches.contains(&choice) {
+                    context.chain_choices.set(chain_id, choice.clone());
+                }
+                Ok(choice)
+            }
+            Err(_) => match chain.selector_fallback {
+                Some(SelectorFallback::First) => Ok(matches.remove(0)),
+                Some(SelectorFallback::Last) => {
+                    Ok(matches.pop().unwrap_or_default())
+                }
+                None => Err(ChainError::SelectorNoChoice {
+                    count: matches.len(),
+                }),
+            },
+        }
+    }
 }
 
 /// A value sourced from the process's environment
@@ -549,3 +848,215 @@ impl<'a> TemplateSource<'a> for EnvironmentTemplateSource<'a> {
         })
     }
 }
+
+/// A session variable, captured from an earlier response
+struct VariableTemplateSource<'a> {
+    pub variable: &'a str,
+}
+
+#[async_trait]
+impl<'a> TemplateSource<'a> for VariableTemplateSource<'a> {
+    async fn render(&self, context: &'a TemplateContext) -> TemplateResult {
+        let value = context.variables.get(self.variable).ok_or_else(|| {
+            TemplateError::VariableUnknown {
+                variable: self.variable.to_owned(),
+            }
+        })?;
+        Ok(RenderedChunk {
+            value,
+            sensitive: false,
+        })
+    }
+}
+
+/// A column of a data file row. Row values are passed as overrides, so if
+/// we get here, the column isn't in the row
+struct RowTemplateSource<'a> {
+    pub column: &'a str,
+}
+
+#[async_trait]
+impl<'a> TemplateSource<'a> for RowTemplateSource<'a> {
+    async fn render(&self, _: &'a TemplateContext) -> TemplateResult {
+        Err(TemplateError::RowColumnUnknown {
+            column: self.column.to_owned(),
+        })
+    }
+}
+
+/// Contents of a file, included inline
+struct FileTemplateSource<'a> {
+    /// Unparsed template for the path
+    pub path: &'a str,
+    /// Encode the contents as base64, instead of decoding them as UTF-8
+    pub base64: bool,
+}
+
+#[async_trait]
+impl<'a> TemplateSource<'a> for FileTemplateSource<'a> {
+    async fn render(&self, context: &'a TemplateContext) -> TemplateResult {
+        let result: Result<_, FileError> = async {
+            // The parser already validated the nested template, so this
+            // shouldn't fail
+            let path = Template::parse(self.path.to_owned())
+                .expect("File path was parsed as part of parent template");
+            context.recursion_count.fetch_add(1, Ordering::Relaxed);
+            let rendered = path.render_stitched(context).await;
+            let path = context.resolve_path(
+                rendered.map_err(|error| FileError::Path(error.into()))?,
+            )?;
+
+            let content = fs::read(&path)
+                .await
+                .map_err(|error| FileError::Read { path, error })?;
+            if self.base64 {
+                Ok(BASE64_STANDARD.encode(content))
+            } else {
+                String::from_utf8(content)
+                    .map_err(|error| FileError::InvalidUtf8 { error })
+            }
+        }
+        .await;
+        let value = result.map_err(|error| TemplateError::File {
+            path: self.path.to_owned(),
+            error,
+        })?;
+        Ok(RenderedChunk {
+            value,
+            sensitive: false,
+        })
+    }
+}
+
+/// A random value, e.g. `{{random_int(1, 10)}}`. Every render gives a new
+/// value, even for the same key in the same template
+struct RandomTemplateSource<'a> {
+    pub function: RandomFunction,
+    /// Unparsed argument list, to identify the key for seeding
+    pub arguments: &'a str,
+}
+
+#[async_trait]
+impl<'a> TemplateSource<'a> for RandomTemplateSource<'a> {
+    async fn render(&self, context: &'a TemplateContext) -> TemplateResult {
+        let key = format!("{}({})", self.function, self.arguments);
+        Ok(RenderedChunk {
+            value: context.random.generate(self.function, &key),
+            sensitive: false,
+        })
+    }
+}
+
+/// A function computed from other values, e.g. `{{sha256(body)}}`
+struct FunctionTemplateSource<'a> {
+    pub function: TemplateFunction,
+    /// Unparsed argument list
+    pub arguments: &'a str,
+}
+
+#[async_trait]
+impl<'a> TemplateSource<'a> for FunctionTemplateSource<'a> {
+    async fn render(&self, context: &'a TemplateContext) -> TemplateResult {
+        let result: Result<_, FunctionError> = async {
+            // Render arguments sequentially. There's typically only one or
+            // two, and this keeps prompts in order
+            let mut arguments: Vec<Vec<u8>> = Vec::new();
+            for argument in parse_function_arguments(self.arguments) {
+                let value = match argument {
+                    FunctionArgument::Body => function::request_body()
+                        .ok_or(FunctionError::BodyUnavailable)?
+                        // Requests without a body are hashed as empty
+                        .unwrap_or_default()
+                        .to_vec(),
+                    FunctionArgument::Key(key) => {
+                        // Arguments can be overridden just like top-level keys
+                        let raw = key.to_string();
+                        if let Some(value) = context.overrides.get(&raw) {
+                            value.clone().into_bytes()
+                        } else {
+                            context
+                                .recursion_count
+                                .fetch_add(1, Ordering::Relaxed);
+                            key.into_source()
+                                .render(context)
+                                .await
+                                .map_err(|error| FunctionError::Argument {
+                                    argument: raw,
+                                    error: Box::new(error),
+                                })?
+                                .value
+                                .into_bytes()
+                        }
+                    }
+                };
+                arguments.push(value);
+            }
+            let arguments: Vec<&[u8]> =
+                arguments.iter().map(Vec::as_slice).collect();
+            Ok(self.function.call(&arguments))
+        }
+        .await;
+        let value = result.map_err(|error| TemplateError::Function {
+            function: self.function.to_string(),
+            error,
+        })?;
+        Ok(RenderedChunk {
+            value,
+            sensitive: false,
+        })
+    }
+}
+
+/// Render a template and parse its output as JSON
+async fn render_json(
+    context: &TemplateContext,
+    field: &str,
+    template: &Template,
+) -> Result<serde_json::Value, ChainError> {
+    let rendered =
+        template.render_stitched(context).await.map_err(|error| {
+            ChainError::Nested {
+                field: field.into(),
+                error: error.into(),
+            }
+        })?;
+    serde_json::from_str(&rendered).map_err(|error| ChainError::PatchParse {
+        field: field.into(),
+        error,
+    })
+}
+
+/// Generate a minimal JSON Merge Patch (RFC 7386) that transforms `from` into
+/// `to`. Objects are diffed recursively; any other value is replaced wholesale
+fn merge_patch_diff(
+    from: &serde_json::Value,
+    to: &serde_json::Value,
+) -> serde_json::Value {
+    use serde_json::{Map, Value};
+
+    match (from, to) {
+        (Value::Object(from), Value::Object(to)) => {
+            let mut patch = Map::new();
+            // Removed fields are nulled out
+            for key in from.keys().filter(|key| !to.contains_key(*key)) {
+                patch.insert(key.clone(), Value::Null);
+            }
+            for (key, to_value) in to {
+                match from.get(key) {
+                    Some(from_value) if from_value == to_value => {}
+                    Some(from_value) => {
+                        patch.insert(
+                            key.clone(),
+                            merge_patch_diff(from_value, to_value),
+                        );
+                    }
+                    None => {
+                        patch.insert(key.clone(), to_value.clone());
+                    }
+                }
+            }
+            Value::Object(patch)
+        }
+        _ => to.clone(),
+    }
+}
@@ -0,0 +1,124 @@
+//! Session variables, captured from responses and used in templates as
+//! `{{vars.<name>}}`
+
+use crate::{collection::Recipe, http::RequestRecord};
+use anyhow::Context;
+use indexmap::IndexMap;
+use std::sync::{Arc, Mutex};
+
+/// Values captured from responses for the rest of the session, by name.
+/// Clones share the same values, so share one between contexts to make
+/// captures visible to later renders.
+#[derive(Clone, Debug, Default)]
+pub struct SessionVariables(Arc<Mutex<IndexMap<String, String>>>);
+
+impl SessionVariables {
+    pub fn get(&self, name: &str) -> Option<String> {
+        self.0.lock().expect("Lock poisoned").get(name).cloned()
+    }
+
+    pub fn set(&self, name: String, value: String) {
+        self.0.lock().expect("Lock poisoned").insert(name, value);
+    }
+
+    /// Store the values from a recipe's `capture` rules, from a response to a
+    /// request built from it. Responses with an error status are ignored.
+    /// Either every value is captured or none are, so a failed rule doesn't
+    /// leave the variables half-updated. Returns the names of the captured
+    /// variables.
+    pub fn capture(
+        &self,
+        recipe: &Recipe,
+        record: &RequestRecord,
+    ) -> anyhow::Result<Vec<String>> {
+        if recipe.capture.is_empty() || !record.response.status.is_success() {
+            return Ok(Vec::new());
+        }
+        let body = record.parse_body()?;
+        let values = recipe
+            .capture
+            .iter()
+            .map(|(name, query)| {
+                let value = query
+                    .query_to_string(&*body)
+                    .with_context(|| format!("Error capturing `{name}`"))?;
+                Ok((name.clone(), value))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let mut variables = self.0.lock().expect("Lock poisoned");
+        Ok(values
+            .into_iter()
+            .map(|(name, value)| {
+                variables.insert(name.clone(), value);
+                name
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test_util::{header_map, *},
+        util::assert_err,
+    };
+    use factori::create;
+    use indexmap::indexmap;
+    use reqwest::StatusCode;
+
+    fn record(status: StatusCode, body: &str) -> RequestRecord {
+        create!(
+            RequestRecord,
+            response: create!(
+                Response,
+                status: status,
+                headers: header_map([("content-type", "application/json")]),
+                body: body.to_owned().into(),
+            ),
+        )
+    }
+
+    #[test]
+    fn test_capture() {
+        let recipe = create!(
+            Recipe,
+            capture: indexmap! {
+                "token".into() => "$.token".parse().unwrap(),
+                "user".into() => "$.user.id".parse().unwrap(),
+            },
+        );
+        let variables = SessionVariables::default();
+        variables.set("token".into(), "old".into());
+
+        // Error statuses don't capture anything
+        let captured = variables
+            .capture(&recipe, &record(StatusCode::UNAUTHORIZED, "{}"))
+            .unwrap();
+        assert!(captured.is_empty());
+
+        // One bad rule means nothing is captured
+        assert_err!(
+            variables.capture(
+                &recipe,
+                &record(StatusCode::OK, r#"{"token": "new"}"#)
+            ),
+            "Error capturing `user`"
+        );
+        assert_eq!(variables.get("token").as_deref(), Some("old"));
+
+        let captured = variables
+            .capture(
+                &recipe,
+                &record(
+                    StatusCode::OK,
+                    r#"{"token": "new", "user": {"id": 3}}"#,
+                ),
+            )
+            .unwrap();
+        assert_eq!(captured, ["token", "user"]);
+        assert_eq!(variables.get("token").as_deref(), Some("new"));
+        assert_eq!(variables.get("user").as_deref(), Some("3"));
+    }
+}
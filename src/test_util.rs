@@ -14,10 +14,18 @@ use reqwest::{
     header::{HeaderMap, HeaderName, HeaderValue},
     Method, StatusCode,
 };
+use std::{
+    env, fs,
+    ops::Deref,
+    path::{Path, PathBuf},
+};
+use uuid::Uuid;
 
 factori!(Collection, {
     default {
+        default_profile = None,
         profiles = Default::default(),
+        constants = Default::default(),
         chains = Default::default(),
         recipes = Default::default(),
         _ignore = Default::default(),
@@ -28,6 +36,11 @@ factori!(Profile, {
     default {
         id = "profile1".into(),
         name = None,
+        base_url = None,
+        authentication = None,
+        color = None,
+        label = None,
+        headers = Default::default(),
         data = Default::default(),
     }
 });
@@ -37,6 +50,7 @@ factori!(Folder, {
         id = "folder1".into(),
         name = None,
         children = Default::default(),
+        disabled = false,
     }
 });
 
@@ -44,12 +58,29 @@ factori!(Recipe, {
     default {
         id = "recipe1".into(),
         name = None,
-        method = "GET".parse().unwrap(),
+        label = None,
+        method = "GET".into(),
         url = "http://localhost".into(),
         body = None,
+        body_format = Default::default(),
         authentication = None,
         query = Default::default(),
         headers = Default::default(),
+        cookies = Default::default(),
+        capture = Default::default(),
+        poll = None,
+        pagination = None,
+        schema = None,
+        persist = true,
+        body_command = None,
+        multipart = None,
+        confirm = None,
+        expects = None,
+        replay = Default::default(),
+        expect_continue = Default::default(),
+        tls = Default::default(),
+        assert = Default::default(),
+        disabled = false,
     }
 });
 
@@ -58,10 +89,17 @@ factori!(Request, {
         id = RequestId::new(),
         profile_id = None,
         recipe_id = "recipe1".into(),
+        label = None,
         method = Method::GET,
         url = "http://localhost/url".parse().unwrap(),
         headers = HeaderMap::new(),
         body = None,
+        body_command = None,
+        persist = true,
+        expects = None,
+        replay = Default::default(),
+        tls = Default::default(),
+        pagination = None,
     }
 });
 
@@ -69,7 +107,12 @@ factori!(Response, {
     default {
         status = StatusCode::OK,
         headers = HeaderMap::new(),
+        trailers = HeaderMap::new(),
         body = Body::default(),
+        headers_only = false,
+        replayed = false,
+        cached = false,
+        tls_version = None,
     }
 });
 
@@ -98,10 +141,15 @@ factori!(Chain, {
         source = ChainSource::Request {
             recipe: RecipeId::default(),
             trigger: Default::default(),
+            section: Default::default(),
+            timeout: None,
+            max_body_size: None,
         },
         sensitive = false,
         selector = None,
         content_type = None,
+        selector_mode = Default::default(),
+        selector_fallback = None,
     }
 });
 
@@ -110,9 +158,13 @@ factori!(TemplateContext, {
         selected_profile = None,
         collection = Default::default(),
         prompter = Box::<TestPrompter>::default(),
+        chain_choices = Default::default(),
+        random = Default::default(),
+        variables = Default::default(),
         http_engine = None,
         database = CollectionDatabase::testing(),
         overrides = Default::default(),
+        path_root = Default::default(),
         recursion_count = Default::default(),
     }
 });
@@ -120,13 +172,23 @@ factori!(TemplateContext, {
 /// Return a static value when prompted, or no value if none is given
 #[derive(Debug, Default)]
 pub struct TestPrompter {
-    value: Option<String>,
+    values: Vec<String>,
 }
 
 impl TestPrompter {
     pub fn new<T: Into<String>>(value: Option<T>) -> Self {
         Self {
-            value: value.map(Into::into),
+            values: value.map(Into::into).into_iter().collect(),
+        }
+    }
+
+    /// Enter each value in order, as if the user is asked again after each
+    /// value that fails the prompt's pattern. If none pass, don't respond
+    pub fn with_attempts<T: Into<String>>(
+        values: impl IntoIterator<Item = T>,
+    ) -> Self {
+        Self {
+            values: values.into_iter().map(Into::into).collect(),
         }
     }
 }
@@ -134,14 +196,53 @@ impl TestPrompter {
 impl Prompter for TestPrompter {
     fn prompt(&self, prompt: Prompt) {
         // If no value was given, check default. If no default, don't respond
-        if let Some(value) = self.value.as_ref() {
-            prompt.channel.respond(value.clone())
-        } else if let Some(default) = prompt.default {
-            prompt.channel.respond(default);
+        let values = if self.values.is_empty() {
+            prompt.default.into_iter().collect()
+        } else {
+            self.values.clone()
+        };
+        let valid = values.into_iter().find(|value| {
+            prompt
+                .pattern
+                .as_ref()
+                .map_or(true, |pattern| pattern.is_match(value))
+                && (prompt.options.is_empty() || prompt.options.contains(value))
+        });
+        if let Some(value) = valid {
+            prompt.channel.respond(value);
         }
     }
 }
 
+/// A uniquely named directory under the system temp directory, deleted along
+/// with its contents when dropped. Use this for tests that touch the file
+/// system, so parallel tests don't collide and nothing is left behind.
+#[derive(Debug)]
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new() -> Self {
+        let path = env::temp_dir().join(format!("slumber-{}", Uuid::new_v4()));
+        fs::create_dir(&path).unwrap();
+        Self(path)
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        // Best effort, since panicking in drop would hide the test's failure
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
 // Some helpful conversion implementations
 impl From<&str> for ProfileId {
     fn from(value: &str) -> Self {
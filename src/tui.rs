@@ -5,37 +5,73 @@ mod signal;
 mod view;
 
 use crate::{
-    collection::{Collection, CollectionFile, ProfileId, RecipeId},
+    cli::GlobalArgs,
+    collection::{Collection, CollectionFile, ProfileId, Recipe, RecipeId},
     config::Config,
     db::{CollectionDatabase, Database},
-    http::{HttpEngine, Request, RequestBuilder},
-    template::{Prompter, Template, TemplateChunk, TemplateContext},
+    http::{
+        HttpEngine, Request, RequestBuildError, RequestBuilder, RequestError,
+        RequestFormat, RequestId, RequestRecord,
+    },
+    template::{
+        ChainChoices, Prompter, SessionVariables, Template, TemplateChunk,
+        TemplateContext,
+    },
     tui::{
         context::TuiContext,
         input::Action,
-        message::{Message, MessageSender, RequestConfig},
+        message::{Confirm, Message, MessageSender, RequestConfig},
         signal::signals,
-        view::{ModalPriority, PreviewPrompter, RequestState, View},
+        view::{
+            ModalPriority, PollAttempt, PreviewPrompter, RequestState,
+            StreamFeed, View,
+        },
     },
-    util::Replaceable,
+    util::{Replaceable, ResultExt},
 };
-use anyhow::{anyhow, Context};
+use anyhow::{anyhow, bail, Context};
+use chrono::Utc;
 use crossterm::{
     event::{DisableMouseCapture, EnableMouseCapture},
     terminal::{EnterAlternateScreen, LeaveAlternateScreen},
 };
+use dialoguer::{Input, Select};
 use futures::Future;
+use itertools::Itertools;
 use notify::{event::ModifyKind, RecursiveMode, Watcher};
 use ratatui::{prelude::CrosstermBackend, Terminal};
 use std::{
+    backtrace::Backtrace,
+    env, fs,
     io::{self, Stdout},
     ops::Deref,
-    path::PathBuf,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
     sync::{Arc, OnceLock},
+    thread,
     time::{Duration, Instant},
 };
-use tokio::sync::mpsc::{self, UnboundedReceiver};
-use tracing::{debug, error, info, trace};
+use tokio::{
+    io::AsyncWriteExt,
+    sync::{
+        mpsc::{self, UnboundedReceiver},
+        oneshot,
+    },
+};
+use tracing::{debug, error, info, trace, warn};
+use uuid::Uuid;
+
+/// Arguments that only apply to the TUI. These are ignored if a subcommand is
+/// given
+#[derive(Debug, clap::Parser)]
+pub struct TuiArgs {
+    /// ID of a recipe to select on launch
+    #[clap(long)]
+    pub recipe: Option<RecipeId>,
+    /// ID of a profile to select on launch
+    #[clap(long, short)]
+    pub profile: Option<ProfileId>,
+}
 
 /// Main controller struct for the TUI. The app uses a React-like architecture
 /// for the view, with a wrapping controller (this struct). The main loop goes
@@ -60,6 +96,14 @@ pub struct Tui {
     /// We only ever need to run DB ops related to our collection, so we can
     /// use a collection-restricted DB handle
     database: CollectionDatabase,
+    /// Remembered picks for chains with multiple selector matches. These
+    /// last for the whole session, including across collection reloads
+    chain_choices: ChainChoices,
+    /// Values captured from responses, shared by every render
+    variables: SessionVariables,
+    /// The last request sent from a recipe, so it can be repeated. This is
+    /// kept here rather than in the view so it survives collection reloads
+    last_request: Option<RequestConfig>,
     should_run: bool,
 }
 
@@ -71,14 +115,23 @@ impl Tui {
 
     /// Start the TUI. Any errors that occur during startup will be panics,
     /// because they prevent TUI execution.
-    pub async fn start(collection_path: Option<PathBuf>) -> anyhow::Result<()> {
+    pub async fn start(
+        global: GlobalArgs,
+        args: TuiArgs,
+    ) -> anyhow::Result<()> {
         initialize_panic_handler();
-        let collection_path = CollectionFile::try_path(collection_path)?;
+        let collection_path = match CollectionFile::try_path(global.file) {
+            Ok(path) => path,
+            // Nothing was given or found, so offer recently opened collections
+            Err(error) => select_recent_collection()?.ok_or(error)?,
+        };
 
         // ===== Initialize global state =====
         // This stuff only needs to be set up *once per session*
 
-        let config = Config::load()?;
+        let mut config = Config::load()?;
+        config.offline |= global.offline;
+        config.resolve.extend(global.resolve);
         // Create a message queue for handling async tasks
         let (messages_tx, messages_rx) = mpsc::unbounded_channel();
         let messages_tx = MessageSender::new(messages_tx);
@@ -92,13 +145,17 @@ impl Tui {
 
         // If the collection fails to load, create an empty one just so we can
         // move along. We'll watch the file and hopefully the user can fix it
-        let collection_file = CollectionFile::load(collection_path.clone())
+        let mut collection_file = CollectionFile::load(collection_path.clone())
             .await
             .unwrap_or_else(|error| {
-                messages_tx.send(Message::Error { error });
+                messages_tx.send(Message::CollectionLoadError(error.into()));
                 CollectionFile::with_path(collection_path)
             });
-        let view = View::new(&collection_file.collection);
+        collection_file
+            .collection
+            .merge_global_chains(&TuiContext::get().config.chains);
+        let mut view = View::new(&collection_file.collection);
+        Self::select_initial(&mut view, &collection_file.collection, args);
 
         // The code to revert the terminal takeover is in `Tui::drop`, so we
         // shouldn't take over the terminal until right before creating the
@@ -112,6 +169,9 @@ impl Tui {
             http_engine,
 
             collection_file,
+            chain_choices: ChainChoices::default(),
+            variables: SessionVariables::default(),
+            last_request: None,
             should_run: true,
 
             view: Replaceable::new(view),
@@ -121,6 +181,31 @@ impl Tui {
         app.run()
     }
 
+    /// Select the profile/recipe requested via CLI args. If no profile was
+    /// given, use the collection's default profile. Unknown IDs aren't fatal;
+    /// we just tell the user and leave the previous selection.
+    fn select_initial(view: &mut View, collection: &Collection, args: TuiArgs) {
+        let profile_id =
+            args.profile.or_else(|| collection.default_profile.clone());
+        let profile_id = profile_id.filter(|profile_id| {
+            let exists = collection.profiles.contains_key(profile_id);
+            if !exists {
+                warn!(%profile_id, "Unknown profile");
+                view.notify(format!("Unknown profile `{profile_id}`"));
+            }
+            exists
+        });
+        let recipe_id = args.recipe.filter(|recipe_id| {
+            let exists = collection.recipes.get(recipe_id).is_some();
+            if !exists {
+                warn!(%recipe_id, "Unknown recipe");
+                view.notify(format!("Unknown recipe `{recipe_id}`"));
+            }
+            exists
+        });
+        view.select(profile_id.as_ref(), recipe_id.as_ref());
+    }
+
     /// Run the main TUI update loop. Any error returned from this is fatal. See
     /// the struct definition for a description of the different phases of the
     /// run loop.
@@ -186,15 +271,23 @@ impl Tui {
             Message::CollectionStartReload => {
                 let messages_tx = self.messages_tx.clone();
                 let future = self.collection_file.reload();
-                self.spawn(async move {
-                    let collection = future.await?;
-                    messages_tx.send(Message::CollectionEndReload(collection));
-                    Ok(())
+                tokio::spawn(async move {
+                    messages_tx.send(match future.await {
+                        Ok(collection) => {
+                            Message::CollectionEndReload(collection)
+                        }
+                        Err(error) => {
+                            Message::CollectionLoadError(error.into())
+                        }
+                    });
                 });
             }
             Message::CollectionEndReload(collection) => {
                 self.reload_collection(collection);
             }
+            Message::CollectionLoadError(error) => {
+                self.view.set_collection_error(error)
+            }
             Message::CollectionEdit => {
                 let path = self.collection_file.path();
                 open::that_detached(path).context("Error opening {path:?}")?;
@@ -206,17 +299,41 @@ impl Tui {
             Message::CopyRequestBody(request_config) => {
                 self.copy_request_body(request_config)?;
             }
-            Message::CopyRequestCurl(request_config) => {
-                self.copy_request_curl(request_config)?;
+            Message::CopyRequestAs {
+                request_config,
+                format,
+            } => {
+                self.copy_request_as(request_config, format)?;
             }
             Message::CopyText(text) => self.view.copy_text(text),
 
+            Message::EditRecipeBody { recipe_id, body } => {
+                self.edit_recipe_body(recipe_id, body)?;
+            }
+            Message::EditQueryTesterBody(body) => {
+                let body = self.edit_text("body", &body)?;
+                self.view.override_query_tester_body(body);
+            }
+            Message::EditRequest { text, channel } => {
+                let result = self.edit_text("request", &text);
+                // The sender may have given up, in which case nobody cares
+                let _ = channel.send(result);
+            }
+
             Message::Error { error } => {
                 self.view.open_modal(error, ModalPriority::High)
             }
 
+            Message::ExplainRequest(request_config) => {
+                self.explain_request(request_config)?;
+            }
+            Message::ExplainRequestResult(explanations) => {
+                self.view.open_modal(explanations, ModalPriority::Low)
+            }
+
             // Manage HTTP life cycle
             Message::HttpBeginRequest(request_config) => {
+                self.last_request = Some(request_config.clone());
                 self.send_request(request_config)?
             }
             Message::HttpBuildError {
@@ -234,14 +351,34 @@ impl Tui {
                 profile_id,
                 recipe_id,
                 request,
+                poll_attempt,
+                wait,
+                progress,
             } => {
+                // Each poll attempt after the first is a new request, so it
+                // needs to restart the request life cycle
+                if poll_attempt.is_some_and(|poll| poll.attempt > 1) {
+                    self.view.set_request_state(
+                        profile_id.clone(),
+                        recipe_id.clone(),
+                        RequestState::building(request.id),
+                    );
+                }
                 self.view.set_request_state(
                     profile_id,
                     recipe_id,
-                    RequestState::loading(request),
+                    RequestState::loading(
+                        request,
+                        poll_attempt,
+                        wait,
+                        progress,
+                    ),
                 );
             }
             Message::HttpComplete(result) => {
+                if let Ok(record) = &result {
+                    self.capture_variables(record);
+                }
                 let (profile_id, recipe_id, state) = match result {
                     Ok(record) => (
                         record.request.profile_id.clone(),
@@ -257,16 +394,57 @@ impl Tui {
                 self.view.set_request_state(profile_id, recipe_id, state);
             }
 
+            Message::HttpNextPage(request) => self.send_built_request(request),
+            Message::HttpReplay { request, edit_body } => {
+                self.replay_request(request, edit_body)?;
+            }
+            Message::HttpRepeatRequest { selected } => {
+                self.repeat_request(selected)?;
+            }
+            Message::HttpStreamStart {
+                recipe_id,
+                feed,
+                stop,
+            } => {
+                let recipes = &self.collection_file.collection.recipes;
+                let name = recipes
+                    .get_recipe(&recipe_id)
+                    .map_or(recipe_id.as_str(), Recipe::name);
+                self.view.open_stream(name, feed, stop);
+            }
+            Message::HttpSchemaViolations(violations) => {
+                self.view.open_modal(violations, ModalPriority::High);
+            }
+
             Message::RequestLoad {
                 profile_id,
                 recipe_id,
             } => {
                 self.load_request(profile_id.as_ref(), &recipe_id)?;
             }
+            Message::RequestDelete {
+                profile_id,
+                recipe_id,
+                request_id,
+            } => {
+                self.confirm_delete_requests(profile_id, recipe_id, request_id)
+            }
+            Message::RequestDeleteConfirmed {
+                profile_id,
+                recipe_id,
+                request_id,
+            } => {
+                self.delete_requests(profile_id, recipe_id, request_id)?;
+            }
+
+            Message::Notify(message) => self.view.notify(message),
 
             Message::PromptStart(prompt) => {
                 self.view.open_modal(prompt, ModalPriority::Low);
             }
+            Message::ConfirmStart(confirm) => {
+                self.view.open_modal(confirm, ModalPriority::Low);
+            }
 
             Message::Quit => self.quit(),
 
@@ -281,6 +459,16 @@ impl Tui {
                     destination,
                 )?;
             }
+            Message::FormatBody {
+                command,
+                body,
+                destination,
+            } => self.spawn(async move {
+                let result = format_body(&command, body.bytes()).await;
+                destination.set(result).map_err(|_| {
+                    anyhow!("Multiple writes to formatted body lock")
+                })
+            }),
         }
         Ok(())
     }
@@ -296,10 +484,17 @@ impl Tui {
     }
 
     /// Spawn a watcher to automatically reload the collection when the file
-    /// changes. Return the watcher because it stops when dropped.
-    fn watch_collection(&self) -> anyhow::Result<impl Watcher> {
+    /// changes. Return the watcher because it stops when dropped. Collections
+    /// from stdin or a URL have nothing to watch, so return `None`.
+    fn watch_collection(&self) -> anyhow::Result<Option<impl Watcher>> {
+        if !self.collection_file.is_on_disk() {
+            return Ok(None);
+        }
+
         // Spawn a watcher for the collection file
         let messages_tx = self.messages_tx.clone();
+        // For a directory, files being added or removed changes the collection
+        let is_dir = self.collection_file.path().is_dir();
         let f = move |result: notify::Result<_>| {
             match result {
                 // Only reload if the file *content* changes
@@ -312,6 +507,17 @@ impl Tui {
                     info!(?event, "Collection file changed, reloading");
                     messages_tx.send(Message::CollectionStartReload);
                 }
+                Ok(
+                    event @ notify::Event {
+                        kind:
+                            notify::EventKind::Create(_)
+                            | notify::EventKind::Remove(_),
+                        ..
+                    },
+                ) if is_dir => {
+                    info!(?event, "Collection file changed, reloading");
+                    messages_tx.send(Message::CollectionStartReload);
+                }
                 // Do nothing for other event kinds
                 Ok(_) => {}
                 Err(err) => {
@@ -322,15 +528,23 @@ impl Tui {
         let mut watcher = notify::recommended_watcher(f)?;
         watcher
             .watch(self.collection_file.path(), RecursiveMode::NonRecursive)?;
+        // Changes to local values should be picked up too. A new local file
+        // won't be noticed until the next launch, which is fine
+        if let Some(local_path) = self.collection_file.local_path() {
+            watcher.watch(&local_path, RecursiveMode::NonRecursive)?;
+        }
         info!(
             path = ?self.collection_file.path(), ?watcher,
             "Watching collection file for changes"
         );
-        Ok(watcher)
+        Ok(Some(watcher))
     }
 
     /// Reload state with a new collection
-    fn reload_collection(&mut self, collection: Collection) {
+    fn reload_collection(&mut self, mut collection: Collection) {
+        collection.merge_global_chains(&TuiContext::get().config.chains);
+        let diff = self.collection_file.collection.diff(&collection);
+        info!(?diff, "Collection changed");
         self.collection_file.collection = collection;
 
         // Rebuild the whole view, because tons of things can change. Drop the
@@ -340,7 +554,7 @@ impl Tui {
             View::new(&self.collection_file.collection)
         });
         self.view.notify(format!(
-            "Reloaded collection from {}",
+            "Reloaded collection from {}: {diff}",
             self.collection_file.path().to_string_lossy()
         ));
     }
@@ -386,10 +600,12 @@ impl Tui {
         Ok(())
     }
 
-    /// Render a request, then copy the equivalent curl command to the clipboard
-    fn copy_request_curl(
+    /// Render a request, then copy the equivalent for a third-party client
+    /// (e.g. a curl command) to the clipboard
+    fn copy_request_as(
         &self,
         request_config: RequestConfig,
+        format: RequestFormat,
     ) -> anyhow::Result<()> {
         let builder = self.get_request_builder(request_config.clone())?;
         let messages_tx = self.messages_tx.clone();
@@ -398,14 +614,110 @@ impl Tui {
             self.template_context(request_config.profile_id, true)?;
         self.spawn(async move {
             let request = builder.build(&template_context).await?;
-            let command = request.to_curl()?;
-            messages_tx.send(Message::CopyText(command));
+            let text = format.generate(&request)?;
+            messages_tx.send(Message::CopyText(text));
+            Ok(())
+        });
+        Ok(())
+    }
+
+    /// Render a request field-by-field, then show where each value came from.
+    /// Prompts use placeholders and chains never trigger requests, since
+    /// this is just for inspection.
+    fn explain_request(
+        &self,
+        request_config: RequestConfig,
+    ) -> anyhow::Result<()> {
+        let builder = self.get_request_builder(request_config.clone())?;
+        let messages_tx = self.messages_tx.clone();
+        let mut template_context =
+            self.template_context(request_config.profile_id, false)?;
+        template_context.http_engine = None;
+        self.spawn(async move {
+            let explanations = builder.explain(&template_context).await;
+            messages_tx.send(Message::ExplainRequestResult(explanations));
             Ok(())
         });
         Ok(())
     }
 
-    /// Launch an HTTP request in a separate task
+    /// Open a recipe body in the user's editor. The TUI is suspended while the
+    /// editor runs, because they both need the terminal. Once the editor exits,
+    /// the edited text replaces the recipe's body for the rest of the session.
+    fn edit_recipe_body(
+        &mut self,
+        recipe_id: RecipeId,
+        body: String,
+    ) -> anyhow::Result<()> {
+        let edited = self.edit_text(&recipe_id, &body)?;
+        let template: Template =
+            edited.try_into().context("Error parsing edited body")?;
+        self.view.override_recipe_body(recipe_id, template);
+        Ok(())
+    }
+
+    /// Open some text in the user's editor, and return the edited text once
+    /// the editor exits. The label is included in the temp file name, to help
+    /// the user identify it.
+    fn edit_text(&mut self, label: &str, body: &str) -> anyhow::Result<String> {
+        let path =
+            env::temp_dir().join(format!("slumber-{label}-{}", Uuid::new_v4()));
+        fs::write(&path, body)
+            .with_context(|| format!("Error writing body to {path:?}"))?;
+
+        let result = self.run_editor(&path).and_then(|()| {
+            fs::read_to_string(&path)
+                .with_context(|| format!("Error reading body from {path:?}"))
+        });
+        // Clean up regardless of the outcome
+        let _ = fs::remove_file(&path)
+            .with_context(|| format!("Error deleting {path:?}"))
+            .traced();
+        let mut edited = result?;
+
+        // Most editors add a trailing newline on save. Strip it, unless the
+        // body already had one, so an unchanged body stays unchanged
+        if !body.ends_with('\n') && edited.ends_with('\n') {
+            edited.pop();
+            if edited.ends_with('\r') {
+                edited.pop();
+            }
+        }
+        Ok(edited)
+    }
+
+    /// Hand the terminal over to the user's editor to edit the given file,
+    /// and block until the editor exits. The terminal is always given back to
+    /// the TUI afterwards, even if the editor fails.
+    fn run_editor(&mut self, path: &Path) -> anyhow::Result<()> {
+        let command = editor_command();
+        let mut parts = command.split_whitespace();
+        let program = parts
+            .next()
+            .ok_or_else(|| anyhow!("Editor command is empty"))?;
+
+        restore_terminal()?;
+        let status = Command::new(program)
+            .args(parts)
+            .arg(path)
+            .status()
+            .with_context(|| format!("Error opening editor `{command}`"));
+        // Take the terminal back *before* checking the editor result. The
+        // buffer is stale now, so force a full redraw
+        take_terminal()?;
+        self.terminal.clear()?;
+
+        let status = status?;
+        if !status.success() {
+            bail!("Editor `{command}` exited with {status}");
+        }
+        Ok(())
+    }
+
+    /// Launch an HTTP request in a separate task. If the recipe has polling
+    /// enabled, the request will be re-sent until the poll condition is met.
+    /// If streaming, it's re-sent after every response until the user stops
+    /// it or a request fails.
     fn send_request(
         &mut self,
         request_config: RequestConfig,
@@ -422,8 +734,30 @@ impl Tui {
         let RequestConfig {
             profile_id,
             recipe_id,
+            headers_only,
+            review,
+            stream,
             ..
         } = request_config;
+        let review = review || TuiContext::get().config.review_requests;
+        let recipe = self
+            .collection_file
+            .collection
+            .recipes
+            .get_recipe(&recipe_id);
+        let poll = recipe.and_then(|recipe| recipe.poll.clone());
+        let confirm = recipe.and_then(|recipe| recipe.confirm);
+        let profile = profile_id.as_ref().and_then(|profile_id| {
+            self.collection_file
+                .collection
+                .profiles
+                .get(profile_id)
+                .cloned()
+        });
+        // There's nothing to validate if we're not loading the body
+        let schema = recipe
+            .and_then(|recipe| recipe.schema.clone())
+            .filter(|_| !headers_only);
 
         // Mark request state as building
         let request_id = builder.id();
@@ -436,30 +770,224 @@ impl Tui {
         // We can't use self.spawn here because HTTP errors are handled
         // differently from all other error types
         tokio::spawn(async move {
+            let report_build_error = |error| {
+                // Report the error, but don't actually return anything
+                messages_tx.send(Message::HttpBuildError {
+                    profile_id: profile_id.clone(),
+                    recipe_id: recipe_id.clone(),
+                    error,
+                });
+            };
+
             // Build the request
-            let request: Arc<Request> = builder
+            let mut request: Arc<Request> = builder
                 .build(&template_context)
                 .await
-                .map_err(|error| {
-                    // Report the error, but don't actually return anything
-                    messages_tx.send(Message::HttpBuildError {
-                        profile_id: profile_id.clone(),
-                        recipe_id: recipe_id.clone(),
-                        error,
-                    });
-                })?
+                .map_err(report_build_error)?
                 .into();
 
-            // Report liftoff
-            messages_tx.send(Message::HttpLoading {
-                profile_id,
-                recipe_id,
-                request: Arc::clone(&request),
-            });
+            // Let the user tweak the request. This goes before confirmation
+            // so the user confirms what will actually be sent
+            if review {
+                let result = async {
+                    let text = request.to_editable()?;
+                    let (tx, rx) = oneshot::channel();
+                    messages_tx
+                        .send(Message::EditRequest { text, channel: tx });
+                    let edited =
+                        rx.await.context("Request edit cancelled")??;
+                    request
+                        .edit(&edited)?
+                        .ok_or_else(|| anyhow!("Request cancelled"))
+                }
+                .await;
+                match result {
+                    Ok(edited) => request = edited.into(),
+                    Err(error) => {
+                        report_build_error(RequestBuildError {
+                            id: request.id,
+                            error,
+                        });
+                        return Err(());
+                    }
+                }
+            }
 
-            // Send the request and report the result to the main thread
-            let result = http_engine.send(request).await;
-            messages_tx.send(Message::HttpComplete(result));
+            // Destructive requests get a chance to be called off. The method
+            // isn't known until the request is built
+            if confirm.unwrap_or_else(|| {
+                TuiContext::get().config.should_confirm(&request.method)
+            }) {
+                let (tx, rx) = oneshot::channel();
+                messages_tx.send(Message::ConfirmStart(Confirm {
+                    message: format!(
+                        "Send {} {}?",
+                        request.method, request.url
+                    ),
+                    profile,
+                    channel: tx,
+                }));
+                // If the modal is dropped without an answer, assume no
+                if !rx.await.unwrap_or(false) {
+                    report_build_error(RequestBuildError {
+                        id: request.id,
+                        error: anyhow!("Request cancelled"),
+                    });
+                    return Err(());
+                }
+            }
+
+            // Render the poll target once, up front. Any failure here is
+            // treated as part of the build
+            let poll = match poll {
+                Some(poll) => {
+                    let expected = poll
+                        .value
+                        .render(&template_context)
+                        .await
+                        .context("Error rendering poll value")
+                        .map_err(|error| {
+                            report_build_error(RequestBuildError {
+                                id: request.id,
+                                error,
+                            })
+                        })?;
+                    Some((poll, expected))
+                }
+                None => None,
+            };
+            // Streaming replaces the recipe's polling, if it has any
+            let poll = poll.filter(|_| !stream);
+            let max_attempts =
+                poll.as_ref().map(|(poll, _)| poll.max_attempts.max(1));
+            let mut stream = if stream {
+                let feed = Arc::<StreamFeed>::default();
+                let (tx, rx) = oneshot::channel();
+                messages_tx.send(Message::HttpStreamStart {
+                    recipe_id: recipe_id.clone(),
+                    feed: Arc::clone(&feed),
+                    stop: tx,
+                });
+                Some((feed, rx))
+            } else {
+                None
+            };
+            let max_attempts = if stream.is_some() {
+                None
+            } else {
+                Some(max_attempts.unwrap_or(1))
+            };
+
+            for attempt in 1..=max_attempts.unwrap_or(u32::MAX) {
+                if attempt > 1 {
+                    // Each attempt gets its own ID, so it's stored separately
+                    // in history
+                    request = request.duplicate().into();
+                }
+
+                // Queue the request first, so we know if it's throttled
+                let queued = http_engine
+                    .clone()
+                    .queue(Arc::clone(&request), headers_only);
+
+                // Report liftoff
+                messages_tx.send(Message::HttpLoading {
+                    profile_id: profile_id.clone(),
+                    recipe_id: recipe_id.clone(),
+                    request: Arc::clone(&request),
+                    poll_attempt: (poll.is_some() || stream.is_some())
+                        .then_some(PollAttempt {
+                            attempt,
+                            max_attempts,
+                        }),
+                    wait: queued.wait,
+                    progress: Arc::clone(&queued.progress),
+                });
+
+                // Send the request and report the result to the main thread.
+                // A stream can be stopped while a request is in flight
+                let result = match &mut stream {
+                    Some((feed, stop)) => {
+                        let start_time = Utc::now();
+                        tokio::select! {
+                            result = queued => result,
+                            _ = stop => {
+                                feed.push("Stream stopped".into());
+                                messages_tx.send(Message::HttpComplete(Err(
+                                    RequestError {
+                                        error: anyhow!("Stream stopped"),
+                                        request,
+                                        start_time,
+                                        end_time: Utc::now(),
+                                    },
+                                )));
+                                return Ok(());
+                            }
+                        }
+                    }
+                    None => queued.await,
+                };
+                if let Some((feed, _)) = &stream {
+                    match &result {
+                        Ok(record) => feed.push_record(record),
+                        Err(error) => feed
+                            .push(format!("Stream stopped: {:#}", error.error)),
+                    }
+                }
+                // Stop polling on error, or once the condition is met
+                let done = match (&poll, &result) {
+                    (Some((poll, expected)), Ok(record)) => {
+                        poll.is_satisfied(&record.response, expected)
+                    }
+                    (None, Ok(_)) => stream.is_none(),
+                    (_, Err(_)) => true,
+                };
+                let validation = match (&schema, &result) {
+                    (Some(schema), Ok(record)) => Some(
+                        schema
+                            .validate(&template_context, &record.response)
+                            .await,
+                    ),
+                    _ => None,
+                };
+                messages_tx.send(Message::HttpComplete(result));
+                // Report validation *after* the response, so it's visible
+                // behind the modal
+                match validation {
+                    Some(Ok(violations)) if violations.is_empty() => {
+                        messages_tx.send(Message::Notify(
+                            "Response matches schema".into(),
+                        ));
+                    }
+                    Some(Ok(violations)) => messages_tx
+                        .send(Message::HttpSchemaViolations(violations)),
+                    Some(Err(error)) => messages_tx.send(Message::Error {
+                        error: error.context("Error validating response"),
+                    }),
+                    None => {}
+                }
+
+                match &poll {
+                    Some(_) if done => {
+                        messages_tx.send(Message::Notify(format!(
+                            "Poll finished after {attempt} attempt(s)"
+                        )));
+                        break;
+                    }
+                    Some((poll, _))
+                        if max_attempts.is_some_and(|max| attempt < max) =>
+                    {
+                        tokio::time::sleep(poll.interval).await;
+                    }
+                    Some(_) => messages_tx.send(Message::Notify(format!(
+                        "Poll condition not met after {} attempts",
+                        max_attempts.unwrap_or_default()
+                    ))),
+                    // Streams keep going until stopped, or a request fails
+                    None if stream.is_some() && done => break,
+                    None => {}
+                }
+            }
 
             // By returning an empty result, we can use `?` to break out early.
             // `return` and `break` don't work in an async block :/
@@ -469,6 +997,104 @@ impl Tui {
         Ok(())
     }
 
+    /// Store session variables from a response, per its recipe's `capture`
+    /// rules
+    fn capture_variables(&self, record: &RequestRecord) {
+        let Some(recipe) = self
+            .collection_file
+            .collection
+            .recipes
+            .get_recipe(&record.request.recipe_id)
+        else {
+            return;
+        };
+        match self.variables.capture(recipe, record) {
+            Ok(captured) if captured.is_empty() => {}
+            Ok(captured) => self.messages_tx.send(Message::Notify(format!(
+                "Captured {}",
+                captured
+                    .iter()
+                    .map(|name| format!("vars.{name}"))
+                    .join(", ")
+            ))),
+            Err(error) => self.messages_tx.send(Message::Error { error }),
+        }
+    }
+
+    /// Re-send the last request sent from a recipe. Unlike a replay, the
+    /// recipe is rendered again, so any edits to the collection apply
+    fn repeat_request(
+        &mut self,
+        selected: Option<RequestConfig>,
+    ) -> anyhow::Result<()> {
+        let Some(last) = self.last_request.clone() else {
+            self.view.notify("No request to repeat yet");
+            return Ok(());
+        };
+        let options = match selected {
+            Some(selected)
+                if selected.profile_id == last.profile_id
+                    && selected.recipe_id == last.recipe_id =>
+            {
+                selected.options
+            }
+            _ => last.options,
+        };
+        let request_config = RequestConfig { options, ..last };
+        self.last_request = Some(request_config.clone());
+        self.send_request(request_config)
+    }
+
+    /// Re-send a request from history, optionally editing its body first.
+    /// The request is sent exactly as it was built originally, without
+    /// re-rendering the recipe.
+    fn replay_request(
+        &mut self,
+        mut request: Request,
+        edit_body: bool,
+    ) -> anyhow::Result<()> {
+        if edit_body {
+            let body = request.body_str()?.unwrap_or_default().to_owned();
+            let edited = self.edit_text(&request.recipe_id, &body)?;
+            // An edited body replaces any command that generated the original
+            request.body_command = None;
+            request.body = Some(edited.into());
+        }
+        self.send_built_request(request);
+        Ok(())
+    }
+
+    /// Send a request that's already been built, skipping the build step
+    fn send_built_request(&mut self, request: Request) {
+        let http_engine = self.http_engine.clone();
+        let messages_tx = self.messages_tx.clone();
+        let request = Arc::new(request);
+
+        let profile_id = request.profile_id.clone();
+        let recipe_id = request.recipe_id.clone();
+        self.view.set_request_state(
+            profile_id.clone(),
+            recipe_id.clone(),
+            RequestState::building(request.id),
+        );
+        let queued = http_engine.queue(Arc::clone(&request), false);
+        self.view.set_request_state(
+            profile_id,
+            recipe_id,
+            RequestState::loading(
+                request,
+                None,
+                queued.wait,
+                Arc::clone(&queued.progress),
+            ),
+        );
+
+        tokio::spawn(async move {
+            let result = queued.await;
+            messages_tx.send(Message::HttpComplete(result));
+        });
+    }
+
     /// Load the most recent request+response for a particular recipe from the
     /// database, and store it in state.
     fn load_request(
@@ -488,6 +1114,77 @@ impl Tui {
         Ok(())
     }
 
+    /// Ask the user before deleting requests from history, since there's no
+    /// getting them back. See [Message::RequestDelete]
+    fn confirm_delete_requests(
+        &self,
+        profile_id: Option<ProfileId>,
+        recipe_id: RecipeId,
+        request_id: Option<RequestId>,
+    ) {
+        let collection = &self.collection_file.collection;
+        let message = match request_id {
+            Some(_) => "Delete this request from history?".to_owned(),
+            None => {
+                let name = collection
+                    .recipes
+                    .get_recipe(&recipe_id)
+                    .map_or(recipe_id.as_str(), Recipe::name);
+                format!("Delete all requests for `{name}` from history?")
+            }
+        };
+        let profile = profile_id
+            .as_ref()
+            .and_then(|profile_id| collection.profiles.get(profile_id))
+            .cloned();
+        let messages_tx = self.messages_tx.clone();
+        tokio::spawn(async move {
+            let (tx, rx) = oneshot::channel();
+            messages_tx.send(Message::ConfirmStart(Confirm {
+                message,
+                profile,
+                channel: tx,
+            }));
+            // If the modal is dropped without an answer, assume no
+            if rx.await.unwrap_or(false) {
+                messages_tx.send(Message::RequestDeleteConfirmed {
+                    profile_id,
+                    recipe_id,
+                    request_id,
+                });
+            }
+        });
+    }
+
+    /// Delete requests from history, then show the most recent request that's
+    /// left in their place. See [Message::RequestDelete]
+    fn delete_requests(
+        &mut self,
+        profile_id: Option<ProfileId>,
+        recipe_id: RecipeId,
+        request_id: Option<RequestId>,
+    ) -> anyhow::Result<()> {
+        let deleted = match request_id {
+            Some(request_id) => {
+                self.database.delete_request(request_id)? as usize
+            }
+            None => self
+                .database
+                .delete_requests(profile_id.as_ref(), &recipe_id)?,
+        };
+        self.view.clear_request_state(
+            profile_id.clone(),
+            recipe_id.clone(),
+            request_id,
+        );
+        self.load_request(profile_id.as_ref(), &recipe_id)?;
+        self.view.notify(format!(
+            "Deleted {deleted} request{} from history",
+            if deleted == 1 { "" } else { "s" }
+        ));
+        Ok(())
+    }
+
     /// Helper to create a [RequestBuilder] based on request parameters
     fn get_request_builder(
         &self,
@@ -564,6 +1261,12 @@ impl Tui {
             database: self.database.clone(),
             overrides: Default::default(),
             prompter,
+            chain_choices: self.chain_choices.clone(),
+            random: Default::default(),
+            variables: self.variables.clone(),
+            path_root: TuiContext::get()
+                .config
+                .path_root(&self.collection_file),
             recursion_count: Default::default(),
         })
     }
@@ -578,32 +1281,156 @@ impl Drop for Tui {
     }
 }
 
-/// Restore terminal state during a panic
+/// Log panics to the log file, and restore terminal state when the TUI
+/// thread panics. The panic then unwinds through the [Tui], so persisted UI
+/// state is still saved as the view is dropped.
+///
+/// Panics in background tasks are caught by tokio and don't take down the
+/// TUI, so for those we *only* log. Restoring the terminal or printing to
+/// stderr would trash the still-running TUI.
 fn initialize_panic_handler() {
     let original_hook = std::panic::take_hook();
+    let tui_thread = thread::current().id();
     std::panic::set_hook(Box::new(move |panic_info| {
-        restore_terminal().unwrap();
-        original_hook(panic_info);
+        let backtrace = Backtrace::force_capture();
+        error!(%panic_info, %backtrace, "Panic");
+        if thread::current().id() == tui_thread {
+            // Don't unwrap, because panicking within the hook aborts
+            if let Err(err) = restore_terminal() {
+                error!(error = err.deref(), "Error restoring terminal");
+            }
+            original_hook(panic_info);
+        }
     }));
 }
 
 /// Set up terminal for TUI
 fn initialize_terminal() -> anyhow::Result<Term> {
-    crossterm::terminal::enable_raw_mode()?;
-    let mut stdout = io::stdout();
-    crossterm::execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
-    let backend = CrosstermBackend::new(stdout);
+    take_terminal()?;
+    let backend = CrosstermBackend::new(io::stdout());
     Ok(Terminal::new(backend)?)
 }
 
-/// Return terminal to initial state
+/// Put the terminal into the state the TUI needs. This is the inverse of
+/// [restore_terminal]
+fn take_terminal() -> anyhow::Result<()> {
+    crossterm::terminal::enable_raw_mode()?;
+    crossterm::execute!(
+        io::stdout(),
+        EnterAlternateScreen,
+        EnableMouseCapture
+    )?;
+    Ok(())
+}
+
+/// Ask the user to pick one of their recently opened collections, or enter the
+/// path to another one. This runs before the TUI takes over the terminal.
+/// Returns `None` if there are no recent collections to pick from.
+fn select_recent_collection() -> anyhow::Result<Option<PathBuf>> {
+    /// Maximum number of collections to offer
+    const MAX_RECENT: usize = 10;
+
+    let recent = Database::load()?
+        .recent_collections(MAX_RECENT)?
+        .into_iter()
+        // Collections may have been moved or deleted since
+        .filter(|path| path.exists())
+        .collect_vec();
+    if recent.is_empty() {
+        return Ok(None);
+    }
+
+    let mut items = recent
+        .iter()
+        .map(|path| path.display().to_string())
+        .collect_vec();
+    items.push("Other...".into());
+    let selection = Select::new()
+        .with_prompt("No collection file found. Open a recent collection")
+        .items(&items)
+        .default(0)
+        .interact()
+        .context("Error selecting collection")?;
+
+    match recent.into_iter().nth(selection) {
+        Some(path) => Ok(Some(path)),
+        None => {
+            let path: String = Input::new()
+                .with_prompt("Path to collection file or directory")
+                .interact_text()
+                .context("Error reading collection path")?;
+            Ok(Some(path.into()))
+        }
+    }
+}
+
+/// Get the command to use for editing files. Like most CLI tools, we check
+/// `$VISUAL`, then `$EDITOR`, then fall back to `vi`. The command may include
+/// arguments, e.g. `code --wait`.
+fn editor_command() -> String {
+    ["VISUAL", "EDITOR"]
+        .into_iter()
+        .filter_map(|var| env::var(var).ok())
+        .find(|command| !command.trim().is_empty())
+        .unwrap_or_else(|| "vi".to_owned())
+}
+
+/// Pipe a body through a formatter command, and get its stdout. Fails if the
+/// command fails or its output isn't UTF-8, so the caller can fall back to
+/// the raw body.
+async fn format_body(
+    command: &[String],
+    body: &[u8],
+) -> anyhow::Result<String> {
+    let [program, args @ ..] = command else {
+        bail!("Formatter command is empty");
+    };
+    info!(?command, "Formatting body");
+    let mut child = tokio::process::Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .with_context(|| format!("Error executing formatter `{program}`"))?;
+    let mut stdin = child.stdin.take().expect("Child stdin not piped");
+    // Write and read at the same time, so a large body can't deadlock on a
+    // full pipe. If the command exits without reading all its input, the
+    // write fails, but the exit status is what matters.
+    let (_, output) = tokio::join!(
+        async move {
+            let result = stdin.write_all(body).await;
+            drop(stdin); // Close stdin so the command sees EOF
+            result
+        },
+        child.wait_with_output(),
+    );
+    let output = output
+        .with_context(|| format!("Error executing formatter `{program}`"))?;
+    if !output.status.success() {
+        bail!(
+            "Formatter `{program}` failed ({}): {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    String::from_utf8(output.stdout).with_context(|| {
+        format!("Output of formatter `{program}` is not valid UTF-8")
+    })
+}
+
+/// Return terminal to initial state. Both steps are always attempted, so one
+/// failing doesn't leave the terminal half-restored
 fn restore_terminal() -> anyhow::Result<()> {
     debug!("Restoring terminal");
-    crossterm::terminal::disable_raw_mode()?;
-    crossterm::execute!(
+    let raw_mode = crossterm::terminal::disable_raw_mode();
+    let screen = crossterm::execute!(
         io::stdout(),
         LeaveAlternateScreen,
         DisableMouseCapture
-    )?;
+    );
+    raw_mode?;
+    screen?;
     Ok(())
 }
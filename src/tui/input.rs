@@ -182,6 +182,11 @@ impl Default for InputEngine {
                     code: KeyCode::Char('c'),
                     modifiers: KeyModifiers::CONTROL,
                 }.into(),
+                // Has to come before `r`, which would also match
+                Action::ToggleReveal => KeyCombination {
+                    code: KeyCode::Char('r'),
+                    modifiers: KeyModifiers::CONTROL,
+                }.into(),
                 Action::ScrollLeft => KeyCombination {
                     code: KeyCode::Left,
                     modifiers: KeyModifiers::SHIFT,
@@ -193,7 +198,14 @@ impl Default for InputEngine {
                 Action::OpenActions => KeyCode::Char('x').into(),
                 Action::OpenHelp => KeyCode::Char('?').into(),
                 Action::Fullscreen => KeyCode::Char('f').into(),
+                Action::ToggleLayout => KeyCode::Char('v').into(),
+                Action::ToggleTable => KeyCode::Char('t').into(),
+                Action::DeleteRequest => KeyCode::Delete.into(),
                 Action::ReloadCollection => KeyCode::F(5).into(),
+                Action::Edit => KeyCode::Char('e').into(),
+                Action::TogglePin => KeyCode::Char('*').into(),
+                Action::CopyValue => KeyCode::Char('y').into(),
+                Action::CopyPath => KeyCode::Char('Y').into(),
                 Action::Search => KeyCode::Char('/').into(),
                 Action::PreviousPane => KeyCode::BackTab.into(),
                 Action::NextPane => KeyCode::Tab.into(),
@@ -206,6 +218,7 @@ impl Default for InputEngine {
                 Action::Home => KeyCode::Home.into(),
                 Action::End => KeyCode::End.into(),
                 Action::Submit => KeyCode::Enter.into(),
+                Action::RepeatRequest => KeyCode::Char('R').into(),
                 Action::Cancel => KeyCode::Esc.into(),
                 Action::SelectProfileList => KeyCode::Char('p').into(),
                 Action::SelectRecipeList => KeyCode::Char('l').into(),
@@ -272,6 +285,9 @@ pub enum Action {
     /// Do a thing, e.g. submit a modal. Alternatively, send a request
     #[display("Send Request/Submit")]
     Submit,
+    /// Re-send the most recently sent request, regardless of what's selected
+    #[display("Repeat Last Request")]
+    RepeatRequest,
     /// Close the current modal/dialog/etc.
     Cancel,
     /// Start a search/filter operation
@@ -280,8 +296,31 @@ pub enum Action {
     /// Force a collection reload (typically it's automatic)
     #[display("Reload Collection")]
     ReloadCollection,
+    /// Open the selected content (e.g. a recipe body) in an external editor
+    Edit,
+    /// Pin/unpin the selected recipe to the top of the recipe list
+    #[display("Pin/Unpin Recipe")]
+    TogglePin,
+    /// Show/hide the value in a sensitive text box, e.g. a password prompt
+    #[display("Show/Hide Sensitive Input")]
+    ToggleReveal,
+    /// Copy the JSON value under the cursor in a body
+    #[display("Copy Value")]
+    CopyValue,
+    /// Copy the JSONPath of the value under the cursor in a body
+    #[display("Copy JSONPath")]
+    CopyPath,
     /// Embiggen a pane
     Fullscreen,
+    /// Cycle the request/response panes between split, stacked, and tabbed
+    #[display("Toggle Layout")]
+    ToggleLayout,
+    /// Switch a JSON body between text and a table of its objects
+    #[display("Toggle Table View")]
+    ToggleTable,
+    /// Delete the selected request from history
+    #[display("Delete Request")]
+    DeleteRequest,
     /// Open the actions modal
     #[display("Actions")]
     OpenActions,
@@ -2,17 +2,23 @@
 //! state updates.
 
 use crate::{
-    collection::{Collection, ProfileId, RecipeId},
+    collection::{Collection, Profile, ProfileId, RecipeId},
     http::{
-        RecipeOptions, Request, RequestBuildError, RequestError, RequestRecord,
+        Body, FieldExplanation, RecipeOptions, Request, RequestBuildError,
+        RequestError, RequestFormat, RequestId, RequestProgress, RequestRecord,
+        SchemaViolation,
     },
     template::{Prompt, Prompter, Template, TemplateChunk},
+    tui::view::{PollAttempt, StreamFeed},
     util::ResultExt,
 };
 use anyhow::Context;
 use derive_more::From;
-use std::sync::{Arc, OnceLock};
-use tokio::sync::mpsc::UnboundedSender;
+use std::{
+    sync::{Arc, OnceLock},
+    time::Duration,
+};
+use tokio::sync::{mpsc::UnboundedSender, oneshot};
 use tracing::trace;
 
 /// Wrapper around a sender for async messages. Cheap to clone and pass around
@@ -55,6 +61,8 @@ pub enum Message {
     CollectionStartReload,
     /// Store a reloaded collection value in state
     CollectionEndReload(Collection),
+    /// Loading the collection failed. Show the error until the next reload
+    CollectionLoadError(anyhow::Error),
     /// Open the collection in the user's editor
     CollectionEdit,
 
@@ -62,14 +70,39 @@ pub enum Message {
     CopyRequestUrl(RequestConfig),
     /// Render request body from a recipe, then copy rendered text
     CopyRequestBody(RequestConfig),
-    /// Render request, then generate an equivalent cURL command and copy it
-    CopyRequestCurl(RequestConfig),
+    /// Render request, then generate an equivalent for a third-party client
+    /// (e.g. cURL) and copy it
+    CopyRequestAs {
+        request_config: RequestConfig,
+        format: RequestFormat,
+    },
     /// Copy some text to the clipboard
     CopyText(String),
 
+    /// Open a recipe body in the user's `$EDITOR`. When the editor exits, the
+    /// edited text will be used as the recipe's body for the rest of the
+    /// session.
+    EditRecipeBody { recipe_id: RecipeId, body: String },
+
+    /// Open the body of the JSONPath tester in the user's editor. The edited
+    /// text replaces the tester's body.
+    EditQueryTesterBody(String),
+
+    /// Open a built request in the user's editor, before it's sent. The
+    /// edited text is sent back on the channel
+    EditRequest {
+        text: String,
+        channel: oneshot::Sender<anyhow::Result<String>>,
+    },
+
     /// An error occurred in some async process and should be shown to the user
     Error { error: anyhow::Error },
 
+    /// Render a request field-by-field, tracking where each value came from
+    ExplainRequest(RequestConfig),
+    /// Show the explanation of a rendered request
+    ExplainRequestResult(Vec<FieldExplanation>),
+
     /// Launch an HTTP request from the given recipe/profile.
     HttpBeginRequest(RequestConfig),
     /// Request failed to build
@@ -83,15 +116,49 @@ pub enum Message {
         profile_id: Option<ProfileId>,
         recipe_id: RecipeId,
         request: Arc<Request>,
+        /// Populated iff the recipe is being polled or streamed
+        poll_attempt: Option<PollAttempt>,
+        /// How long the request is waiting under a rate limit before it's
+        /// actually sent
+        wait: Option<Duration>,
+        /// Upload/download progress, updated by the HTTP engine
+        progress: Arc<RequestProgress>,
     },
     /// The HTTP request either succeeded or failed. We don't need to store the
     /// recipe ID here because it's in the inner container already. Combining
     /// these two cases saves a bit of boilerplate.
     HttpComplete(Result<RequestRecord, RequestError>),
+    /// A streamed recipe has started its request loop. Open a feed of its
+    /// responses, which stops the loop when it's closed
+    HttpStreamStart {
+        recipe_id: RecipeId,
+        feed: Arc<StreamFeed>,
+        /// Dropped to stop the stream
+        stop: oneshot::Sender<()>,
+    },
+    /// A response didn't match its recipe's schema
+    HttpSchemaViolations(Vec<SchemaViolation>),
+    /// Send a request for the next page of a paginated response. The request
+    /// is already built from the previous one, so this skips the build step.
+    HttpNextPage(Request),
+    /// Re-send a request from history exactly as it was built, without
+    /// re-rendering its recipe. The request should already have a new ID. If
+    /// `edit_body` is enabled, the body is opened in the user's editor first.
+    HttpReplay { request: Request, edit_body: bool },
+    /// Re-send the last request that was sent from a recipe, re-rendering the
+    /// recipe so it picks up any changes since. `selected` is the config for
+    /// the current selection; if it's the same recipe/profile, its options
+    /// are used instead of the old ones, so new overrides are included.
+    HttpRepeatRequest { selected: Option<RequestConfig> },
+
+    /// Show an informational notification to the user
+    Notify(String),
 
     /// Show a prompt to the user, asking for some input. Use the included
     /// channel to return the value.
     PromptStart(Prompt),
+    /// Ask the user a yes/no question
+    ConfirmStart(Confirm),
 
     /// Exit the program
     Quit,
@@ -101,6 +168,20 @@ pub enum Message {
         profile_id: Option<ProfileId>,
         recipe_id: RecipeId,
     },
+    /// Ask the user to confirm deleting requests from history, then delete
+    /// them. If `request_id` is `None`, delete every request for the
+    /// profile+recipe.
+    RequestDelete {
+        profile_id: Option<ProfileId>,
+        recipe_id: RecipeId,
+        request_id: Option<RequestId>,
+    },
+    /// The user confirmed a [Message::RequestDelete], so go ahead with it
+    RequestDeleteConfirmed {
+        profile_id: Option<ProfileId>,
+        recipe_id: RecipeId,
+        request_id: Option<RequestId>,
+    },
 
     /// Render a template string, to be previewed in the UI. Ideally this could
     /// be launched directly by the component that needs it, but only the
@@ -115,6 +196,14 @@ pub enum Message {
         profile_id: Option<ProfileId>,
         destination: Arc<OnceLock<Vec<TemplateChunk>>>,
     },
+
+    /// Pipe a response body through a user-configured formatter command. The
+    /// formatted text, or the error, will be written back to the given cell.
+    FormatBody {
+        command: Vec<String>,
+        body: Body,
+        destination: Arc<OnceLock<anyhow::Result<String>>>,
+    },
 }
 
 /// Configuration that defines how to render a request
@@ -123,4 +212,25 @@ pub struct RequestConfig {
     pub profile_id: Option<ProfileId>,
     pub recipe_id: RecipeId,
     pub options: RecipeOptions,
+    /// Discard the response body, and only load status and headers. Only
+    /// relevant when sending the request
+    pub headers_only: bool,
+    /// Let the user edit the built request before it's sent. Only relevant
+    /// when sending the request
+    pub review: bool,
+    /// Re-send the request as soon as each response arrives, until the user
+    /// stops it. Only relevant when sending the request
+    pub stream: bool,
+}
+
+/// A yes/no question for the user. The answer is sent back on the channel
+#[derive(Debug)]
+pub struct Confirm {
+    /// Question to ask the user
+    pub message: String,
+    /// Profile the question relates to. If it's flagged, its badge is shown
+    /// with the question, e.g. so the user knows a request is going to prod
+    pub profile: Option<Profile>,
+    /// How the answer gets back to the asker
+    pub channel: oneshot::Sender<bool>,
 }
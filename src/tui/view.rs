@@ -7,18 +7,20 @@ mod theme;
 mod util;
 
 pub use common::modal::{IntoModal, ModalPriority};
-pub use state::RequestState;
+pub use state::{PollAttempt, RequestState, StreamFeed};
 pub use theme::Theme;
 pub use util::PreviewPrompter;
 
 use crate::{
     collection::{Collection, ProfileId, RecipeId},
+    http::RequestId,
+    template::Template,
     tui::{
         context::TuiContext,
         input::Action,
         message::Message,
         view::{
-            component::{Component, Root},
+            component::{Component, Root, StreamModal},
             draw::Draw,
             event::{Event, EventHandler, EventQueue, Update},
             state::Notification,
@@ -27,7 +29,8 @@ use crate::{
 };
 use anyhow::anyhow;
 use ratatui::Frame;
-use std::fmt::Debug;
+use std::{fmt::Debug, sync::Arc};
+use tokio::sync::oneshot;
 use tracing::{error, trace, trace_span};
 
 /// Primary entrypoint for the view. This contains the main draw functions, as
@@ -53,6 +56,18 @@ impl View {
         }
     }
 
+    /// Select a profile and/or recipe. Unlike most external updates, this is
+    /// applied immediately rather than queued, because it's used to set up the
+    /// initial state of the view. The caller should validate the IDs first;
+    /// unknown IDs are ignored.
+    pub fn select(
+        &mut self,
+        profile_id: Option<&ProfileId>,
+        recipe_id: Option<&RecipeId>,
+    ) {
+        self.root.select(profile_id, recipe_id);
+    }
+
     /// Draw the view to screen. This needs access to the input engine in order
     /// to render input bindings as help messages to the user.
     pub fn draw<'a>(&'a self, frame: &'a mut Frame) {
@@ -77,6 +92,43 @@ impl View {
         });
     }
 
+    /// Queue an event to drop the request state for the given profile+recipe,
+    /// after it was deleted from history. See [Event::HttpClearState]
+    pub fn clear_request_state(
+        &mut self,
+        profile_id: Option<ProfileId>,
+        recipe_id: RecipeId,
+        request_id: Option<RequestId>,
+    ) {
+        EventQueue::push(Event::HttpClearState {
+            profile_id,
+            recipe_id,
+            request_id,
+        });
+    }
+
+    /// Queue an event to replace a recipe's body with the given template, for
+    /// the rest of the session
+    pub fn override_recipe_body(
+        &mut self,
+        recipe_id: RecipeId,
+        body: Template,
+    ) {
+        EventQueue::push(Event::OverrideRecipeBody { recipe_id, body });
+    }
+
+    /// Queue an event to replace the body in the JSONPath tester
+    pub fn override_query_tester_body(&mut self, body: String) {
+        EventQueue::push(Event::OverrideQueryTesterBody(body));
+    }
+
+    /// Queue an event to show an error from loading the collection. The error
+    /// takes over the main screen, so it's hard to miss. It's cleared when the
+    /// view is rebuilt for a successful reload.
+    pub fn set_collection_error(&mut self, error: anyhow::Error) {
+        EventQueue::push(Event::CollectionError(error));
+    }
+
     /// Queue an event to open a new modal. The input can be anything that
     /// converts to modal content
     pub fn open_modal(
@@ -90,6 +142,20 @@ impl View {
         });
     }
 
+    /// Queue an event to open the live feed for a streamed recipe. Closing it
+    /// drops `stop`, which ends the stream.
+    pub fn open_stream(
+        &mut self,
+        recipe_name: &str,
+        feed: Arc<StreamFeed>,
+        stop: oneshot::Sender<()>,
+    ) {
+        EventQueue::open_modal(
+            StreamModal::new(recipe_name, feed, stop),
+            ModalPriority::Low,
+        );
+    }
+
     /// Queue an event to send an informational notification to the user
     pub fn notify(&mut self, message: impl ToString) {
         let notification = Notification::new(message.to_string());
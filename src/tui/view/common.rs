@@ -16,7 +16,7 @@ use crate::{
     http::{RequestBuildError, RequestError},
     tui::{
         context::TuiContext,
-        view::{draw::Generate, state::Notification},
+        view::{draw::Generate, state::Notification, theme::Theme},
     },
     util::MaybeStr,
 };
@@ -105,6 +105,28 @@ impl Generate for &Profile {
     }
 }
 
+/// Badge calling out a flagged profile (one with a color or label), so it's
+/// obvious which environment requests will go to. Shows the label, or the
+/// profile name if there's no label.
+pub struct ProfileBadge<'a> {
+    pub profile: &'a Profile,
+}
+
+impl<'a> Generate for ProfileBadge<'a> {
+    type Output<'this> = Span<'this> where Self: 'this;
+
+    fn generate<'this>(self) -> Self::Output<'this>
+    where
+        Self: 'this,
+    {
+        let text = self.profile.label.as_deref().unwrap_or(self.profile.name());
+        Span::styled(
+            format!(" {text} "),
+            Theme::profile_badge(self.profile.color),
+        )
+    }
+}
+
 impl Generate for &Notification {
     type Output<'this> = Span<'this> where Self: 'this;
 
@@ -6,6 +6,8 @@ use reqwest::header::HeaderMap;
 /// Render HTTP request/response headers in a table
 pub struct HeaderTable<'a> {
     pub headers: &'a HeaderMap,
+    /// Name of the first column, to distinguish headers from trailers
+    pub label: &'a str,
 }
 
 impl<'a> Generate for HeaderTable<'a> {
@@ -21,7 +23,7 @@ impl<'a> Generate for HeaderTable<'a> {
                 .iter()
                 .map(|(k, v)| [Text::from(k.as_str()), v.generate().into()])
                 .collect_vec(),
-            header: Some(["Header", "Value"]),
+            header: Some([self.label, "Value"]),
             alternate_row_style: true,
             ..Default::default()
         }
@@ -35,6 +35,10 @@ where
     pub fn selected(&self) -> &T {
         self.tabs.selected()
     }
+
+    pub fn select(&mut self, tab: &T) {
+        self.tabs.select(tab)
+    }
 }
 
 impl<T> EventHandler for Tabs<T>
@@ -1,6 +1,6 @@
 use crate::{
     collection::ProfileId,
-    template::{Template, TemplateChunk},
+    template::{Template, TemplateChunk, TemplateError},
     tui::{context::TuiContext, message::Message, view::draw::Generate},
 };
 use ratatui::{
@@ -38,24 +38,53 @@ impl TemplatePreview {
     /// defines which profile to use for the render.
     pub fn new(template: Template, profile_id: Option<ProfileId>) -> Self {
         if TuiContext::get().config.preview_templates {
-            // Tell the controller to start rendering the preview, and it'll
-            // store it back here when done
-            let lock = Arc::new(OnceLock::new());
-            TuiContext::send_message(Message::TemplatePreview {
-                // If this is a bottleneck we can Arc it
-                template: template.clone(),
-                profile_id,
-                destination: Arc::clone(&lock),
-            });
-
-            Self::Enabled {
-                template,
-                chunks: lock,
-            }
+            Self::rendered(template, profile_id)
         } else {
             Self::Disabled { template }
         }
     }
+
+    /// Create a template preview that's always rendered, regardless of the
+    /// `preview_templates` setting. Use this when the user explicitly asked
+    /// to see the rendered value.
+    pub fn rendered(template: Template, profile_id: Option<ProfileId>) -> Self {
+        // Tell the controller to start rendering the preview, and it'll store
+        // it back here when done
+        let lock = Arc::new(OnceLock::new());
+        TuiContext::send_message(Message::TemplatePreview {
+            // If this is a bottleneck we can Arc it
+            template: template.clone(),
+            profile_id,
+            destination: Arc::clone(&lock),
+        });
+
+        Self::Enabled {
+            template,
+            chunks: lock,
+        }
+    }
+
+    /// Get the raw template being previewed
+    pub fn template(&self) -> &Template {
+        match self {
+            Self::Disabled { template } | Self::Enabled { template, .. } => {
+                template
+            }
+        }
+    }
+
+    /// Get the first error from rendering, if the render is done and failed
+    pub fn error(&self) -> Option<&TemplateError> {
+        match self {
+            Self::Disabled { .. } => None,
+            Self::Enabled { chunks, .. } => {
+                chunks.get()?.iter().find_map(|chunk| match chunk {
+                    TemplateChunk::Error(error) => Some(error),
+                    _ => None,
+                })
+            }
+        }
+    }
 }
 
 impl Generate for &TemplatePreview {
@@ -22,11 +22,15 @@ use ratatui::{
 pub struct TextBox {
     // Parameters
     sensitive: bool,
+    /// Show sensitive text anyway, while the user checks what they typed
+    revealed: bool,
     focused: bool,
     placeholder_text: String,
     /// Predicate function to apply visual validation effect
     #[debug(skip)]
     validator: Option<Validator>,
+    /// Should invalid text be rejected on submit, rather than just styled?
+    strict: bool,
 
     state: TextState,
 
@@ -45,9 +49,11 @@ impl Default for TextBox {
     fn default() -> Self {
         Self {
             sensitive: false,
+            revealed: false,
             focused: true,
             placeholder_text: Default::default(),
             validator: None,
+            strict: false,
 
             state: Default::default(),
             on_submit: Default::default(),
@@ -91,6 +97,13 @@ impl TextBox {
         self
     }
 
+    /// Refuse to submit text that fails the validator, including empty text.
+    /// The text box stays focused so the user can fix it.
+    pub fn with_strict_validation(mut self) -> Self {
+        self.strict = true;
+        self
+    }
+
     /// Set the callback to be called when the user hits escape
     pub fn with_on_cancel(
         mut self,
@@ -134,9 +147,9 @@ impl TextBox {
     }
 
     /// Check if the current input text is valid. Always returns true if there
-    /// is no validator
-    fn is_valid(&self) -> bool {
-        self.text().is_empty()
+    /// is no validator. Empty text is only checked in strict mode.
+    pub fn is_valid(&self) -> bool {
+        (self.text().is_empty() && !self.strict)
             || self
                 .validator
                 .as_ref()
@@ -146,6 +159,9 @@ impl TextBox {
 
     /// Call parent's submissionc callback
     fn submit(&mut self) {
+        if self.strict && !self.is_valid() {
+            return;
+        }
         if let Some(on_submit) = &self.on_submit {
             on_submit(self);
         }
@@ -198,6 +214,10 @@ impl EventHandler for TextBox {
                 action: Some(Action::Cancel),
                 ..
             } => self.cancel(),
+            Event::Input {
+                action: Some(Action::ToggleReveal),
+                ..
+            } if self.sensitive => self.revealed = !self.revealed,
             Event::Input {
                 action: Some(Action::LeftClick),
                 ..
@@ -221,7 +241,7 @@ impl Draw for TextBox {
             Line::from(self.placeholder_text.as_str())
                 .style(theme.text_box.placeholder)
                 .into()
-        } else if self.sensitive {
+        } else if self.sensitive && !self.revealed {
             Masked::new(&self.state.text, '•').into()
         } else {
             self.state.text.as_str().into()
@@ -21,12 +21,17 @@ use std::{cell::Cell, cmp, fmt::Debug};
 ///
 /// The generic parameter allows for any type that can be converted to ratatui's
 /// `Text`, e.g. `String` or `TemplatePreview`.
+///
+/// Optionally, the window can have a line cursor. When enabled, vertical
+/// navigation moves the cursor instead, and the window scrolls to follow it.
 #[derive(derive_more::Debug, Default)]
 pub struct TextWindow<T> {
     #[debug(skip)]
     text: T,
     offset_x: u16,
     offset_y: u16,
+    /// Selected line, if the cursor is enabled
+    cursor: Option<u16>,
     text_width: Cell<u16>,
     text_height: Cell<u16>,
     window_width: Cell<u16>,
@@ -39,6 +44,7 @@ impl<T> TextWindow<T> {
             text,
             offset_x: 0,
             offset_y: 0,
+            cursor: None,
             text_width: Cell::default(),
             text_height: Cell::default(),
             window_width: Cell::default(),
@@ -46,10 +52,21 @@ impl<T> TextWindow<T> {
         }
     }
 
+    /// Enable the line cursor
+    pub fn with_cursor(mut self) -> Self {
+        self.cursor = Some(0);
+        self
+    }
+
     pub fn text(&self) -> &T {
         &self.text
     }
 
+    /// Get the line index of the cursor, if it's enabled
+    pub fn cursor(&self) -> Option<u16> {
+        self.cursor
+    }
+
     /// Get the final line that we can't scroll past. This will be the first
     /// line of the last page of text
     fn max_scroll_line(&self) -> u16 {
@@ -68,16 +85,63 @@ impl<T> TextWindow<T> {
 
     fn scroll_up(&mut self, lines: u16) {
         self.offset_y = self.offset_y.saturating_sub(lines);
+        self.clamp_cursor();
     }
 
     fn scroll_down(&mut self, lines: u16) {
         self.offset_y = cmp::min(self.offset_y + lines, self.max_scroll_line());
+        self.clamp_cursor();
     }
 
     /// Scroll to a specific line number. The target line will end up as close
     /// to the top of the page as possible
     fn scroll_to(&mut self, line: u16) {
         self.offset_y = cmp::min(line, self.max_scroll_line());
+        self.clamp_cursor();
+    }
+
+    /// Move the cursor up, if it's enabled. Otherwise, scroll up
+    fn cursor_up(&mut self, lines: u16) {
+        match self.cursor {
+            Some(cursor) => self.cursor_to(cursor.saturating_sub(lines)),
+            None => self.scroll_up(lines),
+        }
+    }
+
+    /// Move the cursor down, if it's enabled. Otherwise, scroll down
+    fn cursor_down(&mut self, lines: u16) {
+        match self.cursor {
+            Some(cursor) => self.cursor_to(cursor.saturating_add(lines)),
+            None => self.scroll_down(lines),
+        }
+    }
+
+    /// Move the cursor to a specific line, if it's enabled, and scroll just
+    /// enough to keep it in view. Otherwise, scroll to that line
+    fn cursor_to(&mut self, line: u16) {
+        if self.cursor.is_none() {
+            self.scroll_to(line);
+            return;
+        }
+
+        let cursor = cmp::min(line, self.text_height.get().saturating_sub(1));
+        self.cursor = Some(cursor);
+        if cursor < self.offset_y {
+            self.offset_y = cursor;
+        } else if cursor >= self.offset_y + self.window_height.get() {
+            self.offset_y =
+                (cursor + 1).saturating_sub(self.window_height.get());
+        }
+    }
+
+    /// After scrolling, make sure the cursor is still in view
+    fn clamp_cursor(&mut self) {
+        if let Some(cursor) = &mut self.cursor {
+            let last_visible = (self.offset_y + self.window_height.get())
+                .saturating_sub(1)
+                .max(self.offset_y);
+            *cursor = (*cursor).clamp(self.offset_y, last_visible);
+        }
     }
 
     fn scroll_left(&mut self, columns: u16) {
@@ -97,14 +161,16 @@ impl<T: Debug> EventHandler for TextWindow<T> {
                 action: Some(action),
                 ..
             } => match action {
-                Action::Up | Action::ScrollUp => self.scroll_up(1),
-                Action::Down | Action::ScrollDown => self.scroll_down(1),
+                Action::Up => self.cursor_up(1),
+                Action::Down => self.cursor_down(1),
+                Action::ScrollUp => self.scroll_up(1),
+                Action::ScrollDown => self.scroll_down(1),
                 Action::ScrollLeft => self.scroll_left(1),
                 Action::ScrollRight => self.scroll_right(1),
-                Action::PageUp => self.scroll_up(self.window_height.get()),
-                Action::PageDown => self.scroll_down(self.window_height.get()),
-                Action::Home => self.scroll_to(0),
-                Action::End => self.scroll_to(u16::MAX),
+                Action::PageUp => self.cursor_up(self.window_height.get()),
+                Action::PageDown => self.cursor_down(self.window_height.get()),
+                Action::Home => self.cursor_to(0),
+                Action::End => self.cursor_to(u16::MAX),
                 _ => return Update::Propagate(event),
             },
             _ => return Update::Propagate(event),
@@ -120,7 +186,14 @@ where
 {
     fn draw(&self, frame: &mut Frame, _: (), area: Rect) {
         let theme = &TuiContext::get().theme;
-        let text = Paragraph::new(self.text.generate());
+        let mut text = self.text.generate();
+        if let Some(line) = self
+            .cursor
+            .and_then(|cursor| text.lines.get_mut(cursor as usize))
+        {
+            line.style = line.style.patch(theme.text_window.cursor);
+        }
+        let text = Paragraph::new(text);
         // Assume no line wrapping when calculating line count
         let text_height = text.line_count(u16::MAX) as u16;
 
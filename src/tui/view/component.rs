@@ -1,18 +1,24 @@
 //! Specific single-use components
 
+mod explain;
+mod header_view;
 mod help;
+mod json_table;
 mod misc;
 mod primary;
 mod profile;
 mod profile_list;
+mod query_tester;
 mod recipe_list;
 mod recipe_pane;
 mod record_body;
 mod request_pane;
 mod response_pane;
 mod root;
+mod stream;
 
 pub use root::Root;
+pub use stream::StreamModal;
 
 use crate::tui::view::{
     draw::Draw,
@@ -0,0 +1,180 @@
+//! Show where each value in a rendered request came from
+
+use crate::{
+    http::FieldExplanation,
+    template::TemplateExplanation,
+    tui::view::{
+        common::{
+            modal::{IntoModal, Modal},
+            text_window::TextWindow,
+        },
+        draw::Draw,
+        event::EventHandler,
+        Component,
+    },
+};
+use ratatui::{
+    layout::{Constraint, Rect},
+    Frame,
+};
+use std::fmt::Write;
+
+/// A scrollable breakdown of a rendered request. Each templated field is
+/// listed with its template and final value, followed by every key in the
+/// template and where that key got its value.
+#[derive(Debug)]
+pub struct RequestExplanationModal {
+    text: Component<TextWindow<String>>,
+}
+
+impl RequestExplanationModal {
+    pub fn new(explanations: &[FieldExplanation]) -> Self {
+        Self {
+            text: TextWindow::new(explanation_text(explanations)).into(),
+        }
+    }
+}
+
+impl Modal for RequestExplanationModal {
+    fn title(&self) -> &str {
+        "Request Explanation"
+    }
+
+    fn dimensions(&self) -> (Constraint, Constraint) {
+        (Constraint::Percentage(80), Constraint::Percentage(80))
+    }
+}
+
+impl EventHandler for RequestExplanationModal {
+    fn children(&mut self) -> Vec<Component<&mut dyn EventHandler>> {
+        vec![self.text.as_child()]
+    }
+}
+
+impl Draw for RequestExplanationModal {
+    fn draw(&self, frame: &mut Frame, _: (), area: Rect) {
+        self.text.draw(frame, (), area);
+    }
+}
+
+impl IntoModal for Vec<FieldExplanation> {
+    type Target = RequestExplanationModal;
+
+    fn into_modal(self) -> Self::Target {
+        RequestExplanationModal::new(&self)
+    }
+}
+
+/// Format explanations as a text tree. Fields without any template keys are
+/// skipped, because there's nothing to explain.
+fn explanation_text(explanations: &[FieldExplanation]) -> String {
+    let mut text = String::new();
+    for field in explanations
+        .iter()
+        .filter(|field| !field.explanation.keys.is_empty())
+    {
+        if !text.is_empty() {
+            text.push('\n');
+        }
+        let value = field.explanation.value.as_deref().unwrap_or("<error>");
+        // Writing to a string can't fail
+        let _ = writeln!(text, "{}", field.field);
+        let _ = writeln!(text, "  Template: {}", field.template);
+        let _ = writeln!(text, "  Value:    {value}");
+        write_keys(&mut text, &field.explanation, 1);
+    }
+    if text.is_empty() {
+        text.push_str("No templated fields in this request");
+    }
+    text
+}
+
+/// Write one line per key, with nested explanations indented below their key
+fn write_keys(
+    text: &mut String,
+    explanation: &TemplateExplanation,
+    depth: usize,
+) {
+    let indent = "  ".repeat(depth);
+    for key in &explanation.keys {
+        let _ = match &key.value {
+            Ok(value) => writeln!(
+                text,
+                "{indent}{{{{{}}}}} = {value} ({})",
+                key.key, key.source
+            ),
+            Err(error) => writeln!(
+                text,
+                "{indent}{{{{{}}}}} failed ({}): {error:#}",
+                key.key, key.source
+            ),
+        };
+        if let Some(nested) = &key.nested {
+            write_keys(text, nested, depth + 1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{template::Template, test_util::*};
+    use factori::create;
+    use indexmap::indexmap;
+
+    /// Static fields are skipped, and nested keys are indented
+    #[tokio::test]
+    async fn test_explanation_text() {
+        let profile = create!(
+            Profile,
+            data: indexmap! {
+                "host".into() => "{{scheme}}://localhost".into(),
+                "scheme".into() => "https".into(),
+            },
+        );
+        let context = create!(
+            TemplateContext,
+            collection: create!(
+                Collection,
+                profiles: indexmap! {profile.id.clone() => profile},
+            ),
+            selected_profile: Some("profile1".into()),
+        );
+        let explain = |field: &str, template: &str| {
+            let template: Template = template.into();
+            let context = &context;
+            let field = field.to_owned();
+            async move {
+                FieldExplanation {
+                    field,
+                    explanation: template.explain(context).await,
+                    template,
+                }
+            }
+        };
+        let explanations = vec![
+            explain("method", "GET").await,
+            explain("URL", "{{host}}/users").await,
+            explain("header `X-User`", "{{user}}").await,
+        ];
+
+        assert_eq!(
+            explanation_text(&explanations),
+            "URL
+  Template: {{host}}/users
+  Value:    https://localhost/users
+  {{host}} = https://localhost (profile `profile1`: `{{scheme}}://localhost`)
+    {{scheme}} = https (profile `profile1`: `https`)
+
+header `X-User`
+  Template: {{user}}
+  Value:    <error>
+  {{user}} failed (unknown field): Unknown field `user`
+"
+        );
+        assert_eq!(
+            explanation_text(&explanations[..1]),
+            "No templated fields in this request"
+        );
+    }
+}
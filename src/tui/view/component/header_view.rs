@@ -0,0 +1,255 @@
+//! Request/response header display component
+
+use crate::{
+    tui::{
+        context::TuiContext,
+        input::Action,
+        message::Message,
+        view::{
+            common::{table::Table, text_box::TextBox},
+            draw::{Draw, Generate},
+            event::{Event, EventHandler, EventQueue, Update},
+            state::{select::SelectState, StateCell},
+            util::layout,
+            Component,
+        },
+    },
+    util::MaybeStr,
+};
+use derive_more::Debug;
+use itertools::Itertools;
+use ratatui::{
+    layout::{Constraint, Direction},
+    prelude::Rect,
+    text::Text,
+    widgets::TableState,
+    Frame,
+};
+use reqwest::header::HeaderMap;
+
+/// A table of headers that can be filtered, sorted, and copied from. Use
+/// up/down to select a header, `/` to filter, and `y` to copy the selected
+/// header's value. Copying the whole line is left to the parent's actions
+/// menu, via [Self::selected_line].
+#[derive(Debug)]
+pub struct HeaderView {
+    /// Visible rows. Rebuilt whenever the filter or sorting changes
+    #[debug(skip)]
+    rows: StateCell<(Option<String>, bool), Component<HeaderRows>>,
+    /// Only show headers whose name or value contains this
+    /// (case-insensitive)
+    filter: Option<String>,
+    /// Sort headers by name, instead of the order they were sent in
+    sorted: bool,
+    #[debug(skip)]
+    filter_text_box: Component<TextBox>,
+}
+
+pub struct HeaderViewProps<'a> {
+    pub headers: &'a HeaderMap,
+    /// Name of the first column, e.g. to distinguish headers from trailers
+    pub label: &'a str,
+}
+
+type HeaderRows = SelectState<HeaderRow, TableState>;
+
+#[derive(Debug, PartialEq)]
+struct HeaderRow {
+    header: String,
+    value: String,
+}
+
+/// Callback event from the filter text box when user hits Enter
+struct FilterSubmit(String);
+
+impl HeaderView {
+    /// Switch between sorting headers by name and the original order
+    pub fn toggle_sort(&mut self) {
+        self.sorted = !self.sorted;
+    }
+
+    /// Selected header as a `Header: value` line
+    pub fn selected_line(&self) -> Option<String> {
+        let rows = self.rows.get()?;
+        let row = rows.selected()?;
+        Some(format!("{}: {}", row.header, row.value))
+    }
+
+    /// Copy the selected header's value to the clipboard
+    fn copy_value(&self) {
+        if let Some(value) = self
+            .rows
+            .get()
+            .and_then(|rows| rows.selected().map(|row| row.value.clone()))
+        {
+            TuiContext::send_message(Message::CopyText(value));
+        }
+    }
+}
+
+impl Default for HeaderView {
+    fn default() -> Self {
+        Self {
+            rows: Default::default(),
+            filter: None,
+            sorted: false,
+            filter_text_box: TextBox::default()
+                .with_focus(false)
+                .with_placeholder("'/' to filter headers")
+                // Callback triggers an event, so we can modify our own state
+                .with_on_submit(|text_box| {
+                    EventQueue::push(Event::other(FilterSubmit(
+                        text_box.text().to_owned(),
+                    )))
+                })
+                .into(),
+        }
+    }
+}
+
+impl EventHandler for HeaderView {
+    fn update(&mut self, event: Event) -> Update {
+        match event {
+            Event::Input {
+                action: Some(Action::Search),
+                ..
+            } => self.filter_text_box.focus(),
+            Event::Input {
+                action: Some(Action::CopyValue),
+                ..
+            } => self.copy_value(),
+            Event::Other(ref other) => match other
+                .downcast_ref::<FilterSubmit>()
+            {
+                Some(FilterSubmit(text)) => {
+                    let text = text.trim();
+                    self.filter = (!text.is_empty()).then(|| text.to_owned());
+                }
+                None => return Update::Propagate(event),
+            },
+            _ => return Update::Propagate(event),
+        }
+        Update::Consumed
+    }
+
+    fn children(&mut self) -> Vec<Component<&mut dyn EventHandler>> {
+        if self.filter_text_box.is_focused() {
+            vec![self.filter_text_box.as_child()]
+        } else if let Some(rows) = self.rows.get_mut() {
+            vec![rows.as_child()]
+        } else {
+            vec![]
+        }
+    }
+}
+
+impl<'a> Draw<HeaderViewProps<'a>> for HeaderView {
+    fn draw(&self, frame: &mut Frame, props: HeaderViewProps, area: Rect) {
+        let [table_area, filter_area] = layout(
+            area,
+            Direction::Vertical,
+            [Constraint::Min(0), Constraint::Length(1)],
+        );
+
+        let rows =
+            self.rows
+                .get_or_update((self.filter.clone(), self.sorted), || {
+                    SelectState::new(header_rows(
+                        props.headers,
+                        self.filter.as_deref(),
+                        self.sorted,
+                    ))
+                    .into()
+                });
+        let table = Table {
+            rows: rows
+                .items()
+                .iter()
+                .map(|row| {
+                    [
+                        Text::from(row.header.as_str()),
+                        Text::from(row.value.as_str()),
+                    ]
+                })
+                .collect_vec(),
+            header: Some([props.label, "Value"]),
+            alternate_row_style: true,
+            ..Default::default()
+        };
+        frame.render_stateful_widget(
+            table.generate(),
+            table_area,
+            &mut rows.state_mut(),
+        );
+
+        self.filter_text_box.draw(frame, (), filter_area);
+    }
+}
+
+/// Get the headers to show, filtered and sorted as requested. Sorting is
+/// stable, so repeated headers stay in the order they were sent.
+fn header_rows(
+    headers: &HeaderMap,
+    filter: Option<&str>,
+    sorted: bool,
+) -> Vec<HeaderRow> {
+    let filter = filter.map(str::to_lowercase);
+    let mut rows = headers
+        .iter()
+        .map(|(header, value)| HeaderRow {
+            header: header.as_str().to_owned(),
+            value: MaybeStr(value.as_bytes()).to_string(),
+        })
+        .filter(|row| match &filter {
+            // Header names are already lowercase
+            Some(filter) => {
+                row.header.contains(filter.as_str())
+                    || row.value.to_lowercase().contains(filter.as_str())
+            }
+            None => true,
+        })
+        .collect_vec();
+    if sorted {
+        rows.sort_by(|a, b| a.header.cmp(&b.header));
+    }
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::header_map;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::all(None, false, &["x-cache", "x-cache", "age", "cache-control"])]
+    #[case::sorted(
+        None,
+        true,
+        &["age", "cache-control", "x-cache", "x-cache"]
+    )]
+    #[case::filter_name(
+        Some("CACHE"),
+        false,
+        &["x-cache", "x-cache", "cache-control"]
+    )]
+    #[case::filter_value(Some("miss"), false, &["x-cache"])]
+    #[case::no_match(Some("nope"), false, &[])]
+    fn test_header_rows(
+        #[case] filter: Option<&str>,
+        #[case] sorted: bool,
+        #[case] expected: &[&str],
+    ) {
+        let headers = header_map([
+            ("x-cache", "HIT"),
+            ("age", "12"),
+            ("cache-control", "max-age=60"),
+            ("x-cache", "MISS from edge"),
+        ]);
+        let rows = header_rows(&headers, filter, sorted);
+        assert_eq!(
+            rows.iter().map(|row| row.header.as_str()).collect_vec(),
+            expected
+        );
+    }
+}
@@ -0,0 +1,332 @@
+//! Table view of a JSON array of objects, e.g. from a list endpoint
+
+use crate::tui::{
+    context::TuiContext,
+    input::Action,
+    view::{
+        draw::Draw,
+        event::{Event, EventHandler, Update},
+        state::select::SelectState,
+        util::layout,
+        Component,
+    },
+};
+use derive_more::Debug;
+use itertools::Itertools;
+use ratatui::{
+    layout::{Constraint, Direction},
+    prelude::Rect,
+    text::Line,
+    widgets::{Cell, Paragraph, Row, Table, TableState},
+    Frame,
+};
+use serde_json::Value;
+use std::cmp::Ordering;
+
+/// A JSON array of objects, shown as a table with a column for each key. Use
+/// left/right to pick a column to sort by, and Enter to reverse the sort.
+#[derive(Debug)]
+pub struct JsonTable {
+    columns: Vec<String>,
+    /// Rows in their original order
+    #[debug(skip)]
+    rows: Vec<Vec<TableCell>>,
+    sort: Option<Sort>,
+    /// Visible rows, in sorted order. Rebuilt whenever the sort changes
+    #[debug(skip)]
+    select: Component<SelectState<Vec<TableCell>, TableState>>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Sort {
+    column: usize,
+    descending: bool,
+}
+
+/// A single value in the table
+#[derive(Clone, Debug, PartialEq)]
+struct TableCell {
+    text: String,
+    /// Numbers sort by value, rather than as text
+    number: Option<f64>,
+}
+
+impl JsonTable {
+    /// Build a table from a JSON value. Returns `None` unless the value is a
+    /// non-empty array, and every element is an object. The objects don't
+    /// need to have the same keys: there's a column for every key that
+    /// appears in any of them, in the order they first appear.
+    pub fn new(value: &Value) -> Option<Self> {
+        let objects = value
+            .as_array()
+            .filter(|values| !values.is_empty())?
+            .iter()
+            .map(Value::as_object)
+            .collect::<Option<Vec<_>>>()?;
+        let columns = objects
+            .iter()
+            .flat_map(|object| object.keys())
+            .unique()
+            .cloned()
+            .collect_vec();
+        let rows = objects
+            .iter()
+            .map(|object| {
+                columns
+                    .iter()
+                    .map(|column| TableCell::new(object.get(column)))
+                    .collect_vec()
+            })
+            .collect_vec();
+        Some(Self {
+            select: SelectState::new(rows.clone()).into(),
+            columns,
+            rows,
+            sort: None,
+        })
+    }
+
+    /// Sort by the next (or previous) column. Moving past either end goes
+    /// back to the original order.
+    fn move_sort(&mut self, forward: bool) {
+        let column = match (self.sort, forward) {
+            (None, true) => Some(0),
+            (None, false) => self.columns.len().checked_sub(1),
+            (Some(sort), true) => {
+                Some(sort.column + 1).filter(|&i| i < self.columns.len())
+            }
+            (Some(sort), false) => sort.column.checked_sub(1),
+        };
+        let sort = column.map(|column| Sort {
+            column,
+            descending: false,
+        });
+        self.set_sort(sort);
+    }
+
+    fn set_sort(&mut self, sort: Option<Sort>) {
+        self.sort = sort;
+        self.select = SelectState::new(sorted_rows(&self.rows, sort)).into();
+    }
+}
+
+impl EventHandler for JsonTable {
+    fn update(&mut self, event: Event) -> Update {
+        match event {
+            Event::Input {
+                action: Some(Action::Right),
+                ..
+            } => self.move_sort(true),
+            Event::Input {
+                action: Some(Action::Left),
+                ..
+            } => self.move_sort(false),
+            Event::Input {
+                action: Some(Action::Submit),
+                ..
+            } if self.sort.is_some() => {
+                self.set_sort(self.sort.map(|sort| Sort {
+                    descending: !sort.descending,
+                    ..sort
+                }))
+            }
+            _ => return Update::Propagate(event),
+        }
+        Update::Consumed
+    }
+
+    fn children(&mut self) -> Vec<Component<&mut dyn EventHandler>> {
+        vec![self.select.as_child()]
+    }
+}
+
+impl Draw for JsonTable {
+    fn draw(&self, frame: &mut Frame, _: (), area: Rect) {
+        let tui_context = TuiContext::get();
+        let theme = &tui_context.theme;
+        let [table_area, hint_area] = layout(
+            area,
+            Direction::Vertical,
+            [Constraint::Min(0), Constraint::Length(1)],
+        );
+
+        let header = self.columns.iter().enumerate().map(|(i, column)| {
+            match self.sort {
+                Some(Sort {
+                    column: sorted,
+                    descending,
+                }) if sorted == i => {
+                    let arrow = if descending { "▼" } else { "▲" };
+                    Cell::from(format!("{column} {arrow}"))
+                }
+                _ => Cell::from(column.as_str()),
+            }
+        });
+        let rows = self.select.items().iter().enumerate().map(|(i, row)| {
+            let style = if i % 2 == 1 {
+                theme.table.alt
+            } else {
+                theme.table.text
+            };
+            Row::new(row.iter().map(|cell| Cell::from(cell.text.as_str())))
+                .style(style)
+        });
+        let widths = vec![
+            Constraint::Ratio(1, self.columns.len() as u32);
+            self.columns.len()
+        ];
+        let table = Table::new(rows, widths)
+            .header(Row::new(header).style(theme.table.header))
+            .highlight_style(theme.table.highlight);
+        frame.render_stateful_widget(
+            table,
+            table_area,
+            &mut self.select.state_mut(),
+        );
+
+        let hint = format!(
+            "←/→ to sort, Enter to reverse, {}",
+            tui_context
+                .input_engine
+                .add_hint("raw view", Action::ToggleTable)
+        );
+        frame.render_widget(
+            Paragraph::new(Line::from(hint).style(theme.text_box.placeholder)),
+            hint_area,
+        );
+    }
+}
+
+impl TableCell {
+    /// Strings are shown without quotes. Nested objects and arrays are shown
+    /// as compact JSON, and missing values as blanks.
+    fn new(value: Option<&Value>) -> Self {
+        let text = match value {
+            None => String::new(),
+            Some(Value::String(s)) => s.clone(),
+            Some(value) => value.to_string(),
+        };
+        Self {
+            text,
+            number: value.and_then(Value::as_f64),
+        }
+    }
+}
+
+/// Sort table rows by a column. The sort is stable, so rows with equal values
+/// stay in their original order.
+fn sorted_rows(
+    rows: &[Vec<TableCell>],
+    sort: Option<Sort>,
+) -> Vec<Vec<TableCell>> {
+    let mut rows = rows.to_vec();
+    if let Some(Sort { column, descending }) = sort {
+        rows.sort_by(|a, b| {
+            let ordering = compare_cells(&a[column], &b[column]);
+            if descending {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        });
+    }
+    rows
+}
+
+/// Numbers sort before everything else, by value. Everything else sorts by
+/// text. Ranking by type first keeps the order consistent for columns with
+/// mixed types.
+fn compare_cells(a: &TableCell, b: &TableCell) -> Ordering {
+    match (a.number, b.number) {
+        (Some(a), Some(b)) => a.total_cmp(&b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => a.text.cmp(&b.text),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use serde_json::json;
+
+    fn texts(rows: &[Vec<TableCell>]) -> Vec<Vec<&str>> {
+        rows.iter()
+            .map(|row| row.iter().map(|cell| cell.text.as_str()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_new() {
+        let table = JsonTable::new(&json!([
+            {"id": 1, "name": "Barry"},
+            {"id": 2, "tags": ["a"], "owner": {"id": 3}},
+            {"name": null, "id": 3},
+        ]))
+        .unwrap();
+        assert_eq!(table.columns, ["id", "name", "owner", "tags"]);
+        assert_eq!(
+            texts(&table.rows),
+            [
+                vec!["1", "Barry", "", ""],
+                vec!["2", "", r#"{"id":3}"#, r#"["a"]"#],
+                vec!["3", "null", "", ""],
+            ]
+        );
+    }
+
+    #[rstest]
+    #[case::object(json!({"id": 1}))]
+    #[case::empty(json!([]))]
+    #[case::scalars(json!([1, 2]))]
+    #[case::mixed(json!([{"id": 1}, 2]))]
+    fn test_new_not_table(#[case] value: Value) {
+        assert!(JsonTable::new(&value).is_none());
+    }
+
+    #[test]
+    fn test_sorted_rows() {
+        let table = JsonTable::new(&json!([
+            {"animal": "b", "count": 10},
+            {"animal": "c", "count": 9},
+            {"animal": "a", "count": 10},
+        ]))
+        .unwrap();
+        let sort = |column, descending| Some(Sort { column, descending });
+
+        assert_eq!(
+            texts(&sorted_rows(&table.rows, None)),
+            [["b", "10"], ["c", "9"], ["a", "10"]]
+        );
+        assert_eq!(
+            texts(&sorted_rows(&table.rows, sort(0, false))),
+            [["a", "10"], ["b", "10"], ["c", "9"]]
+        );
+        // Numbers sort by value, and ties keep their original order
+        assert_eq!(
+            texts(&sorted_rows(&table.rows, sort(1, false))),
+            [["c", "9"], ["b", "10"], ["a", "10"]]
+        );
+        assert_eq!(
+            texts(&sorted_rows(&table.rows, sort(1, true))),
+            [["b", "10"], ["a", "10"], ["c", "9"]]
+        );
+    }
+
+    /// In a column with mixed types, numbers come before text
+    #[test]
+    fn test_sorted_rows_mixed() {
+        let table =
+            JsonTable::new(&json!([{"a": 10}, {"a": "5"}, {"a": 9}, {}]))
+                .unwrap();
+        let sort = Some(Sort {
+            column: 0,
+            descending: false,
+        });
+        assert_eq!(
+            texts(&sorted_rows(&table.rows, sort)),
+            [["9"], ["10"], [""], ["5"]]
+        );
+    }
+}
@@ -2,21 +2,32 @@
 //! generic/utility, but don't fall into a clear category.
 
 use crate::{
+    collection::{Profile, PromptPattern},
+    http::SchemaViolation,
     template::{Prompt, PromptChannel},
-    tui::view::{
-        common::{
-            modal::{IntoModal, Modal},
-            text_box::TextBox,
+    tui::{
+        context::TuiContext,
+        input::Action,
+        message::Confirm,
+        view::{
+            common::{
+                modal::{IntoModal, Modal},
+                text_box::TextBox,
+                Pane, ProfileBadge,
+            },
+            component::Component,
+            draw::{Draw, Generate},
+            event::{Event, EventHandler, EventQueue, Update},
+            state::{select::SelectState, Notification},
+            util::layout,
         },
-        component::Component,
-        draw::{Draw, Generate},
-        event::{Event, EventHandler, EventQueue},
-        state::Notification,
     },
 };
+use itertools::Itertools;
 use ratatui::{
-    prelude::{Constraint, Rect},
-    widgets::{Paragraph, Wrap},
+    prelude::{Constraint, Direction, Margin, Rect},
+    text::Line,
+    widgets::{ListState, Paragraph, Wrap},
     Frame,
 };
 use std::{cell::Cell, fmt::Debug, rc::Rc};
@@ -53,6 +64,91 @@ impl IntoModal for anyhow::Error {
     }
 }
 
+/// Full-screen display of an error loading the collection. This replaces the
+/// primary view, because the collection is probably unusable until it's fixed.
+/// YAML errors include a snippet of the file, so spacing is preserved.
+#[derive(Debug)]
+pub struct CollectionErrorView(anyhow::Error);
+
+impl CollectionErrorView {
+    pub fn new(error: anyhow::Error) -> Self {
+        Self(error)
+    }
+}
+
+impl Draw for CollectionErrorView {
+    fn draw(&self, frame: &mut Frame, _: (), area: Rect) {
+        let tui_context = TuiContext::get();
+        let block = Pane {
+            title: "Collection Error",
+            is_focused: true,
+        }
+        .generate();
+        let inner_area = block.inner(area).inner(&Margin::new(1, 0));
+        frame.render_widget(block, area);
+
+        let binding = |action| {
+            tui_context
+                .input_engine
+                .binding(action)
+                .map(|binding| binding.to_string())
+                .unwrap_or_else(|| "<unbound>".into())
+        };
+        let mut text = self.0.generate();
+        text.extend([
+            Line::default(),
+            Line::styled(
+                format!(
+                    "Save the file to reload automatically, or press {} to \
+                    retry. Press {} to dismiss.",
+                    binding(Action::ReloadCollection),
+                    binding(Action::Cancel),
+                ),
+                tui_context.theme.text.highlight,
+            ),
+        ]);
+        frame.render_widget(
+            Paragraph::new(text).wrap(Wrap { trim: false }),
+            inner_area,
+        );
+    }
+}
+
+/// List the ways a response failed to match its recipe's schema
+#[derive(Debug)]
+pub struct SchemaViolationsModal(Vec<SchemaViolation>);
+
+impl Modal for SchemaViolationsModal {
+    fn title(&self) -> &str {
+        "Response Does Not Match Schema"
+    }
+
+    fn dimensions(&self) -> (Constraint, Constraint) {
+        (Constraint::Percentage(60), Constraint::Percentage(40))
+    }
+}
+
+impl EventHandler for SchemaViolationsModal {}
+
+impl Draw for SchemaViolationsModal {
+    fn draw(&self, frame: &mut Frame, _: (), area: Rect) {
+        let lines = self
+            .0
+            .iter()
+            .map(|violation| Line::from(format!("- {violation}")))
+            .collect_vec();
+        frame.render_widget(Paragraph::new(lines).wrap(Wrap::default()), area);
+    }
+}
+
+impl IntoModal for Vec<SchemaViolation> {
+    type Target = SchemaViolationsModal;
+
+    fn into_modal(self) -> Self::Target {
+        SchemaViolationsModal(self)
+    }
+}
+
 /// Inner state for the prompt modal
 #[derive(Debug)]
 pub struct PromptModal {
@@ -63,15 +159,20 @@ pub struct PromptModal {
     /// Flag set before closing to indicate if we should submit in our own
     /// `on_close`. This is set from the text box's `on_submit`.
     submit: Rc<Cell<bool>>,
+    /// If given, the value can't be submitted until it matches
+    pattern: Option<PromptPattern>,
     /// Little editor fucker
     text_box: Component<TextBox>,
+    /// If the prompt has options, the user picks one from this list instead
+    /// of typing in the text box
+    options: Option<Component<SelectState<String, ListState>>>,
 }
 
 impl PromptModal {
     pub fn new(prompt: Prompt) -> Self {
         let submit = Rc::new(Cell::new(false));
         let submit_cell = Rc::clone(&submit);
-        let text_box = TextBox::default()
+        let mut text_box = TextBox::default()
             .with_sensitive(prompt.sensitive)
             .with_default(prompt.default.unwrap_or_default())
             // Make sure cancel gets propagated to close the modal
@@ -83,13 +184,39 @@ impl PromptModal {
                 // submitter will only be called once.
                 submit_cell.set(true);
                 EventQueue::push(Event::CloseModal);
-            })
-            .into();
+            });
+        if let Some(pattern) = prompt.pattern.clone() {
+            // Invalid values can't be submitted, so the user has to fix it or
+            // cancel
+            text_box = text_box
+                .with_validator(move |text| pattern.is_match(text))
+                .with_strict_validation();
+        }
+        // Sensitive input is masked, so tell the user how to check it
+        let reveal_binding = TuiContext::get()
+            .input_engine
+            .binding(Action::ToggleReveal)
+            .filter(|_| prompt.sensitive);
+        let title = match reveal_binding {
+            Some(binding) => format!("{} ({binding} to show)", prompt.message),
+            None => prompt.message,
+        };
+        let options = (!prompt.options.is_empty()).then(|| {
+            let submit = Rc::clone(&submit);
+            SelectState::new(prompt.options)
+                .on_submit(move |_| {
+                    submit.set(true);
+                    EventQueue::push(Event::CloseModal);
+                })
+                .into()
+        });
         Self {
-            title: prompt.message,
+            title,
             channel: prompt.channel,
             submit,
-            text_box,
+            pattern: prompt.pattern,
+            text_box: text_box.into(),
+            options,
         }
     }
 }
@@ -100,26 +227,73 @@ impl Modal for PromptModal {
     }
 
     fn dimensions(&self) -> (Constraint, Constraint) {
-        (Constraint::Percentage(60), Constraint::Length(1))
+        let height = if let Some(options) = &self.options {
+            options.items().len().min(10) as u16
+        } else if self.pattern.is_some() {
+            // Extra line to show the pattern
+            2
+        } else {
+            1
+        };
+        (Constraint::Percentage(60), Constraint::Length(height))
     }
 
     fn on_close(self: Box<Self>) {
         if self.submit.get() {
             // Return the user's value and close the prompt
-            self.channel.respond(self.text_box.into_inner().into_text());
+            let value = match self.options {
+                Some(options) => options.selected().cloned(),
+                None => Some(self.text_box.into_inner().into_text()),
+            };
+            if let Some(value) = value {
+                self.channel.respond(value);
+            }
         }
     }
 }
 
 impl EventHandler for PromptModal {
     fn children(&mut self) -> Vec<Component<&mut dyn EventHandler>> {
-        vec![self.text_box.as_child()]
+        match &mut self.options {
+            Some(options) => vec![options.as_child()],
+            None => vec![self.text_box.as_child()],
+        }
     }
 }
 
 impl Draw for PromptModal {
     fn draw(&self, frame: &mut Frame, _: (), area: Rect) {
-        self.text_box.draw(frame, (), area);
+        if let Some(options) = &self.options {
+            // Options can be multi-line (e.g. JSON objects), but each one only
+            // gets one line
+            let list = ratatui::widgets::List::new(
+                options
+                    .items()
+                    .iter()
+                    .map(|option| option.replace('\n', " ")),
+            )
+            .highlight_style(TuiContext::get().theme.list.highlight);
+            frame.render_stateful_widget(list, area, &mut options.state_mut());
+            return;
+        }
+        let Some(pattern) = &self.pattern else {
+            self.text_box.draw(frame, (), area);
+            return;
+        };
+
+        let [text_box_area, pattern_area] = layout(
+            area,
+            Direction::Vertical,
+            [Constraint::Length(1), Constraint::Length(1)],
+        );
+        self.text_box.draw(frame, (), text_box_area);
+        let message = format!("Must match `{pattern}`");
+        let line = if self.text_box.is_valid() {
+            Line::from(message)
+        } else {
+            Line::styled(message, TuiContext::get().theme.text.error)
+        };
+        frame.render_widget(Paragraph::new(line), pattern_area);
     }
 }
 
@@ -131,6 +305,105 @@ impl IntoModal for Prompt {
     }
 }
 
+/// Ask the user a yes/no question. Submitting answers yes; closing the modal
+/// any other way answers no.
+#[derive(Debug)]
+pub struct ConfirmModal {
+    confirm: Confirm,
+    /// Set on submit, so the answer can be sent in `on_close`
+    answer: bool,
+}
+
+impl Modal for ConfirmModal {
+    fn title(&self) -> &str {
+        "Confirm"
+    }
+
+    fn dimensions(&self) -> (Constraint, Constraint) {
+        // Flagged profiles get an extra line for their badge
+        let height = if self.flagged_profile().is_some() {
+            4
+        } else {
+            3
+        };
+        (Constraint::Percentage(60), Constraint::Length(height))
+    }
+
+    fn on_close(self: Box<Self>) {
+        // The asker may have given up, in which case nobody cares
+        let _ = self.confirm.channel.send(self.answer);
+    }
+}
+
+impl ConfirmModal {
+    fn flagged_profile(&self) -> Option<&Profile> {
+        self.confirm
+            .profile
+            .as_ref()
+            .filter(|profile| profile.is_flagged())
+    }
+}
+
+impl EventHandler for ConfirmModal {
+    fn update(&mut self, event: Event) -> Update {
+        match event {
+            Event::Input {
+                action: Some(Action::Submit),
+                ..
+            } => {
+                self.answer = true;
+                EventQueue::push(Event::CloseModal);
+                Update::Consumed
+            }
+            _ => Update::Propagate(event),
+        }
+    }
+}
+
+impl Draw for ConfirmModal {
+    fn draw(&self, frame: &mut Frame, _: (), area: Rect) {
+        let tui_context = TuiContext::get();
+        let binding = |action| {
+            tui_context
+                .input_engine
+                .binding(action)
+                .map(|binding| binding.to_string())
+                .unwrap_or_else(|| "<unbound>".into())
+        };
+        let mut text = Vec::with_capacity(4);
+        if let Some(profile) = self.flagged_profile() {
+            text.push(Line::from(vec![
+                "Profile: ".into(),
+                ProfileBadge { profile }.generate(),
+            ]));
+        }
+        text.extend([
+            Line::from(self.confirm.message.as_str()),
+            Line::default(),
+            Line::styled(
+                format!(
+                    "Press {} to confirm, {} to cancel",
+                    binding(Action::Submit),
+                    binding(Action::Cancel),
+                ),
+                tui_context.theme.text.highlight,
+            ),
+        ]);
+        frame.render_widget(Paragraph::new(text).wrap(Wrap::default()), area);
+    }
+}
+
+impl IntoModal for Confirm {
+    type Target = ConfirmModal;
+
+    fn into_modal(self) -> Self::Target {
+        ConfirmModal {
+            confirm: self,
+            answer: false,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct NotificationText {
     notification: Notification,
@@ -1,7 +1,8 @@
 //! Components for the "primary" view, which is the paned request/response view
 
 use crate::{
-    collection::{Collection, Profile, Recipe},
+    collection::{Collection, Profile, ProfileId, Recipe, RecipeId},
+    config::ExchangeLayout,
     tui::{
         context::TuiContext,
         input::Action,
@@ -36,6 +37,7 @@ use ratatui::{
     Frame,
 };
 use serde::{Deserialize, Serialize};
+use std::cell::Cell;
 use strum::{EnumCount, EnumIter};
 
 /// Primary TUI view, which shows request/response panes
@@ -44,6 +46,12 @@ pub struct PrimaryView {
     // Own state
     selected_pane: Persistent<FixedSelectState<PrimaryPane>>,
     fullscreen_mode: Persistent<Option<FullscreenMode>>,
+    /// Request/response layout picked by the user during this session. If
+    /// unset, the layout comes from the config, based on terminal width
+    layout_override: Option<ExchangeLayout>,
+    /// Request/response layout as of the last draw, so we know where to go
+    /// from when toggling
+    layout: Cell<ExchangeLayout>,
 
     // Children
     #[debug(skip)]
@@ -104,6 +112,7 @@ impl PrimaryView {
         )
         .into();
         let recipe_list_pane = RecipeListPane::new(&collection.recipes).into();
+        let profile_pane = ProfilePane::new(collection).into();
         Self {
             selected_pane: Persistent::new(
                 PersistentKey::PrimaryPane,
@@ -113,10 +122,12 @@ impl PrimaryView {
                 PersistentKey::FullscreenMode,
                 None,
             ),
+            layout_override: None,
+            layout: Cell::default(),
 
             profile_list_pane,
             recipe_list_pane,
-            profile_pane: Default::default(),
+            profile_pane,
             recipe_pane: Default::default(),
             request_pane: Default::default(),
             response_pane: Default::default(),
@@ -135,6 +146,44 @@ impl PrimaryView {
         self.profile_list_pane.profiles().selected()
     }
 
+    /// Config to send a request for the selected recipe and profile. `None`
+    /// if no recipe is selected.
+    fn request_config(
+        &self,
+        headers_only: bool,
+        review: bool,
+        stream: bool,
+    ) -> Option<RequestConfig> {
+        let recipe = self.selected_recipe()?;
+        Some(RequestConfig {
+            // Reach into the children to grab state (ugly!)
+            recipe_id: recipe.id.clone(),
+            profile_id: self
+                .selected_profile()
+                .map(|profile| profile.id.clone()),
+            options: self.recipe_pane.recipe_options(),
+            headers_only,
+            review,
+            stream,
+        })
+    }
+
+    /// Select a profile and/or recipe, and focus the recipe list if a recipe
+    /// was given. Unknown IDs are ignored.
+    pub fn select(
+        &mut self,
+        profile_id: Option<&ProfileId>,
+        recipe_id: Option<&RecipeId>,
+    ) {
+        if let Some(profile_id) = profile_id {
+            self.profile_list_pane.select_profile(profile_id);
+        }
+        if let Some(recipe_id) = recipe_id {
+            self.recipe_list_pane.select_node(recipe_id);
+            self.selected_pane.select(&PrimaryPane::RecipeList);
+        }
+    }
+
     /// Draw the "normal" view, when nothing is full
     fn draw_all_panes(
         &self,
@@ -142,6 +191,11 @@ impl PrimaryView {
         props: PrimaryViewProps,
         area: Rect,
     ) {
+        let exchange_layout = self.layout_override.unwrap_or_else(|| {
+            TuiContext::get().config.layout.mode(area.width)
+        });
+        self.layout.set(exchange_layout);
+
         // Split the main pane horizontally
         let [left_area, right_area] = layout(
             area,
@@ -152,7 +206,7 @@ impl PrimaryView {
         let [profiles_area, recipes_area] =
             self.get_left_column_layout(left_area);
         let [recipe_area, request_area, response_area] =
-            self.get_right_column_layout(right_area);
+            self.get_right_column_layout(right_area, exchange_layout);
 
         // Primary panes
         self.profile_list_pane.draw(
@@ -186,30 +240,38 @@ impl PrimaryView {
                 RecipePaneProps {
                     is_selected: self.is_selected(PrimaryPane::Recipe),
                     selected_recipe: self.selected_recipe(),
-                    selected_profile_id: self
-                        .selected_profile()
-                        .map(|profile| &profile.id),
+                    selected_profile: self.selected_profile(),
                 },
                 recipe_area,
             );
         }
 
-        self.request_pane.draw(
-            frame,
-            RequestPaneProps {
-                is_selected: self.is_selected(PrimaryPane::Request),
-                active_request: props.active_request,
-            },
-            request_area,
-        );
-        self.response_pane.draw(
-            frame,
-            ResponsePaneProps {
-                is_selected: self.is_selected(PrimaryPane::Response),
-                active_request: props.active_request,
-            },
-            response_area,
-        );
+        // In tabbed layout, one of these is hidden. Clear its area so it
+        // doesn't catch clicks meant for the visible one
+        if request_area.is_empty() {
+            self.request_pane.set_area(request_area);
+        } else {
+            self.request_pane.draw(
+                frame,
+                RequestPaneProps {
+                    is_selected: self.is_selected(PrimaryPane::Request),
+                    active_request: props.active_request,
+                },
+                request_area,
+            );
+        }
+        if response_area.is_empty() {
+            self.response_pane.set_area(response_area);
+        } else {
+            self.response_pane.draw(
+                frame,
+                ResponsePaneProps {
+                    is_selected: self.is_selected(PrimaryPane::Response),
+                    active_request: props.active_request,
+                },
+                response_area,
+            );
+        }
     }
 
     fn toggle_fullscreen(&mut self, mode: FullscreenMode) {
@@ -241,26 +303,93 @@ impl PrimaryView {
         layout(area, Direction::Vertical, [profiles, Constraint::Min(0)])
     }
 
-    /// Get layout for the right column of panes
-    fn get_right_column_layout(&self, area: Rect) -> [Rect; 3] {
-        // Split right column vertically. Expand the currently selected pane
-        let (top, middle, bottom) = if self.is_selected(PrimaryPane::Recipe) {
-            (3, 1, 1)
-        } else if self.is_selected(PrimaryPane::Request) {
-            (1, 3, 1)
-        } else if self.is_selected(PrimaryPane::Response) {
-            (1, 1, 3)
+    /// Get layout for the right column of panes: recipe, request, and
+    /// response. In tabbed layout, the hidden pane gets an empty area.
+    fn get_right_column_layout(
+        &self,
+        area: Rect,
+        exchange_layout: ExchangeLayout,
+    ) -> [Rect; 3] {
+        let request_selected = self.is_selected(PrimaryPane::Request);
+        let response_selected = self.is_selected(PrimaryPane::Response);
+        match exchange_layout {
+            ExchangeLayout::Stacked => {
+                // Split right column vertically. Expand the currently
+                // selected pane
+                let (top, middle, bottom) =
+                    if self.is_selected(PrimaryPane::Recipe) {
+                        (3, 1, 1)
+                    } else if request_selected {
+                        (1, 3, 1)
+                    } else if response_selected {
+                        (1, 1, 3)
+                    } else {
+                        (1, 1, 1) // Default to even sizing
+                    };
+                let denominator = top + middle + bottom;
+                layout(
+                    area,
+                    Direction::Vertical,
+                    [
+                        Constraint::Ratio(top, denominator),
+                        Constraint::Ratio(middle, denominator),
+                        Constraint::Ratio(bottom, denominator),
+                    ],
+                )
+            }
+            ExchangeLayout::Split => {
+                let [recipe_area, exchange_area] =
+                    self.get_exchange_row_layout(area);
+                // Widen the selected side
+                let (left, right) = if request_selected {
+                    (3, 2)
+                } else if response_selected {
+                    (2, 3)
+                } else {
+                    (1, 1)
+                };
+                let [request_area, response_area] = layout(
+                    exchange_area,
+                    Direction::Horizontal,
+                    [
+                        Constraint::Ratio(left, left + right),
+                        Constraint::Ratio(right, left + right),
+                    ],
+                );
+                [recipe_area, request_area, response_area]
+            }
+            ExchangeLayout::Tabbed => {
+                let [recipe_area, exchange_area] =
+                    self.get_exchange_row_layout(area);
+                // The response is usually what you want to see, so show it
+                // unless the request is selected
+                if request_selected {
+                    [recipe_area, exchange_area, Rect::default()]
+                } else {
+                    [recipe_area, Rect::default(), exchange_area]
+                }
+            }
+        }
+    }
+
+    /// Split the right column into the recipe pane, and one row for the
+    /// request and response. Expand whichever is selected
+    fn get_exchange_row_layout(&self, area: Rect) -> [Rect; 2] {
+        let (top, bottom) = if self.is_selected(PrimaryPane::Recipe) {
+            (3, 2)
+        } else if self.is_selected(PrimaryPane::Request)
+            || self.is_selected(PrimaryPane::Response)
+        {
+            (1, 3)
         } else {
-            (1, 1, 1) // Default to even sizing
+            (1, 2)
         };
-        let denominator = top + middle + bottom;
         layout(
             area,
             Direction::Vertical,
             [
-                Constraint::Ratio(top, denominator),
-                Constraint::Ratio(middle, denominator),
-                Constraint::Ratio(bottom, denominator),
+                Constraint::Ratio(top, top + bottom),
+                Constraint::Ratio(bottom, top + bottom),
             ],
         )
     }
@@ -281,21 +410,32 @@ impl EventHandler for PrimaryView {
                 }
             }
             // Send HTTP request
-            Event::HttpSendRequest => {
-                if let Some(recipe) = self.selected_recipe() {
+            Event::HttpSendRequest {
+                headers_only,
+                review,
+                stream,
+            } => {
+                let (headers_only, review, stream) =
+                    (*headers_only, *review, *stream);
+                if let Some(request_config) =
+                    self.request_config(headers_only, review, stream)
+                {
                     TuiContext::send_message(Message::HttpBeginRequest(
-                        RequestConfig {
-                            // Reach into the children to grab state (ugly!)
-                            recipe_id: recipe.id.clone(),
-                            profile_id: self
-                                .selected_profile()
-                                .map(|profile| profile.id.clone()),
-                            options: self.recipe_pane.recipe_options(),
-                        },
+                        request_config,
                     ));
+                    // There won't be a body to look at
+                    if headers_only {
+                        self.response_pane.select_headers_tab();
+                    }
                 }
             }
 
+            // Body was edited externally
+            Event::OverrideRecipeBody { recipe_id, body } => {
+                self.recipe_pane
+                    .set_body_override(recipe_id.clone(), body.clone());
+            }
+
             // Input messages
             Event::Input {
                 action: Some(action),
@@ -327,7 +467,17 @@ impl EventHandler for PrimaryView {
                 }
                 Action::Submit => {
                     // Send a request from anywhere
-                    EventQueue::push(Event::HttpSendRequest);
+                    EventQueue::push(Event::HttpSendRequest {
+                        headers_only: false,
+                        review: false,
+                        stream: false,
+                    });
+                }
+                Action::RepeatRequest => {
+                    // The flags are ignored; the last request's are used
+                    TuiContext::send_message(Message::HttpRepeatRequest {
+                        selected: self.request_config(false, false, false),
+                    });
                 }
                 Action::OpenActions => {
                     EventQueue::open_modal_default::<ActionsModal>();
@@ -368,6 +518,9 @@ impl EventHandler for PrimaryView {
                         }
                     }
                 }
+                Action::ToggleLayout => {
+                    self.layout_override = Some(self.layout.get().next());
+                }
                 // Exit fullscreen
                 Action::Cancel if self.fullscreen_mode.is_some() => {
                     *self.fullscreen_mode = None;
@@ -409,9 +562,7 @@ impl<'a> Draw<PrimaryViewProps<'a>> for PrimaryView {
                 RecipePaneProps {
                     is_selected: true,
                     selected_recipe: self.selected_recipe(),
-                    selected_profile_id: self
-                        .selected_profile()
-                        .map(|profile| &profile.id),
+                    selected_profile: self.selected_profile(),
                 },
                 area,
             ),
@@ -1,59 +1,156 @@
 use crate::{
-    collection::{Profile, ProfileId},
-    tui::view::{
-        common::{table::Table, template_preview::TemplatePreview, Pane},
-        draw::{Draw, Generate},
-        state::StateCell,
+    collection::{ChainId, Collection, Profile, ProfileId},
+    template::Template,
+    tui::{
+        context::TuiContext,
+        view::{
+            common::{table::Table, template_preview::TemplatePreview, Pane},
+            draw::{Draw, Generate},
+            state::StateCell,
+            util::layout,
+        },
     },
 };
 use itertools::Itertools;
-use ratatui::{layout::Rect, Frame};
+use ratatui::{
+    layout::{Constraint, Direction, Rect},
+    text::Text,
+    Frame,
+};
+use std::collections::HashMap;
 
-/// Display the contents of a profile
+/// Display the contents of a profile. Alongside the profile's own fields, this
+/// shows the resolved value of each chain the profile uses, as rendered under
+/// that profile. This makes it easy to sanity check the configuration before
+/// sending anything.
 #[derive(Debug, Default)]
 pub struct ProfilePane {
-    fields: StateCell<ProfileId, Vec<(String, TemplatePreview)>>,
+    /// Chains used by each profile, and whether each has side effects.
+    /// Previews are rendered just by selecting a profile, so chains that run
+    /// a command or send a request aren't previewed. They only run when the
+    /// user sends a request that uses them.
+    chains: HashMap<ProfileId, Vec<(ChainId, bool)>>,
+    state: StateCell<ProfileId, ProfilePaneState>,
 }
 
 pub struct ProfilePaneProps<'a> {
     pub profile: &'a Profile,
 }
 
+/// Rendered previews for the selected profile. Rebuilt when the profile
+/// changes.
+#[derive(Debug)]
+struct ProfilePaneState {
+    fields: Vec<(String, TemplatePreview)>,
+    /// `None` for chains that aren't previewed because of side effects
+    chains: Vec<(ChainId, Option<TemplatePreview>)>,
+}
+
+impl ProfilePane {
+    pub fn new(collection: &Collection) -> Self {
+        Self {
+            chains: collection
+                .profiles
+                .values()
+                .map(|profile| {
+                    (profile.id.clone(), collection.profile_chains(profile))
+                })
+                .collect(),
+            state: Default::default(),
+        }
+    }
+}
+
 impl<'a> Draw<ProfilePaneProps<'a>> for ProfilePane {
     fn draw(&self, frame: &mut Frame, props: ProfilePaneProps<'a>, area: Rect) {
         // Whenever the selected profile changes, rebuild the internal state.
         // This is needed because the template preview rendering is async.
-        let fields =
-            self.fields.get_or_update(props.profile.id.clone(), || {
-                props
-                    .profile
-                    .data
-                    .iter()
-                    .map(|(key, template)| {
-                        (
-                            key.clone(),
-                            TemplatePreview::new(
-                                template.clone(),
-                                Some(props.profile.id.clone()),
-                            ),
+        let state = self.state.get_or_update(props.profile.id.clone(), || {
+            let profile_id = &props.profile.id;
+            let fields = props
+                .profile
+                .data
+                .iter()
+                .map(|(key, template)| {
+                    (
+                        key.clone(),
+                        TemplatePreview::new(
+                            template.clone(),
+                            Some(profile_id.clone()),
+                        ),
+                    )
+                })
+                .collect_vec();
+            // Each chain is rendered independently, so an error in one
+            // doesn't prevent the others from showing
+            let chains = self
+                .chains
+                .get(profile_id)
+                .into_iter()
+                .flatten()
+                .map(|(id, side_effects)| {
+                    let preview = (!side_effects).then(|| {
+                        TemplatePreview::new(
+                            Template::from_chain(id),
+                            Some(profile_id.clone()),
                         )
-                    })
-                    .collect_vec()
-            });
+                    });
+                    (id.clone(), preview)
+                })
+                .collect_vec();
+            ProfilePaneState { fields, chains }
+        });
 
-        let pane = Pane {
+        let block = Pane {
             title: "Profile",
             is_focused: false,
-        };
-        let table = Table {
+        }
+        .generate();
+        let inner_area = block.inner(area);
+        frame.render_widget(block, area);
+
+        let [fields_area, _, chains_area] = layout(
+            inner_area,
+            Direction::Vertical,
+            [
+                // +1 for the header row
+                Constraint::Length(state.fields.len() as u16 + 1),
+                Constraint::Length(1),
+                Constraint::Min(0),
+            ],
+        );
+
+        let fields = Table {
             header: Some(["Field", "Value"]),
-            rows: fields
+            rows: state
+                .fields
                 .iter()
                 .map(|(key, value)| [key.as_str().into(), value.generate()])
                 .collect_vec(),
             alternate_row_style: true,
             ..Default::default()
         };
-        frame.render_widget(table.generate().block(pane.generate()), area);
+        frame.render_widget(fields.generate(), fields_area);
+
+        let chains = Table {
+            header: Some(["Chain", "Value"]),
+            rows: state
+                .chains
+                .iter()
+                .map(|(id, value)| {
+                    let value = match value {
+                        Some(preview) => preview.generate(),
+                        None => Text::styled(
+                            "<runs a command or request; not previewed>",
+                            TuiContext::get().theme.text_box.placeholder,
+                        ),
+                    };
+                    [id.as_str().into(), value]
+                })
+                .collect_vec(),
+            alternate_row_style: true,
+            ..Default::default()
+        };
+        frame.render_widget(chains.generate(), chains_area);
     }
 }
@@ -4,13 +4,14 @@ use crate::{
         context::TuiContext,
         input::Action,
         view::{
-            common::{list::List, Pane},
+            common::{list::List, Pane, ProfileBadge},
             draw::{Draw, Generate},
             event::{Event, EventHandler, EventQueue, Update},
             state::{
                 persistence::{Persistable, Persistent, PersistentKey},
                 select::SelectState,
             },
+            theme::Theme,
             Component,
         },
     },
@@ -18,6 +19,7 @@ use crate::{
 use ratatui::{
     prelude::Rect,
     style::{Modifier, Style},
+    text::Line,
     widgets::Paragraph,
     Frame,
 };
@@ -50,6 +52,11 @@ impl ProfileListPane {
     pub fn profiles(&self) -> &SelectState<Profile> {
         &self.profiles
     }
+
+    /// Select a profile by ID. If the ID isn't in the list, do nothing
+    pub fn select_profile(&mut self, id: &ProfileId) {
+        self.profiles.select(id);
+    }
 }
 
 impl EventHandler for ProfileListPane {
@@ -77,11 +84,22 @@ impl Draw<ProfileListPaneProps> for ProfileListPane {
         let title = TuiContext::get()
             .input_engine
             .add_hint("Profiles", Action::SelectProfileList);
-        let block = Pane {
+        let selected = self.profiles().selected();
+        let mut block = Pane {
             title: &title,
             is_focused: props.is_selected,
         }
         .generate();
+        // Tint the border for flagged profiles, as an extra reminder
+        if let Some(profile) = selected.filter(|profile| profile.is_flagged()) {
+            block = block.border_style(
+                TuiContext::get()
+                    .theme
+                    .pane
+                    .border_style(props.is_selected)
+                    .fg(Theme::profile_color(profile.color)),
+            );
+        }
         let inner_area = block.inner(area);
         frame.render_widget(block, area);
 
@@ -98,16 +116,17 @@ impl Draw<ProfileListPaneProps> for ProfileListPane {
             );
         } else {
             // Pane is not selected - just show the selected profile
-            let profile = self
-                .profiles()
-                .selected()
-                .map(|profile| profile.name())
-                .unwrap_or("<none>");
-            frame.render_widget(
-                Paragraph::new(profile)
-                    .style(Style::new().add_modifier(Modifier::BOLD)),
-                inner_area,
-            )
+            let mut line = Line::styled(
+                selected.map(Profile::name).unwrap_or("<none>"),
+                Style::new().add_modifier(Modifier::BOLD),
+            );
+            if let Some(profile) =
+                selected.filter(|profile| profile.label.is_some())
+            {
+                line.spans.push(" ".into());
+                line.spans.push(ProfileBadge { profile }.generate());
+            }
+            frame.render_widget(Paragraph::new(line), inner_area)
         }
     }
 }
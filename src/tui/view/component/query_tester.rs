@@ -0,0 +1,273 @@
+//! Interactive tester for JSONPath queries
+
+use crate::{
+    http::{ContentType, Query, ResponseContent},
+    tui::{
+        context::TuiContext,
+        input::Action,
+        message::Message,
+        view::{
+            common::{
+                modal::Modal, text_box::TextBox, text_window::TextWindow,
+            },
+            draw::Draw,
+            event::{Event, EventHandler, Update},
+            state::StateCell,
+            util::layout,
+            Component,
+        },
+    },
+};
+use derive_more::Debug;
+use ratatui::{
+    layout::{Alignment, Constraint, Direction, Rect},
+    text::Line,
+    widgets::Paragraph,
+    Frame,
+};
+use serde_json::Value;
+
+/// A modal to try out JSONPath queries against a body, with results updated
+/// as the user types. This uses the same query engine as chain selectors, so
+/// it also shows exactly what a chain with the query would produce. The body
+/// can be replaced in the user's editor, e.g. to paste in some other JSON.
+#[derive(Debug)]
+pub struct QueryTesterModal {
+    /// Content type to parse the body as
+    content_type: ContentType,
+    /// Body text, as given or edited by the user
+    body: String,
+    /// Parsed body, or the error from parsing it
+    #[debug(skip)]
+    parsed_body: Result<Box<dyn ResponseContent>, String>,
+    /// Where the user enters their query
+    #[debug(skip)]
+    query_text_box: Component<TextBox>,
+    /// Results for the current query text. Rebuilt whenever the query changes,
+    /// and reset whenever the body changes
+    #[debug(skip)]
+    output: StateCell<String, QueryOutput>,
+}
+
+/// Results of applying a query to the body
+struct QueryOutput {
+    /// One-line summary, e.g. the number of matches
+    summary: Result<String, String>,
+    /// Every matching value. If there's no query, this is the entire body
+    matches: Component<TextWindow<String>>,
+}
+
+impl QueryTesterModal {
+    pub fn new(content_type: ContentType, body: String) -> Self {
+        let mut modal = Self {
+            content_type,
+            body: String::new(),
+            parsed_body: Err(String::new()),
+            query_text_box: TextBox::default()
+                .with_placeholder("Enter a JSONPath, e.g. $.items[*].id | sort")
+                .with_validator(|text| text.parse::<Query>().is_ok())
+                .into(),
+            output: Default::default(),
+        };
+        modal.set_body(body);
+        modal
+    }
+
+    /// Replace the body being queried
+    fn set_body(&mut self, body: String) {
+        self.parsed_body = self
+            .content_type
+            .parse_content(body.as_bytes())
+            .map_err(|error| format!("Error parsing body: {error}"));
+        self.body = body;
+        self.output = Default::default();
+    }
+
+    /// Open the body in the user's editor. The edited body will be sent back
+    /// to us in an event
+    fn edit_body(&self) {
+        TuiContext::send_message(Message::EditQueryTesterBody(
+            self.body.clone(),
+        ));
+    }
+}
+
+impl Modal for QueryTesterModal {
+    fn title(&self) -> &str {
+        "JSONPath Tester"
+    }
+
+    fn dimensions(&self) -> (Constraint, Constraint) {
+        (Constraint::Percentage(80), Constraint::Percentage(80))
+    }
+}
+
+impl EventHandler for QueryTesterModal {
+    fn update(&mut self, event: Event) -> Update {
+        match event {
+            Event::Input {
+                action: Some(Action::Search),
+                ..
+            } => self.query_text_box.focus(),
+            Event::Input {
+                action: Some(Action::Edit),
+                ..
+            } => self.edit_body(),
+            Event::OverrideQueryTesterBody(body) => self.set_body(body),
+            _ => return Update::Propagate(event),
+        }
+        Update::Consumed
+    }
+
+    fn children(&mut self) -> Vec<Component<&mut dyn EventHandler>> {
+        if self.query_text_box.is_focused() {
+            vec![self.query_text_box.as_child()]
+        } else if let Some(output) = self.output.get_mut() {
+            vec![output.matches.as_child()]
+        } else {
+            vec![]
+        }
+    }
+}
+
+impl Draw for QueryTesterModal {
+    fn draw(&self, frame: &mut Frame, _: (), area: Rect) {
+        let context = TuiContext::get();
+        let [query_area, summary_area, matches_area] = layout(
+            area,
+            Direction::Vertical,
+            [
+                Constraint::Length(1),
+                Constraint::Length(1),
+                Constraint::Min(0),
+            ],
+        );
+
+        self.query_text_box.draw(frame, (), query_area);
+
+        // Re-run the query whenever the text changes, so results are live
+        let query = self.query_text_box.text();
+        let output = self.output.get_or_update(query.to_owned(), || {
+            let (summary, matches) = match &self.parsed_body {
+                Ok(body) => evaluate(body.as_ref(), query),
+                Err(error) => (Err(error.clone()), self.body.clone()),
+            };
+            QueryOutput {
+                summary,
+                matches: TextWindow::new(matches).into(),
+            }
+        });
+
+        let summary = match &output.summary {
+            Ok(summary) => Line::from(summary.as_str()),
+            Err(error) => {
+                Line::styled(error.as_str(), context.theme.text.error)
+            }
+        };
+        frame.render_widget(Paragraph::new(summary), summary_area);
+        let hints = format!(
+            "{} / {}",
+            context.input_engine.add_hint("Edit query", Action::Search),
+            context.input_engine.add_hint("Edit body", Action::Edit),
+        );
+        frame.render_widget(
+            Paragraph::new(hints).alignment(Alignment::Right),
+            summary_area,
+        );
+
+        output.matches.draw(frame, (), matches_area);
+    }
+}
+
+/// Apply a query to a body. Returns a summary of the matches, and the matches
+/// themselves as prettified text. The summary includes the value a chain with
+/// this selector would produce, which requires exactly one match.
+fn evaluate(
+    body: &dyn ResponseContent,
+    query: &str,
+) -> (Result<String, String>, String) {
+    if query.is_empty() {
+        return (Ok("Showing entire body".into()), body.prettify());
+    }
+    let query: Query = match query.parse() {
+        Ok(query) => query,
+        Err(error) => {
+            return (Err(format!("Invalid JSONPath: {error}")), String::new())
+        }
+    };
+
+    let matches = match query.query(body) {
+        Ok(matches) => matches,
+        Err(error) => return (Err(error.to_string()), String::new()),
+    };
+    let count = match matches.to_json().as_ref() {
+        Value::Array(values) => values.len(),
+        _ => 1,
+    };
+    let summary = match query.query_to_string(body) {
+        // Transforms always produce a single value, so the match count would
+        // be meaningless
+        Ok(value) if query.has_transforms() => {
+            Ok(format!("Chain value: {value}"))
+        }
+        Ok(value) => Ok(format!("1 match; chain value: {value}")),
+        Err(error) => Err(format!(
+            "{count} match{}; chain error: {error}",
+            if count == 1 { "" } else { "es" }
+        )),
+    };
+    (summary, matches.prettify())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::Json;
+    use rstest::rstest;
+    use serde_json::json;
+
+    #[rstest]
+    #[case::empty(
+        "",
+        Ok("Showing entire body"),
+        "{\n  \"a\": [\n    1,\n    2\n  ]\n}"
+    )]
+    #[case::one("$.a[0]", Ok("1 match; chain value: 1"), "[\n  1\n]")]
+    #[case::many(
+        "$.a[*]",
+        Err("2 matches; chain error: Expected exactly one result from query"),
+        "[\n  1,\n  2\n]"
+    )]
+    #[case::none(
+        "$.b",
+        Err("0 matches; chain error: Expected exactly one result from query"),
+        "[]"
+    )]
+    #[case::transform("$.a[*] | length", Ok("Chain value: 2"), "2")]
+    #[case::transform_error(
+        "$.a[*] | first | keys",
+        Err("Cannot apply `keys` to number"),
+        ""
+    )]
+    fn test_evaluate(
+        #[case] query: &str,
+        #[case] expected_summary: Result<&str, &str>,
+        #[case] expected_matches: &str,
+    ) {
+        let body = Json::from(json!({"a": [1, 2]}));
+        let (summary, matches) = evaluate(&body, query);
+        assert_eq!(
+            summary.as_deref().map_err(String::as_str),
+            expected_summary
+        );
+        assert_eq!(matches, expected_matches);
+    }
+
+    #[test]
+    fn test_evaluate_invalid() {
+        let body = Json::from(json!({}));
+        let (summary, matches) = evaluate(&body, "$.");
+        assert!(summary.unwrap_err().starts_with("Invalid JSONPath"));
+        assert_eq!(matches, "");
+    }
+}
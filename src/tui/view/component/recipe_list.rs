@@ -17,9 +17,15 @@ use crate::{
 };
 use derive_more::{Deref, DerefMut};
 use itertools::Itertools;
-use ratatui::{prelude::Rect, Frame};
+use ratatui::{
+    prelude::Rect,
+    style::Style,
+    widgets::{List, ListItem},
+    Frame,
+};
 use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
+use tracing::warn;
 
 /// List/tree of recipes and folders. This is mostly just a list, but with some
 /// extra logic to allow expanding/collapsing nodes. This could be made into a
@@ -42,6 +48,13 @@ pub struct RecipeListPane {
     /// Set of all folders that are collapsed
     /// Invariant: No recipes, only folders
     collapsed: Persistent<Collapsed>,
+    /// Recipes pinned to the top of the list, in the order they were pinned.
+    /// This can include recipes that are no longer in the collection, so the
+    /// pin comes back if the recipe does. Saved to the DB whenever it changes
+    pinned: Vec<RecipeId>,
+    /// Number of items at the top of the list that are pinned recipes. These
+    /// are duplicates of nodes that also appear in the tree below.
+    pinned_count: usize,
 }
 
 pub struct RecipeListPaneProps {
@@ -69,14 +82,23 @@ impl RecipeListPane {
             PersistentKey::RecipeCollapsed,
             Collapsed::default(),
         );
-        let persistent = Persistent::new(
-            PersistentKey::RecipeId,
-            build_select_state(recipes, &collapsed),
-        );
+        // Pins are loaded directly instead of via Persistent, because they
+        // should be saved regardless of the UI persistence setting
+        let pinned: Vec<RecipeId> = TuiContext::get()
+            .database
+            .get_ui(PersistentKey::RecipePinned)
+            .ok()
+            .flatten()
+            .unwrap_or_default();
+        let (select_state, pinned_count) =
+            build_select_state(recipes, &collapsed, &pinned);
+        let persistent = Persistent::new(PersistentKey::RecipeId, select_state);
         Self {
             recipes: recipes.clone(),
             select_state: persistent.into(),
             collapsed,
+            pinned,
+            pinned_count,
         }
     }
 
@@ -86,6 +108,83 @@ impl RecipeListPane {
         self.select_state.selected().and_then(RecipeNode::recipe)
     }
 
+    /// Select a recipe/folder by ID. If the node is hidden inside a collapsed
+    /// folder, all of its ancestors will be expanded so it's visible.
+    pub fn select_node(&mut self, id: &RecipeId) {
+        if let Some([ancestors @ .., _]) = self
+            .recipes
+            .get_lookup_key(id)
+            .map(RecipeLookupKey::as_slice)
+        {
+            let mut changed = false;
+            for ancestor in ancestors {
+                changed |= self.collapsed.remove(ancestor);
+            }
+            if changed {
+                self.rebuild_select_state();
+            }
+        }
+        self.select_state.select(id);
+    }
+
+    /// Pin the selected recipe to the top of the list, or unpin it if it's
+    /// already pinned. Folders can't be pinned.
+    fn toggle_selected_pin(&mut self) {
+        let Some(recipe_id) = self
+            .select_state
+            .selected()
+            .and_then(RecipeNode::recipe)
+            .map(|recipe| recipe.id.clone())
+        else {
+            return;
+        };
+        if let Some(index) = self.pinned.iter().position(|id| id == &recipe_id)
+        {
+            self.pinned.remove(index);
+        } else {
+            self.pinned.push(recipe_id);
+        }
+        let _ = TuiContext::get()
+            .database
+            .set_ui(PersistentKey::RecipePinned, &self.pinned);
+        self.rebuild_select_state();
+    }
+
+    /// Rebuild the list of visible items, e.g. after a folder is collapsed or
+    /// a recipe is pinned. The selection is carried over, staying in the same
+    /// section (pinned vs tree) if possible.
+    fn rebuild_select_state(&mut self) {
+        let old_pinned_count = self.pinned_count;
+        let old_index = self.select_state.selected_index();
+        let selected_id = self.select_state.selected().map(|node| node.id());
+
+        let (mut new_select_state, pinned_count) =
+            build_select_state(&self.recipes, &self.collapsed, &self.pinned);
+        if let (Some(old_index), Some(selected_id)) = (old_index, selected_id) {
+            let items = new_select_state.items();
+            let position_in = |range: std::ops::Range<usize>| {
+                items[range.clone()]
+                    .iter()
+                    .position(|node| node.id() == selected_id)
+                    .map(|i| i + range.start)
+            };
+            let pinned_section = 0..pinned_count;
+            let tree_section = pinned_count..items.len();
+            let index = if old_index < old_pinned_count {
+                position_in(pinned_section)
+                    .or_else(|| position_in(tree_section))
+            } else {
+                position_in(tree_section)
+                    .or_else(|| position_in(pinned_section))
+            };
+            if let Some(index) = index {
+                new_select_state.select_index(index);
+            }
+        }
+        self.pinned_count = pinned_count;
+        **self.select_state = new_select_state;
+    }
+
     /// Set the currently selected folder as expanded/collapsed (or toggle it).
     /// If a folder is not selected, do nothing. Returns whether a change was
     /// made.
@@ -111,13 +210,7 @@ impl RecipeListPane {
 
         // If we changed the set of what is visible, rebuild the list state
         if changed {
-            let mut new_select_state =
-                build_select_state(&self.recipes, &self.collapsed);
-            // Carry over the selection
-            if let Some(selected) = self.select_state.selected() {
-                new_select_state.select(selected.id());
-            }
-            **self.select_state = new_select_state;
+            self.rebuild_select_state();
         }
 
         changed
@@ -138,6 +231,7 @@ impl EventHandler for RecipeListPane {
                 Action::Right => {
                     self.set_selected_collapsed(CollapseState::Expand);
                 }
+                Action::TogglePin => self.toggle_selected_pin(),
                 Action::Submit => {
                     if !self.set_selected_collapsed(CollapseState::Toggle) {
                         // Propgate submit event for recipes, so it launches a
@@ -177,7 +271,21 @@ impl Draw<RecipeListPaneProps> for RecipeListPane {
             .select_state
             .items()
             .iter()
-            .map(|node| {
+            .enumerate()
+            .map(|(i, node)| {
+                // Disabled nodes are grayed out, if they aren't hidden
+                let style = if self.recipes.is_disabled(node.id()) {
+                    context.theme.list.disabled
+                } else {
+                    Style::default()
+                };
+
+                // Pinned recipes are shown flat, at the top
+                if i < self.pinned_count {
+                    return ListItem::new(format!("★{}", node.name()))
+                        .style(style);
+                }
+
                 let (icon, name) = match node {
                     RecipeNode::Folder(folder) => {
                         let icon = if self.collapsed.is_collapsed(&folder.id) {
@@ -200,14 +308,15 @@ impl Draw<RecipeListPaneProps> for RecipeListPane {
                     - 1;
 
                 // Apply indentation
-                format!(
+                ListItem::new(format!(
                     "{indent:width$}{icon}{name}",
                     indent = "",
                     width = depth
-                )
+                ))
+                .style(style)
             })
             .collect_vec();
-        let list = ratatui::widgets::List::new(items)
+        let list = List::new(items)
             .block(pane.generate())
             .highlight_style(context.theme.list.highlight);
 
@@ -264,22 +373,48 @@ impl Persistable for Collapsed {
     }
 }
 
-/// Construct select list based on which nodes are currently visible
+/// Construct select list based on which nodes are currently visible. Pinned
+/// recipes go at the top, followed by the tree. Disabled nodes are left out
+/// if the config says to hide them. Returns the select state, and
+/// the number of pinned items at the top.
 fn build_select_state(
     recipes: &RecipeTree,
     collapsed: &Collapsed,
-) -> SelectState<RecipeNode> {
+    pinned: &[RecipeId],
+) -> (SelectState<RecipeNode>, usize) {
     // When highlighting a new recipe, load it from the repo
     fn on_select(_: &mut RecipeNode) {
         // If a recipe isn't selected, this will do nothing
         EventQueue::push(Event::HttpLoadRequest);
     }
 
-    let items = recipes
+    let hide_disabled = TuiContext::get().config.hide_disabled_recipes;
+    let is_hidden = |id: &RecipeId| hide_disabled && recipes.is_disabled(id);
+
+    // Skip any pins that aren't in the collection anymore
+    let pinned_nodes = pinned
         .iter()
-        // Filter out hidden nodes
-        .filter(|(lookup_key, _)| collapsed.is_visible(lookup_key))
-        .map(|(_, node)| node.clone())
+        .filter(|id| !is_hidden(id))
+        .filter_map(|id| match recipes.get_recipe(id) {
+            Some(recipe) => Some(RecipeNode::Recipe(recipe.clone())),
+            None => {
+                warn!(%id, "Pinned recipe not in collection");
+                None
+            }
+        })
+        .collect_vec();
+    let pinned_count = pinned_nodes.len();
+    let items = pinned_nodes
+        .into_iter()
+        .chain(
+            recipes
+                .iter()
+                // Filter out hidden nodes
+                .filter(|(lookup_key, node)| {
+                    collapsed.is_visible(lookup_key) && !is_hidden(node.id())
+                })
+                .map(|(_, node)| node.clone()),
+        )
         .collect();
-    SelectState::new(items).on_select(on_select)
+    (SelectState::new(items).on_select(on_select), pinned_count)
 }
@@ -1,7 +1,7 @@
 use crate::{
-    collection::{Authentication, ProfileId, Recipe, RecipeId},
-    http::RecipeOptions,
-    template::Template,
+    collection::{Authentication, Profile, ProfileId, Recipe, RecipeId},
+    http::{BodyOverride, RecipeOptions, RequestFormat},
+    template::{format_json, Template},
     tui::{
         context::TuiContext,
         input::Action,
@@ -27,15 +27,17 @@ use crate::{
         },
     },
 };
+use anyhow::Context;
 use derive_more::Display;
 use itertools::Itertools;
 use ratatui::{
     prelude::{Constraint, Direction, Rect},
+    text::{Line, Span, Text},
     widgets::{Paragraph, Row, TableState},
     Frame,
 };
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use strum::{EnumCount, EnumIter};
 
 /// Display a request recipe
@@ -45,6 +47,12 @@ pub struct RecipePane {
     /// All UI state derived from the recipe is stored together, and reset when
     /// the recipe or profile changes
     recipe_state: StateCell<RecipeStateKey, RecipeState>,
+    /// Bodies that the user has edited in their external editor. These
+    /// replace the recipe's body until the collection is reloaded.
+    body_overrides: HashMap<RecipeId, Template>,
+    /// Show the fully rendered URL, with query parameters, instead of the
+    /// URL template. Applies to every recipe for the rest of the session.
+    show_resolved_url: bool,
 }
 
 impl Default for RecipePane {
@@ -52,6 +60,8 @@ impl Default for RecipePane {
         Self {
             tabs: Tabs::new(PersistentKey::RecipeTab).into(),
             recipe_state: Default::default(),
+            body_overrides: Default::default(),
+            show_resolved_url: false,
         }
     }
 }
@@ -59,7 +69,7 @@ impl Default for RecipePane {
 pub struct RecipePaneProps<'a> {
     pub is_selected: bool,
     pub selected_recipe: Option<&'a Recipe>,
-    pub selected_profile_id: Option<&'a ProfileId>,
+    pub selected_profile: Option<&'a Profile>,
 }
 
 /// Template preview state will be recalculated when any of these fields change
@@ -71,11 +81,25 @@ struct RecipeStateKey {
 
 #[derive(Debug)]
 struct RecipeState {
+    method: TemplatePreview,
     url: TemplatePreview,
     query: Component<Persistent<SelectState<RowState, TableState>>>,
     headers: Component<Persistent<SelectState<RowState, TableState>>>,
     body: Option<Component<TextWindow<TemplatePreview>>>,
     authentication: Option<Component<AuthenticationDisplay>>,
+    /// Only rendered while the resolved URL is being shown
+    resolved_url: Option<ResolvedUrl>,
+}
+
+/// The URL as it will be sent: the URL template and enabled query parameters,
+/// rendered for the selected profile. Unlike the other previews, these are
+/// rendered even if template previews are disabled, because the user asked
+/// to see them.
+#[derive(Debug)]
+struct ResolvedUrl {
+    url: TemplatePreview,
+    /// One per query row, in the same order
+    query: Vec<TemplatePreview>,
 }
 
 #[derive(
@@ -115,6 +139,24 @@ enum MenuAction {
     CopyBody,
     #[display("Copy as cURL")]
     CopyCurl,
+    #[display("Copy as fetch()")]
+    CopyFetch,
+    #[display("Copy as HTTPie")]
+    CopyHttpie,
+    #[display("Edit Body")]
+    EditBody,
+    #[display("Format Body (JSON)")]
+    FormatBody,
+    #[display("Send Request (Headers Only)")]
+    SendHeadersOnly,
+    #[display("Edit & Send Request")]
+    SendReview,
+    #[display("Stream Responses (Long Poll)")]
+    SendStream,
+    #[display("Show/Hide Resolved URL")]
+    ToggleResolvedUrl,
+    #[display("Explain Request")]
+    Explain,
 }
 
 impl ToStringGenerate for MenuAction {}
@@ -138,6 +180,12 @@ impl RecipePane {
             RecipeOptions {
                 disabled_headers: to_disabled_set(&state.headers),
                 disabled_query_parameters: to_disabled_set(&state.query),
+                body: self.recipe_state.key().and_then(|key| {
+                    self.body_overrides
+                        .get(&key.recipe_id)
+                        .cloned()
+                        .map(BodyOverride::Template)
+                }),
             }
         } else {
             // Shouldn't be possible, because state is initialized on first
@@ -146,6 +194,74 @@ impl RecipePane {
         }
     }
 
+    /// Replace the body of a recipe for the rest of the session. If the recipe
+    /// is currently selected, the body preview is rebuilt immediately.
+    pub fn set_body_override(&mut self, recipe_id: RecipeId, body: Template) {
+        let selected_profile_id = self.recipe_state.key().and_then(|key| {
+            (key.recipe_id == recipe_id)
+                .then(|| key.selected_profile_id.clone())
+        });
+        if let (Some(selected_profile_id), Some(state)) =
+            (selected_profile_id, self.recipe_state.get_mut())
+        {
+            state.body = Some(
+                TextWindow::new(TemplatePreview::new(
+                    body.clone(),
+                    selected_profile_id,
+                ))
+                .into(),
+            );
+        }
+        self.body_overrides.insert(recipe_id, body);
+    }
+
+    /// Open the current body (including any previous edits) in the user's
+    /// external editor
+    fn edit_body(&self) {
+        let (Some(key), Some(state)) =
+            (self.recipe_state.key(), self.recipe_state.get())
+        else {
+            return;
+        };
+        // If the recipe has no body, the user can create one from scratch
+        let body = state
+            .body
+            .as_ref()
+            .map(|body| body.text().template().as_str().to_owned())
+            .unwrap_or_default();
+        TuiContext::send_message(Message::EditRecipeBody {
+            recipe_id: key.recipe_id.clone(),
+            body,
+        });
+    }
+
+    /// Pretty-print the current body as JSON, replacing it for the rest of the
+    /// session. Template keys are left intact. If the body isn't valid JSON,
+    /// show an error with the position of the problem instead.
+    fn format_body(&mut self) {
+        let (Some(key), Some(state)) =
+            (self.recipe_state.key(), self.recipe_state.get())
+        else {
+            return;
+        };
+        let Some(body) = &state.body else {
+            return;
+        };
+        let recipe_id = key.recipe_id.clone();
+        let result = format_json(body.text().template().as_str())
+            .context("Error formatting body as JSON")
+            .and_then(|formatted| {
+                Template::try_from(formatted)
+                    .context("Error parsing formatted body")
+            });
+        // Release the state borrows before modifying the body
+        drop((key, state));
+        match result {
+            Ok(template) => self.set_body_override(recipe_id, template),
+            Err(error) => TuiContext::send_message(Message::Error { error }),
+        }
+    }
+
     fn handle_menu_action(&mut self, action: MenuAction) {
         // Should always be initialized after first render
         let key = self
@@ -156,11 +272,71 @@ impl RecipePane {
             profile_id: key.selected_profile_id.clone(),
             recipe_id: key.recipe_id.clone(),
             options: self.recipe_options(),
+            headers_only: false,
+            review: false,
+            stream: false,
         };
         let message = match action {
             MenuAction::CopyUrl => Message::CopyRequestUrl(request_config),
+            MenuAction::Explain => Message::ExplainRequest(request_config),
             MenuAction::CopyBody => Message::CopyRequestBody(request_config),
-            MenuAction::CopyCurl => Message::CopyRequestCurl(request_config),
+            MenuAction::CopyCurl => Message::CopyRequestAs {
+                request_config,
+                format: RequestFormat::Curl,
+            },
+            MenuAction::CopyFetch => Message::CopyRequestAs {
+                request_config,
+                format: RequestFormat::Fetch,
+            },
+            MenuAction::CopyHttpie => Message::CopyRequestAs {
+                request_config,
+                format: RequestFormat::Httpie,
+            },
+            MenuAction::EditBody => {
+                drop(key);
+                self.edit_body();
+                return;
+            }
+            MenuAction::FormatBody => {
+                drop(key);
+                self.format_body();
+                return;
+            }
+            MenuAction::SendHeadersOnly => {
+                EventQueue::push(Event::HttpSendRequest {
+                    headers_only: true,
+                    review: false,
+                    stream: false,
+                });
+                return;
+            }
+            MenuAction::SendReview => {
+                EventQueue::push(Event::HttpSendRequest {
+                    headers_only: false,
+                    review: true,
+                    stream: false,
+                });
+                return;
+            }
+            MenuAction::SendStream => {
+                EventQueue::push(Event::HttpSendRequest {
+                    headers_only: false,
+                    review: false,
+                    stream: true,
+                });
+                return;
+            }
+            MenuAction::ToggleResolvedUrl => {
+                let profile_id = key.selected_profile_id.clone();
+                drop(key);
+                self.show_resolved_url ^= true;
+                let show = self.show_resolved_url;
+                if let Some(state) = self.recipe_state.get_mut() {
+                    state.resolved_url =
+                        show.then(|| ResolvedUrl::new(state, profile_id));
+                }
+                return;
+            }
         };
         TuiContext::send_message(message);
     }
@@ -173,6 +349,10 @@ impl EventHandler for RecipePane {
                 action: Some(Action::OpenActions),
                 ..
             } => EventQueue::open_modal_default::<ActionsModal<MenuAction>>(),
+            Event::Input {
+                action: Some(Action::Edit),
+                ..
+            } if *self.tabs.selected() == Tab::Body => self.edit_body(),
             Event::Other(callback) => {
                 match callback.downcast_ref::<MenuAction>() {
                     Some(action) => {
@@ -224,8 +404,6 @@ impl<'a> Draw<RecipePaneProps<'a>> for RecipePane {
 
         // Render request contents
         if let Some(recipe) = props.selected_recipe {
-            let method = recipe.method.to_string();
-
             let [metadata_area, tabs_area, content_area] = layout(
                 inner_area,
                 Direction::Vertical,
@@ -236,13 +414,6 @@ impl<'a> Draw<RecipePaneProps<'a>> for RecipePane {
                 ],
             );
 
-            let [method_area, url_area] = layout(
-                metadata_area,
-                Direction::Horizontal,
-                // Method gets just as much as it needs, URL gets the rest
-                [Constraint::Max(method.len() as u16 + 1), Constraint::Min(0)],
-            );
-
             // Whenever the recipe or profile changes, generate a preview for
             // each templated value. Almost anything that could change the
             // preview will either involve changing one of those two things, or
@@ -250,15 +421,40 @@ impl<'a> Draw<RecipePaneProps<'a>> for RecipePane {
             // UI state.
             let recipe_state = self.recipe_state.get_or_update(
                 RecipeStateKey {
-                    selected_profile_id: props.selected_profile_id.cloned(),
+                    selected_profile_id: props
+                        .selected_profile
+                        .map(|profile| profile.id.clone()),
                     recipe_id: recipe.id.clone(),
                 },
-                || RecipeState::new(recipe, props.selected_profile_id),
+                || {
+                    RecipeState::new(
+                        recipe,
+                        props.selected_profile,
+                        self.body_overrides.get(&recipe.id),
+                        self.show_resolved_url,
+                    )
+                },
             );
 
             // First line: Method + URL
+            let method = recipe_state.method.generate();
+            let [method_area, url_area] = layout(
+                metadata_area,
+                Direction::Horizontal,
+                // Method gets just as much as it needs, URL gets the rest
+                [
+                    Constraint::Max(method.width() as u16 + 1),
+                    Constraint::Min(0),
+                ],
+            );
             frame.render_widget(Paragraph::new(method), method_area);
-            frame.render_widget(&recipe_state.url, url_area);
+            match &recipe_state.resolved_url {
+                Some(resolved_url) => frame.render_widget(
+                    Paragraph::new(resolved_url.generate(&recipe_state.query)),
+                    url_area,
+                ),
+                None => frame.render_widget(&recipe_state.url, url_area),
+            }
 
             // Navigation tabs
             self.tabs.draw(frame, (), tabs_area);
@@ -295,7 +491,13 @@ impl<'a> Draw<RecipePaneProps<'a>> for RecipePane {
 impl RecipeState {
     /// Initialize new recipe state. Should be called whenever the recipe or
     /// profile changes
-    fn new(recipe: &Recipe, selected_profile_id: Option<&ProfileId>) -> Self {
+    fn new(
+        recipe: &Recipe,
+        selected_profile: Option<&Profile>,
+        body_override: Option<&Template>,
+        show_resolved_url: bool,
+    ) -> Self {
+        let selected_profile_id = selected_profile.map(|profile| &profile.id);
         let query_items = recipe
             .query
             .iter()
@@ -327,7 +529,11 @@ impl RecipeState {
             })
             .collect();
 
-        Self {
+        let mut state = Self {
+            method: TemplatePreview::new(
+                recipe.method.clone(),
+                selected_profile_id.cloned(),
+            ),
             url: TemplatePreview::new(
                 recipe.url.clone(),
                 selected_profile_id.cloned(),
@@ -342,15 +548,15 @@ impl RecipeState {
                 SelectState::new(header_items).on_submit(RowState::on_submit),
             )
             .into(),
-            body: recipe.body.as_ref().map(|body| {
+            body: body_override.or(recipe.body.as_ref()).map(|body| {
                 TextWindow::new(TemplatePreview::new(
                     body.clone(),
                     selected_profile_id.cloned(),
                 ))
                 .into()
             }),
-            // Map authentication type
-            authentication: recipe.authentication.as_ref().map(
+            // Map authentication type. The profile may replace the recipe's
+            authentication: recipe.authentication(selected_profile).map(
                 |authentication| {
                     match authentication {
                         Authentication::Basic { username, password } => {
@@ -377,10 +583,86 @@ impl RecipeState {
                     .into() // Convert to Component
                 },
             ),
+            resolved_url: None,
+        };
+        if show_resolved_url {
+            state.resolved_url =
+                Some(ResolvedUrl::new(&state, selected_profile_id.cloned()));
         }
+        state
     }
 }
 
+impl ResolvedUrl {
+    /// Start rendering the URL and query parameters from a recipe's state
+    fn new(state: &RecipeState, profile_id: Option<ProfileId>) -> Self {
+        Self {
+            url: TemplatePreview::rendered(
+                state.url.template().clone(),
+                profile_id.clone(),
+            ),
+            query: state
+                .query
+                .items()
+                .iter()
+                .map(|row| {
+                    TemplatePreview::rendered(
+                        row.value.template().clone(),
+                        profile_id.clone(),
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    /// Build the URL line. Disabled query parameters are left out, so this
+    /// takes the query table to check which are enabled. If anything failed
+    /// to render, the first error is shown after the URL.
+    fn generate<'a>(
+        &'a self,
+        query: &'a SelectState<RowState, TableState>,
+    ) -> Line<'a> {
+        let theme = &TuiContext::get().theme;
+        let enabled = query
+            .items()
+            .iter()
+            .zip(&self.query)
+            .filter(|(row, _)| *row.enabled)
+            .collect_vec();
+
+        let mut spans = flatten(self.url.generate());
+        let mut separator = if self.url.template().as_str().contains('?') {
+            "&"
+        } else {
+            "?"
+        };
+        for (row, value) in &enabled {
+            spans.push(separator.into());
+            spans.push(row.key.as_str().into());
+            spans.push("=".into());
+            spans.extend(flatten(value.generate()));
+            separator = "&";
+        }
+
+        let error = self
+            .url
+            .error()
+            .or_else(|| enabled.iter().find_map(|(_, value)| value.error()));
+        if let Some(error) = error {
+            spans.push(Span::styled(
+                format!("  ({error})"),
+                theme.template_preview.error,
+            ));
+        }
+        spans.into()
+    }
+}
+
+/// Join a multi-line text into a single line
+fn flatten(text: Text<'_>) -> Vec<Span<'_>> {
+    text.lines.into_iter().flat_map(|line| line.spans).collect()
+}
+
 /// Display authentication settings. This is basically the underlying
 /// [Authentication] type, but the templates have been rendered
 #[derive(Debug)]
@@ -1,14 +1,17 @@
 //! Request/response body display component
 
 use crate::{
-    http::{Query, ResponseContent},
+    http::{decode_text, ContentType, Query, ResponseContent},
     tui::{
+        context::TuiContext,
         input::Action,
+        message::Message,
         view::{
             common::{text_box::TextBox, text_window::TextWindow},
+            component::json_table::JsonTable,
             draw::Draw,
             event::{Event, EventHandler, EventQueue, Update},
-            state::StateCell,
+            state::{Notification, StateCell},
             util::layout,
             Component,
         },
@@ -17,26 +20,29 @@ use crate::{
 };
 use anyhow::Context;
 use derive_more::Debug;
+use encoding_rs::Encoding;
 use ratatui::{
     layout::{Constraint, Direction},
     prelude::Rect,
     Frame,
 };
-use serde_json_path::JsonPath;
-use std::cell::Cell;
+use std::{cell::Cell, fmt::Write};
 
 /// Display text body of a request/response
 #[derive(Debug)]
 pub struct RecordBody {
     /// Body text content. State cell allows us to reset this whenever the
-    /// request changes
+    /// query changes, or the formatted body becomes available
     #[debug(skip)]
-    text_window: StateCell<Option<Query>, Component<TextWindow<String>>>,
+    text_window: StateCell<(Option<Query>, bool), BodyState>,
     /// Store whether the body can be queried. True only if it's a recognized
     /// and parsed format
     query_available: Cell<bool>,
     /// Expression used to filter the content of the body down
     query: Option<Query>,
+    /// Show the body as a table, if it's an array of objects. This sticks
+    /// across queries, but falls back to text for bodies that aren't tables
+    table_mode: bool,
     /// Where the user enters their body query
     #[debug(skip)]
     query_text_box: Component<TextBox>,
@@ -44,18 +50,105 @@ pub struct RecordBody {
 
 pub struct RecordBodyProps<'a> {
     pub raw_body: &'a [u8],
+    /// Character encoding of the raw body, if declared. Used to display
+    /// bodies that couldn't be parsed
+    pub encoding: Option<&'static Encoding>,
     pub parsed_body: Option<&'a dyn ResponseContent>,
+    /// Output of an external formatter, shown in place of the raw body if the
+    /// body couldn't be parsed
+    pub formatted_body: Option<&'a str>,
 }
 
 /// Callback event from the query text box when user hits Enter
 struct QuerySubmit(String);
 
+/// Rendered body, rebuilt whenever the query changes
+struct BodyState {
+    text_window: Component<TextWindow<String>>,
+    /// If the body is JSON, this tracks which value each line belongs to, so
+    /// we can find the value under the cursor
+    json: Option<Vec<JsonLine>>,
+    /// Table view of the body, if it's an array of objects
+    table: Option<Component<JsonTable>>,
+}
+
+/// The innermost JSON value on one line of prettified text
+#[derive(Debug, PartialEq)]
+struct JsonLine {
+    /// Path from the root to the value
+    path: Vec<PathSegment>,
+    /// Line and byte offset within that line where the value starts
+    start: (usize, usize),
+    /// Line where the value ends. For scalars and empty containers, this is
+    /// the start line
+    end: usize,
+}
+
+/// One step in the path from the root of a JSON value to a nested value
+#[derive(Clone, Debug, PartialEq)]
+enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
 impl RecordBody {
     /// Get visible body text
     pub fn text(&self) -> Option<String> {
         self.text_window
             .get()
-            .map(|text_window| text_window.inner().text().to_owned())
+            .map(|state| state.text_window.inner().text().to_owned())
+    }
+
+    /// Switch between the text and table views. Only bodies that are arrays
+    /// of objects have a table view
+    fn toggle_table(&mut self) {
+        if self.table_mode {
+            self.table_mode = false;
+        } else if self
+            .text_window
+            .get()
+            .is_some_and(|state| state.table.is_some())
+        {
+            self.table_mode = true;
+        } else {
+            EventQueue::push(Event::Notify(Notification::new(
+                "Table view is only available for lists of objects".into(),
+            )));
+        }
+    }
+
+    /// Is the table view visible?
+    fn showing_table(&self) -> bool {
+        self.table_mode
+            && self
+                .text_window
+                .get()
+                .is_some_and(|state| state.table.is_some())
+    }
+
+    /// Copy either the value under the cursor, or its JSONPath, to the
+    /// clipboard. No-op if the body isn't JSON
+    fn copy_at_cursor(&self, action: Action) {
+        let Some(state) = self.text_window.get() else {
+            return;
+        };
+        let Some(json) = &state.json else {
+            return;
+        };
+        let text_window = state.text_window.inner();
+        let Some(line) = text_window
+            .cursor()
+            .and_then(|cursor| json.get(cursor as usize))
+        else {
+            return;
+        };
+
+        let text = if action == Action::CopyPath {
+            json_path(&line.path)
+        } else {
+            json_value_text(text_window.text(), line)
+        };
+        TuiContext::send_message(Message::CopyText(text));
     }
 }
 
@@ -65,10 +158,11 @@ impl Default for RecordBody {
             text_window: Default::default(),
             query_available: Cell::new(false),
             query: Default::default(),
+            table_mode: false,
             query_text_box: TextBox::default()
                 .with_focus(false)
                 .with_placeholder("'/' to filter body with JSONPath")
-                .with_validator(|text| JsonPath::parse(text).is_ok())
+                .with_validator(|text| text.parse::<Query>().is_ok())
                 // Callback triggers an event, so we can modify our own state
                 .with_on_submit(|text_box| {
                     EventQueue::push(Event::other(QuerySubmit(
@@ -87,6 +181,14 @@ impl EventHandler for RecordBody {
                 action: Some(Action::Search),
                 ..
             } if self.query_available.get() => self.query_text_box.focus(),
+            Event::Input {
+                action: Some(action @ (Action::CopyValue | Action::CopyPath)),
+                ..
+            } if !self.showing_table() => self.copy_at_cursor(action),
+            Event::Input {
+                action: Some(Action::ToggleTable),
+                ..
+            } => self.toggle_table(),
             Event::Other(ref other) => {
                 match other.downcast_ref::<QuerySubmit>() {
                     Some(QuerySubmit(text)) => {
@@ -108,10 +210,14 @@ impl EventHandler for RecordBody {
     }
 
     fn children(&mut self) -> Vec<Component<&mut dyn EventHandler>> {
+        let table_mode = self.table_mode;
         if self.query_text_box.is_focused() {
             vec![self.query_text_box.as_child()]
-        } else if let Some(text_window) = self.text_window.get_mut() {
-            vec![text_window.as_child()]
+        } else if let Some(state) = self.text_window.get_mut() {
+            match &mut state.table {
+                Some(table) if table_mode => vec![table.as_child()],
+                _ => vec![state.text_window.as_child()],
+            }
         } else {
             vec![]
         }
@@ -134,14 +240,20 @@ impl<'a> Draw<RecordBodyProps<'a>> for RecordBody {
         );
 
         // Draw the body
-        let text = self.text_window.get_or_update(self.query.clone(), || {
-            init_text_window(
+        let key = (self.query.clone(), props.formatted_body.is_some());
+        let state = self.text_window.get_or_update(key, || {
+            init_state(
                 props.raw_body,
+                props.encoding,
                 props.parsed_body,
+                props.formatted_body,
                 self.query.as_ref(),
             )
         });
-        text.draw(frame, (), body_area);
+        match &state.table {
+            Some(table) if self.table_mode => table.draw(frame, (), body_area),
+            _ => state.text_window.draw(frame, (), body_area),
+        }
 
         if query_available {
             self.query_text_box.draw(frame, (), query_area);
@@ -149,25 +261,328 @@ impl<'a> Draw<RecordBodyProps<'a>> for RecordBody {
     }
 }
 
-fn init_text_window(
+fn init_state(
     raw_body: &[u8],
+    encoding: Option<&'static Encoding>,
     parsed_body: Option<&dyn ResponseContent>,
+    formatted_body: Option<&str>,
     query: Option<&Query>,
-) -> Component<TextWindow<String>> {
+) -> BodyState {
     // Query and prettify text if possible. This involves a lot of cloning
     // because it makes stuff easier. If it becomes a bottleneck on large
     // responses it's fixable.
-    let body = parsed_body
-        .map(|parsed_body| {
-            // Body is a known content type so we parsed it - apply a query if
-            // necessary and prettify the output
-            query
-                .map(|query| query.query(parsed_body).prettify())
-                .unwrap_or_else(|| parsed_body.prettify())
-        })
-        // Content couldn't be parsed, fall back to the raw text
-        // If the text isn't UTF-8, we'll show a placeholder instead
-        .unwrap_or_else(|| format!("{:#}", MaybeStr(raw_body)));
-
-    TextWindow::new(body).into()
+    if let Some(parsed_body) = parsed_body {
+        // Body is a known content type so we parsed it - apply a query if
+        // necessary and prettify the output
+        let queried = match query.map(|query| query.query(parsed_body)) {
+            Some(Ok(queried)) => Some(queried),
+            // A transform failed, so there's nothing to show but the error
+            Some(Err(error)) => {
+                return BodyState {
+                    text_window: TextWindow::new(error.to_string()).into(),
+                    json: None,
+                    table: None,
+                }
+            }
+            None => None,
+        };
+        let body = queried.as_deref().unwrap_or(parsed_body);
+        let text = body.prettify();
+        // Map lines from the text rather than the parsed value, because the
+        // text keeps the key order, duplicate keys, and exact numbers of the
+        // original body
+        let lines =
+            json_lines(&text, body.content_type() == ContentType::Ndjson);
+        let table = JsonTable::new(&body.to_json()).map(Component::from);
+        BodyState {
+            text_window: TextWindow::new(text).with_cursor().into(),
+            json: Some(lines),
+            table,
+        }
+    } else if let Some(formatted_body) = formatted_body {
+        // Content couldn't be parsed, but an external formatter handled it
+        BodyState {
+            text_window: TextWindow::new(formatted_body.to_owned()).into(),
+            json: None,
+            table: None,
+        }
+    } else {
+        // Content couldn't be parsed, fall back to the raw text. If it isn't
+        // valid in its declared encoding or UTF-8, show the bytes instead
+        let text = match decode_text(raw_body, encoding) {
+            Some(text) => text.into_owned(),
+            None => format!("{:#}", MaybeStr(raw_body)),
+        };
+        BodyState {
+            text_window: TextWindow::new(text).into(),
+            json: None,
+            table: None,
+        }
+    }
+}
+
+/// Find the innermost value on each line of prettified JSON text. The text
+/// must be in the layout of [serde_json::to_string_pretty]: each scalar,
+/// empty container, or object entry gets one line, and non-empty containers
+/// get an extra line for the closing bracket. For NDJSON, each line of the
+/// body is prettified on its own, and paths are relative to the array of all
+/// the values.
+fn json_lines(text: &str, ndjson: bool) -> Vec<JsonLine> {
+    /// A non-empty container that hasn't been closed yet
+    struct Open {
+        /// Index of the container's opening line
+        line: usize,
+        is_object: bool,
+        /// Index of the next array element
+        next_index: usize,
+    }
+
+    let mut lines: Vec<JsonLine> = Vec::new();
+    let mut open: Vec<Open> = Vec::new();
+    let mut roots = 0;
+    for (i, line) in text.lines().enumerate() {
+        let trimmed = line.trim_start();
+        let indent = line.len() - trimmed.len();
+        let content = trimmed.strip_suffix(',').unwrap_or(trimmed);
+
+        if matches!(content, "}" | "]") {
+            if let Some(container) = open.pop() {
+                // Closing bracket belongs to the container
+                let opening = &mut lines[container.line];
+                opening.end = i;
+                let line = JsonLine {
+                    path: opening.path.clone(),
+                    start: opening.start,
+                    end: i,
+                };
+                lines.push(line);
+                continue;
+            }
+        }
+
+        let (mut path, segment, column) = match open.last_mut() {
+            Some(parent) => {
+                let path = lines[parent.line].path.clone();
+                if parent.is_object {
+                    let key_len = string_len(trimmed);
+                    let key = &trimmed[..key_len];
+                    let key = serde_json::from_str(key)
+                        .unwrap_or_else(|_| key.to_owned());
+                    // Skip the `: ` after the key
+                    let column = (indent + key_len + 2).min(line.len());
+                    (path, Some(PathSegment::Key(key)), column)
+                } else {
+                    let index = parent.next_index;
+                    parent.next_index += 1;
+                    (path, Some(PathSegment::Index(index)), indent)
+                }
+            }
+            None => {
+                let segment = ndjson.then_some(PathSegment::Index(roots));
+                roots += 1;
+                (Vec::new(), segment, indent)
+            }
+        };
+        path.extend(segment);
+
+        let value = &line[column..];
+        if matches!(value, "{" | "[") {
+            open.push(Open {
+                line: i,
+                is_object: value == "{",
+                next_index: 0,
+            });
+        }
+        lines.push(JsonLine {
+            path,
+            start: (i, column),
+            end: i,
+        });
+    }
+    lines
+}
+
+/// Get the length of the JSON string literal at the start of some text,
+/// including quotes
+fn string_len(text: &str) -> usize {
+    let mut chars = text.char_indices().skip(1);
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '"' => return i + 1,
+            _ => {}
+        }
+    }
+    text.len()
+}
+
+/// Get the text of a JSON value from the prettified text it was found in.
+/// Nested lines are unindented to match the value's own depth. Strings are
+/// unquoted, since that's almost always what you want.
+fn json_value_text(text: &str, line: &JsonLine) -> String {
+    let (start, column) = line.start;
+    let lines: Vec<&str> = text
+        .lines()
+        .skip(start)
+        .take(line.end + 1 - start)
+        .collect();
+    let Some(first) = lines.first() else {
+        return String::new();
+    };
+    let indent = first.len() - first.trim_start().len();
+    let mut value = first[column..].to_owned();
+    for line in &lines[1..] {
+        value.push('\n');
+        value.push_str(line.get(indent..).unwrap_or(line.trim_start()));
+    }
+    // Drop the separator before the next value
+    if value.ends_with(',') {
+        value.pop();
+    }
+
+    if value.starts_with('"') {
+        serde_json::from_str(&value).unwrap_or(value)
+    } else {
+        value
+    }
+}
+
+/// Convert a path to a JSONPath expression. Keys use dot notation where
+/// possible, and bracket notation if they contain special characters
+fn json_path(path: &[PathSegment]) -> String {
+    let mut expression = "$".to_owned();
+    for segment in path {
+        // Writing to a string is infallible
+        let _ = match segment {
+            PathSegment::Key(key)
+                if !key.is_empty()
+                    && !key.starts_with(|c: char| c.is_ascii_digit())
+                    && key.chars().all(|c| c.is_alphanumeric() || c == '_') =>
+            {
+                write!(expression, ".{key}")
+            }
+            PathSegment::Key(key) => write!(
+                expression,
+                "['{}']",
+                key.replace('\\', "\\\\").replace('\'', "\\'")
+            ),
+            PathSegment::Index(index) => write!(expression, "[{index}]"),
+        };
+    }
+    expression
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::Json;
+    use rstest::rstest;
+    use serde_json::json;
+    use serde_json_path::JsonPath;
+
+    /// Each line of prettified JSON should map to the correct path
+    #[test]
+    fn test_json_lines() {
+        let value = json!({
+            "a": 1,
+            "b": [true, {}],
+            "c": {"d": null},
+            "e": [],
+        });
+        let key = |key: &str| PathSegment::Key(key.into());
+        let index = PathSegment::Index;
+        let expected = vec![
+            vec![],                   // {
+            vec![key("a")],           //   "a": 1,
+            vec![key("b")],           //   "b": [
+            vec![key("b"), index(0)], //     true,
+            vec![key("b"), index(1)], //     {}
+            vec![key("b")],           //   ],
+            vec![key("c")],           //   "c": {
+            vec![key("c"), key("d")], //     "d": null
+            vec![key("c")],           //   },
+            vec![key("e")],           //   "e": []
+            vec![],                   // }
+        ];
+        let text = serde_json::to_string_pretty(&value).unwrap();
+        let paths: Vec<_> = json_lines(&text, false)
+            .into_iter()
+            .map(|line| line.path)
+            .collect();
+        assert_eq!(paths, expected);
+    }
+
+    /// NDJSON lines are prefixed with the index of their value
+    #[test]
+    fn test_ndjson_lines() {
+        let text = "{\n  \"a\": 1\n}\n2";
+        let index = PathSegment::Index;
+        let expected = vec![
+            vec![index(0)],                               // {
+            vec![index(0), PathSegment::Key("a".into())], //   "a": 1
+            vec![index(0)],                               // }
+            vec![index(1)],                               // 2
+        ];
+        let paths: Vec<_> = json_lines(text, true)
+            .into_iter()
+            .map(|line| line.path)
+            .collect();
+        assert_eq!(paths, expected);
+    }
+
+    /// Lines are mapped from the text as displayed, so keys stay in their
+    /// original order, duplicate keys each get their own value, and numbers
+    /// are copied exactly
+    #[rstest]
+    #[case::unsorted_keys(r#"{"b":1,"a":2}"#, 1, "$.b", "1")]
+    #[case::unsorted_keys_second(r#"{"b":1,"a":2}"#, 2, "$.a", "2")]
+    #[case::duplicate_key(r#"{"a":1,"a":2}"#, 1, "$.a", "1")]
+    #[case::exact_number(
+        r#"{"a":9007199254740993}"#,
+        1,
+        "$.a",
+        "9007199254740993"
+    )]
+    #[case::string(r#"{"a":"x, \"y\",","b":1}"#, 1, "$.a", "x, \"y\",")]
+    #[case::escaped_key(r#"{"a\"b":1}"#, 1, "$['a\"b']", "1")]
+    #[case::nested(
+        r#"{"b":{"d":[1,2],"c":3},"a":0}"#,
+        1,
+        "$.b",
+        "{\n  \"d\": [\n    1,\n    2\n  ],\n  \"c\": 3\n}"
+    )]
+    #[case::closing_bracket(
+        r#"{"b":[1,2],"a":0}"#,
+        4,
+        "$.b",
+        "[\n  1,\n  2\n]"
+    )]
+    #[case::root(r#"[{"b":1}]"#, 0, "$", "[\n  {\n    \"b\": 1\n  }\n]")]
+    fn test_json_value_text(
+        #[case] body: &str,
+        #[case] line: usize,
+        #[case] expected_path: &str,
+        #[case] expected_value: &str,
+    ) {
+        let text = Json::parse(body.as_bytes()).unwrap().prettify();
+        let lines = json_lines(&text, false);
+        assert_eq!(lines.len(), text.lines().count());
+        let line = &lines[line];
+        assert_eq!(json_path(&line.path), expected_path);
+        assert_eq!(json_value_text(&text, line), expected_value);
+    }
+
+    #[rstest]
+    #[case::root(&[], "$")]
+    #[case::simple(&[PathSegment::Key("a".into()), PathSegment::Index(0)], "$.a[0]")]
+    #[case::special_chars(&[PathSegment::Key("a-b".into())], "$['a-b']")]
+    #[case::leading_digit(&[PathSegment::Key("1a".into())], "$['1a']")]
+    #[case::quote(&[PathSegment::Key("it's".into())], "$['it\\'s']")]
+    fn test_json_path(#[case] path: &[PathSegment], #[case] expected: &str) {
+        assert_eq!(json_path(path), expected);
+        // Make sure the expression is valid
+        JsonPath::parse(expected).unwrap();
+    }
 }
@@ -5,11 +5,11 @@ use crate::{
         input::Action,
         message::Message,
         view::{
-            common::{
-                actions::ActionsModal, header_table::HeaderTable, tabs::Tabs,
-                Pane,
+            common::{actions::ActionsModal, tabs::Tabs, Pane},
+            component::{
+                header_view::{HeaderView, HeaderViewProps},
+                record_body::{RecordBody, RecordBodyProps},
             },
-            component::record_body::{RecordBody, RecordBodyProps},
             draw::{Draw, Generate, ToStringGenerate},
             event::{Event, EventHandler, EventQueue, Update},
             state::{persistence::PersistentKey, RequestState, StateCell},
@@ -47,6 +47,14 @@ enum MenuAction {
     CopyUrl,
     #[display("Copy Body")]
     CopyBody,
+    #[display("Copy Header")]
+    CopyHeader,
+    #[display("Sort/Unsort Headers")]
+    SortHeaders,
+    #[display("Replay Request (Exact)")]
+    Replay,
+    #[display("Replay Request (Edit Body)")]
+    ReplayEditBody,
 }
 
 impl ToStringGenerate for MenuAction {}
@@ -59,10 +67,17 @@ impl EventHandler for RequestPane {
 
 impl<'a> Draw<RequestPaneProps<'a>> for RequestPane {
     fn draw(&self, frame: &mut Frame, props: RequestPaneProps<'a>, area: Rect) {
-        // Render outermost block
-        let title = TuiContext::get()
+        // Render outermost block. Include the request's label, if it has one
+        let mut title = TuiContext::get()
             .input_engine
             .add_hint("Request", Action::SelectRequest);
+        if let Some(label) = props
+            .active_request
+            .and_then(RequestState::request)
+            .and_then(|request| request.label.as_deref())
+        {
+            title = format!("{title} - {label}");
+        }
         let block = Pane {
             title: &title,
             is_focused: props.is_selected,
@@ -146,6 +161,7 @@ struct State {
     /// Persist the request body to track view state. Update whenever the
     /// loaded request changes
     body: Component<RecordBody>,
+    headers: Component<HeaderView>,
 }
 
 impl Default for RenderedRequest {
@@ -206,6 +222,32 @@ impl EventHandler for RenderedRequest {
                             TuiContext::send_message(Message::CopyText(body));
                         }
                     }
+                    Some(MenuAction::CopyHeader) => {
+                        if let Some(line) = self
+                            .state
+                            .get()
+                            .and_then(|state| state.headers.selected_line())
+                        {
+                            TuiContext::send_message(Message::CopyText(line));
+                        }
+                    }
+                    Some(MenuAction::SortHeaders) => {
+                        if let Some(state) = self.state.get_mut() {
+                            state.headers.toggle_sort();
+                        }
+                    }
+                    Some(
+                        action @ (MenuAction::Replay
+                        | MenuAction::ReplayEditBody),
+                    ) => {
+                        if let Some(state) = self.state.get() {
+                            TuiContext::send_message(Message::HttpReplay {
+                                request: state.request.duplicate(),
+                                edit_body: *action
+                                    == MenuAction::ReplayEditBody,
+                            })
+                        }
+                    }
                     None => return Update::Propagate(event),
                 }
             }
@@ -218,12 +260,17 @@ impl EventHandler for RenderedRequest {
         let selected_tab = *self.tabs.selected();
         let mut children = vec![];
         match selected_tab {
-            Tab::Url | Tab::Headers => {}
+            Tab::Url => {}
             Tab::Body => {
                 if let Some(state) = self.state.get_mut() {
                     children.push(state.body.as_child());
                 }
             }
+            Tab::Headers => {
+                if let Some(state) = self.state.get_mut() {
+                    children.push(state.headers.as_child());
+                }
+            }
         }
         // Tabs goes last, because pane content gets priority
         children.push(self.tabs.as_child());
@@ -241,6 +288,7 @@ impl<'a> Draw<RenderedRequestProps<'a>> for RenderedRequest {
         let state = self.state.get_or_update(props.request.id, || State {
             request: Arc::clone(&props.request),
             body: Default::default(),
+            headers: Default::default(),
         });
 
         // Split the main area again to allow tabs
@@ -268,17 +316,20 @@ impl<'a> Draw<RenderedRequestProps<'a>> for RenderedRequest {
                         frame,
                         RecordBodyProps {
                             raw_body: body,
+                            encoding: None,
                             parsed_body: props.parsed_body,
+                            formatted_body: None,
                         },
                         content_area,
                     );
                 }
             }
-            Tab::Headers => frame.render_widget(
-                HeaderTable {
+            Tab::Headers => state.headers.draw(
+                frame,
+                HeaderViewProps {
                     headers: &props.request.headers,
-                }
-                .generate(),
+                    label: "Header",
+                },
                 content_area,
             ),
         }
@@ -1,32 +1,48 @@
 use crate::{
-    http::{RequestId, RequestRecord, ResponseContent},
+    http::{
+        decode_text, Body, ContentType, Request, RequestId, RequestProgress,
+        RequestRecord, ResponseContent, Transfer,
+    },
     tui::{
         context::TuiContext,
         input::Action,
         message::Message,
         view::{
             common::{
-                actions::ActionsModal, header_table::HeaderTable, tabs::Tabs,
-                Pane,
+                actions::ActionsModal, header_table::HeaderTable,
+                modal::ModalPriority, tabs::Tabs, Pane,
+            },
+            component::{
+                header_view::{HeaderView, HeaderViewProps},
+                query_tester::QueryTesterModal,
+                record_body::{RecordBody, RecordBodyProps},
             },
-            component::record_body::{RecordBody, RecordBodyProps},
             draw::{Draw, Generate, ToStringGenerate},
             event::{Event, EventHandler, EventQueue, Update},
-            state::{persistence::PersistentKey, RequestState, StateCell},
+            state::{
+                persistence::PersistentKey, Notification, PollAttempt,
+                RequestState, StateCell,
+            },
+            theme::Theme,
             util::layout,
             Component,
         },
     },
 };
-use chrono::Utc;
+use bytesize::ByteSize;
+use chrono::{Duration, Utc};
 use derive_more::{Debug, Display};
+use encoding_rs::Encoding;
+use itertools::Itertools;
 use ratatui::{
     prelude::{Alignment, Constraint, Direction, Rect},
-    text::Line,
-    widgets::{Paragraph, Wrap},
+    style::Style,
+    text::{Line, Span},
+    widgets::{LineGauge, Paragraph, Wrap},
     Frame,
 };
 use serde::{Deserialize, Serialize};
+use std::{cell::RefCell, sync::Arc};
 use strum::{EnumCount, EnumIter};
 
 /// Display HTTP response state, which could be in progress, complete, or
@@ -34,6 +50,9 @@ use strum::{EnumCount, EnumIter};
 #[derive(Debug, Default)]
 pub struct ResponsePane {
     content: Component<CompleteResponseContent>,
+    /// Progress of the request that's loading, if any. Updated on each draw,
+    /// so the response body can be stopped early
+    loading: RefCell<Option<Arc<RequestProgress>>>,
 }
 
 pub struct ResponsePaneProps<'a> {
@@ -46,11 +65,54 @@ pub struct ResponsePaneProps<'a> {
 enum MenuAction {
     #[display("Copy Body")]
     CopyBody,
+    #[display("Copy Header")]
+    CopyHeader,
+    #[display("Sort/Unsort Headers")]
+    SortHeaders,
+    #[display("Fetch Next Page")]
+    FetchNextPage,
+    #[display("Test JSONPath")]
+    TestQuery,
+    #[display("Delete Request")]
+    DeleteRequest,
+    #[display("Delete All Requests for Recipe")]
+    DeleteAllRequests,
 }
 
 impl ToStringGenerate for MenuAction {}
 
+impl ResponsePane {
+    /// Progress of the loading response, if its body has started loading and
+    /// can be stopped
+    fn stoppable(&self) -> Option<Arc<RequestProgress>> {
+        self.loading
+            .borrow()
+            .as_ref()
+            .filter(|progress| progress.download().is_some())
+            .cloned()
+    }
+
+    /// Switch to the headers tab, e.g. when the body won't be loaded
+    pub fn select_headers_tab(&mut self) {
+        self.content.tabs.select(&Tab::Headers);
+    }
+}
+
 impl EventHandler for ResponsePane {
+    fn update(&mut self, event: Event) -> Update {
+        match event {
+            Event::Input {
+                action: Some(Action::Cancel),
+                ..
+            } => match self.stoppable() {
+                Some(progress) => progress.stop(),
+                None => return Update::Propagate(event),
+            },
+            _ => return Update::Propagate(event),
+        }
+        Update::Consumed
+    }
+
     fn children(&mut self) -> Vec<Component<&mut dyn EventHandler>> {
         vec![self.content.as_child()]
     }
@@ -75,29 +137,101 @@ impl<'a> Draw<ResponsePaneProps<'a>> for ResponsePane {
         frame.render_widget(&block, area);
         let area = block.inner(area);
 
+        *self.loading.borrow_mut() = match props.active_request {
+            Some(RequestState::Loading { progress, .. }) => {
+                Some(Arc::clone(progress))
+            }
+            _ => None,
+        };
         match props.active_request {
             None | Some(RequestState::BuildError { .. }) => {}
             Some(RequestState::Building { .. }) => {
                 frame.render_widget(Paragraph::new("Loading..."), area)
             }
-            Some(RequestState::Loading { start_time, .. }) => {
-                frame.render_widget(Paragraph::new("Loading..."), area);
+            Some(RequestState::Loading {
+                start_time,
+                poll_attempt,
+                queued_until,
+                progress,
+                ..
+            }) => {
+                let text = match poll_attempt {
+                    _ if queued_until
+                        .is_some_and(|until| until > Utc::now()) =>
+                    {
+                        "Waiting for rate limit...".into()
+                    }
+                    Some(PollAttempt {
+                        attempt,
+                        max_attempts: None,
+                    }) => format!("Streaming (response {attempt})..."),
+                    Some(poll_attempt) => {
+                        format!("Polling (attempt {poll_attempt})...")
+                    }
+                    None => "Loading...".into(),
+                };
+                let text = if progress.download().is_some() {
+                    TuiContext::get()
+                        .input_engine
+                        .add_hint(text, Action::Cancel)
+                } else {
+                    text
+                };
+                let [text_area, upload_area, download_area, body_area] = layout(
+                    area,
+                    Direction::Vertical,
+                    [
+                        Constraint::Length(1),
+                        Constraint::Length(1),
+                        Constraint::Length(1),
+                        Constraint::Min(0),
+                    ],
+                );
+                frame.render_widget(Paragraph::new(text), text_area);
                 let duration = Utc::now() - start_time;
                 frame.render_widget(
-                    Paragraph::new(duration.generate())
+                    Paragraph::new(styled_duration(duration))
                         .alignment(Alignment::Right),
-                    area,
+                    text_area,
                 );
+
+                // Show transfer progress, if there's anything to show
+                if let Some(upload) = progress.upload() {
+                    frame.render_widget(
+                        transfer_gauge("Upload", upload),
+                        upload_area,
+                    );
+                }
+                if let Some(download) = progress.download() {
+                    frame.render_widget(
+                        transfer_gauge("Download", download),
+                        download_area,
+                    );
+                }
+
+                // Streamed bodies are shown as they arrive, newest at the
+                // bottom
+                if let Some(body) = progress.partial_body() {
+                    let lines = partial_ndjson(&body, body_area.height.into());
+                    frame.render_widget(
+                        Paragraph::new(lines.join("\n")),
+                        body_area,
+                    );
+                }
             }
 
             Some(RequestState::Response {
                 record,
                 parsed_body,
+                formatted_body,
             }) => self.content.draw(
                 frame,
                 CompleteResponseContentProps {
                     record,
                     parsed_body: parsed_body.as_deref(),
+                    formatted_body: formatted_body
+                        .as_ref()
+                        .and_then(|lock| lock.get()),
                 },
                 area,
             ),
@@ -120,6 +254,22 @@ struct CompleteResponseContent {
     /// loaded request changes
     #[debug(skip)]
     body: StateCell<RequestId, Component<RecordBody>>,
+    /// Same as the body, but for headers
+    #[debug(skip)]
+    headers: StateCell<RequestId, Component<HeaderView>>,
+    /// Request for the next page of results, if the response links to one.
+    /// This is taken when the page is fetched, so each page can only be
+    /// requested once.
+    next_page: StateCell<RequestId, Option<Request>>,
+    /// Number of GraphQL errors in the response body. Only calculated if
+    /// enabled in the config
+    graphql_error_count: StateCell<RequestId, usize>,
+    /// Body, content type, and charset of the response, to open in the
+    /// JSONPath tester
+    query_tester_body:
+        StateCell<RequestId, (ContentType, Body, Option<&'static Encoding>)>,
+    /// The request that got this response, so it can be deleted from history
+    request: StateCell<RequestId, Arc<Request>>,
 }
 
 impl Default for CompleteResponseContent {
@@ -127,6 +277,11 @@ impl Default for CompleteResponseContent {
         Self {
             tabs: Tabs::new(PersistentKey::ResponseTab).into(),
             body: Default::default(),
+            headers: Default::default(),
+            next_page: Default::default(),
+            graphql_error_count: Default::default(),
+            query_tester_body: Default::default(),
+            request: Default::default(),
         }
     }
 }
@@ -134,6 +289,9 @@ impl Default for CompleteResponseContent {
 struct CompleteResponseContentProps<'a> {
     record: &'a RequestRecord,
     parsed_body: Option<&'a dyn ResponseContent>,
+    /// Output of the configured formatter for the body. `None` if there's no
+    /// formatter, or it hasn't finished yet
+    formatted_body: Option<&'a anyhow::Result<String>>,
 }
 
 #[derive(
@@ -152,6 +310,20 @@ enum Tab {
     Headers,
 }
 
+impl CompleteResponseContent {
+    /// Delete the current request from history, or every request for its
+    /// profile+recipe. The user is asked to confirm first.
+    fn delete_request(&self, all: bool) {
+        if let Some(request) = self.request.get() {
+            TuiContext::send_message(Message::RequestDelete {
+                profile_id: request.profile_id.clone(),
+                recipe_id: request.recipe_id.clone(),
+                request_id: (!all).then_some(request.id),
+            });
+        }
+    }
+}
+
 impl EventHandler for CompleteResponseContent {
     fn update(&mut self, event: Event) -> Update {
         match event {
@@ -159,6 +331,10 @@ impl EventHandler for CompleteResponseContent {
                 action: Some(Action::OpenActions),
                 ..
             } => EventQueue::open_modal_default::<ActionsModal<MenuAction>>(),
+            Event::Input {
+                action: Some(Action::DeleteRequest),
+                ..
+            } => self.delete_request(false),
             Event::Other(ref other) => {
                 // Check for an action menu event
                 match other.downcast_ref::<MenuAction>() {
@@ -171,6 +347,52 @@ impl EventHandler for CompleteResponseContent {
                             TuiContext::send_message(Message::CopyText(body));
                         }
                     }
+                    Some(MenuAction::CopyHeader) => {
+                        if let Some(line) = self
+                            .headers
+                            .get()
+                            .and_then(|headers| headers.selected_line())
+                        {
+                            TuiContext::send_message(Message::CopyText(line));
+                        }
+                    }
+                    Some(MenuAction::SortHeaders) => {
+                        if let Some(headers) = self.headers.get_mut() {
+                            headers.toggle_sort();
+                        }
+                    }
+                    Some(MenuAction::FetchNextPage) => {
+                        match self.next_page.get_mut().and_then(Option::take) {
+                            Some(request) => TuiContext::send_message(
+                                Message::HttpNextPage(request),
+                            ),
+                            None => EventQueue::push(Event::Notify(
+                                Notification::new("No next page".into()),
+                            )),
+                        }
+                    }
+                    Some(MenuAction::TestQuery) => {
+                        if let Some(state) = self.query_tester_body.get() {
+                            let (content_type, body, encoding) = &*state;
+                            let body = decode_text(body.bytes(), *encoding)
+                                .unwrap_or_else(|| {
+                                    String::from_utf8_lossy(body.bytes())
+                                });
+                            EventQueue::open_modal(
+                                QueryTesterModal::new(
+                                    *content_type,
+                                    body.into_owned(),
+                                ),
+                                ModalPriority::Low,
+                            );
+                        }
+                    }
+                    Some(MenuAction::DeleteRequest) => {
+                        self.delete_request(false)
+                    }
+                    Some(MenuAction::DeleteAllRequests) => {
+                        self.delete_request(true)
+                    }
                     None => return Update::Propagate(event),
                 }
             }
@@ -188,7 +410,11 @@ impl EventHandler for CompleteResponseContent {
                     children.push(body.as_child());
                 }
             }
-            Tab::Headers => {}
+            Tab::Headers => {
+                if let Some(headers) = self.headers.get_mut() {
+                    children.push(headers.as_child());
+                }
+            }
         }
         // Tabs goes last, because pane content gets priority
         children.push(self.tabs.as_child());
@@ -216,46 +442,214 @@ impl<'a> Draw<CompleteResponseContentProps<'a>> for CompleteResponseContent {
             ],
         );
 
-        // Metadata
-        frame.render_widget(
-            Paragraph::new(response.status.to_string()),
-            header_area,
-        );
+        // Metadata. GraphQL errors are reported in the body rather than the
+        // status, so if enabled, show them alongside the status
+        let graphql_error_count =
+            if TuiContext::get().config.highlight_graphql_errors {
+                *self.graphql_error_count.get_or_update(props.record.id, || {
+                    response.graphql_error_count()
+                })
+            } else {
+                0
+            };
+        let mut status = if graphql_error_count > 0 {
+            Line::styled(
+                format!(
+                    "{} ({graphql_error_count} GraphQL error{})",
+                    response.status,
+                    if graphql_error_count == 1 { "" } else { "s" }
+                ),
+                TuiContext::get().theme.text.error,
+            )
+        } else {
+            Line::styled(
+                response.status.to_string(),
+                TuiContext::get()
+                    .theme
+                    .response
+                    .status_style(response.status),
+            )
+        };
+        // Make it obvious when the response didn't come from the server
+        if response.replayed {
+            status.spans.push(Span::styled(
+                " (replayed)",
+                TuiContext::get().theme.text.highlight,
+            ));
+        }
+        if response.cached {
+            status.spans.push(Span::styled(
+                " (cached body)",
+                TuiContext::get().theme.text.highlight,
+            ));
+        }
+        frame.render_widget(Paragraph::new(status), header_area);
+        let mut metadata = Vec::new();
+        if let Some(tls_version) = response.tls_version {
+            metadata.extend([tls_version.to_string().into(), " / ".into()]);
+        }
+        metadata.extend([
+            props.record.response.body.size().to_string_as(false).into(),
+            " / ".into(),
+            styled_duration(props.record.duration()),
+        ]);
         frame.render_widget(
-            Paragraph::new(Line::from(vec![
-                props.record.response.body.size().to_string_as(false).into(),
-                " / ".into(),
-                props.record.duration().generate(),
-            ]))
-            .alignment(Alignment::Right),
+            Paragraph::new(Line::from(metadata)).alignment(Alignment::Right),
             header_area,
         );
 
         // Navigation tabs
         self.tabs.draw(frame, (), tabs_area);
 
+        self.request.get_or_update(props.record.id, || {
+            Arc::clone(&props.record.request)
+        });
+        // Look for a next page link whenever the record changes
+        self.next_page
+            .get_or_update(props.record.id, || props.record.next_page());
+        // Bodies that weren't parsed can still be tested, e.g. to diagnose a
+        // missing content type. Cloning the body is cheap.
+        self.query_tester_body.get_or_update(props.record.id, || {
+            let content_type = props
+                .parsed_body
+                .map(|body| body.content_type())
+                .unwrap_or(ContentType::Json);
+            (content_type, response.body.clone(), response.charset())
+        });
+
         // Main content for the response
         match self.tabs.selected() {
+            Tab::Body if response.headers_only => frame.render_widget(
+                Paragraph::new("Body not loaded (headers only)"),
+                content_area,
+            ),
             Tab::Body => {
+                let theme = &TuiContext::get().theme;
+                // If the recipe expected a different content type than we got,
+                // or the formatter failed, mention it above the body
+                let mut banners: Vec<Line> = Vec::new();
+                if let Some(message) = props.record.content_type_mismatch() {
+                    banners.push(Line::styled(message, theme.text.highlight));
+                }
+                let formatted_body = match props.formatted_body {
+                    Some(Ok(text)) => Some(text.as_str()),
+                    Some(Err(error)) => {
+                        banners.push(Line::styled(
+                            format!("Error formatting body: {error:#}"),
+                            theme.text.error,
+                        ));
+                        None
+                    }
+                    None => None,
+                };
+                let [banner_area, content_area] = layout(
+                    content_area,
+                    Direction::Vertical,
+                    [
+                        Constraint::Length(banners.len() as u16),
+                        Constraint::Min(0),
+                    ],
+                );
+                frame.render_widget(Paragraph::new(banners), banner_area);
+
                 let body =
                     self.body.get_or_update(props.record.id, Default::default);
                 body.draw(
                     frame,
                     RecordBodyProps {
                         raw_body: response.body.bytes(),
+                        encoding: response.charset(),
                         parsed_body: props.parsed_body,
+                        formatted_body,
                     },
                     content_area,
                 );
             }
 
-            Tab::Headers => frame.render_widget(
-                HeaderTable {
-                    headers: &response.headers,
+            Tab::Headers => {
+                // Trailers go below headers, with a blank line between
+                let trailers_height = if response.trailers.is_empty() {
+                    0
+                } else {
+                    response.trailers.len() as u16 + 2
+                };
+                let [headers_area, trailers_area] = layout(
+                    content_area,
+                    Direction::Vertical,
+                    [Constraint::Min(0), Constraint::Length(trailers_height)],
+                );
+                let headers = self
+                    .headers
+                    .get_or_update(props.record.id, Default::default);
+                headers.draw(
+                    frame,
+                    HeaderViewProps {
+                        headers: &response.headers,
+                        label: "Header",
+                    },
+                    headers_area,
+                );
+                if !response.trailers.is_empty() {
+                    frame.render_widget(
+                        HeaderTable {
+                            headers: &response.trailers,
+                            label: "Trailer",
+                        }
+                        .generate(),
+                        trailers_area,
+                    );
                 }
-                .generate(),
-                content_area,
-            ),
+            }
         }
     }
 }
+
+/// Generate a progress bar for an upload/download. If the total size isn't
+/// known, just show the number of bytes transferred
+fn transfer_gauge(label: &str, transfer: Transfer) -> LineGauge<'static> {
+    let done = ByteSize(transfer.done).to_string_as(false);
+    let (ratio, label) = match (transfer.ratio(), transfer.total) {
+        (Some(ratio), Some(total)) => (
+            ratio,
+            format!("{label} {done} / {}", ByteSize(total).to_string_as(false)),
+        ),
+        _ => (0.0, format!("{label} {done}")),
+    };
+    LineGauge::default()
+        .ratio(ratio)
+        .label(label)
+        .gauge_style(Style::default().fg(Theme::PRIMARY_COLOR))
+}
+
+/// Get the last `count` complete lines of a partially loaded NDJSON body, each
+/// as compact JSON. Lines that aren't valid JSON are shown as-is.
+fn partial_ndjson(body: &[u8], count: usize) -> Vec<String> {
+    // Anything after the last line break is still loading
+    let complete = match body.iter().rposition(|&byte| byte == b'\n') {
+        Some(end) => &body[..end],
+        None => return Vec::new(),
+    };
+    let lines = complete
+        .split(|&byte| byte == b'\n')
+        .filter(|line| !line.iter().all(u8::is_ascii_whitespace))
+        .collect_vec();
+    lines[lines.len().saturating_sub(count)..]
+        .iter()
+        .map(|line| {
+            serde_json::from_slice::<serde_json::Value>(line)
+                .map(|value| value.to_string())
+                .unwrap_or_else(|_| String::from_utf8_lossy(line).into_owned())
+        })
+        .collect()
+}
+
+/// Format a request duration, colored according to the latency thresholds
+fn styled_duration(duration: Duration) -> Span<'static> {
+    let context = TuiContext::get();
+    duration.generate().style(
+        context
+            .theme
+            .response
+            .duration_style(duration, &context.config.latency_thresholds),
+    )
+}
@@ -1,26 +1,30 @@
 use crate::{
     collection::{Collection, ProfileId, RecipeId},
+    http::RequestId,
     tui::{
         context::TuiContext,
         input::Action,
         message::Message,
         view::{
-            common::{actions::GlobalAction, modal::ModalQueue},
+            common::{actions::GlobalAction, modal::ModalQueue, ProfileBadge},
             component::{
                 help::HelpFooter,
-                misc::NotificationText,
+                misc::{CollectionErrorView, NotificationText},
                 primary::{PrimaryView, PrimaryViewProps},
             },
-            draw::Draw,
+            draw::{Draw, Generate},
             event::{Event, EventHandler, Update},
             state::RequestState,
+            theme::Theme,
             util::layout,
             Component,
         },
     },
 };
 use ratatui::{
+    layout::Alignment,
     prelude::{Constraint, Direction, Rect},
+    widgets::Paragraph,
     Frame,
 };
 use std::collections::{hash_map::Entry, HashMap};
@@ -48,6 +52,9 @@ pub struct Root {
     modal_queue: Component<ModalQueue>,
     #[debug(skip)]
     notification_text: Option<Component<NotificationText>>,
+    /// Error from loading the collection, shown in place of the primary view
+    #[debug(skip)]
+    collection_error: Option<Component<CollectionErrorView>>,
 }
 
 impl Root {
@@ -60,9 +67,19 @@ impl Root {
             primary_view: PrimaryView::new(collection).into(),
             modal_queue: Component::default(),
             notification_text: None,
+            collection_error: None,
         }
     }
 
+    /// Select a profile and/or recipe in the primary view
+    pub fn select(
+        &mut self,
+        profile_id: Option<&ProfileId>,
+        recipe_id: Option<&RecipeId>,
+    ) {
+        self.primary_view.select(profile_id, recipe_id);
+    }
+
     /// Get the request state to be displayed
     fn active_request(&self) -> Option<&RequestState> {
         // "No Profile" _is_ a profile
@@ -100,6 +117,30 @@ impl Root {
             }
         }
     }
+
+    /// Drop the request state for a profile+recipe, if it holds a request that
+    /// was deleted from history. With no request ID, any completed request is
+    /// dropped. Requests that are still in flight weren't in history, so
+    /// they're kept either way.
+    fn clear_request(
+        &mut self,
+        profile_id: Option<ProfileId>,
+        recipe_id: RecipeId,
+        request_id: Option<RequestId>,
+    ) {
+        if let Entry::Occupied(entry) =
+            self.active_requests.entry((profile_id, recipe_id))
+        {
+            let state = entry.get();
+            let deleted = match request_id {
+                Some(request_id) => state.id() == request_id,
+                None => matches!(state, RequestState::Response { .. }),
+            };
+            if deleted {
+                entry.remove();
+            }
+        }
+    }
 }
 
 impl EventHandler for Root {
@@ -111,6 +152,16 @@ impl EventHandler for Root {
                 recipe_id,
                 state,
             } => self.update_request(profile_id, recipe_id, state),
+            Event::HttpClearState {
+                profile_id,
+                recipe_id,
+                request_id,
+            } => self.clear_request(profile_id, recipe_id, request_id),
+
+            Event::CollectionError(error) => {
+                self.collection_error =
+                    Some(CollectionErrorView::new(error).into())
+            }
 
             Event::Notify(notification) => {
                 self.notification_text =
@@ -128,6 +179,9 @@ impl EventHandler for Root {
                 Action::ReloadCollection => {
                     TuiContext::send_message(Message::CollectionStartReload)
                 }
+                Action::Cancel if self.collection_error.is_some() => {
+                    self.collection_error = None;
+                }
                 _ => return Update::Propagate(event),
             },
 
@@ -157,8 +211,9 @@ impl EventHandler for Root {
 
         // If a modal is open, don't allow *any* input to the background. We'll
         // still accept input ourselves though, which should only be
-        // high-priority stuff
-        if !modal_open {
+        // high-priority stuff. The primary view is hidden while there's a
+        // collection error, so it gets no input either
+        if !modal_open && self.collection_error.is_none() {
             children.push(self.primary_view.as_child());
         }
 
@@ -175,14 +230,42 @@ impl Draw for Root {
             [Constraint::Min(0), Constraint::Length(1)],
         );
 
-        // Main content
-        self.primary_view.draw(
-            frame,
-            PrimaryViewProps {
-                active_request: self.active_request(),
-            },
+        // Flagged profiles get a banner across the top, so it's hard to miss
+        // which environment requests are going to
+        let flagged_profile = self
+            .primary_view
+            .selected_profile()
+            .filter(|profile| profile.is_flagged())
+            .filter(|_| self.collection_error.is_none());
+        let [header_area, main_area] = layout(
             main_area,
+            Direction::Vertical,
+            [
+                Constraint::Length(flagged_profile.is_some() as u16),
+                Constraint::Min(0),
+            ],
         );
+        if let Some(profile) = flagged_profile {
+            frame.render_widget(
+                Paragraph::new(ProfileBadge { profile }.generate())
+                    .style(Theme::profile_badge(profile.color))
+                    .alignment(Alignment::Center),
+                header_area,
+            );
+        }
+
+        // Main content
+        if let Some(collection_error) = &self.collection_error {
+            collection_error.draw(frame, (), main_area);
+        } else {
+            self.primary_view.draw(
+                frame,
+                PrimaryViewProps {
+                    active_request: self.active_request(),
+                },
+                main_area,
+            );
+        }
 
         // Footer
         let [notification_area, help_area] = layout(
@@ -0,0 +1,83 @@
+//! Live feed of responses for a streamed recipe
+
+use crate::tui::view::{
+    common::{modal::Modal, text_window::TextWindow},
+    draw::Draw,
+    event::EventHandler,
+    state::{StateCell, StreamFeed},
+    Component,
+};
+use derive_more::Debug;
+use ratatui::{
+    layout::{Constraint, Rect},
+    widgets::Paragraph,
+    Frame,
+};
+use std::sync::Arc;
+use tokio::sync::oneshot;
+
+/// A modal showing every response received while streaming a recipe, newest
+/// first. The recipe is re-sent until this modal is closed.
+#[derive(Debug)]
+pub struct StreamModal {
+    title: String,
+    feed: Arc<StreamFeed>,
+    /// Dropping this tells the request loop to stop, so there's nothing else
+    /// to do when the modal is closed
+    #[debug(skip)]
+    _stop: oneshot::Sender<()>,
+    /// Rebuilt whenever a new entry is added, keyed by the entry count
+    #[debug(skip)]
+    text_window: StateCell<usize, Component<TextWindow<String>>>,
+}
+
+impl StreamModal {
+    pub fn new(
+        recipe_name: &str,
+        feed: Arc<StreamFeed>,
+        stop: oneshot::Sender<()>,
+    ) -> Self {
+        Self {
+            title: format!("Streaming {recipe_name}"),
+            feed,
+            _stop: stop,
+            text_window: Default::default(),
+        }
+    }
+}
+
+impl Modal for StreamModal {
+    fn title(&self) -> &str {
+        &self.title
+    }
+
+    fn dimensions(&self) -> (Constraint, Constraint) {
+        (Constraint::Percentage(80), Constraint::Percentage(80))
+    }
+}
+
+impl EventHandler for StreamModal {
+    fn children(&mut self) -> Vec<Component<&mut dyn EventHandler>> {
+        self.text_window
+            .get_mut()
+            .map(|text_window| text_window.as_child())
+            .into_iter()
+            .collect()
+    }
+}
+
+impl Draw for StreamModal {
+    fn draw(&self, frame: &mut Frame, _: (), area: Rect) {
+        let count = self.feed.entry_count();
+        if count == 0 {
+            frame.render_widget(
+                Paragraph::new("Waiting for first response..."),
+                area,
+            );
+            return;
+        }
+        self.text_window
+            .get_or_update(count, || TextWindow::new(self.feed.text()).into())
+            .draw(frame, (), area);
+    }
+}
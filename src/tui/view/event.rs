@@ -3,6 +3,8 @@
 
 use crate::{
     collection::{ProfileId, RecipeId},
+    http::RequestId,
+    template::Template,
     tui::{
         input::Action,
         view::{
@@ -109,7 +111,14 @@ pub enum Event {
     HttpLoadRequest,
     /// User wants to send a new request. Used to communicate from the recipe
     /// list to the parent, where more context is available.
-    HttpSendRequest,
+    HttpSendRequest {
+        /// Discard the response body, and only load status and headers
+        headers_only: bool,
+        /// Let the user edit the built request before it's sent
+        review: bool,
+        /// Re-send the request after each response, until stopped
+        stream: bool,
+    },
     /// Update our state based on external HTTP events
     HttpSetState {
         profile_id: Option<ProfileId>,
@@ -117,6 +126,32 @@ pub enum Event {
         #[debug(skip)]
         state: RequestState,
     },
+    /// Forget a request that was deleted from history, so an older one can
+    /// take its place. If `request_id` is `None`, every request for the
+    /// profile+recipe was deleted.
+    HttpClearState {
+        profile_id: Option<ProfileId>,
+        recipe_id: RecipeId,
+        request_id: Option<RequestId>,
+    },
+
+    /// Replace the body of a recipe for the rest of the session, e.g. after
+    /// the user edited it externally. Used to communicate from the controller
+    /// to the recipe pane.
+    OverrideRecipeBody {
+        recipe_id: RecipeId,
+        #[debug(skip)]
+        body: Template,
+    },
+
+    /// Replace the body in the JSONPath tester, after the user edited it
+    /// externally. Used to communicate from the controller to the tester modal.
+    OverrideQueryTesterBody(#[debug(skip)] String),
+
+    /// The collection failed to load. Show the error in place of the primary
+    /// view, until the collection is successfully reloaded or the user
+    /// dismisses it
+    CollectionError(anyhow::Error),
 
     /// Show a modal to the user
     OpenModal {
@@ -4,15 +4,20 @@ pub mod fixed_select;
 pub mod persistence;
 pub mod select;
 
-use crate::http::{
-    Request, RequestBuildError, RequestError, RequestId, RequestRecord,
-    ResponseContent,
+use crate::{
+    http::{
+        Request, RequestBuildError, RequestError, RequestId, RequestProgress,
+        RequestRecord, ResponseContent,
+    },
+    tui::{context::TuiContext, message::Message},
 };
-use chrono::{DateTime, Duration, Utc};
+use chrono::{DateTime, Duration, Local, Utc};
 use derive_more::Deref;
+use itertools::Itertools;
 use std::{
     cell::{Ref, RefCell},
-    sync::Arc,
+    fmt::{self, Display},
+    sync::{Arc, Mutex, OnceLock},
 };
 
 /// An internally mutable cell for UI state. Certain state needs to be updated
@@ -111,6 +116,12 @@ pub enum RequestState {
         /// pointer to the request as well
         request: Arc<Request>,
         start_time: DateTime<Utc>,
+        /// If the recipe is being polled, which attempt is this?
+        poll_attempt: Option<PollAttempt>,
+        /// If the request is throttled by a rate limit, when will it be sent?
+        queued_until: Option<DateTime<Utc>>,
+        /// Upload/download progress, updated by the HTTP engine
+        progress: Arc<RequestProgress>,
     },
 
     /// A resolved HTTP response, with all content loaded and ready to be
@@ -121,12 +132,78 @@ pub enum RequestState {
         /// For responses of a known content type, we'll pre-parsed it so we
         /// can do fancy things like prettification and filtering later
         parsed_body: Option<Box<dyn ResponseContent>>,
+        /// If the body couldn't be parsed but there's a formatter configured
+        /// for its content type, the formatter's output. This is populated by
+        /// a background task, so it's empty until the formatter finishes.
+        formatted_body: Option<Arc<OnceLock<anyhow::Result<String>>>>,
     },
 
     /// Error occurred sending the request or receiving the response.
     RequestError { error: RequestError },
 }
 
+/// Progress of a polled or streamed recipe
+#[derive(Copy, Clone, Debug)]
+pub struct PollAttempt {
+    /// 1-indexed
+    pub attempt: u32,
+    /// `None` if the recipe is being streamed, which has no limit
+    pub max_attempts: Option<u32>,
+}
+
+impl Display for PollAttempt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.max_attempts {
+            Some(max_attempts) => write!(f, "{}/{max_attempts}", self.attempt),
+            None => write!(f, "{}", self.attempt),
+        }
+    }
+}
+
+/// Responses received while streaming a recipe, i.e. re-sending it as soon as
+/// each response arrives. Shared between the request loop, which adds to it,
+/// and the modal that displays it.
+#[derive(Debug, Default)]
+pub struct StreamFeed {
+    entries: Mutex<Vec<String>>,
+}
+
+impl StreamFeed {
+    /// Add a received response to the feed
+    pub fn push_record(&self, record: &RequestRecord) {
+        let body = parse_body(record)
+            .map(|body| body.prettify())
+            .or_else(|| record.response.body.text().map(str::to_owned))
+            .unwrap_or_else(|| "<binary body>".into());
+        self.push(format!(
+            "[{}] {} ({}ms)\n{body}",
+            record.end_time.with_timezone(&Local).format("%H:%M:%S"),
+            record.response.status,
+            record.duration().num_milliseconds(),
+        ));
+    }
+
+    /// Add a message to the feed, e.g. why the stream stopped
+    pub fn push(&self, entry: String) {
+        self.lock().push(entry);
+    }
+
+    /// Number of entries in the feed
+    pub fn entry_count(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// The whole feed as text, newest entry first
+    pub fn text(&self) -> String {
+        self.lock().iter().rev().join("\n\n")
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<String>> {
+        // Nothing can panic while holding the lock, so it can't be poisoned
+        self.entries.lock().expect("Stream feed lock is poisoned")
+    }
+}
+
 #[derive(Debug)]
 pub struct RequestMetadata {
     /// When was the request launched?
@@ -154,6 +231,17 @@ impl RequestState {
         matches!(self, Self::Building { .. })
     }
 
+    /// Get the built request. Return `None` if the request hasn't been
+    /// successfully built (yet)
+    pub fn request(&self) -> Option<&Arc<Request>> {
+        match self {
+            Self::Building { .. } | Self::BuildError { .. } => None,
+            Self::Loading { request, .. } => Some(request),
+            Self::Response { record, .. } => Some(&record.request),
+            Self::RequestError { error } => Some(&error.request),
+        }
+    }
+
     /// Get metadata about a request. Return `None` if the request hasn't been
     /// successfully built (yet)
     pub fn metadata(&self) -> Option<RequestMetadata> {
@@ -183,10 +271,21 @@ impl RequestState {
     /// be slightly off from when the request was actually launched, but it
     /// shouldn't matter. See [crate::http::HttpEngine::send] for why it can't
     /// report a start time back to us.
-    pub fn loading(request: Arc<Request>) -> Self {
+    pub fn loading(
+        request: Arc<Request>,
+        poll_attempt: Option<PollAttempt>,
+        wait: Option<std::time::Duration>,
+        progress: Arc<RequestProgress>,
+    ) -> Self {
+        let start_time = Utc::now();
         Self::Loading {
             request,
-            start_time: Utc::now(),
+            start_time,
+            poll_attempt,
+            queued_until: wait.and_then(|wait| {
+                Some(start_time + Duration::from_std(wait).ok()?)
+            }),
+            progress,
         }
     }
 
@@ -195,14 +294,49 @@ impl RequestState {
     pub fn response(record: RequestRecord) -> Self {
         // Parsing might get slow on large responses, maybe we want to punt
         // this into a separate task?
-        let parsed_body = record.response.parse_body().ok();
+        let parsed_body = parse_body(&record);
+        let formatted_body =
+            if parsed_body.is_none() && !record.response.headers_only {
+                record
+                    .response
+                    .content_type()
+                    .and_then(|content_type| {
+                        TuiContext::get().config.formatter(content_type)
+                    })
+                    .map(|command| {
+                        // The formatter runs in the background, and writes its
+                        // output back here when done
+                        let lock = Arc::new(OnceLock::new());
+                        TuiContext::send_message(Message::FormatBody {
+                            command: command.to_owned(),
+                            body: record.response.body.clone(),
+                            destination: Arc::clone(&lock),
+                        });
+                        lock
+                    })
+            } else {
+                None
+            };
         Self::Response {
             record,
             parsed_body,
+            formatted_body,
         }
     }
 }
 
+/// Parse a response body for display. JSON numbers keep their exact text
+/// unless the `exact_json_numbers` config field is disabled, in which case the
+/// body is normalized through a parsed JSON value.
+fn parse_body(record: &RequestRecord) -> Option<Box<dyn ResponseContent>> {
+    let body = record.parse_body().ok()?;
+    if TuiContext::get().config.exact_json_numbers {
+        Some(body)
+    } else {
+        Some(body.content_type().parse_json(body.to_json()))
+    }
+}
+
 /// A notification is an ephemeral informational message generated by some async
 /// action. It doesn't grab focus, but will be useful to the user nonetheless.
 /// It should be shown for a short period of time, then disappear on its own.
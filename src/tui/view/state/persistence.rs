@@ -16,7 +16,8 @@ use std::fmt::Debug;
 
 /// A wrapper for any value that will automatically persist it to the state DB.
 /// The value will be loaded from the DB on creation, and saved to the DB on
-/// drop.
+/// drop. If persistence is disabled in the config, this is just a plain
+/// wrapper.
 #[derive(derive_more::Debug, Deref, DerefMut)]
 pub struct Persistent<T: PersistentContainer> {
     key: PersistentKey,
@@ -30,18 +31,25 @@ impl<T: PersistentContainer> Persistent<T> {
     /// of the container.
     pub fn new(key: PersistentKey, mut container: T) -> Self {
         // Load saved value from the database, and select it if available
-        if let Ok(Some(value)) =
-            TuiContext::get()
-                .database
-                .get_ui::<_, <T::Value as Persistable>::Persisted>(&key)
-        {
-            container.set(value);
+        if is_enabled() {
+            if let Ok(Some(value)) =
+                TuiContext::get()
+                    .database
+                    .get_ui::<_, <T::Value as Persistable>::Persisted>(&key)
+            {
+                container.set(value);
+            }
         }
 
         Self { key, container }
     }
 }
 
+/// Is UI state persistence enabled in the config?
+fn is_enabled() -> bool {
+    TuiContext::get().config.persist_ui_state
+}
+
 /// Forward events to the inner state cell
 impl<T> EventHandler for Persistent<T>
 where
@@ -58,6 +66,9 @@ where
 
 impl<T: PersistentContainer> Drop for Persistent<T> {
     fn drop(&mut self) {
+        if !is_enabled() {
+            return;
+        }
         let _ = TuiContext::get().database.set_ui(
             &self.key,
             self.container.get().map(Persistable::get_persistent),
@@ -77,6 +88,10 @@ pub enum PersistentKey {
     RecipeId,
     /// Set of folders that are collapsed in the recipe tree
     RecipeCollapsed,
+    /// List of pinned recipes, in the order they were pinned. Unlike other
+    /// keys, this is stored even if UI state persistence is disabled, because
+    /// it's user data rather than view state.
+    RecipePinned,
     /// Selected tab in the recipe pane
     RecipeTab,
     /// Selected query param, per recipe. Value is the query param name
@@ -114,7 +114,7 @@ impl<Item, State: SelectStateData> SelectState<Item, State> {
     }
 
     /// Select an item by index
-    fn select_index(&mut self, index: usize) {
+    pub fn select_index(&mut self, index: usize) {
         let state = self.state.get_mut();
         let current = state.selected();
         state.select(index);
@@ -1,4 +1,7 @@
+use crate::{collection::ProfileColor, config::LatencyThresholds};
+use chrono::Duration;
 use ratatui::style::{Color, Modifier, Style};
+use reqwest::StatusCode;
 
 /// Configurable visual settings for the UI. Styles are grouped into sub-structs
 /// generally by component.
@@ -6,6 +9,7 @@ use ratatui::style::{Color, Modifier, Style};
 pub struct Theme {
     pub pane: ThemePane,
     pub list: ThemeList,
+    pub response: ThemeResponse,
     pub tab: ThemeTab,
     pub table: ThemeTable,
     pub template_preview: ThemeTemplatePreview,
@@ -19,6 +23,29 @@ impl Theme {
     // two-stage themeing
     pub const PRIMARY_COLOR: Color = Color::LightGreen;
     pub const ERROR_COLOR: Color = Color::Red;
+
+    /// Terminal color for a profile's color. Profiles with a label but no
+    /// color get the primary color.
+    pub fn profile_color(color: Option<ProfileColor>) -> Color {
+        match color {
+            None => Self::PRIMARY_COLOR,
+            Some(ProfileColor::Red) => Color::Red,
+            Some(ProfileColor::Green) => Color::Green,
+            Some(ProfileColor::Yellow) => Color::Yellow,
+            Some(ProfileColor::Blue) => Color::Blue,
+            Some(ProfileColor::Magenta) => Color::Magenta,
+            Some(ProfileColor::Cyan) => Color::Cyan,
+            Some(ProfileColor::Gray) => Color::Gray,
+        }
+    }
+
+    /// Style for the badge of a flagged profile
+    pub fn profile_badge(color: Option<ProfileColor>) -> Style {
+        Style::default()
+            .bg(Self::profile_color(color))
+            .fg(Color::Black)
+            .add_modifier(Modifier::BOLD)
+    }
 }
 
 /// Styles for List component
@@ -26,6 +53,8 @@ impl Theme {
 pub struct ThemeList {
     /// Highlighted item in a list
     pub highlight: Style,
+    /// Item that's shown but inactive, e.g. a disabled recipe
+    pub disabled: Style,
 }
 
 /// Styles for Pane component
@@ -37,6 +66,23 @@ pub struct ThemePane {
     pub border_selected: Style,
 }
 
+/// Styles for response metadata
+#[derive(Debug)]
+pub struct ThemeResponse {
+    /// 2xx status
+    pub success: Style,
+    /// 3xx status
+    pub redirect: Style,
+    /// 4xx status
+    pub client_error: Style,
+    /// 5xx status
+    pub server_error: Style,
+    /// Duration above the `slow` latency threshold
+    pub slow: Style,
+    /// Duration above the `very_slow` latency threshold
+    pub very_slow: Style,
+}
+
 /// Styles for Tab component
 #[derive(Debug)]
 pub struct ThemeTab {
@@ -68,6 +114,8 @@ pub struct ThemeTemplatePreview {
 pub struct ThemeText {
     /// Text that needs some visual emphasis/separation
     pub highlight: Style,
+    /// Text that indicates something went wrong
+    pub error: Style,
 }
 
 /// Styles for TextBox component
@@ -84,6 +132,8 @@ pub struct ThemeTextBox {
 pub struct ThemeTextWindow {
     /// Line numbers on large text areas
     pub line_number: Style,
+    /// Line under the cursor, if enabled
+    pub cursor: Style,
 }
 
 impl Default for Theme {
@@ -100,6 +150,15 @@ impl Default for Theme {
                     .bg(Self::PRIMARY_COLOR)
                     .fg(Color::Black)
                     .add_modifier(Modifier::BOLD),
+                disabled: Style::default().add_modifier(Modifier::DIM),
+            },
+            response: ThemeResponse {
+                success: Style::default().fg(Color::Green),
+                redirect: Style::default().fg(Color::Cyan),
+                client_error: Style::default().fg(Color::Yellow),
+                server_error: Style::default().fg(Self::ERROR_COLOR),
+                slow: Style::default().fg(Color::Yellow),
+                very_slow: Style::default().fg(Self::ERROR_COLOR),
             },
             tab: ThemeTab {
                 highlight: Style::default()
@@ -129,6 +188,7 @@ impl Default for Theme {
                 highlight: Style::default()
                     .fg(Color::Black)
                     .bg(Self::PRIMARY_COLOR),
+                error: Style::default().fg(Self::ERROR_COLOR),
             },
             text_box: ThemeTextBox {
                 text: Style::default().bg(Color::DarkGray),
@@ -138,6 +198,7 @@ impl Default for Theme {
             },
             text_window: ThemeTextWindow {
                 line_number: Style::default().fg(Color::DarkGray),
+                cursor: Style::default().add_modifier(Modifier::REVERSED),
             },
         }
     }
@@ -152,3 +213,64 @@ impl ThemePane {
         }
     }
 }
+
+impl ThemeResponse {
+    /// Style for a status code, based on its class
+    pub fn status_style(&self, status: StatusCode) -> Style {
+        match status.as_u16() {
+            200..=299 => self.success,
+            300..=399 => self.redirect,
+            400..=499 => self.client_error,
+            500..=599 => self.server_error,
+            _ => Style::default(),
+        }
+    }
+
+    /// Style for a request duration, based on the configured thresholds
+    pub fn duration_style(
+        &self,
+        duration: Duration,
+        thresholds: &LatencyThresholds,
+    ) -> Style {
+        let ms = u64::try_from(duration.num_milliseconds()).unwrap_or(0);
+        if ms > thresholds.very_slow {
+            self.very_slow
+        } else if ms > thresholds.slow {
+            self.slow
+        } else {
+            Style::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::informational(100, Style::default())]
+    #[case::success(204, Style::default().fg(Color::Green))]
+    #[case::redirect(302, Style::default().fg(Color::Cyan))]
+    #[case::client_error(404, Style::default().fg(Color::Yellow))]
+    #[case::server_error(503, Style::default().fg(Theme::ERROR_COLOR))]
+    fn test_status_style(#[case] status: u16, #[case] expected: Style) {
+        let status = StatusCode::from_u16(status).unwrap();
+        assert_eq!(Theme::default().response.status_style(status), expected);
+    }
+
+    #[rstest]
+    #[case::fast(500, Style::default())]
+    #[case::at_threshold(1000, Style::default())]
+    #[case::slow(1001, Style::default().fg(Color::Yellow))]
+    #[case::very_slow(6000, Style::default().fg(Theme::ERROR_COLOR))]
+    fn test_duration_style(#[case] ms: i64, #[case] expected: Style) {
+        assert_eq!(
+            Theme::default().response.duration_style(
+                Duration::milliseconds(ms),
+                &LatencyThresholds::default()
+            ),
+            expected
+        );
+    }
+}
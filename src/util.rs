@@ -1,10 +1,18 @@
+pub mod csv;
 pub mod paths;
 
-use crate::{http::RequestError, template::ChainError};
+use crate::{
+    collection::CollectionLoadError, http::RequestError, template::ChainError,
+};
 use derive_more::{DerefMut, Display};
 use serde::de::DeserializeOwned;
-use std::{fmt, iter::FusedIterator, ops::Deref};
+use std::{
+    fmt,
+    iter::{self, FusedIterator},
+    ops::Deref,
+};
 use strum::{EnumCount, IntoEnumIterator};
+use thiserror::Error;
 use tracing::error;
 
 const WEBSITE: &str = "https://slumber.lucaspickering.me";
@@ -13,6 +21,7 @@ const WEBSITE: &str = "https://slumber.lucaspickering.me";
 /// as well as the suffix.
 ///
 /// ```
+/// # use slumber::util::doc_link;
 /// assert_eq!(
 ///     doc_link("api/chain"),
 ///     "https://slumber.lucaspickering.me/book/api/chain.html",
@@ -23,12 +32,85 @@ pub fn doc_link(path: &str) -> String {
 }
 
 /// Parse bytes (probably from a file) into YAML. This will merge any
-/// anchors/aliases.
-pub fn parse_yaml<T: DeserializeOwned>(bytes: &[u8]) -> serde_yaml::Result<T> {
+/// anchors/aliases. If parsing fails, the error includes a snippet of the
+/// source pointing at the problem, when possible.
+pub fn parse_yaml<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, YamlError> {
     // Two-step parsing is required for anchor/alias merging
-    let mut yaml_value = serde_yaml::from_slice::<serde_yaml::Value>(bytes)?;
-    yaml_value.apply_merge()?;
-    serde_yaml::from_value(yaml_value)
+    let result = serde_yaml::from_slice::<serde_yaml::Value>(bytes).and_then(
+        |mut yaml_value| {
+            yaml_value.apply_merge()?;
+            serde_yaml::from_value(yaml_value)
+        },
+    );
+    result.map_err(|error| {
+        // Errors from the second step have no location, because the value has
+        // been detached from the source. Deserializing straight from the
+        // source usually gives the same error (with a location and field
+        // path). Merges can change the error though, so only use it if it's
+        // the same error.
+        let error = match error.location() {
+            Some(_) => error,
+            None => match serde_yaml::from_slice::<T>(bytes) {
+                Err(located)
+                    if located.location().is_some()
+                        && located.to_string().contains(&error.to_string()) =>
+                {
+                    located
+                }
+                _ => error,
+            },
+        };
+        YamlError::new(error, bytes)
+    })
+}
+
+/// An error parsing YAML, with a snippet of the source. The snippet shows the
+/// offending line, with a caret pointing at the problem's column. If the
+/// error has no location, there's no snippet.
+#[derive(Debug, Error)]
+#[error("{error}{snippet}")]
+pub struct YamlError {
+    error: serde_yaml::Error,
+    snippet: String,
+}
+
+impl YamlError {
+    /// Lines of context to show before the offending line
+    const CONTEXT_LINES: usize = 2;
+
+    fn new(error: serde_yaml::Error, source: &[u8]) -> Self {
+        let snippet = error
+            .location()
+            .map(|location| {
+                Self::snippet(
+                    &String::from_utf8_lossy(source),
+                    location.line(),
+                    location.column(),
+                )
+            })
+            .unwrap_or_default();
+        Self { error, snippet }
+    }
+
+    /// Build a snippet for a 1-indexed line and column
+    fn snippet(source: &str, line: usize, column: usize) -> String {
+        let first = line.saturating_sub(Self::CONTEXT_LINES).max(1);
+        let width = line.to_string().len();
+        let mut snippet = String::new();
+        // The error can be on the line *after* the end of the source, e.g. an
+        // unclosed bracket, so pad with empty lines
+        let lines = source
+            .lines()
+            .chain(iter::repeat(""))
+            .skip(first - 1)
+            .zip(first..=line);
+        for (text, number) in lines {
+            snippet.push_str(&format!("\n{number:>width$} | {text}"));
+        }
+        let padding = " ".repeat(column.saturating_sub(1));
+        snippet.push_str(&format!("\n{:width$} | {padding}^", ""));
+        snippet
+    }
 }
 
 /// A value that can be replaced in-place. This is useful for two purposes:
@@ -97,6 +179,15 @@ impl<T> ResultExt<T, RequestError> for Result<T, RequestError> {
     }
 }
 
+impl<T> ResultExt<T, CollectionLoadError> for Result<T, CollectionLoadError> {
+    fn traced(self) -> Self {
+        if let Err(err) = &self {
+            error!(error = err as &dyn std::error::Error);
+        }
+        self
+    }
+}
+
 impl<T> ResultExt<T, ChainError> for Result<T, ChainError> {
     fn traced(self) -> Self {
         if let Err(err) = &self {
@@ -273,8 +364,41 @@ pub(crate) use assert_err;
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rstest::rstest;
+    use serde::Deserialize;
     use strum::EnumIter;
 
+    #[derive(Debug, Deserialize)]
+    #[serde(deny_unknown_fields)]
+    #[allow(dead_code)]
+    struct Data {
+        name: String,
+        count: u32,
+    }
+
+    /// Errors include a snippet pointing at the problem. This covers both
+    /// syntax errors and errors from deserializing the parsed value.
+    #[rstest]
+    #[case::syntax(
+        "name: a\ncount: [1\n",
+        "\n1 | name: a\n2 | count: [1\n3 | \n  | ^"
+    )]
+    #[case::invalid_type(
+        "# Data\nname: a\ncount: many\n",
+        "\n1 | # Data\n2 | name: a\n3 | count: many\n  |        ^"
+    )]
+    #[case::unknown_field(
+        "name: a\ncount: 1\nextra: 3\n",
+        "\n1 | name: a\n2 | count: 1\n3 | extra: 3\n  | ^"
+    )]
+    fn test_parse_yaml_snippet(
+        #[case] yaml: &str,
+        #[case] expected_snippet: &str,
+    ) {
+        let error = parse_yaml::<Data>(yaml.as_bytes()).unwrap_err();
+        assert_eq!(error.snippet, expected_snippet);
+    }
+
     #[derive(Clone, Debug, PartialEq, EnumIter)]
     enum A {
         One,
@@ -0,0 +1,167 @@
+//! A minimal CSV reader, for data files used to parameterize requests. This
+//! follows RFC 4180: fields are comma-separated, and quoted fields can contain
+//! commas, line breaks, and doubled quotes (`""`).
+
+use anyhow::{anyhow, bail};
+use indexmap::IndexMap;
+
+/// One row of a CSV file, mapped by column name
+pub type CsvRow = IndexMap<String, String>;
+
+/// Parse CSV text into rows. The first line is the header, which names the
+/// columns. Every other row must have the same number of fields as the
+/// header. Blank lines are skipped.
+pub fn parse_csv(text: &str) -> anyhow::Result<Vec<CsvRow>> {
+    let mut records = records(text)?.into_iter();
+    let (_, header) = records
+        .next()
+        .ok_or_else(|| anyhow!("CSV is empty; expected a header row"))?;
+    if let Some(duplicate) = header
+        .iter()
+        .enumerate()
+        .find(|(i, column)| header[..*i].contains(column))
+        .map(|(_, column)| column)
+    {
+        bail!("Duplicate column `{duplicate}` in CSV header");
+    }
+
+    records
+        .map(|(line, record)| {
+            if record.len() != header.len() {
+                bail!(
+                    "Line {line}: expected {} fields, found {}",
+                    header.len(),
+                    record.len()
+                );
+            }
+            Ok(header.iter().cloned().zip(record).collect())
+        })
+        .collect()
+}
+
+/// Split text into records, each tagged with the line it starts on
+fn records(text: &str) -> anyhow::Result<Vec<(usize, Vec<String>)>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    // Was the current field quoted? Quoted fields are kept even if empty
+    let mut quoted = false;
+    let mut line = 1;
+    let mut record_line = 1;
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if field.is_empty() && !quoted => {
+                quoted = true;
+                let start_line = line;
+                loop {
+                    match chars.next() {
+                        Some('"') if chars.peek() == Some(&'"') => {
+                            chars.next();
+                            field.push('"');
+                        }
+                        Some('"') => break,
+                        Some(c) => {
+                            if c == '\n' {
+                                line += 1;
+                            }
+                            field.push(c);
+                        }
+                        None => {
+                            bail!("Line {start_line}: unterminated quote")
+                        }
+                    }
+                }
+                // Only a delimiter can follow the closing quote
+                if !matches!(chars.peek(), None | Some(',' | '\r' | '\n')) {
+                    bail!("Line {line}: unexpected text after closing quote");
+                }
+            }
+            ',' => {
+                record.push(std::mem::take(&mut field));
+                quoted = false;
+            }
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                end_record(
+                    &mut records,
+                    &mut record,
+                    &mut field,
+                    quoted,
+                    record_line,
+                );
+                quoted = false;
+                line += 1;
+                record_line = line;
+            }
+            c => field.push(c),
+        }
+    }
+    end_record(&mut records, &mut record, &mut field, quoted, record_line);
+    Ok(records)
+}
+
+/// Finish the current record. A line with nothing on it is skipped
+fn end_record(
+    records: &mut Vec<(usize, Vec<String>)>,
+    record: &mut Vec<String>,
+    field: &mut String,
+    quoted: bool,
+    line: usize,
+) {
+    if record.is_empty() && field.is_empty() && !quoted {
+        return;
+    }
+    record.push(std::mem::take(field));
+    records.push((line, std::mem::take(record)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::assert_err;
+    use indexmap::indexmap;
+    use rstest::rstest;
+
+    #[test]
+    fn test_parse_csv() {
+        let text = "id,name,note\r\n\
+            1,Barry,plain\r\n\
+            \n\
+            2,\"Smith, Jo\",\"said \"\"hi\"\"\nthen left\"\n\
+            3,,\"\"";
+        let expected: Vec<CsvRow> = vec![
+            indexmap! {
+                "id".into() => "1".into(),
+                "name".into() => "Barry".into(),
+                "note".into() => "plain".into(),
+            },
+            indexmap! {
+                "id".into() => "2".into(),
+                "name".into() => "Smith, Jo".into(),
+                "note".into() => "said \"hi\"\nthen left".into(),
+            },
+            indexmap! {
+                "id".into() => "3".into(),
+                "name".into() => "".into(),
+                "note".into() => "".into(),
+            },
+        ];
+        assert_eq!(parse_csv(text).unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case::empty("", "CSV is empty")]
+    #[case::duplicate_column("id,id\n1,2", "Duplicate column `id`")]
+    #[case::wrong_length("a,b\n1,2\n3", "Line 3: expected 2 fields, found 1")]
+    #[case::line_in_quotes(
+        "a,b\n\"1\n2\",3\n4",
+        "Line 4: expected 2 fields, found 1"
+    )]
+    #[case::unterminated_quote("a\n\"oops", "Line 2: unterminated quote")]
+    #[case::text_after_quote("a\n\"1\"2", "unexpected text after closing")]
+    fn test_parse_csv_error(#[case] text: &str, #[case] expected_error: &str) {
+        assert_err!(parse_csv(text), expected_error);
+    }
+}
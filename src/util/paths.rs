@@ -1,10 +1,27 @@
 use anyhow::{anyhow, Context};
 use derive_more::Display;
 use std::{
-    fs,
+    env, fs,
     path::{Path, PathBuf},
+    sync::OnceLock,
 };
 
+/// Environment variable to override the data directory. This takes precedence
+/// over the `data_directory` config field.
+pub const DATA_DIRECTORY_ENV: &str = "SLUMBER_DATA_DIR";
+
+/// Data directory chosen at startup by [DataDirectory::init]
+static ROOT: OnceLock<PathBuf> = OnceLock::new();
+
+/// Replace a leading `~` in a path with the user's home directory. If the
+/// home directory can't be found, the path is returned unchanged.
+pub fn expand_home(path: PathBuf) -> PathBuf {
+    match (path.strip_prefix("~"), dirs::home_dir()) {
+        (Ok(rest), Some(home)) => home.join(rest),
+        _ => path,
+    }
+}
+
 /// The root data directory. All files that Slumber creates on the system should
 /// live here.
 ///
@@ -15,19 +32,40 @@ use std::{
 pub struct DataDirectory(PathBuf);
 
 impl DataDirectory {
-    /// Root directory for all generated files. The value is contextual:
-    /// - In development, use a directory in the current directory
-    /// - In release, use a platform-specific directory in the user's home
+    /// Root directory for all generated files. This is the directory chosen
+    /// by [Self::init], or [Self::base] if it hasn't been called.
     pub fn root() -> Self {
-        if cfg!(debug_assertions) {
-            Self("./data/".into())
-        } else {
-            // According to the docs, this dir will be present on all platforms
-            // https://docs.rs/dirs/latest/dirs/fn.data_dir.html
-            Self(dirs::data_dir().unwrap().join("slumber"))
+        match ROOT.get() {
+            Some(root) => Self(root.clone()),
+            None => Self::base(),
         }
     }
 
+    /// Directory that holds the config file. The config file can't be moved
+    /// by its own `data_directory` field, so this ignores it. The value is
+    /// contextual:
+    /// - If `$SLUMBER_DATA_DIR` is set, use that
+    /// - In development, use a directory in the current directory
+    /// - In release, use a platform-specific directory in the user's home
+    pub fn base() -> Self {
+        Self(base_path(env_override(), cfg!(debug_assertions)))
+    }
+
+    /// Pick the data directory for this process, and make sure it's usable.
+    /// `configured` is the `data_directory` config field, which is ignored if
+    /// `$SLUMBER_DATA_DIR` is set. Relative paths are relative to
+    /// [Self::base]. The directory is created if it doesn't exist, and this
+    /// fails if it isn't writable, so the problem is caught at startup rather
+    /// than whenever something is first written. Call this once, before
+    /// anything uses [Self::root].
+    pub fn init(configured: Option<PathBuf>) -> anyhow::Result<()> {
+        let root =
+            root_path(env_override(), configured, cfg!(debug_assertions));
+        create_root(&root)?;
+        ROOT.set(root)
+            .map_err(|_| anyhow!("Data directory is already initialized"))
+    }
+
     /// Path to the log file
     pub fn log() -> FileGuard {
         // Use a random new file for each session:
@@ -41,6 +79,52 @@ impl DataDirectory {
     }
 }
 
+/// Get the data directory from `$SLUMBER_DATA_DIR`, if set
+fn env_override() -> Option<PathBuf> {
+    env::var_os(DATA_DIRECTORY_ENV)
+        .filter(|path| !path.is_empty())
+        .map(|path| expand_home(path.into()))
+}
+
+/// See [DataDirectory::base]. `development` is whether this is a debug build
+fn base_path(env_override: Option<PathBuf>, development: bool) -> PathBuf {
+    if let Some(path) = env_override {
+        path
+    } else if development {
+        "./data/".into()
+    } else {
+        // According to the docs, this dir will be present on all platforms
+        // https://docs.rs/dirs/latest/dirs/fn.data_dir.html
+        dirs::data_dir().unwrap().join("slumber")
+    }
+}
+
+/// See [DataDirectory::init]. `development` is whether this is a debug build
+fn root_path(
+    env_override: Option<PathBuf>,
+    configured: Option<PathBuf>,
+    development: bool,
+) -> PathBuf {
+    let overridden = env_override.is_some();
+    let base = base_path(env_override, development);
+    match configured {
+        Some(path) if !overridden => base.join(expand_home(path)),
+        _ => base,
+    }
+}
+
+/// Create the data directory if needed, and make sure it's writable
+fn create_root(root: &Path) -> anyhow::Result<()> {
+    fs::create_dir_all(root)
+        .with_context(|| format!("Error creating data directory {root:?}"))?;
+    // Permissions don't tell the whole story (e.g. read-only mounts), so the
+    // only reliable check is to write something
+    let probe = root.join(".write_check");
+    fs::write(&probe, b"")
+        .and_then(|()| fs::remove_file(&probe))
+        .with_context(|| format!("Data directory {root:?} is not writable"))
+}
+
 /// A wrapper around a path to a specific file in the data directory. The
 /// purpose is to make it easy to print a path without any side effects, but
 /// enforce that the path's parent directory is created before actually using
@@ -68,3 +152,99 @@ impl FileGuard {
         Ok(self.0)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_util::TempDir, util::assert_err};
+    use rstest::rstest;
+
+    /// Test precedence of the different data directory sources:
+    /// `$SLUMBER_DATA_DIR`, then the config field (relative to the base), then
+    /// the development/platform default
+    #[rstest]
+    #[case::env_beats_config(Some("/env"), Some("/config"), true, "/env")]
+    #[case::env(Some("/env"), None, false, "/env")]
+    #[case::config_absolute(None, Some("/config"), true, "/config")]
+    #[case::config_relative(None, Some("config"), true, "./data/config")]
+    #[case::development(None, None, true, "./data/")]
+    #[case::platform(None, None, false, "{data}/slumber")]
+    fn test_root_path(
+        #[case] env_override: Option<&str>,
+        #[case] configured: Option<&str>,
+        #[case] development: bool,
+        #[case] expected: &str,
+    ) {
+        let data = dirs::data_dir().unwrap();
+        let expected = expected.replace("{data}", data.to_str().unwrap());
+        assert_eq!(
+            root_path(
+                env_override.map(PathBuf::from),
+                configured.map(PathBuf::from),
+                development
+            ),
+            PathBuf::from(expected)
+        );
+    }
+
+    /// The config field can't move the base directory, because that's where
+    /// the config file lives
+    #[rstest]
+    #[case::env(Some("/env"), true, "/env")]
+    #[case::development(None, true, "./data/")]
+    #[case::platform(None, false, "{data}/slumber")]
+    fn test_base_path(
+        #[case] env_override: Option<&str>,
+        #[case] development: bool,
+        #[case] expected: &str,
+    ) {
+        let data = dirs::data_dir().unwrap();
+        let expected = expected.replace("{data}", data.to_str().unwrap());
+        assert_eq!(
+            base_path(env_override.map(PathBuf::from), development),
+            PathBuf::from(expected)
+        );
+    }
+
+    /// Missing directories are created, and the write probe is cleaned up
+    #[test]
+    fn test_create_root() {
+        let directory = TempDir::new();
+        let root = directory.join("nested");
+        create_root(&root).unwrap();
+        assert!(root.is_dir());
+        assert_eq!(fs::read_dir(&root).unwrap().count(), 0);
+    }
+
+    /// A directory that can't be created is an error
+    #[test]
+    fn test_create_root_error() {
+        let directory = TempDir::new();
+        let file = directory.join("file");
+        fs::write(&file, b"").unwrap();
+        assert_err!(
+            create_root(&file.join("nested")),
+            "Error creating data directory"
+        );
+    }
+
+    /// A directory that exists but can't be written to is an error
+    #[cfg(unix)]
+    #[test]
+    fn test_create_root_read_only() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let directory = TempDir::new();
+        let root = directory.join("root");
+        fs::create_dir(&root).unwrap();
+        fs::set_permissions(&root, fs::Permissions::from_mode(0o555)).unwrap();
+        // Root ignores permissions, so in that case block the probe file
+        // instead. Writing to a directory fails for everyone
+        if fs::write(root.join("check"), b"").is_ok() {
+            fs::remove_file(root.join("check")).unwrap();
+            fs::create_dir(root.join(".write_check")).unwrap();
+        }
+        assert_err!(create_root(&root), "is not writable");
+        fs::set_permissions(&root, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+}